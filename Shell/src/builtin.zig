@@ -24,12 +24,14 @@ pub fn help() void {
     }
 }
 
-pub fn shutdown() noreturn {
-    libc.syscalls.shutdown();
+pub fn shutdown() u64 {
+    const err = libc.sys.utils.zpower(.Shutdown);
+    return @intFromError(err);
 }
 
-pub fn reboot() noreturn {
-    libc.syscalls.reboot();
+pub fn reboot() u64 {
+    const err = libc.sys.utils.zpower(.Reboot);
+    return @intFromError(err);
 }
 
 pub fn clear() void {
@@ -1,3 +1,5 @@
+const libc = @import("libc");
+const File = libc.stdio.FILE;
 const eql = @import("utils.zig").eql;
 const ArrayList = @import("utils.zig").ArrayList;
 
@@ -10,7 +12,28 @@ var environment: ArrayList(EnvironmentVariable) = undefined;
 
 pub fn init() !void {
     environment = try ArrayList(EnvironmentVariable).init();
-    try add_environment_variable("PATH", "sys:/bin");
+    load_default_environment() catch try add_environment_variable("PATH", "sys:/bin");
+}
+
+/// loads the kernel's default environment from proc:/environ, a `NAME=VALUE` line for each variable
+fn load_default_environment() !void {
+    const file = try File.open("proc:/environ", .{ .read = true });
+    defer file.close();
+
+    const environ = try file.reader().readUntilEOF();
+    var current_start: usize = 0;
+    for (environ, 0..) |char, i| {
+        if (char != '\n') continue;
+
+        const line = environ[current_start..i];
+        current_start = i + 1;
+        for (line, 0..) |line_char, j| {
+            if (line_char == '=') {
+                try add_environment_variable(line[0..j], line[j + 1 ..]);
+                break;
+            }
+        }
+    }
 }
 
 pub fn add_environment_variable(name: []const u8, value: []const u8) !void {
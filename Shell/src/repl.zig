@@ -43,7 +43,7 @@ fn spawn(name: []const u8, argv: []const Slice(u8)) Error!u64 {
 }
 
 fn wait(pid: u64) usize {
-    return libc.syscalls.wait(pid);
+    return libc.sys.utils.zwait(pid);
 }
 
 pub fn repl(tokens: []const Token) Error!usize {
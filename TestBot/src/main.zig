@@ -2,7 +2,8 @@ const libc = @import("libc");
 const File = libc.stdio.FILE;
 const Slice = libc.sys.raw.Slice;
 const spawn = libc.sys.utils.zpspwan;
-const wait = libc.syscalls.wait;
+const wait = libc.sys.utils.zwait;
+pub const panic = libc.panic;
 
 var serial: *File = undefined;
 
@@ -20,7 +21,7 @@ pub fn print(fmt: [:0]const u8, args: anytype) void {
 }
 
 const NativeError = libc.sys.errno.Error;
-const ExtraError = error{ UnexpectedError, UnexpectedStatus, UnexpectedStdout };
+const ExtraError = error{ UnexpectedError, UnexpectedStatus, UnexpectedStdout, TimedOut };
 
 const Error = NativeError || ExtraError;
 const Output = struct {
@@ -84,13 +85,47 @@ fn make_args(args: anytype) []const Slice(u8) {
     }
     return &args_array;
 }
+/// how many times TestBot yields waiting for a test binary to exit before the test times out
+const BINARY_TIMEOUT = 200_000;
+
+/// returns wether or not `pid` exited
+fn exited(pid: u64) !bool {
+    var processes: [128]libc.sys.raw.ProcessInfo = undefined;
+    _ = try libc.sys.utils.zpcollect(&processes);
+    for (processes) |process| {
+        if (process.pid == pid) return process.status == .Zombie;
+    }
+    return true;
+}
+
+/// waits for `pid` to exit and returns it's exit code, kills it and fails with `TimedOut` if it takes too long
+fn wait_timeout(pid: u64) !u64 {
+    var waited: usize = 0;
+    while (!try exited(pid)) : (waited += 1) {
+        if (waited == BINARY_TIMEOUT) {
+            libc.sys.utils.zpkill(pid) catch {};
+            _ = wait(pid);
+            extra_info = "the test binary didn't exit in time and was killed";
+            return error.TimedOut;
+        }
+        libc.sys.utils.zyield();
+    }
+
+    const status = try libc.sys.utils.zwait_status(pid);
+    if (status.kind == .Killed) {
+        extra_info = "the test binary was killed";
+        return error.UnexpectedStatus;
+    }
+    return status.value;
+}
+
 /// executes a binary with arguments and returns the output worte to fd 1
 fn test_binary(comptime path: []const u8, args: []const Slice(u8)) !Output {
     const test_log = try File.open("ram:/test.txt", .{ .write = true, .read = true });
     defer test_log.close();
 
     const pid = try spawn(path, args, "[TestCase]: " ++ path);
-    const status = wait(pid);
+    const status = try wait_timeout(pid);
 
     const buffer = try test_log.reader().readUntilEOF();
     return .{ .stdout = buffer, .status = status };
@@ -123,6 +158,12 @@ fn write(file: []const u8, data: []const u8) !void {
     output.uninit();
 }
 
+fn cp(src: []const u8, dest: []const u8) !void {
+    const output = try test_binary("sys:/bin/cp", make_args(.{ "cp", src, dest }));
+    try output.expect(null, 0);
+    output.uninit();
+}
+
 fn cat(file: []const u8) !Output {
     const output = try test_binary("sys:/bin/cat", make_args(.{ "cat", file }));
     return output;
@@ -192,6 +233,575 @@ pub fn ls_test() Error!void {
     output.uninit();
 }
 
+/// closed resources shouldn't be usable again even if their slot was reused
+pub fn dup_test() Error!void {
+    const fd = try libc.sys.io.zopen("ram:/test/test_file");
+    defer libc.sys.io.zclose(fd) catch {};
+
+    const copy = try libc.sys.io.zdup(fd);
+    defer libc.sys.io.zclose(copy) catch {};
+
+    const closed = try libc.sys.io.zdup(fd);
+    try libc.sys.io.zclose(closed);
+    if (libc.sys.io.zfstat(closed)) |_| {
+        extra_info = "fstat on a closed resource succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildResource) return err;
+
+    const reused = try libc.sys.io.zdup(fd);
+    defer libc.sys.io.zclose(reused) catch {};
+    if (reused == closed) {
+        extra_info = "a reused resource slot got the same ri";
+        return error.UnexpectedError;
+    }
+
+    // `copy` is one of the first 3 untagged slots so dup2 onto it keeps its ri
+    if (try libc.sys.io.zdup2(fd, copy) != copy) {
+        extra_info = "dup2 changed an untagged ri";
+        return error.UnexpectedError;
+    }
+
+    // dup2 mustn't grow the table without a bound
+    if (libc.sys.io.zdup2(fd, 1 << 20)) |_| {
+        extra_info = "dup2 onto a huge ri succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildResource) return err;
+}
+
+pub fn vectored_io_test() Error!void {
+    const IoVec = libc.sys.raw.IoVec;
+    libc.sys.io.zcreate("ram:/test/vectored") catch |err| if (err != error.AlreadyExists) return err;
+
+    const fd = try libc.sys.io.zopen("ram:/test/vectored");
+    defer libc.sys.io.zclose(fd) catch {};
+
+    var hello = "hello, ".*;
+    var world = "world".*;
+    const wrote = try libc.sys.io.zwritev(fd, &[_]IoVec{ IoVec.from(&hello), IoVec.from(&world) });
+    if (wrote != hello.len + world.len) {
+        extra_info = "writev didn't write all the buffers";
+        return error.UnexpectedError;
+    }
+
+    var first: [5]u8 = undefined;
+    var second: [16]u8 = undefined;
+    const read = try libc.sys.io.zreadv(fd, &[_]IoVec{ IoVec.from(&first), IoVec.from(&second) });
+    if (read != wrote or !libc.extra.eql(u8, &first, "hello") or !libc.extra.eql(u8, second[0 .. read - first.len], ", world")) {
+        extra_info = "readv didn't read back what writev wrote";
+        return error.UnexpectedError;
+    }
+}
+
+pub fn cp_test() Error!void {
+    try cp("test/test_file", "test/copied_file");
+    const copied = try cat("test/copied_file");
+    try copied.expect("test data\n", 0);
+    copied.uninit();
+}
+
+pub fn poll_test() Error!void {
+    const fd = try libc.sys.io.zopen_with("ram:/test/test_file", .{ .nonblocking = true });
+    defer libc.sys.io.zclose(fd) catch {};
+
+    var fds = [_]libc.sys.raw.PollFd{.{ .fd = @bitCast(fd), .events = .{ .readable = true } }};
+    const ready = try libc.sys.io.zpoll(&fds, 0);
+    if (ready != 1 or !fds[0].revents.readable or fds[0].revents.writable) {
+        extra_info = "a ramfs file wasn't reported as only readable";
+        return error.UnexpectedError;
+    }
+
+    // nothing was registered since it was opened
+    const events = try libc.sys.io.zopen_with("dev:/events", .{ .nonblocking = true });
+    defer libc.sys.io.zclose(events) catch {};
+
+    var buffer: [64]u8 = undefined;
+    if (libc.sys.io.zread(events, &buffer)) |_| {
+        extra_info = "a non-blocking read of dev:/events didn't fail";
+        return error.UnexpectedError;
+    } else |err| if (err != error.WouldBlock) return err;
+}
+
+pub fn event_queue_test() Error!void {
+    const io = libc.sys.io;
+    const evq = try io.EventQueue.init();
+    defer evq.deinit();
+
+    const level = try io.zopen("ram:/test/test_file");
+    defer io.zclose(level) catch {};
+    const edge = try io.zopen("ram:/test/test_file");
+    defer io.zclose(edge) catch {};
+
+    try evq.add(level, .{ .readable = true }, .{}, 1);
+    try evq.add(edge, .{ .readable = true }, .{ .edge_triggered = true }, 2);
+
+    var events: [2]libc.sys.raw.Event = undefined;
+    // both are ready the first time, after that only the level triggered one is reported
+    if ((try evq.wait(&events, 0)).len != 2) {
+        extra_info = "expected both resources to be reported on the first wait";
+        return error.UnexpectedError;
+    }
+
+    const ready = try evq.wait(&events, 0);
+    if (ready.len != 1 or ready[0].data != 1) {
+        extra_info = "expected only the level triggered resource to be reported again";
+        return error.UnexpectedError;
+    }
+}
+
+/// the display should report at least the mode it's currently in
+pub fn video_modes_test() Error!void {
+    const io = libc.sys.io;
+    const VideoMode = libc.sys.raw.VideoMode;
+
+    const fd = try io.zopen("dev:/fb");
+    defer io.zclose(fd) catch {};
+
+    var modes: [16]VideoMode = undefined;
+    const read = try io.zread(fd, @as([*]u8, @ptrCast(&modes))[0..@sizeOf(@TypeOf(modes))]);
+    if (read == 0 or read % @sizeOf(VideoMode) != 0) {
+        extra_info = "dev:/fb didn't return a list of whole modes";
+        return error.UnexpectedError;
+    }
+
+    // unknown commands shouldn't be accepted
+    if (io.zctl(fd, 0xFFFF, 0)) |_| {
+        extra_info = "an unknown dev:/fb ctl succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.OperationNotSupported) return err;
+
+    const mode = try io.zctl_get(VideoMode, fd, @intFromEnum(libc.sys.raw.FrameBufferCtl.GetMode));
+    if (mode.width == 0 or mode.height == 0 or mode.bpp == 0) {
+        extra_info = "dev:/fb returned an empty current mode";
+        return error.UnexpectedError;
+    }
+
+    // neither should commands of other devices
+    if (io.zctl_get(usize, fd, @intFromEnum(libc.sys.raw.TTYCtl.GetForeground))) |_| {
+        extra_info = "dev:/fb accepted a dev:/tty ctl";
+        return error.UnexpectedError;
+    } else |err| if (err != error.OperationNotSupported) return err;
+}
+
+/// a surface should be mapped once per process and only be readable after a present
+pub fn surface_test() Error!void {
+    const surface = libc.sys.surface;
+
+    const fd = try surface.zsurface_create(32, 16);
+    defer libc.sys.io.zclose(fd) catch {};
+
+    const info = try surface.zsurface_map(fd);
+    if (info.width != 32 or info.height != 16) {
+        extra_info = "the surface has the wrong size";
+        return error.UnexpectedError;
+    }
+
+    const pixels = info.pixels();
+    @memset(pixels, 0x00FF00FF);
+
+    const again = try surface.zsurface_map(fd);
+    if (again.addr != info.addr or again.id != info.id) {
+        extra_info = "mapping a surface twice gave a different mapping";
+        return error.UnexpectedError;
+    }
+
+    try surface.zsurface_flush(fd);
+    if (surface.zsurface_create(0, 16)) |_| {
+        extra_info = "created an empty surface";
+        return error.UnexpectedError;
+    } else |err| if (err != error.NotSupported) return err;
+}
+
+/// the surfaces of a process can't take more than 64 MiB at once, freeing a surface gives it's memory back
+pub fn surface_limit_test() Error!void {
+    const surface = libc.sys.surface;
+    const io = libc.sys.io;
+
+    if (surface.zsurface_create(8192, 8192)) |fd| {
+        io.zclose(fd) catch {};
+        extra_info = "created a surface larger than the per process limit";
+        return error.UnexpectedError;
+    } else |err| if (err != error.OutOfMemory) return err;
+
+    // 32 MiB each, together they take the whole limit
+    const first = try surface.zsurface_create(4096, 2048);
+    const second = try surface.zsurface_create(4096, 2048);
+    defer io.zclose(second) catch {};
+
+    {
+        errdefer io.zclose(first) catch {};
+        if (surface.zsurface_create(32, 16)) |fd| {
+            io.zclose(fd) catch {};
+            extra_info = "created a surface past the per process limit";
+            return error.UnexpectedError;
+        } else |err| if (err != error.OutOfMemory) return err;
+    }
+
+    try io.zclose(first);
+    const third = try surface.zsurface_create(32, 16);
+    try io.zclose(third);
+}
+
+/// plays a short beep if there is a sound device
+pub fn audio_test() Error!void {
+    const io = libc.sys.io;
+    const raw = libc.sys.raw;
+
+    const fd = io.zopen("dev:/audio") catch |err| switch (err) {
+        error.NoSuchAFileOrDirectory => return,
+        else => return err,
+    };
+    defer io.zclose(fd) catch {};
+
+    const rate = 48000;
+    try io.zctl(fd, @intFromEnum(raw.AudioCtl.SetFormat), raw.AudioFormat.S16.pack(rate, 1));
+
+    // 0.1s of a 440Hz square wave
+    var samples: [rate / 10]i16 = undefined;
+    for (&samples, 0..) |*sample, i| {
+        sample.* = if ((i * 440 * 2 / rate) % 2 == 0) 4000 else -4000;
+    }
+    _ = try io.zwrite(fd, @as([*]const u8, @ptrCast(&samples))[0..@sizeOf(@TypeOf(samples))]);
+
+    // unsupported formats shouldn't be accepted
+    if (io.zctl(fd, @intFromEnum(raw.AudioCtl.SetFormat), raw.AudioFormat.S16.pack(12345, 1))) |_| {
+        extra_info = "dev:/audio accepted an unsupported rate";
+        return error.UnexpectedError;
+    } else |err| if (err != error.OperationNotSupported) return err;
+}
+
+/// the spawn builder should pass the priority through and report missing binaries
+pub fn spawn_builder_test() Error!void {
+    const Spawn = libc.sys.utils.Spawn;
+
+    const test_log = try File.open("ram:/test.txt", .{ .write = true, .read = true });
+    defer test_log.close();
+
+    const pid = try Spawn.init("[TestCase]: spawn builder").args(make_args(.{ "echo", "low" })).with_priority(.Low).path("sys:/bin/echo");
+    const status = wait(pid);
+
+    const output: Output = .{ .stdout = try test_log.reader().readUntilEOF(), .status = status };
+    try output.expect("low\n", 0);
+    output.uninit();
+
+    if (Spawn.init("[TestCase]: missing").path("sys:/bin/missing")) |_| {
+        extra_info = "spawning a missing binary succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.NoSuchAFileOrDirectory) return err;
+}
+
+/// background jobs are collected with `zwait_any` in any order
+pub fn wait_any_test() Error!void {
+    const zwait_any = libc.sys.utils.zwait_any;
+
+    const test_log = try File.open("ram:/test.txt", .{ .write = true, .read = true });
+    defer test_log.close();
+
+    const pid = try libc.sys.utils.Spawn.init("[TestCase]: background job").args(make_args(.{ "echo", "job" })).with_flags(.{ .clone_resources = true, .background = true }).path("sys:/bin/echo");
+    const exited = (try zwait_any(false)) orelse return error.UnexpectedError;
+    if (exited.pid != pid or exited.status != 0) {
+        extra_info = "zwait_any returned the wrong child";
+        return error.UnexpectedStatus;
+    }
+
+    if (zwait_any(true)) |_| {
+        extra_info = "zwait_any succeeded without children";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildPid) return err;
+}
+
+fn bytes_eql(a: []const u8, b: []const u8) bool {
+    if (a.len != b.len) return false;
+    for (a, 0..) |byte, i| {
+        if (byte != b[i]) return false;
+    }
+    return true;
+}
+
+pub fn uname_test() Error!void {
+    const utils = libc.sys.utils;
+
+    const utsname = try utils.zuname();
+    if (!bytes_eql(utils.field(&utsname.sysname), "SafaOS") or !bytes_eql(utils.field(&utsname.machine), "x86_64")) {
+        extra_info = "unexpected sysname or machine";
+        return error.UnexpectedError;
+    }
+
+    const set = try test_binary("sys:/bin/hostname", make_args(.{ "hostname", "testbot" }));
+    try set.expect("", 0);
+    set.uninit();
+
+    const get = try test_binary("sys:/bin/hostname", make_args(.{"hostname"}));
+    try get.expect("testbot\n", 0);
+    get.uninit();
+
+    if (utils.zsethostname("has space")) |_| {
+        extra_info = "an invaild hostname was accepted";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildStr) return err;
+
+    const previous = utils.field(&utsname.nodename);
+    try utils.zsethostname(previous);
+}
+
+/// TestBot is spawned by Eve so it runs as root, dropping it's uid is tested by the kernel since it can't be undone
+pub fn uid_test() Error!void {
+    const utils = libc.sys.utils;
+
+    if (utils.getuid() != 0) {
+        extra_info = "TestBot isn't running as root";
+        return error.UnexpectedError;
+    }
+    try utils.zsetuid(0);
+}
+
+/// userspace processes don't inherit the power capability unless Eve grants it to them, so controlling
+/// the cpus fails instead of being refused by the cpu itself
+pub fn capabilities_test() Error!void {
+    if (libc.sys.utils.zcpuctl(0, false)) |_| {
+        extra_info = "offlining the bootstrap cpu succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.MissingPermissions) return err;
+}
+
+/// names longer than the kernel can store fail with `StrTooLong` instead of being truncated
+pub fn str_too_long_test() Error!void {
+    const utils = libc.sys.utils;
+    const io = libc.sys.io;
+
+    const long_name = "n" ** 65;
+    if (utils.Spawn.init(long_name).path("sys:/bin/true")) |pid| {
+        _ = wait(pid);
+        extra_info = "a process name longer than 64 bytes was accepted";
+        return error.UnexpectedError;
+    } else |err| if (err != error.StrTooLong) return err;
+
+    const pid = try utils.Spawn.init(long_name[0..64]).path("sys:/bin/true");
+    _ = try wait_timeout(pid);
+
+    const long_file = "ram:/" ++ "f" ** 129;
+    if (io.zcreate(long_file)) |_| {
+        extra_info = "a file name longer than 128 bytes was accepted";
+        return error.UnexpectedError;
+    } else |err| if (err != error.StrTooLong) return err;
+
+    const longest_file = long_file[0 .. long_file.len - 1];
+    try io.zcreate(longest_file);
+    try io.zremove(longest_file);
+
+    if (utils.zenv_set("V" ** 1025, "value")) |_| {
+        extra_info = "an environment variable name longer than 1024 bytes was accepted";
+        return error.UnexpectedError;
+    } else |err| if (err != error.StrTooLong) return err;
+}
+
+/// a bad pointer passed to a syscall should be reported instead of faulting the kernel
+pub fn bad_pointer_test() Error!void {
+    const unmapped: [*]const u8 = @ptrFromInt(0x1000);
+    if (libc.sys.io.zopen(unmapped[0..8])) |_| {
+        extra_info = "opening an unmapped path succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildPtr) return err;
+
+    // the ring0 stack is mapped in the lower half but only accessible from the kernel
+    const kernel_only: [*]const u8 = @ptrFromInt(0x7A00_0000_0000);
+    if (libc.sys.io.zopen(kernel_only[0..8])) |_| {
+        extra_info = "opening a kernel only path succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildPtr) return err;
+}
+
+/// `zwait_status` tells processes that exited apart from processes that were killed
+pub fn exit_status_test() Error!void {
+    const utils = libc.sys.utils;
+
+    const pid = try spawn("sys:/bin/true", make_args(.{"true"}), "[TestCase]: true");
+    const status = try utils.zwait_status(pid);
+    if (status.kind != .Exited or status.value != 0) {
+        extra_info = "true wasn't reported as exiting with 0";
+        return error.UnexpectedStatus;
+    }
+
+    // it was already waited on
+    if (utils.zwait_status(pid)) |_| {
+        extra_info = "waiting on a process twice succeeded";
+        return error.UnexpectedError;
+    } else |err| if (err != error.InvaildPid) return err;
+
+    const spinning = try spawn("sys:/bin/TestBot", make_args(.{ "TestBot", "spin" }), "[TestCase]: spin");
+    try utils.zpkill(spinning);
+    const killed = try utils.zwait_status(spinning);
+    if (killed.kind != .Killed or killed.value == 0) {
+        extra_info = "a killed process wasn't reported as killed by TestBot";
+        return error.UnexpectedStatus;
+    }
+}
+
+/// a panicking process reports it's panic message to the kernel, which keeps it in
+/// proc:/<pid>/abort until the process is waited on
+pub fn abort_report_test() Error!void {
+    const pid = try spawn("sys:/bin/TestBot", make_args(.{ "TestBot", "panic" }), "[TestCase]: panic");
+    var waited: usize = 0;
+    while (!try exited(pid)) : (waited += 1) {
+        if (waited == BINARY_TIMEOUT) {
+            libc.sys.utils.zpkill(pid) catch {};
+            _ = wait(pid);
+            extra_info = "the panicking process didn't exit in time and was killed";
+            return error.TimedOut;
+        }
+        libc.sys.utils.zyield();
+    }
+
+    var buffer: [64]u8 = undefined;
+    const message = read_abort(pid, &buffer);
+    if (wait(pid) != 1) {
+        extra_info = "a panicking process didn't exit with 1";
+        return error.UnexpectedStatus;
+    }
+
+    if (!libc.extra.eql(u8, try message, "TestBot abort\n")) {
+        extra_info = "proc:/<pid>/abort doesn't have the panic message";
+        return error.UnexpectedError;
+    }
+}
+
+/// reads proc:/<pid>/abort into `buffer`
+fn read_abort(pid: u64, buffer: []u8) ![]u8 {
+    var path_buffer: [32]u8 = undefined;
+    const prefix = "proc:/";
+    const suffix = "/abort";
+    @memcpy(path_buffer[0..prefix.len], prefix);
+    _ = libc.extra.itoa(pid, path_buffer[prefix.len..].ptr, 10);
+
+    var len = prefix.len;
+    while (path_buffer[len] != 0) len += 1;
+    @memcpy(path_buffer[len .. len + suffix.len], suffix);
+
+    const fd = try libc.sys.io.zopen(path_buffer[0 .. len + suffix.len]);
+    defer libc.sys.io.zclose(fd) catch {};
+    return buffer[0..try libc.sys.io.zread(fd, buffer)];
+}
+
+/// no kernel section should be both writable and executable
+pub fn wx_test() Error!void {
+    const output = try cat("proc:/memmap");
+    defer output.uninit();
+    try output.expect(null, 0);
+
+    var i: usize = 0;
+    while (i + 3 <= output.stdout.len) : (i += 1) {
+        if (libc.extra.eql(u8, output.stdout[i .. i + 3], "rwx")) {
+            extra_info = "a kernel section is mapped writable and executable";
+            return error.UnexpectedError;
+        }
+    }
+}
+
+/// makes a package out of a copy of echo, installs it and runs it from sys:/pkgs/bin
+pub fn pkg_test() Error!void {
+    try mkdir("ram:/pkgtest");
+    try mkdir("ram:/pkgtest/bin");
+    try touch("ram:/pkgtest/manifest");
+    try write("ram:/pkgtest/manifest", "name=pkgtest\nversion=1.0\nbinaries=pkgecho\n");
+    try cp("sys:/bin/echo", "ram:/pkgtest/bin/pkgecho");
+
+    const tar = try test_binary("sys:/bin/tar", make_args(.{ "tar", "-c", "ram:/pkgtest.pkg", "ram:/pkgtest" }));
+    try tar.expect(null, 0);
+    tar.uninit();
+
+    const install = try test_binary("sys:/bin/pkg", make_args(.{ "pkg", "install", "ram:/pkgtest.pkg" }));
+    try install.expect("installed pkgtest 1.0\n", 0);
+    install.uninit();
+
+    const list = try test_binary("sys:/bin/pkg", make_args(.{ "pkg", "list" }));
+    try list.expect("pkgtest 1.0\n", 0);
+    list.uninit();
+
+    const output = try test_binary("sys:/pkgs/bin/pkgecho", make_args(.{ "pkgecho", "from a package" }));
+    try output.expect("from a package\n", 0);
+    output.uninit();
+
+    // installed packages can't be replaced
+    const again = try test_binary("sys:/bin/pkg", make_args(.{ "pkg", "install", "ram:/pkgtest.pkg" }));
+    defer again.uninit();
+    if (again.status == 0) {
+        extra_info = "installing a package twice succeeded";
+        return error.UnexpectedStatus;
+    }
+}
+
+pub fn coreutils_test() Error!void {
+    try mkdir("ram:/coreutils");
+    try mkdir("ram:/coreutils/dir");
+    try touch("ram:/coreutils/lines");
+    try write("ram:/coreutils/lines", "one\ntwo\nthree\nfour\nfive\n");
+
+    const head = try test_binary("sys:/bin/head", make_args(.{ "head", "-n", "2", "ram:/coreutils/lines" }));
+    try head.expect("one\ntwo\n", 0);
+    head.uninit();
+
+    const tail = try test_binary("sys:/bin/tail", make_args(.{ "tail", "-n", "2", "ram:/coreutils/lines" }));
+    try tail.expect("four\nfive\n", 0);
+    tail.uninit();
+
+    const grep = try test_binary("sys:/bin/grep", make_args(.{ "grep", "o", "ram:/coreutils/lines" }));
+    try grep.expect("one\ntwo\nfour\n", 0);
+    grep.uninit();
+
+    const no_match = try test_binary("sys:/bin/grep", make_args(.{ "grep", "six", "ram:/coreutils/lines" }));
+    defer no_match.uninit();
+    if (no_match.status == 0) {
+        extra_info = "grep succeeded without a match";
+        return error.UnexpectedStatus;
+    }
+
+    const mv = try test_binary("sys:/bin/mv", make_args(.{ "mv", "ram:/coreutils/lines", "ram:/coreutils/dir/lines" }));
+    try mv.expect(null, 0);
+    mv.uninit();
+
+    var cwd_buffer: [1024]u8 = undefined;
+    const cwd = cwd_buffer[0..try libc.sys.io.zgetcwd(&cwd_buffer)];
+    try chdir("ram:/coreutils/dir");
+    const long = try test_binary("sys:/bin/ls", make_args(.{ "-l", "--raw" }));
+    try chdir(cwd);
+    try long.expect(
+        \dir  0 ..
+        \file 24 lines
+        \
+    , 0);
+    long.uninit();
+
+    // files are copied then removed across drives
+    const across = try test_binary("sys:/bin/mv", make_args(.{ "mv", "ram:/coreutils/dir/lines", "sys:/coreutils_lines" }));
+    try across.expect(null, 0);
+    across.uninit();
+
+    const moved = try test_binary("sys:/bin/head", make_args(.{ "head", "-n", "1", "sys:/coreutils_lines" }));
+    try moved.expect("one\n", 0);
+    moved.uninit();
+
+    const rm = try test_binary("sys:/bin/rm", make_args(.{ "rm", "sys:/coreutils_lines" }));
+    try rm.expect(null, 0);
+    rm.uninit();
+
+    try touch("ram:/coreutils/dir/file");
+    const not_empty = try test_binary("sys:/bin/rm", make_args(.{ "rm", "ram:/coreutils" }));
+    defer not_empty.uninit();
+    if (not_empty.status == 0) {
+        extra_info = "rm removed a directory that isn't empty";
+        return error.UnexpectedStatus;
+    }
+
+    const recursive = try test_binary("sys:/bin/rm", make_args(.{ "rm", "-r", "ram:/coreutils" }));
+    try recursive.expect(null, 0);
+    recursive.uninit();
+
+    if (libc.sys.io.zopen("ram:/coreutils")) |fd| {
+        libc.sys.io.zclose(fd) catch {};
+        extra_info = "rm -r didn't remove the directory";
+        return error.UnexpectedError;
+    } else |err| if (err != error.NoSuchAFileOrDirectory) return err;
+}
+
 pub fn memory_info_test() Error!void {
     const output = try meminfo();
     if (!meminfo_output.eql(&output)) {
@@ -209,8 +819,14 @@ pub fn memory_info_test() Error!void {
     output.uninit();
 }
 
-fn run_test(comptime name: []const u8, func: fn () Error!void) Error!void {
+/// besides the human readable output every test prints a
+/// `[TEST RESULT] module=<TestBot|TestBotStress> name=<name> status=<ok|failed|skipped> ...` line and each
+/// module a `[TEST SUMMARY] module=<module> passed=<n> failed=<n> skipped=<n>` line once it is done, like the kernel tests
+fn run_test(comptime module: []const u8, comptime name: []const u8, func: fn () Error!void) Error!void {
     print("\x1b[36m[TEST]\x1b[0m running: " ++ name ++ "\n", .{});
+    last_output = null;
+    expected_output = null;
+    extra_info = null;
 
     func() catch |err| {
         const err_name = @errorName(err);
@@ -228,19 +844,22 @@ fn run_test(comptime name: []const u8, func: fn () Error!void) Error!void {
         }
 
         print("\x1b[0m", .{});
+        print("[TEST RESULT] module=" ++ module ++ " name=" ++ name ++ " status=failed reason=%.*s\n", .{ err_name.len, err_name.ptr });
         return err;
     };
 
     print("\x1b[32m[OK]\x1b[0m\n", .{});
+    print("[TEST RESULT] module=" ++ module ++ " name=" ++ name ++ " status=ok\n", .{});
 }
 
 const TestCase = struct { name: []const u8, func: fn () Error!void };
 
-fn get_tests() []const TestCase {
-    const info = @typeInfo(@This());
+/// the tests are the `pub fn xxx() Error!void` in `namespace` in the order they are declared
+fn get_tests(comptime namespace: type) []const TestCase {
+    const info = @typeInfo(namespace);
     comptime var tests: []const TestCase = &[_]TestCase{};
     inline for (info.Struct.decls) |decl| {
-        const func = @field(@This(), decl.name);
+        const func = @field(namespace, decl.name);
         if (@TypeOf(func) == fn () Error!void)
             tests = tests ++ &[_]TestCase{.{ .name = decl.name, .func = func }};
     }
@@ -248,21 +867,158 @@ fn get_tests() []const TestCase {
     return tests;
 }
 
-fn run_tests(comptime tests: []const TestCase) Error!void {
+/// the tests depend on the files the tests before them made so the tests after a failed one are skipped
+fn run_tests(comptime module: []const u8, comptime tests: []const TestCase) Error!void {
+    var passed: usize = 0;
+    var skipped: usize = 0;
+    var failure: ?Error = null;
+
     inline for (tests) |test_case| {
-        try run_test(test_case.name, test_case.func);
+        if (failure != null) {
+            skipped += 1;
+            print("[TEST RESULT] module=" ++ module ++ " name=" ++ test_case.name ++ " status=skipped reason=previous_failure\n", .{});
+        } else if (run_test(module, test_case.name, test_case.func)) {
+            passed += 1;
+        } else |err| failure = err;
+    }
+
+    const failed: usize = if (failure != null) 1 else 0;
+    print("[TEST SUMMARY] module=" ++ module ++ " passed=%l failed=%l skipped=%l\n", .{ passed, failed, skipped });
+    if (failure) |err| return err;
+
+    print("\x1b[36m[" ++ module ++ "]\x1b[0m: \x1b[32m[PASSED]\x1b[0m\n", .{});
+}
+
+/// the stress tests, they run after the other tests if TestBot is started with `stress` (the `stress=1`
+/// kernel command line option), they check that the kernel returns errors instead of panicking and that
+/// the memory goes back to what it was before them
+const stress = struct {
+    const SPAWN_ROUNDS = 200;
+    const RAMFS_FILES = 2000;
+    /// ramfs never reuses inode ids so it's id table grows with every file made
+    const ALLOWED_GROWTH = 64 * 1024;
+
+    var baseline: usize = 0;
+
+    fn used_memory() !usize {
+        return (try libc.sys.utils.zsysinfo()).used_mem;
+    }
+
+    pub fn memory_baseline_capture() Error!void {
+        baseline = try used_memory();
+    }
+
+    /// spawns processes that exit on their own and processes that have to be killed
+    pub fn spawn_kill_stress() Error!void {
+        for (0..SPAWN_ROUNDS) |_| {
+            const pid = try spawn("sys:/bin/true", make_args(.{"true"}), "[StressTest]: true");
+            _ = try wait_timeout(pid);
+
+            const spinning = try spawn("sys:/bin/TestBot", make_args(.{ "TestBot", "spin" }), "[StressTest]: spin");
+            try libc.sys.utils.zpkill(spinning);
+            if ((try libc.sys.utils.zwait_status(spinning)).kind != .Killed) {
+                extra_info = "a killed process wasn't reported as killed";
+                return error.UnexpectedStatus;
+            }
+        }
+
+        // only descendants can be killed
+        if (libc.sys.utils.zpkill(0)) |_| {
+            extra_info = "killing a process that isn't a child succeeded";
+            return error.UnexpectedError;
+        } else |err| if (err != error.MissingPermissions) return err;
+    }
+
+    /// makes and removes a lot of ramfs files
+    pub fn ramfs_stress() Error!void {
+        const io = libc.sys.io;
+        try io.zcreatedir("ram:/stress");
+
+        var data: [512]u8 = undefined;
+        @memset(&data, 'S');
+        for (0..RAMFS_FILES) |i| {
+            var buffer: [32]u8 = undefined;
+            const path = file_path(&buffer, i);
+            try io.zcreate(path);
+
+            const fd = try io.zopen(path);
+            defer io.zclose(fd) catch {};
+            _ = try io.zwrite(fd, &data);
+        }
+
+        for (0..RAMFS_FILES) |i| {
+            var buffer: [32]u8 = undefined;
+            try io.zremove(file_path(&buffer, i));
+        }
+        try io.zremove("ram:/stress");
     }
 
-    print("\x1b[36m[TestBot]\x1b[0m: \x1b[32m[PASSED]\x1b[0m\n", .{});
+    fn file_path(buffer: *[32]u8, i: usize) []const u8 {
+        const prefix = "ram:/stress/";
+        @memcpy(buffer[0..prefix.len], prefix);
+        _ = libc.extra.itoa(i, buffer[prefix.len..].ptr, 10);
+
+        var len = prefix.len;
+        while (buffer[len] != 0) len += 1;
+        return buffer[0..len];
+    }
+
+    /// a child allocates until the kernel refuses to give it more memory
+    pub fn oom_stress() Error!void {
+        const pid = try spawn("sys:/bin/TestBot", make_args(.{ "TestBot", "oom" }), "[StressTest]: oom");
+        if (try wait_timeout(pid) != 0) {
+            extra_info = "the process didn't get an error when it ran out of memory";
+            return error.UnexpectedStatus;
+        }
+    }
+
+    pub fn memory_baseline_stress() Error!void {
+        const used = try used_memory();
+        print("\x1b[36m[TestBot]\x1b[0m: memory used before the stress tests %lu bytes, after %lu bytes\n", .{ baseline, used });
+        if (used > baseline + ALLOWED_GROWTH) {
+            extra_info = "the memory didn't go back to what it was before the stress tests";
+            return error.UnexpectedError;
+        }
+    }
+};
+
+/// `TestBot spin`, never exits so that it has to be killed
+fn spin() noreturn {
+    while (true) libc.sys.utils.zyield();
 }
+
+/// `TestBot oom`, allocates until it gets an error, exits with an error if it couldn't allocate at all
+fn oom() !void {
+    var chunks: usize = 0;
+    while (true) : (chunks += 1) {
+        const chunk = libc.stdlib.zalloc(u8, 1024 * 1024) catch break;
+        @memset(chunk, 0xAA);
+    }
+
+    if (chunks == 0) return error.OutOfMemory;
+}
+
 pub fn main() !void {
+    const args = libc.sys.args();
+    const mode = args.nth(1) orelse "";
+    // TestBot runs itself for the stress tests
+    if (libc.extra.eql(u8, mode, "spin")) spin();
+    if (libc.extra.eql(u8, mode, "oom")) return oom();
+    if (libc.extra.eql(u8, mode, "panic")) @panic("TestBot abort");
+
     // fd 0
     serial = try File.open("dev:/ss", .{ .write = true, .read = true });
     defer serial.close();
 
-    const tests = get_tests();
+    const tests = get_tests(@This());
     print("\x1b[36m[TEST]\x1b[0m: TestBot running %l tests ...\n", .{tests.len});
-    try run_tests(tests);
+    try run_tests("TestBot", tests);
+
+    if (libc.extra.eql(u8, mode, "stress")) {
+        const stress_tests = get_tests(stress);
+        print("\x1b[36m[TEST]\x1b[0m: TestBot running %l stress tests ...\n", .{stress_tests.len});
+        try run_tests("TestBotStress", stress_tests);
+    }
 }
 
 comptime {
@@ -0,0 +1,28 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+pub const panic = libc.panic;
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 3) {
+        try printf("expected a file to copy from, and a file to copy to\n", .{});
+        return error.NotEnoughArguments;
+    }
+    const src = args.nth(1).?;
+    const dest = args.nth(2).?;
+
+    const src_fd = try io.zopen(src);
+    defer io.zclose(src_fd) catch {};
+
+    io.zcreate(dest) catch |err| if (err != error.AlreadyExists) return err;
+    const dest_fd = try io.zopen(dest);
+    defer io.zclose(dest_fd) catch {};
+
+    // the data is copied inside the kernel without going through userspace
+    _ = try io.zcopy_file_range(src_fd, dest_fd, ~@as(usize, 0));
+}
+
+comptime {
+    _ = libc;
+}
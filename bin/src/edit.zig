@@ -0,0 +1,310 @@
+// a small nano style text editor, `edit <file>` opens the file (or a new one) with the TTY in raw mode
+// the arrows, home and end move the cursor, ctrl+s saves and ctrl+q quits
+const libc = @import("libc");
+const io = libc.sys.io;
+const raw = libc.sys.raw;
+const printf = libc.stdio.zprintf;
+pub const panic = libc.panic;
+
+const MAX_SIZE = 64 * 1024;
+// the size of the terminal can't be asked for so the editor assumes one
+const ROWS = 24;
+const COLS = 80;
+/// every row and the status line, plus the escape sequences
+const FRAME_SIZE = (COLS + 1) * (ROWS + 1) + 128;
+
+const CTRL_Q = 0x11;
+const CTRL_S = 0x13;
+const BACKSPACE = 0x7F;
+const ESCAPE = 0x1B;
+
+const Key = union(enum) {
+    char: u8,
+    up,
+    down,
+    left,
+    right,
+    home,
+    end,
+    delete,
+    backspace,
+    save,
+    quit,
+};
+
+/// reads keys from the TTY
+const Input = struct {
+    fd: isize,
+    buffer: [32]u8 = undefined,
+    at: usize = 0,
+    len: usize = 0,
+
+    fn next_byte(self: *Input) !u8 {
+        // the TTY waits until a key is pressed
+        while (self.at == self.len) {
+            self.len = try io.zread(self.fd, &self.buffer);
+            self.at = 0;
+        }
+
+        defer self.at += 1;
+        return self.buffer[self.at];
+    }
+
+    fn next(self: *Input) !Key {
+        while (true) {
+            const byte = try self.next_byte();
+            switch (byte) {
+                CTRL_Q => return .quit,
+                CTRL_S => return .save,
+                BACKSPACE => return .backspace,
+                ESCAPE => {
+                    if (try self.next_byte() != '[') continue;
+                    switch (try self.next_byte()) {
+                        'A' => return .up,
+                        'B' => return .down,
+                        'C' => return .right,
+                        'D' => return .left,
+                        'H' => return .home,
+                        'F' => return .end,
+                        '3' => if (try self.next_byte() == '~') return .delete,
+                        else => {},
+                    }
+                },
+                '\n', '\t', ' '...'~' => return .{ .char = byte },
+                // the rest of the control characters
+                else => {},
+            }
+        }
+    }
+};
+
+/// the file's text, `len` bytes of it are used
+var text: [MAX_SIZE]u8 = undefined;
+var len: usize = 0;
+/// the index of the byte in `text` the cursor is on
+var cursor: usize = 0;
+/// the first line and column on the screen
+var top: usize = 0;
+var left: usize = 0;
+var modified = false;
+var status: []const u8 = "";
+
+var frame: [FRAME_SIZE]u8 = undefined;
+var frame_len: usize = 0;
+
+fn line_start(at: usize) usize {
+    var start = at;
+    while (start > 0 and text[start - 1] != '\n') start -= 1;
+    return start;
+}
+
+fn line_end(at: usize) usize {
+    var end = at;
+    while (end < len and text[end] != '\n') end += 1;
+    return end;
+}
+
+fn cursor_line() usize {
+    var line: usize = 0;
+    for (text[0..cursor]) |c| {
+        if (c == '\n') line += 1;
+    }
+    return line;
+}
+
+fn move_up() void {
+    const start = line_start(cursor);
+    if (start == 0) return;
+
+    const column = cursor - start;
+    const prev_start = line_start(start - 1);
+    cursor = @min(prev_start + column, start - 1);
+}
+
+fn move_down() void {
+    const end = line_end(cursor);
+    if (end == len) return;
+
+    const column = cursor - line_start(cursor);
+    const next_start = end + 1;
+    cursor = @min(next_start + column, line_end(next_start));
+}
+
+fn insert(c: u8) void {
+    if (len == text.len) {
+        status = "the file is too big";
+        return;
+    }
+
+    var i = len;
+    while (i > cursor) : (i -= 1) text[i] = text[i - 1];
+    text[cursor] = c;
+    len += 1;
+    cursor += 1;
+    modified = true;
+}
+
+fn remove_at(at: usize) void {
+    for (at..len - 1) |i| text[i] = text[i + 1];
+    len -= 1;
+    modified = true;
+}
+
+/// scrolls the screen until the cursor is on it
+fn scroll() void {
+    const line = cursor_line();
+    if (line < top) top = line;
+    if (line >= top + ROWS) top = line - ROWS + 1;
+
+    const column = cursor - line_start(cursor);
+    if (column < left) left = column;
+    if (column >= left + COLS) left = column - COLS + 1;
+}
+
+fn push(data: []const u8) void {
+    const amount = @min(data.len, frame.len - frame_len);
+    @memcpy(frame[frame_len .. frame_len + amount], data[0..amount]);
+    frame_len += amount;
+}
+
+/// the character under the cursor is drawn black on white
+fn push_cursor(c: u8) void {
+    push("\x1B[47m\x1B[30m");
+    push(&[_]u8{c});
+    push("\x1B[0m");
+}
+
+fn render(tty: isize, filename: []const u8) !void {
+    scroll();
+    frame_len = 0;
+    // the screen is cleared and drawn again from the top left on every key
+    push("\x1B[2J\x1B[H");
+
+    var start: usize = 0;
+    var line: usize = 0;
+    while (line < top) : (line += 1) start = line_end(start) + 1;
+
+    for (0..ROWS) |_| {
+        if (start > len) {
+            push("~\n");
+            continue;
+        }
+
+        const end = line_end(start);
+        var column = left;
+        while (column < left + COLS) : (column += 1) {
+            const at = start + column;
+            if (at > end) break;
+
+            // the end of the line is drawn as a space when the cursor is on it
+            const c = if (at == end or text[at] == '\t') ' ' else text[at];
+            if (at == cursor) push_cursor(c) else if (at != end) push(&[_]u8{c});
+        }
+
+        push("\n");
+        start = end + 1;
+    }
+
+    push("\x1B[47m\x1B[30m ");
+    push(filename);
+    if (modified) push(" [modified]");
+    push(" | ^S save ^Q quit ");
+    push(status);
+    push("\x1B[0m");
+
+    _ = try io.zwrite(tty, frame[0..frame_len]);
+}
+
+fn load(path: []const u8) !void {
+    const fd = io.zopen(path) catch |err| {
+        if (err != error.NoSuchAFileOrDirectory) return err;
+        status = "new file";
+        return;
+    };
+    defer io.zclose(fd) catch {};
+
+    while (len < text.len) {
+        const count = try io.zread(fd, text[len..]);
+        if (count == 0) return;
+        len += count;
+    }
+
+    var extra: [1]u8 = undefined;
+    if (try io.zread(fd, &extra) != 0) {
+        try printf("edit: the file is bigger than %lu bytes\n", .{@as(usize, MAX_SIZE)});
+        return error.OutOfMemory;
+    }
+}
+
+/// files can't be truncated so the file is removed and created again
+fn save(path: []const u8) !void {
+    io.zremove(path) catch |err| if (err != error.NoSuchAFileOrDirectory) return err;
+    try io.zcreate(path);
+
+    const fd = try io.zopen(path);
+    defer io.zclose(fd) catch {};
+
+    var wrote: usize = 0;
+    while (wrote < len) wrote += try io.zwrite(fd, text[wrote..len]);
+    modified = false;
+}
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 2) {
+        try printf("usage: edit <file>\n", .{});
+        return error.NotEnoughArguments;
+    }
+    const path = args.nth(1).?;
+    try load(path);
+
+    const tty = try io.zopen("dev:/tty");
+    defer io.zclose(tty) catch {};
+
+    try io.zctl(tty, @intFromEnum(raw.TTYCtl.SetRaw), 1);
+    defer {
+        io.zctl(tty, @intFromEnum(raw.TTYCtl.SetRaw), 0) catch {};
+        _ = io.zwrite(tty, "\x1B[2J\x1B[H") catch {};
+    }
+
+    var input = Input{ .fd = tty };
+    var quit_armed = false;
+    while (true) {
+        try render(tty, path);
+        const key = try input.next();
+        status = "";
+
+        switch (key) {
+            .char => |c| insert(c),
+            .up => move_up(),
+            .down => move_down(),
+            .left => cursor -|= 1,
+            .right => cursor = @min(cursor + 1, len),
+            .home => cursor = line_start(cursor),
+            .end => cursor = line_end(cursor),
+            .delete => if (cursor < len) remove_at(cursor),
+            .backspace => if (cursor > 0) {
+                cursor -= 1;
+                remove_at(cursor);
+            },
+            .save => {
+                save(path) catch |err| {
+                    status = @errorName(err);
+                    continue;
+                };
+                status = "saved";
+            },
+            .quit => {
+                if (!modified or quit_armed) return;
+                status = "unsaved changes, press ^Q again to quit";
+                quit_armed = true;
+                continue;
+            },
+        }
+        quit_armed = false;
+    }
+}
+
+comptime {
+    _ = libc;
+}
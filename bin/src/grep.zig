@@ -0,0 +1,60 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const File = libc.stdio.File;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+fn contains(haystack: []const u8, needle: []const u8) bool {
+    if (needle.len > haystack.len) return false;
+    for (0..haystack.len - needle.len + 1) |i| {
+        if (eql(u8, haystack[i .. i + needle.len], needle)) return true;
+    }
+    return false;
+}
+
+/// prints every line of `filename` that contains `pattern`, prefixed with the filename if `prefix`
+/// returns the amount of matching lines
+fn grep(pattern: []const u8, filename: []const u8, prefix: bool) !usize {
+    const file = try File.open(filename, .{ .read = true });
+    defer file.close();
+
+    const data = try file.reader().readUntilEOF();
+    defer libc.stdlib.zfree(u8, data);
+
+    var matches: usize = 0;
+    var start: usize = 0;
+    while (start < data.len) {
+        var end = start;
+        while (end < data.len and data[end] != '\n') end += 1;
+
+        const line = data[start..end];
+        if (contains(line, pattern)) {
+            matches += 1;
+            if (prefix) try printf("%.*s:", .{ filename.len, filename.ptr });
+            try printf("%.*s\n", .{ line.len, line.ptr });
+        }
+        start = end + 1;
+    }
+    return matches;
+}
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 3) {
+        try printf("usage: grep <pattern> <file>...\n", .{});
+        return error.NotEnoughArguments;
+    }
+
+    const pattern = args.nth(1).?;
+    const prefix = args.count() > 3;
+
+    var matches: usize = 0;
+    for (2..args.count()) |i| matches += try grep(pattern, args.nth(i).?, prefix);
+
+    // like other greps nothing matching is a failure
+    if (matches == 0) return error.NoMatches;
+}
+
+comptime {
+    _ = libc;
+}
@@ -0,0 +1,51 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const File = libc.stdio.File;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+fn parse_count(arg: []const u8) !usize {
+    if (arg.len == 0) return error.InvaildStr;
+    var count: usize = 0;
+    for (arg) |c| {
+        if (c < '0' or c > '9') return error.InvaildStr;
+        count = count * 10 + (c - '0');
+    }
+    return count;
+}
+
+pub fn main() !void {
+    var args = libc.sys.args();
+    _ = args.next();
+
+    var lines: usize = 10;
+    var filename: ?[]const u8 = null;
+    while (args.next()) |arg| {
+        if (eql(u8, arg, "-n")) {
+            lines = try parse_count(args.next() orelse "");
+        } else filename = arg;
+    }
+
+    if (filename == null) {
+        try printf("usage: head [-n lines] <file>\n", .{});
+        return error.NotEnoughArguments;
+    }
+
+    const file = try File.open(filename.?, .{ .read = true });
+    defer file.close();
+
+    const data = try file.reader().readUntilEOF();
+    defer libc.stdlib.zfree(u8, data);
+
+    // prints everything up to and including the `lines`th newline
+    var end: usize = 0;
+    var printed: usize = 0;
+    while (end < data.len and printed < lines) : (end += 1) {
+        if (data[end] == '\n') printed += 1;
+    }
+    try printf("%.*s", .{ end, data.ptr });
+}
+
+comptime {
+    _ = libc;
+}
@@ -0,0 +1,21 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const utils = libc.sys.utils;
+const Error = libc.sys.errno.Error;
+
+/// prints the hostname, or sets it to the first argument which requires the hostname capability
+pub fn main() Error!void {
+    var args = libc.sys.args();
+
+    if (args.count() >= 2) {
+        return utils.zsethostname(args.nth(1).?);
+    }
+
+    const utsname = try utils.zuname();
+    const hostname = utils.field(&utsname.nodename);
+    try printf("%.*s\n", .{ hostname.len, hostname.ptr });
+}
+
+comptime {
+    _ = libc;
+}
@@ -0,0 +1,60 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+pub const panic = libc.panic;
+
+const PATH = "ram:/iobench.tmp";
+const TOTAL = 1024 * 1024;
+const CHUNK_SIZES = [_]usize{ 512, 4096, 64 * 1024 };
+
+/// the buffer the chunks are written from and read into, reads and writes use the user buffer directly
+/// and only check that it is mapped, so this measures the filesystem and syscall overhead per chunk size
+var buffer: [64 * 1024]u8 align(4096) = undefined;
+
+fn rdtsc() u64 {
+    var low: u32 = undefined;
+    var high: u32 = undefined;
+    asm volatile ("rdtsc"
+        : [low] "={eax}" (low),
+          [high] "={edx}" (high),
+    );
+    return (@as(u64, high) << 32) | low;
+}
+
+/// writes then reads `TOTAL` bytes to `PATH` in `chunk` sized chunks
+/// returns the cycles taken to write and to read
+fn bench(chunk: usize) !struct { u64, u64 } {
+    const data = buffer[0..chunk];
+    @memset(data, 0xAA);
+
+    const fd = try io.zopen(PATH);
+    var start = rdtsc();
+    var i: usize = 0;
+    while (i < TOTAL) : (i += chunk) _ = try io.zwrite(fd, data);
+    const write_cycles = rdtsc() - start;
+    try io.zclose(fd);
+
+    const read_fd = try io.zopen(PATH);
+    defer io.zclose(read_fd) catch {};
+
+    start = rdtsc();
+    i = 0;
+    while (i < TOTAL) : (i += chunk) _ = try io.zread(read_fd, data);
+    const read_cycles = rdtsc() - start;
+
+    return .{ write_cycles, read_cycles };
+}
+
+pub fn main() !void {
+    io.zcreate(PATH) catch |err| if (err != error.AlreadyExists) return err;
+
+    try printf("%luKiB on ramfs, cycles per KiB:\n", .{TOTAL / 1024});
+    for (CHUNK_SIZES) |chunk| {
+        const write_cycles, const read_cycles = try bench(chunk);
+        try printf("chunk %luB: write %lu, read %lu\n", .{ chunk, write_cycles / (TOTAL / 1024), read_cycles / (TOTAL / 1024) });
+    }
+}
+
+comptime {
+    _ = libc;
+}
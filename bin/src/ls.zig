@@ -9,13 +9,26 @@ pub fn main() !void {
     defer cwd.close();
 
     var raw_output = false;
+    var long_output = false;
     while (args.next()) |arg| {
         if (eql(u8, arg, "--raw")) {
             raw_output = true;
+        } else if (eql(u8, arg, "-l")) {
+            long_output = true;
         }
     }
 
     while (cwd.next()) |ent| {
+        if (long_output) {
+            // the kind and the size are all the attributes a DirEntry has
+            const kind: []const u8 = switch (ent.kind) {
+                1 => "dir ",
+                2 => "dev ",
+                else => "file",
+            };
+            try printf("%.*s %lu ", .{ kind.len, kind.ptr, ent.size });
+        }
+
         if (!raw_output) {
             if (ent.kind == 1)
                 try printf("\x1B[38;2;0;100;255m%.*s\n\x1B[0m", .{ ent.name_length, &ent.name })
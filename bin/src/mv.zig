@@ -0,0 +1,42 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+pub const panic = libc.panic;
+
+/// files can't be renamed across drives, they are copied then removed instead
+fn copy_then_remove(src: []const u8, dest: []const u8) !void {
+    const src_fd = try io.zopen(src);
+    defer io.zclose(src_fd) catch {};
+
+    const kind = (try io.zfstat(src_fd)).kind;
+    if (kind != 0) {
+        try printf("mv: only files can be moved to another drive\n", .{});
+        return error.OperationNotSupported;
+    }
+
+    try io.zcreate(dest);
+    const dest_fd = try io.zopen(dest);
+    defer io.zclose(dest_fd) catch {};
+
+    _ = try io.zcopy_file_range(src_fd, dest_fd, ~@as(usize, 0));
+    try io.zremove(src);
+}
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 3) {
+        try printf("expected a file to move, and where to move it to\n", .{});
+        return error.NotEnoughArguments;
+    }
+    const src = args.nth(1).?;
+    const dest = args.nth(2).?;
+
+    io.zrename(src, dest) catch |err| {
+        if (err != error.OperationNotSupported) return err;
+        try copy_then_remove(src, dest);
+    };
+}
+
+comptime {
+    _ = libc;
+}
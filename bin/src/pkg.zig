@@ -0,0 +1,283 @@
+// installs packages into sys:/pkgs without rebuilding the iso
+// a package is a ustar archive (made with `tar -c`) with a `manifest` at it's root, the manifest is a
+// `key=value` line for each of `name`, `version` and `binaries` (`;` separated)
+// the package is extracted into `sys:/pkgs/<name>` and every binary is copied from `bin/<binary>` to
+// `sys:/pkgs/bin` which is added to the default PATH
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+const utils = libc.sys.utils;
+const Slice = libc.sys.raw.Slice;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+const BLOCK_SIZE = 512;
+const PATH_MAX = 256;
+const MANIFEST_MAX = 4096;
+
+const PKGS_DIR = "sys:/pkgs";
+const PKGS_BIN = "sys:/pkgs/bin";
+
+fn usage() !void {
+    try printf("usage: pkg install <package>\n       pkg list\n", .{});
+    return error.NotEnoughArguments;
+}
+
+/// iterates over the non empty parts of `data` separated by `sep`
+const Split = struct {
+    data: []const u8,
+    sep: u8,
+
+    fn next(self: *Split) ?[]const u8 {
+        while (self.data.len > 0) {
+            var end: usize = 0;
+            while (end < self.data.len and self.data[end] != self.sep) end += 1;
+
+            const part = self.data[0..end];
+            self.data = if (end < self.data.len) self.data[end + 1 ..] else self.data[end..];
+            if (part.len > 0) return part;
+        }
+        return null;
+    }
+};
+
+fn split(data: []const u8, sep: u8) Split {
+    return .{ .data = data, .sep = sep };
+}
+
+const Manifest = struct {
+    name: []const u8 = "",
+    version: []const u8 = "",
+    /// `;` separated
+    binaries: []const u8 = "",
+
+    fn parse(data: []const u8) !Manifest {
+        var manifest = Manifest{};
+        var lines = split(data, '\n');
+        while (lines.next()) |line| {
+            var sep: usize = 0;
+            while (sep < line.len and line[sep] != '=') sep += 1;
+            if (sep == line.len) return error.Corrupted;
+
+            const key = line[0..sep];
+            const value = line[sep + 1 ..];
+
+            if (eql(u8, key, "name")) manifest.name = value else if (eql(u8, key, "version")) manifest.version = value else if (eql(u8, key, "binaries")) manifest.binaries = value;
+        }
+
+        // the name is a directory in sys:/pkgs next to `bin`
+        if (manifest.name.len == 0 or manifest.version.len == 0 or eql(u8, manifest.name, "bin")) return error.Corrupted;
+        for (manifest.name) |c| if (c == '/' or c == ':') return error.Corrupted;
+        var binaries = split(manifest.binaries, ';');
+        while (binaries.next()) |binary| for (binary) |c| if (c == '/' or c == ':') return error.Corrupted;
+        return manifest;
+    }
+};
+
+/// reads until `buffer` is full, returns false if the end of the file is reached first
+fn read_exact(fd: isize, buffer: []u8) !bool {
+    var amount: usize = 0;
+    while (amount < buffer.len) {
+        const count = try io.zread(fd, buffer[amount..]);
+        if (count == 0) return false;
+        amount += count;
+    }
+    return true;
+}
+
+/// reads the whole file at `path` into `buffer`
+fn read_file(path: []const u8, buffer: []u8) ![]u8 {
+    const fd = try io.zopen(path);
+    defer io.zclose(fd) catch {};
+
+    var amount: usize = 0;
+    while (amount < buffer.len) {
+        const count = try io.zread(fd, buffer[amount..]);
+        if (count == 0) break;
+        amount += count;
+    }
+    return buffer[0..amount];
+}
+
+fn exists(path: []const u8) bool {
+    const fd = io.zopen(path) catch return false;
+    io.zclose(fd) catch {};
+    return true;
+}
+
+/// joins `a` and `b` with a `/` into `buffer`
+fn join(buffer: []u8, a: []const u8, b: []const u8) ![]const u8 {
+    const len = a.len + 1 + b.len;
+    if (len > buffer.len) return error.InvaildPath;
+
+    @memcpy(buffer[0..a.len], a);
+    buffer[a.len] = '/';
+    @memcpy(buffer[a.len + 1 .. len], b);
+    return buffer[0..len];
+}
+
+/// the part of `field` before the first NUL
+fn cstr(field: []const u8) []const u8 {
+    for (field, 0..) |c, i| if (c == 0) return field[0..i];
+    return field;
+}
+
+fn parse_octal(field: []const u8) usize {
+    var value: usize = 0;
+    var i: usize = 0;
+    while (i < field.len and field[i] == ' ') i += 1;
+    while (i < field.len and field[i] >= '0' and field[i] <= '7') : (i += 1) value = value * 8 + (field[i] - '0');
+    return value;
+}
+
+/// finds the manifest in the archive at `path` and reads it into `buffer`
+fn read_manifest(path: []const u8, buffer: []u8) ![]u8 {
+    const archive = try io.zopen(path);
+    defer io.zclose(archive) catch {};
+
+    var header: [BLOCK_SIZE]u8 = undefined;
+    while (try read_exact(archive, &header)) {
+        if (!eql(u8, header[257..262], "ustar")) break;
+
+        const name = cstr(header[0..100]);
+        const size = parse_octal(header[124..136]);
+        const blocks = (size + BLOCK_SIZE - 1) / BLOCK_SIZE;
+
+        if (eql(u8, name, "manifest") or eql(u8, name, "./manifest")) {
+            if (size > buffer.len) return error.Corrupted;
+            if (!try read_exact(archive, buffer[0..size])) return error.Corrupted;
+            return buffer[0..size];
+        }
+
+        var block: [BLOCK_SIZE]u8 = undefined;
+        for (0..blocks) |_| if (!try read_exact(archive, &block)) return error.Corrupted;
+    }
+
+    try printf("pkg: %.*s has no manifest\n", .{ path.len, path.ptr });
+    return error.Corrupted;
+}
+
+fn create_dir(path: []const u8) !void {
+    io.zcreatedir(path) catch |err| if (err != error.AlreadyExists) return err;
+}
+
+/// adds PKGS_BIN to the default PATH if it isn't already there, new shells pick it up
+fn add_to_path() !void {
+    var buffer: [1024]u8 = undefined;
+    const path = utils.zenv_get("PATH", &buffer) catch "";
+
+    var parts = split(path, ';');
+    while (parts.next()) |part| if (eql(u8, part, PKGS_BIN)) return;
+
+    var new_buffer: [1024]u8 = undefined;
+    const sep: []const u8 = if (path.len == 0) "" else ";";
+    const len = path.len + sep.len + PKGS_BIN.len;
+    if (len > new_buffer.len) return error.InvaildStr;
+
+    @memcpy(new_buffer[0..path.len], path);
+    @memcpy(new_buffer[path.len .. path.len + sep.len], sep);
+    @memcpy(new_buffer[path.len + sep.len .. len], PKGS_BIN);
+
+    utils.zenv_set("PATH", new_buffer[0..len]) catch |err| {
+        if (err != error.MissingPermissions) return err;
+        try printf("pkg: couldn't add " ++ PKGS_BIN ++ " to PATH, add it yourself\n", .{});
+    };
+}
+
+fn install(archive_path: []const u8) !void {
+    var manifest_buffer: [MANIFEST_MAX]u8 = undefined;
+    const manifest = try Manifest.parse(try read_manifest(archive_path, &manifest_buffer));
+
+    var dir_buffer: [PATH_MAX]u8 = undefined;
+    const dir = try join(&dir_buffer, PKGS_DIR, manifest.name);
+    // files can't be removed yet so a package can't be replaced
+    if (exists(dir)) {
+        try printf("pkg: %.*s is already installed\n", .{ manifest.name.len, manifest.name.ptr });
+        return error.AlreadyExists;
+    }
+
+    var binaries = split(manifest.binaries, ';');
+    while (binaries.next()) |binary| {
+        var path_buffer: [PATH_MAX]u8 = undefined;
+        if (exists(try join(&path_buffer, PKGS_BIN, binary))) {
+            try printf("pkg: %.*s is already provided by another package\n", .{ binary.len, binary.ptr });
+            return error.AlreadyExists;
+        }
+    }
+
+    try create_dir(PKGS_DIR);
+    try create_dir(PKGS_BIN);
+    try create_dir(dir);
+
+    const argv = [_]Slice(u8){ Slice(u8).from("tar"), Slice(u8).from("-x"), Slice(u8).from(archive_path), Slice(u8).from(dir) };
+    const pid = try utils.zpspwan("sys:/bin/tar", &argv, "tar");
+    if (utils.zwait(pid) != 0) return error.Corrupted;
+
+    binaries = split(manifest.binaries, ';');
+    while (binaries.next()) |binary| {
+        var bin_buffer: [PATH_MAX]u8 = undefined;
+        var src_buffer: [PATH_MAX]u8 = undefined;
+        var dest_buffer: [PATH_MAX]u8 = undefined;
+        const src = try join(&src_buffer, try join(&bin_buffer, dir, "bin"), binary);
+        const dest = try join(&dest_buffer, PKGS_BIN, binary);
+
+        const src_fd = io.zopen(src) catch |err| {
+            try printf("pkg: the package has no bin/%.*s\n", .{ binary.len, binary.ptr });
+            return err;
+        };
+        defer io.zclose(src_fd) catch {};
+
+        try io.zcreate(dest);
+        const dest_fd = try io.zopen(dest);
+        defer io.zclose(dest_fd) catch {};
+        _ = try io.zcopy_file_range(src_fd, dest_fd, ~@as(usize, 0));
+    }
+
+    try add_to_path();
+    try printf("installed %.*s %.*s\n", .{ manifest.name.len, manifest.name.ptr, manifest.version.len, manifest.version.ptr });
+}
+
+/// prints the name and version of every installed package
+fn list() !void {
+    const dir = io.zopen(PKGS_DIR) catch |err| {
+        if (err == error.NoSuchAFileOrDirectory) return;
+        return err;
+    };
+    defer io.zclose(dir) catch {};
+
+    var entries: [16]io.raw.DirEntry = undefined;
+    while (true) {
+        const read = try io.zreaddir(dir, &entries);
+        if (read.len == 0) break;
+
+        for (read) |*entry| {
+            const name = entry.name[0..entry.name_length];
+            if (entry.kind != 1 or eql(u8, name, "..") or eql(u8, name, ".") or eql(u8, name, "bin")) continue;
+
+            var dir_buffer: [PATH_MAX]u8 = undefined;
+            var path_buffer: [PATH_MAX]u8 = undefined;
+            var manifest_buffer: [MANIFEST_MAX]u8 = undefined;
+            const path = try join(&path_buffer, try join(&dir_buffer, PKGS_DIR, name), "manifest");
+
+            const manifest = Manifest.parse(try read_file(path, &manifest_buffer)) catch continue;
+            try printf("%.*s %.*s\n", .{ manifest.name.len, manifest.name.ptr, manifest.version.len, manifest.version.ptr });
+        }
+    }
+}
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 2) return usage();
+
+    const command = args.nth(1).?;
+    if (eql(u8, command, "install")) {
+        if (args.count() < 3) return usage();
+        try install(args.nth(2).?);
+    } else if (eql(u8, command, "list")) {
+        try list();
+    } else return usage();
+}
+
+comptime {
+    _ = libc;
+}
@@ -0,0 +1,78 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+const PATH_MAX = 256;
+
+/// removes everything inside of the directory at `path` then the directory itself
+fn remove_all(path: []const u8) !void {
+    const dir = try io.zopen(path);
+    const kind = (try io.zfstat(dir)).kind;
+    if (kind != 1) {
+        try io.zclose(dir);
+        return io.zremove(path);
+    }
+
+    // removing entries while reading the directory would skip some of them so the directory is
+    // reread from the start after every removal
+    while (true) {
+        const diriter = try io.zdiriter_open(dir);
+        var child: ?io.raw.DirEntry = null;
+        while (io.zdiriter_next(diriter)) |entry| {
+            const name = entry.name[0..entry.name_length];
+            if (eql(u8, name, "..") or eql(u8, name, ".")) continue;
+            child = entry;
+            break;
+        }
+        try io.zdiriter_close(diriter);
+
+        const entry = child orelse break;
+        const name = entry.name[0..entry.name_length];
+        var buffer: [PATH_MAX]u8 = undefined;
+        const len = path.len + 1 + name.len;
+        if (len > buffer.len) return error.InvaildPath;
+
+        @memcpy(buffer[0..path.len], path);
+        buffer[path.len] = '/';
+        @memcpy(buffer[path.len + 1 .. len], name);
+        try remove_all(buffer[0..len]);
+    }
+
+    try io.zclose(dir);
+    try io.zremove(path);
+}
+
+pub fn main() !void {
+    var args = libc.sys.args();
+    _ = args.next();
+
+    var recursive = false;
+    var removed: usize = 0;
+    while (args.next()) |arg| {
+        if (eql(u8, arg, "-r")) {
+            recursive = true;
+            continue;
+        }
+
+        if (recursive) {
+            try remove_all(arg);
+        } else {
+            io.zremove(arg) catch |err| {
+                if (err == error.DirectoryNotEmpty) try printf("rm: %.*s is not empty, use -r to remove it\n", .{ arg.len, arg.ptr });
+                return err;
+            };
+        }
+        removed += 1;
+    }
+
+    if (removed == 0) {
+        try printf("expected a file to remove\n", .{});
+        return error.NotEnoughArguments;
+    }
+}
+
+comptime {
+    _ = libc;
+}
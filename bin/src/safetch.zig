@@ -6,6 +6,10 @@ pub fn main() !void {
     const sysinfo = libc.sys.utils.sysinfo().?;
     const total_memory = sysinfo.total_mem / 1024 / 1024;
     const used_memory = sysinfo.used_mem / 1024 / 1024;
+    const utsname = try libc.sys.utils.zuname();
+    const hostname = libc.sys.utils.field(&utsname.nodename);
+    const release = libc.sys.utils.field(&utsname.release);
+    const commit = libc.sys.utils.field(&utsname.version);
 
     // draw the logo
     const logo_file = try libc.stdio.FILE.open("sys:/logo.txt", .{ .read = true });
@@ -18,9 +22,9 @@ pub fn main() !void {
     // start drawing from the end of the start of the logo
     try print("\x1b[11A", .{});
 
-    try print("\x1b[31C\x1b[31mroot\x1b[0m@\x1b[31mlocalhost\x1b[0m\n\n", .{});
-    try print("\x1b[31C\x1b[31mOS:\x1b[0m SafaOS (UNKNOWN)\n", .{});
-    try print("\x1b[31C\x1b[31mKernel:\x1b[0m Snowball (UNKNOWN)\n", .{});
+    try print("\x1b[31C\x1b[31mroot\x1b[0m@\x1b[31m%.*s\x1b[0m\n\n", .{ hostname.len, hostname.ptr });
+    try print("\x1b[31C\x1b[31mOS:\x1b[0m SafaOS (%.*s)\n", .{ release.len, release.ptr });
+    try print("\x1b[31C\x1b[31mKernel:\x1b[0m Snowball (%.*s)\n", .{ commit.len, commit.ptr });
     try print("\x1b[31C\x1b[31mTerminal:\x1b[0m dev:/tty\n", .{});
     try print("\x1b[31C\x1b[31mMemory:\x1b[0m %luMiB / %luMiB\n\n", .{ used_memory, total_memory });
 
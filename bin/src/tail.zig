@@ -0,0 +1,59 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const File = libc.stdio.File;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+fn parse_count(arg: []const u8) !usize {
+    if (arg.len == 0) return error.InvaildStr;
+    var count: usize = 0;
+    for (arg) |c| {
+        if (c < '0' or c > '9') return error.InvaildStr;
+        count = count * 10 + (c - '0');
+    }
+    return count;
+}
+
+pub fn main() !void {
+    var args = libc.sys.args();
+    _ = args.next();
+
+    var lines: usize = 10;
+    var filename: ?[]const u8 = null;
+    while (args.next()) |arg| {
+        if (eql(u8, arg, "-n")) {
+            lines = try parse_count(args.next() orelse "");
+        } else filename = arg;
+    }
+
+    if (filename == null) {
+        try printf("usage: tail [-n lines] <file>\n", .{});
+        return error.NotEnoughArguments;
+    }
+
+    const file = try File.open(filename.?, .{ .read = true });
+    defer file.close();
+
+    const data = try file.reader().readUntilEOF();
+    defer libc.stdlib.zfree(u8, data);
+
+    // walks back from the end counting newlines, a trailing newline doesn't start a line
+    var start: usize = data.len;
+    if (start > 0 and data[start - 1] == '\n') start -= 1;
+
+    var found: usize = 0;
+    while (start > 0) : (start -= 1) {
+        if (data[start - 1] == '\n') {
+            found += 1;
+            if (found == lines) break;
+        }
+    }
+
+    if (lines == 0) start = data.len;
+    const rest = data[start..];
+    try printf("%.*s", .{ rest.len, rest.ptr });
+}
+
+comptime {
+    _ = libc;
+}
@@ -0,0 +1,223 @@
+const libc = @import("libc");
+const printf = libc.stdio.zprintf;
+const io = libc.sys.io;
+const eql = libc.extra.eql;
+pub const panic = libc.panic;
+
+const BLOCK_SIZE = 512;
+const PATH_MAX = 256;
+
+const TYPE_NORMAL = '0';
+const TYPE_DIR = '5';
+
+fn usage() !void {
+    try printf("usage: tar -c <archive> <dir>\n       tar -x <archive> [dir]\n       tar -t <archive>\n", .{});
+    return error.NotEnoughArguments;
+}
+
+fn write_all(fd: isize, buffer: []const u8) !void {
+    var written: usize = 0;
+    while (written < buffer.len) written += try io.zwrite(fd, buffer[written..]);
+}
+
+/// reads until `buffer` is full, returns false if the end of the file is reached first
+fn read_exact(fd: isize, buffer: []u8) !bool {
+    var amount: usize = 0;
+    while (amount < buffer.len) {
+        const count = try io.zread(fd, buffer[amount..]);
+        if (count == 0) return false;
+        amount += count;
+    }
+    return true;
+}
+
+/// joins `a` and `b` with a `/` into `buffer`, `a` can be empty
+fn join(buffer: []u8, a: []const u8, b: []const u8) ![]const u8 {
+    const sep: usize = if (a.len == 0 or a[a.len - 1] == '/') 0 else 1;
+    const len = a.len + sep + b.len;
+    if (len > buffer.len) return error.InvaildPath;
+
+    @memcpy(buffer[0..a.len], a);
+    if (sep == 1) buffer[a.len] = '/';
+    @memcpy(buffer[a.len + sep .. len], b);
+    return buffer[0..len];
+}
+
+/// the part of `field` before the first NUL
+fn cstr(field: []const u8) []const u8 {
+    for (field, 0..) |c, i| if (c == 0) return field[0..i];
+    return field;
+}
+
+fn trim_slashes(path: []const u8) []const u8 {
+    var len = path.len;
+    while (len > 1 and path[len - 1] == '/') len -= 1;
+    return path[0..len];
+}
+
+/// writes `value` as zero padded octal digits followed by a NUL filling `field`
+fn write_octal(field: []u8, value: usize) void {
+    var v = value;
+    var i = field.len - 1;
+    field[i] = 0;
+    while (i > 0) {
+        i -= 1;
+        field[i] = '0' + @as(u8, @intCast(v & 7));
+        v >>= 3;
+    }
+}
+
+fn parse_octal(field: []const u8) usize {
+    var value: usize = 0;
+    var i: usize = 0;
+    while (i < field.len and field[i] == ' ') i += 1;
+    while (i < field.len and field[i] >= '0' and field[i] <= '7') : (i += 1) value = value * 8 + (field[i] - '0');
+    return value;
+}
+
+fn write_header(archive: isize, name: []const u8, kind: u8, mode: usize, size: usize) !void {
+    if (name.len > 100) return error.InvaildPath;
+
+    var header = [_]u8{0} ** BLOCK_SIZE;
+    @memcpy(header[0..name.len], name);
+    write_octal(header[100..108], mode);
+    write_octal(header[108..116], 0);
+    write_octal(header[116..124], 0);
+    write_octal(header[124..136], size);
+    write_octal(header[136..148], 0);
+    header[156] = kind;
+    @memcpy(header[257..263], "ustar\x00");
+    @memcpy(header[263..265], "00");
+
+    // the checksum is calculated with it's own field filled with spaces
+    @memset(header[148..156], ' ');
+    var checksum: usize = 0;
+    for (header) |byte| checksum += byte;
+    write_octal(header[148..155], checksum);
+
+    try write_all(archive, &header);
+}
+
+/// packs every entry in the directory `path` naming them `prefix/<name>`
+fn pack(archive: isize, path: []const u8, prefix: []const u8) !void {
+    const dir = try io.zopen(path);
+    defer io.zclose(dir) catch {};
+
+    var entries: [16]io.raw.DirEntry = undefined;
+    while (true) {
+        const read = try io.zreaddir(dir, &entries);
+        if (read.len == 0) break;
+
+        for (read) |*entry| {
+            const name = entry.name[0..entry.name_length];
+            if (eql(u8, name, "..") or eql(u8, name, ".")) continue;
+
+            var path_buffer: [PATH_MAX]u8 = undefined;
+            var name_buffer: [PATH_MAX]u8 = undefined;
+            const entry_path = try join(&path_buffer, path, name);
+            const archived = try join(&name_buffer, prefix, name);
+
+            switch (entry.kind) {
+                // directories
+                1 => {
+                    var dir_name_buffer: [PATH_MAX]u8 = undefined;
+                    try write_header(archive, try join(&dir_name_buffer, archived, ""), TYPE_DIR, 0o755, 0);
+                    try pack(archive, entry_path, archived);
+                },
+                // files
+                0 => {
+                    try write_header(archive, archived, TYPE_NORMAL, 0o644, entry.size);
+
+                    const file = try io.zopen(entry_path);
+                    defer io.zclose(file) catch {};
+
+                    var block: [BLOCK_SIZE]u8 = undefined;
+                    var remaining = entry.size;
+                    while (remaining > 0) {
+                        const amount = @min(remaining, BLOCK_SIZE);
+                        @memset(&block, 0);
+                        if (!try read_exact(file, block[0..amount])) return error.Corrupted;
+
+                        try write_all(archive, &block);
+                        remaining -= amount;
+                    }
+                },
+                // devices can't be archived
+                else => try printf("tar: skipping device %.*s\n", .{ entry_path.len, entry_path.ptr }),
+            }
+        }
+    }
+}
+
+/// extracts `archive` into `dest`, if `dest` is null the entries are only listed
+fn unpack(archive: isize, dest: ?[]const u8) !void {
+    var header: [BLOCK_SIZE]u8 = undefined;
+    while (try read_exact(archive, &header)) {
+        // the archive ends with zeroed blocks
+        if (!eql(u8, header[257..262], "ustar")) break;
+
+        const name = cstr(header[0..100]);
+        const size = parse_octal(header[124..136]);
+        const kind = header[156];
+
+        var file: ?isize = null;
+        defer if (file) |fd| io.zclose(fd) catch {};
+
+        if (dest) |dir| {
+            var path_buffer: [PATH_MAX]u8 = undefined;
+            const path = try join(&path_buffer, dir, name);
+
+            switch (kind) {
+                TYPE_DIR => io.zcreatedir(trim_slashes(path)) catch |err| if (err != error.AlreadyExists) return err,
+                TYPE_NORMAL, 0 => {
+                    io.zcreate(path) catch |err| if (err != error.AlreadyExists) return err;
+                    file = try io.zopen(path);
+                },
+                else => try printf("tar: skipping unsupported entry %.*s\n", .{ name.len, name.ptr }),
+            }
+        } else try printf("%.*s\n", .{ name.len, name.ptr });
+
+        // the data is padded to BLOCK_SIZE
+        var block: [BLOCK_SIZE]u8 = undefined;
+        var remaining = size;
+        while (remaining > 0) {
+            if (!try read_exact(archive, &block)) return error.Corrupted;
+
+            const amount = @min(remaining, BLOCK_SIZE);
+            if (file) |fd| try write_all(fd, block[0..amount]);
+            remaining -= amount;
+        }
+    }
+}
+
+pub fn main() !void {
+    const args = libc.sys.args();
+    if (args.count() < 3) return usage();
+
+    const mode = args.nth(1).?;
+    const archive_path = args.nth(2).?;
+
+    if (eql(u8, mode, "-c")) {
+        if (args.count() < 4) return usage();
+        const dir = args.nth(3).?;
+
+        io.zcreate(archive_path) catch |err| if (err != error.AlreadyExists) return err;
+        const archive = try io.zopen(archive_path);
+        defer io.zclose(archive) catch {};
+
+        try pack(archive, dir, "");
+        // 2 zeroed blocks mark the end of the archive
+        const end = [_]u8{0} ** (BLOCK_SIZE * 2);
+        try write_all(archive, &end);
+    } else if (eql(u8, mode, "-x") or eql(u8, mode, "-t")) {
+        const archive = try io.zopen(archive_path);
+        defer io.zclose(archive) catch {};
+
+        const dest: ?[]const u8 = if (eql(u8, mode, "-t")) null else if (args.count() > 3) args.nth(3).? else "";
+        try unpack(archive, dest);
+    } else return usage();
+}
+
+comptime {
+    _ = libc;
+}
@@ -1,15 +1,25 @@
 // build.rs
+#[path = "build/iso.rs"]
+mod iso;
+#[path = "build/rofs.rs"]
+mod rofs;
+
 use std::{
     collections::HashSet,
     env::current_dir,
     fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
     io::empty,
     path::{Path, PathBuf},
     process::{Command, Output},
 };
 
 use tar::{Builder, Header};
-const ISO_PATH: &str = "safaos.iso";
+/// set to make the iso with xorriso instead of `iso::make_iso`
+const XORRISO_ENV: &str = "SAFA_USE_XORRISO";
+// named after the arch, the kernel is only ported to x86_64 for now which is also the target of the
+// kernel artifact in Cargo.toml
+const ISO_PATH: &str = "safaos-x86_64.iso";
 // (dir relative from build.rs, dir in ramdisk)
 // or (file relative from build.rs, path in ramdisk)
 const RAMDISK_CONTENT: &[(&str, &str)] = &[
@@ -18,23 +28,108 @@ const RAMDISK_CONTENT: &[(&str, &str)] = &[
     ("TestBot/zig-out/bin/TestBot", "bin/TestBot"),
     ("ramdisk-include/", ""),
 ];
+/// empty directories in the rofs image, the kernel mounts a directory of ram: over each of them since
+/// sys: is read-only, see `vfs::unpack_ramdisk`
+const RAMDISK_WRITABLE_DIRS: &[&str] = &["pkgs"];
+// (zig project, paths it is built from), a project is only rebuilt when one of them changed
+const ZIG_PROJECTS: &[(&str, &[&str])] = &[
+    ("Shell", &["Shell", "libc"]),
+    ("bin", &["bin", "libc"]),
+    ("TestBot", &["TestBot", "libc"]),
+];
+/// set to rebuild everything ignoring the cache
+const FORCE_REBUILD_ENV: &str = "SAFA_FORCE_REBUILD";
+/// set to `kernel` or `ramdisk` to only put that in `iso_root` without making the iso, what the
+/// last builds put there is kept, see `cargo safa build --only`
+const BUILD_ONLY_ENV: &str = "SAFA_BUILD_ONLY";
+/// only one of them is in `iso_root/boot` depending on the `tar-ramdisk` and `compressed-ramdisk`
+/// features
+const RAMDISK_NAMES: &[&str] = &["ramdisk.rofs", "ramdisk.tar", "ramdisk.tar.gz"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuildOnly {
+    Kernel,
+    Ramdisk,
+    /// everything
+    Iso,
+}
+
+impl BuildOnly {
+    fn from_env() -> Self {
+        match std::env::var(BUILD_ONLY_ENV).as_deref() {
+            Ok("kernel") => Self::Kernel,
+            Ok("ramdisk") => Self::Ramdisk,
+            Ok("iso") | Ok("") | Err(_) => Self::Iso,
+            Ok(only) => panic!("{BUILD_ONLY_ENV} must be kernel, ramdisk or iso not {only}"),
+        }
+    }
 
-fn limine_make() -> Output {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Kernel => "kernel",
+            Self::Ramdisk => "ramdisk",
+            Self::Iso => "iso",
+        }
+    }
+}
+
+/// hashes the contents of every file under `path` (or `path` itself if it is a file), zig's output
+/// and cache directories are skipped
+fn hash_path(path: &Path, hasher: &mut DefaultHasher) {
+    if path.is_file() {
+        path.hash(hasher);
+        fs::read(path).unwrap().hash(hasher);
+    } else if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.sort();
+
+        for entry in entries {
+            let name = entry.file_name().and_then(|name| name.to_str());
+            if matches!(name, Some("zig-out" | ".zig-cache" | "zig-cache")) {
+                continue;
+            }
+            hash_path(&entry, hasher);
+        }
+    }
+}
+
+/// the hashes and the last ramdisk are kept in cargo's `OUT_DIR` which stays between builds
+fn cache_dir() -> PathBuf {
+    PathBuf::from(std::env::var_os("OUT_DIR").unwrap())
+}
+
+/// returns true if `name` was last built from inputs hashing to `hash`
+fn is_cached(name: &str, hash: u64) -> bool {
+    if std::env::var_os(FORCE_REBUILD_ENV).is_some() {
+        return false;
+    }
+
+    fs::read_to_string(cache_dir().join(format!("{name}.hash")))
+        .is_ok_and(|cached| cached == format!("{hash:016x}"))
+}
+
+fn save_hash(name: &str, hash: u64) {
+    fs::write(
+        cache_dir().join(format!("{name}.hash")),
+        format!("{hash:016x}"),
+    )
+    .unwrap();
+}
+
+/// the binary branch has every file the iso needs prebuilt
+fn limine_clone() {
     if !fs::exists("limine").unwrap() {
-        Command::new("git")
+        out(Command::new("git")
             .arg("clone")
             .arg("https://github.com/limine-bootloader/limine.git")
             .arg("--branch=v8.x-binary")
             .arg("--depth=1")
             .output()
-            .unwrap();
+            .unwrap());
     }
-
-    Command::new("make")
-        .arg("-C")
-        .arg("limine")
-        .output()
-        .unwrap()
 }
 
 fn out(mut output: Output) {
@@ -46,45 +141,52 @@ fn setup_iso_root() {
     fs::create_dir_all("iso_root/EFI/BOOT").unwrap();
 }
 
+/// copies every file in `files` into the directory `dest`
+fn copy_into(files: &[&str], dest: &str) {
+    for file in files {
+        let name = Path::new(file).file_name().unwrap();
+        fs::copy(file, Path::new(dest).join(name))
+            .unwrap_or_else(|err| panic!("failed copying {file} into {dest}: {err}"));
+    }
+}
+
 fn put_kernel_img() {
     let kernel = PathBuf::from(std::env::var_os("CARGO_BIN_FILE_KERNEL_kernel").unwrap());
-    out(Command::new("mv")
-        .arg("-v")
-        .arg(kernel)
-        .arg("iso_root/boot/kernel")
-        .output()
-        .unwrap());
+    fs::copy(kernel, "iso_root/boot/kernel").unwrap();
 }
 
 fn put_limine_config() {
-    out(Command::new("cp")
-        .arg("-v")
-        .arg("limine.conf")
-        .arg("limine/limine-bios.sys")
-        .arg("limine/limine-bios-cd.bin")
-        .arg("limine/limine-uefi-cd.bin")
-        .arg("iso_root/boot/limine")
-        .output()
-        .unwrap())
+    copy_into(
+        &[
+            "limine.conf",
+            "limine/limine-bios.sys",
+            "limine/limine-bios-cd.bin",
+            "limine/limine-uefi-cd.bin",
+        ],
+        "iso_root/boot/limine",
+    );
 }
 
 fn put_boot_files() {
-    out(Command::new("cp")
-        .arg("-v")
-        .arg("limine/BOOTX64.EFI")
-        .arg("iso_root/EFI/BOOT")
-        .output()
-        .unwrap());
-
-    out(Command::new("cp")
-        .arg("-v")
-        .arg("limine/BOOTIA32.EFI")
-        .arg("iso_root/EFI/BOOT")
-        .output()
-        .unwrap());
+    copy_into(
+        &["limine/BOOTX64.EFI", "limine/BOOTIA32.EFI"],
+        "iso_root/EFI/BOOT",
+    );
 }
 
 fn make_iso() {
+    if std::env::var_os(XORRISO_ENV).is_none() {
+        match iso::make_iso(
+            Path::new("iso_root"),
+            "boot/limine/limine-bios-cd.bin",
+            "boot/limine/limine-uefi-cd.bin",
+            Path::new(ISO_PATH),
+        ) {
+            Ok(()) => return,
+            Err(err) => eprintln!("failed making the iso ({err}), falling back to xorriso"),
+        }
+    }
+
     // command too long ):
     out(Command::new("bash")
         .arg("-c")
@@ -99,27 +201,36 @@ fn make_iso() {
         .unwrap())
 }
 
-fn compile_programs() -> Output {
+fn compile_programs() {
     Command::new("make")
         .arg("-C")
         .arg("programs")
         .output()
         .unwrap();
-    Command::new("bash")
-        .arg("-c")
-        .arg("cd Shell && zig build")
-        .output()
-        .unwrap();
-    Command::new("bash")
-        .arg("-c")
-        .arg("cd bin && zig build")
-        .output()
-        .unwrap();
-    Command::new("bash")
-        .arg("-c")
-        .arg("cd TestBot && zig build")
-        .output()
-        .unwrap()
+
+    for (project, inputs) in ZIG_PROJECTS {
+        let mut hasher = DefaultHasher::new();
+        for input in *inputs {
+            hash_path(Path::new(input), &mut hasher);
+        }
+        let hash = hasher.finish();
+
+        if is_cached(project, hash) && Path::new(project).join("zig-out").exists() {
+            eprintln!("{project} is up to date");
+            continue;
+        }
+
+        let output = Command::new("zig")
+            .arg("build")
+            .current_dir(project)
+            .output()
+            .unwrap();
+
+        if output.status.success() {
+            save_hash(project, hash);
+        }
+        out(output);
+    }
 }
 
 fn make_ramdisk() {
@@ -162,28 +273,149 @@ fn make_ramdisk() {
     tar_builder.finish().unwrap();
 }
 
+/// adds every file under the directory `src` to `image` at `dest`
+fn add_dir_all(image: &mut rofs::Image, src: &Path, dest: &str) {
+    image.add_dir(dest);
+
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        let name = entry.file_name().into_string().unwrap();
+        let dest = format!("{dest}/{name}");
+
+        if path.is_dir() {
+            add_dir_all(image, &path, &dest);
+        } else {
+            image.add_file(&dest, &fs::read(path).unwrap());
+        }
+    }
+}
+
+/// makes the rofs image out of RAMDISK_CONTENT
+fn make_image() {
+    let mut image = rofs::Image::new();
+
+    for (src, dest) in RAMDISK_CONTENT {
+        let src = Path::new(src);
+        if src.is_file() {
+            let data =
+                fs::read(src).expect("ramdisk contents corrupt file missing, edit RAMDISK_CONTENT");
+            image.add_file(dest, &data);
+        } else if src.is_dir() {
+            add_dir_all(&mut image, src, dest);
+        } else {
+            panic!("ramdisk content is nethier a file nor directory (or doesn't exists), edit RAMDISK_CONTENT");
+        }
+    }
+
+    for dir in RAMDISK_WRITABLE_DIRS {
+        image.add_dir(dir);
+    }
+
+    fs::write("iso_root/boot/ramdisk.rofs", image.finish()).unwrap();
+}
+
+/// replaces `ramdisk.tar` with `ramdisk.tar.gz`, the kernel loads whichever exists
+fn compress_ramdisk() {
+    out(Command::new("gzip")
+        .arg("-9")
+        .arg("-f")
+        .arg("iso_root/boot/ramdisk.tar")
+        .output()
+        .unwrap());
+}
+
+/// puts the ramdisk in `iso_root/boot`, reusing the last one if none of RAMDISK_CONTENT changed
+/// the ramdisk is a rofs image unless the `tar-ramdisk` feature is enabled
+fn put_ramdisk() {
+    let tar = std::env::var_os("CARGO_FEATURE_TAR_RAMDISK").is_some();
+    let compressed = std::env::var_os("CARGO_FEATURE_COMPRESSED_RAMDISK").is_some();
+    let name = if compressed {
+        "ramdisk.tar.gz"
+    } else if tar {
+        "ramdisk.tar"
+    } else {
+        "ramdisk.rofs"
+    };
+
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    for (src, dest) in RAMDISK_CONTENT {
+        dest.hash(&mut hasher);
+        hash_path(Path::new(src), &mut hasher);
+    }
+    let hash = hasher.finish();
+
+    let cached = cache_dir().join(name);
+    let path = Path::new("iso_root/boot").join(name);
+    // a ramdisk built with other features could be left over from an earlier `--only ramdisk` build
+    for stale in RAMDISK_NAMES.iter().filter(|stale| **stale != name) {
+        let _ = fs::remove_file(Path::new("iso_root/boot").join(stale));
+    }
+
+    if is_cached("ramdisk", hash) && cached.exists() {
+        eprintln!("ramdisk is up to date");
+        fs::copy(cached, path).unwrap();
+        return;
+    }
+
+    if tar {
+        make_ramdisk();
+    } else {
+        make_image();
+    }
+    if compressed {
+        compress_ramdisk();
+    }
+
+    fs::copy(path, cached).unwrap();
+    save_hash("ramdisk", hash);
+}
+
 fn cleanup() {
     let _ = fs::remove_dir_all("iso_root");
 }
 /// TODO: spilt into more functions and make it work on other oses like windows
 fn main() {
-    cleanup();
-    out(limine_make());
+    let only = BuildOnly::from_env();
+    // partial builds replace their part of the last build
+    if only == BuildOnly::Iso {
+        cleanup();
+    }
+    limine_clone();
     setup_iso_root();
 
-    put_kernel_img();
-    put_limine_config();
-    put_boot_files();
+    if only != BuildOnly::Ramdisk {
+        put_kernel_img();
+    }
+    if only == BuildOnly::Iso {
+        put_limine_config();
+        put_boot_files();
+    }
 
-    out(compile_programs());
-    make_ramdisk();
-    make_iso();
+    if only != BuildOnly::Kernel {
+        compile_programs();
+        put_ramdisk();
+    }
+    if only == BuildOnly::Iso {
+        make_iso();
+    }
     let iso_path = current_dir().unwrap().join(ISO_PATH);
     println!("cargo:rerun-if-changed={}", iso_path.display());
     println!("cargo:rerun-if-changed=limine");
     println!("cargo:rerun-if-changed=programs/build");
     println!("cargo:rerun-if-changed=programs");
+    println!("cargo:rerun-if-env-changed={FORCE_REBUILD_ENV}");
+    println!("cargo:rerun-if-env-changed={XORRISO_ENV}");
+    println!("cargo:rerun-if-env-changed={BUILD_ONLY_ENV}");
 
     // pass the disk image paths as env variables to the `main.rs`
     println!("cargo:rustc-env=ISO_PATH={}", iso_path.display());
+    // the files the iso is made from, `cargo safa image` makes the usb image out of them
+    println!(
+        "cargo:rustc-env=ISO_ROOT={}",
+        current_dir().unwrap().join("iso_root").display()
+    );
+    // the iso is out of date after a partial build so the runner boots `iso_root` instead
+    println!("cargo:rustc-env=BUILD_ONLY={}", only.name());
 }
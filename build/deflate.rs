@@ -0,0 +1,171 @@
+//! a small deflate (RFC 1951) compressor for the blocks of the rofs image, it only uses the fixed
+//! huffman codes and a greedy LZ77 match finder which is good enough for blocks of a few KiBs
+//! the kernel decompresses them with kernel/src/utils/inflate.rs
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW_SIZE: usize = 32 * 1024;
+/// how many previous positions with the same hash are tried before giving up on a longer match
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 12;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    output: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// writes the lowest `len` bits of `value` starting from the least significant one
+    fn bits(&mut self, value: u32, len: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += len;
+
+        while self.count >= 8 {
+            self.output.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// huffman codes are written starting from the most significant bit
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.bits as u8);
+        }
+        self.output
+    }
+}
+
+/// writes the literal or length symbol `symbol` with the fixed codes
+fn literal(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xC0 + symbol - 280, 8),
+    }
+}
+
+/// the index of the biggest base that is at most `value`
+fn symbol_of(bases: &[u16], value: usize) -> usize {
+    bases
+        .iter()
+        .rposition(|base| *base as usize <= value)
+        .unwrap()
+}
+
+fn write_match(writer: &mut BitWriter, len: usize, dist: usize) {
+    let index = symbol_of(&LENGTH_BASE, len);
+    literal(writer, 257 + index as u16);
+    writer.bits(
+        (len - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+
+    let index = symbol_of(&DIST_BASE, dist);
+    writer.code(index as u32, 5);
+    writer.bits(
+        (dist - DIST_BASE[index] as usize) as u32,
+        DIST_EXTRA[index] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let hash = hash(&data[pos..]);
+        prev[pos] = head[hash];
+        head[hash] = pos;
+    }
+}
+
+/// compresses `data` into a raw deflate stream made of a single block
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // the last block, fixed codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    // the last position with each hash and the previous position with the same hash as each position
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+
+        if pos + MIN_MATCH <= data.len() {
+            let max = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            let mut tries = 0;
+
+            while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && tries < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, pos - candidate);
+                    if len == max {
+                        break;
+                    }
+                }
+
+                candidate = prev[candidate];
+                tries += 1;
+            }
+        }
+
+        let (len, dist) = best;
+        if len >= MIN_MATCH {
+            write_match(&mut writer, len, dist);
+            for pos in pos..pos + len {
+                insert(data, pos, &mut head, &mut prev);
+            }
+            pos += len;
+        } else {
+            literal(&mut writer, data[pos] as u16);
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    // end of block
+    literal(&mut writer, 256);
+    writer.finish()
+}
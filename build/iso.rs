@@ -0,0 +1,442 @@
+// makes a bootable ISO9660 image out of a directory without xorriso, the same layout as
+// `xorriso -as mkisofs -b <bios> -no-emul-boot -boot-load-size 4 -boot-info-table
+// --efi-boot <uefi> -efi-boot-part --efi-boot-image`
+// the El Torito catalog boots limine from a cd on BIOS and UEFI, and an MBR partition over the UEFI
+// image boots it when the iso is used as a disk (which is how OsHelper runs it)
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const SECTOR_SIZE: usize = 2048;
+const PVD_LBA: usize = 16;
+const BOOT_RECORD_LBA: usize = 17;
+const TERMINATOR_LBA: usize = 18;
+const FIRST_FREE_LBA: usize = 19;
+/// the recording date of every record, 2024-01-01
+const DATE: [u8; 7] = [124, 1, 1, 0, 0, 0, 0];
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn both_u16(buf: &mut [u8], value: u16) {
+    buf[..2].copy_from_slice(&value.to_le_bytes());
+    buf[2..4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn both_u32(buf: &mut [u8], value: u32) {
+    buf[..4].copy_from_slice(&value.to_le_bytes());
+    buf[4..8].copy_from_slice(&value.to_be_bytes());
+}
+
+fn sectors(size: usize) -> usize {
+    size.div_ceil(SECTOR_SIZE)
+}
+
+/// pads `field` with spaces
+fn a_string(field: &mut [u8], value: &str) {
+    field.fill(b' ');
+    field[..value.len()].copy_from_slice(value.as_bytes());
+}
+
+struct Dir {
+    identifier: Vec<u8>,
+    path: PathBuf,
+    parent: usize,
+    /// (identifier, rock ridge name, child) sorted by identifier
+    children: Vec<(Vec<u8>, String, Child)>,
+    lba: usize,
+    size: usize,
+}
+
+struct File {
+    /// relative to the root with `/` as the separator
+    path: String,
+    source: PathBuf,
+    lba: usize,
+    size: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Child {
+    Dir(usize),
+    File(usize),
+}
+
+/// the directory record of `identifier`, `system_use` is the rock ridge entries
+fn dir_record(
+    identifier: &[u8],
+    lba: usize,
+    size: usize,
+    is_dir: bool,
+    system_use: &[u8],
+) -> Vec<u8> {
+    let mut len = 33 + identifier.len();
+    // the identifier is padded to an even length
+    len += len % 2;
+    let total = len + system_use.len() + (system_use.len() % 2);
+
+    let mut record = vec![0u8; total];
+    record[0] = total as u8;
+    both_u32(&mut record[2..10], lba as u32);
+    both_u32(&mut record[10..18], size as u32);
+    record[18..25].copy_from_slice(&DATE);
+    record[25] = if is_dir { 0x02 } else { 0 };
+    both_u16(&mut record[28..32], 1);
+    record[32] = identifier.len() as u8;
+    record[33..33 + identifier.len()].copy_from_slice(identifier);
+    record[len..len + system_use.len()].copy_from_slice(system_use);
+    record
+}
+
+/// the rock ridge alternate name entry, keeps the names' case and characters
+fn rock_ridge_name(name: &str) -> Vec<u8> {
+    let mut entry = vec![b'N', b'M', 5 + name.len() as u8, 1, 0];
+    entry.extend_from_slice(name.as_bytes());
+    entry
+}
+
+/// the SUSP indicator that has to be in the root's `.` record for rock ridge to be used
+const SUSP_INDICATOR: [u8; 7] = [b'S', b'P', 7, 1, 0xBE, 0xEF, 0];
+
+fn path_table_entry(identifier: &[u8], lba: usize, parent: u16, big_endian: bool) -> Vec<u8> {
+    let mut entry = vec![0u8; 8 + identifier.len() + identifier.len() % 2];
+    entry[0] = identifier.len() as u8;
+    if big_endian {
+        entry[2..6].copy_from_slice(&(lba as u32).to_be_bytes());
+        entry[6..8].copy_from_slice(&parent.to_be_bytes());
+    } else {
+        entry[2..6].copy_from_slice(&(lba as u32).to_le_bytes());
+        entry[6..8].copy_from_slice(&parent.to_le_bytes());
+    }
+    entry[8..8 + identifier.len()].copy_from_slice(identifier);
+    entry
+}
+
+struct Tree {
+    /// in path table order, breadth first with the root first
+    dirs: Vec<Dir>,
+    files: Vec<File>,
+}
+
+impl Tree {
+    fn read(root: &Path) -> io::Result<Self> {
+        let mut tree = Self {
+            dirs: vec![Dir {
+                identifier: vec![0],
+                path: root.to_path_buf(),
+                parent: 0,
+                children: Vec::new(),
+                lba: 0,
+                size: 0,
+            }],
+            files: Vec::new(),
+        };
+
+        // dirs are pushed in the order they are found which is already breadth first
+        let mut index = 0;
+        while index < tree.dirs.len() {
+            let mut entries = fs::read_dir(&tree.dirs[index].path)?
+                .map(|entry| {
+                    let entry = entry?;
+                    let name = entry.file_name().into_string().map_err(|name| {
+                        invalid(format!("{name:?} isn't a valid utf8 file name"))
+                    })?;
+                    Ok((name, entry.path()))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            // the path table has to be sorted by identifier
+            entries.sort_by_key(|(name, _)| name.to_uppercase());
+
+            let prefix = tree.relative_path(index);
+            let mut children = Vec::new();
+
+            for (name, path) in entries {
+                // the identifier and the rock ridge name both have to fit in a 255 bytes record
+                if name.len() > 100 {
+                    return Err(invalid(format!("{name} is too long for an iso record")));
+                }
+
+                if path.is_dir() {
+                    let identifier = name.to_uppercase().into_bytes();
+                    tree.dirs.push(Dir {
+                        identifier: identifier.clone(),
+                        path,
+                        parent: index,
+                        children: Vec::new(),
+                        lba: 0,
+                        size: 0,
+                    });
+                    children.push((identifier, name, Child::Dir(tree.dirs.len() - 1)));
+                } else {
+                    let identifier = format!("{};1", name.to_uppercase()).into_bytes();
+                    let size = fs::metadata(&path)?.len() as usize;
+                    tree.files.push(File {
+                        path: format!("{prefix}{name}"),
+                        source: path,
+                        lba: 0,
+                        size,
+                    });
+                    children.push((identifier, name, Child::File(tree.files.len() - 1)));
+                }
+            }
+
+            children.sort_by(|a, b| a.0.cmp(&b.0));
+            tree.dirs[index].children = children;
+            index += 1;
+        }
+
+        Ok(tree)
+    }
+
+    /// the path of `dir` relative to the root ending with a `/` unless it is the root
+    fn relative_path(&self, mut dir: usize) -> String {
+        let mut parts = Vec::new();
+        while dir != 0 {
+            parts.push(
+                self.dirs[dir]
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+            dir = self.dirs[dir].parent;
+        }
+
+        let mut path = String::new();
+        for part in parts.iter().rev() {
+            path.push_str(part);
+            path.push('/');
+        }
+        path
+    }
+
+    fn file(&self, path: &str) -> io::Result<&File> {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .ok_or_else(|| invalid(format!("{path} doesn't exist")))
+    }
+
+    /// the records of `dir`, records can't cross a sector so they are each paired with their
+    /// offset in the directory's extent
+    fn records(&self, dir: usize) -> Vec<(usize, Vec<u8>)> {
+        let this = &self.dirs[dir];
+        let parent = &self.dirs[this.parent];
+
+        let dot_system_use: &[u8] = if dir == 0 { &SUSP_INDICATOR } else { &[] };
+        let mut records = vec![
+            dir_record(&[0], this.lba, this.size, true, dot_system_use),
+            dir_record(&[1], parent.lba, parent.size, true, &[]),
+        ];
+
+        for (identifier, name, child) in &this.children {
+            let (lba, size, is_dir) = match *child {
+                Child::Dir(index) => (self.dirs[index].lba, self.dirs[index].size, true),
+                Child::File(index) => (self.files[index].lba, self.files[index].size, false),
+            };
+            records.push(dir_record(
+                identifier,
+                lba,
+                size,
+                is_dir,
+                &rock_ridge_name(name),
+            ));
+        }
+
+        let mut offset = 0;
+        records
+            .into_iter()
+            .map(|record| {
+                let left = SECTOR_SIZE - offset % SECTOR_SIZE;
+                if record.len() > left {
+                    offset += left;
+                }
+                let at = offset;
+                offset += record.len();
+                (at, record)
+            })
+            .collect()
+    }
+
+    fn path_table(&self, big_endian: bool) -> Vec<u8> {
+        self.dirs
+            .iter()
+            .flat_map(|dir| {
+                path_table_entry(&dir.identifier, dir.lba, dir.parent as u16 + 1, big_endian)
+            })
+            .collect()
+    }
+}
+
+fn write_sector(image: &mut [u8], lba: usize, data: &[u8]) {
+    let at = lba * SECTOR_SIZE;
+    image[at..at + data.len()].copy_from_slice(data);
+}
+
+/// the El Torito boot catalog, a BIOS entry and a UEFI section
+fn boot_catalog(bios: &File, uefi: &File) -> [u8; SECTOR_SIZE] {
+    let mut catalog = [0u8; SECTOR_SIZE];
+
+    // validation entry, the words of the entry sum to 0
+    catalog[0] = 1;
+    catalog[30] = 0x55;
+    catalog[31] = 0xAA;
+    let sum = catalog[..32].chunks(2).fold(0u16, |sum, word| {
+        sum.wrapping_add(u16::from_le_bytes([word[0], word[1]]))
+    });
+    catalog[28..30].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+
+    // the default entry boots on BIOS with no emulation, loading 4 virtual sectors
+    catalog[32] = 0x88;
+    catalog[38..40].copy_from_slice(&4u16.to_le_bytes());
+    catalog[40..44].copy_from_slice(&(bios.lba as u32).to_le_bytes());
+
+    // the final section header with one UEFI entry
+    catalog[64] = 0x91;
+    catalog[65] = 0xEF;
+    catalog[66..68].copy_from_slice(&1u16.to_le_bytes());
+
+    let uefi_sectors = uefi.size.div_ceil(512).min(u16::MAX as usize) as u16;
+    catalog[96] = 0x88;
+    catalog[102..104].copy_from_slice(&uefi_sectors.to_le_bytes());
+    catalog[104..108].copy_from_slice(&(uefi.lba as u32).to_le_bytes());
+
+    catalog
+}
+
+/// patches the boot info table limine's BIOS cd image expects at offset 8
+fn patch_boot_info_table(image: &mut [u8], bios: &File) {
+    let data = &mut image[bios.lba * SECTOR_SIZE..bios.lba * SECTOR_SIZE + bios.size];
+    let checksum = data[64..].chunks(4).fold(0u32, |sum, word| {
+        let mut bytes = [0u8; 4];
+        bytes[..word.len()].copy_from_slice(word);
+        sum.wrapping_add(u32::from_le_bytes(bytes))
+    });
+
+    data[8..12].copy_from_slice(&(PVD_LBA as u32).to_le_bytes());
+    data[12..16].copy_from_slice(&(bios.lba as u32).to_le_bytes());
+    data[16..20].copy_from_slice(&(bios.size as u32).to_le_bytes());
+    data[20..24].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// an MBR with a single EFI system partition over the UEFI image
+fn mbr(uefi: &File) -> [u8; 512] {
+    let mut mbr = [0u8; 512];
+    let entry = &mut mbr[446..462];
+    entry[1..4].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+    entry[4] = 0xEF;
+    entry[5..8].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+    entry[8..12].copy_from_slice(&((uefi.lba * (SECTOR_SIZE / 512)) as u32).to_le_bytes());
+    entry[12..16].copy_from_slice(&(uefi.size.div_ceil(512) as u32).to_le_bytes());
+
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+    mbr
+}
+
+fn primary_volume_descriptor(tree: &Tree, total: usize, l_table: usize, m_table: usize) -> Vec<u8> {
+    let root = &tree.dirs[0];
+    let mut pvd = vec![0u8; SECTOR_SIZE];
+
+    pvd[0] = 1;
+    pvd[1..6].copy_from_slice(b"CD001");
+    pvd[6] = 1;
+    a_string(&mut pvd[8..40], "");
+    a_string(&mut pvd[40..72], "SAFAOS");
+    both_u32(&mut pvd[80..88], total as u32);
+    both_u16(&mut pvd[120..124], 1);
+    both_u16(&mut pvd[124..128], 1);
+    both_u16(&mut pvd[128..132], SECTOR_SIZE as u16);
+    both_u32(&mut pvd[132..140], tree.path_table(false).len() as u32);
+    pvd[140..144].copy_from_slice(&(l_table as u32).to_le_bytes());
+    pvd[148..152].copy_from_slice(&(m_table as u32).to_be_bytes());
+    pvd[156..190].copy_from_slice(&dir_record(&[0], root.lba, root.size, true, &[]));
+    a_string(&mut pvd[190..702], "");
+    a_string(&mut pvd[574..702], "SAFAOS BUILDER");
+    a_string(&mut pvd[702..813], "");
+    // unspecified dates are 16 '0's and a zero offset
+    for date in pvd[813..881].chunks_mut(17) {
+        date[..16].fill(b'0');
+    }
+    pvd[881] = 1;
+    pvd
+}
+
+/// writes the contents of `root` into an iso at `output`, `bios` and `uefi` are the paths of
+/// limine's cd images relative to `root`
+pub fn make_iso(root: &Path, bios_path: &str, uefi_path: &str, output: &Path) -> io::Result<()> {
+    let mut tree = Tree::read(root)?;
+
+    // the sizes of the directories don't depend on where things are
+    for dir in 0..tree.dirs.len() {
+        let records = tree.records(dir);
+        let (at, last) = records.last().unwrap();
+        tree.dirs[dir].size = sectors(at + last.len()) * SECTOR_SIZE;
+    }
+
+    let table_sectors = sectors(tree.path_table(false).len());
+    let l_table = FIRST_FREE_LBA;
+    let m_table = l_table + table_sectors;
+    let catalog_lba = m_table + table_sectors;
+
+    let mut lba = catalog_lba + 1;
+    for dir in &mut tree.dirs {
+        dir.lba = lba;
+        lba += sectors(dir.size);
+    }
+    for file in &mut tree.files {
+        file.lba = lba;
+        lba += sectors(file.size);
+    }
+    let total = lba;
+
+    let mut image = vec![0u8; total * SECTOR_SIZE];
+
+    let bios = tree.file(bios_path)?;
+    let uefi = tree.file(uefi_path)?;
+    write_sector(&mut image, 0, &mbr(uefi));
+
+    let pvd = primary_volume_descriptor(&tree, total, l_table, m_table);
+    write_sector(&mut image, PVD_LBA, &pvd);
+
+    let mut boot_record = [0u8; SECTOR_SIZE];
+    boot_record[1..6].copy_from_slice(b"CD001");
+    boot_record[6] = 1;
+    boot_record[7..30].copy_from_slice(b"EL TORITO SPECIFICATION");
+    boot_record[71..75].copy_from_slice(&(catalog_lba as u32).to_le_bytes());
+    write_sector(&mut image, BOOT_RECORD_LBA, &boot_record);
+
+    let mut terminator = [0u8; SECTOR_SIZE];
+    terminator[0] = 255;
+    terminator[1..6].copy_from_slice(b"CD001");
+    terminator[6] = 1;
+    write_sector(&mut image, TERMINATOR_LBA, &terminator);
+
+    write_sector(&mut image, l_table, &tree.path_table(false));
+    write_sector(&mut image, m_table, &tree.path_table(true));
+    write_sector(&mut image, catalog_lba, &boot_catalog(bios, uefi));
+
+    for dir in 0..tree.dirs.len() {
+        let start = tree.dirs[dir].lba * SECTOR_SIZE;
+        for (at, record) in tree.records(dir) {
+            image[start + at..start + at + record.len()].copy_from_slice(&record);
+        }
+    }
+
+    for file in &tree.files {
+        let data = fs::read(&file.source)?;
+        if data.len() != file.size {
+            return Err(invalid(format!(
+                "{} changed while making the iso",
+                file.path
+            )));
+        }
+        write_sector(&mut image, file.lba, &data);
+    }
+
+    patch_boot_info_table(&mut image, bios);
+    fs::write(output, image)
+}
@@ -0,0 +1,181 @@
+//! makes the read-only image the kernel mounts as sys:, see kernel/src/drivers/vfs/rofs.rs for the
+//! layout, everything is little endian
+//! the contents of files are split into blocks of `BLOCK_SIZE` bytes that are compressed on their own,
+//! a block is stored once however many files have it
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+#[path = "deflate.rs"]
+mod deflate;
+
+const MAGIC: &[u8; 8] = b"SAFAROFS";
+const VERSION: u32 = 1;
+pub const BLOCK_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 64;
+
+const KIND_FILE: u32 = 0;
+const KIND_DIR: u32 = 1;
+
+enum Node {
+    /// the blocks of the file and it's size
+    File(Vec<u32>, usize),
+    Dir(BTreeMap<String, Node>),
+}
+
+struct Block {
+    /// the compressed data, or the data itself if it didn't get smaller
+    data: Vec<u8>,
+    len: usize,
+}
+
+pub struct Image {
+    root: Node,
+    blocks: Vec<Block>,
+    /// the index of every block by it's contents
+    block_ids: HashMap<Vec<u8>, u32>,
+}
+
+impl Image {
+    pub fn new() -> Self {
+        Self {
+            root: Node::Dir(BTreeMap::new()),
+            blocks: Vec::new(),
+            block_ids: HashMap::new(),
+        }
+    }
+
+    /// the directory at `path` creating it and it's parents if they don't exist, `path` is
+    /// separated by `/` and relative to the root
+    fn dir(&mut self, path: &str) -> &mut BTreeMap<String, Node> {
+        let mut current = &mut self.root;
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let Node::Dir(children) = current else {
+                panic!("{path} is inside of a file");
+            };
+            current = children
+                .entry(name.to_string())
+                .or_insert_with(|| Node::Dir(BTreeMap::new()));
+        }
+
+        match current {
+            Node::Dir(children) => children,
+            Node::File(..) => panic!("{path} is a file"),
+        }
+    }
+
+    pub fn add_dir(&mut self, path: &str) {
+        self.dir(path);
+    }
+
+    /// adds a file at `path` replacing any file that was there
+    pub fn add_file(&mut self, path: &str, data: &[u8]) {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        let blocks = data
+            .chunks(BLOCK_SIZE)
+            .map(|block| self.add_block(block))
+            .collect();
+        self.dir(parent)
+            .insert(name.to_string(), Node::File(blocks, data.len()));
+    }
+
+    fn add_block(&mut self, block: &[u8]) -> u32 {
+        if let Some(id) = self.block_ids.get(block) {
+            return *id;
+        }
+
+        let compressed = deflate::deflate(block);
+        let data = if compressed.len() < block.len() {
+            compressed
+        } else {
+            block.to_vec()
+        };
+
+        let id = self.blocks.len() as u32;
+        self.blocks.push(Block {
+            data,
+            len: block.len(),
+        });
+        self.block_ids.insert(block.to_vec(), id);
+        id
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        // inodeids are given breadth first so the children of a directory have increasing ids
+        let mut inodes = Vec::new();
+        let mut queue = VecDeque::from([("/".to_string(), &self.root)]);
+        let mut next_id = 1;
+
+        let mut refs: Vec<u32> = Vec::new();
+        let mut names: Vec<u8> = Vec::new();
+
+        while let Some((name, node)) = queue.pop_front() {
+            let (kind, start, size) = match node {
+                Node::File(blocks, size) => {
+                    let start = refs.len();
+                    refs.extend(blocks);
+                    (KIND_FILE, start, *size)
+                }
+                Node::Dir(children) => {
+                    let start = refs.len();
+                    for (name, child) in children {
+                        refs.push(next_id);
+                        next_id += 1;
+                        queue.push_back((name.clone(), child));
+                    }
+                    (KIND_DIR, start, children.len())
+                }
+            };
+
+            let mut inode = Vec::new();
+            inode.extend(kind.to_le_bytes());
+            inode.extend((name.len() as u32).to_le_bytes());
+            inode.extend((names.len() as u32).to_le_bytes());
+            inode.extend((start as u32).to_le_bytes());
+            inode.extend((size as u64).to_le_bytes());
+            inodes.push(inode);
+
+            names.extend(name.as_bytes());
+        }
+
+        let inodes_offset = HEADER_SIZE;
+        let blocks_offset = inodes_offset + inodes.len() * 24;
+        let refs_offset = blocks_offset + self.blocks.len() * 16;
+        let names_offset = refs_offset + refs.len() * 4;
+        let data_offset = names_offset + names.len();
+
+        let mut image = Vec::new();
+        image.extend(MAGIC);
+        image.extend(VERSION.to_le_bytes());
+        image.extend((BLOCK_SIZE as u32).to_le_bytes());
+        image.extend((inodes.len() as u32).to_le_bytes());
+        image.extend((self.blocks.len() as u32).to_le_bytes());
+        image.extend((refs.len() as u32).to_le_bytes());
+        image.extend(0u32.to_le_bytes());
+        for offset in [inodes_offset, blocks_offset, refs_offset, names_offset] {
+            image.extend((offset as u64).to_le_bytes());
+        }
+
+        for inode in inodes {
+            image.extend(inode);
+        }
+
+        let mut offset = data_offset;
+        for block in &self.blocks {
+            image.extend((offset as u64).to_le_bytes());
+            image.extend((block.data.len() as u32).to_le_bytes());
+            image.extend((block.len as u32).to_le_bytes());
+            offset += block.data.len();
+        }
+
+        for id in refs {
+            image.extend(id.to_le_bytes());
+        }
+        image.extend(names);
+
+        for block in self.blocks {
+            image.extend(block.data);
+        }
+        image
+    }
+}
@@ -0,0 +1,477 @@
+//! a table-driven VT parser, based on Paul Williams' DEC compatible state machine
+//! (https://vt100.net/emu/dec_ansi_parser)
+//!
+//! the parser is fed chars and calls the methods of a [`Perform`] for each printable char, control
+//! char and complete escape sequence, it keeps it's state between calls so a sequence can be split
+//! across writes
+//!
+//! differences from the DEC parser:
+//! - the input is unicode chars, every char above 0x7F is printed in the ground state, put in DCS
+//!   and OSC strings and aborts (or is ignored in) everything else, C1 controls aren't recognized
+//! - BEL terminates OSC strings like xterm does
+//! - CAN and SUB abort OSC strings instead of dispatching them
+//! - DEL is ignored in the ground state
+//! - sequences with more than [`MAX_INTERMEDIATES`] intermediates or OSC strings longer than
+//!   [`MAX_OSC_LEN`] are dropped, parameters after the first [`MAX_PARAMS`] are dropped
+
+pub const MAX_PARAMS: usize = 16;
+pub const MAX_INTERMEDIATES: usize = 2;
+pub const MAX_OSC_LEN: usize = 512;
+
+/// receives the output of a [`Parser`], every method does nothing by default
+#[allow(unused_variables)]
+pub trait Perform {
+    /// a printable char
+    fn print(&mut self, c: char) {}
+    /// a C0 control char, such as `\n` or `\r`
+    fn execute(&mut self, byte: u8) {}
+    /// `ESC [ <params> <intermediates> <action>`, missing parameters are 0
+    fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], action: char) {}
+    /// `ESC <intermediates> <byte>`
+    fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) {}
+    /// `ESC ] <data> BEL` or `ESC ] <data> ESC \`, the data isn't split at `;`
+    fn osc_dispatch(&mut self, data: &[u8]) {}
+    /// the start of a DCS string `ESC P <params> <intermediates> <action>`, followed by [`Perform::put`]
+    /// for each char of it and then [`Perform::unhook`]
+    fn hook(&mut self, params: &[u16], intermediates: &[u8], action: char) {}
+    fn put(&mut self, c: char) {}
+    fn unhook(&mut self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    CsiIgnore,
+    DcsEntry,
+    DcsParam,
+    DcsIntermediate,
+    DcsPassthrough,
+    DcsIgnore,
+    OscString,
+    SosPmApcString,
+}
+
+impl State {
+    const COUNT: usize = Self::SosPmApcString as usize + 1;
+    const ALL: [Self; Self::COUNT] = [
+        Self::Ground,
+        Self::Escape,
+        Self::EscapeIntermediate,
+        Self::CsiEntry,
+        Self::CsiParam,
+        Self::CsiIntermediate,
+        Self::CsiIgnore,
+        Self::DcsEntry,
+        Self::DcsParam,
+        Self::DcsIntermediate,
+        Self::DcsPassthrough,
+        Self::DcsIgnore,
+        Self::OscString,
+        Self::SosPmApcString,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Action {
+    None,
+    Ignore,
+    Print,
+    Execute,
+    Collect,
+    Param,
+    EscDispatch,
+    CsiDispatch,
+    Put,
+    OscPut,
+}
+
+impl Action {
+    const ALL: [Self; 10] = [
+        Self::None,
+        Self::Ignore,
+        Self::Print,
+        Self::Execute,
+        Self::Collect,
+        Self::Param,
+        Self::EscDispatch,
+        Self::CsiDispatch,
+        Self::Put,
+        Self::OscPut,
+    ];
+}
+
+/// the class of every char above 0x7F
+const NON_ASCII: usize = 0x80;
+const CLASSES: usize = NON_ASCII + 1;
+
+/// a transition packed as `action << 4 | next state`
+#[derive(Clone, Copy)]
+struct Transition(u8);
+
+impl Transition {
+    const fn new(action: Action, state: State) -> Self {
+        Self((action as u8) << 4 | state as u8)
+    }
+
+    const fn action(self) -> Action {
+        Action::ALL[(self.0 >> 4) as usize]
+    }
+
+    const fn state(self) -> State {
+        State::ALL[(self.0 & 0xF) as usize]
+    }
+}
+
+/// the C0 controls that are executed (or ignored, put...) in every state, CAN, SUB and ESC are
+/// handled by [`TABLE`]'s "anywhere" transitions instead
+const fn is_c0(byte: usize) -> bool {
+    matches!(byte, 0x00..=0x17 | 0x19 | 0x1C..=0x1F)
+}
+
+const fn set(
+    row: &mut [Transition; CLASSES],
+    start: usize,
+    end: usize,
+    action: Action,
+    state: State,
+) {
+    let mut byte = start;
+    while byte <= end {
+        row[byte] = Transition::new(action, state);
+        byte += 1;
+    }
+}
+
+/// sets every C0 control of `row` to `action` without leaving `state`
+const fn set_c0(row: &mut [Transition; CLASSES], action: Action, state: State) {
+    let mut byte = 0;
+    while byte < 0x20 {
+        if is_c0(byte) {
+            row[byte] = Transition::new(action, state);
+        }
+        byte += 1;
+    }
+}
+
+const fn build_row(state: State) -> [Transition; CLASSES] {
+    use Action::*;
+    let mut row = [Transition::new(Ignore, state); CLASSES];
+
+    match state {
+        State::Ground => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x20, 0x7E, Print, state);
+            set(&mut row, NON_ASCII, NON_ASCII, Print, state);
+        }
+        State::Escape => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x20, 0x2F, Collect, State::EscapeIntermediate);
+            set(&mut row, 0x30, 0x7E, EscDispatch, State::Ground);
+            set(
+                &mut row,
+                b'P' as usize,
+                b'P' as usize,
+                None,
+                State::DcsEntry,
+            );
+            set(
+                &mut row,
+                b'X' as usize,
+                b'X' as usize,
+                None,
+                State::SosPmApcString,
+            );
+            set(
+                &mut row,
+                b'[' as usize,
+                b'[' as usize,
+                None,
+                State::CsiEntry,
+            );
+            // ST only terminates the strings, it does nothing on it's own
+            set(
+                &mut row,
+                b'\\' as usize,
+                b'\\' as usize,
+                None,
+                State::Ground,
+            );
+            set(
+                &mut row,
+                b']' as usize,
+                b']' as usize,
+                None,
+                State::OscString,
+            );
+            set(
+                &mut row,
+                b'^' as usize,
+                b'_' as usize,
+                None,
+                State::SosPmApcString,
+            );
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::Ground);
+        }
+        State::EscapeIntermediate => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x20, 0x2F, Collect, state);
+            set(&mut row, 0x30, 0x7E, EscDispatch, State::Ground);
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::Ground);
+        }
+        State::CsiEntry | State::CsiParam => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x20, 0x2F, Collect, State::CsiIntermediate);
+            set(&mut row, 0x30, 0x39, Param, State::CsiParam);
+            set(&mut row, 0x3A, 0x3A, Ignore, State::CsiIgnore);
+            set(&mut row, 0x3B, 0x3B, Param, State::CsiParam);
+            // private markers such as `?` are only allowed before the parameters
+            if matches!(state, State::CsiEntry) {
+                set(&mut row, 0x3C, 0x3F, Collect, State::CsiParam);
+            } else {
+                set(&mut row, 0x3C, 0x3F, Ignore, State::CsiIgnore);
+            }
+            set(&mut row, 0x40, 0x7E, CsiDispatch, State::Ground);
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::CsiIgnore);
+        }
+        State::CsiIntermediate => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x20, 0x2F, Collect, state);
+            set(&mut row, 0x30, 0x3F, Ignore, State::CsiIgnore);
+            set(&mut row, 0x40, 0x7E, CsiDispatch, State::Ground);
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::CsiIgnore);
+        }
+        State::CsiIgnore => {
+            set_c0(&mut row, Execute, state);
+            set(&mut row, 0x40, 0x7E, None, State::Ground);
+        }
+        State::DcsEntry | State::DcsParam => {
+            set(&mut row, 0x20, 0x2F, Collect, State::DcsIntermediate);
+            set(&mut row, 0x30, 0x39, Param, State::DcsParam);
+            set(&mut row, 0x3A, 0x3A, Ignore, State::DcsIgnore);
+            set(&mut row, 0x3B, 0x3B, Param, State::DcsParam);
+            if matches!(state, State::DcsEntry) {
+                set(&mut row, 0x3C, 0x3F, Collect, State::DcsParam);
+            } else {
+                set(&mut row, 0x3C, 0x3F, Ignore, State::DcsIgnore);
+            }
+            set(&mut row, 0x40, 0x7E, None, State::DcsPassthrough);
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::DcsIgnore);
+        }
+        State::DcsIntermediate => {
+            set(&mut row, 0x20, 0x2F, Collect, state);
+            set(&mut row, 0x30, 0x3F, Ignore, State::DcsIgnore);
+            set(&mut row, 0x40, 0x7E, None, State::DcsPassthrough);
+            set(&mut row, NON_ASCII, NON_ASCII, Ignore, State::DcsIgnore);
+        }
+        State::DcsPassthrough => {
+            set_c0(&mut row, Put, state);
+            set(&mut row, 0x20, 0x7E, Put, state);
+            set(&mut row, NON_ASCII, NON_ASCII, Put, state);
+        }
+        State::OscString => {
+            set(&mut row, 0x07, 0x07, None, State::Ground);
+            set(&mut row, 0x20, 0x7F, OscPut, state);
+            set(&mut row, NON_ASCII, NON_ASCII, OscPut, state);
+        }
+        State::DcsIgnore | State::SosPmApcString => {}
+    }
+
+    // the "anywhere" transitions
+    set(&mut row, 0x18, 0x18, Execute, State::Ground);
+    set(&mut row, 0x1A, 0x1A, Execute, State::Ground);
+    set(&mut row, 0x1B, 0x1B, None, State::Escape);
+    row
+}
+
+const fn build_table() -> [[Transition; CLASSES]; State::COUNT] {
+    let mut table = [[Transition::new(Action::Ignore, State::Ground); CLASSES]; State::COUNT];
+    let mut i = 0;
+    while i < State::COUNT {
+        table[i] = build_row(State::ALL[i]);
+        i += 1;
+    }
+    table
+}
+
+static TABLE: [[Transition; CLASSES]; State::COUNT] = build_table();
+
+#[derive(Debug, Clone)]
+pub struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    /// the number of parameters started so far
+    params_len: usize,
+    intermediates: [u8; MAX_INTERMEDIATES],
+    intermediates_len: usize,
+    /// set when there are more intermediates than [`MAX_INTERMEDIATES`] or the OSC string is
+    /// longer than [`MAX_OSC_LEN`], the sequence is dropped
+    overflowed: bool,
+    osc: [u8; MAX_OSC_LEN],
+    osc_len: usize,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            params_len: 0,
+            intermediates: [0; MAX_INTERMEDIATES],
+            intermediates_len: 0,
+            overflowed: false,
+            osc: [0; MAX_OSC_LEN],
+            osc_len: 0,
+        }
+    }
+
+    /// returns wether or not the parser is in the middle of a sequence
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+
+    fn clear(&mut self) {
+        self.params_len = 0;
+        self.intermediates_len = 0;
+        self.overflowed = false;
+        self.osc_len = 0;
+    }
+
+    fn params(&self) -> &[u16] {
+        &self.params[..self.params_len.min(MAX_PARAMS)]
+    }
+
+    fn intermediates(&self) -> &[u8] {
+        &self.intermediates[..self.intermediates_len]
+    }
+
+    fn param(&mut self, byte: u8) {
+        // the first parameter is started implicitly
+        if self.params_len == 0 {
+            self.params_len = 1;
+            self.params[0] = 0;
+        }
+
+        if byte == b';' {
+            if self.params_len < MAX_PARAMS {
+                self.params[self.params_len] = 0;
+            }
+            // counts the dropped parameters too so the digits after them are dropped as well
+            self.params_len = self.params_len.saturating_add(1);
+            return;
+        }
+
+        if self.params_len > MAX_PARAMS {
+            return;
+        }
+
+        let param = &mut self.params[self.params_len - 1];
+        *param = param
+            .saturating_mul(10)
+            .saturating_add((byte - b'0') as u16);
+    }
+
+    fn collect(&mut self, byte: u8) {
+        if self.intermediates_len == MAX_INTERMEDIATES {
+            self.overflowed = true;
+        } else {
+            self.intermediates[self.intermediates_len] = byte;
+            self.intermediates_len += 1;
+        }
+    }
+
+    fn osc_put(&mut self, c: char) {
+        let len = c.len_utf8();
+        if self.osc_len + len > MAX_OSC_LEN {
+            self.overflowed = true;
+            return;
+        }
+
+        c.encode_utf8(&mut self.osc[self.osc_len..]);
+        self.osc_len += len;
+    }
+
+    fn perform_action<P: Perform>(&mut self, performer: &mut P, action: Action, c: char) {
+        // the actions other than Print, Put and OscPut are only ever triggered by ascii chars
+        let byte = c as u8;
+        match action {
+            Action::None | Action::Ignore => {}
+            Action::Print => performer.print(c),
+            Action::Execute => performer.execute(byte),
+            Action::Collect => self.collect(byte),
+            Action::Param => self.param(byte),
+            Action::EscDispatch if !self.overflowed => {
+                performer.esc_dispatch(self.intermediates(), byte)
+            }
+            Action::CsiDispatch if !self.overflowed => {
+                performer.csi_dispatch(self.params(), self.intermediates(), c)
+            }
+            Action::EscDispatch | Action::CsiDispatch => {}
+            Action::Put if !self.overflowed => performer.put(c),
+            Action::Put => {}
+            Action::OscPut => self.osc_put(c),
+        }
+    }
+
+    /// the exit action of the current state, `c` is the char that made the parser leave it
+    fn exit<P: Perform>(&mut self, performer: &mut P, c: char) {
+        match self.state {
+            State::OscString if !self.overflowed && matches!(c, '\x07' | '\x1b') => {
+                performer.osc_dispatch(&self.osc[..self.osc_len])
+            }
+            State::DcsPassthrough if !self.overflowed => performer.unhook(),
+            _ => {}
+        }
+    }
+
+    /// the entry action of the current state, `c` is the char that made the parser enter it
+    fn enter<P: Perform>(&mut self, performer: &mut P, c: char) {
+        match self.state {
+            State::Escape | State::CsiEntry | State::DcsEntry | State::OscString => self.clear(),
+            State::DcsPassthrough if !self.overflowed => {
+                performer.hook(self.params(), self.intermediates(), c)
+            }
+            _ => {}
+        }
+    }
+
+    /// feeds `c` to the parser
+    pub fn advance<P: Perform>(&mut self, performer: &mut P, c: char) {
+        let class = (c as usize).min(NON_ASCII);
+        let transition = TABLE[self.state as usize][class];
+        let (action, state) = (transition.action(), transition.state());
+
+        // a transition to the same state doesn't run the exit and entry actions, except for ESC
+        // which restarts the sequence
+        let changes_state = state != self.state || c == '\x1b';
+        if changes_state {
+            self.exit(performer, c);
+        }
+
+        self.perform_action(performer, action, c);
+
+        if changes_state {
+            self.state = state;
+            self.enter(performer, c);
+        }
+    }
+
+    /// feeds every char of `s` to the parser
+    pub fn advance_str<P: Perform>(&mut self, performer: &mut P, s: &str) {
+        for c in s.chars() {
+            self.advance(performer, c);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! utilities shared between the kernel and userspace, everything here is `no_std` and doesn't
+//! allocate
+#![no_std]
+
+pub mod ansi;
+pub mod utf8;
@@ -0,0 +1,155 @@
+//! UTF-8 for things that are fed bytes in pieces, such as a terminal, and the amount of cells a char
+//! takes on a terminal
+//!
+//! a char split across two writes is decoded once both arrived, invalid bytes are decoded as
+//! [`char::REPLACEMENT_CHARACTER`] like [`core::str::from_utf8`] would report them
+
+const REPLACEMENT: &str = "\u{FFFD}";
+
+/// decodes bytes into strs keeping the incomplete sequence at the end of them for the next call
+#[derive(Debug, Clone, Copy)]
+pub struct Decoder {
+    pending: [u8; 4],
+    pending_len: usize,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            pending: [0; 4],
+            pending_len: 0,
+        }
+    }
+
+    /// calls `then` with each valid piece of `bytes`, including the sequence left incomplete by the
+    /// previous call once it's completed
+    pub fn decode(&mut self, mut bytes: &[u8], mut then: impl FnMut(&str)) {
+        while self.pending_len != 0 {
+            let Some((&byte, rest)) = bytes.split_first() else {
+                return;
+            };
+
+            self.pending[self.pending_len] = byte;
+            match core::str::from_utf8(&self.pending[..=self.pending_len]) {
+                Ok(str) => {
+                    self.pending_len = 0;
+                    bytes = rest;
+                    then(str);
+                }
+                Err(err) if err.error_len().is_none() => {
+                    self.pending_len += 1;
+                    bytes = rest;
+                }
+                // `byte` doesn't continue the sequence, it is decoded on it's own
+                Err(_) => {
+                    self.pending_len = 0;
+                    then(REPLACEMENT);
+                }
+            }
+        }
+
+        while !bytes.is_empty() {
+            match core::str::from_utf8(bytes) {
+                Ok(str) => return then(str),
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    if !valid.is_empty() {
+                        then(unsafe { core::str::from_utf8_unchecked(valid) });
+                    }
+
+                    match err.error_len() {
+                        Some(len) => {
+                            then(REPLACEMENT);
+                            bytes = &rest[len..];
+                        }
+                        None => {
+                            self.pending[..rest.len()].copy_from_slice(rest);
+                            self.pending_len = rest.len();
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// the chars that are drawn on top of the previous char
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x2028, 0x202E),
+    (0x2060, 0x2064),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0100, 0xE01EF),
+];
+
+/// the chars that take two cells, CJK and emoji
+const DOUBLE_WIDTH: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x23E9, 0x23EC),
+    (0x2614, 0x2615),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F900, 0x1F9FF),
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                core::cmp::Ordering::Less
+            } else if start > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// the amount of cells `c` takes on a terminal, 0 for control chars and combining marks
+pub fn width(c: char) -> usize {
+    if c.is_control() || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(DOUBLE_WIDTH, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// the amount of cells `s` takes on a terminal
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(width).sum()
+}
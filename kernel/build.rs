@@ -1,4 +1,111 @@
+//! links the kernel with kernel/linker.ld and injects the metadata of the build, see
+//! `utils::expose::kernelinfo`:
+//! `SAFAOS_COMMIT` the git commit the kernel is built from with `-dirty` appended if the tree has
+//! uncommitted changes, "unknown" if git isn't avalible or the kernel isn't built from a git checkout
+//! `SAFAOS_BUILD_TIME` the time the build script ran in UTC or `SOURCE_DATE_EPOCH` if it is set for
+//! a reproducible build
+//! `SAFAOS_RUSTC_VERSION` the output of `rustc --version`
+//! `SAFAOS_FEATURES` the enabled cargo features separated by spaces
+//! `SAFAOS_PROFILE` debug or release
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// runs `program` with `args` and returns it's trimmed stdout if it succeeded
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_string())
+}
+
+fn commit() -> String {
+    let Some(commit) = run("git", &["rev-parse", "--short", "HEAD"]).filter(|c| !c.is_empty())
+    else {
+        return String::from("unknown");
+    };
+
+    match run("git", &["status", "--porcelain", "--untracked-files=no"]) {
+        Some(status) if !status.is_empty() => format!("{commit}-dirty"),
+        _ => commit,
+    }
+}
+
+/// formats `secs` since the unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+fn format_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn build_time() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs())
+        });
+    format_time(secs)
+}
+
+fn features() -> String {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(name, _)| {
+            let feature = name.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+
+    if features.is_empty() {
+        String::from("none")
+    } else {
+        features.join(" ")
+    }
+}
+
 fn main() {
     println!("cargo:rustc-link-arg=-Tkernel/linker.ld");
     println!("cargo:rerun-if-changed=kernel/linker.ld");
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let rustc_version = run(&rustc, &["--version"]).unwrap_or_else(|| String::from("unknown"));
+
+    println!("cargo:rustc-env=SAFAOS_COMMIT={}", commit());
+    println!("cargo:rustc-env=SAFAOS_BUILD_TIME={}", build_time());
+    println!("cargo:rustc-env=SAFAOS_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=SAFAOS_FEATURES={}", features());
+    println!(
+        "cargo:rustc-env=SAFAOS_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+
+    // HEAD changes when switching branches and the refs when committing
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
 }
@@ -5,10 +5,37 @@ pub mod x86_64;
 pub use x86_64::threading;
 
 #[cfg(target_arch = "x86_64")]
-pub use x86_64::{init_phase1, init_phase2};
+pub use x86_64::{
+    halt_all, init_phase1, init_phase2, msi_message, msi_message_to, nx_enabled, pci_ecam_regions,
+    without_interrupts, PCI_INTERRUPT_VECTOR,
+};
 
 #[cfg(target_arch = "x86_64")]
 pub use x86_64::power;
 
+#[cfg(all(target_arch = "x86_64", feature = "test"))]
+pub use x86_64::qemu;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::cpu;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::idle;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::profiler;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::random;
+
 #[cfg(target_arch = "x86_64")]
 pub use x86_64::serial;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::tlb;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::syscalls;
+
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::usercopy;
@@ -1,6 +1,6 @@
 use core::mem::offset_of;
 
-use crate::{arch::x86_64::inw, hddm, serial, RSDP_ADDR};
+use crate::{arch::x86_64::inw, debug, error, hddm, info, serial, warn, RSDP_ADDR};
 
 use super::outb;
 
@@ -48,6 +48,27 @@ pub struct ACPIHeader {
     creator_revision: u32,
 }
 
+impl ACPIHeader {
+    /// checks the checksum of the whole table self is the header of
+    pub fn vaildate(&self) -> bool {
+        let byte_array = self as *const ACPIHeader as *const u8;
+        let bytes = unsafe { core::slice::from_raw_parts(byte_array, self.len as usize) };
+
+        bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+    }
+
+    /// returns the bytes of the table self is the header of, excluding the header
+    /// empty if the table is shorter than it's header
+    pub fn body(&self) -> &[u8] {
+        let Some(len) = (self.len as usize).checked_sub(size_of::<ACPIHeader>()) else {
+            return &[];
+        };
+
+        let byte_array = self as *const ACPIHeader as *const u8;
+        unsafe { core::slice::from_raw_parts(byte_array.add(size_of::<ACPIHeader>()), len) }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct RSDT {
@@ -167,6 +188,91 @@ pub struct MADTRecord {
     pub length: u8,
 }
 
+/// MADT record of type 0, a processor and it's local apic
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct MADTLocalApic {
+    _header: MADTRecord,
+    pub processor_id: u8,
+    pub apic_id: u8,
+    pub flags: u32,
+}
+
+impl MADTLocalApic {
+    /// the processor is enabled or can be enabled
+    pub fn usable(&self) -> bool {
+        self.flags & 0b11 != 0
+    }
+}
+
+pub struct MADTRecordIter<'a> {
+    madt: &'a MADT,
+    offset: usize,
+}
+
+impl<'a> Iterator for MADTRecordIter<'a> {
+    type Item = &'a MADTRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.madt.header.len as usize;
+        if self.offset + size_of::<MADTRecord>() > len {
+            return None;
+        }
+
+        let record =
+            unsafe { &*((self.madt as *const MADT).byte_add(self.offset) as *const MADTRecord) };
+
+        // a corrupted record would loop forever
+        if record.length == 0 {
+            return None;
+        }
+
+        self.offset += record.length as usize;
+        Some(record)
+    }
+}
+
+/// the PCI express memory mapped configuration space table
+#[allow(clippy::upper_case_acronyms)]
+#[repr(C, packed)]
+#[derive(Debug)]
+pub struct MCFG {
+    pub header: ACPIHeader,
+    _reserved: u64,
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct MCFGEntry {
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    _reserved: u32,
+}
+
+impl MCFG {
+    pub fn get(ptsd: &dyn PTSD) -> Option<&MCFG> {
+        unsafe {
+            ptsd.get_entry_of_signatrue(*b"MCFG")
+                .map(|ptr| &*(ptr as *const MCFG))
+        }
+    }
+
+    /// returns the ECAM regions described by self, empty if the table is too short to have any
+    pub fn entries(&self) -> &[MCFGEntry] {
+        let Some(len) = (self.header.len as usize).checked_sub(size_of::<MCFG>()) else {
+            return &[];
+        };
+        let len = len / size_of::<MCFGEntry>();
+
+        unsafe {
+            let ptr = (self as *const MCFG).byte_add(size_of::<MCFG>()) as *const MCFGEntry;
+            core::slice::from_raw_parts(ptr, len)
+        }
+    }
+}
+
 // any sdt
 pub trait SDT {
     fn header(&self) -> &ACPIHeader;
@@ -175,29 +281,43 @@ pub trait SDT {
         self.header().len
     }
 
-    /// returns the address of element number n and it's size
-    unsafe fn nth(&self, n: usize) -> (usize, usize);
+    /// returns the address of element number n and it's offset, None if there is no such element
+    unsafe fn nth(&self, n: usize) -> Option<(usize, usize)>;
 }
 
 // RSDT and RSDT
 // stands for Parent Table of System Descriptors (yes it gave me ptsd)
 pub trait PTSD: SDT {
-    // returns (ptr, offset)
-    // offset can be used to iter
-    // offset is the offset starting from the first byte of Self
+    /// returns the first table with the signatrue `signatrue` and a vaild checksum
+    /// tables with a bad checksum are skipped and logged
     unsafe fn get_entry_of_signatrue(&self, signatrue: [u8; 4]) -> Option<*const ACPIHeader> {
         for i in 0..(self.count()) {
-            let item = self.nth(i).0 as *const ACPIHeader;
-            if (*item).signatrue == signatrue {
+            let Some((addr, _)) = self.nth(i) else {
+                break;
+            };
+
+            let item = addr as *const ACPIHeader;
+            if (*item).signatrue != signatrue {
+                continue;
+            }
+
+            if (*item).vaildate() {
                 return Some(item);
             }
+
+            error!(
+                RSDT,
+                "skipping the {} table at {:#x}, bad checksum",
+                core::str::from_utf8(&signatrue).unwrap_or("????"),
+                addr
+            );
         }
         None
     }
 
     // table item count
     fn count(&self) -> usize {
-        (self.len() as usize - size_of::<ACPIHeader>()) / 4
+        (self.len() as usize).saturating_sub(size_of::<ACPIHeader>()) / 4
     }
 }
 
@@ -206,11 +326,15 @@ impl SDT for RSDT {
         &self.header
     }
 
-    unsafe fn nth(&self, n: usize) -> (usize, usize) {
+    unsafe fn nth(&self, n: usize) -> Option<(usize, usize)> {
+        if n >= self.count() {
+            return None;
+        }
+
         let addr = *self.table.as_ptr().add(n) as usize;
         let addr = addr | hddm();
 
-        (addr, 0)
+        Some((addr, 0))
     }
 }
 
@@ -219,7 +343,11 @@ impl SDT for XSDT {
         &self.header
     }
 
-    unsafe fn nth(&self, n: usize) -> (usize, usize) {
+    unsafe fn nth(&self, n: usize) -> Option<(usize, usize)> {
+        if n >= self.count() {
+            return None;
+        }
+
         let this = self as *const Self;
 
         let offset = offset_of!(XSDT, table);
@@ -229,11 +357,15 @@ impl SDT for XSDT {
         let addr = core::ptr::read_unaligned(addr) as usize;
         let addr = addr | hddm();
 
-        (addr, 0)
+        Some((addr, 0))
     }
 }
 
-impl PTSD for XSDT {}
+impl PTSD for XSDT {
+    fn count(&self) -> usize {
+        (self.len() as usize).saturating_sub(size_of::<ACPIHeader>()) / 8
+    }
+}
 impl PTSD for RSDT {}
 
 impl SDT for FADT {
@@ -241,66 +373,136 @@ impl SDT for FADT {
         &self.header
     }
 
-    unsafe fn nth(&self, _: usize) -> (usize, usize) {
-        panic!("FADT SDT doesn't support nth!")
+    /// the FADT has no elements
+    unsafe fn nth(&self, _: usize) -> Option<(usize, usize)> {
+        None
     }
 }
 
 impl FADT {
-    pub fn get(ptsd: &dyn PTSD) -> &FADT {
-        unsafe { &*(ptsd.get_entry_of_signatrue(*b"FACP").unwrap() as *const FADT) }
+    /// None if there is no FADT or it's checksum is bad
+    pub fn get(ptsd: &dyn PTSD) -> Option<&FADT> {
+        unsafe {
+            ptsd.get_entry_of_signatrue(*b"FACP")
+                .map(|ptr| &*(ptr as *const FADT))
+        }
     }
-}
 
-impl SDT for MADT {
-    fn header(&self) -> &ACPIHeader {
-        &self.header
+    /// returns wether or not the table is long enough to have every byte before `end`
+    /// ACPI 1.0 FADTs end after `flags`, the fields after it (the reset register and the extended
+    /// `x_` fields) must only be read if this returns true for their end
+    fn has_until(&self, end: usize) -> bool {
+        self.header.len as usize >= end
     }
 
-    unsafe fn nth(&self, n: usize) -> (usize, usize) {
-        let addr = self as *const Self;
+    /// the reset register is supported
+    pub fn reset_supported(&self) -> bool {
+        self.has_until(offset_of!(FADT, reset_value) + size_of::<u8>())
+            && self.flags & (1 << 10) != 0
+    }
 
-        if n == 0 {
-            let base = (addr).byte_add(size_of::<MADT>());
-            return (base as usize, base as usize - addr as usize);
-        }
+    /// returns the PM1 control blocks io ports, prefers the extended blocks if avalible
+    pub fn pm1_cnt_blks(&self) -> (u16, Option<u16>) {
+        let io_port = |blk: GenericAddressStructure| {
+            (blk.address != 0 && blk.address_space == 1).then_some(blk.address as u16)
+        };
 
-        let base = self.nth(0).0;
-        let mut record = base + (*(base as *const MADTRecord)).length as usize;
+        let (x_pm1a, x_pm1b) = if self
+            .has_until(offset_of!(FADT, x_pm1b_cnt_blk) + size_of::<GenericAddressStructure>())
+        {
+            (io_port(self.x_pm1a_cnt_blk), io_port(self.x_pm1b_cnt_blk))
+        } else {
+            (None, None)
+        };
 
-        for _ in 1..n - 1 {
-            let next_record = record as *const MADTRecord;
-            let len = (*next_record).length;
-            record += len as usize;
+        let pm1a = x_pm1a.unwrap_or(self.pm1a_cnt_blk as u16);
+        let pm1b = x_pm1b.unwrap_or(self.pm1b_cnt_blk as u16);
+
+        (pm1a, (pm1b != 0).then_some(pm1b))
+    }
+
+    pub fn dsdt(&self) -> &ACPIHeader {
+        let has_x_dsdt = self.has_until(offset_of!(FADT, x_dsdt) + size_of::<u64>());
+        let addr = if has_x_dsdt && self.x_dsdt != 0 {
+            self.x_dsdt as usize
+        } else {
+            self.dsdt as usize
+        };
+
+        unsafe { &*((addr | hddm()) as *const ACPIHeader) }
+    }
+
+    /// finds the \_S5_ package in the DSDT and returns the SLP_TYPa and SLP_TYPb values
+    /// already shifted into place for the PM1 control blocks
+    pub fn s5_sleep_types(&self) -> Option<(u16, u16)> {
+        let body = self.dsdt().body();
+        let s5 = body.windows(4).position(|window| window == b"_S5_")?;
+
+        // must be a NameOp (optionally followed by the root char) then a PackageOp
+        let is_name = (s5 >= 1 && body[s5 - 1] == 0x08)
+            || (s5 >= 2 && body[s5 - 2] == 0x08 && body[s5 - 1] == b'\\');
+        if !is_name || *body.get(s5 + 4)? != 0x12 {
+            return None;
         }
 
-        (record, record - addr as usize)
+        // skips the PkgLength and the NumElements
+        let mut at = s5 + 5;
+        at += ((*body.get(at)? & 0xC0) >> 6) as usize + 2;
+
+        let mut next = || {
+            // BytePrefix
+            if *body.get(at)? == 0x0A {
+                at += 1;
+            }
+            let value = *body.get(at)? as u16;
+            at += 1;
+            Some(value << 10)
+        };
+
+        Some((next()?, next()?))
     }
 }
 
-impl MADT {
-    pub unsafe fn get_record_of_type(&self, ty: u8) -> Option<*const MADTRecord> {
-        let len = self.header.len;
-        let mut current_offset = 0;
-        let mut i = 0;
+impl SDT for MADT {
+    fn header(&self) -> &ACPIHeader {
+        &self.header
+    }
 
-        while current_offset <= len as usize {
-            let (ptr, offset) = self.nth(i);
-            let ptr = ptr as *const MADTRecord;
+    unsafe fn nth(&self, n: usize) -> Option<(usize, usize)> {
+        let record = self.records().nth(n)? as *const MADTRecord;
+        let addr = self as *const Self;
 
-            if (*ptr).entry_type == ty {
-                return Some(ptr);
-            }
+        Some((record as usize, record as usize - addr as usize))
+    }
+}
 
-            i += 1;
-            current_offset = offset;
+impl MADT {
+    pub fn records(&self) -> MADTRecordIter {
+        MADTRecordIter {
+            madt: self,
+            offset: size_of::<MADT>(),
         }
+    }
 
-        None
+    pub unsafe fn get_record_of_type(&self, ty: u8) -> Option<*const MADTRecord> {
+        self.records()
+            .find(|record| record.entry_type == ty)
+            .map(|record| record as *const MADTRecord)
     }
 
-    pub fn get(ptsd: &dyn PTSD) -> &MADT {
-        unsafe { &*(ptsd.get_entry_of_signatrue(*b"APIC").unwrap() as *const MADT) }
+    /// returns an iterator over the processors local apics
+    pub fn local_apics(&self) -> impl Iterator<Item = &MADTLocalApic> {
+        self.records()
+            .filter(|record| record.entry_type == 0)
+            .map(|record| unsafe { &*(record as *const MADTRecord as *const MADTLocalApic) })
+    }
+
+    /// None if there is no MADT or it's checksum is bad
+    pub fn get(ptsd: &dyn PTSD) -> Option<&MADT> {
+        unsafe {
+            ptsd.get_entry_of_signatrue(*b"APIC")
+                .map(|ptr| &*(ptr as *const MADT))
+        }
     }
 }
 
@@ -308,9 +510,7 @@ fn get_rsdp() -> RSDPDesc {
     let addr = *RSDP_ADDR | hddm();
     let ptr = addr as *mut RSDPDesc;
 
-    let desc = unsafe { *ptr };
-    assert!(desc.vaildate());
-    desc
+    unsafe { *ptr }
 }
 
 pub fn get_sdt() -> &'static dyn PTSD {
@@ -329,14 +529,62 @@ pub fn get_sdt() -> &'static dyn PTSD {
     unsafe { &*rsdt_ptr }
 }
 
+/// logs the tables we use that are missing or corrupted and what was discovered
+/// the tables with a bad checksum are skipped when looked up, see `PTSD::get_entry_of_signatrue`
+pub fn init() {
+    // there is nothing to fall back to for these
+    if !get_rsdp().vaildate() {
+        warn!(RSDT, "the RSDP has a bad checksum, using it anyway");
+    }
+
+    let sdt = get_sdt();
+    if !sdt.header().vaildate() {
+        warn!(RSDT, "the RSDT/XSDT has a bad checksum, using it anyway");
+    }
+
+    if FADT::get(sdt).is_none() {
+        warn!(
+            FADT,
+            "no vaild FADT, shutting down and rebooting may not work"
+        );
+    }
+
+    match MADT::get(sdt) {
+        Some(madt) => debug!(
+            MADT,
+            "found {} processors",
+            madt.local_apics().filter(|apic| apic.usable()).count()
+        ),
+        None => warn!(
+            MADT,
+            "no vaild MADT, the IO APIC and the other processors can't be found"
+        ),
+    }
+
+    if let Some(mcfg) = MCFG::get(sdt) {
+        for entry in mcfg.entries() {
+            let (base, segment) = (entry.base_address, entry.segment_group);
+            debug!(
+                MCFG,
+                "ECAM segment {} at {:#x} buses {}..={}",
+                segment,
+                base,
+                entry.start_bus,
+                entry.end_bus
+            );
+        }
+    }
+}
+
 /// enable the acpi if not already enabled
 pub fn enable_acpi(fadt: &FADT) {
     if !(fadt.smi_cmd == 0
         || ((fadt.acpi_enable == fadt.acpi_disable) && fadt.acpi_disable == 0)
         || inw(fadt.pm1a_cnt_blk as u16) & 1 == 1)
     {
-        serial!(
-            "enabling the acpi... smi: 0x{:X}, enable: 0x{:X}\n",
+        info!(
+            FADT,
+            "enabling the acpi... smi: 0x{:X}, enable: 0x{:X}",
             fadt.smi_cmd as u16,
             fadt.acpi_enable
         );
@@ -0,0 +1,311 @@
+//! cpus managment
+//! only the BSP is started for now, other cpus are discovered from the MADT and added as offline,
+//! there is no way to bring them online until the kernel can start APs
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use alloc::{format, string::String, vec::Vec};
+use core::arch::x86_64::__cpuid;
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+use crate::utils::errors::ErrorStatus;
+
+use super::{
+    acpi::{self, MADT},
+    cycles_frequency,
+    interrupts::apic::{get_local_apic_addr, get_local_apic_reg},
+    PCI_INTERRUPT_VECTOR,
+};
+
+/// the interrupt vectors counted per cpu and their names, see `count_irq`
+pub const IRQS: [(u8, &str); 3] = [
+    (TIMER_INTERRUPT_VECTOR, "timer"),
+    (0x21, "keyboard"),
+    (PCI_INTERRUPT_VECTOR, "pci"),
+];
+const TIMER_INTERRUPT_VECTOR: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CPUState {
+    Online,
+    /// never started, doesn't run any processes
+    Offline,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
+pub struct CPU {
+    pub id: usize,
+    pub apic_id: u8,
+    pub state: CPUState,
+    /// the bootstrap processor, cannot be offlined
+    pub bsp: bool,
+    /// tsc cycles spent waiting in the idle loop
+    pub idle_cycles: AtomicU64,
+    /// how many times the idle loop waited for an interrupt
+    pub idle_entries: AtomicU64,
+    /// how many times the scheduler switched to another process on this cpu
+    pub context_switches: AtomicU64,
+    /// how many times each of `IRQS` was raised on this cpu
+    pub irqs: [AtomicU64; IRQS.len()],
+    /// the tsc value the last time the scheduler ran on this cpu, 0 if it never did,
+    /// see `threading::watchdog`
+    pub heartbeat: AtomicU64,
+}
+
+impl CPU {
+    pub const fn new(id: usize, apic_id: u8, state: CPUState, bsp: bool) -> Self {
+        Self {
+            id,
+            apic_id,
+            state,
+            bsp,
+            idle_cycles: AtomicU64::new(0),
+            idle_entries: AtomicU64::new(0),
+            context_switches: AtomicU64::new(0),
+            irqs: [const { AtomicU64::new(0) }; IRQS.len()],
+            heartbeat: AtomicU64::new(0),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref CPUS: RwLock<Vec<CPU>> = RwLock::new(Vec::new());
+}
+
+/// returns the local apic id of the current cpu
+pub fn current_apic_id() -> u8 {
+    let local_apic_addr = get_local_apic_addr();
+    unsafe { *(get_local_apic_reg(local_apic_addr, 0x20) as *const u32) >> 24 }
+        .try_into()
+        .unwrap()
+}
+
+/// adds the processors discovered in the MADT to the cpus list, the current cpu (the BSP) is the
+/// only one online
+pub fn init() {
+    let bsp_apic_id = current_apic_id();
+    let madt = MADT::get(acpi::get_sdt());
+    let mut cpus = CPUS.write();
+
+    cpus.push(CPU::new(0, bsp_apic_id, CPUState::Online, true));
+
+    for local_apic in madt.into_iter().flat_map(MADT::local_apics) {
+        if !local_apic.usable() || local_apic.apic_id == bsp_apic_id {
+            continue;
+        }
+
+        let id = cpus.len();
+        cpus.push(CPU::new(id, local_apic.apic_id, CPUState::Offline, false));
+    }
+}
+
+/// returns the id of the current cpu, None if the cpus weren't discovered yet or the list is being
+/// changed
+pub fn current_id() -> Option<usize> {
+    let cpus = CPUS.try_read()?;
+    if cpus.is_empty() {
+        return None;
+    }
+
+    let apic_id = current_apic_id();
+    cpus.iter()
+        .find(|cpu| cpu.apic_id == apic_id)
+        .map(|cpu| cpu.id)
+}
+
+/// like `with_current` but does nothing if the cpus list is being changed or the current cpu isn't
+/// registered yet, for use in interrupt handlers
+fn try_with_current(then: impl FnOnce(&CPU)) {
+    let Some(cpus) = CPUS.try_read() else {
+        return;
+    };
+
+    let apic_id = current_apic_id();
+    if let Some(cpu) = cpus.iter().find(|cpu| cpu.apic_id == apic_id) {
+        then(cpu)
+    }
+}
+
+/// counts an interrupt on `vector` for the current cpu, vectors that aren't in `IRQS` are ignored
+pub fn count_irq(vector: u8) {
+    let Some(index) = IRQS.iter().position(|(irq, _)| *irq == vector) else {
+        return;
+    };
+
+    try_with_current(|cpu| {
+        cpu.irqs[index].fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// counts a timer interrupt for the current cpu, yields raise the same vector but aren't counted
+pub fn count_timer_irq() {
+    count_irq(TIMER_INTERRUPT_VECTOR);
+}
+
+/// counts a switch to another process for the current cpu
+pub fn count_context_switch() {
+    try_with_current(|cpu| {
+        cpu.context_switches.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// sets the heartbeat of the current cpu to now
+pub fn touch_heartbeat() {
+    try_with_current(|cpu| cpu.heartbeat.store(super::cycles(), Ordering::Relaxed));
+}
+
+/// returns the id and heartbeat of the current cpu, None if it can't be found without spinning
+pub fn heartbeat() -> Option<(usize, u64)> {
+    let mut results = None;
+    try_with_current(|cpu| results = Some((cpu.id, cpu.heartbeat.load(Ordering::Relaxed))));
+    results
+}
+
+/// the base and max frequency in MHz reported by CPUID leaf 0x16, falling back to the TSC frequency
+/// for the base frequency, None if the cpu reports neither
+fn frequency() -> Option<(u64, Option<u64>)> {
+    let max_leaf = unsafe { __cpuid(0).eax };
+    let leaf = (max_leaf >= 0x16).then(|| unsafe { __cpuid(0x16) });
+
+    match leaf {
+        Some(leaf) if leaf.eax != 0 => {
+            Some((leaf.eax as u64, (leaf.ebx != 0).then_some(leaf.ebx as u64)))
+        }
+        _ => cycles_frequency().map(|hz| (hz / 1_000_000, None)),
+    }
+}
+
+/// executes `then` on the current cpu
+pub fn with_current<T, R>(then: T) -> R
+where
+    T: FnOnce(&CPU) -> R,
+{
+    let apic_id = current_apic_id();
+    let cpus = CPUS.read();
+    let cpu = cpus
+        .iter()
+        .find(|cpu| cpu.apic_id == apic_id)
+        .expect("current cpu is not registered");
+
+    then(cpu)
+}
+
+/// requests cpu `id` to be offline, processes can't be migrated away from a cpu yet so this only
+/// succeeds if it already is, the BSP handles the timer and the ioapic interrupts and can't be offlined
+/// at all (ErrorStatus::Busy) and any other online cpu fails with ErrorStatus::OperationNotSupported
+pub fn offline(id: usize) -> Result<(), ErrorStatus> {
+    let cpus = CPUS.read();
+    let cpu = cpus.get(id).ok_or(ErrorStatus::InvaildResource)?;
+
+    match cpu.state {
+        CPUState::Offline => Ok(()),
+        CPUState::Online if cpu.bsp => Err(ErrorStatus::Busy),
+        CPUState::Online => Err(ErrorStatus::OperationNotSupported),
+    }
+}
+
+/// requests cpu `id` to be online, APs can't be started yet so this only succeeds if it already is
+/// and fails with ErrorStatus::OperationNotSupported otherwise
+pub fn online(id: usize) -> Result<(), ErrorStatus> {
+    let cpus = CPUS.read();
+    let cpu = cpus.get(id).ok_or(ErrorStatus::InvaildResource)?;
+
+    match cpu.state {
+        CPUState::Online => Ok(()),
+        CPUState::Offline => Err(ErrorStatus::OperationNotSupported),
+    }
+}
+
+/// parks the current cpu forever
+pub fn park() -> ! {
+    loop {
+        unsafe { core::arch::asm!("cli; hlt") }
+    }
+}
+
+/// generates the content of proc:/cpuinfo
+pub fn cpuinfo() -> String {
+    let mut results = String::new();
+    let frequency = match frequency() {
+        Some((base, Some(max))) => format!("{base} MHz (max {max} MHz)"),
+        Some((base, None)) => format!("{base} MHz"),
+        None => String::from("unknown"),
+    };
+
+    for cpu in CPUS.read().iter() {
+        let state = match cpu.state {
+            CPUState::Online => "online",
+            CPUState::Offline => "offline",
+        };
+
+        _ = writeln!(results, "cpu {}:", cpu.id);
+        _ = writeln!(results, "  apic id: {}", cpu.apic_id);
+        _ = writeln!(results, "  state: {}", state);
+        _ = writeln!(results, "  bsp: {}", cpu.bsp);
+        _ = writeln!(results, "  frequency: {}", frequency);
+
+        let idle_cycles = cpu.idle_cycles.load(Ordering::Relaxed);
+        let idle_entries = cpu.idle_entries.load(Ordering::Relaxed);
+        match cycles_frequency() {
+            Some(hz) => {
+                _ = writeln!(
+                    results,
+                    "  idle: {} ms, {} cycles, {} entries",
+                    idle_cycles * 1000 / hz,
+                    idle_cycles,
+                    idle_entries
+                )
+            }
+            None => {
+                _ = writeln!(
+                    results,
+                    "  idle: {} cycles, {} entries",
+                    idle_cycles, idle_entries
+                )
+            }
+        }
+
+        _ = writeln!(
+            results,
+            "  context switches: {}",
+            cpu.context_switches.load(Ordering::Relaxed)
+        );
+
+        let irqs: Vec<String> = IRQS
+            .iter()
+            .zip(&cpu.irqs)
+            .map(|((vector, name), count)| {
+                format!("{vector:#x} {name} {}", count.load(Ordering::Relaxed))
+            })
+            .collect();
+        _ = writeln!(results, "  irqs: {}", irqs.join(", "));
+    }
+
+    results
+}
+
+/// generates the content of proc:/interrupts, how many times each of `IRQS` was raised on each cpu
+/// followed by the PCI irqs and the cpus they interrupt
+pub fn interrupts_info() -> String {
+    let mut results = String::new();
+    let cpus = CPUS.read();
+
+    let ids: Vec<String> = cpus.iter().map(|cpu| format!("{}", cpu.id)).collect();
+    _ = writeln!(results, "cpus: {}", ids.join(" "));
+
+    for (index, (vector, name)) in IRQS.iter().enumerate() {
+        let counts: Vec<String> = cpus
+            .iter()
+            .map(|cpu| format!("{}", cpu.irqs[index].load(Ordering::Relaxed)))
+            .collect();
+        _ = writeln!(results, "{vector:#x} {name}: {}", counts.join(" "));
+    }
+    drop(cpus);
+
+    results.push_str(&crate::drivers::pci::irqs_info());
+    results
+}
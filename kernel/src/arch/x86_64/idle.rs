@@ -0,0 +1,64 @@
+//! the idle governor, ran by Eve once it has nothing else to do
+//! also polls the drivers in `crate::drivers::poll`
+//! waits for interrupts in the lowest power state avalible (MWAIT if supported otherwise HLT)
+//! and masks the scheduler tick while Eve is the only process
+//! this is only tick masking, the apic timer is never reprogrammed for the next timer deadline (see
+//! `threading::timer`), as long as any other process exists the tick keeps firing periodically even if
+//! all of them are asleep
+use core::{
+    arch::{asm, x86_64::__cpuid},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{drivers::poll, logging, threading};
+
+use super::{cpu, cycles, interrupts::apic};
+
+/// the address monitored by MWAIT, nothing writes to it, we only wake up on interrupts
+static MONITOR_LINE: AtomicU64 = AtomicU64::new(0);
+
+/// wether or not the cpu supports MONITOR/MWAIT
+fn has_mwait() -> bool {
+    unsafe { __cpuid(1).ecx & (1 << 3) != 0 }
+}
+
+/// enables interrupts and waits for the next one
+#[inline(always)]
+fn wait_for_interrupt(mwait: bool) {
+    unsafe {
+        if mwait {
+            asm!("monitor", in("rax") MONITOR_LINE.as_ptr(), in("ecx") 0, in("edx") 0, options(nostack));
+            // sti only takes effect after the next insturaction so we cannot miss an interrupt
+            asm!("sti; mwait", in("eax") 0, in("ecx") 0, options(nostack));
+        } else {
+            asm!("sti; hlt", options(nostack));
+        }
+    }
+}
+
+/// the idle loop, polls the drivers that needs polling then waits for an interrupt
+/// the scheduler tick is masked while Eve is the only process and no driver needs periodic polling,
+/// with no other processes there are no timers that could expire, it is unmasked as soon as something
+/// else gets spawned
+pub fn idle_loop() -> ! {
+    let mwait = has_mwait();
+
+    loop {
+        poll::poll();
+        logging::channel::try_flush();
+
+        let masked = threading::pcount() <= 1 && !poll::needs_tick();
+        apic::set_timer_masked(masked);
+
+        let start = cycles();
+        wait_for_interrupt(mwait);
+        let end = cycles();
+
+        cpu::with_current(|cpu| {
+            cpu.idle_cycles.fetch_add(end - start, Ordering::Relaxed);
+            cpu.idle_entries.fetch_add(1, Ordering::Relaxed);
+            // the tick may have been masked while idle, an idle cpu isn't stuck
+            cpu.heartbeat.store(end, Ordering::Relaxed);
+        });
+    }
+}
@@ -46,6 +46,14 @@ pub fn send_eoi() {
     }
 }
 
+/// the id of the local apic of the current cpu
+pub fn local_apic_id() -> u8 {
+    let id = unsafe {
+        core::ptr::read_volatile(get_local_apic_reg(get_local_apic_addr(), 0x20) as *const u32)
+    };
+    (id >> 24) as u8
+}
+
 #[repr(C, packed)]
 #[derive(Debug, Clone)]
 pub struct MADTIOApic {
@@ -142,6 +150,18 @@ fn enable_apic_timer(local_apic_addr: VirtAddr) {
     }
 }
 
+/// masks or unmasks the local apic timer interrupt
+pub fn set_timer_masked(masked: bool) {
+    let lvt = get_local_apic_reg(get_local_apic_addr(), 0x320) as *mut u32;
+    let mask = (LVTEntryFlags::DISABLED.bits() as u32) << 8;
+
+    unsafe {
+        let value = core::ptr::read_volatile(lvt);
+        let value = if masked { value | mask } else { value & !mask };
+        core::ptr::write_volatile(lvt, value);
+    }
+}
+
 pub fn enable_apic_interrupts() {
     let local_apic_addr = get_local_apic_addr();
     let sivr = get_local_apic_reg(local_apic_addr, 0xF0) as *mut u32;
@@ -149,7 +169,8 @@ pub fn enable_apic_interrupts() {
     unsafe {
         core::ptr::write_volatile(sivr, 0x1ff);
 
-        let madt = MADT::get(acpi::get_sdt());
+        // the IO APIC can only be found through the MADT
+        let madt = MADT::get(acpi::get_sdt()).expect("no vaild MADT to find the IO APIC in");
         let ioapic_addr = get_io_apic_addr(madt);
         let apic_id = *(get_local_apic_reg(local_apic_addr, 0x20) as *const u8);
         enable_apic_timer(local_apic_addr);
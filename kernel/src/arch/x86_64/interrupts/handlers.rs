@@ -7,7 +7,7 @@ use super::idt::{GateDescriptor, IDTT};
 use super::{InterruptFrame, TrapFrame};
 
 use crate::arch::x86_64::interrupts::apic::send_eoi;
-use crate::arch::x86_64::{inb, threading};
+use crate::arch::x86_64::{cpu, inb, profiler, threading, usercopy, PCI_INTERRUPT_VECTOR};
 use crate::{drivers, serial};
 
 const ATTR_TRAP: u8 = 0xF;
@@ -41,6 +41,7 @@ macro_rules! create_idt {
 lazy_static! {
     pub static ref IDT: IDTT = create_idt!(
         (0, divide_by_zero_handler, ATTR_INT),
+        (2, nmi_handler, ATTR_INT),
         (3, breakpoint_handler, ATTR_INT | ATTR_RING3),
         (6, invaild_opcode, ATTR_INT),
         (8, dobule_fault_handler, ATTR_TRAP, 0),
@@ -49,6 +50,7 @@ lazy_static! {
         (14, page_fault_handler, ATTR_TRAP),
         (0x20, threading::context_switch_stub, ATTR_INT, 1),
         (0x21, keyboard_interrupt_handler, ATTR_INT),
+        (0x22, pci_interrupt_handler, ATTR_INT),
         (0x80, syscall_base, ATTR_INT | ATTR_RING3)
     );
 }
@@ -58,12 +60,23 @@ extern "x86-interrupt" fn divide_by_zero_handler(frame: InterruptFrame) {
     panic!("---- Divide By Zero Exception ----\n{}", frame);
 }
 
+/// NMIs are only raised by the profiler's performance counter, others are ignored since there is nothing
+/// that can be done about them and nothing can be safely locked to report them
+extern "x86-interrupt" fn nmi_handler(frame: InterruptFrame) {
+    usercopy::clac();
+    let rbp: usize;
+    // the handler's frame pointer points at the saved frame pointer of the interrupted code
+    unsafe { asm!("mov {}, [rbp]", out(reg) rbp) }
+    profiler::handle_nmi(&frame, rbp);
+}
+
 extern "x86-interrupt" fn invaild_opcode(frame: InterruptFrame) {
     panic!("---- Invaild OPCODE ----\n{}", frame);
 }
 
 #[no_mangle]
 extern "x86-interrupt" fn breakpoint_handler(frame: InterruptFrame) {
+    usercopy::clac();
     serial!("hi from interrupt, breakpoint!\n{}", frame);
 }
 
@@ -84,26 +97,56 @@ extern "x86-interrupt" fn general_protection_fault_handler(frame: TrapFrame) {
 
 #[no_mangle]
 extern "x86-interrupt" fn page_fault_handler(frame: TrapFrame) {
+    usercopy::clac();
     let cr2: u64;
     unsafe { asm!("mov cr2, {}", out(reg) cr2) }
 
+    // the kernel faulted while accessing user memory in a place that expects it, continue at it's fixup
+    if frame.code_segment & 3 == 0 {
+        if let Some(fixup) = usercopy::fixup(frame.insturaction as usize) {
+            // the frame is the one the cpu pushed so this changes where iretq returns to
+            unsafe {
+                core::ptr::write_volatile(
+                    core::ptr::addr_of!(frame.insturaction).cast_mut(),
+                    fixup as u64,
+                )
+            };
+            return;
+        }
+    }
+
+    #[cfg(feature = "debug-poison")]
+    if let Some(mapping) = crate::memory::poison::guard_page_of(cr2 as usize) {
+        let (pid, name) = crate::threading::expose::try_current_ident()
+            .unwrap_or((0, crate::utils::types::Name::new_lossy("??")));
+
+        panic!(
+            "---- Guard Page Hit ----\naddress: {:#x}\n{} overflow in process {} ({})\n{}",
+            cr2, mapping, name, pid, frame
+        )
+    }
+
     panic!("---- Page Fault ----\naddress: {:#x}\n{}", cr2, frame)
 }
 
 #[inline]
 pub fn handle_ps2_keyboard() {
     let key = inb(0x60);
-    // outside of this function the keyboard should only be read from
-    if let Some(encoded) = drivers::keyboard::KEYBOARD
-        .try_write()
-        .map(|mut writer| writer.handle_ps2_set_1(key))
-        .filter(|key| *key != drivers::keyboard::keys::Key::NULL_KEY)
-    {
-        crate::__navi_key_pressed(encoded);
-    }
+    // the key is processed later by the keyboard driver outside of interrupt context
+    drivers::keyboard::push_scancode(key);
 }
 #[no_mangle]
 pub extern "x86-interrupt" fn keyboard_interrupt_handler() {
+    usercopy::clac();
+    cpu::count_irq(0x21);
     handle_ps2_keyboard();
     send_eoi();
 }
+
+#[no_mangle]
+pub extern "x86-interrupt" fn pci_interrupt_handler() {
+    usercopy::clac();
+    cpu::count_irq(PCI_INTERRUPT_VECTOR);
+    drivers::pci::handle_interrupt();
+    send_eoi();
+}
@@ -99,6 +99,16 @@ pub fn read_msr(msr: u32) -> PhysAddr {
     (high as usize) << 32 | (low as usize)
 }
 
+pub fn write_msr(msr: u32, value: usize) {
+    let (low, high) = (value as u32, (value >> 32) as u32);
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") msr, in("eax") low, in("edx") high
+        );
+    }
+}
+
 pub fn init_idt() {
     unsafe {
         asm!("lidt [{}]", in(reg) &*IDTDesc, options(nostack));
@@ -1,17 +1,31 @@
 mod acpi;
+pub mod cpu;
 pub mod gdt;
+pub mod idle;
 pub mod interrupts;
 pub mod power;
+pub mod profiler;
+#[cfg(feature = "test")]
+pub mod qemu;
+pub mod random;
 pub mod serial;
-mod syscalls;
+pub mod syscalls;
 pub mod threading;
+pub mod tlb;
+pub mod usercopy;
 
-use core::arch::asm;
+use alloc::vec::Vec;
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use acpi::{get_sdt, FADT};
+use acpi::{get_sdt, FADT, MCFG};
 use interrupts::{apic, init_idt};
 use serial::init_serial;
 
+use crate::memory::PhysAddr;
+
 use self::gdt::init_gdt;
 
 pub fn inb(port: u16) -> u8 {
@@ -42,6 +56,24 @@ pub fn inw(port: u16) -> u16 {
     value
 }
 
+/// executes `then` with interrupts disabled, restores the interrupt flag afterwards
+#[inline]
+pub fn without_interrupts<T, R>(then: T) -> R
+where
+    T: FnOnce() -> R,
+{
+    let rflags: u64;
+    unsafe { asm!("pushfq; pop {}", out(reg) rflags) };
+    unsafe { asm!("cli") };
+
+    let results = then();
+
+    if rflags & (1 << 9) != 0 {
+        unsafe { asm!("sti") };
+    }
+    results
+}
+
 #[inline]
 pub fn enable_sse() {
     unsafe {
@@ -83,10 +115,142 @@ fn _enable_avx() {
     }
 }
 
+static NX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// enables the no-execute bit in page table entries if the cpu supports it
+fn enable_nx() {
+    const EFER: u32 = 0xC0000080;
+    const EFER_NXE: usize = 1 << 11;
+
+    let supported = unsafe { core::arch::x86_64::__cpuid(0x80000001).edx & (1 << 20) != 0 };
+    if supported {
+        interrupts::write_msr(EFER, interrupts::read_msr(EFER) | EFER_NXE);
+        NX_ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// wether or not SMAP is enabled, `stac` and `clac` are only executed if it is because they are
+/// invaild opcodes otherwise
+#[no_mangle]
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// enables SMEP and SMAP if the cpu supports them
+/// the kernel can never execute user pages and can only access them inside a `usercopy::UserAccess` window
+fn enable_smep_smap() {
+    const CR4_SMEP: usize = 1 << 20;
+    const CR4_SMAP: usize = 1 << 21;
+
+    use core::arch::x86_64::{__cpuid, __cpuid_count};
+    let features = unsafe {
+        if __cpuid(0).eax < 7 {
+            return;
+        }
+        __cpuid_count(7, 0).ebx
+    };
+
+    let mut flags = 0;
+    if features & (1 << 7) != 0 {
+        flags |= CR4_SMEP;
+    }
+
+    if features & (1 << 20) != 0 {
+        flags |= CR4_SMAP;
+        SMAP_ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    unsafe {
+        asm!(
+            "
+            mov rax, cr4
+            or rax, {}
+            mov cr4, rax
+        ",
+            in(reg) flags, out("rax") _,
+            options(nostack)
+        )
+    }
+}
+
+/// returns wether or not the no-execute bit is enabled
+/// if it isn't `EntryFlags::NO_EXECUTE` is a reserved bit and mapping with it faults
+pub fn nx_enabled() -> bool {
+    NX_ENABLED.load(Ordering::Relaxed)
+}
+
+/// returns wether or not SMAP is enabled, see `usercopy::UserAccess`
+pub fn smap_enabled() -> bool {
+    SMAP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// returns the PCI express memory mapped configuration space regions described by the MCFG table
+/// as (physical base address, start bus, end bus), the base address is the address of bus 0
+pub fn pci_ecam_regions() -> Vec<(PhysAddr, u8, u8)> {
+    let Some(mcfg) = MCFG::get(get_sdt()) else {
+        return Vec::new();
+    };
+
+    mcfg.entries()
+        .iter()
+        .map(|entry| {
+            (
+                entry.base_address as PhysAddr,
+                entry.start_bus,
+                entry.end_bus,
+            )
+        })
+        .collect()
+}
+
+/// the interrupt vector PCI devices interrupt with, every handler registered with
+/// `drivers::pci::register_interrupt_handler` is called on it
+pub const PCI_INTERRUPT_VECTOR: u8 = 0x22;
+
+/// returns the address and data of a message signaled interrupt that raises `vector` on the current cpu
+pub fn msi_message(vector: u8) -> (u64, u32) {
+    msi_message_to(vector, interrupts::apic::local_apic_id())
+}
+
+/// like `msi_message` but raises `vector` on the cpu with the local apic id `apic_id`
+pub fn msi_message_to(vector: u8, apic_id: u8) -> (u64, u32) {
+    let address = 0xFEE0_0000 | ((apic_id as u64) << 12);
+    (address, vector as u32)
+}
+
+/// returns the amount of cycles since reset (the TSC), for measuring short intervals
+#[inline(always)]
+pub fn cycles() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+lazy_static::lazy_static! {
+    /// the TSC frequency in Hz reported by CPUID leaf 0x15 (or the base frequency from leaf 0x16),
+    /// None if the cpu reports neither
+    static ref CYCLES_FREQUENCY: Option<u64> = unsafe {
+        use core::arch::x86_64::__cpuid;
+        let max_leaf = __cpuid(0).eax;
+        let tsc = (max_leaf >= 0x15).then(|| __cpuid(0x15));
+        let base_mhz = (max_leaf >= 0x16).then(|| __cpuid(0x16).eax);
+
+        match (tsc, base_mhz) {
+            (Some(tsc), _) if tsc.eax != 0 && tsc.ebx != 0 && tsc.ecx != 0 => {
+                Some(tsc.ecx as u64 * tsc.ebx as u64 / tsc.eax as u64)
+            }
+            (_, Some(mhz)) if mhz != 0 => Some(mhz as u64 * 1_000_000),
+            _ => None,
+        }
+    };
+}
+
+/// returns the frequency of `cycles` in Hz if the cpu reports it
+pub fn cycles_frequency() -> Option<u64> {
+    *CYCLES_FREQUENCY
+}
+
 /// simple init less likely to panic
 /// highly required
 #[inline]
 pub fn init_phase1() {
+    enable_nx();
     init_serial();
     init_gdt();
     init_idt();
@@ -94,7 +258,18 @@ pub fn init_phase1() {
 /// complexer init
 #[inline]
 pub fn init_phase2() {
-    acpi::enable_acpi(FADT::get(get_sdt()));
+    acpi::init();
+    if let Some(fadt) = FADT::get(get_sdt()) {
+        acpi::enable_acpi(fadt);
+    }
     apic::enable_apic_interrupts();
+    cpu::init();
     enable_sse();
+    enable_smep_smap();
+}
+
+/// stops every cpu, used on panic
+/// there is only the BSP running for now so it just parks the current cpu
+pub fn halt_all() -> ! {
+    cpu::park()
 }
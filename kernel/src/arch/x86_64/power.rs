@@ -1,25 +1,27 @@
 use core::arch::asm;
 
-use crate::{println, serial};
+use crate::{hddm, println, warn};
 
 use super::{
     acpi::{self, FADT},
     outb, outw,
 };
 
+/// used if the \_S5_ object couldn't be found in the DSDT
 const SLP_TYP_S5: u16 = 0x1C00;
 const SLP_EN: u16 = 1 << 13;
 
 pub fn shutdown() {
-    let fadt = FADT::get(acpi::get_sdt());
+    super::profiler::dump();
+    if let Some(fadt) = FADT::get(acpi::get_sdt()) {
+        let (slp_typa, slp_typb) = fadt.s5_sleep_types().unwrap_or((SLP_TYP_S5, SLP_TYP_S5));
+        let (pm1a_cnt_blk, pm1b_cnt_blk) = fadt.pm1_cnt_blks();
 
-    let pm1a_cnt_blk = fadt.pm1a_cnt_blk as u16;
-    let shutdown_command = SLP_TYP_S5 | SLP_EN;
-    outw(pm1a_cnt_blk, shutdown_command);
+        outw(pm1a_cnt_blk, slp_typa | SLP_EN);
 
-    if fadt.pm1b_cnt_blk != 0 {
-        let pm1b_cnt_blk = fadt.pm1b_cnt_blk as u16;
-        outw(pm1b_cnt_blk, shutdown_command);
+        if let Some(pm1b_cnt_blk) = pm1b_cnt_blk {
+            outw(pm1b_cnt_blk, slp_typb | SLP_EN);
+        }
     }
 
     // if failed to shutdown shutdown qemu!
@@ -30,10 +32,16 @@ pub fn shutdown() {
 pub fn reboot() {
     unsafe { asm!("cli") };
 
-    let fadt = FADT::get(acpi::get_sdt());
-    match fadt.reset_reg.address_space {
-        1 => outb(fadt.reset_reg.address as u16, fadt.reset_value),
-        _ => serial!("unknown fadt reset_reg? {:#?}\n", fadt.reset_reg),
+    if let Some(fadt) = FADT::get(acpi::get_sdt()).filter(|fadt| fadt.reset_supported()) {
+        let address = fadt.reset_reg.address as usize;
+
+        match fadt.reset_reg.address_space {
+            0 => unsafe {
+                core::ptr::write_volatile((address | hddm()) as *mut u8, fadt.reset_value)
+            },
+            1 => outb(address as u16, fadt.reset_value),
+            _ => warn!(FADT, "unknown fadt reset_reg? {:#?}", fadt.reset_reg),
+        }
     }
 
     // force-reboot because acpi sucks!
@@ -0,0 +1,286 @@
+//! a sampling profiler, samples where the cpu is from a performance counter overflow NMI every
+//! `1 / SAMPLE_HZ` seconds worth of unhalted cycles, falling back to sampling on each scheduler tick if
+//! the cpu has no architectural performance monitoring (qemu without kvm)
+//! samples are kept in a ring buffer and shown in proc:/profile, a line per sample with the id of the
+//! cpu followed by the interrupted rip and the return addresses of the kernel frames it was called from
+//! `cargo run -- profile=<path>` asks the kernel to profile from boot and dump the samples to the
+//! serial when it exits, the runner turns them into folded stacks for flamegraphs
+use core::{
+    arch::x86_64::__cpuid,
+    cell::UnsafeCell,
+    fmt::Write,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+};
+
+use alloc::string::String;
+
+use crate::{
+    serial,
+    threading::{RING0_STACK_END, RING0_STACK_START, STACK_END, STACK_SIZE, STACK_START},
+};
+
+use super::{
+    cpu, cycles_frequency,
+    interrupts::{
+        apic::{get_local_apic_addr, get_local_apic_reg},
+        read_msr, write_msr, InterruptFrame,
+    },
+    threading::CPUStatus,
+};
+
+const SAMPLE_HZ: u64 = 1000;
+/// assumed when the cpu doesn't report the tsc frequency
+const FALLBACK_FREQUENCY: u64 = 3_000_000_000;
+const MAX_SAMPLES: usize = 2048;
+const MAX_FRAMES: usize = 16;
+
+const IA32_PMC0: u32 = 0xC1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+const IA32_PERF_GLOBAL_STATUS: u32 = 0x38E;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38F;
+const IA32_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+
+const UNHALTED_CORE_CYCLES: usize = 0x3C;
+const EVTSEL_USR: usize = 1 << 16;
+const EVTSEL_OS: usize = 1 << 17;
+const EVTSEL_INT: usize = 1 << 20;
+const EVTSEL_EN: usize = 1 << 22;
+
+const LVT_PERFORMANCE_COUNTER: u16 = 0x340;
+const LVT_NMI: u32 = 0b100 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+
+/// printed around the samples in `dump`, the runner looks for them
+const DUMP_BEGIN: &str = "[PROFILE BEGIN]";
+const DUMP_END: &str = "[PROFILE END]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Source {
+    Pmu = 1,
+    Timer = 2,
+}
+
+#[derive(Clone, Copy)]
+struct Sample {
+    cpu: u8,
+    len: u8,
+    frames: [u64; MAX_FRAMES],
+}
+
+impl Sample {
+    const fn empty() -> Self {
+        Self {
+            cpu: 0,
+            len: 0,
+            frames: [0; MAX_FRAMES],
+        }
+    }
+}
+
+/// only written by the sampling interrupt so it doesn't need a lock, which an NMI couldn't take anyways
+/// a sample being written while `profile_info` reads it may be torn
+struct Samples(UnsafeCell<[Sample; MAX_SAMPLES]>);
+unsafe impl Sync for Samples {}
+
+static SAMPLES: Samples = Samples(UnsafeCell::new([Sample::empty(); MAX_SAMPLES]));
+/// the amount of samples ever taken, the next sample is written at `WRITTEN % MAX_SAMPLES`
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// a `Source`, 0 if the profiler never ran
+static SOURCE: AtomicU8 = AtomicU8::new(0);
+
+/// wether or not the cpu has a general purpose counter that can count unhalted core cycles and the
+/// global control and status msrs (architectural performance monitoring version 2)
+fn has_pmu() -> bool {
+    unsafe {
+        if __cpuid(0).eax < 0xA {
+            return false;
+        }
+
+        let leaf = __cpuid(0xA);
+        let version = leaf.eax & 0xFF;
+        let counters = (leaf.eax >> 8) & 0xFF;
+        // set bits in ebx mean the event is *not* available
+        version >= 2 && counters >= 1 && leaf.ebx & 1 == 0
+    }
+}
+
+fn write_lvt(value: u32) {
+    let lvt = get_local_apic_reg(get_local_apic_addr(), LVT_PERFORMANCE_COUNTER) as *mut u32;
+    unsafe { core::ptr::write_volatile(lvt, value) }
+}
+
+/// the value the counter is reset to so it overflows after `1 / SAMPLE_HZ` seconds
+/// writes to the counter are sign extended from 32 bits so the period has to fit in 31 bits
+fn reload_value() -> usize {
+    let period =
+        (cycles_frequency().unwrap_or(FALLBACK_FREQUENCY) / SAMPLE_HZ).min(i32::MAX as u64);
+    (period as i64).wrapping_neg() as usize
+}
+
+/// the end of the stack `rsp` is in, used to bound the frame pointer walk so a function without a
+/// frame pointer can't send us reading unmapped memory
+fn stack_end(rsp: usize) -> usize {
+    if (STACK_START..STACK_END).contains(&rsp) {
+        STACK_END
+    } else if (RING0_STACK_START..RING0_STACK_END).contains(&rsp) {
+        RING0_STACK_END
+    } else {
+        // Eve's boot stack, the memory after it is mapped by the hddm
+        rsp.saturating_add(STACK_SIZE)
+    }
+}
+
+/// records a sample of the interrupted code, kernel frames are walked starting at `rbp`
+fn record(rip: usize, rsp: usize, mut rbp: usize, kernel: bool) {
+    let index = WRITTEN.load(Ordering::Relaxed) as usize % MAX_SAMPLES;
+    let sample = unsafe { &mut (*SAMPLES.0.get())[index] };
+
+    sample.cpu = cpu::current_id().unwrap_or(0) as u8;
+    sample.frames[0] = rip as u64;
+    let mut len = 1;
+
+    if kernel {
+        let end = stack_end(rsp);
+        while len < MAX_FRAMES && rbp >= rsp && rbp % 8 == 0 && rbp + 16 <= end {
+            let frame = rbp as *const usize;
+            let (next, return_addr) = unsafe { (*frame, *frame.add(1)) };
+            if return_addr == 0 {
+                break;
+            }
+
+            sample.frames[len] = return_addr as u64;
+            len += 1;
+
+            if next <= rbp {
+                break;
+            }
+            rbp = next;
+        }
+    }
+
+    sample.len = len as u8;
+    WRITTEN.fetch_add(1, Ordering::Release);
+}
+
+/// starts sampling, from performance counter NMIs if the cpu supports them otherwise from the scheduler
+/// tick, returns where the samples come from
+pub fn start() -> Source {
+    let source = if has_pmu() {
+        write_lvt(LVT_NMI);
+        write_msr(IA32_PERFEVTSEL0, 0);
+        write_msr(IA32_PMC0, reload_value());
+        write_msr(
+            IA32_PERFEVTSEL0,
+            UNHALTED_CORE_CYCLES | EVTSEL_USR | EVTSEL_OS | EVTSEL_INT | EVTSEL_EN,
+        );
+        write_msr(IA32_PERF_GLOBAL_CTRL, read_msr(IA32_PERF_GLOBAL_CTRL) | 1);
+        Source::Pmu
+    } else {
+        Source::Timer
+    };
+
+    SOURCE.store(source as u8, Ordering::Relaxed);
+    RUNNING.store(true, Ordering::Release);
+    source
+}
+
+/// stops sampling, the samples are kept
+pub fn stop() {
+    RUNNING.store(false, Ordering::Release);
+
+    if SOURCE.load(Ordering::Relaxed) == Source::Pmu as u8 {
+        write_msr(IA32_PERFEVTSEL0, 0);
+        write_lvt(LVT_NMI | LVT_MASKED);
+    }
+}
+
+pub fn running() -> bool {
+    RUNNING.load(Ordering::Acquire)
+}
+
+/// the amount of samples ever taken
+pub fn samples() -> u64 {
+    WRITTEN.load(Ordering::Acquire)
+}
+
+/// handles an NMI, returns false if it wasn't raised by the profiler's counter
+/// `rbp` is the frame pointer of the interrupted code
+pub fn handle_nmi(frame: &InterruptFrame, rbp: usize) -> bool {
+    if !running() || SOURCE.load(Ordering::Relaxed) != Source::Pmu as u8 {
+        return false;
+    }
+
+    if read_msr(IA32_PERF_GLOBAL_STATUS) & 1 == 0 {
+        return false;
+    }
+
+    record(
+        frame.insturaction as usize,
+        frame.stack_pointer as usize,
+        rbp,
+        frame.code_segment & 3 == 0,
+    );
+
+    write_msr(IA32_PMC0, reload_value());
+    write_msr(IA32_PERF_GLOBAL_OVF_CTRL, 1);
+    // the lvt entry is masked when the NMI is delivered
+    write_lvt(LVT_NMI);
+    true
+}
+
+/// called on every scheduler tick with what it interrupted, samples it if the profiler fell back to
+/// the scheduler tick
+pub fn tick(context: &CPUStatus) {
+    if !running() || SOURCE.load(Ordering::Relaxed) != Source::Timer as u8 {
+        return;
+    }
+
+    let registers = context.registers();
+    record(
+        registers.rip as usize,
+        registers.rsp as usize,
+        registers.rbp as usize,
+        context.cs & 3 == 0,
+    );
+}
+
+/// generates the content of proc:/profile, a header line followed by the samples from oldest to newest
+pub fn profile_info() -> String {
+    let written = samples();
+    let count = written.min(MAX_SAMPLES as u64);
+    let source = match SOURCE.load(Ordering::Relaxed) {
+        1 => "pmu",
+        2 => "timer",
+        _ => "none",
+    };
+
+    let mut results = String::new();
+    _ = writeln!(
+        results,
+        "# source: {source}, samples: {count}, lost: {}",
+        written - count
+    );
+
+    for i in written - count..written {
+        let sample = unsafe { &(*SAMPLES.0.get())[i as usize % MAX_SAMPLES] };
+        _ = write!(results, "{}", sample.cpu);
+        for frame in &sample.frames[..sample.len as usize] {
+            _ = write!(results, " {frame:#x}");
+        }
+        results.push('\n');
+    }
+
+    results
+}
+
+/// stops the profiler and writes the samples to the serial for the runner, does nothing if it isn't running
+pub fn dump() {
+    if !running() {
+        return;
+    }
+
+    stop();
+    serial!("{DUMP_BEGIN}\n{}{DUMP_END}\n", profile_info());
+}
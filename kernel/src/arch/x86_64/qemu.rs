@@ -0,0 +1,72 @@
+//! qemu's fw_cfg and isa-debug-exit devices, the runner adds a fw_cfg file to ask the kernel to exit
+//! qemu once it is done (`cargo run -- test` and `cargo run -- headless`) and another to ask it to
+//! profile from boot (`cargo run -- profile=<path>`)
+use super::{inb, outb, outw};
+
+const FW_CFG_SELECTOR: u16 = 0x510;
+const FW_CFG_DATA: u16 = 0x511;
+const FW_CFG_SIGNATURE: u16 = 0x00;
+const FW_CFG_FILE_DIR: u16 = 0x19;
+
+/// the fw_cfg file the runner adds when it attached isa-debug-exit
+const DEBUG_EXIT_FILE: &[u8] = b"opt/safaos/debug-exit";
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+/// the fw_cfg file the runner adds to collect a profile, see `profiler`
+const PROFILE_FILE: &[u8] = b"opt/safaos/profile";
+
+/// the tests and the snapshots passed
+pub const EXIT_SUCCESS: u8 = 0;
+pub const EXIT_TESTS_FAILED: u8 = 1;
+pub const EXIT_PANIC: u8 = 2;
+
+fn fw_cfg_read(buffer: &mut [u8]) {
+    for byte in buffer {
+        *byte = inb(FW_CFG_DATA);
+    }
+}
+
+/// returns true if qemu has a fw_cfg file named `name`, false if it doesn't or this isn't qemu
+fn fw_cfg_has_file(name: &[u8]) -> bool {
+    outw(FW_CFG_SELECTOR, FW_CFG_SIGNATURE);
+    let mut signature = [0u8; 4];
+    fw_cfg_read(&mut signature);
+    if signature != *b"QEMU" {
+        return false;
+    }
+
+    outw(FW_CFG_SELECTOR, FW_CFG_FILE_DIR);
+    let mut count = [0u8; 4];
+    fw_cfg_read(&mut count);
+
+    // a big endian size, selector and reserved field followed by a 56 bytes NUL terminated name
+    for _ in 0..u32::from_be_bytes(count) {
+        let mut file = [0u8; 64];
+        fw_cfg_read(&mut file);
+
+        let file_name = &file[8..];
+        let len = file_name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(file_name.len());
+        if &file_name[..len] == name {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// wether or not the runner asked the kernel to profile from boot
+pub fn profile_requested() -> bool {
+    fw_cfg_has_file(PROFILE_FILE)
+}
+
+/// exits qemu with `(code << 1) | 1` as it's exit status if the runner asked for it
+/// does nothing otherwise
+pub fn debug_exit(code: u8) {
+    crate::logging::channel::try_flush();
+    super::profiler::dump();
+    if fw_cfg_has_file(DEBUG_EXIT_FILE) {
+        outb(DEBUG_EXIT_PORT, code);
+    }
+}
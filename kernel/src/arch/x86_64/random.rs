@@ -0,0 +1,27 @@
+//! hardware random numbers with RDRAND, used to seed dev:/urandom
+use core::arch::x86_64::{__cpuid, _rdrand64_step};
+
+use lazy_static::lazy_static;
+
+/// RDRAND can fail if the cpu ran out of entropy for a moment, intel recommends trying 10 times
+const RETRIES: usize = 10;
+
+lazy_static! {
+    /// wether or not the cpu supports RDRAND
+    static ref HAS_RDRAND: bool = unsafe { __cpuid(1).ecx & (1 << 30) != 0 };
+}
+
+/// returns a random number from the cpu, None if it doesn't support RDRAND or it failed
+pub fn hardware_random() -> Option<u64> {
+    if !*HAS_RDRAND {
+        return None;
+    }
+
+    let mut value = 0;
+    for _ in 0..RETRIES {
+        if unsafe { _rdrand64_step(&mut value) } == 1 {
+            return Some(value);
+        }
+    }
+    None
+}
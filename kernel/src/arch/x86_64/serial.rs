@@ -59,6 +59,7 @@ impl Write for Serial {
     }
 }
 
+/// see `crate::logging::channel`
 pub fn _serial(args: fmt::Arguments) {
-    SERIAL.inner.lock().write_fmt(args).unwrap();
+    crate::logging::channel::write(args);
 }
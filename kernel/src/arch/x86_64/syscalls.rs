@@ -24,6 +24,15 @@ pub struct SyscallContext {
     pub rbx: u64,
     pub frame: InterruptFrame,
 }
+/// returns the context the current process was in when it invoked the current syscall
+/// syscalls from ring3 switch to the top of the ring0 stack so the context `syscall_base` pushes is always there
+/// # Safety
+/// must only be called from a syscall invoked by a userspace process
+pub unsafe fn caller_context() -> &'static SyscallContext {
+    let ptr = crate::threading::RING0_STACK_END - size_of::<SyscallContext>();
+    &*(ptr as *const SyscallContext)
+}
+
 global_asm!(
     "
 .section .rodata
@@ -50,6 +59,52 @@ syscall_table:
     .quad syspspawn
     .quad sysshutdown
     .quad sysreboot
+    .quad syscpuctl
+    .quad syspower
+    .quad syssync
+    .quad sysdup
+    .quad sysdup2
+    .quad sysreadv
+    .quad syswritev
+    .quad syscopy_file_range
+    .quad syspoll
+    .quad sysevq_create
+    .quad sysevq_ctl
+    .quad sysevq_wait
+    .quad sysptrace
+    .quad sysbacktrace
+    .quad sysctl
+    .quad syssurface_create
+    .quad syssurface_open
+    .quad syssurface_map
+    .quad syssurface_flush
+    .quad syssurface_present
+    .quad sysenv_get
+    .quad sysenv_set
+    .quad syswait_any
+    .quad sysfchdir
+    .quad sysreaddir
+    .quad sysremove
+    .quad sysrename
+    .quad syspkill
+    .quad sysctlbuf
+    .quad sysuname
+    .quad syssethostname
+    .quad sysreport_abort
+    .quad syswait_status
+    .quad syssleep
+    .quad sysgetuid
+    .quad syssetuid
+    .quad sysbind
+    .quad sysunbind
+    .quad sysmount_bind
+    .quad sysumount
+    .quad sysflock
+    .quad syslink
+    .quad sysget_xattr
+    .quad sysset_xattr
+    .quad syslist_xattrs
+    .quad sysremove_xattr
 syscall_table_end:
 
 SYSCALL_TABLE_INFO:
@@ -58,8 +113,9 @@ SYSCALL_TABLE_INFO:
 .global syscall_base
 
 syscall_base:
+    // unsigned so that negative syscall numbers are unsupported too
     cmp rax, [SYSCALL_TABLE_INFO]
-    jge unsupported
+    jae unsupported
     push rbx
     push rcx
     push rdx
@@ -74,6 +130,11 @@ syscall_base:
     push r13
     push r14
     push r15
+    // userspace can set AC itself, syscalls open their own user access windows where they need them
+    cmp byte ptr [rip + SMAP_ENABLED], 0
+    je 1f
+    clac
+1:
     call [syscall_table + rax * 8]
     pop r15
     pop r14
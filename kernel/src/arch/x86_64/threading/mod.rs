@@ -83,6 +83,30 @@ pub struct CPUStatus {
     xmm0: [u8; 16],
 }
 
+/// the general purpose registers of a process, given to userspace debuggers
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Registers {
+    pub rip: u64,
+    pub rsp: u64,
+    pub rflags: u64,
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+}
+
 impl CPUStatus {
     pub fn at(&self) -> usize {
         self.rip as usize
@@ -91,6 +115,29 @@ impl CPUStatus {
     pub fn stack_at(&self) -> usize {
         self.rsp as usize
     }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            rip: self.rip,
+            rsp: self.rsp,
+            rflags: { self.rflags }.bits(),
+            rax: self.rax,
+            rbx: self.rbx,
+            rcx: self.rcx,
+            rdx: self.rdx,
+            rsi: self.rsi,
+            rdi: self.rdi,
+            rbp: self.rbp,
+            r8: self.r8,
+            r9: self.r9,
+            r10: self.r10,
+            r11: self.r11,
+            r12: self.r12,
+            r13: self.r13,
+            r14: self.r14,
+            r15: self.r15,
+        }
+    }
 }
 
 global_asm!(
@@ -153,6 +200,11 @@ restore_cpu_status:
     iretq
 
 context_switch_stub:
+    // closes the user access window of the interrupted code, it's flags are restored by iretq
+    cmp byte ptr [rip + SMAP_ENABLED], 0
+    je 1f
+    clac
+1:
     sub rsp, 16*16      // allocate space for xmm registers
     movdqu [rsp+0x00], xmm0
     movdqu [rsp+0x10], xmm1
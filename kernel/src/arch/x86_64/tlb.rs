@@ -0,0 +1,22 @@
+//! TLB invalidation
+//! only the BSP is running for now (APs are never started, see `cpu`) so a shootdown is just a local
+//! invalidation, once APs are started `shootdown` has to IPI every other online cpu too
+use core::arch::asm;
+
+use crate::VirtAddr;
+
+/// invalidates the TLB entry of the page containing `addr` on the current cpu
+#[inline(always)]
+pub fn invalidate_page(addr: VirtAddr) {
+    unsafe {
+        asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
+    }
+}
+
+/// invalidates the page containing `addr` on every cpu that may have it cached
+/// should be called after a mapping is removed or it's permissions are tightened, even if the page
+/// table isn't the current one since the higher half is shared
+#[inline]
+pub fn shootdown(addr: VirtAddr) {
+    invalidate_page(addr);
+}
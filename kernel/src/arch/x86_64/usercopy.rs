@@ -0,0 +1,154 @@
+//! copying from and to user memory without trusting it to be mapped
+//! the copy is a single `rep movsb` that has an entry in the exception table, if it faults the page
+//! fault handler resumes execution at it's fixup which returns the amount of bytes left instead of panicking
+//! with SMAP the kernel can only touch user pages inside a `UserAccess` window, the copies open their own
+use core::arch::{asm, global_asm};
+
+use crate::{
+    memory::paging::{current_root_table, EntryFlags},
+    utils::errors::ErrorStatus,
+    VirtAddr,
+};
+
+/// the end of the lower half, user pointers must be below it
+const USER_END: VirtAddr = 0x0000_8000_0000_0000;
+
+global_asm!(
+    "
+.section .text
+.global __copy_user
+// rdi: dest, rsi: src, rdx: len, returns the amount of bytes that weren't copied in rax
+__copy_user:
+    mov rcx, rdx
+copy_user_fault:
+    rep movsb
+copy_user_fixup:
+    mov rax, rcx
+    ret
+
+.section .rodata
+.global __exception_table
+.global __exception_table_end
+// pairs of (an insturaction that may fault, where to continue if it does)
+__exception_table:
+    .quad copy_user_fault, copy_user_fixup
+__exception_table_end:
+"
+);
+
+#[repr(C)]
+struct ExceptionEntry {
+    fault: usize,
+    fixup: usize,
+}
+
+extern "C" {
+    static __exception_table: ExceptionEntry;
+    static __exception_table_end: ExceptionEntry;
+    fn __copy_user(dest: *mut u8, src: *const u8, len: usize) -> usize;
+}
+
+const RFLAGS_AC: u64 = 1 << 18;
+
+/// a window in which the kernel can access user pages directly (sets the AC flag), closed once dropped
+/// windows can be nested, only the outermost one closes
+/// the flag is part of the saved context so a window stays open for the code that opened it across
+/// interrupts and context switches, interrupt handlers start with it closed (see `clac`)
+pub struct UserAccess {
+    was_open: bool,
+}
+
+impl UserAccess {
+    pub fn open() -> Self {
+        if !super::smap_enabled() {
+            return Self { was_open: true };
+        }
+
+        let rflags: u64;
+        unsafe {
+            asm!("pushfq; pop {}", out(reg) rflags);
+            asm!("stac", options(nostack));
+        }
+
+        Self {
+            was_open: rflags & RFLAGS_AC != 0,
+        }
+    }
+}
+
+impl Drop for UserAccess {
+    fn drop(&mut self) {
+        if !self.was_open {
+            unsafe { asm!("clac", options(nostack)) }
+        }
+    }
+}
+
+/// closes the user access window of the interrupted code, or the one userspace opened by setting AC itself,
+/// called first thing by every interrupt handler that returns, iretq restores the interrupted code's flags
+#[inline(always)]
+pub fn clac() {
+    if super::smap_enabled() {
+        unsafe { asm!("clac", options(nostack)) }
+    }
+}
+
+/// returns where to continue if the kernel faults at `rip`, None if faulting there is a bug
+pub fn fixup(rip: usize) -> Option<usize> {
+    let table = unsafe {
+        let start = &__exception_table as *const ExceptionEntry;
+        let end = &__exception_table_end as *const ExceptionEntry;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    };
+
+    table
+        .iter()
+        .find(|entry| entry.fault == rip)
+        .map(|entry| entry.fixup)
+}
+
+/// returns wether or not `ptr..ptr + len` is entirely in the lower half and every page in it is
+/// mapped accessible from userspace (and writable if `writable`)
+/// the lower half also has kernel only pages (such as the ring0 stack) so being below `USER_END` isn't enough
+fn is_user_range(ptr: VirtAddr, len: usize, writable: bool) -> bool {
+    if !ptr.checked_add(len).is_some_and(|end| end <= USER_END) {
+        return false;
+    }
+
+    let flags = if writable {
+        EntryFlags::USER_ACCESSIBLE | EntryFlags::WRITABLE
+    } else {
+        EntryFlags::USER_ACCESSIBLE
+    };
+    unsafe { current_root_table() }.is_mapped_with(ptr, len, flags)
+}
+
+/// copies `dest.len()` bytes from the user pointer `src` to `dest`
+/// returns Err(ErrorStatus::InvaildPtr) if `src` isn't a user pointer or any of it's pages isn't
+/// mapped accessible from userspace
+pub fn copy_from_user(dest: &mut [u8], src: *const u8) -> Result<(), ErrorStatus> {
+    if !is_user_range(src as VirtAddr, dest.len(), false) {
+        return Err(ErrorStatus::InvaildPtr);
+    }
+
+    let _access = UserAccess::open();
+    match unsafe { __copy_user(dest.as_mut_ptr(), src, dest.len()) } {
+        0 => Ok(()),
+        _ => Err(ErrorStatus::InvaildPtr),
+    }
+}
+
+/// copies `src` to the user pointer `dest`
+/// returns Err(ErrorStatus::InvaildPtr) if `dest` isn't a user pointer or any of it's pages isn't
+/// mapped writable from userspace, some of `src` may have been copied
+pub fn copy_to_user(dest: *mut u8, src: &[u8]) -> Result<(), ErrorStatus> {
+    if !is_user_range(dest as VirtAddr, src.len(), true) {
+        return Err(ErrorStatus::InvaildPtr);
+    }
+
+    let _access = UserAccess::open();
+    match unsafe { __copy_user(dest, src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => Err(ErrorStatus::InvaildPtr),
+    }
+}
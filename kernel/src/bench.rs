@@ -0,0 +1,185 @@
+//! scheduler benchmarks, ran after the tests with the `bench` feature
+//! each benchmark prints a single line over serial that looks like
+//! `[BENCH] name=<name> iterations=<n> cycles=<total> per_iteration=<cycles>`
+//! so that scheduler and locking regressions can be picked up from the log
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use macros::test_module;
+
+use crate::{
+    arch::x86_64 as arch,
+    cross_println,
+    drivers::vfs::expose as vfs,
+    threading::expose::{thread_exit, thread_yeild},
+};
+
+const IPC_PATH: &str = "ram:/bench";
+const LOOKUP_DIR: &str = "ram:/lookup";
+/// the size of a message sent through `IPC_PATH`
+const MESSAGE_SIZE: usize = 4096;
+const MESSAGES: usize = 256;
+const ROUND_TRIPS: usize = 1000;
+
+/// odd when it's the partner's turn, even when it's the benchmark's turn
+static PING: AtomicUsize = AtomicUsize::new(0);
+/// the amount of messages the sender wrote so far
+static SENT: AtomicUsize = AtomicUsize::new(0);
+
+fn report(name: &str, iterations: usize, cycles: u64) {
+    cross_println!(
+        "[BENCH] name={} iterations={} cycles={} per_iteration={}",
+        name,
+        iterations,
+        cycles,
+        cycles / iterations as u64
+    );
+}
+
+/// measures the cycles `then` takes and reports them as the benchmark `name`, returns the cycles
+fn measure(name: &str, iterations: usize, then: impl FnOnce()) -> u64 {
+    let start = arch::cycles();
+    then();
+    let cycles = arch::cycles() - start;
+    report(name, iterations, cycles);
+    cycles
+}
+
+/// answers every ping with a pong
+fn ponger() -> ! {
+    for round in 0..ROUND_TRIPS {
+        let ping = round * 2 + 1;
+        while PING.load(Ordering::Acquire) != ping {
+            thread_yeild();
+        }
+        PING.store(ping + 1, Ordering::Release);
+    }
+
+    thread_exit(0);
+    unreachable!()
+}
+
+/// writes `MESSAGES` messages to `IPC_PATH`
+fn sender() -> ! {
+    let fd = vfs::open(IPC_PATH).unwrap();
+    let message = [0xAA; MESSAGE_SIZE];
+
+    for _ in 0..MESSAGES {
+        assert_eq!(vfs::write(fd, &message).unwrap(), MESSAGE_SIZE);
+        SENT.fetch_add(1, Ordering::Release);
+    }
+
+    vfs::close(fd).unwrap();
+    thread_exit(0);
+    unreachable!()
+}
+
+#[test_module]
+pub mod bench_module {
+    use core::sync::atomic::Ordering;
+
+    use super::{
+        measure, ponger, sender, IPC_PATH, LOOKUP_DIR, MESSAGES, MESSAGE_SIZE, PING, ROUND_TRIPS,
+        SENT,
+    };
+    use crate::{
+        drivers::vfs::expose as vfs,
+        threading::{
+            expose::{kspawn, pspawn, thread_yeild, wait, SpawnFlags},
+            processes::ContextPriority,
+        },
+    };
+    use alloc::format;
+
+    /// opening files in a small and then a big ramfs directory, the directories are indexed by name so
+    /// the lookups shouldn't get much slower
+    fn ramfs_lookup() {
+        const SMALL: usize = 100;
+        const LARGE: usize = 10_000;
+        const LOOKUPS: usize = 1000;
+
+        let create = |range: core::ops::Range<usize>| {
+            for i in range {
+                vfs::create(&format!("{LOOKUP_DIR}/{i}")).unwrap();
+            }
+        };
+        // only the first `SMALL` files are looked up so both runs do the same work other then the lookup
+        let lookup = || {
+            for i in 0..LOOKUPS {
+                let fd = vfs::open(&format!("{LOOKUP_DIR}/{}", i % SMALL)).unwrap();
+                vfs::close(fd).unwrap();
+            }
+        };
+
+        vfs::createdir(LOOKUP_DIR).unwrap();
+        create(0..SMALL);
+        let small = measure("ramfs_lookup_small", LOOKUPS, lookup);
+
+        create(SMALL..LARGE);
+        let large = measure("ramfs_lookup_large", LOOKUPS, lookup);
+
+        assert!(
+            large < small * 4,
+            "looking up in a directory with {LARGE} files took {large} cycles, {small} with {SMALL}"
+        );
+    }
+
+    /// spawning and reaping a process
+    fn spawn_throughput() {
+        const ITERATIONS: usize = 16;
+
+        measure("spawn", ITERATIONS, || {
+            for _ in 0..ITERATIONS {
+                let pid = pspawn(
+                    "BENCH_SPAWN",
+                    "sys:/bin/true",
+                    &[],
+                    SpawnFlags::empty(),
+                    ContextPriority::default(),
+                )
+                .unwrap();
+                wait(pid);
+            }
+        });
+    }
+
+    /// switching to another process and back, both sides wait by yielding
+    fn context_switch_round_trip() {
+        PING.store(0, Ordering::Release);
+        let pid = kspawn("BENCH_PONG", ponger).unwrap();
+
+        measure("context_switch_round_trip", ROUND_TRIPS, || {
+            for round in 0..ROUND_TRIPS {
+                let ping = round * 2 + 1;
+                PING.store(ping, Ordering::Release);
+                while PING.load(Ordering::Acquire) != ping + 1 {
+                    thread_yeild();
+                }
+            }
+        });
+
+        assert_eq!(wait(pid), 0);
+    }
+
+    /// passing `MESSAGE_SIZE` bytes messages from one process to another through a ramfs file, there
+    /// are no pipes yet
+    fn ipc_throughput() {
+        vfs::create(IPC_PATH).unwrap();
+        SENT.store(0, Ordering::Release);
+
+        let fd = vfs::open(IPC_PATH).unwrap();
+        let mut buffer = [0; MESSAGE_SIZE];
+        let pid = kspawn("BENCH_SENDER", sender).unwrap();
+
+        measure("ipc", MESSAGES, || {
+            for received in 0..MESSAGES {
+                while SENT.load(Ordering::Acquire) <= received {
+                    thread_yeild();
+                }
+                assert_eq!(vfs::read(fd, &mut buffer).unwrap(), MESSAGE_SIZE);
+            }
+        });
+
+        vfs::close(fd).unwrap();
+        assert_eq!(wait(pid), 0);
+    }
+}
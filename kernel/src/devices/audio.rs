@@ -0,0 +1,79 @@
+//! dev:/audio, the sound output
+//! writing PCM frames to it plays them blocking until they were played and
+//! `AudioCtl::SetFormat` switches the format of the frames
+use crate::drivers::{
+    vfs::{FSError, FSResult},
+    virtio::sound::{self, AudioFormat, PcmFormat, VirtioSound},
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum AudioCtl {
+    /// sets the format of the written frames, `arg` is the rate in Hz (bits 0..32), the amount of
+    /// channels (bits 32..40) and the `AudioFormat` (bits 40..48)
+    SetFormat = ctl::cmd(CtlClass::Audio, 0),
+}
+
+impl TryFrom<u16> for AudioCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::Audio, value) {
+            Some(0) => Ok(Self::SetFormat),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct AudioDevice(&'static VirtioSound);
+
+impl AudioDevice {
+    /// returns None if there is no sound device
+    pub fn new() -> Option<Self> {
+        sound::get().map(Self)
+    }
+}
+
+impl CharDevice for AudioDevice {
+    fn name(&self) -> &'static str {
+        "audio"
+    }
+
+    fn raw(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        self.0.write(buffer)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match AudioCtl::try_from(cmd) {
+            Ok(AudioCtl::SetFormat) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                let format = AudioFormat::try_from((arg >> 40) as u8)
+                    .map_err(|()| FSError::OperationNotSupported)?;
+
+                self.0.set_format(PcmFormat {
+                    rate: arg as u32,
+                    channels: (arg >> 32) as u8,
+                    format,
+                })
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+
+    fn quiesce(&self) {
+        self.0.quiesce();
+    }
+}
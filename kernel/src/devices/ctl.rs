@@ -0,0 +1,60 @@
+//! the registry of device command numbers, every device has it's own class so a command sent to
+//! the wrong device is rejected instead of being taken for one of it's own commands
+//! a command is the class in the high byte and the number of the command in the low byte, class 0
+//! is the numbering from before classes were added and is accepted by every device so old binaries
+//! keep working
+//! commands take their argument from an input buffer and write their result into an output buffer,
+//! both have to be the exact size the command expects, `arg` in the docs of a command is the usize
+//! in it's input
+use crate::drivers::vfs::{FSError, FSResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CtlClass {
+    Tty = 1,
+    FrameBuffer = 2,
+    Audio = 3,
+    Irq = 4,
+    Profiler = 5,
+    Input = 6,
+}
+
+pub const fn cmd(class: CtlClass, nr: u8) -> u16 {
+    ((class as u16) << 8) | nr as u16
+}
+
+/// returns the number of `cmd` if it is one of the commands of `class`
+pub const fn nr(class: CtlClass, cmd: u16) -> Option<u8> {
+    match cmd >> 8 {
+        0 => Some(cmd as u8),
+        c if c == class as u16 => Some(cmd as u8),
+        _ => None,
+    }
+}
+
+/// reads the argument of a command that takes a single usize
+pub fn arg(input: &[u8]) -> FSResult<usize> {
+    let bytes = input.try_into().map_err(|_| FSError::InvaildArgument)?;
+    Ok(usize::from_ne_bytes(bytes))
+}
+
+/// writes the result of a command into `output`
+pub fn output<T: Copy>(output: &mut [u8], value: &T) -> FSResult<()> {
+    if output.len() != size_of::<T>() {
+        return Err(FSError::InvaildArgument);
+    }
+
+    let bytes =
+        unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    output.copy_from_slice(bytes);
+    Ok(())
+}
+
+/// for commands that don't return anything
+pub fn no_output(output: &[u8]) -> FSResult<()> {
+    if output.is_empty() {
+        Ok(())
+    } else {
+        Err(FSError::InvaildArgument)
+    }
+}
@@ -0,0 +1,93 @@
+//! dev:/fb, the display
+//! reading it gives the list of supported video modes as an array of `VideoMode`s and
+//! `FrameBufferCtl::SetMode` switches to one of them
+use crate::{
+    drivers::{
+        framebuffer::{VideoMode, FRAMEBUFFER_DRIVER},
+        vfs::{FSError, FSResult},
+    },
+    terminal::FRAMEBUFFER_TERMINAL,
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum FrameBufferCtl {
+    /// switches to the mode at index `arg` in the mode list
+    SetMode = ctl::cmd(CtlClass::FrameBuffer, 0),
+    /// writes the current `VideoMode` into the output
+    GetMode = ctl::cmd(CtlClass::FrameBuffer, 1),
+}
+
+impl TryFrom<u16> for FrameBufferCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::FrameBuffer, value) {
+            Some(0) => Ok(Self::SetMode),
+            Some(1) => Ok(Self::GetMode),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct FrameBufferDevice;
+
+pub static FRAMEBUFFER_DEVICE: FrameBufferDevice = FrameBufferDevice;
+
+impl CharDevice for FrameBufferDevice {
+    fn name(&self) -> &'static str {
+        "fb"
+    }
+
+    fn raw(&self) -> bool {
+        true
+    }
+
+    /// reads as many whole modes as fit in `buffer`
+    fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        let framebuffer = FRAMEBUFFER_DRIVER.read();
+        let modes = framebuffer.modes();
+        let count = modes.len().min(buffer.len() / size_of::<VideoMode>());
+
+        let bytes = unsafe {
+            core::slice::from_raw_parts(modes.as_ptr() as *const u8, count * size_of::<VideoMode>())
+        };
+        buffer[..bytes.len()].copy_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn write(&self, _buffer: &[u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match FrameBufferCtl::try_from(cmd) {
+            Ok(FrameBufferCtl::SetMode) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                let mut framebuffer = FRAMEBUFFER_DRIVER.write();
+                let old_mode = framebuffer.mode();
+                framebuffer.set_mode(arg)?;
+
+                let switched = framebuffer.mode() != old_mode;
+                drop(framebuffer);
+                // the TTY's cursor is relative to the old buffer
+                if switched {
+                    FRAMEBUFFER_TERMINAL.write().clear();
+                }
+                Ok(())
+            }
+            Ok(FrameBufferCtl::GetMode) => {
+                if !input.is_empty() {
+                    return Err(FSError::InvaildArgument);
+                }
+                ctl::output(output, &FRAMEBUFFER_DRIVER.read().mode())
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! dev:/input/event0, the raw stream of the events of every input driver, reading it gives as many
+//! whole `InputEvent`s as fit in the buffer and waits if there are none
+//! `InputCtl::Grab` requires `Capabilities::COMPOSITOR`
+use crate::{
+    drivers::{
+        input::{self, InputEvent},
+        vfs::{FSError, FSResult, PollEvents},
+    },
+    threading::{
+        expose::{getpid, has_capabilities},
+        processes::Capabilities,
+        waitqueue::WaitQueue,
+    },
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum InputCtl {
+    /// if `arg` isn't 0 the caller grabs the input so keys are no longer given to the TTY, otherwise
+    /// the caller releases it's grab, fails with `FSError::ResourceBusy` if another process grabbed it
+    Grab = ctl::cmd(CtlClass::Input, 0),
+}
+
+impl TryFrom<u16> for InputCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::Input, value) {
+            Some(0) => Ok(Self::Grab),
+            _ => Err(()),
+        }
+    }
+}
+
+const EVENT_SIZE: usize = size_of::<InputEvent>();
+
+pub struct InputDevice;
+pub static INPUT_DEVICE: InputDevice = InputDevice;
+
+impl CharDevice for InputDevice {
+    fn name(&self) -> &'static str {
+        "input/event0"
+    }
+
+    fn raw(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        if buffer.len() < EVENT_SIZE {
+            return Err(FSError::InvaildArgument);
+        }
+
+        let mut written = 0;
+        while buffer.len() - written >= EVENT_SIZE {
+            let Some(event) = input::pop() else {
+                break;
+            };
+
+            ctl::output(&mut buffer[written..written + EVENT_SIZE], &event)?;
+            written += EVENT_SIZE;
+        }
+
+        if written == 0 {
+            Err(FSError::ResourceBusy)
+        } else {
+            Ok(written)
+        }
+    }
+
+    fn write(&self, _buffer: &[u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn poll(&self) -> PollEvents {
+        if input::pending() {
+            PollEvents::READABLE
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        Some(&input::WAITERS)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match InputCtl::try_from(cmd) {
+            Ok(InputCtl::Grab) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                if !has_capabilities(Capabilities::COMPOSITOR) {
+                    return Err(FSError::MissingPermissions);
+                }
+
+                let pid = getpid();
+                let results = if arg != 0 {
+                    input::grab(pid)
+                } else {
+                    input::release(pid)
+                };
+                results.map_err(|()| FSError::ResourceBusy)
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+}
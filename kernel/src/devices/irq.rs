@@ -0,0 +1,68 @@
+//! dev:/irq, controls which cpu the irqs listed in proc:/interrupts interrupt
+//! `IrqCtl::SetAffinity` requires `Capabilities::POWER`
+use crate::{
+    drivers::{
+        pci,
+        vfs::{FSError, FSResult},
+    },
+    threading::{expose::has_capabilities, processes::Capabilities},
+    utils::errors::ErrorStatus,
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum IrqCtl {
+    /// makes the irq in bits 0..32 of `arg` interrupt the cpu in bits 32..64 of `arg`
+    SetAffinity = ctl::cmd(CtlClass::Irq, 0),
+}
+
+impl TryFrom<u16> for IrqCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::Irq, value) {
+            Some(0) => Ok(Self::SetAffinity),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct IrqDevice;
+pub static IRQ_DEVICE: IrqDevice = IrqDevice;
+
+impl CharDevice for IrqDevice {
+    fn name(&self) -> &'static str {
+        "irq"
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn write(&self, _buffer: &[u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match IrqCtl::try_from(cmd) {
+            Ok(IrqCtl::SetAffinity) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                if !has_capabilities(Capabilities::POWER) {
+                    return Err(FSError::MissingPermissions);
+                }
+
+                let (irq, cpu) = (arg as u32 as usize, arg >> 32);
+                pci::set_affinity(irq, cpu).map_err(|err| match err {
+                    ErrorStatus::Busy => FSError::ResourceBusy,
+                    _ => FSError::InvaildArgument,
+                })
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+}
@@ -1,56 +1,161 @@
+pub mod audio;
+pub mod ctl;
+pub mod framebuffer;
+pub mod input;
+pub mod irq;
+pub mod profiler;
+pub mod pseudo;
 pub mod serial;
 pub mod tty;
 
 use alloc::{
-    collections::linked_list::LinkedList,
+    boxed::Box,
+    collections::VecDeque,
     string::{String, ToString},
+    vec::Vec,
 };
 use lazy_static::lazy_static;
-use spin::Mutex;
 
 use crate::{
     arch::serial::SERIAL,
-    drivers::vfs::{FSResult, InodeOps},
+    drivers::vfs::{FSError, FSResult, InodeOps, PollEvents},
     terminal::FRAMEBUFFER_TERMINAL,
+    threading::waitqueue::WaitQueue,
+    utils::locks::Mutex,
 };
 
+/// a device being registered or unregistered, read from dev:/events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Added(&'static str),
+    Removed(&'static str),
+}
+
+/// the amount of events kept for readers of dev:/events that didn't catch up yet
+const MAX_EVENTS: usize = 64;
+
 pub struct DeviceManager {
-    devices: LinkedList<&'static dyn Device>,
+    /// the registered devices and their ids in the order they were registered, ids aren't reused
+    /// so the dev: entry of a removed device doesn't end up pointing to another device
+    devices: Vec<(usize, &'static dyn Device)>,
+    next_id: usize,
+    /// the last `MAX_EVENTS` events, the first one is event number `events_start`
+    events: VecDeque<DeviceEvent>,
+    events_start: usize,
 }
 
 impl DeviceManager {
     pub fn new() -> Self {
         Self {
-            devices: LinkedList::new(),
+            devices: Vec::new(),
+            next_id: 1,
+            events: VecDeque::new(),
+            events_start: 0,
         }
     }
-    pub fn add_device(&mut self, device: &'static dyn Device) {
-        self.devices.push_back(device);
-    }
 
-    pub fn devices(&self) -> &LinkedList<&'static dyn Device> {
-        &self.devices
+    fn push_event(&mut self, event: DeviceEvent) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+            self.events_start += 1;
+        }
+        self.events.push_back(event);
     }
 
-    pub fn get_device_at(&self, index: usize) -> Option<&'static dyn Device> {
-        for (i, device) in self.devices.iter().enumerate() {
-            if i == index {
-                return Some(*device);
-            }
+    /// registers `device` and returns it's id, fails if a device with the same name exists
+    pub fn add_device(&mut self, device: &'static dyn Device) -> FSResult<usize> {
+        if self.find(Device::name(device)).is_some() {
+            return Err(FSError::AlreadyExists);
         }
 
-        None
+        let id = self.next_id;
+        self.next_id += 1;
+        self.devices.push((id, device));
+        self.push_event(DeviceEvent::Added(Device::name(device)));
+        Ok(id)
+    }
+
+    /// unregisters the device `id`, it's dev: entry is removed and files already opened on it fail
+    /// with `FSError::NoSuchAFileOrDirectory`
+    pub fn remove_device(&mut self, id: usize) -> FSResult<&'static dyn Device> {
+        let index = self
+            .devices
+            .iter()
+            .position(|(device_id, _)| *device_id == id)
+            .ok_or(FSError::NoSuchAFileOrDirectory)?;
+
+        let (_, device) = self.devices.remove(index);
+        self.push_event(DeviceEvent::Removed(Device::name(device)));
+        Ok(device)
+    }
+
+    /// the registered devices and their ids in ascending id order
+    pub fn devices(&self) -> impl Iterator<Item = (usize, &'static dyn Device)> + '_ {
+        self.devices.iter().copied()
+    }
+
+    pub fn get_device(&self, id: usize) -> Option<&'static dyn Device> {
+        self.devices()
+            .find(|(device_id, _)| *device_id == id)
+            .map(|(_, device)| device)
+    }
+
+    /// returns the id of the device named `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.devices()
+            .find(|(_, device)| Device::name(*device) == name)
+            .map(|(id, _)| id)
+    }
+
+    /// the number of the next event
+    pub fn events_end(&self) -> usize {
+        self.events_start + self.events.len()
+    }
+
+    /// returns the event number `number` or None if it didn't happen yet
+    /// events that are no longer kept are skipped, returns the number of the returned event too
+    pub fn event(&self, number: usize) -> Option<(usize, DeviceEvent)> {
+        let number = number.max(self.events_start);
+        let event = *self.events.get(number - self.events_start)?;
+        Some((number, event))
     }
 }
 
 pub trait Device: Send + Sync + InodeOps {
     fn name(&self) -> &'static str;
+    /// wether or not the device gives direct access to the hardware, opening it requires
+    /// `Capabilities::RAW_DEVICES`
+    fn raw(&self) -> bool {
+        false
+    }
+    /// stops the device from doing any more work, called before shutting down or rebooting
+    fn quiesce(&self) {}
 }
 
 pub trait CharDevice: Send + Sync {
     fn name(&self) -> &'static str;
     fn read(&self, buffer: &mut [u8]) -> FSResult<usize>;
     fn write(&self, buffer: &[u8]) -> FSResult<usize>;
+    fn quiesce(&self) {}
+    /// see `Device::raw`
+    fn raw(&self) -> bool {
+        false
+    }
+    /// returns the operations that can be done on the device right now without waiting
+    fn poll(&self) -> PollEvents {
+        PollEvents::all()
+    }
+    /// see `InodeOps::wait_queue`
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        None
+    }
+    /// see `InodeOps::ctl`
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        _ = cmd;
+        _ = input;
+        _ = output;
+        Err(FSError::OperationNotSupported)
+    }
 }
 
 impl<T: CharDevice> InodeOps for T {
@@ -75,6 +180,18 @@ impl<T: CharDevice> InodeOps for T {
         CharDevice::write(self, buffer)
     }
 
+    fn poll(&self) -> PollEvents {
+        CharDevice::poll(self)
+    }
+
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        CharDevice::wait_queue(self)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        CharDevice::ctl(self, cmd, input, output)
+    }
+
     fn inodeid(&self) -> usize {
         0
     }
@@ -84,12 +201,68 @@ impl<T: CharDevice> Device for T {
     fn name(&self) -> &'static str {
         self.name()
     }
+
+    fn quiesce(&self) {
+        CharDevice::quiesce(self)
+    }
+
+    fn raw(&self) -> bool {
+        CharDevice::raw(self)
+    }
 }
+/// woken up whenever a device is registered or unregistered, see dev:/events
+pub static EVENT_WAITERS: WaitQueue = WaitQueue::new();
+
 lazy_static! {
-    pub static ref DEVICE_MANAGER: Mutex<DeviceManager> = Mutex::new(DeviceManager::new());
+    pub static ref DEVICE_MANAGER: Mutex<DeviceManager> =
+        Mutex::new("DeviceManager", DeviceManager::new());
+}
+
+/// registers `device`, a dev: entry with it's name is created for it and an event is sent to the
+/// readers of dev:/events, returns the id to unregister it with
+pub fn register(device: &'static dyn Device) -> FSResult<usize> {
+    let id = DEVICE_MANAGER.lock().add_device(device)?;
+    EVENT_WAITERS.wake_all();
+    Ok(id)
+}
+
+/// unregisters the device `id` (see `register`), for devices that can be unplugged
+// no driver supports unplugging yet
+#[allow(dead_code)]
+pub fn unregister(id: usize) -> FSResult<&'static dyn Device> {
+    let device = DEVICE_MANAGER.lock().remove_device(id)?;
+    EVENT_WAITERS.wake_all();
+    // the files opened on it are ready now so that their waiters get the error
+    if let Some(queue) = InodeOps::wait_queue(device) {
+        queue.wake_all();
+    }
+    Ok(device)
 }
 
 pub fn init() {
-    DEVICE_MANAGER.lock().add_device(&*FRAMEBUFFER_TERMINAL);
-    DEVICE_MANAGER.lock().add_device(&*SERIAL);
+    // the names are all different
+    register(&*FRAMEBUFFER_TERMINAL).unwrap();
+    register(&*SERIAL).unwrap();
+    register(&framebuffer::FRAMEBUFFER_DEVICE).unwrap();
+    register(&input::INPUT_DEVICE).unwrap();
+    register(&irq::IRQ_DEVICE).unwrap();
+    register(&profiler::PROFILER_DEVICE).unwrap();
+    register(&pseudo::NULL_DEVICE).unwrap();
+    register(&pseudo::ZERO_DEVICE).unwrap();
+    register(&pseudo::FULL_DEVICE).unwrap();
+    register(&pseudo::URANDOM_DEVICE).unwrap();
+}
+
+/// registers dev:/audio if the sound driver was initialized
+pub fn init_audio() {
+    if let Some(audio) = audio::AudioDevice::new() {
+        register(Box::leak(Box::new(audio))).unwrap();
+    }
+}
+
+/// quiesces all devices, called before shutting down or rebooting
+pub fn quiesce() {
+    for (_, device) in DEVICE_MANAGER.lock().devices() {
+        device.quiesce();
+    }
 }
@@ -0,0 +1,66 @@
+//! dev:/profiler, starts and stops the sampling profiler, the samples are in proc:/profile
+//! `ProfilerCtl::SetRunning` requires `Capabilities::DEBUG`
+use crate::{
+    arch::profiler,
+    drivers::vfs::{FSError, FSResult},
+    threading::{expose::has_capabilities, processes::Capabilities},
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ProfilerCtl {
+    /// starts profiling if `arg` isn't 0 and stops otherwise
+    SetRunning = ctl::cmd(CtlClass::Profiler, 0),
+}
+
+impl TryFrom<u16> for ProfilerCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::Profiler, value) {
+            Some(0) => Ok(Self::SetRunning),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct ProfilerDevice;
+pub static PROFILER_DEVICE: ProfilerDevice = ProfilerDevice;
+
+impl CharDevice for ProfilerDevice {
+    fn name(&self) -> &'static str {
+        "profiler"
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn write(&self, _buffer: &[u8]) -> FSResult<usize> {
+        Err(FSError::OperationNotSupported)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match ProfilerCtl::try_from(cmd) {
+            Ok(ProfilerCtl::SetRunning) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                if !has_capabilities(Capabilities::DEBUG) {
+                    return Err(FSError::MissingPermissions);
+                }
+
+                match (arg != 0, profiler::running()) {
+                    (true, false) => _ = profiler::start(),
+                    (false, true) => profiler::stop(),
+                    _ => {}
+                }
+                Ok(())
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+}
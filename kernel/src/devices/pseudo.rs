@@ -0,0 +1,113 @@
+//! the classic pseudo devices
+//! dev:/null reads nothing and discards writes, dev:/zero reads zeros and discards writes, dev:/full
+//! reads zeros and fails every write with `FSError::OutOfMemory` (there is no "no space left" error)
+//! and dev:/urandom reads random bytes, writing to it mixes the written bytes into it's state
+use crate::{
+    arch::{random::hardware_random, x86_64::cycles},
+    drivers::vfs::{FSError, FSResult},
+    utils::locks::Mutex,
+};
+
+use super::CharDevice;
+
+pub struct NullDevice;
+pub static NULL_DEVICE: NullDevice = NullDevice;
+
+impl CharDevice for NullDevice {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn read(&self, _buffer: &mut [u8]) -> FSResult<usize> {
+        Ok(0)
+    }
+
+    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        Ok(buffer.len())
+    }
+}
+
+pub struct ZeroDevice;
+pub static ZERO_DEVICE: ZeroDevice = ZeroDevice;
+
+impl CharDevice for ZeroDevice {
+    fn name(&self) -> &'static str {
+        "zero"
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        buffer.fill(0);
+        Ok(buffer.len())
+    }
+
+    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        Ok(buffer.len())
+    }
+}
+
+pub struct FullDevice;
+pub static FULL_DEVICE: FullDevice = FullDevice;
+
+impl CharDevice for FullDevice {
+    fn name(&self) -> &'static str {
+        "full"
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        buffer.fill(0);
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _buffer: &[u8]) -> FSResult<usize> {
+        Err(FSError::OutOfMemory)
+    }
+}
+
+/// a splitmix64 generator, it's state is mixed with RDRAND (if the cpu supports it) and the TSC on
+/// every read so it isn't predictable from earlier reads, it isn't good enough for cryptography
+/// without RDRAND
+pub struct UrandomDevice {
+    state: Mutex<u64>,
+}
+
+pub static URANDOM_DEVICE: UrandomDevice = UrandomDevice {
+    state: Mutex::new("Urandom", 0),
+};
+
+impl UrandomDevice {
+    fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl CharDevice for UrandomDevice {
+    fn name(&self) -> &'static str {
+        "urandom"
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        let mut state = self.state.lock();
+        *state ^= hardware_random().unwrap_or(0) ^ cycles();
+
+        for chunk in buffer.chunks_mut(size_of::<u64>()) {
+            let random = Self::next(&mut state).to_ne_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+        Ok(buffer.len())
+    }
+
+    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        let mut state = self.state.lock();
+        for chunk in buffer.chunks(size_of::<u64>()) {
+            let mut bytes = [0u8; size_of::<u64>()];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            *state ^= u64::from_ne_bytes(bytes);
+            Self::next(&mut state);
+        }
+        Ok(buffer.len())
+    }
+}
@@ -1,8 +1,7 @@
-use core::fmt::Write;
-
 use crate::{
     arch::serial::Serial,
     drivers::vfs::{FSError, FSResult},
+    logging::channel,
     utils::Locked,
 };
 
@@ -19,11 +18,7 @@ impl CharDevice for Locked<Serial> {
 
     fn write(&self, buffer: &[u8]) -> FSResult<usize> {
         let str = unsafe { core::str::from_utf8_unchecked(buffer) };
-
-        self.try_lock()
-            .ok_or(FSError::ResourceBusy)?
-            .write_str(str)
-            .unwrap();
+        channel::write(format_args!("{}", str));
         FSResult::Ok(buffer.len())
     }
 }
@@ -1,14 +1,86 @@
-use core::fmt::Write;
-
-use alloc::string::String;
 use spin::RwLock;
 
 use crate::{
-    drivers::vfs::{FSError, FSResult},
-    terminal::TTY,
+    drivers::vfs::{FSError, FSResult, PollEvents},
+    terminal::{clipboard, FRAMEBUFFER_TERMINAL_WAITERS, TTY},
+    threading::{
+        expose::{getpid, is_background, is_descendant, pexists},
+        waitqueue::WaitQueue,
+    },
+};
+
+use super::{
+    ctl::{self, CtlClass},
+    CharDevice,
 };
 
-use super::CharDevice;
+/// job control, a shell spawns jobs with `SpawnFlags::BACKGROUND` so they can't read input, to bring a
+/// job to the foreground it gives it the TTY with `SetForeground` and waits for it, then takes the
+/// TTY back with `SetForeground` and it's own pid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum TTYCtl {
+    /// makes `arg` the foreground process, only it and it's descendants can read input, 0 gives
+    /// the input back to every process that isn't in the background
+    /// the caller has to be the current foreground process or one of it's ancestors and `arg` has
+    /// to be the caller or one of it's descendants
+    SetForeground = ctl::cmd(CtlClass::Tty, 0),
+    /// puts the TTY in raw mode if `arg` isn't 0 or back in line mode if it is, in raw mode every
+    /// key is read as soon as it is pressed without being echoed, arrows, home, end and delete are
+    /// read as their ansi escape sequences, backspace as 0x7F and ctrl + a..z as 0x1..0x1A
+    /// only the foreground process can change the mode, raw mode ends when the process exits
+    SetRaw = ctl::cmd(CtlClass::Tty, 1),
+    /// writes the pid of the foreground process as a usize into the output, 0 if every process
+    /// that isn't in the background can read input
+    GetForeground = ctl::cmd(CtlClass::Tty, 2),
+    /// replaces the contents of the clipboard with the input, which can be up to
+    /// `clipboard::MAX_CLIPBOARD` bytes
+    SetClipboard = ctl::cmd(CtlClass::Tty, 3),
+    /// writes the size of the contents of the clipboard as a usize into the output
+    GetClipboardSize = ctl::cmd(CtlClass::Tty, 4),
+    /// writes the contents of the clipboard into the output which has to be exactly the size given
+    /// by `GetClipboardSize`, if it was changed in between this fails with `FSError::InvaildArgument`
+    GetClipboard = ctl::cmd(CtlClass::Tty, 5),
+}
+
+impl TryFrom<u16> for TTYCtl {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match ctl::nr(CtlClass::Tty, value) {
+            Some(0) => Ok(Self::SetForeground),
+            Some(1) => Ok(Self::SetRaw),
+            Some(2) => Ok(Self::GetForeground),
+            Some(3) => Ok(Self::SetClipboard),
+            Some(4) => Ok(Self::GetClipboardSize),
+            Some(5) => Ok(Self::GetClipboard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// returns wether or not the current process can read the input of `tty`
+fn in_foreground(tty: &TTY) -> bool {
+    let pid = getpid();
+    match tty.foreground {
+        // the background jobs of a foreground shell are still it's descendants
+        Some(foreground) if pexists(foreground) => {
+            is_descendant(pid, foreground) && (!is_background(pid) || is_background(foreground))
+        }
+        _ => !is_background(pid),
+    }
+}
+
+/// returns wether or not `tty` is in raw mode, leaves raw mode if the process that entered it exited
+fn in_raw_mode(tty: &mut TTY) -> bool {
+    match tty.raw {
+        Some(owner) if pexists(owner) => true,
+        Some(_) => {
+            tty.set_raw(None);
+            false
+        }
+        None => false,
+    }
+}
 
 impl CharDevice for RwLock<TTY<'_>> {
     fn name(&self) -> &'static str {
@@ -16,6 +88,22 @@ impl CharDevice for RwLock<TTY<'_>> {
     }
 
     fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+        // background processes wait until they are brought to the foreground
+        if self.try_read().is_none_or(|tty| !in_foreground(&tty)) {
+            return Err(FSError::ResourceBusy);
+        }
+
+        if let Some(mut tty) = self.try_write() {
+            if in_raw_mode(&mut tty) {
+                // whatever was pressed so far
+                if tty.stdin_buffer.is_empty() {
+                    return Err(FSError::ResourceBusy);
+                }
+
+                return Ok(tty.stdin_buffer.read(buffer));
+            }
+        }
+
         if self
             .try_write()
             .is_none_or(|tty| !tty.stdin_buffer.ends_with('\n'))
@@ -26,24 +114,109 @@ impl CharDevice for RwLock<TTY<'_>> {
 
         self.write().disable_input();
 
-        let stdin_buffer = &mut self.write().stdin_buffer;
+        Ok(self.write().stdin_buffer.read(buffer))
+    }
+
+    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        self.try_write()
+            .ok_or(FSError::ResourceBusy)?
+            .write_bytes(buffer);
+        Ok(buffer.len())
+    }
+
+    /// in line mode a line can be entered while there are waiters, see `FRAMEBUFFER_TERMINAL_WAITERS`
+    fn poll(&self) -> PollEvents {
+        let Some(tty) = self.try_read() else {
+            return PollEvents::empty();
+        };
 
-        let count = if stdin_buffer.len() <= buffer.len() {
-            stdin_buffer.len()
+        let ready = if tty.raw.is_some_and(pexists) {
+            !tty.stdin_buffer.is_empty()
         } else {
-            buffer.len()
+            tty.stdin_buffer.ends_with('\n')
         };
 
-        buffer[..count].copy_from_slice(&stdin_buffer.as_str().as_bytes()[..count]);
-        stdin_buffer.inner.drain(..count);
-        Ok(count)
+        if in_foreground(&tty) && ready {
+            PollEvents::all()
+        } else {
+            PollEvents::WRITABLE
+        }
     }
 
-    fn write(&self, buffer: &[u8]) -> FSResult<usize> {
-        let _ = self
-            .try_write()
-            .ok_or(FSError::ResourceBusy)?
-            .write_str(&String::from_utf8_lossy(buffer));
-        Ok(buffer.len())
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        Some(&FRAMEBUFFER_TERMINAL_WAITERS)
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        match TTYCtl::try_from(cmd) {
+            Ok(TTYCtl::SetForeground) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                let pid = getpid();
+                let mut tty = self.try_write().ok_or(FSError::ResourceBusy)?;
+
+                let owns = match tty.foreground {
+                    Some(foreground) if pexists(foreground) => is_descendant(foreground, pid),
+                    _ => true,
+                };
+                if !owns || (arg != 0 && !is_descendant(arg, pid)) {
+                    return Err(FSError::MissingPermissions);
+                }
+
+                tty.foreground = (arg != 0).then_some(arg);
+                drop(tty);
+                // the processes that were waiting in the background may be able to read now
+                FRAMEBUFFER_TERMINAL_WAITERS.wake_all();
+                Ok(())
+            }
+            Ok(TTYCtl::SetRaw) => {
+                let arg = ctl::arg(input)?;
+                ctl::no_output(output)?;
+                let mut tty = self.try_write().ok_or(FSError::ResourceBusy)?;
+                if !in_foreground(&tty) {
+                    return Err(FSError::MissingPermissions);
+                }
+
+                let owner = (arg != 0).then(getpid);
+                if owner != tty.raw {
+                    tty.set_raw(owner);
+                    drop(tty);
+                    FRAMEBUFFER_TERMINAL_WAITERS.wake_all();
+                }
+                Ok(())
+            }
+            Ok(TTYCtl::GetForeground) => {
+                if !input.is_empty() {
+                    return Err(FSError::InvaildArgument);
+                }
+
+                let tty = self.try_read().ok_or(FSError::ResourceBusy)?;
+                let foreground = tty.foreground.filter(|pid| pexists(*pid)).unwrap_or(0);
+                ctl::output(output, &foreground)
+            }
+            Ok(TTYCtl::SetClipboard) => {
+                ctl::no_output(output)?;
+                clipboard::set(input).map_err(|()| FSError::InvaildArgument)
+            }
+            Ok(TTYCtl::GetClipboardSize) => {
+                if !input.is_empty() {
+                    return Err(FSError::InvaildArgument);
+                }
+
+                ctl::output(output, &clipboard::len())
+            }
+            Ok(TTYCtl::GetClipboard) => {
+                if !input.is_empty() {
+                    return Err(FSError::InvaildArgument);
+                }
+
+                clipboard::get(output).map_err(|()| FSError::InvaildArgument)
+            }
+            Err(()) => Err(FSError::OperationNotSupported),
+        }
+    }
+
+    fn quiesce(&self) {
+        self.write().disable_input();
     }
 }
@@ -3,6 +3,7 @@ use lazy_static::lazy_static;
 use spin::RwLock;
 
 use crate::{
+    drivers::vfs::{FSError, FSResult},
     limine,
     memory::page_allocator::{PageAlloc, GLOBAL_PAGE_ALLOCATOR},
     utils::display::RGB,
@@ -15,6 +16,27 @@ pub enum PixelFormat {
     /// TODO: use
     Bgr,
 }
+/// a video mode the display supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct VideoMode {
+    pub width: u64,
+    pub height: u64,
+    /// bits per pixel
+    pub bpp: u16,
+}
+
+/// a display that replaces the framebuffer the bootloader set up, for example a virtio-gpu scanout
+pub trait DisplayDriver: Send + Sync {
+    /// the modes the display supports, the first one is the preferred one
+    fn modes(&self) -> Vec<VideoMode>;
+    /// switches the display to `mode`
+    /// returns the new video buffer and it's info, the previous video buffer is no longer vaild
+    fn set_mode(&self, mode: VideoMode) -> FSResult<(&'static mut [u8], FrameBufferInfo)>;
+    /// makes the changes written to the video buffer visible
+    fn flush(&self);
+}
+
 #[derive(Debug)]
 pub struct FrameBufferInfo {
     /// number of pixels between start of a line and another
@@ -25,21 +47,85 @@ pub struct FrameBufferInfo {
 
 pub struct FrameBuffer {
     pub info: FrameBufferInfo,
+    mode: VideoMode,
+    modes: Vec<VideoMode>,
     buffer_display_index: usize,
     buffer: Vec<u8, PageAlloc>,
     video_buffer: &'static mut [u8],
+    driver: Option<&'static dyn DisplayDriver>,
 }
 
 impl FrameBuffer {
     pub fn new() -> Self {
-        let (video_buffer, info) = limine::get_framebuffer();
+        let (video_buffer, info, mode) = limine::get_framebuffer();
         let mut buffer = Vec::with_capacity_in(video_buffer.len(), &*GLOBAL_PAGE_ALLOCATOR);
         buffer.resize(video_buffer.len(), 0);
+
+        let mut modes = limine::get_video_modes();
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+
         Self {
             info,
+            mode,
+            modes,
             buffer_display_index: 0,
             buffer,
             video_buffer,
+            driver: None,
+        }
+    }
+
+    /// makes `driver` the display, switching to it's preferred mode
+    pub fn attach_driver(&mut self, driver: &'static dyn DisplayDriver) -> FSResult<()> {
+        let modes = driver.modes();
+        let mode = *modes.first().ok_or(FSError::OperationNotSupported)?;
+        let (video_buffer, info) = driver.set_mode(mode)?;
+
+        self.driver = Some(driver);
+        self.modes = modes;
+        self.resize(video_buffer, info, mode);
+        Ok(())
+    }
+
+    /// replaces the video buffer, the buffer keeps the same amount of screens and is cleared
+    fn resize(&mut self, video_buffer: &'static mut [u8], info: FrameBufferInfo, mode: VideoMode) {
+        let screens = (self.buffer.len() / self.video_buffer.len()).max(1);
+
+        self.buffer.clear();
+        self.buffer.resize(video_buffer.len() * screens, 0);
+        self.buffer_display_index = 0;
+
+        self.info = info;
+        self.mode = mode;
+        self.video_buffer = video_buffer;
+        self.sync_pixels();
+    }
+
+    /// the current mode
+    pub fn mode(&self) -> VideoMode {
+        self.mode
+    }
+
+    /// the modes the display supports
+    pub fn modes(&self) -> &[VideoMode] {
+        &self.modes
+    }
+
+    /// switches the display to the mode at `index` in `modes`, switching clears the buffer
+    /// the limine framebuffer is set up by the bootloader and can't be changed after boot, so without a
+    /// `DisplayDriver` only the current mode is accepted and anything else returns
+    /// `FSError::OperationNotSupported`
+    pub fn set_mode(&mut self, index: usize) -> FSResult<()> {
+        match (self.modes.get(index).copied(), self.driver) {
+            (Some(mode), _) if mode == self.mode => Ok(()),
+            (Some(mode), Some(driver)) => {
+                let (video_buffer, info) = driver.set_mode(mode)?;
+                self.resize(video_buffer, info, mode);
+                Ok(())
+            }
+            _ => Err(FSError::OperationNotSupported),
         }
     }
 
@@ -49,24 +135,77 @@ impl FrameBuffer {
         self.buffer.resize(self.buffer.len() + size, 0);
     }
 
+    /// the height of the whole buffer in pixels including the parts that aren't displayed
+    #[inline(always)]
+    pub fn buffer_height(&self) -> usize {
+        self.buffer.len() / self.info.bytes_per_pixel / self.width()
+    }
+
+    /// sets the pixel at `x`, `y` in the buffer to `color`
+    /// out of buffer coordinates are silently clipped
     pub fn set_pixel(&mut self, x: usize, y: usize, color: RGB) {
-        let index = x + y * self.info.stride;
+        if x >= self.width() || y >= self.buffer_height() {
+            return;
+        }
+
+        let index = (x + y * self.info.stride) * self.info.bytes_per_pixel;
         let mut bytes = color.bytes();
 
         if self.info.pixel_format == PixelFormat::Rgb {
             bytes.reverse();
         }
-        self.buffer
-            [index * self.info.bytes_per_pixel..index * self.info.bytes_per_pixel + bytes.len()]
-            .copy_from_slice(&bytes);
+
+        let len = bytes.len().min(self.info.bytes_per_pixel);
+        self.buffer[index..index + len].copy_from_slice(&bytes[..len]);
+    }
+
+    /// clips the rectangle at `x`, `y` with size `width`x`height` to the buffer
+    /// returns the visible width and height
+    fn clip(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        let width = width.min(self.width().saturating_sub(x));
+        let height = height.min(self.buffer_height().saturating_sub(y));
+        (width, height)
+    }
+
+    /// fills the rectangle at `x`, `y` with size `width`x`height` with `color`
+    /// the parts that are out of the buffer are silently clipped
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: RGB) {
+        self.blit(x, y, width, height, |_, _| color);
+    }
+
+    /// draws a `width`x`height` image at `x`, `y`, `pixel(col, row)` returns the color of each pixel
+    /// the parts that are out of the buffer are silently clipped and `pixel` isn't called for them
+    pub fn blit(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pixel: impl Fn(usize, usize) -> RGB,
+    ) {
+        let (width, height) = self.clip(x, y, width, height);
+
+        for row in 0..height {
+            for col in 0..width {
+                self.set_pixel(x + col, y + row, pixel(col, row));
+            }
+        }
     }
 
     /// draws all pixels in the buffer to the actual video_buffer
     pub fn sync_pixels(&mut self) {
+        // the cursor may have been set past the end of the buffer
+        let max_index = self.buffer.len().saturating_sub(self.video_buffer.len());
+        self.buffer_display_index = self.buffer_display_index.min(max_index);
+
         self.video_buffer.copy_from_slice(
             &self.buffer
                 [self.buffer_display_index..self.buffer_display_index + self.video_buffer.len()],
         );
+
+        if let Some(driver) = self.driver {
+            driver.flush();
+        }
     }
 
     #[inline]
@@ -85,9 +224,9 @@ impl FrameBuffer {
             }
             core::cmp::Ordering::Greater => {
                 let amount = pixels as usize * self.info.bytes_per_pixel;
-                if amount + self.buffer_display_index >= self.buffer.len() - self.video_buffer.len()
-                {
-                    self.buffer_display_index = self.buffer.len() - self.video_buffer.len();
+                let max_index = self.buffer.len().saturating_sub(self.video_buffer.len());
+                if amount + self.buffer_display_index >= max_index {
+                    self.buffer_display_index = max_index;
                     return;
                 }
 
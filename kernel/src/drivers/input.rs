@@ -0,0 +1,126 @@
+//! the input subsystem, keyboard drivers push their events here instead of giving them to a consumer
+//! every event is queued for the readers of dev:/input/event0, unless a process grabbed the input
+//! (see `InputCtl::Grab`, for a compositor) keys are also given to the framebuffer TTY
+//! the grab is released once the grabbing process exits
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use heapless::Deque;
+use spin::Mutex;
+
+use super::keyboard::{keys::Key, HandleKey};
+use crate::{
+    terminal::{FRAMEBUFFER_TERMINAL, FRAMEBUFFER_TERMINAL_WAITERS},
+    threading::{self, expose::pexists, waitqueue::WaitQueue},
+};
+
+/// the amount of events kept for the readers, the oldest are dropped once full
+const MAX_EVENTS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum InputKind {
+    /// a key was pressed
+    Key = 1,
+}
+
+/// an event as it is read from dev:/input/event0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct InputEvent {
+    /// the scheduler tick the event was pushed at
+    pub time: u64,
+    pub kind: InputKind,
+    /// the `KeyCode` of keys
+    pub code: u16,
+    /// the `KeyFlags` of keys
+    pub value: u32,
+}
+
+impl InputEvent {
+    pub fn key(key: Key) -> Self {
+        Self {
+            time: threading::ticks(),
+            kind: InputKind::Key,
+            code: key.code as u16,
+            value: key.flags.bits() as u32,
+        }
+    }
+}
+
+static EVENTS: Mutex<Deque<InputEvent, MAX_EVENTS>> = Mutex::new(Deque::new());
+/// the pid of the process that grabbed the input, 0 if none
+static GRABBER: AtomicUsize = AtomicUsize::new(0);
+
+/// woken up whenever an event is pushed, see dev:/input/event0
+pub static WAITERS: WaitQueue = WaitQueue::new();
+
+fn push(event: InputEvent) {
+    let mut events = EVENTS.lock();
+    if events.is_full() {
+        events.pop_front();
+    }
+    _ = events.push_back(event);
+    drop(events);
+
+    WAITERS.wake_all();
+}
+
+/// called by the keyboard drivers for each pressed key
+pub fn push_key(key: Key) {
+    push(InputEvent::key(key));
+
+    if grabber().is_none() {
+        // the terminal could be locked by whatever the driver interrupted
+        if let Some(mut terminal) = FRAMEBUFFER_TERMINAL.try_write() {
+            // processes polling the input need a line to be entered just like readers
+            if terminal.raw.is_none() && FRAMEBUFFER_TERMINAL_WAITERS.has_waiters() {
+                terminal.enable_input();
+            }
+            terminal.handle_key(key);
+        }
+        FRAMEBUFFER_TERMINAL_WAITERS.wake_all();
+    }
+}
+
+/// takes the oldest event
+pub fn pop() -> Option<InputEvent> {
+    EVENTS.lock().pop_front()
+}
+
+/// wether or not there are events waiting to be read
+pub fn pending() -> bool {
+    !EVENTS.lock().is_empty()
+}
+
+/// the pid of the process that grabbed the input, releases the grab if it exited
+pub fn grabber() -> Option<usize> {
+    match GRABBER.load(Ordering::Relaxed) {
+        0 => None,
+        pid if pexists(pid) => Some(pid),
+        pid => {
+            _ = GRABBER.compare_exchange(pid, 0, Ordering::Relaxed, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+/// makes `pid` the only consumer of the input, fails if another process grabbed it
+pub fn grab(pid: usize) -> Result<(), ()> {
+    let current = grabber().unwrap_or(0);
+    if current != 0 && current != pid {
+        return Err(());
+    }
+
+    GRABBER
+        .compare_exchange(current, pid, Ordering::Relaxed, Ordering::Relaxed)
+        .map(|_| ())
+        .map_err(|_| ())
+}
+
+/// gives the input back to the TTY if `pid` grabbed it, fails otherwise
+pub fn release(pid: usize) -> Result<(), ()> {
+    GRABBER
+        .compare_exchange(pid, 0, Ordering::Relaxed, Ordering::Relaxed)
+        .map(|_| ())
+        .map_err(|_| ())
+}
@@ -29,21 +29,9 @@ impl Key {
     pub const ALT_KEY: Key = Self::new(KeyCode::Alt, KeyFlags::empty());
     pub const CAPSLOCK_KEY: Key = Self::new(KeyCode::CapsLock, KeyFlags::empty());
 
-    #[inline]
-    pub fn is_pressed(&self) -> bool {
-        super::KEYBOARD.read().is_pressed(*self)
-    }
-
     pub const fn new(code: KeyCode, flags: KeyFlags) -> Self {
         Self { code, flags }
     }
-
-    pub const fn default() -> Self {
-        Self {
-            code: KeyCode::NULL,
-            flags: KeyFlags::empty(),
-        }
-    }
 }
 
 macro_rules! row {
@@ -148,13 +136,6 @@ pub enum KeyCode {
     LastKey,
 }
 
-impl KeyCode {
-    #[inline]
-    pub fn is_pressed(&self) -> bool {
-        super::KEYBOARD.read().code_is_pressed(*self)
-    }
-}
-
 impl LowerHex for KeyCode {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         LowerHex::fmt(&(*self as u8), f)
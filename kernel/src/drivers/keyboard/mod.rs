@@ -1,12 +1,16 @@
 pub mod keys;
 mod set1;
 
-use heapless::Vec;
+use heapless::{Deque, Vec};
 
 use keys::{Key, KeyCode, KeyFlags};
-use spin::RwLock;
+use spin::{Mutex, Once, RwLock};
+
+use super::poll::{self, PollId, PolledDriver};
+use crate::arch::without_interrupts;
 
 const MAX_KEYS: usize = 256;
+const MAX_PENDING_SCANCODES: usize = 64;
 
 pub struct Keyboard {
     current_keys: Vec<Key, MAX_KEYS>,
@@ -88,15 +92,6 @@ impl Keyboard {
         Key::new(keycode, flags)
     }
 
-    pub fn is_pressed(&self, key: Key) -> bool {
-        for ckey in &self.current_keys {
-            if ckey.code == key.code && ckey.flags == key.flags {
-                return true;
-            }
-        }
-        false
-    }
-
     pub fn code_is_pressed(&self, code: KeyCode) -> bool {
         for ckey in &self.current_keys {
             if ckey.code == code {
@@ -110,3 +105,50 @@ impl Keyboard {
 pub trait HandleKey {
     fn handle_key(&mut self, key: Key);
 }
+
+/// scancodes recived by the interrupt handler waiting to be processed by `PS2Keyboard::poll`
+static PENDING_SCANCODES: Mutex<Deque<u8, MAX_PENDING_SCANCODES>> = Mutex::new(Deque::new());
+static PS2_POLL_ID: Once<PollId> = Once::new();
+
+/// the ps/2 keyboard, the interrupt handler only queues the scancodes and wakes it up
+/// the keys are then processed outside of interrupt context
+pub struct PS2Keyboard;
+
+impl PolledDriver for PS2Keyboard {
+    fn name(&self) -> &'static str {
+        "ps2-keyboard"
+    }
+
+    fn poll(&self) {
+        loop {
+            // the interrupt handler must never find the queue locked
+            let scancode = without_interrupts(|| PENDING_SCANCODES.lock().pop_front());
+
+            let Some(scancode) = scancode else {
+                break;
+            };
+
+            let encoded = KEYBOARD.write().handle_ps2_set_1(scancode);
+            if encoded != Key::NULL_KEY {
+                super::input::push_key(encoded);
+            }
+        }
+    }
+}
+
+/// registers the ps/2 keyboard to be polled
+pub fn init() {
+    PS2_POLL_ID.call_once(|| poll::register(&PS2Keyboard));
+}
+
+/// queues a scancode recived from the ps/2 keyboard, called by the interrupt handler
+pub fn push_scancode(scancode: u8) {
+    if let Some(mut pending) = PENDING_SCANCODES.try_lock() {
+        // drops the scancode if there is too much pending
+        _ = pending.push_back(scancode);
+    }
+
+    if let Some(id) = PS2_POLL_ID.get() {
+        poll::wake(*id);
+    }
+}
@@ -1,4 +1,9 @@
 pub mod framebuffer;
+pub mod input;
 pub mod keyboard;
 pub mod keymapper;
+pub mod pci;
+pub mod poll;
+pub mod surface;
 pub mod vfs;
+pub mod virtio;
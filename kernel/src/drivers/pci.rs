@@ -0,0 +1,288 @@
+//! PCI express devices, enumerated by brute forcing the memory mapped configuration spaces (ECAM)
+//! described by the MCFG table
+//! every MSI-X entry enabled with `PciDevice::enable_msix` is an irq, numbered in the order they were
+//! enabled, that can be moved to another cpu with `set_affinity`
+use core::fmt::Write;
+
+use alloc::{string::String, vec::Vec};
+use spin::RwLock;
+
+use crate::{
+    arch::{
+        self,
+        cpu::{CPUState, CPUS},
+    },
+    debug, hddm,
+    memory::{page_allocator::GLOBAL_PAGE_ALLOCATOR, PhysAddr, VirtAddr},
+    utils::errors::ErrorStatus,
+};
+
+const VENDOR_ID: usize = 0x00;
+const DEVICE_ID: usize = 0x02;
+const COMMAND: usize = 0x04;
+const STATUS: usize = 0x06;
+const HEADER_TYPE: usize = 0x0E;
+const BAR0: usize = 0x10;
+const CAPABILITIES_POINTER: usize = 0x34;
+
+const COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+const COMMAND_INTERRUPT_DISABLE: u16 = 1 << 10;
+const STATUS_CAPABILITIES_LIST: u16 = 1 << 4;
+
+const CAP_MSIX: u8 = 0x11;
+const MSIX_ENABLE: u16 = 1 << 15;
+const MSIX_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_ENTRY_SIZE: usize = 16;
+const MSIX_VECTOR_MASKED: u32 = 1;
+
+/// called on every PCI interrupt, the vector is shared so each handler has to check if it's device
+/// has anything to do
+static INTERRUPT_HANDLERS: RwLock<Vec<fn()>> = RwLock::new(Vec::new());
+/// the enabled MSI-X entries, the index of an entry is it's irq number
+static MSIX_ENTRIES: RwLock<Vec<MsixEntry>> = RwLock::new(Vec::new());
+
+struct MsixEntry {
+    name: &'static str,
+    device: PciDevice,
+    /// the virtual address of the entry in the MSI-X table
+    entry: VirtAddr,
+    /// the id of the cpu the entry interrupts
+    cpu: usize,
+}
+
+/// writes a message to the MSI-X table entry at `entry`, it's masked while it's written so the device
+/// never sees half of the new message
+unsafe fn write_msix_entry(entry: VirtAddr, (address, data): (u64, u32)) {
+    let ptr = entry as *mut u32;
+    unsafe {
+        ptr.add(3).write_volatile(MSIX_VECTOR_MASKED);
+        ptr.write_volatile(address as u32);
+        ptr.add(1).write_volatile((address >> 32) as u32);
+        ptr.add(2).write_volatile(data);
+        ptr.add(3).write_volatile(0);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    /// the virtual address of the function's configuration space
+    config: VirtAddr,
+}
+
+impl PciDevice {
+    #[inline(always)]
+    pub fn read_u8(&self, offset: usize) -> u8 {
+        unsafe { core::ptr::read_volatile((self.config + offset) as *const u8) }
+    }
+
+    #[inline(always)]
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        unsafe { core::ptr::read_volatile((self.config + offset) as *const u16) }
+    }
+
+    #[inline(always)]
+    pub fn read_u32(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.config + offset) as *const u32) }
+    }
+
+    #[inline(always)]
+    pub fn write_u16(&self, offset: usize, value: u16) {
+        unsafe { core::ptr::write_volatile((self.config + offset) as *mut u16, value) }
+    }
+
+    /// lets the device access memory and respond to memory accesses,
+    /// legacy interrupts are disabled because nothing handles them yet
+    pub fn enable_bus_master(&self) {
+        let command = self.read_u16(COMMAND);
+        self.write_u16(
+            COMMAND,
+            command | COMMAND_MEMORY_SPACE | COMMAND_BUS_MASTER | COMMAND_INTERRUPT_DISABLE,
+        );
+    }
+
+    /// returns the physical address of the memory BAR number `index`
+    /// returns None if it isn't a memory BAR or `index` is invaild
+    pub fn bar(&self, index: u8) -> Option<PhysAddr> {
+        if index >= 6 {
+            return None;
+        }
+
+        let bar = self.read_u32(BAR0 + index as usize * 4);
+        // io space
+        if bar & 1 != 0 {
+            return None;
+        }
+
+        let low = (bar & !0xF) as PhysAddr;
+        // 64 bit BARs take the next BAR as the high half
+        if (bar >> 1) & 0b11 == 0b10 {
+            if index >= 5 {
+                return None;
+            }
+            let high = self.read_u32(BAR0 + (index as usize + 1) * 4) as PhysAddr;
+            return Some(low | (high << 32));
+        }
+
+        Some(low)
+    }
+
+    /// points the first MSI-X table entry to `arch::PCI_INTERRUPT_VECTOR` on the current cpu and
+    /// enables MSI-X, the entry is listed in proc:/interrupts as `name`
+    /// returns the entry index the device should use or None if the device doesn't support MSI-X
+    pub fn enable_msix(&self, name: &'static str) -> Option<u16> {
+        let (_, cap) = self.capabilities().find(|(id, _)| *id == CAP_MSIX)?;
+
+        let control = self.read_u16(cap + 2);
+        let table = self.read_u32(cap + 4);
+        let bar = self.bar((table & 0b111) as u8)?;
+        let table_offset = (table & !0b111) as usize;
+
+        let entry = GLOBAL_PAGE_ALLOCATOR
+            .lock()
+            .map_mmio(bar + table_offset, MSIX_ENTRY_SIZE)
+            .ok()? as VirtAddr;
+
+        unsafe { write_msix_entry(entry, arch::msi_message(arch::PCI_INTERRUPT_VECTOR)) };
+        self.write_u16(cap + 2, (control | MSIX_ENABLE) & !MSIX_FUNCTION_MASK);
+
+        MSIX_ENTRIES.write().push(MsixEntry {
+            name,
+            device: *self,
+            entry,
+            cpu: arch::cpu::current_id().unwrap_or(0),
+        });
+        Some(0)
+    }
+
+    /// iterates over the capabilities list, yields (capability id, offset in the configuration space)
+    pub fn capabilities(&self) -> impl Iterator<Item = (u8, usize)> + '_ {
+        let mut offset = if self.read_u16(STATUS) & STATUS_CAPABILITIES_LIST != 0 {
+            (self.read_u8(CAPABILITIES_POINTER) & 0xFC) as usize
+        } else {
+            0
+        };
+        // guards against looping forever over a corrupted list
+        let mut remaining = 48;
+
+        core::iter::from_fn(move || {
+            if offset == 0 || remaining == 0 {
+                return None;
+            }
+            remaining -= 1;
+
+            let results = (self.read_u8(offset), offset);
+            offset = (self.read_u8(offset + 1) & 0xFC) as usize;
+            Some(results)
+        })
+    }
+}
+
+/// returns all the PCI functions present
+pub fn devices() -> Vec<PciDevice> {
+    let mut devices = Vec::new();
+
+    for (base, start_bus, end_bus) in arch::pci_ecam_regions() {
+        for bus in start_bus..=end_bus {
+            for device in 0..32u8 {
+                for function in 0..8u8 {
+                    let config = (base
+                        + ((bus as PhysAddr) << 20)
+                        + ((device as PhysAddr) << 15)
+                        + ((function as PhysAddr) << 12))
+                        | hddm();
+
+                    let read_u16 = |offset: usize| unsafe {
+                        core::ptr::read_volatile((config + offset) as *const u16)
+                    };
+                    let vendor_id = read_u16(VENDOR_ID);
+                    if vendor_id == 0xFFFF {
+                        if function == 0 {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    let found = PciDevice {
+                        bus,
+                        device,
+                        function,
+                        vendor_id,
+                        device_id: read_u16(DEVICE_ID),
+                        config,
+                    };
+                    devices.push(found);
+
+                    // not a multi-function device
+                    if function == 0 && found.read_u8(HEADER_TYPE) & 0x80 == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    debug!(PciDevice, "found {} PCI functions", devices.len());
+    devices
+}
+
+/// registers `handler` to be called on every PCI interrupt
+pub fn register_interrupt_handler(handler: fn()) {
+    arch::without_interrupts(|| INTERRUPT_HANDLERS.write().push(handler));
+}
+
+/// calls every registered interrupt handler, called from the PCI interrupt handler
+pub fn handle_interrupt() {
+    // only ever held for writing with interrupts disabled
+    let Some(handlers) = INTERRUPT_HANDLERS.try_read() else {
+        return;
+    };
+
+    for handler in handlers.iter() {
+        handler();
+    }
+}
+
+/// makes the irq `irq` interrupt the cpu `cpu` instead, for spreading the interrupts of busy devices
+/// fails with `ErrorStatus::InvaildResource` if either doesn't exist and `ErrorStatus::Busy` if the
+/// cpu is offline
+pub fn set_affinity(irq: usize, cpu: usize) -> Result<(), ErrorStatus> {
+    let apic_id = {
+        let cpus = CPUS.read();
+        let cpu = cpus.get(cpu).ok_or(ErrorStatus::InvaildResource)?;
+        if cpu.state != CPUState::Online {
+            return Err(ErrorStatus::Busy);
+        }
+        cpu.apic_id
+    };
+
+    let mut entries = MSIX_ENTRIES.write();
+    let entry = entries.get_mut(irq).ok_or(ErrorStatus::InvaildResource)?;
+    let message = arch::msi_message_to(arch::PCI_INTERRUPT_VECTOR, apic_id);
+    unsafe { write_msix_entry(entry.entry, message) };
+    entry.cpu = cpu;
+
+    debug!(PciDevice, "irq {irq} ({}) moved to cpu {cpu}", entry.name);
+    Ok(())
+}
+
+/// the irqs and the cpus they interrupt, part of proc:/interrupts
+pub fn irqs_info() -> String {
+    let mut results = String::new();
+
+    for (irq, entry) in MSIX_ENTRIES.read().iter().enumerate() {
+        let device = &entry.device;
+        _ = writeln!(
+            results,
+            "irq {irq}: {} {:02x}:{:02x}.{} cpu {}",
+            entry.name, device.bus, device.device, device.function, entry.cpu
+        );
+    }
+
+    results
+}
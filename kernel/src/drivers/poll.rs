@@ -0,0 +1,148 @@
+//! drivers that needs to do work outside of interrupt context
+//! they are polled by Eve's idle loop either every `poll_interval` ticks or once woken up by their
+//! interrupt handler using `wake`
+use core::{
+    arch::x86_64::_rdtsc,
+    fmt::Write,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use alloc::{string::String, vec::Vec};
+use spin::RwLock;
+
+use crate::threading;
+
+pub trait PolledDriver: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// the driver is polled every returned amount of scheduler ticks,
+    /// `None` if it should only be polled once woken up
+    fn poll_interval(&self) -> Option<u64> {
+        None
+    }
+    fn poll(&self);
+}
+
+/// an id returned by `register` used to wake up the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollId(usize);
+
+struct PolledEntry {
+    driver: &'static dyn PolledDriver,
+    woken: AtomicBool,
+    /// the tsc value at the time the driver was woken up
+    woken_at: AtomicU64,
+    last_poll_tick: AtomicU64,
+
+    polls: AtomicU64,
+    /// tsc cycles spent in `poll`
+    poll_cycles: AtomicU64,
+    /// the maximum amount of tsc cycles between a wake up and the poll that handled it
+    max_latency: AtomicU64,
+}
+
+static POLLED_DRIVERS: RwLock<Vec<PolledEntry>> = RwLock::new(Vec::new());
+/// set before shutting down or rebooting, no more polling happens after it is set
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// registers a driver to be polled
+pub fn register(driver: &'static dyn PolledDriver) -> PollId {
+    let mut drivers = POLLED_DRIVERS.write();
+    drivers.push(PolledEntry {
+        driver,
+        woken: AtomicBool::new(false),
+        woken_at: AtomicU64::new(0),
+        last_poll_tick: AtomicU64::new(0),
+        polls: AtomicU64::new(0),
+        poll_cycles: AtomicU64::new(0),
+        max_latency: AtomicU64::new(0),
+    });
+
+    PollId(drivers.len() - 1)
+}
+
+/// wakes up the driver with `id` so it gets polled as soon as possible
+/// safe to call from interrupt handlers
+pub fn wake(id: PollId) {
+    // the lock may be held by the poller, it is only ever held for reading after init
+    let Some(drivers) = POLLED_DRIVERS.try_read() else {
+        return;
+    };
+
+    if let Some(entry) = drivers.get(id.0) {
+        if !entry.woken.swap(true, Ordering::AcqRel) {
+            entry.woken_at.store(unsafe { _rdtsc() }, Ordering::Relaxed);
+        }
+    }
+}
+
+/// polls every driver that was woken up or it's interval has elapsed
+pub fn poll() {
+    if STOPPED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let ticks = threading::ticks();
+
+    for entry in POLLED_DRIVERS.read().iter() {
+        let woken = entry.woken.swap(false, Ordering::AcqRel);
+        let elapsed = entry.driver.poll_interval().is_some_and(|interval| {
+            ticks - entry.last_poll_tick.load(Ordering::Relaxed) >= interval
+        });
+
+        if !woken && !elapsed {
+            continue;
+        }
+
+        let start = unsafe { _rdtsc() };
+        if woken {
+            let latency = start.saturating_sub(entry.woken_at.load(Ordering::Relaxed));
+            entry.max_latency.fetch_max(latency, Ordering::Relaxed);
+        }
+
+        entry.driver.poll();
+
+        let end = unsafe { _rdtsc() };
+        entry.poll_cycles.fetch_add(end - start, Ordering::Relaxed);
+        entry.polls.fetch_add(1, Ordering::Relaxed);
+        entry.last_poll_tick.store(ticks, Ordering::Relaxed);
+    }
+}
+
+/// wether or not a driver has to be polled periodically, if so the scheduler tick cannot be stopped
+pub fn needs_tick() -> bool {
+    POLLED_DRIVERS
+        .read()
+        .iter()
+        .any(|entry| entry.driver.poll_interval().is_some())
+}
+
+/// stops polling the drivers, called before shutting down or rebooting
+pub fn stop() {
+    STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// generates the content of proc:/drivers
+pub fn drivers_info() -> String {
+    let mut results = String::new();
+
+    for entry in POLLED_DRIVERS.read().iter() {
+        _ = writeln!(results, "{}:", entry.driver.name());
+        match entry.driver.poll_interval() {
+            Some(interval) => _ = writeln!(results, "  interval: {} ticks", interval),
+            None => _ = writeln!(results, "  interval: none, woken up by interrupts"),
+        }
+        _ = writeln!(results, "  polls: {}", entry.polls.load(Ordering::Relaxed));
+        _ = writeln!(
+            results,
+            "  poll time: {} cycles",
+            entry.poll_cycles.load(Ordering::Relaxed)
+        );
+        _ = writeln!(
+            results,
+            "  max wake latency: {} cycles",
+            entry.max_latency.load(Ordering::Relaxed)
+        );
+    }
+
+    results
+}
@@ -0,0 +1,319 @@
+//! surfaces, shared pixel buffers for a userspace window server
+//! a process creates a surface, maps it and draws to it, then passes it's id to the compositor (a
+//! process with `Capabilities::COMPOSITOR`) by any means, the compositor opens it by id, maps it and
+//! composes it to the display
+//! both ends are notified through `poll` and event queues, the compositor's handle is readable after
+//! the owner commits a frame and the owner's handle is readable after the next present (vsync)
+//! the memory of a surface is charged to the process that created it until the surface is freed, see
+//! `MAX_PROCESS_SURFACE_BYTES`
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    drivers::{framebuffer::FRAMEBUFFER_DRIVER, vfs::PollEvents},
+    hddm,
+    memory::{
+        frame_allocator::{self, Frame},
+        paging::PAGE_SIZE,
+    },
+    threading::{
+        self,
+        expose::has_capabilities,
+        processes::Capabilities,
+        resources::{self, Resource},
+        waitqueue::WaitQueue,
+    },
+    utils::{display::RGB, errors::ErrorStatus},
+};
+
+/// the maximum width and height of a surface in pixels
+pub const MAX_SURFACE_SIZE: usize = 8192;
+/// the maximum amount of bytes the surfaces created by a single process can take at once, a surface
+/// stops counting once it is freed and not when it's owner's handle is closed since the compositor may
+/// still have it open
+pub const MAX_PROCESS_SURFACE_BYTES: usize = 64 * 1024 * 1024;
+/// pixels are 0x00RRGGBB
+pub const BYTES_PER_PIXEL: usize = 4;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+/// the amount of presents done so far, the vsync counter
+static PRESENTS: AtomicUsize = AtomicUsize::new(0);
+/// woken up on every present and commit, shared by all surfaces since the compositor waits on most of them
+static WAITERS: WaitQueue = WaitQueue::new();
+
+lazy_static! {
+    /// all the surfaces by id, surfaces are freed when their last handle and mapping is gone
+    static ref SURFACES: Mutex<BTreeMap<usize, Weak<Surface>>> = Mutex::new(BTreeMap::new());
+    /// the amount of bytes taken by the surfaces of each process that weren't freed yet by pid
+    static ref SURFACE_BYTES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+}
+
+/// charges `bytes` to the surfaces of `pid`, fails with `ErrorStatus::OutOfMemory` if it would go over
+/// `MAX_PROCESS_SURFACE_BYTES`
+fn charge(pid: usize, bytes: usize) -> Result<(), ErrorStatus> {
+    let mut surface_bytes = SURFACE_BYTES.lock();
+    let charged = surface_bytes.entry(pid).or_insert(0);
+
+    if *charged + bytes > MAX_PROCESS_SURFACE_BYTES {
+        if *charged == 0 {
+            surface_bytes.remove(&pid);
+        }
+        return Err(ErrorStatus::OutOfMemory);
+    }
+
+    *charged += bytes;
+    Ok(())
+}
+
+/// releases `bytes` charged to `pid` with `charge`
+fn release(pid: usize, bytes: usize) {
+    let mut surface_bytes = SURFACE_BYTES.lock();
+    if let Some(charged) = surface_bytes.get_mut(&pid) {
+        *charged -= bytes;
+        if *charged == 0 {
+            surface_bytes.remove(&pid);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Surface {
+    pub id: usize,
+    pub width: usize,
+    pub height: usize,
+    /// the process the surface is charged to
+    owner: usize,
+    frames: Vec<Frame>,
+    /// the amount of frames the owner committed so far
+    commits: AtomicUsize,
+}
+
+impl Surface {
+    /// allocates a zeroed `width`x`height` surface charged to `owner`, see `charge`
+    /// returns Err(ErrorStatus::OutOfMemory) if out of memory or `owner` has too many surfaces
+    fn new(width: usize, height: usize, owner: usize) -> Result<Self, ErrorStatus> {
+        let page_count = (width * height * BYTES_PER_PIXEL).div_ceil(PAGE_SIZE);
+        charge(owner, page_count * PAGE_SIZE)?;
+        let mut frames = Vec::with_capacity(page_count);
+
+        for _ in 0..page_count {
+            let Some(frame) = frame_allocator::allocate_frame() else {
+                frames
+                    .into_iter()
+                    .for_each(frame_allocator::deallocate_frame);
+                release(owner, page_count * PAGE_SIZE);
+                return Err(ErrorStatus::OutOfMemory);
+            };
+
+            unsafe {
+                core::ptr::write_bytes((frame.start_address | hddm()) as *mut u8, 0, PAGE_SIZE);
+            }
+            frames.push(frame);
+        }
+
+        Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            width,
+            height,
+            owner,
+            frames,
+            commits: AtomicUsize::new(0),
+        })
+    }
+
+    /// the frames backing the surface in order
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// returns the pixel at `x`, `y`
+    fn pixel(&self, x: usize, y: usize) -> RGB {
+        let offset = (x + y * self.width) * BYTES_PER_PIXEL;
+        let frame = self.frames[offset / PAGE_SIZE];
+        let addr = (frame.start_address + offset % PAGE_SIZE) | hddm();
+
+        let pixel = unsafe { core::ptr::read_volatile(addr as *const u32) };
+        RGB::new((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        release(self.owner, self.frames.len() * PAGE_SIZE);
+        self.frames
+            .drain(..)
+            .for_each(frame_allocator::deallocate_frame);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceRole {
+    /// the handle returned by `create`
+    Owner,
+    /// the handle returned by `open`
+    Compositor,
+}
+
+/// a surface resource
+#[derive(Debug, Clone)]
+pub struct SurfaceHandle {
+    surface: Arc<Surface>,
+    role: SurfaceRole,
+    /// the counter value this handle last acknowledged, for the owner it is the presents and for the
+    /// compositor it is the commits
+    seen: usize,
+}
+
+impl SurfaceHandle {
+    /// the handle is readable if there is something that wasn't acknowledged with `flush` yet
+    pub fn poll(&self) -> PollEvents {
+        let current = match self.role {
+            SurfaceRole::Owner => PRESENTS.load(Ordering::Acquire),
+            SurfaceRole::Compositor => self.surface.commits.load(Ordering::Acquire),
+        };
+
+        if current != self.seen {
+            PollEvents::READABLE
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    /// see `InodeOps::wait_queue`
+    pub fn wait_queue(&self) -> &'static WaitQueue {
+        &WAITERS
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct SurfaceInfo {
+    pub id: usize,
+    pub width: usize,
+    pub height: usize,
+    /// where the surface is mapped in the calling process
+    pub addr: usize,
+}
+
+/// executes `then` on the surface resource `ri`
+fn with_surface<T, R>(ri: usize, then: T) -> Result<R, ErrorStatus>
+where
+    T: FnOnce(&mut SurfaceHandle) -> R,
+{
+    resources::with_resource(ri, |resource| {
+        if let Resource::Surface(handle) = resource {
+            Ok(then(handle))
+        } else {
+            Err(ErrorStatus::InvaildResource)
+        }
+    })
+    .ok_or(ErrorStatus::InvaildResource)?
+}
+
+/// creates a new `width`x`height` surface owned by the current process, returns it's ri
+/// fails with `ErrorStatus::OutOfMemory` if the surface would take the current process over `MAX_PROCESS_SURFACE_BYTES`
+pub fn create(width: usize, height: usize) -> Result<usize, ErrorStatus> {
+    if width == 0 || height == 0 || width > MAX_SURFACE_SIZE || height > MAX_SURFACE_SIZE {
+        return Err(ErrorStatus::NotSupported);
+    }
+
+    let surface = Arc::new(Surface::new(width, height, threading::expose::getpid())?);
+
+    let mut surfaces = SURFACES.lock();
+    surfaces.retain(|_, surface| surface.strong_count() > 0);
+    surfaces.insert(surface.id, Arc::downgrade(&surface));
+    drop(surfaces);
+
+    Ok(resources::add_resource(Resource::Surface(SurfaceHandle {
+        surface,
+        role: SurfaceRole::Owner,
+        seen: PRESENTS.load(Ordering::Acquire),
+    })))
+}
+
+/// opens the surface `id` as a compositor, returns the ri of the handle
+pub fn open(id: usize) -> Result<usize, ErrorStatus> {
+    if !has_capabilities(Capabilities::COMPOSITOR) {
+        return Err(ErrorStatus::MissingPermissions);
+    }
+
+    let surface = SURFACES
+        .lock()
+        .get(&id)
+        .and_then(Weak::upgrade)
+        .ok_or(ErrorStatus::InvaildResource)?;
+
+    let seen = surface.commits.load(Ordering::Acquire);
+    Ok(resources::add_resource(Resource::Surface(SurfaceHandle {
+        surface,
+        role: SurfaceRole::Compositor,
+        seen,
+    })))
+}
+
+/// maps the surface `ri` in the current process
+pub fn map(ri: usize) -> Result<SurfaceInfo, ErrorStatus> {
+    let surface = with_surface(ri, |handle| handle.surface.clone())?;
+    let (id, width, height) = (surface.id, surface.width, surface.height);
+
+    let addr = threading::expose::map_surface(surface).map_err(|_| ErrorStatus::OutOfMemory)?;
+
+    Ok(SurfaceInfo {
+        id,
+        width,
+        height,
+        addr,
+    })
+}
+
+/// for the owner commits a new frame and acknowledges the last present,
+/// for the compositor acknowledges the last commit
+pub fn flush(ri: usize) -> Result<(), ErrorStatus> {
+    let role = with_surface(ri, |handle| {
+        match handle.role {
+            SurfaceRole::Owner => {
+                handle.surface.commits.fetch_add(1, Ordering::AcqRel);
+                handle.seen = PRESENTS.load(Ordering::Acquire);
+            }
+            SurfaceRole::Compositor => {
+                handle.seen = handle.surface.commits.load(Ordering::Acquire);
+            }
+        }
+        handle.role
+    })?;
+
+    if role == SurfaceRole::Owner {
+        WAITERS.wake_all();
+    }
+    Ok(())
+}
+
+/// draws the surface `ri` to the top left of the display, ends the frame waking up the owners
+/// waiting for a vsync
+pub fn present(ri: usize) -> Result<(), ErrorStatus> {
+    if !has_capabilities(Capabilities::COMPOSITOR) {
+        return Err(ErrorStatus::MissingPermissions);
+    }
+
+    let surface = with_surface(ri, |handle| handle.surface.clone())?;
+
+    let mut framebuffer = FRAMEBUFFER_DRIVER.write();
+    // the top of the displayed part of the buffer
+    let y = framebuffer.get_cursor() / framebuffer.width();
+    framebuffer.blit(0, y, surface.width, surface.height, |col, row| {
+        surface.pixel(col, row)
+    });
+    framebuffer.sync_pixels();
+    drop(framebuffer);
+
+    PRESENTS.fetch_add(1, Ordering::AcqRel);
+    WAITERS.wake_all();
+    Ok(())
+}
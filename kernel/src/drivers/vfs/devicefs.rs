@@ -1,13 +1,62 @@
 use alloc::{
+    format,
     string::{String, ToString},
     sync::Arc,
     vec::Vec,
 };
 use spin::Mutex;
 
-use crate::devices::{Device, DEVICE_MANAGER};
+use crate::{
+    devices::{Device, DeviceEvent, DEVICE_MANAGER, EVENT_WAITERS},
+    threading::{expose::has_capabilities, processes::Capabilities, waitqueue::WaitQueue},
+};
+
+use super::{FSError, FSResult, FileDescriptor, Inode, InodeOps, InodeType, Path, PollEvents, FS};
+
+/// dev:/events, reading it gives a `add <name>` or `remove <name>` line for every device registered
+/// or unregistered since it was opened, or `lost <amount>` if the reader didn't keep up
+const EVENTS_INODE: usize = 1;
+const EVENTS_NAME: &str = "events";
+
+/// the directories devices can be put in by naming them `<directory>/<name>`
+const DIRECTORIES: &[&str] = &["input"];
+/// the inode id of `DIRECTORIES[index]` is `DIRECTORY_INODES | index`
+const DIRECTORY_INODES: usize = 1 << 63;
+
+/// the inode id of the device `id`
+fn device_inode(id: usize) -> usize {
+    id + EVENTS_INODE
+}
+
+/// the device `inodeid` is the inode of, None for the root, dev:/events and the directories
+fn device_of(inodeid: usize) -> Option<&'static dyn Device> {
+    if inodeid <= EVENTS_INODE || inodeid & DIRECTORY_INODES != 0 {
+        return None;
+    }
+    DEVICE_MANAGER.lock().get_device(inodeid - EVENTS_INODE)
+}
+
+/// the name of the device named `name` in `directory` (an index in `DIRECTORIES`, None for the root),
+/// None if it isn't in it
+fn name_in(directory: Option<usize>, name: &str) -> Option<&str> {
+    match (directory, name.split_once('/')) {
+        (None, None) => Some(name),
+        (Some(index), Some((dir, name))) if DIRECTORIES[index] == dir => Some(name),
+        _ => None,
+    }
+}
 
-use super::{DirIter, FSResult, FileDescriptor, Inode, InodeOps, InodeType, Path, FS};
+/// the inode ids of the devices in `directory` (see `name_in`) after `cursor`
+fn devices_in(directory: Option<usize>, cursor: usize, max: usize) -> Vec<usize> {
+    let manager = DEVICE_MANAGER.lock();
+    manager
+        .devices()
+        .filter(|(_, device)| name_in(directory, Device::name(*device)).is_some())
+        .map(|(id, _)| device_inode(id))
+        .filter(|inodeid| *inodeid > cursor)
+        .take(max)
+        .collect()
+}
 
 pub struct DeviceManagerInode;
 impl InodeOps for Mutex<DeviceManagerInode> {
@@ -24,45 +73,99 @@ impl InodeOps for Mutex<DeviceManagerInode> {
     }
 
     fn contains(&self, name: &str) -> bool {
-        for device in DEVICE_MANAGER.lock().devices().iter() {
-            if Device::name(*device) == name {
-                return true;
-            }
-        }
-        false
+        self.get(name).is_ok()
     }
 
     fn get(&self, name: &str) -> crate::drivers::vfs::FSResult<usize> {
-        for (i, device) in DEVICE_MANAGER.lock().devices().iter().enumerate() {
-            if Device::name(*device) == name {
-                return Ok(i + 1);
-            }
+        if name == EVENTS_NAME {
+            return Ok(EVENTS_INODE);
+        }
+
+        if let Some(index) = DIRECTORIES.iter().position(|directory| *directory == name) {
+            return Ok(DIRECTORY_INODES | index);
         }
-        Err(super::FSError::NoSuchAFileOrDirectory)
+
+        DEVICE_MANAGER
+            .lock()
+            .find(name)
+            .map(device_inode)
+            .ok_or(super::FSError::NoSuchAFileOrDirectory)
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        let devices = devices_in(None, cursor, max);
+        let directories = (0..DIRECTORIES.len()).map(|index| DIRECTORY_INODES | index);
+
+        Ok(core::iter::once(EVENTS_INODE)
+            .chain(devices)
+            .chain(directories)
+            .filter(|inodeid| *inodeid > cursor)
+            .take(max)
+            .collect())
+    }
+}
+
+/// one of `DIRECTORIES`
+pub struct DeviceDirInode(usize);
+
+impl InodeOps for Mutex<DeviceDirInode> {
+    fn inodeid(&self) -> usize {
+        DIRECTORY_INODES | self.lock().0
+    }
+
+    fn kind(&self) -> InodeType {
+        InodeType::Directory
+    }
+
+    fn name(&self) -> String {
+        DIRECTORIES[self.lock().0].to_string()
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        if name == ".." {
+            return Ok(0);
+        }
+
+        let path = format!("{}/{}", DIRECTORIES[self.lock().0], name);
+        DEVICE_MANAGER
+            .lock()
+            .find(&path)
+            .map(device_inode)
+            .ok_or(FSError::NoSuchAFileOrDirectory)
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        Ok(devices_in(Some(self.lock().0), cursor, max))
     }
 }
 
 #[derive(Clone)]
 pub struct DeviceInode {
     inodeid: usize,
+    name: &'static str,
 }
 
 impl DeviceInode {
-    pub fn create(inodeid: usize) -> Inode {
-        Arc::new(Mutex::new(Self { inodeid }))
+    pub fn create(inodeid: usize, name: &'static str) -> Inode {
+        Arc::new(Mutex::new(Self { inodeid, name }))
     }
 
-    pub fn device(&self) -> &'static dyn Device {
+    /// fails if the device was unregistered since it was opened
+    pub fn device(&self) -> FSResult<&'static dyn Device> {
         DEVICE_MANAGER
             .lock()
-            .get_device_at(self.inodeid - 1)
-            .unwrap()
+            .get_device(self.inodeid - EVENTS_INODE)
+            .ok_or(FSError::NoSuchAFileOrDirectory)
     }
 }
 
 impl InodeOps for Mutex<DeviceInode> {
     fn name(&self) -> String {
-        Device::name(self.lock().device()).to_string()
+        self.lock().name.to_string()
     }
 
     fn inodeid(&self) -> usize {
@@ -74,11 +177,105 @@ impl InodeOps for Mutex<DeviceInode> {
     }
 
     fn read(&self, buffer: &mut [u8], offset: usize, count: usize) -> FSResult<usize> {
-        self.lock().device().read(buffer, offset, count)
+        let device = self.lock().device()?;
+        device.read(buffer, offset, count)
     }
 
     fn write(&self, buffer: &[u8], offset: usize) -> FSResult<usize> {
-        self.lock().device().write(buffer, offset)
+        let device = self.lock().device()?;
+        device.write(buffer, offset)
+    }
+
+    /// an unregistered device is always ready so waiters wake up and get the error
+    fn poll(&self) -> PollEvents {
+        let device = self.lock().device();
+        device.map_or(PollEvents::all(), |device| device.poll())
+    }
+
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        let device = self.lock().device().ok()?;
+        device.wait_queue()
+    }
+
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        let device = self.lock().device()?;
+        device.ctl(cmd, input, output)
+    }
+}
+
+/// every open of dev:/events gets it's own inode so every reader has it's own cursor
+pub struct DeviceEventsInode {
+    /// the number of the next event to read
+    cursor: usize,
+}
+
+impl DeviceEventsInode {
+    pub fn create() -> Inode {
+        let cursor = DEVICE_MANAGER.lock().events_end();
+        Arc::new(Mutex::new(Self { cursor }))
+    }
+}
+
+impl InodeOps for Mutex<DeviceEventsInode> {
+    fn name(&self) -> String {
+        EVENTS_NAME.to_string()
+    }
+
+    fn inodeid(&self) -> usize {
+        EVENTS_INODE
+    }
+
+    fn kind(&self) -> InodeType {
+        InodeType::Device
+    }
+
+    /// reads as many whole lines as fit in `buffer`, waits if there are no new events
+    fn read(&self, buffer: &mut [u8], _offset: usize, _count: usize) -> FSResult<usize> {
+        let mut inode = self.lock();
+        let manager = DEVICE_MANAGER.lock();
+        let mut written = 0;
+
+        while let Some((number, event)) = manager.event(inode.cursor) {
+            let line = match event {
+                _ if number != inode.cursor => format!("lost {}\n", number - inode.cursor),
+                DeviceEvent::Added(name) => format!("add {}\n", name),
+                DeviceEvent::Removed(name) => format!("remove {}\n", name),
+            };
+
+            if line.len() > buffer.len() - written {
+                if written == 0 {
+                    return Err(FSError::InvaildArgument);
+                }
+                break;
+            }
+
+            buffer[written..written + line.len()].copy_from_slice(line.as_bytes());
+            written += line.len();
+            // a `lost` line is followed by the event it was found at
+            inode.cursor = if number != inode.cursor {
+                number
+            } else {
+                number + 1
+            };
+        }
+
+        if written == 0 {
+            Err(FSError::ResourceBusy)
+        } else {
+            Ok(written)
+        }
+    }
+
+    fn poll(&self) -> PollEvents {
+        if self.lock().cursor < DEVICE_MANAGER.lock().events_end() {
+            PollEvents::READABLE
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        Some(&EVENT_WAITERS)
     }
 }
 
@@ -108,17 +305,32 @@ impl FS for DeviceFS {
             return Ok(Some(self.root_inode.clone()));
         }
 
-        for (i, _) in DEVICE_MANAGER.lock().devices().iter().enumerate() {
-            if i == inode_id - 1 {
-                return Ok(Some(DeviceInode::create(inode_id)));
-            }
+        if inode_id == EVENTS_INODE {
+            return Ok(Some(DeviceEventsInode::create()));
+        }
+
+        if inode_id & DIRECTORY_INODES != 0 {
+            let index = inode_id & !DIRECTORY_INODES;
+            return Ok((index < DIRECTORIES.len())
+                .then(|| Arc::new(Mutex::new(DeviceDirInode(index))) as Inode));
         }
 
-        Ok(None)
+        let device = DEVICE_MANAGER.lock().get_device(inode_id - EVENTS_INODE);
+        // a device in a directory is named after the last part of it's name
+        Ok(device.map(|device| {
+            let name = Device::name(device);
+            DeviceInode::create(inode_id, name.rsplit('/').next().unwrap_or(name))
+        }))
     }
 
     fn open(&self, path: Path) -> FSResult<FileDescriptor> {
         let resolved = self.reslove_path(path)?;
+        if device_of(resolved.inodeid()).is_some_and(|device| device.raw())
+            && !has_capabilities(Capabilities::RAW_DEVICES)
+        {
+            return Err(FSError::MissingPermissions);
+        }
+
         Ok(FileDescriptor::new(
             self as *const Self as *mut Self,
             resolved.clone(),
@@ -132,18 +344,4 @@ impl FS for DeviceFS {
     fn read(&self, file_descriptor: &mut FileDescriptor, buffer: &mut [u8]) -> FSResult<usize> {
         file_descriptor.node.read(buffer, 0, 0)
     }
-
-    fn diriter_open(&self, _fd: &mut FileDescriptor) -> FSResult<DirIter> {
-        let length = DEVICE_MANAGER.lock().devices().len();
-
-        let mut inodeids = Vec::with_capacity(length);
-        for inodeid in 0..length {
-            inodeids.push(inodeid + 1);
-        }
-
-        Ok(DirIter::new(
-            self as *const DeviceFS as *mut DeviceFS,
-            inodeids.into_boxed_slice(),
-        ))
-    }
 }
@@ -0,0 +1,141 @@
+//! event queues, a resource that remembers which resources and events a process is interested in
+//! so that it can wait on all of them at once and get the ready ones in batches
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+use super::{FSError, FSResult, PollEvents};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InterestFlags: u8 {
+        /// only report events that became ready since the last wait instead of every time they are
+        /// ready (level triggered)
+        const EDGE_TRIGGERED = 1 << 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventQueueOp {
+    Add,
+    Modify,
+    Remove,
+}
+
+impl TryFrom<usize> for EventQueueOp {
+    type Error = ();
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Add),
+            1 => Ok(Self::Modify),
+            2 => Ok(Self::Remove),
+            _ => Err(()),
+        }
+    }
+}
+
+/// a ready resource returned by `EventQueue::collect`
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Event {
+    /// the data given when registering the interest
+    pub data: usize,
+    pub events: PollEvents,
+}
+
+#[derive(Debug, Clone)]
+struct Interest {
+    ri: usize,
+    events: PollEvents,
+    flags: InterestFlags,
+    data: usize,
+    /// the events that were ready the last time this interest was checked, used for edge triggering
+    last: PollEvents,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventQueue {
+    interests: Vec<Interest>,
+}
+
+impl EventQueue {
+    pub const fn new() -> Self {
+        Self {
+            interests: Vec::new(),
+        }
+    }
+
+    /// adds, modifies or removes the interest in the resource `ri`
+    pub fn ctl(
+        &mut self,
+        op: EventQueueOp,
+        ri: usize,
+        events: PollEvents,
+        flags: InterestFlags,
+        data: usize,
+    ) -> FSResult<()> {
+        let index = self.interests.iter().position(|interest| interest.ri == ri);
+
+        match (op, index) {
+            (EventQueueOp::Add, Some(_)) => Err(FSError::AlreadyExists),
+            (EventQueueOp::Add, None) => {
+                self.interests.push(Interest {
+                    ri,
+                    events,
+                    flags,
+                    data,
+                    last: PollEvents::empty(),
+                });
+                Ok(())
+            }
+            (EventQueueOp::Modify, Some(index)) => {
+                let interest = &mut self.interests[index];
+                interest.events = events;
+                interest.flags = flags;
+                interest.data = data;
+                interest.last = PollEvents::empty();
+                Ok(())
+            }
+            (EventQueueOp::Remove, Some(index)) => {
+                self.interests.remove(index);
+                Ok(())
+            }
+            (_, None) => Err(FSError::InvaildFileDescriptorOrRes),
+        }
+    }
+
+    /// the resources this queue is interested in, in the order `collect` expects their readiness in
+    pub fn resources(&self) -> Vec<usize> {
+        self.interests.iter().map(|interest| interest.ri).collect()
+    }
+
+    /// takes the current readiness of each resource (in the order of `resources`)
+    /// and writes the events to report to `events`, returns the amount of events written
+    pub fn collect(&mut self, readiness: &[PollEvents], events: &mut [Event]) -> usize {
+        let mut count = 0;
+
+        for (interest, ready) in self.interests.iter_mut().zip(readiness) {
+            if count >= events.len() {
+                break;
+            }
+
+            let ready = *ready & interest.events;
+            let reported = if interest.flags.contains(InterestFlags::EDGE_TRIGGERED) {
+                ready.difference(interest.last)
+            } else {
+                ready
+            };
+            interest.last = ready;
+
+            if !reported.is_empty() {
+                events[count] = Event {
+                    data: interest.data,
+                    events: reported,
+                };
+                count += 1;
+            }
+        }
+
+        count
+    }
+}
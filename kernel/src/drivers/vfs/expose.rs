@@ -2,9 +2,22 @@
 //! a resource index instead of a file descriptor aka ri
 use core::fmt::Debug;
 
-use crate::threading::resources::{self, with_resource, Resource};
+use alloc::{string::String, vec::Vec};
 
-use super::{FSError, FSResult, FileDescriptor, Inode, InodeType, Path, FS, VFS_STRUCT};
+use crate::threading::{
+    self,
+    resources::{self, with_resource, Resource},
+    timer::Timeout,
+    waitqueue::Waiter,
+};
+use crate::utils::types::FileName;
+
+use super::{
+    evq::{Event, EventQueue, EventQueueOp, InterestFlags},
+    flock::{LockOp, LockRange},
+    DirIter, FSError, FSResult, FileDescriptor, Inode, InodeType, OpenFlags, Path, PollEvents, FS,
+    VFS_STRUCT,
+};
 
 /// gets a FileDescriptor from a fd (file_descriptor id) may return Err(FSError::InvaildFileDescriptor)
 fn with_fd<T, R>(ri: usize, then: T) -> FSResult<R>
@@ -23,48 +36,193 @@ where
 
 #[no_mangle]
 pub fn open(path: Path) -> FSResult<usize> {
-    let fd = VFS_STRUCT
-        .try_read()
-        .ok_or(FSError::ResourceBusy)?
-        .open(path)?;
+    open_with(path, OpenFlags::empty())
+}
+
+/// opens `path` or creates an unnamed file in the directory `path` if `flags` has
+/// `OpenFlags::TMPFILE`, see `link`
+pub fn open_with(path: Path, flags: OpenFlags) -> FSResult<usize> {
+    let mut fd = if flags.contains(OpenFlags::TMPFILE) {
+        VFS_STRUCT
+            .try_write()
+            .ok_or(FSError::ResourceBusy)?
+            .create_unnamed(path)?
+    } else {
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .open(path)?
+    };
+    fd.flags = flags;
     Ok(resources::add_resource(Resource::File(fd)))
 }
 
+/// turns `FSError::ResourceBusy` into `FSError::WouldBlock` if `fd` is non-blocking
+/// so that the caller doesn't wait for it
+fn nonblocking<R>(fd: &FileDescriptor, results: FSResult<R>) -> FSResult<R> {
+    match results {
+        Err(FSError::ResourceBusy) if fd.flags.contains(OpenFlags::NONBLOCKING) => {
+            Err(FSError::WouldBlock)
+        }
+        results => results,
+    }
+}
+
 #[no_mangle]
+/// closes any kind of resource
 pub fn close(ri: usize) -> FSResult<()> {
-    with_fd(ri, |fd| {
-        VFS_STRUCT
+    with_resource(ri, |resource| match resource {
+        Resource::File(fd) => VFS_STRUCT
             .try_read()
             .ok_or(FSError::ResourceBusy)?
-            .close(fd)
-    })??;
+            .close(fd),
+        _ => Ok(()),
+    })
+    .ok_or(FSError::InvaildFileDescriptorOrRes)??;
 
     _ = resources::remove_resource(ri);
     Ok(())
 }
 
+#[no_mangle]
+/// duplicates the resource `ri` returns the ri of the duplicate
+/// the duplicate has it's own file position
+pub fn dup(ri: usize) -> FSResult<usize> {
+    resources::dup(ri).map_err(|_| FSError::InvaildFileDescriptorOrRes)
+}
+
+#[no_mangle]
+/// makes `dest_ri` a duplicate of `ri` closing whatever `dest_ri` was pointing to first
+/// returns the new ri of `dest_ri`
+pub fn dup2(ri: usize, dest_ri: usize) -> FSResult<usize> {
+    let (new_ri, removed) =
+        resources::dup2(ri, dest_ri).map_err(|_| FSError::InvaildFileDescriptorOrRes)?;
+
+    if let Some(Resource::File(mut fd)) = removed {
+        VFS_STRUCT.read().close(&mut fd)?;
+    }
+    Ok(new_ri)
+}
+
 #[no_mangle]
 pub fn read(ri: usize, buffer: &mut [u8]) -> FSResult<usize> {
     with_fd(ri, |fd| {
-        VFS_STRUCT
+        let results = VFS_STRUCT
             .try_read()
-            .ok_or(FSError::ResourceBusy)?
-            .read(fd, buffer)
+            .ok_or(FSError::ResourceBusy)
+            .and_then(|vfs| vfs.read(fd, buffer));
+        nonblocking(fd, results)
     })?
 }
 
 #[no_mangle]
 pub fn write(ri: usize, buffer: &[u8]) -> FSResult<usize> {
     with_fd(ri, |fd| {
-        VFS_STRUCT
+        let results = VFS_STRUCT
             .try_read()
-            .ok_or(FSError::ResourceBusy)?
-            .write(fd, buffer)
+            .ok_or(FSError::ResourceBusy)
+            .and_then(|vfs| vfs.write(fd, buffer));
+        nonblocking(fd, results)
+    })?
+}
+
+/// reads into each buffer of `buffers` in order, stops at the first short read
+/// returns the total amount of bytes read
+/// if a buffer fails after something was already read the amount read so far is returned instead of the error,
+/// so that a retry never reads the same data twice or drops it
+pub fn readv(ri: usize, buffers: &mut [&mut [u8]]) -> FSResult<usize> {
+    with_fd(ri, |fd| {
+        let results = VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)
+            .and_then(|vfs| {
+                let mut total = 0;
+
+                for buffer in buffers {
+                    let count = match vfs.read(fd, buffer) {
+                        Ok(count) => count,
+                        Err(_) if total > 0 => break,
+                        Err(err) => return Err(err),
+                    };
+                    total += count;
+
+                    if count < buffer.len() {
+                        break;
+                    }
+                }
+                Ok(total)
+            });
+        nonblocking(fd, results)
     })?
 }
 
+/// writes each buffer of `buffers` in order, stops at the first short write
+/// returns the total amount of bytes written
+/// like `readv` a failure after something was already written returns the amount written so far
+pub fn writev(ri: usize, buffers: &[&[u8]]) -> FSResult<usize> {
+    with_fd(ri, |fd| {
+        let results = VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)
+            .and_then(|vfs| {
+                let mut total = 0;
+
+                for buffer in buffers {
+                    let count = match vfs.write(fd, buffer) {
+                        Ok(count) => count,
+                        Err(_) if total > 0 => break,
+                        Err(err) => return Err(err),
+                    };
+                    total += count;
+
+                    if count < buffer.len() {
+                        break;
+                    }
+                }
+                Ok(total)
+            });
+        nonblocking(fd, results)
+    })?
+}
+
+/// copies up to `len` bytes from `src_ri` to `dest_ri` without going through userspace, stops at the end of `src_ri`
+/// returns the amount of bytes copied
+pub fn copy_file_range(src_ri: usize, dest_ri: usize, len: usize) -> FSResult<usize> {
+    const CHUNK_SIZE: usize = 4096;
+    // locked once so that we never fail with ResourceBusy after having copied something
+    let vfs = VFS_STRUCT.try_read().ok_or(FSError::ResourceBusy)?;
+
+    let mut buffer = [0u8; CHUNK_SIZE];
+    let mut copied = 0;
+
+    while copied < len {
+        let amount = (len - copied).min(CHUNK_SIZE);
+        let count = with_fd(src_ri, |fd| vfs.read(fd, &mut buffer[..amount]))??;
+        if count == 0 {
+            break;
+        }
+
+        with_fd(dest_ri, |fd| vfs.write(fd, &buffer[..count]))??;
+        copied += count;
+
+        if count < amount {
+            break;
+        }
+    }
+
+    Ok(copied)
+}
+
+/// fails with `FSError::NameTooLong` if the name `path` ends with doesn't fit in a `DirEntry`
+fn check_name(path: Path) -> FSResult<()> {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    FileName::try_new(name).map_err(|_| FSError::NameTooLong)?;
+    Ok(())
+}
+
 #[no_mangle]
 pub fn create(path: Path) -> FSResult<()> {
+    check_name(path)?;
     VFS_STRUCT
         .try_write()
         .ok_or(FSError::ResourceBusy)?
@@ -73,13 +231,136 @@ pub fn create(path: Path) -> FSResult<()> {
 
 #[no_mangle]
 pub fn createdir(path: Path) -> FSResult<()> {
+    check_name(path)?;
     VFS_STRUCT
         .try_write()
         .ok_or(FSError::ResourceBusy)?
         .createdir(path)
 }
 
+pub fn remove(path: Path) -> FSResult<()> {
+    VFS_STRUCT
+        .try_write()
+        .ok_or(FSError::ResourceBusy)?
+        .remove(path)
+}
+
+pub fn rename(old: Path, new: Path) -> FSResult<()> {
+    check_name(new)?;
+    VFS_STRUCT
+        .try_write()
+        .ok_or(FSError::ResourceBusy)?
+        .rename(old, new)
+}
+
+/// gives the unnamed file `ri` opened with `OpenFlags::TMPFILE` the name `path` replacing the file
+/// that has it, so a file can be written fully before it replaces another
+pub fn link(ri: usize, path: Path) -> FSResult<()> {
+    check_name(path)?;
+    with_fd(ri, |fd| {
+        VFS_STRUCT
+            .try_write()
+            .ok_or(FSError::ResourceBusy)?
+            .link(fd, path)
+    })?
+}
+
+/// binds `drive` to the directory `target` in the mount namespace of the current process, the
+/// processes it spawns afterwards see it too, see `super::namespace`
+pub fn bind(drive: &str, target: Path) -> FSResult<()> {
+    let target = VFS_STRUCT
+        .try_read()
+        .ok_or(FSError::ResourceBusy)?
+        .reslove_dir(target)?;
+    threading::expose::with_namespace_mut(|namespace| namespace.bind(drive, target))
+}
+
+/// removes the bind of `drive` from the mount namespace of the current process
+pub fn unbind(drive: &str) -> FSResult<()> {
+    threading::expose::with_namespace_mut(|namespace| namespace.unbind(drive))
+}
+
+/// takes or releases an advisory lock on `range` of the file `ri`, see `super::flock`
+/// fails with `FSError::ResourceBusy` if another file holds a conflicting lock or with
+/// `FSError::WouldBlock` instead if `ri` is non-blocking
+pub fn flock(ri: usize, op: LockOp, range: LockRange) -> FSResult<()> {
+    with_fd(ri, |fd| nonblocking(fd, super::flock::lock(fd, op, range)))?
+}
+
+/// exposes the directory `src` at `dst` for every process, see `VFS::bind`
+pub fn mount_bind(src: Path, dst: Path) -> FSResult<()> {
+    VFS_STRUCT
+        .try_write()
+        .ok_or(FSError::ResourceBusy)?
+        .bind(src, dst)
+}
+
+/// removes the bind mount at `dst`
+pub fn umount(dst: Path) -> FSResult<()> {
+    VFS_STRUCT
+        .try_write()
+        .ok_or(FSError::ResourceBusy)?
+        .umount(dst)
+}
+
 pub const MAX_NAME_LEN: usize = 128;
+/// the biggest value an extended attribute can have, their names are limited to `MAX_NAME_LEN`
+pub const MAX_XATTR_SIZE: usize = 4096;
+
+fn check_xattr_name(name: &str) -> FSResult<()> {
+    if name.is_empty() {
+        return Err(FSError::InvaildArgument);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(FSError::NameTooLong);
+    }
+    Ok(())
+}
+
+/// the value of the extended attribute `name` of `ri`, see `FS::get_xattr`
+pub fn get_xattr(ri: usize, name: &str) -> FSResult<Vec<u8>> {
+    check_xattr_name(name)?;
+    with_fd(ri, |fd| {
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .get_xattr(fd, name)
+    })?
+}
+
+/// fails with `FSError::NameTooLong` if `value` is bigger than `MAX_XATTR_SIZE`
+pub fn set_xattr(ri: usize, name: &str, value: &[u8]) -> FSResult<()> {
+    check_xattr_name(name)?;
+    if value.len() > MAX_XATTR_SIZE {
+        return Err(FSError::NameTooLong);
+    }
+
+    with_fd(ri, |fd| {
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .set_xattr(fd, name, value)
+    })?
+}
+
+pub fn list_xattrs(ri: usize) -> FSResult<Vec<String>> {
+    with_fd(ri, |fd| {
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .list_xattrs(fd)
+    })?
+}
+
+pub fn remove_xattr(ri: usize, name: &str) -> FSResult<()> {
+    check_xattr_name(name)?;
+    with_fd(ri, |fd| {
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .remove_xattr(fd, name)
+    })?
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[repr(C)]
@@ -87,27 +368,22 @@ pub struct DirEntry {
     pub kind: InodeType,
     pub size: usize,
     pub name_length: usize,
-    pub name: [u8; 128],
+    pub name: [u8; MAX_NAME_LEN],
 }
 
 impl DirEntry {
     pub fn get_from_inode(inode: Inode) -> Self {
-        let name = inode.name();
-        let name_slice = name.as_bytes();
+        // `check_name` keeps the names of created files short enough, the rest are truncated
+        let name = FileName::new_lossy(&inode.name());
 
         let kind = inode.kind();
         let size = inode.size().unwrap_or(0);
 
-        let name_length = name_slice.len();
-        let mut name = [0u8; MAX_NAME_LEN];
-
-        name[..name_length].copy_from_slice(name_slice);
-
         Self {
             kind,
             size,
-            name_length,
-            name,
+            name_length: name.len(),
+            name: name.to_padded(),
         }
     }
 
@@ -116,20 +392,160 @@ impl DirEntry {
     }
 }
 
+/// a resource to wait on with `poll`
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PollFd {
+    pub ri: usize,
+    /// the events to wait for
+    pub events: PollEvents,
+    /// the events that happened, set by `poll`
+    pub revents: PollEvents,
+}
+
+/// returns the operations that can be done on `ri` without waiting
+/// if `waiter` is given the wait queue of `ri` is added to it first, so that it is woken up once that changes
+fn poll_ready(ri: usize, waiter: Option<&mut Waiter>) -> FSResult<PollEvents> {
+    with_resource(ri, |resource| {
+        let queue = match resource {
+            Resource::File(fd) => fd.node.wait_queue(),
+            Resource::Surface(surface) => Some(surface.wait_queue()),
+            Resource::DirIter(_) | Resource::EventQueue(_) => None,
+        };
+
+        if let (Some(waiter), Some(queue)) = (waiter, queue) {
+            waiter.add(queue);
+        }
+
+        match resource {
+            Resource::File(fd) => fd.node.poll(),
+            Resource::DirIter(_) => PollEvents::READABLE,
+            Resource::EventQueue(_) => PollEvents::empty(),
+            Resource::Surface(surface) => surface.poll(),
+        }
+    })
+    .ok_or(FSError::InvaildFileDescriptorOrRes)
+}
+
+/// executes `then` on the event queue `evq_ri`
+fn with_evq<T, R>(evq_ri: usize, then: T) -> FSResult<R>
+where
+    T: FnOnce(&mut EventQueue) -> R,
+{
+    with_resource(evq_ri, |resource| {
+        if let Resource::EventQueue(evq) = resource {
+            Ok(then(evq))
+        } else {
+            Err(FSError::InvaildFileDescriptorOrRes)
+        }
+    })
+    .ok_or(FSError::InvaildFileDescriptorOrRes)?
+}
+
+/// creates a new empty event queue resource, returns it's ri
+pub fn evq_create() -> usize {
+    resources::add_resource(Resource::EventQueue(EventQueue::new()))
+}
+
+/// adds, modifies or removes the interest of the event queue `evq_ri` in `ri`
+/// `data` is returned with every event of `ri`
+pub fn evq_ctl(
+    evq_ri: usize,
+    op: EventQueueOp,
+    ri: usize,
+    events: PollEvents,
+    flags: InterestFlags,
+    data: usize,
+) -> FSResult<()> {
+    if op == EventQueueOp::Add {
+        // makes sure `ri` exists
+        poll_ready(ri, None)?;
+    }
+
+    with_evq(evq_ri, |evq| evq.ctl(op, ri, events, flags, data))?
+}
+
+/// waits until any of the resources in the event queue `evq_ri` is ready or `timeout` scheduler ticks pass,
+/// (see `poll`) writes the events to `events` and returns the amount written
+/// closed resources are never ready
+/// like `poll` the process sleeps until one of the resources or the timeout wakes it up
+pub fn evq_wait(evq_ri: usize, events: &mut [Event], timeout: usize) -> FSResult<usize> {
+    let timeout = Timeout::new(timeout);
+    let mut waiter = Waiter::new();
+
+    loop {
+        let resources = with_evq(evq_ri, |evq| evq.resources())?;
+        // has to be done outside of `with_evq` because checking a resource requires the resources
+        // of the process
+        let readiness = resources
+            .into_iter()
+            .map(|ri| poll_ready(ri, Some(&mut waiter)).unwrap_or(PollEvents::empty()))
+            .collect::<Vec<_>>();
+
+        let count = with_evq(evq_ri, |evq| evq.collect(&readiness, events))?;
+
+        if count > 0 || timeout.expired() {
+            return Ok(count);
+        }
+
+        waiter.wait();
+    }
+}
+
+/// waits until at least one of `fds` is ready for one of it's `events` or until `timeout` scheduler ticks pass
+/// a `timeout` of 0 only checks once and a `timeout` of usize::MAX waits forever
+/// returns the amount of ready resources and sets the `revents` of each of `fds`
+/// the process sleeps until one of `fds` wakes it up through it's wait queue or it's timeout fires
+pub fn poll(fds: &mut [PollFd], timeout: usize) -> FSResult<usize> {
+    let timeout = Timeout::new(timeout);
+    let mut waiter = Waiter::new();
+
+    loop {
+        let mut ready = 0;
+        for fd in fds.iter_mut() {
+            fd.revents = poll_ready(fd.ri, Some(&mut waiter))? & fd.events;
+            if !fd.revents.is_empty() {
+                ready += 1;
+            }
+        }
+
+        if ready > 0 || timeout.expired() {
+            return Ok(ready);
+        }
+
+        waiter.wait();
+    }
+}
+
 #[no_mangle]
 /// opens a diriter as a resource
 /// return the ri of the diriter
 pub fn diriter_open(fd_ri: usize) -> FSResult<usize> {
     let diriter = with_fd(fd_ri, |fd| {
-        VFS_STRUCT
-            .try_read()
-            .ok_or(FSError::ResourceBusy)?
-            .diriter_open(fd)
+        if !fd.node.is_dir() {
+            return Err(FSError::NotADirectory);
+        }
+        Ok(DirIter::new(fd.clone()))
     })??;
 
     Ok(resources::add_resource(Resource::DirIter(diriter)))
 }
 
+/// fills `entries` with the next entries of the directory `ri`, returns the amount filled which is 0
+/// at the end of the directory, the cursor is kept in `ri` so it can be resumed by the next call
+pub fn readdir(ri: usize, entries: &mut [DirEntry]) -> FSResult<usize> {
+    with_fd(ri, |fd| {
+        if !fd.node.is_dir() {
+            return Err(FSError::NotADirectory);
+        }
+
+        VFS_STRUCT
+            .try_read()
+            .ok_or(FSError::ResourceBusy)?
+            .readdir(fd, entries)
+    })?
+}
+
 pub fn diriter_next(dir_ri: usize, direntry: &mut DirEntry) -> FSResult<()> {
     resources::with_resource(dir_ri, |resource| {
         if let Resource::DirIter(diriter) = resource {
@@ -159,3 +575,32 @@ pub fn fstat(ri: usize, direntry: &mut DirEntry) -> FSResult<()> {
     *direntry = DirEntry::get_from_inode(node);
     Ok(())
 }
+
+/// returns the path the directory `ri` was opened with
+/// fails with `FSError::NoSuchAFileOrDirectory` if the path doesn't refer to that directory anymore
+pub fn dir_path(ri: usize) -> FSResult<String> {
+    let (node, path) = with_fd(ri, |fd| (fd.node.clone(), fd.path.clone()))?;
+    if !node.is_dir() {
+        return Err(FSError::NotADirectory);
+    }
+
+    let vfs = VFS_STRUCT.try_read().ok_or(FSError::ResourceBusy)?;
+    let (mountpoint, global) = vfs.get_from_path_checked(&path)?;
+    if mountpoint.reslove_path(&global)?.inodeid() != node.inodeid() {
+        return Err(FSError::NoSuchAFileOrDirectory);
+    }
+
+    Ok(path)
+}
+
+/// sends the device specific command `cmd` with the argument `input` to the file `ri`, the result
+/// is written into `output`
+pub fn ctl(ri: usize, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+    with_fd(ri, |fd| fd.node.ctl(cmd, input, output))?
+}
+
+#[no_mangle]
+/// syncs all mounted filesystems
+pub fn sync() -> FSResult<()> {
+    VFS_STRUCT.read().sync_all()
+}
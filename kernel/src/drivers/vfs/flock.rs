@@ -0,0 +1,164 @@
+//! advisory file locks, a lock is either shared or exclusive and covers a whole file or a range of
+//! it's bytes, locks of different owners conflict if they overlap and one of them is exclusive
+//! they are advisory, reading and writing ignores them and only taking a lock can fail because of one
+//! a lock is owned by the file it was taken with and the duplicates of it (including the copies a
+//! child gets), it is released by unlocking it or once the last of them is closed which also happens
+//! when the processes holding them exit
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use spin::Mutex;
+
+use super::{FSError, FSResult, FileDescriptor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LockOp {
+    Shared,
+    Exclusive,
+    Unlock,
+}
+
+impl TryFrom<usize> for LockOp {
+    type Error = FSError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Shared),
+            1 => Ok(Self::Exclusive),
+            2 => Ok(Self::Unlock),
+            _ => Err(FSError::InvaildArgument),
+        }
+    }
+}
+
+/// the bytes `start..end` of a file, `end` is `usize::MAX` for a range that goes past the end of
+/// the file however big it gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockRange {
+    start: usize,
+    end: usize,
+}
+
+impl LockRange {
+    /// `len` bytes starting at `start`, a `len` of 0 means up to the end of the file
+    pub fn new(start: usize, len: usize) -> Self {
+        let end = match len {
+            0 => usize::MAX,
+            len => start.saturating_add(len),
+        };
+        Self { start, end }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// held by a file descriptor and it's duplicates, it's locks are released when the last one is
+/// dropped
+#[derive(Debug)]
+pub struct LockOwner(usize);
+
+static NEXT_OWNER: AtomicUsize = AtomicUsize::new(0);
+
+impl LockOwner {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self(NEXT_OWNER.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+impl Drop for LockOwner {
+    fn drop(&mut self) {
+        release(self.0);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lock {
+    owner: usize,
+    exclusive: bool,
+    range: LockRange,
+}
+
+/// the locks of each file that has any, by the address of it's filesystem and it's inode id
+static LOCKS: Mutex<BTreeMap<(usize, usize), Vec<Lock>>> = Mutex::new(BTreeMap::new());
+
+fn file_of(fd: &FileDescriptor) -> (usize, usize) {
+    (fd.mountpoint as *const () as usize, fd.node.inodeid())
+}
+
+/// locks or unlocks `range` of the file `fd`, replacing the locks `fd` already has in it
+/// fails with `FSError::ResourceBusy` if another owner holds a lock that conflicts
+pub fn lock(fd: &FileDescriptor, op: LockOp, range: LockRange) -> FSResult<()> {
+    let owner = fd.lock_owner.0;
+    let file = file_of(fd);
+
+    let mut locks = LOCKS.lock();
+    let conflicts = |lock: &Lock| {
+        lock.owner != owner
+            && lock.range.overlaps(&range)
+            && (lock.exclusive || op == LockOp::Exclusive)
+    };
+    if op != LockOp::Unlock
+        && locks
+            .get(&file)
+            .is_some_and(|held| held.iter().any(conflicts))
+    {
+        return Err(FSError::ResourceBusy);
+    }
+
+    let held = locks.entry(file).or_default();
+
+    // cuts `range` out of the owner's locks, keeping what is left on each side
+    let mut kept = Vec::with_capacity(held.len() + 2);
+    for lock in held.drain(..) {
+        if lock.owner != owner || !lock.range.overlaps(&range) {
+            kept.push(lock);
+            continue;
+        }
+
+        if lock.range.start < range.start {
+            kept.push(Lock {
+                range: LockRange {
+                    start: lock.range.start,
+                    end: range.start,
+                },
+                ..lock
+            });
+        }
+        if lock.range.end > range.end {
+            kept.push(Lock {
+                range: LockRange {
+                    start: range.end,
+                    end: lock.range.end,
+                },
+                ..lock
+            });
+        }
+    }
+
+    if op != LockOp::Unlock {
+        kept.push(Lock {
+            owner,
+            exclusive: op == LockOp::Exclusive,
+            range,
+        });
+    }
+
+    if kept.is_empty() {
+        locks.remove(&file);
+    } else {
+        *held = kept;
+    }
+    Ok(())
+}
+
+/// releases every lock of `owner`
+fn release(owner: usize) {
+    let mut locks = LOCKS.lock();
+    locks.retain(|_, held| {
+        held.retain(|lock| lock.owner != owner);
+        !held.is_empty()
+    });
+}
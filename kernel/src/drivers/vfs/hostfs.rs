@@ -0,0 +1,428 @@
+//! host:, the directory the host shares through virtio-9p (see `virtio::ninep`), nothing is cached
+//! so files changed on either side are seen right away
+//! inodeids are given to paths the first time they are seen and kept until the kernel restarts, a
+//! file that is renamed gets a new one
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use spin::Mutex;
+
+use crate::{
+    drivers::virtio::ninep::{self, Qid, Virtio9p, O_CREAT, O_EXCL, O_RDONLY, O_RDWR},
+    info,
+};
+
+use super::{FSError, FSResult, FileDescriptor, Inode, InodeOps, InodeType, Path, FS, VFS_STRUCT};
+
+/// the permissions created files and directories have on the host
+const FILE_MODE: u32 = 0o644;
+const DIR_MODE: u32 = 0o755;
+
+/// the paths relative to the shared directory by inodeid, the root is "" and 0
+struct Paths {
+    ids: BTreeMap<String, usize>,
+    paths: Vec<String>,
+}
+
+impl Paths {
+    fn new() -> Self {
+        Self {
+            ids: BTreeMap::from([(String::new(), 0)]),
+            paths: Vec::from([String::new()]),
+        }
+    }
+
+    fn id_of(&mut self, path: &str) -> usize {
+        if let Some(id) = self.ids.get(path) {
+            return *id;
+        }
+
+        let id = self.paths.len();
+        self.paths.push(path.to_string());
+        self.ids.insert(path.to_string(), id);
+        id
+    }
+}
+
+struct Shared {
+    client: &'static Virtio9p,
+    paths: Mutex<Paths>,
+}
+
+fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{parent}/{name}")
+    }
+}
+
+/// the names `path` is made of after it's drive
+fn components(path: Path) -> Vec<&str> {
+    let path = match path.split_once(':') {
+        Some((drive, rest)) if !drive.contains(['/', '\\']) => rest,
+        _ => path,
+    };
+
+    path.split(['/', '\\'])
+        .filter(|name| !name.is_empty() && *name != ".")
+        .collect()
+}
+
+/// the names of the parent of `path` and the name of `path` in it
+fn split_parent(path: Path) -> FSResult<(Vec<&str>, &str)> {
+    let mut names = components(path);
+    let name = names.pop().ok_or(FSError::InvaildPath)?;
+    Ok((names, name))
+}
+
+pub struct HostInode {
+    shared: Arc<Shared>,
+    /// relative to the shared directory, separated by `/`
+    path: String,
+    inodeid: usize,
+    qid: Qid,
+    /// walked to `path`, opened by the first read or write
+    fid: u32,
+    opened: Mutex<bool>,
+}
+
+impl HostInode {
+    fn new(shared: Arc<Shared>, path: String) -> FSResult<Self> {
+        let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+        let (fid, qid) = shared.client.walk(&names)?;
+        let inodeid = shared.paths.lock().id_of(&path);
+
+        Ok(Self {
+            shared,
+            path,
+            inodeid,
+            qid,
+            fid,
+            opened: Mutex::new(false),
+        })
+    }
+
+    /// opens the fid for reading and writing if it isn't yet, files the host doesn't allow writing
+    /// to are opened for reading only
+    fn open(&self) -> FSResult<()> {
+        let mut opened = self.opened.lock();
+        if *opened {
+            return Ok(());
+        }
+
+        let client = self.shared.client;
+        match client.lopen(self.fid, O_RDWR) {
+            Err(FSError::MissingPermissions) => client.lopen(self.fid, O_RDONLY)?,
+            results => results?,
+        }
+        *opened = true;
+        Ok(())
+    }
+}
+
+impl Drop for HostInode {
+    fn drop(&mut self) {
+        _ = self.shared.client.clunk(self.fid);
+    }
+}
+
+impl InodeOps for HostInode {
+    fn name(&self) -> String {
+        match self.path.rsplit_once('/') {
+            Some((_, name)) => name.to_string(),
+            None if self.path.is_empty() => "/".to_string(),
+            None => self.path.clone(),
+        }
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        if !self.qid.is_dir() {
+            return Err(FSError::NotADirectory);
+        }
+
+        let path = join(&self.path, name);
+        let names: Vec<&str> = path.split('/').collect();
+        let (fid, _) = self.shared.client.walk(&names)?;
+        _ = self.shared.client.clunk(fid);
+
+        Ok(self.shared.paths.lock().id_of(&path))
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn size(&self) -> FSResult<usize> {
+        if self.qid.is_dir() {
+            return Err(FSError::NotAFile);
+        }
+        Ok(self.shared.client.size(self.fid)? as usize)
+    }
+
+    fn read(&self, buffer: &mut [u8], offset: usize, count: usize) -> FSResult<usize> {
+        self.open()?;
+
+        let mut read = 0;
+        while read < count {
+            let amount = self.shared.client.read(
+                self.fid,
+                (offset + read) as u64,
+                &mut buffer[read..count],
+            )?;
+            if amount == 0 {
+                break;
+            }
+            read += amount;
+        }
+
+        Ok(read)
+    }
+
+    fn write(&self, buffer: &[u8], offset: usize) -> FSResult<usize> {
+        self.open()?;
+
+        let mut written = 0;
+        while written < buffer.len() {
+            let amount = self.shared.client.write(
+                self.fid,
+                (offset + written) as u64,
+                &buffer[written..],
+            )?;
+            if amount == 0 {
+                break;
+            }
+            written += amount;
+        }
+
+        Ok(written)
+    }
+
+    fn truncate(&self, size: usize) -> FSResult<()> {
+        self.shared.client.truncate(self.fid, size as u64)
+    }
+
+    fn inodeid(&self) -> usize {
+        self.inodeid
+    }
+
+    fn kind(&self) -> InodeType {
+        if self.qid.is_dir() {
+            InodeType::Directory
+        } else {
+            InodeType::File
+        }
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        if !self.qid.is_dir() {
+            return Err(FSError::NotADirectory);
+        }
+
+        // the directory is listed with a fid of it's own so listing it doesn't open the inode's
+        let client = self.shared.client;
+        let names: Vec<&str> = self
+            .path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .collect();
+        let (fid, _) = client.walk(&names)?;
+
+        let list = || -> FSResult<Vec<String>> {
+            client.lopen(fid, O_RDONLY)?;
+
+            let mut children = Vec::new();
+            let mut offset = 0;
+            loop {
+                let dirents = client.readdir(fid, offset)?;
+                let Some(last) = dirents.last() else {
+                    return Ok(children);
+                };
+                offset = last.offset;
+
+                children.extend(
+                    dirents
+                        .into_iter()
+                        .map(|dirent| dirent.name)
+                        .filter(|name| name != "." && name != ".."),
+                );
+            }
+        };
+        let children = list();
+        _ = client.clunk(fid);
+
+        let mut paths = self.shared.paths.lock();
+        let mut inodeids: Vec<usize> = children?
+            .iter()
+            .map(|name| paths.id_of(&join(&self.path, name)))
+            .filter(|inodeid| *inodeid > cursor)
+            .collect();
+
+        inodeids.sort_unstable();
+        inodeids.truncate(max);
+        Ok(inodeids)
+    }
+}
+
+pub struct HostFS {
+    shared: Arc<Shared>,
+}
+
+impl HostFS {
+    pub fn new(client: &'static Virtio9p) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                client,
+                paths: Mutex::new(Paths::new()),
+            }),
+        }
+    }
+
+    /// calls `then` with a fid walked to `names` and it's qid, the fid is clunked after
+    fn with_walk<R>(
+        &self,
+        names: &[&str],
+        then: impl FnOnce(u32, Qid) -> FSResult<R>,
+    ) -> FSResult<R> {
+        let (fid, qid) = self.shared.client.walk(names)?;
+        let results = then(fid, qid);
+        _ = self.shared.client.clunk(fid);
+        results
+    }
+
+    /// calls `then` with a fid walked to the parent of `path` and the name of `path` in it
+    fn with_parent<R>(
+        &self,
+        path: Path,
+        then: impl FnOnce(u32, &str) -> FSResult<R>,
+    ) -> FSResult<R> {
+        let (parent, name) = split_parent(path)?;
+        self.with_walk(&parent, |fid, qid| {
+            if !qid.is_dir() {
+                return Err(FSError::NotADirectory);
+            }
+            then(fid, name)
+        })
+    }
+}
+
+impl FS for HostFS {
+    fn name(&self) -> &'static str {
+        "hostfs"
+    }
+
+    fn get_inode(&self, inode_id: usize) -> FSResult<Option<Inode>> {
+        let Some(path) = self.shared.paths.lock().paths.get(inode_id).cloned() else {
+            return Ok(None);
+        };
+
+        match HostInode::new(self.shared.clone(), path) {
+            Ok(inode) => Ok(Some(Arc::new(inode))),
+            // removed on the host since
+            Err(FSError::NoSuchAFileOrDirectory) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn open(&self, path: Path) -> FSResult<FileDescriptor> {
+        // walked at once instead of a name at a time like `reslove_path`
+        let path = components(path).join("/");
+        let node = HostInode::new(self.shared.clone(), path)?;
+
+        Ok(FileDescriptor::new(
+            self as *const HostFS as *mut HostFS,
+            Arc::new(node),
+        ))
+    }
+
+    fn read(&self, file_descriptor: &mut FileDescriptor, buffer: &mut [u8]) -> FSResult<usize> {
+        if file_descriptor.node.is_dir() {
+            return Err(FSError::NotAFile);
+        }
+
+        let count = file_descriptor
+            .node
+            .read(buffer, file_descriptor.read_pos, buffer.len())?;
+
+        file_descriptor.read_pos += count;
+        Ok(count)
+    }
+
+    fn write(&self, file_descriptor: &mut FileDescriptor, buffer: &[u8]) -> FSResult<usize> {
+        if file_descriptor.node.is_dir() {
+            return Err(FSError::NotAFile);
+        }
+
+        // like ram: writing to the start of a file replaces it's contents
+        if file_descriptor.write_pos == 0 {
+            file_descriptor.node.truncate(0)?;
+        }
+
+        let count = file_descriptor
+            .node
+            .write(buffer, file_descriptor.write_pos)?;
+
+        file_descriptor.write_pos += count;
+        Ok(count)
+    }
+
+    fn create(&mut self, path: Path) -> FSResult<()> {
+        let client = self.shared.client;
+        // the fid of the directory becomes the fid of the created file
+        self.with_parent(path, |fid, name| {
+            client.lcreate(fid, name, O_RDWR | O_CREAT | O_EXCL, FILE_MODE)
+        })
+    }
+
+    fn createdir(&mut self, path: Path) -> FSResult<()> {
+        let client = self.shared.client;
+        self.with_parent(path, |fid, name| client.mkdir(fid, name, DIR_MODE))
+    }
+
+    fn remove(&mut self, path: Path) -> FSResult<()> {
+        let is_dir = self.with_walk(&components(path), |_, qid| Ok(qid.is_dir()))?;
+
+        let client = self.shared.client;
+        self.with_parent(path, |fid, name| client.unlinkat(fid, name, is_dir))
+    }
+
+    fn rename(&mut self, old: Path, new: Path) -> FSResult<()> {
+        let (old_names, new_names) = (components(old), components(new));
+        if old_names == new_names {
+            return self.with_walk(&old_names, |_, _| Ok(()));
+        }
+        // a directory can't be moved into itself
+        if new_names.starts_with(&old_names) {
+            return Err(FSError::InvaildPath);
+        }
+        // the host would replace `new`
+        if self.with_walk(&new_names, |_, _| Ok(())).is_ok() {
+            return Err(FSError::AlreadyExists);
+        }
+
+        let client = self.shared.client;
+        self.with_parent(old, |old_dir, old_name| {
+            self.with_parent(new, |new_dir, new_name| {
+                client.renameat(old_dir, old_name, new_dir, new_name)
+            })
+        })
+    }
+}
+
+/// mounts the directory the host shares as host: if there is one
+pub fn init() {
+    let Some(client) = ninep::get() else {
+        return;
+    };
+
+    let hostfs = Box::new(HostFS::new(client));
+    if VFS_STRUCT.write().mount(b"host", hostfs).is_ok() {
+        info!(HostFS, "mounted {} as host:", client.tag());
+    }
+}
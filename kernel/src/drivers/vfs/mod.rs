@@ -3,15 +3,27 @@ pub mod expose;
 
 use crate::{
     debug, limine,
-    threading::expose::getcwd,
+    threading::{
+        expose::{getcwd, namespace},
+        waitqueue::WaitQueue,
+    },
     utils::{
         errors::{ErrorStatus, IntoErr},
+        inflate,
+        locks::RwLock,
+        path::normalize,
         ustar::{self, TarArchiveIter},
     },
+    warn,
 };
 pub mod devicefs;
+pub mod evq;
+pub mod flock;
+pub mod hostfs;
+pub mod namespace;
 pub mod procfs;
 pub mod ramfs;
+pub mod rofs;
 
 use alloc::{
     borrow::ToOwned,
@@ -21,13 +33,13 @@ use alloc::{
     sync::Arc,
     vec::Vec,
 };
+use bitflags::bitflags;
 use expose::DirEntry;
 use lazy_static::lazy_static;
-use spin::RwLock;
 pub type Path<'a> = &'a str;
 
 lazy_static! {
-    pub static ref VFS_STRUCT: RwLock<VFS> = RwLock::new(VFS::new());
+    pub static ref VFS_STRUCT: RwLock<VFS> = RwLock::new("VFS", VFS::new());
 }
 
 pub fn init() {
@@ -41,25 +53,88 @@ pub fn init() {
         .unwrap();
     // processes
     vfs.mount(b"proc", Box::new(procfs::ProcFS::new())).unwrap();
-    // ramdisk
-    let mut ramdisk = limine::get_ramdisk();
+
+    debug!(VFS, "done ...");
+}
+
+/// the directories of a rofs ramdisk that are kept writable by binding a directory of ram: over them,
+/// see `RAMDISK_WRITABLE_DIRS` in build.rs
+const RAMDISK_WRITABLE_DIRS: &[&str] = &["pkgs"];
+
+/// mounts the ramdisk as sys:, a rofs image is mounted as is and a tar archive is unpacked into a
+/// RamFS, the VFS is only locked to mount it
+pub fn unpack_ramdisk() {
+    let ramdisk = limine::get_ramdisk();
+    if rofs::is_image(ramdisk) {
+        let image = rofs::RoFS::new(ramdisk).expect("failed reading ramdisk image");
+        let mut vfs = VFS_STRUCT.write();
+        vfs.mount(b"sys", Box::new(image)).expect("failed mounting");
+
+        for dir in RAMDISK_WRITABLE_DIRS {
+            let src = alloc::format!("ram:/{dir}");
+            let dst = alloc::format!("sys:/{dir}");
+            if let Err(err) = vfs.createdir(&src).and_then(|()| vfs.bind(&src, &dst)) {
+                warn!(VFS, "failed making {} writable: {:?}", dst, err);
+            }
+        }
+        return;
+    }
+
+    // the files reference the archive instead of copying it so it is kept forever, the ramdisk module
+    // itself is never reclaimed
+    let ramdisk: &'static [u8] = if inflate::is_gzip(ramdisk) {
+        inflate::gunzip(ramdisk)
+            .expect("failed decompressing ramdisk")
+            .leak()
+    } else {
+        ramdisk
+    };
+
+    let mut ramdisk = unsafe { TarArchiveIter::new(ramdisk.as_ptr()) };
     let mut ramfs = Box::new(ramfs::RamFS::new());
     VFS::unpack_tar(&mut *ramfs, &mut ramdisk).expect("failed unpacking ramdisk archive");
-    vfs.mount(b"sys", ramfs).expect("failed mounting");
+    VFS_STRUCT
+        .write()
+        .mount(b"sys", ramfs)
+        .expect("failed mounting");
+}
 
-    debug!(VFS, "done ...");
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OpenFlags: u8 {
+        /// reads and writes that would otherwise wait return FSError::WouldBlock instead
+        const NONBLOCKING = 1 << 0;
+        /// the path is a directory and an unnamed file is created on it's drive instead of opening
+        /// it, see `FS::create_unnamed`
+        const TMPFILE = 1 << 1;
+    }
+}
+
+bitflags! {
+    /// the operations that can be done on a resource without waiting
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[repr(C)]
+    pub struct PollEvents: u8 {
+        const READABLE = 1 << 0;
+        const WRITABLE = 1 << 1;
+    }
 }
 
 #[derive(Clone)]
 pub struct FileDescriptor {
     pub mountpoint: *mut dyn FS,
     pub node: Inode,
-    /// acts as a dir entry index for directories
+    /// the cursor of `FS::readdir` for directories
     /// acts as a byte index for files
     pub read_pos: usize,
     /// acts as a byte index for files
     /// doesn't do anything for directories
     pub write_pos: usize,
+    pub flags: OpenFlags,
+    /// the absolute normalized path it was opened with, see `expose::dir_path`
+    pub path: String,
+    /// owns the locks taken with this file, shared with it's duplicates, see `flock`
+    pub lock_owner: Arc<flock::LockOwner>,
 }
 
 impl FileDescriptor {
@@ -67,8 +142,11 @@ impl FileDescriptor {
         Self {
             mountpoint,
             node,
+            flags: OpenFlags::empty(),
+            path: String::new(),
             read_pos: 0,
             write_pos: 0,
+            lock_owner: flock::LockOwner::new(),
         }
     }
 }
@@ -87,6 +165,20 @@ pub enum FSError {
     AlreadyExists,
     NotExecuteable,
     ResourceBusy,
+    /// the resource is busy and was opened with `OpenFlags::NONBLOCKING`
+    WouldBlock,
+    MissingPermissions,
+    DirectoryNotEmpty,
+    OutOfMemory,
+    /// a buffer doesn't have the size the operation expects, for example the buffers given to `ctl`
+    InvaildArgument,
+    /// a file name longer than `expose::MAX_NAME_LEN`
+    NameTooLong,
+    /// the extended attribute isn't set, see `FS::get_xattr`
+    NoSuchAttribute,
+    /// the data of the filesystem is invaild, for example a block of a rofs image that doesn't
+    /// decompress
+    Corrupted,
 }
 
 impl IntoErr for FSError {
@@ -97,11 +189,18 @@ impl IntoErr for FSError {
             Self::NotADirectory => ErrorStatus::NotADirectory,
             Self::NoSuchAFileOrDirectory => ErrorStatus::NoSuchAFileOrDirectory,
             Self::InvaildPath => ErrorStatus::InvaildPath,
-            Self::InvaildDrive => ErrorStatus::NoSuchAFileOrDirectory,
+            Self::InvaildDrive | Self::NoSuchAttribute => ErrorStatus::NoSuchAFileOrDirectory,
             Self::InvaildFileDescriptorOrRes => ErrorStatus::InvaildResource,
             Self::AlreadyExists => ErrorStatus::AlreadyExists,
             Self::NotExecuteable => ErrorStatus::NotExecutable,
             Self::ResourceBusy => ErrorStatus::Busy,
+            Self::WouldBlock => ErrorStatus::WouldBlock,
+            Self::MissingPermissions => ErrorStatus::MissingPermissions,
+            Self::DirectoryNotEmpty => ErrorStatus::DirectoryNotEmpty,
+            Self::OutOfMemory => ErrorStatus::OutOfMemory,
+            Self::InvaildArgument => ErrorStatus::ArgumentOutOfDomain,
+            Self::NameTooLong => ErrorStatus::StrTooLong,
+            Self::Corrupted => ErrorStatus::Corrupted,
         }
     }
 }
@@ -131,6 +230,9 @@ pub trait InodeOps: Send + Sync {
     fn size(&self) -> FSResult<usize> {
         Err(FSError::OperationNotSupported)
     }
+    /// regenerates the data of a generated file, called by the filesystem before the file is read
+    /// from the start
+    fn regenerate(&self) {}
     /// attempts to read `count` bytes of node data if it is a file
     /// panics if invaild `offset`
     /// returns the amount of bytes read
@@ -158,11 +260,39 @@ pub trait InodeOps: Send + Sync {
         Err(FSError::OperationNotSupported)
     }
 
+    /// attempts to remove `name` from self returning it's inodeid
+    /// returns an FSError::NotADirectory if not a directory
+    fn remove(&self, name: &str) -> FSResult<usize> {
+        _ = name;
+        Err(FSError::OperationNotSupported)
+    }
+
     fn truncate(&self, size: usize) -> FSResult<()> {
         _ = size;
         Err(FSError::OperationNotSupported)
     }
 
+    /// returns the operations that can be done on node right now without waiting
+    fn poll(&self) -> PollEvents {
+        PollEvents::all()
+    }
+
+    /// the queue woken up whenever the result of `poll` may have changed,
+    /// None if the node is always ready
+    fn wait_queue(&self) -> Option<&'static WaitQueue> {
+        None
+    }
+
+    /// sends the node specific command `cmd` with the argument `input`, the command writes it's
+    /// result into `output`, used by devices for operations that aren't reads or writes
+    /// see `crate::devices::ctl` for the command numbers
+    fn ctl(&self, cmd: u16, input: &[u8], output: &mut [u8]) -> FSResult<()> {
+        _ = cmd;
+        _ = input;
+        _ = output;
+        Err(FSError::OperationNotSupported)
+    }
+
     fn inodeid(&self) -> usize;
     fn kind(&self) -> InodeType;
 
@@ -171,10 +301,21 @@ pub trait InodeOps: Send + Sync {
         self.kind() == InodeType::Directory
     }
 
-    fn open_diriter(&self, fs: *mut dyn FS) -> FSResult<DirIter> {
-        _ = fs;
+    /// returns the inodeids of up to `max` children after `cursor` in increasing order, `cursor` is
+    /// the last inodeid returned or 0 to start from the first child
+    /// children added or removed in between reads never cause the others to be skipped or returned twice
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        _ = cursor;
+        _ = max;
         Err(FSError::OperationNotSupported)
     }
+
+    /// returns the child `inodeid` if the directory makes it's children itself, `FS::readdir` uses
+    /// it instead of `FS::get_inode` when it returns Some
+    fn child(&self, inodeid: usize) -> Option<Inode> {
+        _ = inodeid;
+        None
+    }
 }
 
 /// unknown inode type
@@ -182,36 +323,24 @@ pub type Inode = Arc<dyn InodeOps>;
 /// inode type with a known type
 pub type InodeOf<T> = Arc<T>;
 
-#[derive(Debug, Clone)]
+/// reads a directory one entry at a time with `FS::readdir`, it's state is only the cursor of `fd`
+#[derive(Clone)]
 pub struct DirIter {
-    fs: *mut dyn FS,
-    inode_ids: Box<[usize]>,
-    index: usize,
+    fd: FileDescriptor,
 }
 
 impl DirIter {
-    pub const fn new(fs: *mut dyn FS, inode_ids: Box<[usize]>) -> Self {
-        Self {
-            fs,
-            inode_ids,
-            index: 0,
-        }
+    pub fn new(mut fd: FileDescriptor) -> Self {
+        fd.read_pos = 0;
+        Self { fd }
     }
 
     pub fn next(&mut self) -> Option<DirEntry> {
-        let index = self.index;
-        self.index += 1;
-
-        if index >= self.inode_ids.len() {
-            return None;
-        }
-
-        let inode_id = self.inode_ids[index];
-        let inode = unsafe { (*self.fs).get_inode(inode_id) };
+        let mut entry = [unsafe { DirEntry::zeroed() }];
+        let mountpoint = self.fd.mountpoint;
 
-        match inode {
-            Ok(Some(inode)) => Some(DirEntry::get_from_inode(inode)),
-            Ok(None) => self.next(),
+        match unsafe { (*mountpoint).readdir(&mut self.fd, &mut entry) } {
+            Ok(1) => Some(entry[0].clone()),
             _ => None,
         }
     }
@@ -331,27 +460,123 @@ pub trait FS: Send + Sync {
         _ = path;
         Err(FSError::OperationNotSupported)
     }
+    /// creates a file at `path` containing `data`, filesystems that can reference `data` until the
+    /// file is first written to instead of copying it override this
+    fn create_static(&mut self, path: Path, data: &'static [u8]) -> FSResult<()> {
+        self.create(path)?;
+
+        let mut opened = self.open(path)?;
+        self.write(&mut opened, data)?;
+        self.close(&mut opened)
+    }
     /// creates an empty dir named `name` in `path`
     fn createdir(&mut self, path: Path) -> FSResult<()> {
         _ = path;
         Err(FSError::OperationNotSupported)
     }
+    /// removes the file or empty directory at `path`, opened file descriptors keep working
+    fn remove(&mut self, path: Path) -> FSResult<()> {
+        _ = path;
+        Err(FSError::OperationNotSupported)
+    }
+    /// moves `old` to `new` which must not exist, both are in this fs
+    fn rename(&mut self, old: Path, new: Path) -> FSResult<()> {
+        _ = old;
+        _ = new;
+        Err(FSError::OperationNotSupported)
+    }
 
-    /// opens an iterator of directroy entires, fd must be a directory
-    fn diriter_open(&self, fd: &mut FileDescriptor) -> FSResult<DirIter> {
-        fd.node.open_diriter(fd.mountpoint)
+    /// creates a file without a name on this fs, `dir` is a directory of it, the file is dropped once
+    /// it's last file descriptor is closed unless it is given a name with `FS::link` first
+    fn create_unnamed(&mut self, dir: Path) -> FSResult<FileDescriptor> {
+        _ = dir;
+        Err(FSError::OperationNotSupported)
+    }
+    /// gives the unnamed file `fd` the name `path`, replacing the file at `path` if there is one,
+    /// processes either see the old file or the new one
+    /// fails with `FSError::AlreadyExists` if `fd` already has a name
+    fn link(&mut self, fd: &FileDescriptor, path: Path) -> FSResult<()> {
+        _ = fd;
+        _ = path;
+        Err(FSError::OperationNotSupported)
+    }
+
+    /// the value of the extended attribute `name` of the file or directory `fd`, extended attributes
+    /// are small pieces of data kept with it such as a hash of it's contents
+    /// fails with `FSError::NoSuchAttribute` if it isn't set
+    fn get_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<Vec<u8>> {
+        _ = fd;
+        _ = name;
+        Err(FSError::OperationNotSupported)
+    }
+    /// sets the extended attribute `name` of `fd` to `value` replacing it's previous value
+    fn set_xattr(&self, fd: &FileDescriptor, name: &str, value: &[u8]) -> FSResult<()> {
+        _ = fd;
+        _ = name;
+        _ = value;
+        Err(FSError::OperationNotSupported)
+    }
+    /// the names of the extended attributes of `fd` in order
+    fn list_xattrs(&self, fd: &FileDescriptor) -> FSResult<Vec<String>> {
+        _ = fd;
+        Err(FSError::OperationNotSupported)
+    }
+    /// fails with `FSError::NoSuchAttribute` if `name` isn't set
+    fn remove_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<()> {
+        _ = fd;
+        _ = name;
+        Err(FSError::OperationNotSupported)
+    }
+
+    /// fills `entries` with the entries of the directory `fd` after it's cursor (the `read_pos`) and
+    /// moves the cursor past them, returns the amount filled which is 0 at the end of the directory
+    fn readdir(&self, fd: &mut FileDescriptor, entries: &mut [DirEntry]) -> FSResult<usize> {
+        let mut count = 0;
+
+        while count < entries.len() {
+            let inodeids = fd.node.children_after(fd.read_pos, entries.len() - count)?;
+            if inodeids.is_empty() {
+                break;
+            }
+
+            for inodeid in inodeids {
+                fd.read_pos = inodeid;
+                let inode = match fd.node.child(inodeid) {
+                    Some(inode) => Some(inode),
+                    None => self.get_inode(inodeid)?,
+                };
+
+                // the child could have been removed since
+                if let Some(inode) = inode {
+                    entries[count] = DirEntry::get_from_inode(inode);
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// writes any cached data back to the underlying storage
+    /// does nothing for filesystems that lives in memory
+    fn sync_all(&self) -> FSResult<()> {
+        Ok(())
     }
 }
 
 #[allow(clippy::upper_case_acronyms)]
 pub struct VFS {
     drivers: BTreeMap<Vec<u8>, Box<dyn FS>>,
+    /// the bind mounts, the paths they are mounted at and the directories they expose, both
+    /// normalized paths in the global drives, see `VFS::bind`
+    binds: BTreeMap<String, String>,
 }
 
 impl VFS {
     pub fn new() -> Self {
         Self {
             drivers: BTreeMap::new(),
+            binds: BTreeMap::new(),
         }
     }
     /// mounts a file system as a drive
@@ -368,6 +593,95 @@ impl VFS {
         }
     }
 
+    /// exposes the directory `src` at `dst` until it's unmounted with `umount`, `dst` is either the
+    /// root of a drive that isn't mounted or an existing directory which is hidden by the bind
+    /// unlike the binds of a namespace every process sees it
+    pub fn bind(&mut self, src: Path, dst: Path) -> FSResult<()> {
+        let src = self.reslove_dir(src)?;
+        let view = Self::absolute(dst)?;
+        let dst = Self::global(&view);
+
+        let (drive, rest) = dst.split_once(':').ok_or(FSError::InvaildDrive)?;
+        let new_drive = rest == "/" && self.get_with_name(drive.as_bytes()).is_none();
+        if !new_drive {
+            self.reslove_dir(&view)?;
+        }
+
+        if self.binds.contains_key(&dst) {
+            return Err(FSError::AlreadyExists);
+        }
+        self.binds.insert(dst, src);
+        Ok(())
+    }
+
+    /// removes the bind mount at `dst`, drives can't be unmounted since files opened on them
+    /// reference them
+    pub fn umount(&mut self, dst: Path) -> FSResult<()> {
+        let dst = Self::global(&Self::absolute(dst)?);
+        if self.binds.remove(&dst).is_some() {
+            return Ok(());
+        }
+
+        let drive = dst.split_once(':').map(|(drive, _)| drive.as_bytes());
+        if drive.is_some_and(|drive| self.get_with_name(drive).is_some()) {
+            Err(FSError::ResourceBusy)
+        } else {
+            Err(FSError::NoSuchAFileOrDirectory)
+        }
+    }
+
+    /// the path a bind mount redirects `path` to, the bind mounted at the longest prefix of it wins
+    fn reslove_binds(&self, path: String) -> String {
+        let bind = self
+            .binds
+            .iter()
+            .filter(|(dst, _)| {
+                let dst = dst.trim_end_matches('/');
+                path.strip_prefix(dst)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+            })
+            .max_by_key(|(dst, _)| dst.len());
+
+        match bind {
+            Some((dst, src)) => {
+                let rest = &path[dst.trim_end_matches('/').len()..];
+                let mut results = String::from(src.trim_end_matches('/'));
+                results.push_str(rest);
+                results
+            }
+            None => path,
+        }
+    }
+
+    /// the path in the global drives the absolute path `path` of the current process refers to
+    fn global(path: Path) -> String {
+        namespace()
+            .reslove(path)
+            .unwrap_or_else(|| path.to_string())
+    }
+
+    /// generates the data of proc:/mounts, a line for each drive and bind mount
+    pub fn mounts_info() -> String {
+        let Some(vfs) = VFS_STRUCT.try_read() else {
+            return String::new();
+        };
+
+        let mut results = String::new();
+        for (name, mountpoint) in &vfs.drivers {
+            results.push_str(&String::from_utf8_lossy(name));
+            results.push_str(": ");
+            results.push_str(mountpoint.name());
+            results.push('\n');
+        }
+        for (dst, src) in &vfs.binds {
+            results.push_str(dst);
+            results.push_str(" bind ");
+            results.push_str(src);
+            results.push('\n');
+        }
+        results
+    }
+
     /// gets a drive from `self` named "`name`"
     /// or "`name`:" muttabily
     pub(self) fn get_with_name_mut(&mut self, name: &[u8]) -> Option<&mut (dyn FS + '_)> {
@@ -395,43 +709,41 @@ impl VFS {
 
         self.drivers.get(name).map(|x| &**x)
     }
-    /// gets the drive name from `path` then gets the drive
-    /// path must be absolute starting with DRIVE_NAME:/
-    /// also handles relative path
-    pub(self) fn get_from_path_mut(&mut self, path: Path) -> FSResult<(&mut dyn FS, String)> {
+    /// normalizes `path` which can be relative to the cwd, see `path::normalize`
+    /// the results is the path as the current process sees it, before it's namespace is applied
+    fn absolute(path: Path) -> FSResult<String> {
         let mut spilt_path = path.split(&['/', '\\']);
 
         let drive = spilt_path.next().ok_or(FSError::InvaildDrive)?;
-        let full_path = if !(drive.ends_with(':')) {
-            &(getcwd().to_owned() + path)
+        if !(drive.ends_with(':')) {
+            normalize(&(getcwd().to_owned() + path))
         } else {
-            path
-        };
+            normalize(path)
+        }
+    }
 
-        self.get_from_path_checked_mut(full_path)
+    /// gets the drive name from `path` then gets the drive
+    /// path must be absolute starting with DRIVE_NAME:/
+    /// also handles relative path, the returned path is normalized see `path::normalize`
+    pub(self) fn get_from_path_mut(&mut self, path: Path) -> FSResult<(&mut dyn FS, String)> {
+        self.get_from_path_checked_mut(&Self::absolute(path)?)
     }
 
     /// gets the drive name from `path` then gets the drive
     /// path must be absolute starting with DRIVE_NAME:/
-    /// also handles relative path
+    /// also handles relative path, the returned path is normalized see `path::normalize`
     pub(self) fn get_from_path(&self, path: Path) -> FSResult<(&dyn FS, String)> {
-        let mut spilt_path = path.split(&['/', '\\']);
-
-        let drive = spilt_path.next().ok_or(FSError::InvaildDrive)?;
-        let full_path = if !(drive.ends_with(':')) {
-            &(getcwd().to_owned() + path)
-        } else {
-            path
-        };
-
-        self.get_from_path_checked(full_path)
+        self.get_from_path_checked(&Self::absolute(path)?)
     }
 
     /// get_from_path but path cannot be realtive to cwd
+    /// the drives bound in the namespace of the current process are looked up first and then the bind
+    /// mounts, the returned path is the path in the global drives, see `namespace` and `VFS::bind`
     pub(self) fn get_from_path_checked_mut(
         &mut self,
         path: Path,
     ) -> FSResult<(&mut dyn FS, String)> {
+        let path = self.reslove_binds(Self::global(path));
         let mut spilt_path = path.split(&['/', '\\']);
 
         let drive = spilt_path.next().ok_or(FSError::InvaildDrive)?;
@@ -442,12 +754,13 @@ impl VFS {
         Ok((
             self.get_with_name_mut(drive.as_bytes())
                 .ok_or(FSError::InvaildDrive)?,
-            path.to_string(),
+            path,
         ))
     }
 
-    /// get_from_path but path cannot be realtive to cwd
+    /// get_from_path but path cannot be realtive to cwd, see `get_from_path_checked_mut`
     pub(self) fn get_from_path_checked(&self, path: Path) -> FSResult<(&dyn FS, String)> {
+        let path = self.reslove_binds(Self::global(path));
         let mut spilt_path = path.split(&['/', '\\']);
 
         let drive = spilt_path.next().ok_or(FSError::InvaildDrive)?;
@@ -458,7 +771,7 @@ impl VFS {
         Ok((
             self.get_with_name(drive.as_bytes())
                 .ok_or(FSError::InvaildDrive)?,
-            path.to_string(),
+            path,
         ))
     }
 
@@ -466,6 +779,13 @@ impl VFS {
     /// handles relative paths
     /// returns the absolute path if it is a dir
     pub fn verify_path_dir(&self, path: Path) -> FSResult<String> {
+        let path = Self::absolute(path)?;
+        self.reslove_dir(&path)?;
+        Ok(path)
+    }
+
+    /// like `verify_path_dir` but returns the path in the global drives, what a namespace binds to
+    pub fn reslove_dir(&self, path: Path) -> FSResult<String> {
         let (mountpoint, path) = self.get_from_path(path)?;
 
         let res = mountpoint.reslove_path(&path)?;
@@ -476,18 +796,14 @@ impl VFS {
         Ok(path)
     }
 
-    pub fn unpack_tar(fs: &mut dyn FS, tar: &mut TarArchiveIter) -> FSResult<()> {
+    /// unpacks `tar` into `fs`, the files reference their data in the archive until they are written to
+    /// if `fs` supports it, see `FS::create_static`
+    pub fn unpack_tar(fs: &mut dyn FS, tar: &mut TarArchiveIter<'static>) -> FSResult<()> {
         while let Some(inode) = tar.next() {
             let path = inode.name();
 
             match inode.kind {
-                ustar::Type::NORMAL => {
-                    fs.create(path)?;
-
-                    let mut opened = fs.open(path)?;
-                    fs.write(&mut opened, inode.data())?;
-                    fs.close(&mut opened)?;
-                }
+                ustar::Type::NORMAL => fs.create_static(path, inode.data())?,
 
                 ustar::Type::DIR => fs.createdir(path.trim_end_matches('/'))?,
 
@@ -496,6 +812,74 @@ impl VFS {
         }
         Ok(())
     }
+
+    /// packs the directory at `path` recursively into a tar archive
+    /// entries are named relative to `path`, devices are skipped
+    #[cfg(feature = "test")]
+    pub fn pack_tar(&self, path: Path) -> FSResult<Vec<u8>> {
+        let mut builder = ustar::TarArchiveBuilder::new();
+        self.pack_dir(&mut builder, path.trim_end_matches('/'), "")?;
+        Ok(builder.finish())
+    }
+
+    #[cfg(feature = "test")]
+    fn pack_dir(
+        &self,
+        builder: &mut ustar::TarArchiveBuilder,
+        path: Path,
+        prefix: &str,
+    ) -> FSResult<()> {
+        let mut dir = self.open(path)?;
+        let mut entries: [DirEntry; 16] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+
+        loop {
+            let count = self.readdir(&mut dir, &mut entries)?;
+            if count == 0 {
+                break;
+            }
+
+            for entry in &entries[..count] {
+                let name = core::str::from_utf8(&entry.name[..entry.name_length])
+                    .map_err(|_| FSError::InvaildPath)?;
+                if name == ".." || name == "." {
+                    continue;
+                }
+
+                let entry_path = alloc::format!("{path}/{name}");
+                let archived = alloc::format!("{prefix}{name}");
+
+                match entry.kind {
+                    InodeType::Directory => {
+                        builder
+                            .append_dir(&archived)
+                            .map_err(|()| FSError::InvaildPath)?;
+                        self.pack_dir(builder, &entry_path, &alloc::format!("{archived}/"))?;
+                    }
+                    InodeType::File => {
+                        let mut file = self.open(&entry_path)?;
+                        let mut data = Vec::with_capacity(entry.size);
+                        let mut buffer = [0u8; 512];
+
+                        loop {
+                            let read = self.read(&mut file, &mut buffer)?;
+                            if read == 0 {
+                                break;
+                            }
+                            data.extend_from_slice(&buffer[..read]);
+                        }
+                        self.close(&mut file)?;
+
+                        builder
+                            .append_file(&archived, &data)
+                            .map_err(|()| FSError::InvaildPath)?;
+                    }
+                    InodeType::Device => {}
+                }
+            }
+        }
+
+        self.close(&mut dir)
+    }
 }
 
 impl FS for VFS {
@@ -504,9 +888,12 @@ impl FS for VFS {
     }
 
     fn open(&self, path: Path) -> FSResult<FileDescriptor> {
-        let (mountpoint, path) = self.get_from_path(path)?;
+        let path = Self::absolute(path)?;
+        let (mountpoint, global) = self.get_from_path_checked(&path)?;
 
-        let file = mountpoint.open(&path)?;
+        let mut file = mountpoint.open(&global)?;
+        // the path the process sees so `expose::dir_path` gives back a path in it's namespace
+        file.path = path;
 
         Ok(file)
     }
@@ -535,11 +922,82 @@ impl FS for VFS {
         mountpoint.createdir(&path)
     }
 
+    fn remove(&mut self, path: Path) -> FSResult<()> {
+        let (mountpoint, path) = self.get_from_path_mut(path)?;
+
+        mountpoint.remove(&path)
+    }
+
+    /// `old` and `new` have to be on the same drive
+    fn rename(&mut self, old: Path, new: Path) -> FSResult<()> {
+        let (_, new) = self.get_from_path(new)?;
+        let (mountpoint, old) = self.get_from_path_mut(old)?;
+
+        let drive = |path: &str| path.split_once(':').map(|(drive, _)| drive.to_owned());
+        if drive(&old) != drive(&new) {
+            return Err(FSError::OperationNotSupported);
+        }
+
+        mountpoint.rename(&old, &new)
+    }
+
+    fn create_unnamed(&mut self, dir: Path) -> FSResult<FileDescriptor> {
+        let (mountpoint, dir) = self.get_from_path_mut(dir)?;
+
+        mountpoint.create_unnamed(&dir)
+    }
+
+    /// `path` has to be on the drive `fd` was created on
+    fn link(&mut self, fd: &FileDescriptor, path: Path) -> FSResult<()> {
+        let (mountpoint, path) = self.get_from_path_mut(path)?;
+
+        if !core::ptr::addr_eq(mountpoint as *const dyn FS, fd.mountpoint) {
+            return Err(FSError::OperationNotSupported);
+        }
+
+        mountpoint.link(fd, &path)
+    }
+
     fn close(&self, file_descriptor: &mut FileDescriptor) -> FSResult<()> {
         unsafe { (*file_descriptor.mountpoint).close(file_descriptor) }
     }
 
-    fn diriter_open(&self, fd: &mut FileDescriptor) -> FSResult<DirIter> {
-        unsafe { (*fd.mountpoint).diriter_open(fd) }
+    fn readdir(&self, fd: &mut FileDescriptor, entries: &mut [DirEntry]) -> FSResult<usize> {
+        unsafe { (*fd.mountpoint).readdir(fd, entries) }
+    }
+
+    fn get_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<Vec<u8>> {
+        unsafe { (*fd.mountpoint).get_xattr(fd, name) }
+    }
+
+    fn set_xattr(&self, fd: &FileDescriptor, name: &str, value: &[u8]) -> FSResult<()> {
+        unsafe { (*fd.mountpoint).set_xattr(fd, name, value) }
+    }
+
+    fn list_xattrs(&self, fd: &FileDescriptor) -> FSResult<Vec<String>> {
+        unsafe { (*fd.mountpoint).list_xattrs(fd) }
+    }
+
+    fn remove_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<()> {
+        unsafe { (*fd.mountpoint).remove_xattr(fd, name) }
+    }
+
+    /// syncs every mounted filesystem, returns the first error after attempting to sync all of them
+    fn sync_all(&self) -> FSResult<()> {
+        let mut results = Ok(());
+
+        for (name, mountpoint) in &self.drivers {
+            if let Err(err) = mountpoint.sync_all() {
+                warn!(
+                    VFS,
+                    "failed to sync {}: {:?}",
+                    String::from_utf8_lossy(name),
+                    err
+                );
+                results = results.and(Err(err));
+            }
+        }
+
+        results
     }
 }
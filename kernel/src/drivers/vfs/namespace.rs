@@ -0,0 +1,58 @@
+//! mount namespaces, the drives a process sees on top of the drives mounted in `VFS`
+//! a namespace binds drive names to directories of the global drives, a bind can hide a global drive
+//! with the same name, lookups go through the namespace of the current process first and then the
+//! global drives
+//! a namespace is shared with the processes spawned after it was set up and copied the first time one
+//! of them binds or unbinds a drive (copy-on-mount), so a parent can bind, spawn and unbind to give a
+//! drive to a single child, see `expose::bind`
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+
+use super::{FSError, FSResult, Path};
+
+#[derive(Debug, Clone, Default)]
+pub struct Namespace {
+    /// drive names without the `:` and the normalized global paths of the directories they are bound to
+    binds: BTreeMap<String, String>,
+}
+
+impl Namespace {
+    pub const fn new() -> Self {
+        Self {
+            binds: BTreeMap::new(),
+        }
+    }
+
+    /// binds `drive` to `target` replacing any previous bind of it, `target` is a normalized path in
+    /// the global drives
+    /// fails with `FSError::InvaildDrive` if `drive` is empty or has a `:` or a separator in it
+    pub fn bind(&mut self, drive: &str, target: String) -> FSResult<()> {
+        if drive.is_empty() || drive.contains([':', '/', '\\']) {
+            return Err(FSError::InvaildDrive);
+        }
+
+        self.binds.insert(drive.to_string(), target);
+        Ok(())
+    }
+
+    /// removes the bind of `drive`, a global drive it was hiding becomes visible again
+    pub fn unbind(&mut self, drive: &str) -> FSResult<()> {
+        self.binds
+            .remove(drive)
+            .map(|_| ())
+            .ok_or(FSError::InvaildDrive)
+    }
+
+    /// the path in the global drives the absolute path `path` refers to, None if it's drive isn't
+    /// bound
+    pub fn reslove(&self, path: Path) -> Option<String> {
+        let (drive, rest) = path.split_once(':')?;
+        let target = self.binds.get(drive)?;
+
+        let mut results = String::from(target.trim_end_matches('/'));
+        results.push_str(rest);
+        Some(results)
+    }
+}
@@ -1,44 +1,353 @@
+//! proc:/ has a generated file for each of `PROC_FILES` and a directory for each process
+//! the files are generated again every time they are read from the start, once a read reaches the
+//! end the file descriptor goes back to the start so reading it again gives fresh data
+//! `proc:/<pid>/fd` has a file named after each ri the process has open, containing the type of the
+//! resource and the path it was opened with
+//! `proc:/<pid>/maps` has the mapped ranges of the process's address space, see `AliveProcessState::maps`
+//! `proc:/<pid>/memory` has the amount of memory resident and mapped in the process, see
+//! `AliveProcessState::memory_info`
+//! `proc:/<pid>/threads` has a file named after each tid with the thread's status, processes only
+//! have a single thread so the tid is the pid
+//! `proc:/<pid>/abort` has the message the process reported before exiting, empty if it didn't
+//! the files of a process are taken when they are opened since the scheduler is locked while files
+//! and directories are read
 use core::str;
 
 use alloc::{format, string::String, sync::Arc, vec::Vec};
 
-use crate::threading::{
-    expose::{getinfo, getpids},
-    processes::ProcessInfo,
+use crate::{
+    arch::{cpu, profiler},
+    drivers::poll,
+    eve, logging,
+    memory::{shrinker, sorcery},
+    threading::{
+        expose::{getpids, pabort, pexists, pmaps, pmemory, presources, pthread_status},
+        timer,
+    },
+    utils::{env, expose, locks::Mutex},
 };
 
-use super::{DirIter, FSError, FSResult, FileDescriptor, Inode};
+use super::{FSError, FSResult, FileDescriptor, Inode};
+
+/// generates the data of a file in proc:/
+type ProcFileGenerator = fn() -> String;
+/// files in the root of proc:/, their data is generated each time they are read from the start
+const PROC_FILES: &[(&str, ProcFileGenerator)] = &[
+    ("cpuinfo", cpu::cpuinfo),
+    ("drivers", poll::drivers_info),
+    ("environ", env::environ),
+    ("eve-journal", eve::eve_journal),
+    ("interrupts", cpu::interrupts_info),
+    ("kmsg", logging::kmsg),
+    ("kernelinfo", expose::kernelinfo),
+    ("meminfo", shrinker::meminfo),
+    ("memmap", sorcery::memmap_info),
+    ("mounts", super::VFS::mounts_info),
+    ("profile", profiler::profile_info),
+    ("services", eve::services_info),
+    ("timers", timer::timers_info),
+];
+/// inodeids before this are used by `PROC_FILES`, process inodeid is it's pid + `PROCESS_INODES_START`
+const PROCESS_INODES_START: usize = PROC_FILES.len() + 1;
+/// the inodeid of `proc:/<pid>/memory` is `MEMORY_INODES | pid`
+const MEMORY_INODES: usize = 1 << 57;
+/// the inodeid of `proc:/<pid>/abort` is `ABORT_INODES | pid`
+const ABORT_INODES: usize = 1 << 58;
+/// the inodeid of `proc:/<pid>/threads/<tid>` is `THREAD_INODES | tid`
+const THREAD_INODES: usize = 1 << 59;
+/// the inodeid of `proc:/<pid>/threads` is `THREADS_DIR_INODES | pid`
+const THREADS_DIR_INODES: usize = 1 << 60;
+/// the inodeid of `proc:/<pid>/maps` is `MAPS_INODES | pid`
+const MAPS_INODES: usize = 1 << 61;
+/// the inodeid of `proc:/<pid>/fd` is `FD_DIR_INODES | pid`
+const FD_DIR_INODES: usize = 1 << 62;
+/// the inodeid of `proc:/<pid>/fd/<ri>` is `FD_INODES | pid << 32 | (ri & FD_INDEX_MASK)`
+const FD_INODES: usize = 1 << 63;
+const FD_INDEX_MASK: usize = u32::MAX as usize;
+
+fn fd_inodeid(pid: usize, ri: usize) -> usize {
+    FD_INODES | pid << 32 | (ri & FD_INDEX_MASK)
+}
 
 pub struct ProcFS;
+/// a process, it only keeps the pid so it never goes stale
 #[derive(Clone)]
-pub struct ProcInode(ProcessInfo);
+pub struct ProcInode(usize);
 pub struct RootProcessInode;
 
+/// `proc:/<pid>/fd`, the resources are taken when it is opened because directories are read with
+/// the scheduler locked
+pub struct ProcFdDirInode {
+    pid: usize,
+    resources: Vec<(usize, String)>,
+}
+
+/// `proc:/<pid>/threads`
+pub struct ProcThreadsDirInode(usize);
+
+/// a file of a process, generated when it is opened
+pub struct ProcSnapshotInode {
+    inodeid: usize,
+    name: String,
+    data: String,
+}
+
+/// a generated read-only file
+pub struct ProcFileInode {
+    inodeid: usize,
+    name: &'static str,
+    generate: ProcFileGenerator,
+    data: Mutex<String>,
+}
+
+impl ProcFileInode {
+    /// generates the data of `PROC_FILES[index]`
+    pub fn create(index: usize) -> Inode {
+        let (name, generate) = PROC_FILES[index];
+
+        Arc::new(Self {
+            inodeid: index + 1,
+            name,
+            generate,
+            data: Mutex::new("ProcFile", generate()),
+        })
+    }
+}
+
+impl super::InodeOps for ProcFileInode {
+    fn inodeid(&self) -> usize {
+        self.inodeid
+    }
+
+    fn kind(&self) -> super::InodeType {
+        super::InodeType::File
+    }
+
+    fn name(&self) -> String {
+        String::from(self.name)
+    }
+
+    fn size(&self) -> FSResult<usize> {
+        Ok(self.data.lock().len())
+    }
+
+    fn regenerate(&self) {
+        *self.data.lock() = (self.generate)();
+    }
+
+    fn read(&self, buffer: &mut [u8], offset: usize, count: usize) -> FSResult<usize> {
+        buffer[..count].copy_from_slice(&self.data.lock().as_bytes()[offset..offset + count]);
+        Ok(count)
+    }
+}
+
 impl super::InodeOps for ProcInode {
     fn inodeid(&self) -> usize {
-        self.0.pid + 1
+        self.0 + PROCESS_INODES_START
     }
 
     fn kind(&self) -> super::InodeType {
-        super::InodeType::Device
+        super::InodeType::Directory
     }
 
     fn name(&self) -> String {
-        format!("{}", self.0.pid)
+        format!("{}", self.0)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        match name {
+            "abort" => Ok(ABORT_INODES | self.0),
+            "fd" => Ok(FD_DIR_INODES | self.0),
+            "maps" => Ok(MAPS_INODES | self.0),
+            "memory" => Ok(MEMORY_INODES | self.0),
+            "threads" => Ok(THREADS_DIR_INODES | self.0),
+            _ => Err(FSError::NoSuchAFileOrDirectory),
+        }
     }
 
-    fn contains(&self, _: &str) -> bool {
-        false
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        let inodeids = [
+            MEMORY_INODES | self.0,
+            ABORT_INODES | self.0,
+            THREADS_DIR_INODES | self.0,
+            MAPS_INODES | self.0,
+            FD_DIR_INODES | self.0,
+        ]
+        .into_iter()
+        .filter(|inodeid| *inodeid > cursor)
+        .take(max)
+        .collect();
+
+        Ok(inodeids)
     }
 
-    fn get(&self, _: &str) -> FSResult<usize> {
-        Err(FSError::NotADirectory)
+    fn child(&self, inodeid: usize) -> Option<Inode> {
+        // only listed so the data isn't needed
+        if inodeid & FD_DIR_INODES != 0 {
+            Some(Arc::new(ProcFdDirInode {
+                pid: self.0,
+                resources: Vec::new(),
+            }))
+        } else if inodeid & MAPS_INODES != 0 {
+            Some(ProcSnapshotInode::create(inodeid, "maps", String::new()))
+        } else if inodeid & ABORT_INODES != 0 {
+            Some(ProcSnapshotInode::create(inodeid, "abort", String::new()))
+        } else if inodeid & MEMORY_INODES != 0 {
+            Some(ProcSnapshotInode::create(inodeid, "memory", String::new()))
+        } else {
+            Some(Arc::new(ProcThreadsDirInode(self.0)))
+        }
     }
 }
 
 impl ProcInode {
-    pub fn new(process: ProcessInfo) -> Inode {
-        Arc::new(Self(process))
+    pub fn new(pid: usize) -> Inode {
+        Arc::new(Self(pid))
+    }
+}
+
+impl ProcFdDirInode {
+    /// takes the resources of `pid`, locks the scheduler
+    pub fn create(pid: usize) -> Option<Inode> {
+        Some(Arc::new(Self {
+            pid,
+            resources: presources(pid)?,
+        }))
+    }
+}
+
+impl super::InodeOps for ProcFdDirInode {
+    fn inodeid(&self) -> usize {
+        FD_DIR_INODES | self.pid
+    }
+
+    fn kind(&self) -> super::InodeType {
+        super::InodeType::Directory
+    }
+
+    fn name(&self) -> String {
+        String::from("fd")
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        let ri = name
+            .parse::<usize>()
+            .map_err(|_| FSError::NoSuchAFileOrDirectory)?;
+
+        if self.resources.iter().any(|(open, _)| *open == ri) {
+            Ok(fd_inodeid(self.pid, ri))
+        } else {
+            Err(FSError::NoSuchAFileOrDirectory)
+        }
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        let inodeids = self
+            .resources
+            .iter()
+            .map(|(ri, _)| fd_inodeid(self.pid, *ri))
+            .filter(|inodeid| *inodeid > cursor)
+            .take(max)
+            .collect();
+
+        Ok(inodeids)
+    }
+
+    fn child(&self, inodeid: usize) -> Option<Inode> {
+        let (ri, description) = self
+            .resources
+            .iter()
+            .find(|(ri, _)| fd_inodeid(self.pid, *ri) == inodeid)?;
+
+        Some(ProcSnapshotInode::create(
+            inodeid,
+            format!("{}", ri),
+            format!("{}\n", description),
+        ))
+    }
+}
+
+impl super::InodeOps for ProcThreadsDirInode {
+    fn inodeid(&self) -> usize {
+        THREADS_DIR_INODES | self.0
+    }
+
+    fn kind(&self) -> super::InodeType {
+        super::InodeType::Directory
+    }
+
+    fn name(&self) -> String {
+        String::from("threads")
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        if name.parse::<usize>() == Ok(self.0) {
+            Ok(THREAD_INODES | self.0)
+        } else {
+            Err(FSError::NoSuchAFileOrDirectory)
+        }
+    }
+
+    fn children_after(&self, cursor: usize, _: usize) -> FSResult<Vec<usize>> {
+        let inodeid = THREAD_INODES | self.0;
+        Ok(if inodeid > cursor {
+            alloc::vec![inodeid]
+        } else {
+            Vec::new()
+        })
+    }
+
+    fn child(&self, inodeid: usize) -> Option<Inode> {
+        Some(ProcSnapshotInode::create(
+            inodeid,
+            format!("{}", self.0),
+            String::new(),
+        ))
+    }
+}
+
+impl ProcSnapshotInode {
+    pub fn create(inodeid: usize, name: impl Into<String>, data: String) -> Inode {
+        Arc::new(Self {
+            inodeid,
+            name: name.into(),
+            data,
+        })
+    }
+}
+
+impl super::InodeOps for ProcSnapshotInode {
+    fn inodeid(&self) -> usize {
+        self.inodeid
+    }
+
+    fn kind(&self) -> super::InodeType {
+        super::InodeType::File
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn size(&self) -> FSResult<usize> {
+        Ok(self.data.len())
+    }
+
+    fn read(&self, buffer: &mut [u8], offset: usize, count: usize) -> FSResult<usize> {
+        buffer[..count].copy_from_slice(&self.data.as_bytes()[offset..offset + count]);
+        Ok(count)
     }
 }
 
@@ -55,10 +364,37 @@ impl super::InodeOps for RootProcessInode {
         String::from("")
     }
 
-    fn open_diriter(&self, fs: *mut dyn super::FS) -> FSResult<DirIter> {
-        let inodeids = getpids().iter().map(|pid| pid + 1).collect::<Vec<_>>();
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
 
-        Ok(DirIter::new(fs, inodeids.into_boxed_slice()))
+    fn get(&self, name: &str) -> FSResult<usize> {
+        if let Some(index) = PROC_FILES.iter().position(|(file, _)| *file == name) {
+            return Ok(index + 1);
+        }
+
+        let pid = name
+            .parse::<usize>()
+            .map_err(|_| FSError::NoSuchAFileOrDirectory)?;
+
+        if pexists(pid) {
+            Ok(pid + PROCESS_INODES_START)
+        } else {
+            Err(FSError::NoSuchAFileOrDirectory)
+        }
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        let mut pids = getpids();
+        pids.sort_unstable();
+
+        let inodeids = (1..PROCESS_INODES_START)
+            .chain(pids.iter().map(|pid| pid + PROCESS_INODES_START))
+            .filter(|inodeid| *inodeid > cursor)
+            .take(max)
+            .collect();
+
+        Ok(inodeids)
     }
 }
 impl ProcFS {
@@ -78,6 +414,30 @@ impl super::FS for ProcFS {
 
         Ok(FileDescriptor::new(self as *const Self as *mut Self, node))
     }
+
+    fn read(&self, file_descriptor: &mut FileDescriptor, buffer: &mut [u8]) -> FSResult<usize> {
+        if file_descriptor.read_pos == 0 {
+            file_descriptor.node.regenerate();
+        }
+
+        let file_size = file_descriptor.node.size()?;
+        let count = buffer
+            .len()
+            .min(file_size.saturating_sub(file_descriptor.read_pos));
+
+        file_descriptor
+            .node
+            .read(buffer, file_descriptor.read_pos, count)?;
+
+        if count == 0 && !buffer.is_empty() {
+            // the next read starts over with fresh data
+            file_descriptor.read_pos = 0;
+        } else {
+            file_descriptor.read_pos += count;
+        }
+        Ok(count)
+    }
+
     fn root_inode(&self) -> FSResult<Inode> {
         Ok(Arc::new(RootProcessInode))
     }
@@ -87,7 +447,60 @@ impl super::FS for ProcFS {
             return Ok(Some(self.root_inode()?));
         }
 
-        let pid = inode_id - 1;
-        Ok(getinfo(pid).map(ProcInode::new))
+        if inode_id < PROCESS_INODES_START {
+            return Ok(Some(ProcFileInode::create(inode_id - 1)));
+        }
+
+        // reached by opening a path, so the scheduler isn't locked
+        if inode_id & FD_INODES != 0 {
+            let pid = (inode_id & !FD_INODES) >> 32;
+            let index = inode_id & FD_INDEX_MASK;
+            let resource = presources(pid).and_then(|resources| {
+                resources
+                    .into_iter()
+                    .find(|(ri, _)| ri & FD_INDEX_MASK == index)
+            });
+
+            return Ok(resource.map(|(ri, description)| {
+                ProcSnapshotInode::create(inode_id, format!("{}", ri), format!("{}\n", description))
+            }));
+        }
+
+        if inode_id & FD_DIR_INODES != 0 {
+            return Ok(ProcFdDirInode::create(inode_id & !FD_DIR_INODES));
+        }
+
+        if inode_id & MAPS_INODES != 0 {
+            let pid = inode_id & !MAPS_INODES;
+            return Ok(pmaps(pid).map(|maps| ProcSnapshotInode::create(inode_id, "maps", maps)));
+        }
+
+        if inode_id & ABORT_INODES != 0 {
+            let pid = inode_id & !ABORT_INODES;
+            return Ok(
+                pabort(pid).map(|message| ProcSnapshotInode::create(inode_id, "abort", message))
+            );
+        }
+
+        if inode_id & MEMORY_INODES != 0 {
+            let pid = inode_id & !MEMORY_INODES;
+            return Ok(
+                pmemory(pid).map(|memory| ProcSnapshotInode::create(inode_id, "memory", memory))
+            );
+        }
+
+        if inode_id & THREADS_DIR_INODES != 0 {
+            let pid = inode_id & !THREADS_DIR_INODES;
+            return Ok(pexists(pid).then(|| Arc::new(ProcThreadsDirInode(pid)) as Inode));
+        }
+
+        if inode_id & THREAD_INODES != 0 {
+            let tid = inode_id & !THREAD_INODES;
+            return Ok(pthread_status(tid)
+                .map(|status| ProcSnapshotInode::create(inode_id, format!("{}", tid), status)));
+        }
+
+        let pid = inode_id - PROCESS_INODES_START;
+        Ok(pexists(pid).then(|| ProcInode::new(pid)))
     }
 }
@@ -1,14 +1,19 @@
+use crate::memory::shrinker;
 use alloc::string::ToString;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
 use spin::Mutex;
 
-use super::{DirIter, InodeOf};
+use super::InodeOf;
 use super::{FSError, FSResult, FileDescriptor, Inode, InodeOps, InodeType, Path, FS};
 
 pub enum RamInodeData {
     Data(Vec<u8>),
+    /// a file unpacked from the ramdisk referencing it's data in the archive, copied into `Data` the
+    /// first time it is written to or truncated
+    Static(&'static [u8]),
+    /// name -> inodeid, indexed so that looking up a child doesn't scan the directory
     Children(BTreeMap<String, usize>),
     HardLink(Inode),
 }
@@ -17,6 +22,8 @@ pub struct RamInode {
     name: String,
     data: RamInodeData,
     inodeid: usize,
+    /// the extended attributes, see `FS::get_xattr`
+    xattrs: BTreeMap<String, Vec<u8>>,
 }
 impl RamInode {
     fn new(name: String, data: RamInodeData, inodeid: usize) -> Mutex<Self> {
@@ -24,6 +31,7 @@ impl RamInode {
             name,
             data,
             inodeid,
+            xattrs: BTreeMap::new(),
         })
     }
 
@@ -35,6 +43,10 @@ impl RamInode {
         ))
     }
 
+    fn new_static(name: String, data: &'static [u8], inodeid: usize) -> InodeOf<Mutex<Self>> {
+        Arc::new(RamInode::new(name, RamInodeData::Static(data), inodeid))
+    }
+
     fn new_dir(name: String, inodeid: usize) -> InodeOf<Mutex<Self>> {
         Arc::new(RamInode::new(
             name,
@@ -48,10 +60,31 @@ impl RamInode {
     }
 }
 
+impl RamInodeData {
+    /// the data of a file that can be written to, copies static data, keeping only the first `keep` bytes
+    fn data_mut(&mut self, keep: usize) -> FSResult<&mut Vec<u8>> {
+        if let RamInodeData::Static(data) = *self {
+            let data = &data[..keep.min(data.len())];
+            let mut copy = Vec::new();
+            shrinker::retry(data.len(), || copy.try_reserve_exact(data.len()))
+                .map_err(|_| FSError::OutOfMemory)?;
+            copy.extend_from_slice(data);
+
+            *self = RamInodeData::Data(copy);
+        }
+
+        match self {
+            RamInodeData::Data(ref mut data) => Ok(data),
+            _ => Err(FSError::NotAFile),
+        }
+    }
+}
+
 impl InodeOps for Mutex<RamInode> {
     fn size(&self) -> FSResult<usize> {
         match self.lock().data {
             RamInodeData::Data(ref data) => Ok(data.len()),
+            RamInodeData::Static(data) => Ok(data.len()),
             _ => Err(FSError::NotAFile),
         }
     }
@@ -76,12 +109,11 @@ impl InodeOps for Mutex<RamInode> {
 
     fn truncate(&self, size: usize) -> FSResult<()> {
         match self.lock().data {
-            RamInodeData::Data(ref mut data) => {
-                data.truncate(size);
+            RamInodeData::HardLink(ref inode) => inode.truncate(size),
+            ref mut data => {
+                data.data_mut(size)?.truncate(size);
                 Ok(())
             }
-            RamInodeData::HardLink(ref inode) => inode.truncate(size),
-            _ => Err(FSError::NotAFile),
         }
     }
 
@@ -91,6 +123,10 @@ impl InodeOps for Mutex<RamInode> {
                 buffer[..count].copy_from_slice(&data[offset..offset + count]);
                 Ok(count)
             }
+            RamInodeData::Static(data) => {
+                buffer[..count].copy_from_slice(&data[offset..offset + count]);
+                Ok(count)
+            }
             RamInodeData::HardLink(ref inode) => inode.read(buffer, offset, count),
             _ => Err(FSError::NotAFile),
         }
@@ -98,16 +134,22 @@ impl InodeOps for Mutex<RamInode> {
 
     fn write(&self, buffer: &[u8], offset: usize) -> FSResult<usize> {
         match self.lock().data {
-            RamInodeData::Data(ref mut data) => {
-                if data.len() < buffer.len() + offset {
-                    data.resize(buffer.len() + offset, 0);
+            RamInodeData::HardLink(ref inode) => inode.write(buffer, offset),
+            ref mut data => {
+                let data = data.data_mut(usize::MAX)?;
+                let end = offset
+                    .checked_add(buffer.len())
+                    .ok_or(FSError::OutOfMemory)?;
+                if data.len() < end {
+                    let additional = end - data.len();
+                    shrinker::retry(additional, || data.try_reserve(additional))
+                        .map_err(|_| FSError::OutOfMemory)?;
+                    data.resize(end, 0);
                 }
 
-                data[offset..(offset + buffer.len())].copy_from_slice(buffer);
+                data[offset..end].copy_from_slice(buffer);
                 Ok(buffer.len())
             }
-            RamInodeData::HardLink(ref inode) => inode.write(buffer, offset),
-            _ => Err(FSError::NotAFile),
         }
     }
 
@@ -126,10 +168,20 @@ impl InodeOps for Mutex<RamInode> {
         }
     }
 
+    fn remove(&self, name: &str) -> FSResult<usize> {
+        match self.lock().data {
+            RamInodeData::Children(ref mut tree) => {
+                tree.remove(name).ok_or(FSError::NoSuchAFileOrDirectory)
+            }
+            RamInodeData::HardLink(ref inode) => inode.remove(name),
+            _ => Err(FSError::NotADirectory),
+        }
+    }
+
     fn kind(&self) -> InodeType {
         match self.lock().data {
             RamInodeData::Children(_) => InodeType::Directory,
-            RamInodeData::Data(_) => InodeType::File,
+            RamInodeData::Data(_) | RamInodeData::Static(_) => InodeType::File,
             RamInodeData::HardLink(ref inode) => inode.kind(),
         }
     }
@@ -141,39 +193,91 @@ impl InodeOps for Mutex<RamInode> {
     fn inodeid(&self) -> usize {
         self.lock().inodeid
     }
-    fn open_diriter(&self, fs: *mut dyn FS) -> FSResult<DirIter> {
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
         match self.lock().data {
-            RamInodeData::Children(ref data) => Ok(DirIter::new(
-                fs,
-                data.iter().map(|(_, inodeid)| *inodeid).collect(),
-            )),
+            // new inodes get the next inodeid so the cursor is after every child that existed when it was taken
+            RamInodeData::Children(ref data) => {
+                let mut inodeids: Vec<usize> = data
+                    .values()
+                    .copied()
+                    .filter(|inodeid| *inodeid > cursor)
+                    .collect();
+
+                inodeids.sort_unstable();
+                inodeids.truncate(max);
+                Ok(inodeids)
+            }
 
-            RamInodeData::HardLink(ref inode) => inode.open_diriter(fs),
+            RamInodeData::HardLink(ref inode) => inode.children_after(cursor, max),
             _ => Err(FSError::NotADirectory),
         }
     }
 }
 
 pub struct RamFS {
-    inodes: Vec<Inode>,
+    /// indexed by inodeid, removed inodes leave a None behind so that the inodeids stay increasing
+    inodes: Vec<Option<InodeOf<Mutex<RamInode>>>>,
+    /// the files made by `create_unnamed` that weren't linked yet by their inodeid, their slot in
+    /// `inodes` is None until then
+    unnamed: BTreeMap<usize, Weak<Mutex<RamInode>>>,
 }
 
 impl RamFS {
     pub fn new() -> Self {
         Self {
-            inodes: vec![RamInode::new_dir("/".to_string(), 0)],
+            inodes: vec![Some(RamInode::new_dir("/".to_string(), 0))],
+            unnamed: BTreeMap::new(),
         }
     }
 
     fn make_hardlink(&mut self, inodeid: usize, name: String) -> usize {
-        let inode = self.inodes.get_mut(inodeid).unwrap();
-        let inode = inode.clone();
+        let inode = self.inodes[inodeid].clone().unwrap();
         let inodeid = self.inodes.len();
 
         self.inodes
-            .push(RamInode::new_hardlink(name, inode, inodeid));
+            .push(Some(RamInode::new_hardlink(name, inode, inodeid)));
         inodeid
     }
+
+    /// returns the inode `inodeid` refers to following hardlinks
+    fn ram_inode(&self, inodeid: usize) -> FSResult<InodeOf<Mutex<RamInode>>> {
+        let inode = self
+            .inodes
+            .get(inodeid)
+            .cloned()
+            .flatten()
+            .ok_or(FSError::NoSuchAFileOrDirectory)?;
+
+        let target = match inode.lock().data {
+            RamInodeData::HardLink(ref target) => Some(target.inodeid()),
+            _ => None,
+        };
+
+        match target {
+            Some(target) => self.ram_inode(target),
+            None => Ok(inode),
+        }
+    }
+
+    /// the inode `fd` has opened following hardlinks, including unnamed files
+    fn fd_inode(&self, fd: &FileDescriptor) -> FSResult<InodeOf<Mutex<RamInode>>> {
+        let inodeid = fd.node.inodeid();
+        match self.unnamed.get(&inodeid).and_then(Weak::upgrade) {
+            Some(inode) => Ok(inode),
+            None => self.ram_inode(inodeid),
+        }
+    }
+
+    /// like `reslove_path_uncreated` but `.` and `..` aren't valid names and the parent is returned
+    /// as a `RamInode`
+    fn reslove_parent<'a>(&self, path: Path<'a>) -> FSResult<(InodeOf<Mutex<RamInode>>, &'a str)> {
+        let (parent, name) = self.reslove_path_uncreated(path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FSError::InvaildPath);
+        }
+
+        Ok((self.ram_inode(parent.inodeid())?, name))
+    }
 }
 
 impl FS for RamFS {
@@ -183,8 +287,8 @@ impl FS for RamFS {
 
     #[inline]
     fn get_inode(&self, inode_id: usize) -> FSResult<Option<Inode>> {
-        let node = self.inodes.get(inode_id);
-        Ok(node.cloned())
+        let node = self.inodes.get(inode_id).cloned().flatten();
+        Ok(node.map(|node| node as Inode))
     }
 
     fn open(&self, path: Path) -> FSResult<FileDescriptor> {
@@ -236,7 +340,19 @@ impl FS for RamFS {
         resloved.insert(name, inodeid)?;
 
         let node = RamInode::new_file(name.to_string(), &[], inodeid);
-        self.inodes.push(node);
+        self.inodes.push(Some(node));
+
+        Ok(())
+    }
+
+    fn create_static(&mut self, path: Path, data: &'static [u8]) -> FSResult<()> {
+        let inodeid = self.inodes.len();
+
+        let (resloved, name) = self.reslove_path_uncreated(path)?;
+        resloved.insert(name, inodeid)?;
+
+        let node = RamInode::new_static(name.to_string(), data, inodeid);
+        self.inodes.push(Some(node));
 
         Ok(())
     }
@@ -248,11 +364,143 @@ impl FS for RamFS {
         resloved.insert(name, inodeid)?;
 
         let node = RamInode::new_dir(name.to_string(), inodeid);
-        self.inodes.push(node.clone());
+        self.inodes.push(Some(node.clone()));
 
         let inodeid = self.make_hardlink(resloved.inodeid(), "..".to_string());
         node.insert("..", inodeid)?;
 
         Ok(())
     }
+
+    fn remove(&mut self, path: Path) -> FSResult<()> {
+        let (parent, name) = self.reslove_parent(path)?;
+        let inodeid = parent.get(name)?;
+        let inode = self.ram_inode(inodeid)?;
+
+        // an empty directory only has `..`
+        let parent_link = match inode.lock().data {
+            RamInodeData::Children(ref children) => {
+                if children.keys().any(|child| child != "..") {
+                    return Err(FSError::DirectoryNotEmpty);
+                }
+                children.get("..").copied()
+            }
+            _ => None,
+        };
+
+        parent.remove(name)?;
+        self.inodes[inodeid] = None;
+        if let Some(parent_link) = parent_link {
+            self.inodes[parent_link] = None;
+        }
+
+        Ok(())
+    }
+
+    fn rename(&mut self, old: Path, new: Path) -> FSResult<()> {
+        if old == new {
+            return self.reslove_path(old).map(|_| ());
+        }
+        // a directory can't be moved into itself
+        if new.starts_with(old) && new.as_bytes().get(old.len()) == Some(&b'/') {
+            return Err(FSError::InvaildPath);
+        }
+
+        let (old_parent, old_name) = self.reslove_parent(old)?;
+        let (new_parent, new_name) = self.reslove_parent(new)?;
+
+        let inodeid = old_parent.get(old_name)?;
+        new_parent.insert(new_name, inodeid)?;
+        old_parent.remove(old_name)?;
+
+        let inode = self.ram_inode(inodeid)?;
+        inode.lock().name = new_name.to_string();
+
+        // `..` has to point to the new parent
+        if inode.is_dir() && old_parent.inodeid() != new_parent.inodeid() {
+            let old_link = inode.remove("..")?;
+            self.inodes[old_link] = None;
+
+            let link = self.make_hardlink(new_parent.inodeid(), "..".to_string());
+            inode.insert("..", link)?;
+        }
+
+        Ok(())
+    }
+
+    fn create_unnamed(&mut self, dir: Path) -> FSResult<FileDescriptor> {
+        if !self.reslove_path(dir)?.is_dir() {
+            return Err(FSError::NotADirectory);
+        }
+        // the ones that were closed without being linked
+        self.unnamed.retain(|_, inode| inode.strong_count() != 0);
+
+        let inodeid = self.inodes.len();
+        let node = RamInode::new_file(String::new(), &[], inodeid);
+        self.inodes.push(None);
+        self.unnamed.insert(inodeid, Arc::downgrade(&node));
+
+        Ok(FileDescriptor::new(
+            self as *const RamFS as *mut RamFS,
+            node,
+        ))
+    }
+
+    fn link(&mut self, fd: &FileDescriptor, path: Path) -> FSResult<()> {
+        let inodeid = fd.node.inodeid();
+        let node = self
+            .unnamed
+            .get(&inodeid)
+            .and_then(Weak::upgrade)
+            .ok_or(FSError::AlreadyExists)?;
+
+        let (parent, name) = self.reslove_parent(path)?;
+        if parent.contains(name) {
+            let old = parent.get(name)?;
+            if self.ram_inode(old)?.is_dir() {
+                return Err(FSError::NotAFile);
+            }
+
+            // the VFS is locked for writing so nothing sees the name missing in between, files
+            // that have the old file opened keep it
+            parent.remove(name)?;
+            self.inodes[old] = None;
+        }
+
+        parent.insert(name, inodeid)?;
+        node.lock().name = name.to_string();
+        self.inodes[inodeid] = Some(node);
+        self.unnamed.remove(&inodeid);
+        Ok(())
+    }
+
+    fn get_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<Vec<u8>> {
+        self.fd_inode(fd)?
+            .lock()
+            .xattrs
+            .get(name)
+            .cloned()
+            .ok_or(FSError::NoSuchAttribute)
+    }
+
+    fn set_xattr(&self, fd: &FileDescriptor, name: &str, value: &[u8]) -> FSResult<()> {
+        self.fd_inode(fd)?
+            .lock()
+            .xattrs
+            .insert(name.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn list_xattrs(&self, fd: &FileDescriptor) -> FSResult<Vec<String>> {
+        Ok(self.fd_inode(fd)?.lock().xattrs.keys().cloned().collect())
+    }
+
+    fn remove_xattr(&self, fd: &FileDescriptor, name: &str) -> FSResult<()> {
+        self.fd_inode(fd)?
+            .lock()
+            .xattrs
+            .remove(name)
+            .map(|_| ())
+            .ok_or(FSError::NoSuchAttribute)
+    }
 }
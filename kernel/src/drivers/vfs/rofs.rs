@@ -0,0 +1,363 @@
+//! a read-only filesystem for the base system, the builder makes an image of the ramdisk contents
+//! (see build/rofs.rs) which is mounted as sys: instead of unpacking a tar archive into a RamFS
+//! the contents of files are split into blocks of `BLOCK_SIZE` bytes that are compressed on their own
+//! and stored once however many files have them, a block is only decompressed when it is read and
+//! the last `CACHED_BLOCKS` decompressed blocks are kept
+//!
+//! the image is little endian, it's header is followed by the inodes, the blocks, the refs, the names
+//! and then the data of the blocks
+//! - header: the magic, the version, the block size, the amount of inodes, blocks and refs, a
+//!   reserved u32 and then the u64 offsets of the inodes, the blocks, the refs and the names
+//! - inode (24 bytes): the kind (0 for files and 1 for directories), the length of it's name, the
+//!   offset of it's name in the names, it's first ref and it's u64 size, the refs of a file are it's
+//!   blocks and the refs of a directory are it's children sorted by name, the size of a directory
+//!   is the amount of children
+//! - block (16 bytes): the u64 offset of it's data, it's compressed length and it's length, the
+//!   blocks that didn't get smaller compressed are stored as is with both lengths equal
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use spin::Mutex;
+
+use crate::utils::inflate;
+
+use super::{FSError, FSResult, FileDescriptor, Inode, InodeOps, InodeType, Path, FS};
+
+const MAGIC: &[u8; 8] = b"SAFAROFS";
+const VERSION: usize = 1;
+pub const BLOCK_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 64;
+const INODE_SIZE: usize = 24;
+const BLOCK_ENTRY_SIZE: usize = 16;
+
+const KIND_FILE: usize = 0;
+const KIND_DIR: usize = 1;
+
+/// the amount of decompressed blocks kept for the next reads
+const CACHED_BLOCKS: usize = 32;
+
+/// a decompressed block by the address of it's image and it's index
+type CachedBlock = ((usize, usize), Arc<[u8]>);
+
+/// the last decompressed blocks, the most recently read last
+static CACHE: Mutex<VecDeque<CachedBlock>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoFSError {
+    /// doesn't start with the magic or has a different version or block size
+    NotAnImage,
+    /// a table, a name or a block is out of the image or an inode refers to something that doesn't
+    /// exist
+    Corrupted,
+}
+
+pub fn is_image(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+fn u64_at(data: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+/// the `count` entries of `size` bytes starting at `offset` of `data`
+fn table(data: &[u8], offset: usize, count: usize, size: usize) -> Result<&[u8], RoFSError> {
+    count
+        .checked_mul(size)
+        .and_then(|len| len.checked_add(offset))
+        .and_then(|end| data.get(offset..end))
+        .ok_or(RoFSError::Corrupted)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    offset: usize,
+    compressed_len: usize,
+    len: usize,
+}
+
+struct Image {
+    data: &'static [u8],
+    blocks: Vec<Block>,
+}
+
+impl Image {
+    /// calls `then` with the decompressed block `index`
+    fn with_block<R>(&self, index: usize, then: impl FnOnce(&[u8]) -> R) -> FSResult<R> {
+        let block = self.blocks[index];
+        let data = &self.data[block.offset..block.offset + block.compressed_len];
+        if block.compressed_len == block.len {
+            return Ok(then(data));
+        }
+
+        let key = (self.data.as_ptr() as usize, index);
+        let cached = {
+            let mut cache = CACHE.lock();
+            let position = cache.iter().position(|(cached, _)| *cached == key);
+            position.and_then(|position| {
+                let entry = cache.remove(position)?;
+                let block = entry.1.clone();
+                cache.push_back(entry);
+                Some(block)
+            })
+        };
+
+        let block = match cached {
+            Some(block) => block,
+            None => {
+                // decompressed without holding the cache so other blocks can be read meanwhile
+                let mut output = Vec::with_capacity(block.len);
+                inflate::inflate(data, &mut output).map_err(|_| FSError::Corrupted)?;
+                if output.len() != block.len {
+                    return Err(FSError::Corrupted);
+                }
+
+                let output: Arc<[u8]> = output.into();
+                let mut cache = CACHE.lock();
+                if cache.len() == CACHED_BLOCKS {
+                    cache.pop_front();
+                }
+                cache.push_back((key, output.clone()));
+                output
+            }
+        };
+
+        Ok(then(&block))
+    }
+}
+
+enum RoInodeData {
+    /// the blocks of the file
+    File(Vec<usize>),
+    /// the names and inodeids of the children sorted by name
+    Dir(Vec<(&'static str, usize)>),
+}
+
+pub struct RoInode {
+    image: Arc<Image>,
+    name: &'static str,
+    inodeid: usize,
+    /// the size of the file in bytes
+    size: usize,
+    data: RoInodeData,
+}
+
+impl InodeOps for RoInode {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn get(&self, name: &str) -> FSResult<usize> {
+        match self.data {
+            RoInodeData::Dir(ref children) => children
+                .binary_search_by(|(child, _)| (*child).cmp(name))
+                .map(|index| children[index].1)
+                .map_err(|_| FSError::NoSuchAFileOrDirectory),
+            RoInodeData::File(_) => Err(FSError::NotADirectory),
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_ok()
+    }
+
+    fn size(&self) -> FSResult<usize> {
+        match self.data {
+            RoInodeData::File(_) => Ok(self.size),
+            RoInodeData::Dir(_) => Err(FSError::NotAFile),
+        }
+    }
+
+    fn read(&self, buffer: &mut [u8], offset: usize, count: usize) -> FSResult<usize> {
+        let RoInodeData::File(ref blocks) = self.data else {
+            return Err(FSError::NotAFile);
+        };
+
+        let mut read = 0;
+        while read < count {
+            let pos = offset + read;
+            let start = pos % BLOCK_SIZE;
+
+            read += self.image.with_block(blocks[pos / BLOCK_SIZE], |block| {
+                let amount = (block.len() - start).min(count - read);
+                buffer[read..read + amount].copy_from_slice(&block[start..start + amount]);
+                amount
+            })?;
+        }
+
+        Ok(read)
+    }
+
+    fn inodeid(&self) -> usize {
+        self.inodeid
+    }
+
+    fn kind(&self) -> InodeType {
+        match self.data {
+            RoInodeData::File(_) => InodeType::File,
+            RoInodeData::Dir(_) => InodeType::Directory,
+        }
+    }
+
+    fn children_after(&self, cursor: usize, max: usize) -> FSResult<Vec<usize>> {
+        let RoInodeData::Dir(ref children) = self.data else {
+            return Err(FSError::NotADirectory);
+        };
+
+        let mut inodeids: Vec<usize> = children
+            .iter()
+            .map(|(_, inodeid)| *inodeid)
+            .filter(|inodeid| *inodeid > cursor)
+            .collect();
+
+        inodeids.sort_unstable();
+        inodeids.truncate(max);
+        Ok(inodeids)
+    }
+}
+
+pub struct RoFS {
+    /// indexed by inodeid, the root is 0
+    inodes: Vec<Arc<RoInode>>,
+}
+
+impl RoFS {
+    /// reads the image `data`, everything but the data of the blocks is checked and read here
+    pub fn new(data: &'static [u8]) -> Result<Self, RoFSError> {
+        let header = data.get(..HEADER_SIZE).ok_or(RoFSError::NotAnImage)?;
+        if !is_image(header) || u32_at(header, 8) != VERSION || u32_at(header, 12) != BLOCK_SIZE {
+            return Err(RoFSError::NotAnImage);
+        }
+
+        let (inode_count, block_count, ref_count) =
+            (u32_at(header, 16), u32_at(header, 20), u32_at(header, 24));
+        let inodes = table(data, u64_at(header, 32), inode_count, INODE_SIZE)?;
+        let blocks = table(data, u64_at(header, 40), block_count, BLOCK_ENTRY_SIZE)?;
+        let refs = table(data, u64_at(header, 48), ref_count, 4)?;
+        let names = data.get(u64_at(header, 56)..).ok_or(RoFSError::Corrupted)?;
+
+        let blocks = blocks
+            .chunks_exact(BLOCK_ENTRY_SIZE)
+            .map(|entry| {
+                let block = Block {
+                    offset: u64_at(entry, 0),
+                    compressed_len: u32_at(entry, 8),
+                    len: u32_at(entry, 12),
+                };
+
+                let in_image = table(data, block.offset, block.compressed_len, 1).is_ok();
+                if !in_image || block.compressed_len > block.len || block.len > BLOCK_SIZE {
+                    return Err(RoFSError::Corrupted);
+                }
+                Ok(block)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let refs: Vec<usize> = refs.chunks_exact(4).map(|id| u32_at(id, 0)).collect();
+
+        // the name, kind, size and refs of each inode
+        let mut raw = Vec::with_capacity(inode_count);
+        for inode in inodes.chunks_exact(INODE_SIZE) {
+            let (kind, size) = (u32_at(inode, 0), u64_at(inode, 16));
+            let name = table(names, u32_at(inode, 8), u32_at(inode, 4), 1)?;
+            let name = core::str::from_utf8(name).map_err(|_| RoFSError::Corrupted)?;
+
+            let count = match kind {
+                KIND_FILE => size.div_ceil(BLOCK_SIZE),
+                KIND_DIR => size,
+                _ => return Err(RoFSError::Corrupted),
+            };
+            let start = u32_at(inode, 12);
+            let refs = start
+                .checked_add(count)
+                .and_then(|end| refs.get(start..end))
+                .ok_or(RoFSError::Corrupted)?;
+
+            raw.push((name, kind, size, refs));
+        }
+
+        if raw.first().is_none_or(|(_, kind, ..)| *kind != KIND_DIR) {
+            return Err(RoFSError::Corrupted);
+        }
+
+        let image = Arc::new(Image { data, blocks });
+        let mut inodes = Vec::with_capacity(inode_count);
+        for (inodeid, &(name, kind, size, refs)) in raw.iter().enumerate() {
+            let data = if kind == KIND_FILE {
+                // every block is full but the last
+                let full = refs.iter().enumerate().all(|(index, block)| {
+                    let len = (size - index * BLOCK_SIZE).min(BLOCK_SIZE);
+                    image
+                        .blocks
+                        .get(*block)
+                        .is_some_and(|block| block.len == len)
+                });
+                if !full {
+                    return Err(RoFSError::Corrupted);
+                }
+                RoInodeData::File(refs.to_vec())
+            } else {
+                let children = refs
+                    .iter()
+                    .map(|child| match raw.get(*child) {
+                        Some((name, ..)) if *child != 0 => Ok((*name, *child)),
+                        _ => Err(RoFSError::Corrupted),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if !children.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+                    return Err(RoFSError::Corrupted);
+                }
+                RoInodeData::Dir(children)
+            };
+
+            inodes.push(Arc::new(RoInode {
+                image: image.clone(),
+                name,
+                inodeid,
+                size,
+                data,
+            }));
+        }
+
+        Ok(Self { inodes })
+    }
+}
+
+impl FS for RoFS {
+    fn name(&self) -> &'static str {
+        "rofs"
+    }
+
+    fn get_inode(&self, inode_id: usize) -> FSResult<Option<Inode>> {
+        Ok(self
+            .inodes
+            .get(inode_id)
+            .map(|inode| inode.clone() as Inode))
+    }
+
+    fn open(&self, path: Path) -> FSResult<FileDescriptor> {
+        let node = self.reslove_path(path)?;
+
+        Ok(FileDescriptor::new(self as *const RoFS as *mut RoFS, node))
+    }
+
+    fn read(&self, file_descriptor: &mut FileDescriptor, buffer: &mut [u8]) -> FSResult<usize> {
+        let file_size = file_descriptor.node.size()?;
+        let count = buffer
+            .len()
+            .min(file_size.saturating_sub(file_descriptor.read_pos));
+
+        file_descriptor
+            .node
+            .read(buffer, file_descriptor.read_pos, count)?;
+
+        file_descriptor.read_pos += count;
+        Ok(count)
+    }
+}
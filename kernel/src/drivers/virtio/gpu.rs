@@ -0,0 +1,445 @@
+//! virtio-gpu 2D driver, displays a guest memory backed resource on scanout 0
+//! it is attached to the framebuffer as a `DisplayDriver` so the TTY renders to it when present
+use alloc::{boxed::Box, vec::Vec};
+use spin::Mutex;
+
+use crate::{
+    drivers::{
+        framebuffer::{DisplayDriver, FrameBufferInfo, PixelFormat, VideoMode, FRAMEBUFFER_DRIVER},
+        pci,
+        vfs::{FSError, FSResult},
+    },
+    error, info,
+    memory::page_allocator::{PageAlloc, GLOBAL_PAGE_ALLOCATOR},
+    terminal::FRAMEBUFFER_TERMINAL,
+};
+
+use super::{
+    as_bytes, as_bytes_mut, VirtQueue, VirtioDevice, VirtioError, VIRTIO_DEVICE_ID_BASE,
+    VIRTIO_VENDOR_ID,
+};
+
+const VIRTIO_GPU_DEVICE_ID: u16 = 16;
+const CONTROL_QUEUE: u16 = 0;
+
+const CMD_GET_DISPLAY_INFO: u32 = 0x0100;
+const CMD_RESOURCE_CREATE_2D: u32 = 0x0101;
+const CMD_RESOURCE_UNREF: u32 = 0x0102;
+const CMD_SET_SCANOUT: u32 = 0x0103;
+const CMD_RESOURCE_FLUSH: u32 = 0x0104;
+const CMD_TRANSFER_TO_HOST_2D: u32 = 0x0105;
+const CMD_RESOURCE_ATTACH_BACKING: u32 = 0x0106;
+const CMD_RESOURCE_DETACH_BACKING: u32 = 0x0107;
+
+const RESP_OK_NODATA: u32 = 0x1100;
+const RESP_OK_DISPLAY_INFO: u32 = 0x1101;
+
+/// blue, green, red, unused in memory, same as the limine framebuffer
+const FORMAT_B8G8R8X8_UNORM: u32 = 2;
+
+const MAX_SCANOUTS: usize = 16;
+const BYTES_PER_PIXEL: usize = 4;
+
+/// offered in addition to the display's preferred mode
+const EXTRA_MODES: [(u64, u64); 5] = [
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1920, 1080),
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct CtrlHeader {
+    kind: u32,
+    flags: u32,
+    fence_id: u64,
+    ctx_id: u32,
+    ring_idx: u8,
+    _padding: [u8; 3],
+}
+
+impl CtrlHeader {
+    const fn new(kind: u32) -> Self {
+        Self {
+            kind,
+            flags: 0,
+            fence_id: 0,
+            ctx_id: 0,
+            ring_idx: 0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct DisplayOne {
+    rect: Rect,
+    enabled: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct DisplayInfo {
+    header: CtrlHeader,
+    modes: [DisplayOne; MAX_SCANOUTS],
+}
+
+#[repr(C)]
+struct ResourceCreate2D {
+    header: CtrlHeader,
+    resource_id: u32,
+    format: u32,
+    width: u32,
+    height: u32,
+}
+
+#[repr(C)]
+struct ResourceId {
+    header: CtrlHeader,
+    resource_id: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct AttachBacking {
+    header: CtrlHeader,
+    resource_id: u32,
+    nr_entries: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct MemEntry {
+    addr: u64,
+    length: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct SetScanout {
+    header: CtrlHeader,
+    rect: Rect,
+    scanout_id: u32,
+    resource_id: u32,
+}
+
+#[repr(C)]
+struct TransferToHost2D {
+    header: CtrlHeader,
+    rect: Rect,
+    offset: u64,
+    resource_id: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct ResourceFlush {
+    header: CtrlHeader,
+    rect: Rect,
+    resource_id: u32,
+    _padding: u32,
+}
+
+/// a resource displayed on the scanout and the guest memory backing it
+struct Scanout {
+    resource_id: u32,
+    width: u32,
+    height: u32,
+    /// the memory the video buffer points to
+    _backing: Vec<u8, PageAlloc>,
+}
+
+struct Inner {
+    queue: VirtQueue,
+    scanout: Option<Scanout>,
+    next_resource_id: u32,
+}
+
+impl Inner {
+    /// sends `request` (followed by `extra`) on the control queue and waits for the response
+    /// the requests and responses live on the heap because the device needs their physical addresses
+    fn command<R>(
+        &mut self,
+        request: &[u8],
+        extra: &[u8],
+        response: &mut R,
+    ) -> Result<(), VirtioError> {
+        let mut request = Vec::from(request);
+        request.extend_from_slice(extra);
+
+        let mut response_buffer: Box<[u8]> = Vec::from(as_bytes_mut(response)).into_boxed_slice();
+        self.queue
+            .submit(&[&request], &mut [&mut response_buffer])?;
+
+        as_bytes_mut(response).copy_from_slice(&response_buffer);
+        Ok(())
+    }
+
+    /// sends a command that is answered with a `RESP_OK_NODATA` header
+    fn command_nodata<T>(&mut self, request: &T, extra: &[u8]) -> FSResult<()> {
+        let mut response = CtrlHeader::default();
+        self.command(as_bytes(request), extra, &mut response)
+            .map_err(|_| FSError::OperationNotSupported)?;
+
+        if response.kind != RESP_OK_NODATA {
+            error!(VirtioGpu, "command failed with {:#x}", response.kind);
+            return Err(FSError::OperationNotSupported);
+        }
+        Ok(())
+    }
+
+    fn display_info(&mut self) -> Option<DisplayInfo> {
+        let request = CtrlHeader::new(CMD_GET_DISPLAY_INFO);
+        let mut response = DisplayInfo::default();
+        self.command(as_bytes(&request), &[], &mut response).ok()?;
+
+        (response.header.kind == RESP_OK_DISPLAY_INFO).then_some(response)
+    }
+
+    /// creates a resource for `mode` backed by newly allocated memory and makes it scanout 0
+    /// the previous scanout resource is destroyed afterwards
+    fn create_scanout(&mut self, mode: VideoMode) -> FSResult<&'static mut [u8]> {
+        let (width, height) = (mode.width as u32, mode.height as u32);
+        let size = width as usize * height as usize * BYTES_PER_PIXEL;
+
+        let mut backing = Vec::with_capacity_in(size, &*GLOBAL_PAGE_ALLOCATOR);
+        backing.resize(size, 0);
+
+        let resource_id = self.next_resource_id;
+        self.next_resource_id += 1;
+
+        self.command_nodata(
+            &ResourceCreate2D {
+                header: CtrlHeader::new(CMD_RESOURCE_CREATE_2D),
+                resource_id,
+                format: FORMAT_B8G8R8X8_UNORM,
+                width,
+                height,
+            },
+            &[],
+        )?;
+
+        // the backing pages aren't physically contiguous, each page gets an entry
+        let mut entries: Vec<MemEntry> = Vec::new();
+        for page in backing.chunks(crate::memory::paging::PAGE_SIZE) {
+            let addr = super::virt_to_phys(page.as_ptr() as usize)
+                .ok_or(FSError::OperationNotSupported)?;
+            match entries.last_mut() {
+                Some(last) if last.addr + last.length as u64 == addr as u64 => {
+                    last.length += page.len() as u32
+                }
+                _ => entries.push(MemEntry {
+                    addr: addr as u64,
+                    length: page.len() as u32,
+                    _padding: 0,
+                }),
+            }
+        }
+
+        let entries_bytes = unsafe {
+            core::slice::from_raw_parts(
+                entries.as_ptr() as *const u8,
+                entries.len() * size_of::<MemEntry>(),
+            )
+        };
+        self.command_nodata(
+            &AttachBacking {
+                header: CtrlHeader::new(CMD_RESOURCE_ATTACH_BACKING),
+                resource_id,
+                nr_entries: entries.len() as u32,
+            },
+            entries_bytes,
+        )?;
+
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.command_nodata(
+            &SetScanout {
+                header: CtrlHeader::new(CMD_SET_SCANOUT),
+                rect,
+                scanout_id: 0,
+                resource_id,
+            },
+            &[],
+        )?;
+
+        // the buffer is owned by the scanout and lives until the next mode switch
+        let video_buffer =
+            unsafe { core::slice::from_raw_parts_mut(backing.as_mut_ptr(), backing.len()) };
+
+        let old = self.scanout.replace(Scanout {
+            resource_id,
+            width,
+            height,
+            _backing: backing,
+        });
+
+        if let Some(old) = old {
+            for kind in [CMD_RESOURCE_DETACH_BACKING, CMD_RESOURCE_UNREF] {
+                let request = ResourceId {
+                    header: CtrlHeader::new(kind),
+                    resource_id: old.resource_id,
+                    _padding: 0,
+                };
+                _ = self.command_nodata(&request, &[]);
+            }
+        }
+
+        Ok(video_buffer)
+    }
+
+    /// copies the scanout's backing to the host and displays it
+    fn flush(&mut self) {
+        let Some(scanout) = &self.scanout else {
+            return;
+        };
+
+        let resource_id = scanout.resource_id;
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: scanout.width,
+            height: scanout.height,
+        };
+
+        _ = self.command_nodata(
+            &TransferToHost2D {
+                header: CtrlHeader::new(CMD_TRANSFER_TO_HOST_2D),
+                rect,
+                offset: 0,
+                resource_id,
+                _padding: 0,
+            },
+            &[],
+        );
+        _ = self.command_nodata(
+            &ResourceFlush {
+                header: CtrlHeader::new(CMD_RESOURCE_FLUSH),
+                rect,
+                resource_id,
+                _padding: 0,
+            },
+            &[],
+        );
+    }
+}
+
+pub struct VirtioGpu {
+    inner: Mutex<Inner>,
+    modes: Vec<VideoMode>,
+    _device: VirtioDevice,
+}
+
+impl VirtioGpu {
+    fn new(pci: &pci::PciDevice) -> Result<Self, VirtioError> {
+        let device = VirtioDevice::new(pci, 0)?;
+        let queue = device.queue(CONTROL_QUEUE, None)?;
+        device.finish();
+
+        let mut inner = Inner {
+            queue,
+            scanout: None,
+            next_resource_id: 1,
+        };
+
+        let mode = |width: u64, height: u64| VideoMode {
+            width,
+            height,
+            bpp: (BYTES_PER_PIXEL * 8) as u16,
+        };
+
+        let mut modes = Vec::new();
+        if let Some(info) = inner.display_info() {
+            let preferred = info.modes[0];
+            if preferred.enabled != 0 && preferred.rect.width != 0 && preferred.rect.height != 0 {
+                modes.push(mode(
+                    preferred.rect.width as u64,
+                    preferred.rect.height as u64,
+                ));
+            }
+        }
+
+        for (width, height) in EXTRA_MODES {
+            let mode = mode(width, height);
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+        }
+
+        Ok(Self {
+            inner: Mutex::new(inner),
+            modes,
+            _device: device,
+        })
+    }
+}
+
+impl DisplayDriver for VirtioGpu {
+    fn modes(&self) -> Vec<VideoMode> {
+        self.modes.clone()
+    }
+
+    fn set_mode(&self, mode: VideoMode) -> FSResult<(&'static mut [u8], FrameBufferInfo)> {
+        let video_buffer = self.inner.lock().create_scanout(mode)?;
+        let info = FrameBufferInfo {
+            stride: mode.width as usize,
+            bytes_per_pixel: BYTES_PER_PIXEL,
+            pixel_format: PixelFormat::Rgb,
+        };
+
+        Ok((video_buffer, info))
+    }
+
+    fn flush(&self) {
+        self.inner.lock().flush();
+    }
+}
+
+/// looks for a virtio-gpu device and if found makes it the display the TTY renders to
+pub fn init() {
+    let Some(pci) = pci::devices().into_iter().find(|device| {
+        device.vendor_id == VIRTIO_VENDOR_ID
+            && device.device_id == VIRTIO_DEVICE_ID_BASE + VIRTIO_GPU_DEVICE_ID
+    }) else {
+        return;
+    };
+
+    info!(
+        VirtioGpu,
+        "found at {:02x}:{:02x}.{}", pci.bus, pci.device, pci.function
+    );
+
+    let gpu = match VirtioGpu::new(&pci) {
+        Ok(gpu) => Box::leak(Box::new(gpu)),
+        Err(err) => {
+            error!(VirtioGpu, "failed to initialize: {:?}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = FRAMEBUFFER_DRIVER.write().attach_driver(gpu) {
+        error!(VirtioGpu, "failed to attach: {:?}", err);
+        return;
+    }
+
+    // the TTY's cursor is relative to the old buffer
+    FRAMEBUFFER_TERMINAL.write().clear();
+    info!(VirtioGpu, "attached, modes: {:?}", gpu.modes);
+}
@@ -0,0 +1,437 @@
+//! the virtio over PCI (modern, virtio 1.0+) transport and split virtqueues
+//! each queue has one request in flight at a time, completion is either polled or signaled by an
+//! MSI-X interrupt
+pub mod gpu;
+pub mod ninep;
+pub mod sound;
+
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    sync::atomic::{fence, Ordering},
+};
+
+use alloc::vec::Vec;
+
+use crate::{
+    hddm,
+    memory::{
+        frame_allocator,
+        page_allocator::GLOBAL_PAGE_ALLOCATOR,
+        paging::{current_root_table, Page, PAGE_SIZE},
+        PhysAddr, VirtAddr,
+    },
+};
+
+use super::pci::PciDevice;
+
+pub const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+/// modern virtio devices have a PCI device id of 0x1040 + the virtio device id
+pub const VIRTIO_DEVICE_ID_BASE: u16 = 0x1040;
+
+const PCI_CAP_VENDOR: u8 = 0x09;
+const CAP_COMMON_CFG: u8 = 1;
+const CAP_NOTIFY_CFG: u8 = 2;
+const CAP_DEVICE_CFG: u8 = 4;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+const STATUS_FAILED: u8 = 128;
+
+/// feature bit 32
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+const NO_VECTOR: u16 = 0xFFFF;
+
+/// the maximum amount of descriptors a queue is created with
+const MAX_QUEUE_SIZE: u16 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtioError {
+    /// the device doesn't have the required capabilities or BARs
+    InvaildDevice,
+    /// the device didn't accept the features we support
+    FeaturesRejected,
+    /// the queue doesn't exist
+    NoQueue,
+    OutOfMemory,
+    /// the request has more segments than the queue has descriptors
+    RequestTooLarge,
+}
+
+#[repr(C)]
+struct CommonCfg {
+    device_feature_select: u32,
+    device_feature: u32,
+    driver_feature_select: u32,
+    driver_feature: u32,
+    config_msix_vector: u16,
+    num_queues: u16,
+    device_status: u8,
+    config_generation: u8,
+    queue_select: u16,
+    queue_size: u16,
+    queue_msix_vector: u16,
+    queue_enable: u16,
+    queue_notify_off: u16,
+    queue_desc_low: u32,
+    queue_desc_high: u32,
+    queue_driver_low: u32,
+    queue_driver_high: u32,
+    queue_device_low: u32,
+    queue_device_high: u32,
+}
+
+macro_rules! read_cfg {
+    ($cfg: expr, $field: ident) => {
+        unsafe { core::ptr::read_volatile(addr_of!((*$cfg).$field)) }
+    };
+}
+
+macro_rules! write_cfg {
+    ($cfg: expr, $field: ident, $value: expr) => {
+        unsafe { core::ptr::write_volatile(addr_of_mut!((*$cfg).$field), $value) }
+    };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// the bytes of a request or response structure
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+fn as_bytes_mut<T>(value: &mut T) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(value as *mut T as *mut u8, size_of::<T>()) }
+}
+
+/// translates the kernel address `addr` to a physical address
+/// `addr` must be mapped by 4KiB pages (the heap and the page allocator), not by the huge pages of the
+/// hddm
+fn virt_to_phys(addr: VirtAddr) -> Option<PhysAddr> {
+    let frame = unsafe { current_root_table().get_frame(Page::containing_address(addr))? };
+    Some(frame.start_address + addr % PAGE_SIZE)
+}
+
+/// allocates a zeroed frame for the device to access, returns it's physical and virtual address
+fn allocate_dma_frame() -> Result<(PhysAddr, VirtAddr), VirtioError> {
+    let frame = frame_allocator::allocate_frame().ok_or(VirtioError::OutOfMemory)?;
+    let virt = frame.start_address | hddm();
+    unsafe {
+        core::ptr::write_bytes(virt as *mut u8, 0, PAGE_SIZE);
+    }
+    Ok((frame.start_address, virt))
+}
+
+/// a split virtqueue, each of the descriptor table, the available ring and the used ring gets a frame
+pub struct VirtQueue {
+    index: u16,
+    size: u16,
+    descriptors: *mut Descriptor,
+    /// flags, idx, ring[size]
+    avail: *mut u16,
+    /// flags, idx, ring[size]
+    used: *mut u16,
+    last_used: u16,
+    notify: *mut u16,
+}
+
+impl VirtQueue {
+    fn avail_idx(&self) -> *mut u16 {
+        unsafe { self.avail.add(1) }
+    }
+
+    fn used_idx(&self) -> *const u16 {
+        unsafe { self.used.add(1) }
+    }
+
+    /// adds a request made of the device readable buffers `request` followed by the device writable
+    /// buffers `response` and notifies the device without waiting for it
+    /// only one request may be in flight, the buffers must live until `pop_used` returns it
+    pub fn push(
+        &mut self,
+        request: &[&[u8]],
+        response: &mut [&mut [u8]],
+    ) -> Result<(), VirtioError> {
+        // the buffers may cross page boundries and the pages aren't physically contiguous, so every page
+        // gets it's own descriptor
+        let mut segments: Vec<(PhysAddr, u32, u16)> = Vec::new();
+        let mut push = |buffer: &[u8], flags: u16| -> Result<(), VirtioError> {
+            let mut addr = buffer.as_ptr() as VirtAddr;
+            let end = addr + buffer.len();
+
+            while addr < end {
+                let len = (PAGE_SIZE - addr % PAGE_SIZE).min(end - addr);
+                let phys = virt_to_phys(addr).ok_or(VirtioError::OutOfMemory)?;
+                segments.push((phys, len as u32, flags));
+                addr += len;
+            }
+            Ok(())
+        };
+
+        for buffer in request {
+            push(buffer, 0)?;
+        }
+        for buffer in response.iter() {
+            push(buffer, DESC_F_WRITE)?;
+        }
+
+        if segments.is_empty() || segments.len() > self.size as usize {
+            return Err(VirtioError::RequestTooLarge);
+        }
+
+        // there is only ever one request in flight so the chain always starts at descriptor 0
+        let count = segments.len();
+        for (i, (addr, len, flags)) in segments.into_iter().enumerate() {
+            let next = if i + 1 < count { DESC_F_NEXT } else { 0 };
+            unsafe {
+                self.descriptors.add(i).write_volatile(Descriptor {
+                    addr: addr as u64,
+                    len,
+                    flags: flags | next,
+                    next: (i + 1) as u16,
+                });
+            }
+        }
+
+        unsafe {
+            let idx = self.avail_idx().read_volatile();
+            self.avail
+                .add(2 + (idx % self.size) as usize)
+                .write_volatile(0);
+            fence(Ordering::SeqCst);
+            self.avail_idx().write_volatile(idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+            self.notify.write_volatile(self.index);
+        }
+        Ok(())
+    }
+
+    /// returns the amount of bytes the device wrote to the next request it used if any
+    pub fn pop_used(&mut self) -> Option<u32> {
+        unsafe {
+            if self.used_idx().read_volatile() == self.last_used {
+                return None;
+            }
+            fence(Ordering::SeqCst);
+
+            let elem =
+                (self.used.add(2) as *const UsedElem).add((self.last_used % self.size) as usize);
+            self.last_used = self.last_used.wrapping_add(1);
+            Some(addr_of!((*elem).len).read_volatile())
+        }
+    }
+
+    /// pushes a request (see `push`) and spins until the device uses it
+    /// returns the amount of bytes the device wrote
+    pub fn submit(
+        &mut self,
+        request: &[&[u8]],
+        response: &mut [&mut [u8]],
+    ) -> Result<u32, VirtioError> {
+        self.push(request, response)?;
+
+        loop {
+            if let Some(len) = self.pop_used() {
+                return Ok(len);
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// the queue memory is owned by the queue and only accessed through &mut self
+unsafe impl Send for VirtQueue {}
+
+/// a virtio device on the PCI bus, after `VirtioDevice::new` the device is reset and acknowledged
+/// then the queues can be set up using `VirtioDevice::queue` and finally `VirtioDevice::finish` makes
+/// the device live
+pub struct VirtioDevice {
+    common: *mut CommonCfg,
+    notify_base: VirtAddr,
+    notify_multiplier: u32,
+    /// 0 if the device has no device specific configuration
+    device_cfg: VirtAddr,
+}
+
+// the configuration structures are device memory
+unsafe impl Send for VirtioDevice {}
+unsafe impl Sync for VirtioDevice {}
+
+impl VirtioDevice {
+    /// finds the configuration structures of `pci`, resets it and negotiates the features
+    /// the device specific `features` (bits 0..32) are offered in addition to VIRTIO_F_VERSION_1
+    pub fn new(pci: &PciDevice, features: u32) -> Result<Self, VirtioError> {
+        pci.enable_bus_master();
+
+        let mut common = None;
+        let mut notify = None;
+        let mut device_cfg = None;
+
+        for (id, offset) in pci.capabilities() {
+            if id != PCI_CAP_VENDOR {
+                continue;
+            }
+
+            let cfg_type = pci.read_u8(offset + 3);
+            let bar = pci.read_u8(offset + 4);
+            let bar_offset = pci.read_u32(offset + 8) as usize;
+            let length = pci.read_u32(offset + 12) as usize;
+
+            let map = || -> Result<VirtAddr, VirtioError> {
+                let phys = pci.bar(bar).ok_or(VirtioError::InvaildDevice)?;
+                let ptr = GLOBAL_PAGE_ALLOCATOR
+                    .lock()
+                    .map_mmio(phys + bar_offset, length)
+                    .map_err(|_| VirtioError::OutOfMemory)?;
+                Ok(ptr as VirtAddr)
+            };
+
+            match cfg_type {
+                CAP_COMMON_CFG if common.is_none() => common = Some(map()?),
+                CAP_NOTIFY_CFG if notify.is_none() => {
+                    let multiplier = pci.read_u32(offset + 16);
+                    notify = Some((map()?, multiplier))
+                }
+                CAP_DEVICE_CFG if device_cfg.is_none() => device_cfg = Some(map()?),
+                _ => {}
+            }
+        }
+
+        let (Some(common), Some((notify_base, notify_multiplier))) = (common, notify) else {
+            return Err(VirtioError::InvaildDevice);
+        };
+
+        let this = Self {
+            common: common as *mut CommonCfg,
+            notify_base,
+            notify_multiplier,
+            device_cfg: device_cfg.unwrap_or(0),
+        };
+
+        this.set_status(0);
+        while this.status() != 0 {
+            core::hint::spin_loop();
+        }
+
+        this.set_status(STATUS_ACKNOWLEDGE);
+        this.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        write_cfg!(this.common, device_feature_select, 0);
+        let offered = read_cfg!(this.common, device_feature) & features;
+        write_cfg!(this.common, driver_feature_select, 0);
+        write_cfg!(this.common, driver_feature, offered);
+
+        write_cfg!(this.common, device_feature_select, 1);
+        let offered_high = read_cfg!(this.common, device_feature);
+        if offered_high & FEATURE_VERSION_1 == 0 {
+            this.set_status(STATUS_FAILED);
+            return Err(VirtioError::FeaturesRejected);
+        }
+        write_cfg!(this.common, driver_feature_select, 1);
+        write_cfg!(this.common, driver_feature, FEATURE_VERSION_1);
+
+        this.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if this.status() & STATUS_FEATURES_OK == 0 {
+            this.set_status(STATUS_FAILED);
+            return Err(VirtioError::FeaturesRejected);
+        }
+        // configuration changes are ignored
+        write_cfg!(this.common, config_msix_vector, NO_VECTOR);
+
+        Ok(this)
+    }
+
+    /// reads the u32 at `offset` in the device specific configuration
+    /// returns None if the device doesn't have one
+    pub fn read_device_cfg(&self, offset: usize) -> Option<u32> {
+        if self.device_cfg == 0 {
+            return None;
+        }
+        unsafe { Some(((self.device_cfg + offset) as *const u32).read_volatile()) }
+    }
+
+    fn status(&self) -> u8 {
+        read_cfg!(self.common, device_status)
+    }
+
+    fn set_status(&self, status: u8) {
+        write_cfg!(self.common, device_status, status);
+    }
+
+    /// sets up the queue number `index`, the device interrupts through the MSI-X table entry
+    /// `msix_entry` when it uses a buffer, if None completion has to be polled
+    pub fn queue(&self, index: u16, msix_entry: Option<u16>) -> Result<VirtQueue, VirtioError> {
+        if index >= read_cfg!(self.common, num_queues) {
+            return Err(VirtioError::NoQueue);
+        }
+
+        write_cfg!(self.common, queue_select, index);
+        let size = read_cfg!(self.common, queue_size).min(MAX_QUEUE_SIZE);
+        if size == 0 {
+            return Err(VirtioError::NoQueue);
+        }
+        write_cfg!(self.common, queue_size, size);
+
+        let (desc_phys, desc_virt) = allocate_dma_frame()?;
+        let (avail_phys, avail_virt) = allocate_dma_frame()?;
+        let (used_phys, used_virt) = allocate_dma_frame()?;
+
+        write_cfg!(self.common, queue_desc_low, desc_phys as u32);
+        write_cfg!(self.common, queue_desc_high, (desc_phys >> 32) as u32);
+        write_cfg!(self.common, queue_driver_low, avail_phys as u32);
+        write_cfg!(self.common, queue_driver_high, (avail_phys >> 32) as u32);
+        write_cfg!(self.common, queue_device_low, used_phys as u32);
+        write_cfg!(self.common, queue_device_high, (used_phys >> 32) as u32);
+
+        let notify_off = read_cfg!(self.common, queue_notify_off);
+        let notify = self.notify_base + notify_off as usize * self.notify_multiplier as usize;
+
+        let avail = avail_virt as *mut u16;
+        let flags = if msix_entry.is_some() {
+            0
+        } else {
+            AVAIL_F_NO_INTERRUPT
+        };
+        unsafe { avail.write_volatile(flags) };
+        write_cfg!(
+            self.common,
+            queue_msix_vector,
+            msix_entry.unwrap_or(NO_VECTOR)
+        );
+
+        write_cfg!(self.common, queue_enable, 1);
+
+        Ok(VirtQueue {
+            index,
+            size,
+            descriptors: desc_virt as *mut Descriptor,
+            avail,
+            used: used_virt as *mut u16,
+            last_used: 0,
+            notify: notify as *mut u16,
+        })
+    }
+
+    /// tells the device that the driver is done setting it up
+    pub fn finish(&self) {
+        self.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK | STATUS_DRIVER_OK);
+    }
+}
@@ -0,0 +1,581 @@
+//! virtio-9p driver, a 9P2000.L client for the directory the host shares through qemu's virtfs (see
+//! `vfs::hostfs` which mounts it as host:)
+//! requests are sent one at a time, the queue is polled for their responses, files are refered to
+//! by fids the client picks, a fid is walked to a path then opened and is free again once clunked
+use alloc::{string::String, vec, vec::Vec};
+use spin::{Mutex, MutexGuard, Once};
+
+use crate::{
+    drivers::{
+        pci,
+        vfs::{FSError, FSResult},
+    },
+    error, info,
+    threading::expose::thread_yeild,
+};
+
+use super::{VirtQueue, VirtioDevice, VirtioError, VIRTIO_DEVICE_ID_BASE, VIRTIO_VENDOR_ID};
+
+const VIRTIO_9P_DEVICE_ID: u16 = 9;
+/// qemu's virtio-9p-pci is a transitional device, it has the modern interface under the legacy id
+const VIRTIO_9P_TRANSITIONAL_ID: u16 = 0x1009;
+/// the device configuration has the mount tag
+const FEATURE_MOUNT_TAG: u32 = 1 << 0;
+const REQUEST_QUEUE: u16 = 0;
+
+const VERSION: &str = "9P2000.L";
+/// the biggest message sent or received, the device may pick a smaller one
+const MAX_MSIZE: usize = 32 * 1024;
+/// size[4] type[1] tag[2]
+const HEADER_SIZE: usize = 7;
+/// the header of a Rread or a Rreaddir followed by the count[4]
+const READ_OVERHEAD: usize = HEADER_SIZE + 4;
+/// the header of a Twrite followed by the fid[4] offset[8] count[4]
+const WRITE_OVERHEAD: usize = HEADER_SIZE + 16;
+/// the most names a single walk can have
+const MAX_WALK: usize = 16;
+
+/// the fid of the shared directory
+const ROOT_FID: u32 = 0;
+const NO_FID: u32 = !0;
+const NO_TAG: u16 = !0;
+/// every request is sent alone so they can all have the same tag
+const TAG: u16 = 1;
+
+// the types of the requests, the type of their response is one more
+const RLERROR: u8 = 7;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+const QID_DIR: u8 = 0x80;
+const GETATTR_SIZE: u64 = 0x200;
+const SETATTR_SIZE: u32 = 0x8;
+const AT_REMOVEDIR: u32 = 0x200;
+
+pub const O_RDONLY: u32 = 0;
+pub const O_RDWR: u32 = 2;
+pub const O_CREAT: u32 = 0o100;
+pub const O_EXCL: u32 = 0o200;
+
+static NINEP: Once<Virtio9p> = Once::new();
+
+/// turns the linux errno of a Rlerror into an FSError
+fn errno(code: u32) -> FSError {
+    match code {
+        1 | 13 | 30 => FSError::MissingPermissions,
+        2 => FSError::NoSuchAFileOrDirectory,
+        12 | 28 => FSError::OutOfMemory,
+        17 => FSError::AlreadyExists,
+        20 => FSError::NotADirectory,
+        21 => FSError::NotAFile,
+        22 => FSError::InvaildArgument,
+        36 => FSError::NameTooLong,
+        39 => FSError::DirectoryNotEmpty,
+        _ => FSError::OperationNotSupported,
+    }
+}
+
+/// identifies a file on the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Qid {
+    pub kind: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+impl Qid {
+    pub fn is_dir(&self) -> bool {
+        self.kind & QID_DIR != 0
+    }
+}
+
+/// an entry of a Rreaddir
+#[derive(Debug, Clone)]
+pub struct Dirent {
+    /// where the next Treaddir continues after this entry
+    pub offset: u64,
+    pub name: String,
+}
+
+/// a request being written, the size is filled in by `Message::finish`
+pub struct Message(Vec<u8>);
+
+impl Message {
+    pub fn new(kind: u8, tag: u16) -> Self {
+        let mut bytes = vec![0; 4];
+        bytes.push(kind);
+        bytes.extend(tag.to_le_bytes());
+        Self(bytes)
+    }
+
+    pub fn u16(mut self, value: u16) -> Self {
+        self.0.extend(value.to_le_bytes());
+        self
+    }
+
+    pub fn u32(mut self, value: u32) -> Self {
+        self.0.extend(value.to_le_bytes());
+        self
+    }
+
+    pub fn u64(mut self, value: u64) -> Self {
+        self.0.extend(value.to_le_bytes());
+        self
+    }
+
+    /// a string is it's u16 length followed by it's bytes
+    pub fn str(mut self, value: &str) -> Self {
+        self = self.u16(value.len() as u16);
+        self.0.extend(value.as_bytes());
+        self
+    }
+
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.0.extend(value);
+        self
+    }
+
+    fn kind(&self) -> u8 {
+        self.0[4]
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let size = self.0.len() as u32;
+        self.0[..4].copy_from_slice(&size.to_le_bytes());
+        self.0
+    }
+}
+
+/// reads the fields of a response, a response that ends early is `FSError::Corrupted`
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> FSResult<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(FSError::Corrupted);
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> FSResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> FSResult<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> FSResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> FSResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// names the host has that aren't UTF-8 are replaced lossily
+    pub fn str(&mut self) -> FSResult<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    pub fn qid(&mut self) -> FSResult<Qid> {
+        Ok(Qid {
+            kind: self.u8()?,
+            version: self.u32()?,
+            path: self.u64()?,
+        })
+    }
+
+    /// the data of a Rread or a Rreaddir
+    pub fn data(&mut self) -> FSResult<&'a [u8]> {
+        let count = self.u32()? as usize;
+        self.take(count)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// checks that `response` is the response to a request of type `kind`, returns a reader of the
+/// fields after it's header
+/// a Rlerror is turned into the FSError of it's errno
+pub fn check_response(response: &[u8], kind: u8) -> FSResult<Reader<'_>> {
+    let mut reader = Reader::new(response);
+    let size = reader.u32()? as usize;
+    let response_kind = reader.u8()?;
+    reader.u16()?;
+
+    if size < HEADER_SIZE || size > response.len() {
+        return Err(FSError::Corrupted);
+    }
+    reader.data = &response[HEADER_SIZE..size];
+
+    match response_kind {
+        RLERROR => Err(errno(reader.u32()?)),
+        _ if response_kind == kind + 1 => Ok(reader),
+        _ => Err(FSError::Corrupted),
+    }
+}
+
+/// the entries in the data of a Rreaddir
+pub fn parse_dirents(data: &[u8]) -> FSResult<Vec<Dirent>> {
+    let mut reader = Reader::new(data);
+    let mut dirents = Vec::new();
+
+    while !reader.is_empty() {
+        // qid[13], the child is walked to when it's needed
+        reader.qid()?;
+        let offset = reader.u64()?;
+        // the type is the same as the qid's
+        reader.u8()?;
+        let name = reader.str()?;
+        dirents.push(Dirent { offset, name });
+    }
+
+    Ok(dirents)
+}
+
+struct Inner {
+    queue: VirtQueue,
+    msize: usize,
+    /// fids that were clunked and can be used again
+    free_fids: Vec<u32>,
+    next_fid: u32,
+}
+
+impl Inner {
+    /// sends `request` and waits for it's response, returns the response as is
+    fn send(&mut self, request: &[u8]) -> FSResult<Vec<u8>> {
+        let mut response = vec![0u8; self.msize];
+        self.queue
+            .push(&[request], &mut [&mut response])
+            .map_err(|_| FSError::OperationNotSupported)?;
+
+        loop {
+            if let Some(len) = self.queue.pop_used() {
+                response.truncate(len as usize);
+                return Ok(response);
+            }
+            thread_yeild();
+        }
+    }
+
+    /// sends `request` and returns the fields of it's response, see `check_response`
+    fn call(&mut self, request: Message) -> FSResult<Vec<u8>> {
+        let kind = request.kind();
+        let response = self.send(&request.finish())?;
+        check_response(&response, kind)?;
+        Ok(response)
+    }
+
+    fn allocate_fid(&mut self) -> u32 {
+        self.free_fids.pop().unwrap_or_else(|| {
+            self.next_fid += 1;
+            self.next_fid
+        })
+    }
+
+    fn clunk(&mut self, fid: u32) -> FSResult<()> {
+        // the fid is free even if the clunk failed
+        self.free_fids.push(fid);
+        self.call(Message::new(TCLUNK, TAG).u32(fid)).map(|_| ())
+    }
+}
+
+pub struct Virtio9p {
+    inner: Mutex<Inner>,
+    tag: String,
+    root: Qid,
+    _device: VirtioDevice,
+}
+
+impl Virtio9p {
+    fn new(pci: &pci::PciDevice) -> Result<Self, VirtioError> {
+        let device = VirtioDevice::new(pci, FEATURE_MOUNT_TAG)?;
+        let queue = device.queue(REQUEST_QUEUE, None)?;
+        device.finish();
+
+        // tag_len[2] tag[tag_len]
+        let tag = device.read_device_cfg(0).map_or(String::new(), |first| {
+            let len = first as u16 as usize;
+            let bytes: Vec<u8> = (0..(len + 2).div_ceil(4))
+                .flat_map(|word| device.read_device_cfg(word * 4).unwrap().to_le_bytes())
+                .skip(2)
+                .take(len)
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        });
+
+        let mut inner = Inner {
+            queue,
+            msize: MAX_MSIZE,
+            free_fids: Vec::new(),
+            next_fid: ROOT_FID,
+        };
+
+        let version = Message::new(TVERSION, NO_TAG)
+            .u32(MAX_MSIZE as u32)
+            .str(VERSION);
+        let response = inner
+            .call(version)
+            .map_err(|_| VirtioError::InvaildDevice)?;
+        let mut reader = check_response(&response, TVERSION).unwrap();
+        let msize = reader.u32().map_err(|_| VirtioError::InvaildDevice)?;
+        if reader.str().ok().as_deref() != Some(VERSION) {
+            return Err(VirtioError::FeaturesRejected);
+        }
+        inner.msize = (msize as usize).min(MAX_MSIZE);
+
+        let attach = Message::new(TATTACH, TAG)
+            .u32(ROOT_FID)
+            .u32(NO_FID)
+            .str("root")
+            .str("")
+            .u32(0);
+        let response = inner.call(attach).map_err(|_| VirtioError::InvaildDevice)?;
+        let root = check_response(&response, TATTACH)
+            .and_then(|mut reader| reader.qid())
+            .map_err(|_| VirtioError::InvaildDevice)?;
+
+        Ok(Self {
+            inner: Mutex::new(inner),
+            tag,
+            root,
+            _device: device,
+        })
+    }
+
+    /// locks the driver, yielding while another thread is waiting for a response
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        loop {
+            if let Some(inner) = self.inner.try_lock() {
+                return inner;
+            }
+            thread_yeild();
+        }
+    }
+
+    /// the name the host gave the shared directory
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// returns a new fid walked from the shared directory through `names` and the qid of the file
+    /// it ends at
+    pub fn walk(&self, names: &[&str]) -> FSResult<(u32, Qid)> {
+        let mut inner = self.lock();
+        let fid = inner.allocate_fid();
+        let mut qid = self.root;
+        let mut from = ROOT_FID;
+
+        let mut chunks = names.chunks(MAX_WALK);
+        // an empty walk clones the shared directory
+        let first = chunks.next().unwrap_or(&[]);
+        for chunk in core::iter::once(first).chain(chunks) {
+            let mut request = Message::new(TWALK, TAG)
+                .u32(from)
+                .u32(fid)
+                .u16(chunk.len() as u16);
+            for name in chunk {
+                request = request.str(name);
+            }
+
+            let results = inner.call(request).and_then(|response| {
+                let mut reader = check_response(&response, TWALK)?;
+                let count = reader.u16()? as usize;
+                // the walk stops at the first name that doesn't exist
+                if count < chunk.len() {
+                    return Err(FSError::NoSuchAFileOrDirectory);
+                }
+                for _ in 0..count {
+                    qid = reader.qid()?;
+                }
+                Ok(())
+            });
+
+            if let Err(err) = results {
+                if from == fid {
+                    _ = inner.clunk(fid);
+                } else {
+                    inner.free_fids.push(fid);
+                }
+                return Err(err);
+            }
+            from = fid;
+        }
+
+        Ok((fid, qid))
+    }
+
+    pub fn clunk(&self, fid: u32) -> FSResult<()> {
+        self.lock().clunk(fid)
+    }
+
+    /// opens the walked `fid` with the linux open `flags`
+    pub fn lopen(&self, fid: u32, flags: u32) -> FSResult<()> {
+        let request = Message::new(TLOPEN, TAG).u32(fid).u32(flags);
+        self.lock().call(request).map(|_| ())
+    }
+
+    /// creates the file `name` in the directory `dir` which becomes the fid of the file opened with
+    /// `flags`
+    pub fn lcreate(&self, dir: u32, name: &str, flags: u32, mode: u32) -> FSResult<()> {
+        let request = Message::new(TLCREATE, TAG)
+            .u32(dir)
+            .str(name)
+            .u32(flags)
+            .u32(mode)
+            .u32(0);
+        self.lock().call(request).map(|_| ())
+    }
+
+    pub fn mkdir(&self, dir: u32, name: &str, mode: u32) -> FSResult<()> {
+        let request = Message::new(TMKDIR, TAG)
+            .u32(dir)
+            .str(name)
+            .u32(mode)
+            .u32(0);
+        self.lock().call(request).map(|_| ())
+    }
+
+    /// removes `name` from the directory `dir`, `is_dir` has to be set if `name` is a directory
+    pub fn unlinkat(&self, dir: u32, name: &str, is_dir: bool) -> FSResult<()> {
+        let flags = if is_dir { AT_REMOVEDIR } else { 0 };
+        let request = Message::new(TUNLINKAT, TAG).u32(dir).str(name).u32(flags);
+        self.lock().call(request).map(|_| ())
+    }
+
+    pub fn renameat(&self, old_dir: u32, old: &str, new_dir: u32, new: &str) -> FSResult<()> {
+        let request = Message::new(TRENAMEAT, TAG)
+            .u32(old_dir)
+            .str(old)
+            .u32(new_dir)
+            .str(new);
+        self.lock().call(request).map(|_| ())
+    }
+
+    /// the size of the file `fid` in bytes
+    pub fn size(&self, fid: u32) -> FSResult<u64> {
+        let request = Message::new(TGETATTR, TAG).u32(fid).u64(GETATTR_SIZE);
+        let response = self.lock().call(request)?;
+
+        let mut reader = check_response(&response, TGETATTR)?;
+        // valid[8] qid[13] mode[4] uid[4] gid[4] nlink[8] rdev[8]
+        reader.take(49)?;
+        reader.u64()
+    }
+
+    pub fn truncate(&self, fid: u32, size: u64) -> FSResult<()> {
+        // valid mode uid gid size atime_sec atime_nsec mtime_sec mtime_nsec
+        let request = Message::new(TSETATTR, TAG)
+            .u32(fid)
+            .u32(SETATTR_SIZE)
+            .u32(0)
+            .u32(0)
+            .u32(0)
+            .u64(size)
+            .bytes(&[0; 32]);
+        self.lock().call(request).map(|_| ())
+    }
+
+    /// reads from the opened `fid` at `offset` into `buffer`, returns the amount read which is less
+    /// than asked for if the message size is smaller than `buffer` and 0 at the end of the file
+    pub fn read(&self, fid: u32, offset: u64, buffer: &mut [u8]) -> FSResult<usize> {
+        let mut inner = self.lock();
+        let count = buffer.len().min(inner.msize - READ_OVERHEAD);
+        let request = Message::new(TREAD, TAG)
+            .u32(fid)
+            .u64(offset)
+            .u32(count as u32);
+        let response = inner.call(request)?;
+
+        let data = check_response(&response, TREAD)?.data()?;
+        let read = data.len().min(buffer.len());
+        buffer[..read].copy_from_slice(&data[..read]);
+        Ok(read)
+    }
+
+    /// writes `data` to the opened `fid` at `offset`, returns the amount written which is less than
+    /// `data` if the message size is smaller than it
+    pub fn write(&self, fid: u32, offset: u64, data: &[u8]) -> FSResult<usize> {
+        let mut inner = self.lock();
+        let count = data.len().min(inner.msize - WRITE_OVERHEAD);
+        let request = Message::new(TWRITE, TAG)
+            .u32(fid)
+            .u64(offset)
+            .u32(count as u32)
+            .bytes(&data[..count]);
+        let response = inner.call(request)?;
+
+        let written = check_response(&response, TWRITE)?.u32()? as usize;
+        Ok(written.min(count))
+    }
+
+    /// the entries of the opened directory `fid` after `offset`, returns an empty Vec at the end of
+    /// it
+    pub fn readdir(&self, fid: u32, offset: u64) -> FSResult<Vec<Dirent>> {
+        let mut inner = self.lock();
+        let count = inner.msize - READ_OVERHEAD;
+        let request = Message::new(TREADDIR, TAG)
+            .u32(fid)
+            .u64(offset)
+            .u32(count as u32);
+        let response = inner.call(request)?;
+
+        parse_dirents(check_response(&response, TREADDIR)?.data()?)
+    }
+}
+
+/// returns the shared directory if one was found
+pub fn get() -> Option<&'static Virtio9p> {
+    NINEP.get()
+}
+
+/// looks for a virtio-9p device and attaches to the directory it shares
+pub fn init() {
+    let Some(pci) = pci::devices().into_iter().find(|device| {
+        device.vendor_id == VIRTIO_VENDOR_ID
+            && (device.device_id == VIRTIO_DEVICE_ID_BASE + VIRTIO_9P_DEVICE_ID
+                || device.device_id == VIRTIO_9P_TRANSITIONAL_ID)
+    }) else {
+        return;
+    };
+
+    info!(
+        Virtio9p,
+        "found at {:02x}:{:02x}.{}", pci.bus, pci.device, pci.function
+    );
+
+    match Virtio9p::new(&pci) {
+        Ok(ninep) => {
+            let ninep = NINEP.call_once(|| ninep);
+            info!(Virtio9p, "attached to {}", ninep.tag());
+        }
+        Err(err) => {
+            error!(Virtio9p, "failed to initialize: {:?}", err);
+        }
+    }
+}
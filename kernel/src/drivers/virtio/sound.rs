@@ -0,0 +1,463 @@
+//! virtio-snd driver, plays PCM frames on the first output stream of the device
+//! writes are split into periods and block until the device is done playing each of them, the
+//! device signals that through MSI-X when supported otherwise the tx queue is polled
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{vec, vec::Vec};
+use spin::{Mutex, MutexGuard, Once};
+
+use crate::{
+    debug,
+    drivers::{
+        pci,
+        vfs::{FSError, FSResult},
+    },
+    error, info,
+    threading::expose::thread_yeild,
+};
+
+use super::{
+    as_bytes, as_bytes_mut, VirtQueue, VirtioDevice, VirtioError, VIRTIO_DEVICE_ID_BASE,
+    VIRTIO_VENDOR_ID,
+};
+
+const VIRTIO_SND_DEVICE_ID: u16 = 25;
+const CONTROL_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 2;
+
+const CMD_PCM_INFO: u32 = 0x0100;
+const CMD_PCM_SET_PARAMS: u32 = 0x0101;
+const CMD_PCM_PREPARE: u32 = 0x0102;
+const CMD_PCM_RELEASE: u32 = 0x0103;
+const CMD_PCM_START: u32 = 0x0104;
+const CMD_PCM_STOP: u32 = 0x0105;
+
+const STATUS_OK: u32 = 0x8000;
+const DIRECTION_OUTPUT: u8 = 0;
+
+/// the offset of the amount of PCM streams in the device configuration
+const CFG_STREAMS: usize = 4;
+/// the maximum amount of streams queried
+const MAX_STREAMS: u32 = 16;
+
+/// the amount of bytes sent to the device at once
+const PERIOD_BYTES: usize = 4096;
+const PERIODS: usize = 4;
+
+/// (rate in Hz, virtio rate code)
+const RATES: [(u32, u8); 14] = [
+    (5512, 0),
+    (8000, 1),
+    (11025, 2),
+    (16000, 3),
+    (22050, 4),
+    (32000, 5),
+    (44100, 6),
+    (48000, 7),
+    (64000, 8),
+    (88200, 9),
+    (96000, 10),
+    (176400, 11),
+    (192000, 12),
+    (384000, 13),
+];
+
+/// set by the interrupt handler when the device used a tx buffer
+static TX_COMPLETED: AtomicBool = AtomicBool::new(false);
+static SOUND: Once<VirtioSound> = Once::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AudioFormat {
+    U8,
+    S16,
+    S32,
+}
+
+impl AudioFormat {
+    const fn code(self) -> u8 {
+        match self {
+            Self::U8 => 4,
+            Self::S16 => 5,
+            Self::S32 => 17,
+        }
+    }
+
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::S16 => 2,
+            Self::S32 => 4,
+        }
+    }
+}
+
+impl TryFrom<u8> for AudioFormat {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::U8),
+            1 => Ok(Self::S16),
+            2 => Ok(Self::S32),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    /// in Hz
+    pub rate: u32,
+    pub channels: u8,
+    pub format: AudioFormat,
+}
+
+impl PcmFormat {
+    /// the format used until userspace sets one
+    const DEFAULT: Self = Self {
+        rate: 48000,
+        channels: 2,
+        format: AudioFormat::S16,
+    };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Header {
+    code: u32,
+}
+
+#[repr(C)]
+struct QueryInfo {
+    header: Header,
+    start_id: u32,
+    count: u32,
+    size: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct PcmInfo {
+    hda_fn_nid: u32,
+    features: u32,
+    /// bit n is set if the format with the code n is supported
+    formats: u64,
+    /// bit n is set if the rate with the code n is supported
+    rates: u64,
+    direction: u8,
+    channels_min: u8,
+    channels_max: u8,
+    _padding: [u8; 5],
+}
+
+#[repr(C)]
+struct SetParams {
+    header: Header,
+    stream_id: u32,
+    buffer_bytes: u32,
+    period_bytes: u32,
+    features: u32,
+    channels: u8,
+    format: u8,
+    rate: u8,
+    _padding: u8,
+}
+
+#[repr(C)]
+struct PcmHeader {
+    header: Header,
+    stream_id: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct PcmStatus {
+    status: u32,
+    latency_bytes: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    /// no parameters are set
+    Released,
+    Prepared,
+    Running,
+}
+
+struct Inner {
+    control: VirtQueue,
+    tx: VirtQueue,
+    /// wether the device interrupts when it uses a tx buffer
+    interrupts: bool,
+    stream_id: u32,
+    info: PcmInfo,
+    format: PcmFormat,
+    state: StreamState,
+}
+
+impl Inner {
+    /// sends `request` on the control queue and waits for the response, fails if the response's
+    /// status isn't `STATUS_OK`
+    /// the requests and responses live on the heap because the device needs their physical addresses
+    fn command(&mut self, request: &[u8], response: &mut [u8]) -> FSResult<()> {
+        let request = Vec::from(request);
+        let mut response_buffer = Vec::from(&*response);
+
+        self.control
+            .submit(&[&request], &mut [&mut response_buffer])
+            .map_err(|_| FSError::OperationNotSupported)?;
+        response.copy_from_slice(&response_buffer);
+
+        let status = u32::from_ne_bytes([response[0], response[1], response[2], response[3]]);
+        if status != STATUS_OK {
+            error!(VirtioSound, "command failed with {:#x}", status);
+            return Err(FSError::OperationNotSupported);
+        }
+        Ok(())
+    }
+
+    fn stream_command(&mut self, code: u32) -> FSResult<()> {
+        let request = PcmHeader {
+            header: Header { code },
+            stream_id: self.stream_id,
+        };
+        let mut response = Header::default();
+        self.command(as_bytes(&request), as_bytes_mut(&mut response))
+    }
+
+    /// returns the info of every stream, the device has `count` streams
+    fn pcm_info(&mut self, count: u32) -> FSResult<Vec<PcmInfo>> {
+        let request = QueryInfo {
+            header: Header { code: CMD_PCM_INFO },
+            start_id: 0,
+            count,
+            size: size_of::<PcmInfo>() as u32,
+        };
+
+        let mut response = vec![0; size_of::<Header>() + count as usize * size_of::<PcmInfo>()];
+        self.command(as_bytes(&request), &mut response)?;
+
+        let infos = response[size_of::<Header>()..]
+            .chunks_exact(size_of::<PcmInfo>())
+            .map(|info| unsafe { (info.as_ptr() as *const PcmInfo).read_unaligned() })
+            .collect();
+        Ok(infos)
+    }
+
+    /// stops and releases the stream if it was running or prepared
+    fn release(&mut self) {
+        if self.state == StreamState::Running {
+            _ = self.stream_command(CMD_PCM_STOP);
+        }
+        if self.state != StreamState::Released {
+            _ = self.stream_command(CMD_PCM_RELEASE);
+        }
+        self.state = StreamState::Released;
+    }
+
+    /// switches the stream to `format`, the stream is stopped until the next write
+    fn set_format(&mut self, format: PcmFormat) -> FSResult<()> {
+        let rate = RATES
+            .iter()
+            .find(|(rate, _)| *rate == format.rate)
+            .map(|(_, code)| *code)
+            .ok_or(FSError::OperationNotSupported)?;
+
+        let supported = self.info.formats & (1 << format.format.code()) != 0
+            && self.info.rates & (1 << rate) != 0
+            && (self.info.channels_min..=self.info.channels_max).contains(&format.channels);
+        if !supported {
+            return Err(FSError::OperationNotSupported);
+        }
+
+        self.release();
+
+        let request = SetParams {
+            header: Header {
+                code: CMD_PCM_SET_PARAMS,
+            },
+            stream_id: self.stream_id,
+            buffer_bytes: (PERIOD_BYTES * PERIODS) as u32,
+            period_bytes: PERIOD_BYTES as u32,
+            features: 0,
+            channels: format.channels,
+            format: format.format.code(),
+            rate,
+            _padding: 0,
+        };
+        let mut response = Header::default();
+        self.command(as_bytes(&request), as_bytes_mut(&mut response))?;
+        self.stream_command(CMD_PCM_PREPARE)?;
+
+        self.format = format;
+        self.state = StreamState::Prepared;
+        Ok(())
+    }
+
+    /// waits for the device to use the tx buffer in flight
+    fn wait_tx(&mut self) {
+        loop {
+            if (!self.interrupts || TX_COMPLETED.swap(false, Ordering::AcqRel))
+                && self.tx.pop_used().is_some()
+            {
+                return;
+            }
+            thread_yeild();
+        }
+    }
+
+    /// plays `buffer` blocking until the device is done with it
+    fn write(&mut self, buffer: &[u8]) -> FSResult<usize> {
+        match self.state {
+            StreamState::Released => return Err(FSError::OperationNotSupported),
+            StreamState::Prepared => {
+                self.stream_command(CMD_PCM_START)?;
+                self.state = StreamState::Running;
+            }
+            StreamState::Running => {}
+        }
+
+        // only whole frames are played
+        let frame_size = self.format.channels as usize * self.format.format.bytes_per_sample();
+        let period = PERIOD_BYTES - PERIOD_BYTES % frame_size;
+        let len = buffer.len() - buffer.len() % frame_size;
+
+        for chunk in buffer[..len].chunks(period) {
+            let mut request = Vec::with_capacity(size_of::<u32>() + chunk.len());
+            request.extend_from_slice(&self.stream_id.to_ne_bytes());
+            request.extend_from_slice(chunk);
+            let mut status = Vec::from(as_bytes(&PcmStatus::default()));
+
+            self.tx
+                .push(&[&request], &mut [&mut status])
+                .map_err(|_| FSError::OperationNotSupported)?;
+            self.wait_tx();
+        }
+
+        Ok(len)
+    }
+}
+
+pub struct VirtioSound {
+    inner: Mutex<Inner>,
+    _device: VirtioDevice,
+}
+
+impl VirtioSound {
+    fn new(pci: &pci::PciDevice) -> Result<Self, VirtioError> {
+        let device = VirtioDevice::new(pci, 0)?;
+        let msix_entry = pci.enable_msix("virtio-sound");
+
+        let control = device.queue(CONTROL_QUEUE, None)?;
+        let tx = device.queue(TX_QUEUE, msix_entry)?;
+        device.finish();
+
+        if msix_entry.is_some() {
+            pci::register_interrupt_handler(on_interrupt);
+        }
+
+        let streams = device
+            .read_device_cfg(CFG_STREAMS)
+            .ok_or(VirtioError::InvaildDevice)?
+            .min(MAX_STREAMS);
+
+        let mut inner = Inner {
+            control,
+            tx,
+            interrupts: msix_entry.is_some(),
+            stream_id: 0,
+            info: unsafe { core::mem::zeroed() },
+            format: PcmFormat::DEFAULT,
+            state: StreamState::Released,
+        };
+
+        let infos = inner
+            .pcm_info(streams)
+            .map_err(|_| VirtioError::InvaildDevice)?;
+        let (stream_id, info) = infos
+            .into_iter()
+            .enumerate()
+            .find(|(_, info)| info.direction == DIRECTION_OUTPUT)
+            .ok_or(VirtioError::InvaildDevice)?;
+
+        inner.stream_id = stream_id as u32;
+        inner.info = info;
+        if inner.set_format(PcmFormat::DEFAULT).is_err() {
+            debug!(
+                VirtioSound,
+                "default format not supported, waiting for userspace to set one"
+            );
+        }
+
+        Ok(Self {
+            inner: Mutex::new(inner),
+            _device: device,
+        })
+    }
+
+    /// locks the driver, yielding while another thread is playing
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        loop {
+            if let Some(inner) = self.inner.try_lock() {
+                return inner;
+            }
+            thread_yeild();
+        }
+    }
+
+    pub fn format(&self) -> PcmFormat {
+        self.lock().format
+    }
+
+    /// switches the output to `format`, fails if the device doesn't support it
+    pub fn set_format(&self, format: PcmFormat) -> FSResult<()> {
+        self.lock().set_format(format)
+    }
+
+    /// plays the PCM frames in `buffer` in the current format, blocks until they were played
+    /// returns the amount of bytes played, trailing bytes that don't make up a whole frame are ignored
+    pub fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+        self.lock().write(buffer)
+    }
+
+    /// stops the output
+    pub fn quiesce(&self) {
+        if let Some(mut inner) = self.inner.try_lock() {
+            inner.release();
+        }
+    }
+}
+
+fn on_interrupt() {
+    TX_COMPLETED.store(true, Ordering::Release);
+}
+
+/// returns the sound device if one was found
+pub fn get() -> Option<&'static VirtioSound> {
+    SOUND.get()
+}
+
+/// looks for a virtio-snd device with an output stream
+pub fn init() {
+    let Some(pci) = pci::devices().into_iter().find(|device| {
+        device.vendor_id == VIRTIO_VENDOR_ID
+            && device.device_id == VIRTIO_DEVICE_ID_BASE + VIRTIO_SND_DEVICE_ID
+    }) else {
+        return;
+    };
+
+    info!(
+        VirtioSound,
+        "found at {:02x}:{:02x}.{}", pci.bus, pci.device, pci.function
+    );
+
+    match VirtioSound::new(&pci) {
+        Ok(sound) => {
+            let sound = SOUND.call_once(|| sound);
+            info!(VirtioSound, "initialized, format: {:?}", sound.format());
+        }
+        Err(err) => {
+            error!(VirtioSound, "failed to initialize: {:?}", err);
+        }
+    }
+}
@@ -0,0 +1,656 @@
+//! Eve's service manager, Eve starts the services once their dependencies are up and restarts them
+//! depending on their `Restart` policy, the services are supervised from Eve's idle loop
+//! services are compiled in (see `builtin_services`), read from the `[service.<name>]` sections of
+//! `INIT_CONFIG` and from the files in `INIT_DIR`, a file there is a service named after the file
+//! without any section, later services override earlier ones with the same name
+//! `INIT_CONFIG` can also have an `[env]` section that sets variables in the default environment,
+//! processes don't have their own environment so that is the environment of every service
+//! and a `[tty]` section whose `font` is the path of a PSF2 font the framebuffer TTY switches to
+//! both are parsed with `utils::config`, a service has the keys:
+//! `path` the elf to spawn, a path starting with `$` is the name of a variable in the default environment
+//! `args` the arguments separated by spaces
+//! `stdin`, `stdout` and `stderr` the paths they are opened from, otherwise they are Eve's (dev:/tty)
+//! `depends` the services that have to be running (or have exited successfully) first, separated by spaces
+//! `binds` drives only the service (and what it spawns) sees, `drive=path` separated by spaces, a bind
+//! can hide a drive, see `vfs::namespace`
+//! `restart` one of `never`, `on-crash` and `always`
+//! `capabilities` the capabilities granted to the service on top of the ones every process inherits,
+//! separated by spaces such as `power` or `raw-devices`, what it spawns doesn't get them, see
+//! `Capabilities::INHERITED`
+//! `autostart` `yes` if the service is started on boot, otherwise it only starts once requested with `start`
+//! a crash is exiting with a non zero exit code, a crashed service is restarted after a backoff that
+//! doubles with each crash in a row, Eve gives up on it after `MAX_CRASHES` crashes in a row
+//! what happens to the services is written to proc:/eve-journal and their status to proc:/services
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use spin::{Mutex, Once, RwLock};
+
+use crate::{
+    drivers::{
+        poll::{self, PollId, PolledDriver},
+        vfs::{
+            expose::{bind, close, open, read, readdir, DirEntry},
+            FSResult, InodeType,
+        },
+    },
+    info,
+    terminal::{font::FontError, framebuffer},
+    threading::{
+        self,
+        expose::{namespace, pspawn_with_stdio, set_namespace, try_wait, SpawnFlags},
+        processes::{Capabilities, ContextPriority},
+    },
+    utils::{
+        config::{self, Section},
+        env,
+    },
+    warn,
+};
+
+const INIT_CONFIG: &str = "sys:/etc/init.cfg";
+const INIT_DIR: &str = "sys:/etc/init";
+/// the backoff after the first crash in scheduler ticks
+const BACKOFF_TICKS: u64 = 8;
+/// the backoff stops doubling after this many crashes in a row
+const MAX_BACKOFF_SHIFT: u32 = 6;
+const MAX_CRASHES: u32 = 5;
+/// a service that ran for this many ticks before exiting didn't crash in a row
+const STABLE_TICKS: u64 = 1000;
+/// how often the services are checked in scheduler ticks while any of them needs supervising
+const SUPERVISE_INTERVAL: u64 = 4;
+/// the amount of lines kept in the journal
+const JOURNAL_MAX: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restart {
+    Never,
+    OnCrash,
+    Always,
+}
+
+impl Restart {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::OnCrash => "on-crash",
+            Self::Always => "always",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// never started
+    Stopped,
+    /// waiting for it's dependencies to start
+    Waiting,
+    Running(usize),
+    /// restarted once the scheduler ticks reach the value
+    Backoff(u64),
+    /// exited with the value and won't be restarted
+    Exited(usize),
+    /// crashed too many times in a row or couldn't be spawned
+    Failed,
+}
+
+impl Status {
+    /// wether or not the services that depend on this one can start
+    fn is_up(self) -> bool {
+        matches!(self, Self::Running(_) | Self::Exited(0))
+    }
+
+    /// wether or not Eve has to keep checking on this service
+    fn is_active(self) -> bool {
+        matches!(self, Self::Running(_) | Self::Waiting | Self::Backoff(_))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "stopped"),
+            Self::Waiting => write!(f, "waiting for dependencies"),
+            Self::Running(pid) => write!(f, "running (pid {pid})"),
+            Self::Backoff(until) => write!(f, "restarting at tick {until}"),
+            Self::Exited(code) => write!(f, "exited ({code})"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+pub struct Service {
+    name: String,
+    path: String,
+    args: Vec<String>,
+    depends: Vec<String>,
+    /// the paths stdin, stdout and stderr are opened from, None keeps Eve's
+    stdio: [Option<String>; 3],
+    /// the drives bound in the service's mount namespace and the directories they are bound to
+    binds: Vec<(String, String)>,
+    /// the capabilities granted to the service, see `expose::spawn_with_stdio`
+    capabilities: Capabilities,
+    restart: Restart,
+    autostart: bool,
+    /// set by `start`, handled by the next `supervise`
+    requested: AtomicBool,
+    status: Status,
+    /// crashes in a row
+    crashes: u32,
+    /// the tick the service was last started at
+    started_at: u64,
+    restarts: usize,
+}
+
+impl Service {
+    fn new(name: &str, path: &str, restart: Restart, autostart: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            args: Vec::new(),
+            depends: Vec::new(),
+            stdio: [None, None, None],
+            binds: Vec::new(),
+            capabilities: Capabilities::empty(),
+            restart,
+            autostart,
+            requested: AtomicBool::new(false),
+            status: Status::Stopped,
+            crashes: 0,
+            started_at: 0,
+            restarts: 0,
+        }
+    }
+
+    /// reads a service from the entries of a config section, see the module docs for the keys
+    fn from_section(name: &str, section: &Section) -> Result<Self, &'static str> {
+        let mut service = Self::new(name, "", Restart::Never, false);
+
+        for &(key, value) in &section.entries {
+            let words = || value.split_whitespace().map(str::to_string).collect();
+
+            match key {
+                "path" => service.path = value.to_string(),
+                "args" => service.args = words(),
+                "depends" => service.depends = words(),
+                "stdin" => service.stdio[0] = Some(value.to_string()),
+                "stdout" => service.stdio[1] = Some(value.to_string()),
+                "stderr" => service.stdio[2] = Some(value.to_string()),
+                "binds" => {
+                    for bind in value.split_whitespace() {
+                        let (drive, path) = bind
+                            .split_once('=')
+                            .ok_or("a bind has to be `drive=path`")?;
+                        service.binds.push((drive.to_string(), path.to_string()));
+                    }
+                }
+                "capabilities" => {
+                    for name in value.split_whitespace() {
+                        service.capabilities |=
+                            Capabilities::from_name(&name.to_uppercase().replace('-', "_"))
+                                .ok_or("unknown capability")?;
+                    }
+                }
+                "restart" => {
+                    service.restart = match value {
+                        "never" => Restart::Never,
+                        "on-crash" => Restart::OnCrash,
+                        "always" => Restart::Always,
+                        _ => return Err("invaild restart policy"),
+                    }
+                }
+                "autostart" => {
+                    service.autostart = match value {
+                        "yes" => true,
+                        "no" => false,
+                        _ => return Err("autostart has to be `yes` or `no`"),
+                    }
+                }
+                _ => return Err("unknown key"),
+            }
+        }
+
+        if service.path.is_empty() {
+            return Err("no path");
+        }
+        Ok(service)
+    }
+
+    /// parses a service file in `INIT_DIR`, it has the keys of a service without any section
+    pub fn parse(name: &str, data: &str) -> Result<Self, String> {
+        let sections = config::parse(data).map_err(|err| err.to_string())?;
+
+        match sections.as_slice() {
+            [] => Err("no path".to_string()),
+            [section] if section.name.is_empty() => {
+                Self::from_section(name, section).map_err(str::to_string)
+            }
+            _ => Err("sections aren't allowed in a service file".to_string()),
+        }
+    }
+
+    #[cfg(feature = "test")]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(feature = "test")]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    #[cfg(feature = "test")]
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// asks Eve to start the service on the next `supervise`
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    fn spawn(&mut self, now: u64) {
+        let path = match self.path.strip_prefix('$') {
+            Some(var) => env::get(var),
+            None => Some(self.path.clone()),
+        };
+        let Some(path) = path else {
+            journal(format_args!("{}: {} isn't set", self.name, self.path));
+            self.status = Status::Failed;
+            return;
+        };
+
+        // the service is spawned with Eve's namespace and the binds, Eve gets it's own back after
+        // so the binds are only seen by the service
+        let namespace = namespace();
+        for (drive, target) in &self.binds {
+            if let Err(err) = bind(drive, target) {
+                set_namespace(namespace);
+                journal(format_args!(
+                    "{}: failed to bind {drive}: to {target}: {err:?}",
+                    self.name
+                ));
+                self.status = Status::Failed;
+                return;
+            }
+        }
+
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        let stdio = self.stdio.each_ref().map(|path| path.as_deref());
+        let results = pspawn_with_stdio(
+            &self.name,
+            &path,
+            &args,
+            SpawnFlags::CLONE_RESOURCES,
+            ContextPriority::default(),
+            &stdio,
+            self.capabilities,
+        );
+        set_namespace(namespace);
+
+        match results {
+            Ok(pid) => {
+                journal(format_args!("{}: started (pid {pid})", self.name));
+                self.status = Status::Running(pid);
+                self.started_at = now;
+            }
+            Err(err) => {
+                journal(format_args!("{}: failed to spawn {path}: {err}", self.name));
+                self.status = Status::Failed;
+            }
+        }
+    }
+
+    fn exited(&mut self, pid: usize, exit_code: usize, now: u64) {
+        if now - self.started_at >= STABLE_TICKS {
+            self.crashes = 0;
+        }
+
+        let crashed = exit_code != 0;
+        if crashed {
+            self.crashes += 1;
+        }
+        journal(format_args!(
+            "{}: pid {pid} exited with {exit_code}",
+            self.name
+        ));
+
+        let restart = match self.restart {
+            Restart::Never => false,
+            Restart::OnCrash => crashed,
+            Restart::Always => true,
+        };
+
+        self.status = if !restart {
+            Status::Exited(exit_code)
+        } else if self.crashes >= MAX_CRASHES {
+            journal(format_args!(
+                "{}: crashed {} times in a row, giving up",
+                self.name, self.crashes
+            ));
+            Status::Failed
+        } else {
+            let backoff = BACKOFF_TICKS << self.crashes.min(MAX_BACKOFF_SHIFT);
+            journal(format_args!("{}: restarting in {backoff} ticks", self.name));
+            self.restarts += 1;
+            Status::Backoff(now + backoff)
+        };
+    }
+}
+
+static SERVICES: RwLock<Vec<Service>> = RwLock::new(Vec::new());
+static JOURNAL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+/// set while any service needs supervising, the scheduler tick is only needed while it is set
+static SUPERVISING: AtomicBool = AtomicBool::new(false);
+static EVE_POLL_ID: Once<PollId> = Once::new();
+
+/// adds a line to proc:/eve-journal
+fn journal(args: fmt::Arguments) {
+    info!(Eve, "{}", args);
+
+    let mut journal = JOURNAL.lock();
+    if journal.len() == JOURNAL_MAX {
+        journal.pop_front();
+    }
+    journal.push_back(format!("[{}] {}", threading::ticks(), args));
+}
+
+/// the services Eve knows about without reading `INIT_DIR`
+fn builtin_services() -> Vec<Service> {
+    // started by the TTY (ctrl + shift + C), the shell reads the rest of the default environment
+    // from proc:/environ
+    // the shell can shutdown and reboot, the processes it spawns can't
+    let mut shell = Service::new("Shell", "$SHELL", Restart::OnCrash, false);
+    shell.capabilities = Capabilities::POWER;
+    Vec::from([shell])
+}
+
+fn read_file(path: &str) -> FSResult<String> {
+    read_bytes(path).map(|data| String::from_utf8_lossy(&data).into_owned())
+}
+
+fn read_bytes(path: &str) -> FSResult<Vec<u8>> {
+    let ri = open(path)?;
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 512];
+
+    let result = loop {
+        match read(ri, &mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(count) => data.extend_from_slice(&buffer[..count]),
+            Err(err) => break Err(err),
+        }
+    };
+
+    close(ri)?;
+    result.map(|()| data)
+}
+
+/// reads the services and the default environment from the config file `path`, a `[service.<name>]`
+/// section is a service and the `[env]` section sets variables in the default environment
+/// bad services are written to the journal and skipped
+fn load_config(path: &str) -> Vec<Service> {
+    let mut services = Vec::new();
+    // there doesn't have to be one
+    let Ok(data) = read_file(path) else {
+        return services;
+    };
+
+    let sections = match config::parse(&data) {
+        Ok(sections) => sections,
+        Err(err) => {
+            journal(format_args!("{path}: {err}, ignored"));
+            return services;
+        }
+    };
+
+    for section in &sections {
+        if section.name == "env" {
+            for &(name, value) in &section.entries {
+                if env::set(name, value).is_err() {
+                    journal(format_args!("{path}: invaild variable {name}, skipped"));
+                }
+            }
+            continue;
+        }
+
+        if section.name == "tty" {
+            for &(key, value) in &section.entries {
+                match key {
+                    "font" => load_font(value),
+                    _ => journal(format_args!("{path}: unknown tty key {key}, skipped")),
+                }
+            }
+            continue;
+        }
+
+        let Some(name) = section.name.strip_prefix("service.") else {
+            journal(format_args!(
+                "{path}: unknown section [{}], skipped",
+                section.name
+            ));
+            continue;
+        };
+
+        match Service::from_section(name, section) {
+            Ok(service) => services.push(service),
+            Err(err) => journal(format_args!("{name}: {err}, skipped")),
+        }
+    }
+
+    services
+}
+
+/// switches the framebuffer TTY to the font at `path`, failures are written to the journal
+fn load_font(path: &str) {
+    let results = read_bytes(path)
+        .map_err(|_| "failed to read")
+        .and_then(|data| framebuffer::load_font(&data).map_err(FontError::as_str));
+
+    match results {
+        Ok(()) => journal(format_args!("tty: switched to the font {path}")),
+        Err(err) => journal(format_args!("tty: {path}: {err}, keeping the current font")),
+    }
+}
+
+/// reads the services in `dir`, bad services are written to the journal and skipped
+fn load_dir(dir: &str) -> Vec<Service> {
+    let mut services = Vec::new();
+    // there doesn't have to be any
+    let Ok(ri) = open(dir) else {
+        return services;
+    };
+
+    let mut entries: [DirEntry; 8] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+    while let Ok(count @ 1..) = readdir(ri, &mut entries) {
+        for entry in &entries[..count] {
+            if entry.kind != InodeType::File {
+                continue;
+            }
+
+            let name = String::from_utf8_lossy(&entry.name[..entry.name_length]);
+            let service = read_file(&format!("{dir}/{name}"))
+                .map_err(|_| "failed to read".to_string())
+                .and_then(|data| Service::parse(&name, &data));
+
+            match service {
+                Ok(service) => services.push(service),
+                Err(err) => journal(format_args!("{name}: {err}, skipped")),
+            }
+        }
+    }
+
+    _ = close(ri);
+    services
+}
+
+/// orders `services` so every service comes after it's dependencies, services with unknown
+/// dependencies or in a dependency cycle are failed
+pub fn order(mut services: Vec<Service>) -> Vec<Service> {
+    let mut ordered: Vec<Service> = Vec::with_capacity(services.len());
+
+    loop {
+        let ready = services.iter().position(|service| {
+            service
+                .depends
+                .iter()
+                .all(|dep| ordered.iter().any(|s| s.name == *dep))
+        });
+
+        match ready {
+            Some(index) => ordered.push(services.remove(index)),
+            None => break,
+        }
+    }
+
+    for mut service in services {
+        journal(format_args!(
+            "{}: unknown dependency or dependency cycle",
+            service.name
+        ));
+        service.status = Status::Failed;
+        ordered.push(service);
+    }
+    ordered
+}
+
+/// starts the requested services whose dependencies are up and handles the exited ones, `services`
+/// has to be ordered with `order`, returns wether or not any of them still needs supervising
+pub fn supervise(services: &mut [Service], now: u64) -> bool {
+    for index in 0..services.len() {
+        let (dependencies, rest) = services.split_at_mut(index);
+        let service = &mut rest[0];
+
+        if let Status::Running(pid) = service.status {
+            if let Some(exit_code) = try_wait(pid) {
+                service.exited(pid, exit_code, now);
+            }
+        }
+
+        if service.requested.swap(false, Ordering::Relaxed) && !service.status.is_active() {
+            service.crashes = 0;
+            service.status = Status::Waiting;
+        }
+
+        if matches!(service.status, Status::Backoff(until) if now >= until) {
+            service.status = Status::Waiting;
+        }
+
+        if service.status == Status::Waiting {
+            let mut up = true;
+            for dependency in dependencies
+                .iter_mut()
+                .filter(|s| service.depends.contains(&s.name))
+            {
+                up &= dependency.status.is_up();
+                if dependency.status == Status::Stopped {
+                    dependency.request();
+                }
+            }
+
+            if up {
+                service.spawn(now);
+            }
+        }
+    }
+
+    // dependencies requested above are handled on the next call
+    services
+        .iter()
+        .any(|s| s.status.is_active() || s.requested.load(Ordering::Relaxed))
+}
+
+struct Eve;
+
+impl PolledDriver for Eve {
+    fn name(&self) -> &'static str {
+        "eve"
+    }
+
+    fn poll_interval(&self) -> Option<u64> {
+        SUPERVISING
+            .load(Ordering::Relaxed)
+            .then_some(SUPERVISE_INTERVAL)
+    }
+
+    fn poll(&self) {
+        let supervising = supervise(&mut SERVICES.write(), threading::ticks());
+        SUPERVISING.store(supervising, Ordering::Relaxed);
+    }
+}
+
+/// loads the services and starts the ones that start on boot, called by Eve before idling
+pub fn init() {
+    let mut services = builtin_services();
+    for service in load_config(INIT_CONFIG)
+        .into_iter()
+        .chain(load_dir(INIT_DIR))
+    {
+        services.retain(|s| s.name != service.name);
+        services.push(service);
+    }
+
+    let services = order(services);
+    for service in services.iter().filter(|s| s.autostart) {
+        service.request();
+    }
+
+    info!(Eve, "{} services", services.len());
+    *SERVICES.write() = services;
+    SUPERVISING.store(true, Ordering::Relaxed);
+    poll::wake(*EVE_POLL_ID.call_once(|| poll::register(&Eve)));
+}
+
+/// asks Eve to start the service `name` if it isn't already running or about to, must not be called
+/// from interrupt handlers
+pub fn start(name: &str) {
+    match SERVICES.read().iter().find(|s| s.name == name) {
+        Some(service) => service.request(),
+        None => {
+            warn!(Eve, "no service named {name}");
+            return;
+        }
+    }
+
+    SUPERVISING.store(true, Ordering::Relaxed);
+    if let Some(id) = EVE_POLL_ID.get() {
+        poll::wake(*id);
+    }
+}
+
+/// generates the data of proc:/eve-journal
+pub fn eve_journal() -> String {
+    let mut results = String::new();
+    for line in JOURNAL.lock().iter() {
+        _ = writeln!(results, "{line}");
+    }
+    results
+}
+
+/// generates the data of proc:/services
+pub fn services_info() -> String {
+    let mut results = String::new();
+
+    for service in SERVICES.read().iter() {
+        _ = writeln!(results, "{}:", service.name);
+        _ = writeln!(results, "  status: {}", service.status);
+        _ = writeln!(results, "  path: {}", service.path);
+        _ = writeln!(results, "  restart: {}", service.restart.name());
+        if service.depends.is_empty() {
+            _ = writeln!(results, "  depends: none");
+        } else {
+            _ = writeln!(results, "  depends: {}", service.depends.join(" "));
+        }
+        _ = writeln!(results, "  restarts: {}", service.restarts);
+    }
+
+    results
+}
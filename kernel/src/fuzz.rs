@@ -0,0 +1,159 @@
+//! syscall fuzzer, ran after the tests with the `fuzz` feature
+//! an untrusted kernel process (see `ProcessFlags::UNTRUSTED`) invokes random syscalls with random
+//! arguments including misaligned, unmapped, kernel and non-canonical pointers, the kernel must only
+//! ever return error statuses and never panic
+//! `fuzz_iterations=<n>` and `fuzz_seed=<n>` in the kernel command line change the amount of syscalls
+//! and the seed, the seed is printed so that a failure can be reproduced
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use macros::test_module;
+
+use crate::{
+    cross_println, hddm,
+    threading::{expose::thread_exit, RING0_STACK_START, STACK_SIZE},
+    utils::errors::ErrorStatus,
+};
+
+const DEFAULT_ITERATIONS: u64 = 10_000;
+const DEFAULT_SEED: u64 = 0x5AFA_0500;
+/// one past the last syscall
+const SYSCALL_COUNT: u64 = 68;
+const SYSYIELD: u64 = 1;
+/// syscalls that aren't fuzzed, exit would stop the fuzzer, wait and wait_status block until a
+/// random pid exits and sbrk can eat all of the memory
+const SKIPPED: &[u64] = &[0, 11, 18, 54];
+/// (syscall, argument) of timeouts and sleeps, they are always 0 so that the fuzzer never blocks
+const TIMEOUTS: &[(u64, usize)] = &[(30, 2), (33, 3), (55, 0)];
+
+static ITERATIONS: AtomicU64 = AtomicU64::new(DEFAULT_ITERATIONS);
+static SEED: AtomicU64 = AtomicU64::new(DEFAULT_SEED);
+
+/// xorshift64
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, max: u64) -> u64 {
+        self.next() % max
+    }
+}
+
+/// returns a random syscall argument, biased towards values syscalls have to reject
+fn argument(rng: &mut Rng) -> u64 {
+    match rng.below(9) {
+        0 => 0,
+        // ris, lengths and ops
+        1 => rng.below(16),
+        2 => u64::MAX - rng.below(16),
+        // misaligned
+        3 => rng.next() | 1,
+        // unmapped user pages
+        4 => 0x1000 * rng.below(0x10000),
+        // kernel only pages in the lower half
+        5 => RING0_STACK_START as u64 + rng.below(STACK_SIZE as u64),
+        6 => hddm() as u64 + rng.below(1 << 30),
+        // non-canonical
+        7 => (1 << 47) + rng.below(1 << 32),
+        _ => rng.next(),
+    }
+}
+
+/// # Safety
+/// the arguments can be anything but the syscall mustn't be one of `SKIPPED`
+unsafe fn syscall(number: u64, args: [u64; 6]) -> u64 {
+    let results;
+    asm!(
+        "int 0x80",
+        inlateout("rax") number => results,
+        in("rdi") args[0],
+        in("rsi") args[1],
+        in("rdx") args[2],
+        in("rcx") args[3],
+        in("r8") args[4],
+        in("r9") args[5],
+    );
+    results
+}
+
+fn fuzzer() -> ! {
+    let mut rng = Rng(SEED.load(Ordering::Relaxed));
+
+    for iteration in 0..ITERATIONS.load(Ordering::Relaxed) {
+        // numbers past the end of the syscall table are fuzzed too
+        let number = match rng.below(16) {
+            0 => rng.next(),
+            _ => rng.below(SYSCALL_COUNT),
+        };
+        if SKIPPED.contains(&number) {
+            continue;
+        }
+
+        let mut args = [0; 6];
+        for arg in &mut args {
+            *arg = argument(&mut rng);
+        }
+        for (syscall, index) in TIMEOUTS {
+            if *syscall == number {
+                args[*index] = 0;
+            }
+        }
+
+        let results = unsafe { syscall(number, args) };
+        if number != SYSYIELD && results > ErrorStatus::NotEnoughArguments as u64 {
+            cross_println!(
+                "[FUZZ] iteration {} syscall {} {:#x?} returned an invaild status {:#x}",
+                iteration,
+                number,
+                args,
+                results
+            );
+            thread_exit(1);
+        }
+    }
+
+    thread_exit(0);
+    unreachable!()
+}
+
+#[test_module]
+pub mod fuzz_module {
+    use core::sync::atomic::Ordering;
+
+    use super::{fuzzer, ITERATIONS, SEED};
+    use crate::{
+        serial,
+        test::cmdline_option,
+        threading::{
+            expose::{kspawn_with, wait},
+            processes::ProcessFlags,
+        },
+    };
+
+    fn syscalls() {
+        if let Some(iterations) = cmdline_option("fuzz_iterations").and_then(|n| n.parse().ok()) {
+            ITERATIONS.store(iterations, Ordering::Relaxed);
+        }
+        // xorshift gets stuck at 0
+        if let Some(seed) = cmdline_option("fuzz_seed").and_then(|n| n.parse().ok()) {
+            SEED.store(u64::max(seed, 1), Ordering::Relaxed);
+        }
+
+        serial!(
+            "[FUZZ] seed={} iterations={}\n",
+            SEED.load(Ordering::Relaxed),
+            ITERATIONS.load(Ordering::Relaxed)
+        );
+
+        let pid = kspawn_with("FUZZER", fuzzer, ProcessFlags::UNTRUSTED).unwrap();
+        assert_eq!(wait(pid), 0);
+    }
+}
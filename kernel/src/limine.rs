@@ -1,9 +1,9 @@
 use alloc::slice;
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use limine::file::File;
 use limine::framebuffer::MemoryModel;
 use limine::modules::InternalModule;
-use limine::modules::ModuleFlags;
 use limine::request::FramebufferRequest;
 use limine::request::HhdmRequest;
 use limine::request::KernelAddressRequest;
@@ -17,8 +17,8 @@ use limine::BaseRevision;
 
 use crate::drivers::framebuffer::FrameBufferInfo;
 use crate::drivers::framebuffer::PixelFormat;
+use crate::drivers::framebuffer::VideoMode;
 use crate::memory::align_up;
-use crate::utils::ustar::TarArchiveIter;
 
 #[used]
 #[link_section = ".requests"]
@@ -48,14 +48,20 @@ static MMAP_REQUEST: MemoryMapRequest = MemoryMapRequest::new();
 #[link_section = ".requests"]
 static FRAMEBUFFER_REQUEST: FramebufferRequest = FramebufferRequest::new();
 
-const RAMDISK_MODULE: InternalModule = InternalModule::new()
-    .with_path(c"ramdisk.tar")
-    .with_flags(ModuleFlags::REQUIRED);
+const RAMDISK_MODULE: InternalModule = InternalModule::new().with_path(c"ramdisk.tar");
+/// the builder gzips the ramdisk when built with the `compressed-ramdisk` feature
+const COMPRESSED_RAMDISK_MODULE: InternalModule =
+    InternalModule::new().with_path(c"ramdisk.tar.gz");
+/// the builder makes a rofs image instead of a tar archive unless built with the `tar-ramdisk` feature
+const IMAGE_RAMDISK_MODULE: InternalModule = InternalModule::new().with_path(c"ramdisk.rofs");
 
 #[used]
 #[link_section = ".requests"]
-static MODULES_REQUEST: ModuleRequest =
-    ModuleRequest::new().with_internal_modules(&[&RAMDISK_MODULE]);
+static MODULES_REQUEST: ModuleRequest = ModuleRequest::new().with_internal_modules(&[
+    &RAMDISK_MODULE,
+    &COMPRESSED_RAMDISK_MODULE,
+    &IMAGE_RAMDISK_MODULE,
+]);
 
 pub fn get_phy_offset() -> usize {
     HHDM_REQUEST.get_response().unwrap().offset() as usize
@@ -69,6 +75,12 @@ pub fn kernel_file() -> &'static File {
     KERNEL_FILE_REQUEST.get_response().unwrap().file()
 }
 
+/// returns the command line the kernel was booted with, empty if it isn't valid utf8
+#[cfg(feature = "test")]
+pub fn kernel_cmdline() -> &'static str {
+    core::str::from_utf8(kernel_file().cmdline()).unwrap_or("")
+}
+
 /// returns addr to the kernel image and it's size
 pub fn kernel_image_info() -> (*const u8, usize) {
     let file = kernel_file();
@@ -101,7 +113,24 @@ pub fn get_phy_offset_end() -> usize {
     get_phy_offset() + *MEMORY_END
 }
 
-pub fn get_framebuffer() -> (&'static mut [u8], FrameBufferInfo) {
+/// returns the video modes the display supports, may be empty if the bootloader doesn't report them
+pub fn get_video_modes() -> Vec<VideoMode> {
+    let mut buffers = FRAMEBUFFER_REQUEST.get_response().unwrap().framebuffers();
+    let first = buffers.next().unwrap();
+
+    first
+        .modes()
+        .unwrap_or_default()
+        .iter()
+        .map(|mode| VideoMode {
+            width: mode.width,
+            height: mode.height,
+            bpp: mode.bpp,
+        })
+        .collect()
+}
+
+pub fn get_framebuffer() -> (&'static mut [u8], FrameBufferInfo, VideoMode) {
     let mut buffers = FRAMEBUFFER_REQUEST.get_response().unwrap().framebuffers();
     let first = buffers.next().unwrap();
 
@@ -121,17 +150,27 @@ pub fn get_framebuffer() -> (&'static mut [u8], FrameBufferInfo) {
 
     let size = (first.width() * first.height() * first.bpp() as u64 / 8) as usize;
     let buffer = unsafe { slice::from_raw_parts_mut(first.addr(), size) };
+    let mode = VideoMode {
+        width: first.width(),
+        height: first.height(),
+        bpp: first.bpp(),
+    };
 
-    (buffer, info)
+    (buffer, info, mode)
 }
 
+/// only one of the ramdisk modules exists
 pub fn get_ramdisk_file() -> &'static File {
     MODULES_REQUEST
         .get_response()
         .expect("failed getting modules!")
-        .modules()[0]
+        .modules()
+        .first()
+        .expect("no ramdisk module loaded")
 }
 
-pub fn get_ramdisk() -> TarArchiveIter<'static> {
-    unsafe { TarArchiveIter::new(get_ramdisk_file().addr()) }
+/// returns the ramdisk's contents, a rofs image or a tar archive which may be gzip compressed
+pub fn get_ramdisk() -> &'static [u8] {
+    let file = get_ramdisk_file();
+    unsafe { slice::from_raw_parts(file.addr(), file.size() as usize) }
 }
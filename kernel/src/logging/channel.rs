@@ -0,0 +1,244 @@
+//! the serial log channel, writers never wait for the serial port
+//! every cpu has it's own staging ring, a write is split into lines and each line is committed to
+//! the ring of the current cpu as a single record, whoever wins `FLUSHING` then writes the committed
+//! records to the serial port while the others return right away, so lines from different cpus never
+//! interleave and only one cpu at a time waits for the port
+//! the rings are lock free so they can be written to from interrupts too, if the interrupted code
+//! was flushing it picks up the interrupt's lines once it resumes
+//! a ring that is full drops the new lines, the amount of dropped bytes is reported on the next flush
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+};
+
+use crate::arch::{
+    cpu,
+    serial::{write_serial_string, Serial},
+};
+
+/// the amount of rings, cpus share rings if there are more of them
+const RINGS: usize = 8;
+/// the size of a ring in bytes, has to be a multiple of `WORD`
+const RING_SIZE: usize = 16 * 1024;
+/// the longest record, longer lines are split into multiple records
+const LINE_MAX: usize = 256;
+
+const WORD: usize = size_of::<u32>();
+/// the header of a record that only pads the ring to it's end, the rest of the header is the size
+/// of the padding
+const PADDING: u32 = 1 << 31;
+
+/// a multi producer single consumer ring of records, a record is a header word with the length of
+/// the line followed by the line, the header is 0 until the record is committed
+struct Ring {
+    words: [AtomicU32; RING_SIZE / WORD],
+    /// the amount of bytes ever reserved
+    head: AtomicUsize,
+    /// the amount of bytes ever consumed
+    tail: AtomicUsize,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Self {
+            words: [const { AtomicU32::new(0) }; RING_SIZE / WORD],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// reserves `size` bytes, returns their position or None if the ring is full
+    fn reserve(&self, size: usize) -> Option<usize> {
+        let mut head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let offset = head % RING_SIZE;
+            // records don't wrap around
+            let padding = if offset + size > RING_SIZE {
+                RING_SIZE - offset
+            } else {
+                0
+            };
+
+            if head + padding + size - self.tail.load(Ordering::Acquire) > RING_SIZE {
+                return None;
+            }
+
+            match self.head.compare_exchange_weak(
+                head,
+                head + padding + size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    if padding != 0 {
+                        self.words[offset / WORD]
+                            .store(PADDING | padding as u32, Ordering::Release);
+                    }
+                    return Some(head + padding);
+                }
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// returns false if the ring is full
+    fn push(&self, line: &[u8]) -> bool {
+        let size = (WORD + line.len()).next_multiple_of(WORD);
+        let Some(position) = self.reserve(size) else {
+            return false;
+        };
+
+        let offset = position % RING_SIZE;
+        unsafe {
+            let data = self.words.as_ptr().add(offset / WORD + 1) as *mut u8;
+            core::ptr::copy_nonoverlapping(line.as_ptr(), data, line.len());
+        }
+        self.words[offset / WORD].store(line.len() as u32, Ordering::Release);
+        true
+    }
+
+    /// writes the committed records to the serial port in order, stops at the first one that isn't
+    /// committed yet, only called by the holder of `FLUSHING`
+    fn flush(&self) {
+        loop {
+            let tail = self.tail.load(Ordering::Relaxed);
+            if tail == self.head.load(Ordering::Acquire) {
+                return;
+            }
+
+            let offset = tail % RING_SIZE;
+            let header = self.words[offset / WORD].load(Ordering::Acquire);
+            let size = match header {
+                0 => return,
+                _ if header & PADDING != 0 => (header & !PADDING) as usize,
+                len => {
+                    let line = unsafe {
+                        let data = self.words.as_ptr().add(offset / WORD + 1) as *const u8;
+                        core::slice::from_raw_parts(data, len as usize)
+                    };
+                    // the lines are split from strs at '\n' or at a char boundary
+                    write_serial_string(unsafe { core::str::from_utf8_unchecked(line) });
+                    (WORD + len as usize).next_multiple_of(WORD)
+                }
+            };
+
+            // a header of a later record can land anywhere in the consumed bytes
+            for word in &self.words[offset / WORD..(offset + size) / WORD] {
+                word.store(0, Ordering::Relaxed);
+            }
+            self.tail.store(tail + size, Ordering::Release);
+        }
+    }
+
+    /// wether or not the next record is committed
+    fn has_committed(&self) -> bool {
+        let tail = self.tail.load(Ordering::Acquire);
+        tail != self.head.load(Ordering::Acquire)
+            && self.words[tail % RING_SIZE / WORD].load(Ordering::Acquire) != 0
+    }
+}
+
+static RING_LIST: [Ring; RINGS] = [const { Ring::new() }; RINGS];
+/// held by whoever is writing the rings to the serial port
+static FLUSHING: AtomicBool = AtomicBool::new(false);
+/// the amount of bytes dropped since the last flush
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+/// set once the kernel panics, writes go to the serial port directly from then on
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// splits what is written into lines and pushes each one as a record
+struct LineWriter<'a> {
+    ring: &'a Ring,
+    line: [u8; LINE_MAX],
+    len: usize,
+}
+
+impl LineWriter<'_> {
+    fn commit(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        if !self.ring.push(&self.line[..self.len]) {
+            DROPPED.fetch_add(self.len, Ordering::Relaxed);
+        }
+        self.len = 0;
+    }
+}
+
+impl Write for LineWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut bytes = [0u8; 4];
+            let bytes = c.encode_utf8(&mut bytes).as_bytes();
+
+            if self.len + bytes.len() > LINE_MAX {
+                self.commit();
+            }
+            self.line[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+
+            if c == '\n' {
+                self.commit();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn flush_rings() {
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if dropped != 0 {
+        _ = writeln!(Serial, "[log: dropped {} bytes]", dropped);
+    }
+
+    for ring in &RING_LIST {
+        ring.flush();
+    }
+}
+
+/// writes the committed lines to the serial port unless another cpu (or the code this interrupted)
+/// is already doing it
+pub fn try_flush() {
+    // lines committed after the flush but before `FLUSHING` is released are flushed by looping,
+    // lines that aren't committed yet are flushed by their writer once it commits them
+    while RING_LIST.iter().any(Ring::has_committed) || DROPPED.load(Ordering::Relaxed) != 0 {
+        if FLUSHING
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+
+        flush_rings();
+        FLUSHING.store(false, Ordering::Release);
+    }
+}
+
+/// writes `args` to the serial port through the ring of the current cpu
+pub fn write(args: fmt::Arguments) {
+    if PANICKING.load(Ordering::Relaxed) {
+        _ = Serial.write_fmt(args);
+        return;
+    }
+
+    let ring = &RING_LIST[cpu::current_id().unwrap_or(0) % RINGS];
+    let mut writer = LineWriter {
+        ring,
+        line: [0; LINE_MAX],
+        len: 0,
+    };
+
+    _ = writer.write_fmt(args);
+    writer.commit();
+    try_flush();
+}
+
+/// writes everything that is still in the rings and makes writes go to the serial port directly,
+/// the cpu that was flushing could have been stopped in the middle of a record so `FLUSHING` is
+/// ignored
+pub fn panic() {
+    PANICKING.store(true, Ordering::Relaxed);
+    flush_rings();
+}
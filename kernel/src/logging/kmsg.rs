@@ -0,0 +1,63 @@
+//! the kmsg ring, keeps the last `KMSG_SIZE` bytes of the log
+//! it doesn't allocate so it can be logged to before the heap is ready
+use core::fmt::{self, Write};
+
+use alloc::{string::String, vec::Vec};
+use spin::Mutex;
+
+const KMSG_SIZE: usize = 64 * 1024;
+
+struct Ring {
+    buffer: [u8; KMSG_SIZE],
+    /// the index of the oldest byte
+    start: usize,
+    len: usize,
+}
+
+impl Write for Ring {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            let end = (self.start + self.len) % KMSG_SIZE;
+            self.buffer[end] = byte;
+
+            if self.len == KMSG_SIZE {
+                self.start = (self.start + 1) % KMSG_SIZE;
+            } else {
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+static KMSG: Mutex<Ring> = Mutex::new(Ring {
+    buffer: [0; KMSG_SIZE],
+    start: 0,
+    len: 0,
+});
+
+pub(super) fn push(args: fmt::Arguments) {
+    // a message logged while the ring is written to by the same cpu (from an interrupt) is only
+    // written to the other outputs
+    if let Some(mut ring) = KMSG.try_lock() {
+        _ = ring.write_fmt(args);
+    }
+}
+
+/// generates the data of proc:/kmsg, the first line is dropped if the ring wrapped into it
+pub fn kmsg() -> String {
+    let ring = KMSG.lock();
+    let (newest, oldest) = ring.buffer.split_at(ring.start);
+    let mut bytes = oldest.iter().chain(newest).take(ring.len).copied();
+
+    if ring.len == KMSG_SIZE {
+        bytes
+            .by_ref()
+            .take_while(|byte| *byte != b'\n')
+            .for_each(drop);
+    }
+    let bytes: Vec<u8> = bytes.collect();
+    // the ring could have wrapped in the middle of a character
+    String::from_utf8(bytes)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
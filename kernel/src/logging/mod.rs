@@ -0,0 +1,171 @@
+//! the kernel log, every message is prefixed with the time since boot, the cpu it was logged on
+//! and the subsystem that logged it
+//! messages are written to the serial, to the kmsg ring (read from proc:/kmsg) and while booting
+//! (from `terminal_ready` to `boot_done`) messages that are at least `Level::Info` are written to
+//! the terminal too
+//! the serial is written through `channel` so lines from different cpus don't interleave
+//! messages above `MAX_LEVEL` are compiled out, debug messages are only kept in debug builds
+pub mod channel;
+mod kmsg;
+
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    arch::{
+        cpu,
+        x86_64::{cycles, cycles_frequency},
+    },
+    terminal::FRAMEBUFFER_TERMINAL,
+};
+
+pub use kmsg::kmsg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+        }
+    }
+
+    const fn color(&self) -> &'static str {
+        match self {
+            Self::Error => "\x1B[38;2;255;0;0m",
+            Self::Warn => "\x1B[38;2;255;200;0m",
+            Self::Info => "\x1B[38;2;0;200;0m",
+            Self::Debug => "\x1B[38;2;0;155;200m",
+        }
+    }
+}
+
+/// the most verbose level that is logged
+pub const MAX_LEVEL: Level = if cfg!(debug_assertions) {
+    Level::Debug
+} else {
+    Level::Info
+};
+
+/// wether or not messages are written to the terminal
+static TO_TERMINAL: AtomicBool = AtomicBool::new(false);
+
+/// starts writing messages to the terminal, it can't be used before the heap is ready
+pub fn terminal_ready() {
+    TO_TERMINAL.store(true, Ordering::Relaxed);
+}
+
+/// stops writing messages to the terminal, called once the kernel is done booting
+pub fn boot_done() {
+    TO_TERMINAL.store(false, Ordering::Relaxed);
+}
+
+/// the time since reset in seconds and microseconds, or the amount of cycles if the cpu doesn't
+/// report it's TSC frequency
+struct Timestamp;
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cycles = cycles();
+        match cycles_frequency() {
+            Some(frequency) => {
+                let micros = cycles as u128 * 1_000_000 / frequency as u128;
+                write!(f, "{:5}.{:06}", micros / 1_000_000, micros % 1_000_000)
+            }
+            None => write!(f, "{:12}", cycles),
+        }
+    }
+}
+
+/// the id of the current cpu, `?` before the cpus are discovered
+struct CpuId;
+
+impl fmt::Display for CpuId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match cpu::current_id() {
+            Some(id) => write!(f, "{}", id),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+#[doc(hidden)]
+/// use the `log!` macro (or `error!`, `warn!`, `info!` and `debug!`) instead
+pub fn _log(level: Level, subsystem: &str, args: fmt::Arguments) {
+    let (timestamp, cpu) = (Timestamp, CpuId);
+
+    channel::write(format_args!(
+        "[{timestamp}] cpu{cpu} {}{}\x1B[38;2;255;155;0m {subsystem}:\x1B[0m {args}\n",
+        level.color(),
+        level.name(),
+    ));
+
+    kmsg::push(format_args!(
+        "[{timestamp}] cpu{cpu} {} {subsystem}: {args}\n",
+        level.name()
+    ));
+
+    if level <= Level::Info && TO_TERMINAL.load(Ordering::Relaxed) {
+        // the terminal could be locked by whatever is logging
+        if let Some(mut terminal) = FRAMEBUFFER_TERMINAL.try_write() {
+            _ = writeln!(
+                terminal,
+                "{}{}\x1B[0m {subsystem}: {args}",
+                level.color(),
+                level.name(),
+            );
+        }
+    }
+}
+
+/// logs a message at `$level` for the subsystem `$mod`, $mod must be a type
+#[macro_export]
+macro_rules! log {
+    ($level: expr, $mod: path, $($arg:tt)*) => {
+        if $level <= $crate::logging::MAX_LEVEL {
+            // makes sure $mod is a vaild type
+            let _ = core::marker::PhantomData::<$mod>;
+            $crate::logging::_log($level, stringify!($mod), format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($mod: path, $($arg:tt)*) => {
+        $crate::log!($crate::logging::Level::Error, $mod, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($mod: path, $($arg:tt)*) => {
+        $crate::log!($crate::logging::Level::Warn, $mod, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($mod: path, $($arg:tt)*) => {
+        $crate::log!($crate::logging::Level::Info, $mod, $($arg)*)
+    };
+}
+
+/// runtime debug info, only logged in debug builds
+#[macro_export]
+macro_rules! debug {
+    ($mod: path, $($arg:tt)*) => {
+        $crate::log!($crate::logging::Level::Debug, $mod, $($arg)*)
+    };
+}
@@ -6,14 +6,20 @@
 #![feature(pattern)]
 #![feature(box_vec_non_null)]
 
+#[cfg(feature = "bench")]
+mod bench;
+#[cfg(feature = "fuzz")]
+mod fuzz;
 #[cfg(feature = "test")]
 mod test;
 
 mod arch;
 mod devices;
 mod drivers;
+mod eve;
 mod globals;
 mod limine;
+mod logging;
 mod memory;
 mod syscalls;
 mod terminal;
@@ -23,8 +29,6 @@ mod utils;
 extern crate alloc;
 use arch::x86_64::serial;
 
-use drivers::keyboard::keys::Key;
-use drivers::keyboard::HandleKey;
 use drivers::vfs;
 use globals::*;
 
@@ -33,7 +37,9 @@ use limine::get_phy_offset_end;
 pub use memory::PhysAddr;
 pub use memory::VirtAddr;
 use terminal::FRAMEBUFFER_TERMINAL;
+use threading::expose::{kspawn, thread_exit, wait};
 use threading::Scheduler;
+use utils::boot::{self, Stage};
 
 #[macro_export]
 macro_rules! print {
@@ -73,33 +79,19 @@ use core::panic::PanicInfo;
 #[macro_export]
 macro_rules! cross_println {
     ($($arg:tt)*) => {
-        $crate::serial!($($arg)*);
-        $crate::serial!("\n");
-
-
+        $crate::serial!("{}\n", format_args!($($arg)*));
         $crate::println!(r"{}", format_args!($($arg)*));
     };
 }
 
-/// runtime debug info that is only avalible though test feature
-/// takes a $mod and an Arguments, mod must be a type
-#[macro_export]
-macro_rules! debug {
-    ($mod: path, $($arg:tt)*) => {
-        // makes sure $mod is a vaild type
-        let _ = core::marker::PhantomData::<$mod>;
-        $crate::serial!("\x1B[38;2;0;155;200m[DEBUG]\x1B[38;2;255;155;0m {}: \x1B[0m{}\n", stringify!($mod), format_args!($($arg)*));
-    };
-}
-
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     unsafe { asm!("cli") }
     unsafe {
-        arch::x86_64::serial::SERIAL.inner.force_unlock();
         FRAMEBUFFER_TERMINAL.force_write_unlock();
     }
+    logging::channel::panic();
 
     FRAMEBUFFER_TERMINAL.write().clear();
     cross_println!(
@@ -109,9 +101,12 @@ fn panic(info: &PanicInfo) -> ! {
     );
     print_stack_trace();
 
+    #[cfg(feature = "test")]
+    arch::qemu::debug_exit(arch::qemu::EXIT_PANIC);
+
     // crate::serial!("tty stdout dump:\n{}\n", crate::terminal().stdout_buffer);
     // crate::serial!("tty stdin dump:\n{}\n", crate::terminal().stdin_buffer);
-    khalt()
+    arch::halt_all()
 }
 
 #[allow(unused)]
@@ -145,7 +140,16 @@ fn print_stack_trace() {
 
 #[no_mangle]
 pub extern "C" fn kinit() {
+    utils::boot::start();
     arch::init_phase1();
+    serial!(
+        "SafaOS {} ({}) built {} with {}, features: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        utils::expose::COMMIT,
+        utils::expose::BUILD_TIME,
+        utils::expose::RUSTC_VERSION,
+        utils::expose::FEATURES,
+    );
     // initing globals
     let phy_offset = get_phy_offset();
     unsafe {
@@ -158,16 +162,23 @@ pub extern "C" fn kinit() {
         limine::get_phy_offset_end(),
     );
 
-    memory::sorcery::init_page_table();
-    memory::init(get_phy_offset_end());
+    boot::time(Stage::Memory, || {
+        memory::sorcery::init_page_table();
+        memory::init(get_phy_offset_end());
+    });
     println!("Terminal initialized successfuly");
+    logging::terminal_ready();
 
     // initing the arch
-    arch::init_phase2();
+    boot::time(Stage::Arch, arch::init_phase2);
 
-    unsafe {
+    boot::time(Stage::Devices, || {
         devices::init();
-        vfs::init();
+        drivers::keyboard::init();
+    });
+    boot::time(Stage::Vfs, vfs::init);
+
+    unsafe {
         debug!(Scheduler, "Eve starting...");
         Scheduler::init(kmain as usize, "Eve");
     }
@@ -182,6 +193,15 @@ fn kstart() -> ! {
 #[no_mangle]
 fn kmain() -> ! {
     debug!(Scheduler, "done ...");
+    // the ramdisk and the virtio drivers don't depend on each other, everything after the joins may
+    // depend on both
+    let ramdisk = kspawn("Ramdisk", unpack_ramdisk).expect("failed spawning the ramdisk thread");
+    let drivers = kspawn("Drivers", init_drivers).expect("failed spawning the drivers thread");
+    wait(ramdisk);
+    wait(drivers);
+    boot::done();
+
+    logging::boot_done();
     let stdin = vfs::expose::open("dev:/tty").unwrap();
     let stdout = vfs::expose::open("dev:/tty").unwrap();
     serial!(
@@ -190,22 +210,47 @@ fn kmain() -> ! {
         stdout
     );
 
+    #[cfg(feature = "test")]
+    if arch::qemu::profile_requested() {
+        let source = arch::profiler::start();
+        serial!("profiling from {:?}\n", source);
+    }
+
     #[cfg(feature = "test")]
     test::testing_module::test_main();
+    #[cfg(feature = "test")]
+    test::snapshot_module::test_main();
+    #[cfg(feature = "bench")]
+    bench::bench_module::test_main();
+    #[cfg(feature = "fuzz")]
+    fuzz::fuzz_module::test_main();
 
     println!("finished running tests...");
     println!("\x1B[38;2;0;255;0mBoot success! press ctrl + shift + C to start the shell\x1B[0m");
 
     serial!("finished initing...\n");
+    #[cfg(feature = "test")]
+    arch::qemu::debug_exit(arch::qemu::EXIT_SUCCESS);
+    eve::init();
     serial!("idle!\n");
     // listening to interrupts
-    khalt()
+    arch::idle::idle_loop()
 }
 
-// whenever a key is pressed this function should be called
-// this executes a few other kernel-functions
-pub fn __navi_key_pressed(key: Key) {
-    if let Some(mut writer) = FRAMEBUFFER_TERMINAL.try_write() {
-        writer.handle_key(key);
-    };
+fn unpack_ramdisk() -> ! {
+    boot::time(Stage::Ramdisk, vfs::unpack_ramdisk);
+    thread_exit(0);
+    unreachable!()
+}
+
+fn init_drivers() -> ! {
+    boot::time(Stage::Drivers, || {
+        drivers::virtio::sound::init();
+        devices::init_audio();
+        drivers::virtio::gpu::init();
+        drivers::virtio::ninep::init();
+        vfs::hostfs::init();
+    });
+    thread_exit(0);
+    unreachable!()
 }
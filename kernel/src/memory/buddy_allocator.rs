@@ -7,7 +7,7 @@ use crate::{debug, memory::frame_allocator, utils::Locked};
 
 use super::{
     align_up,
-    paging::{current_root_table, EntryFlags, IterPage, Page},
+    paging::{current_root_table, EntryFlags, IterPage, Page, PAGE_SIZE},
     VirtAddr,
 };
 
@@ -115,6 +115,11 @@ impl BuddyAllocator<'_> {
 
     pub fn expand_heap_by<'b>(&mut self, size: usize) -> Option<&'b mut Block> {
         debug!(BuddyAllocator, "expanding the heap by {:#x}", size);
+        // fails early instead of mapping pages that can't be used
+        if size.div_ceil(PAGE_SIZE) > frame_allocator::free_frames() {
+            return None;
+        }
+
         let iter = IterPage {
             start: Page::containing_address(self.heap_end),
             end: Page::containing_address(self.heap_end + size),
@@ -125,7 +130,11 @@ impl BuddyAllocator<'_> {
                 if current_root_table().get_frame(page).is_none() {
                     let frame = frame_allocator::allocate_frame()?;
                     current_root_table()
-                        .map_to(page, frame, EntryFlags::PRESENT | EntryFlags::WRITABLE)
+                        .map_to(
+                            page,
+                            frame,
+                            EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+                        )
                         .ok()?;
                 }
             }
@@ -278,6 +287,8 @@ impl BuddyAllocator<'_> {
     pub unsafe fn deallocmut(&mut self, ptr: *mut u8) {
         let block: *mut Block = ptr.byte_sub(size_of::<Block>()).cast();
         (*block).free = true;
+        #[cfg(feature = "debug-poison")]
+        super::poison::poison_heap(ptr, (*block).size - size_of::<Block>());
         self.coalescence_buddies_full();
     }
 }
@@ -200,6 +200,8 @@ pub fn allocate_frame() -> Option<Frame> {
 
 #[inline(always)]
 pub fn deallocate_frame(frame: Frame) {
+    #[cfg(feature = "debug-poison")]
+    super::poison::poison_frame(frame);
     REGION_ALLOCATOR.lock().deallocate_frame(frame)
 }
 
@@ -213,3 +215,12 @@ pub fn mapped_frames() -> usize {
 pub fn usable_frames() -> usize {
     REGION_ALLOCATOR.lock().usable_frames()
 }
+
+/// returns the number of frames that can still be allocated
+#[inline(always)]
+pub fn free_frames() -> usize {
+    let allocator = REGION_ALLOCATOR.lock();
+    allocator
+        .usable_frames()
+        .saturating_sub(allocator.mapped_frames())
+}
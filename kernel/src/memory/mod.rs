@@ -2,6 +2,9 @@ pub mod buddy_allocator;
 pub mod frame_allocator;
 pub mod page_allocator;
 pub mod paging;
+#[cfg(feature = "debug-poison")]
+pub mod poison;
+pub mod shrinker;
 pub mod sorcery;
 
 // types for better code reability
@@ -61,7 +64,7 @@ unsafe fn init_heap(heap_start: usize) -> Result<(), MapToError> {
 
     serial!("Iter created!\n");
 
-    let flags = EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE;
+    let flags = EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE;
 
     for page in page_range {
         let frame = frame_allocator::allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
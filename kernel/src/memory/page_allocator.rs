@@ -10,9 +10,11 @@ use lazy_static::lazy_static;
 use crate::{debug, utils::Locked};
 
 use super::{
-    align_up, frame_allocator,
+    align_up,
+    frame_allocator::{self, Frame},
     paging::{current_root_table, EntryFlags, IterPage, MapToError, Page, PAGE_SIZE},
     sorcery::ROOT_BINDINGS,
+    PhysAddr,
 };
 
 pub struct PageAllocator {
@@ -60,7 +62,7 @@ impl PageAllocator {
                 current_root_table().map_to(
                     page,
                     frame,
-                    EntryFlags::PRESENT | EntryFlags::WRITABLE,
+                    EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
                 )?;
             }
         }
@@ -69,6 +71,43 @@ impl PageAllocator {
         Ok(start_page.start_address as *mut u8)
     }
 
+    /// maps `size` bytes of device memory starting at the physical address `phys` as uncached
+    /// returns a pointer to the mapped memory, mappings made with this are never freed
+    pub fn map_mmio(&mut self, phys: PhysAddr, size: usize) -> Result<*mut u8, MapToError> {
+        let offset = phys % PAGE_SIZE;
+        let page_count = (offset + size).div_ceil(PAGE_SIZE);
+
+        let start = self.last_allocation.1;
+        let end = start + page_count * PAGE_SIZE;
+
+        if end > self.heap_end {
+            return Err(MapToError::FrameAllocationFailed);
+        }
+
+        let iter = IterPage {
+            start: Page::containing_address(start),
+            end: Page::containing_address(end),
+        };
+
+        for (i, page) in iter.enumerate() {
+            let frame = Frame::containing_address(phys - offset + i * PAGE_SIZE);
+            unsafe {
+                current_root_table().map_to(
+                    page,
+                    frame,
+                    EntryFlags::PRESENT
+                        | EntryFlags::WRITABLE
+                        | EntryFlags::NO_CACHE
+                        | EntryFlags::NO_EXECUTE,
+                )?;
+            }
+        }
+        // counted as an allocation that is never deallocated so the heap is never reset over it
+        self.last_allocation = (start, end);
+        self.allocations += 1;
+        Ok((start + offset) as *mut u8)
+    }
+
     unsafe fn deallocmut(&mut self, ptr: *mut u8, size: usize) {
         let start = ptr as usize;
         let end = start + size;
@@ -3,9 +3,11 @@ const HIGHER_HALF_ENTRY: usize = 256;
 
 pub const PAGE_SIZE: usize = 4096;
 use crate::{
+    arch::{self, tlb},
     hddm,
     memory::{translate, PhysAddr},
 };
+use alloc::vec::Vec;
 use bitflags::bitflags;
 use core::{
     arch::asm,
@@ -99,7 +101,7 @@ impl Entry {
 
 #[cfg(target_arch = "x86_64")]
 bitflags! {
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct EntryFlags: u64 {
         const PRESENT =         1;
         const WRITABLE =        1 << 1;
@@ -150,6 +152,55 @@ impl PageTable {
     }
 }
 
+/// pushes the pages mapped by the `level` table `table` (covering `count` entries from `base`) to
+/// `ranges`, merging them with the previous range when they follow it with the same flags
+fn collect_ranges(
+    table: &PageTable,
+    level: u8,
+    base: VirtAddr,
+    count: usize,
+    ranges: &mut Vec<(VirtAddr, VirtAddr, EntryFlags)>,
+) {
+    let mask = EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE | EntryFlags::NO_EXECUTE;
+
+    for (i, entry) in table.entries[..count].iter().enumerate() {
+        if !entry.is_mapped() {
+            continue;
+        }
+
+        let addr = base | i << (12 + 9 * (level as usize - 1));
+        if level > 1 && !entry.flags().contains(EntryFlags::HUGE_PAGE) {
+            collect_ranges(
+                entry.mapped_to().unwrap(),
+                level - 1,
+                addr,
+                ENTRY_COUNT,
+                ranges,
+            );
+            continue;
+        }
+
+        let end = addr + (PAGE_SIZE << (9 * (level as usize - 1)));
+        let flags = entry.flags() & mask;
+        match ranges.last_mut() {
+            Some((_, last_end, last_flags)) if *last_end == addr && *last_flags == flags => {
+                *last_end = end
+            }
+            _ => ranges.push((addr, end, flags)),
+        }
+    }
+}
+
+impl PageTable {
+    /// returns the mapped ranges of the lower half as `(start, end, flags)`, only the
+    /// WRITABLE, USER_ACCESSIBLE and NO_EXECUTE flags are kept
+    pub fn lower_half_ranges(&self) -> Vec<(VirtAddr, VirtAddr, EntryFlags)> {
+        let mut ranges = Vec::new();
+        collect_ranges(self, 4, 0, HIGHER_HALF_ENTRY, &mut ranges);
+        ranges
+    }
+}
+
 impl Index<usize> for PageTable {
     type Output = Entry;
     fn index(&self, index: usize) -> &Self::Output {
@@ -184,7 +235,7 @@ pub enum MapToError {
 
 impl Entry {
     #[inline(always)]
-    /// changes the entry flags to `flags`
+    /// adds `flags` to the entry flags
     /// if the entry is not present it allocates a new frame and uses it's address as entry's
     /// then returns the entry address as a pagetable
     #[cfg(target_arch = "x86_64")]
@@ -194,7 +245,7 @@ impl Entry {
         if self.is_mapped() {
             let addr = self.frame().unwrap().start_address;
 
-            self.set(flags, addr);
+            self.set(self.flags() | flags, addr);
             let virt_addr = addr | hddm();
             let entry_ptr = virt_addr as *mut PageTable;
 
@@ -236,6 +287,15 @@ impl Entry {
     }
 }
 
+/// removes the flags the cpu doesn't support from `flags`
+fn supported_flags(flags: EntryFlags) -> EntryFlags {
+    if arch::nx_enabled() {
+        flags
+    } else {
+        flags - EntryFlags::NO_EXECUTE
+    }
+}
+
 impl PageTable {
     /// maps a virtual `Page` to physical `Frame`
     pub fn map_to(
@@ -244,20 +304,110 @@ impl PageTable {
         frame: Frame,
         flags: EntryFlags,
     ) -> Result<(), MapToError> {
+        let flags = supported_flags(flags);
         let (level_1_index, level_2_index, level_3_index, level_4_index) =
             translate(page.start_address);
-        let level_3_table = self[level_4_index].map(flags)?;
+        // the permissions are enforced by the level 1 entry only, the tables above it are shared
+        // with other pages so they have to allow everything
+        let table_flags =
+            EntryFlags::PRESENT | EntryFlags::WRITABLE | (flags & EntryFlags::USER_ACCESSIBLE);
+        let level_3_table = self[level_4_index].map(table_flags)?;
 
-        let level_2_table = level_3_table[level_3_index].map(flags)?;
+        let level_2_table = level_3_table[level_3_index].map(table_flags)?;
 
-        let level_1_table = level_2_table[level_2_index].map(flags)?;
+        let level_1_table = level_2_table[level_2_index].map(table_flags)?;
 
         let entry = &mut level_1_table[level_1_index];
+        let was_mapped = entry.is_mapped();
 
         *entry = Entry::new(flags, frame.start_address);
+
+        if was_mapped {
+            tlb::shootdown(page.start_address);
+        }
         Ok(())
     }
 
+    /// changes the flags of the already mapped `page` to `flags`
+    /// returns false if `page` isn't mapped or is a part of a huge page
+    pub fn set_flags(&mut self, page: Page, flags: EntryFlags) -> bool {
+        let (level_1_index, level_2_index, level_3_index, level_4_index) =
+            translate(page.start_address);
+
+        let mut table: &mut PageTable = self;
+        for index in [level_4_index, level_3_index, level_2_index] {
+            let entry = &table[index];
+            if entry.flags().contains(EntryFlags::HUGE_PAGE) {
+                return false;
+            }
+
+            match entry.mapped_to() {
+                Some(next) => table = next,
+                None => return false,
+            }
+        }
+
+        let entry = &mut table[level_1_index];
+        let Some(frame) = entry.frame() else {
+            return false;
+        };
+
+        entry.set(supported_flags(flags), frame.start_address);
+        tlb::shootdown(page.start_address);
+        true
+    }
+
+    /// returns the flags `page` is mapped with if it is mapped
+    pub fn flags_of(&mut self, page: Page) -> Option<EntryFlags> {
+        let (level_1_index, level_2_index, level_3_index, level_4_index) =
+            translate(page.start_address);
+
+        let mut table: &mut PageTable = self;
+        for index in [level_4_index, level_3_index, level_2_index] {
+            let entry = &table[index];
+            if entry.flags().contains(EntryFlags::HUGE_PAGE) {
+                return entry.is_mapped().then(|| entry.flags());
+            }
+            table = entry.mapped_to()?;
+        }
+
+        let entry = &table[level_1_index];
+        entry.is_mapped().then(|| entry.flags())
+    }
+
+    /// gets the entry page points to in the level 1 table if there is any
+    fn get_entry(&mut self, page: Page) -> Option<&'static mut Entry> {
+        let (level_1_index, level_2_index, level_3_index, level_4_index) =
+            translate(page.start_address);
+        let level_3_table = self[level_4_index].mapped_to()?;
+        let level_2_table = level_3_table[level_3_index].mapped_to()?;
+        let level_1_table = level_2_table[level_2_index].mapped_to()?;
+
+        Some(&mut level_1_table[level_1_index])
+    }
+
+    /// returns wether or not every page in `start..start + len` is mapped with at least `flags`
+    pub fn is_mapped_with(&mut self, start: VirtAddr, len: usize, flags: EntryFlags) -> bool {
+        if len == 0 {
+            return true;
+        }
+
+        let Some(end) = start
+            .checked_add(len)
+            .and_then(|end| end.checked_add(PAGE_SIZE - 1))
+        else {
+            return false;
+        };
+
+        let start_page = Page::containing_address(start);
+        let end_page = Page::containing_address(end);
+
+        Page::iter_pages(start_page, end_page).all(|page| {
+            self.get_entry(page)
+                .is_some_and(|entry| entry.flags().contains(flags | EntryFlags::PRESENT))
+        })
+    }
+
     /// gets the frame page points to
     pub fn get_frame(&mut self, page: Page) -> Option<Frame> {
         let (level_1_index, level_2_index, level_3_index, level_4_index) =
@@ -271,10 +421,29 @@ impl PageTable {
         entry.frame()
     }
 
-    /// unmap page and all of it's entries
+    /// unmap page and deallocates the frame it points to
     pub fn unmap(&mut self, page: Page) {
-        self.get_frame(page)
-            .inspect(|x| frame_allocator::deallocate_frame(*x));
+        let Some(entry) = self.get_entry(page) else {
+            return;
+        };
+
+        if let Some(frame) = entry.frame() {
+            entry.0 = 0;
+            tlb::shootdown(page.start_address);
+            frame_allocator::deallocate_frame(frame);
+        }
+    }
+
+    /// unmaps page without deallocating the frame it points to, for frames that are shared
+    pub fn unmap_shared(&mut self, page: Page) {
+        let Some(entry) = self.get_entry(page) else {
+            return;
+        };
+
+        if entry.frame().is_some() {
+            entry.0 = 0;
+            tlb::shootdown(page.start_address);
+        }
     }
 }
 
@@ -0,0 +1,40 @@
+//! debugging aids enabled by the `debug-poison` feature
+//! freed frames and heap blocks are filled with a poison pattern so that reading freed memory gives
+//! recognizable garbage instead of stale but plausible data, and faults in the unmapped pages
+//! right below the stacks are reported as stack overflows instead of generic page faults
+use crate::{
+    hddm,
+    threading::{RING0_STACK_START, STACK_START},
+};
+
+use super::{frame_allocator::Frame, paging::PAGE_SIZE, VirtAddr};
+
+/// the byte freed frames are filled with, a pointer read from a freed frame is non-canonical
+pub const FRAME_POISON: u8 = 0xDF;
+/// the byte freed heap blocks are filled with
+pub const HEAP_POISON: u8 = 0xDB;
+
+/// fills `frame` with `FRAME_POISON`
+pub fn poison_frame(frame: Frame) {
+    let ptr = (frame.start_address | hddm()) as *mut u8;
+    unsafe { core::ptr::write_bytes(ptr, FRAME_POISON, PAGE_SIZE) }
+}
+
+/// fills `ptr..ptr + len` with `HEAP_POISON`
+/// # Safety
+/// `ptr..ptr + len` must be a freed heap block that is still mapped
+pub unsafe fn poison_heap(ptr: *mut u8, len: usize) {
+    core::ptr::write_bytes(ptr, HEAP_POISON, len)
+}
+
+/// returns the name of the mapping `addr` is the guard page of if it is in one
+/// guard pages are never mapped so any access to them is an overflow of the mapping above
+pub fn guard_page_of(addr: VirtAddr) -> Option<&'static str> {
+    const GUARDS: [(VirtAddr, &str); 2] =
+        [(STACK_START, "stack"), (RING0_STACK_START, "ring0 stack")];
+
+    GUARDS
+        .iter()
+        .find(|(start, _)| (start - PAGE_SIZE..*start).contains(&addr))
+        .map(|(_, name)| *name)
+}
@@ -0,0 +1,105 @@
+//! caches register a `Shrinker` so the memory subsystem can ask them to release memory when an
+//! allocation fails, the stats of every shrinker are in proc:/meminfo
+//! shrinkers can be called with filesystem locks held so they shouldn't lock inodes
+use core::fmt::Write;
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{threading::processes, utils::locks::Mutex};
+
+use super::{frame_allocator, paging::PAGE_SIZE};
+
+pub struct Shrinker {
+    pub name: &'static str,
+    /// returns the amount of bytes the cache could release
+    pub count: fn() -> usize,
+    /// releases at least `target` bytes if it can, returns the amount of bytes released
+    pub shrink: fn(target: usize) -> usize,
+}
+
+struct Registered {
+    shrinker: &'static Shrinker,
+    /// how many times the shrinker was asked to release memory
+    calls: usize,
+    released: usize,
+}
+
+static SHRINKERS: Mutex<Vec<Registered>> = Mutex::new("Shrinkers", Vec::new());
+
+// no cache registers itself yet
+#[allow(dead_code)]
+pub fn register(shrinker: &'static Shrinker) {
+    SHRINKERS.lock().push(Registered {
+        shrinker,
+        calls: 0,
+        released: 0,
+    });
+}
+
+#[allow(dead_code)]
+pub fn unregister(shrinker: &'static Shrinker) {
+    SHRINKERS
+        .lock()
+        .retain(|registered| !core::ptr::eq(registered.shrinker, shrinker));
+}
+
+/// asks the shrinkers in the order they were registered to release memory until `target` bytes are
+/// released, returns the amount of bytes released
+pub fn shrink(target: usize) -> usize {
+    let mut released = 0;
+
+    for registered in SHRINKERS.lock().iter_mut() {
+        if released >= target {
+            break;
+        }
+
+        if (registered.shrinker.count)() == 0 {
+            continue;
+        }
+
+        let amount = (registered.shrinker.shrink)(target - released);
+        registered.calls += 1;
+        registered.released += amount;
+        released += amount;
+    }
+
+    released
+}
+
+/// calls `then`, if it fails asks the shrinkers to release `bytes` and calls it once more
+pub fn retry<T, E>(bytes: usize, mut then: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    match then() {
+        Err(_) if shrink(bytes) > 0 => then(),
+        results => results,
+    }
+}
+
+/// generates the data of proc:/meminfo
+pub fn meminfo() -> String {
+    let total = frame_allocator::usable_frames() * PAGE_SIZE;
+    let free = frame_allocator::free_frames() * PAGE_SIZE;
+    let mut results = String::new();
+
+    _ = writeln!(results, "total: {}", total);
+    _ = writeln!(results, "used: {}", total - free);
+    _ = writeln!(results, "free: {}", free);
+    // the rest of the used memory is the kernel's
+    _ = writeln!(
+        results,
+        "processes: {}",
+        processes::resident_pages() * PAGE_SIZE
+    );
+
+    for registered in SHRINKERS.lock().iter() {
+        _ = writeln!(
+            results,
+            "shrinker {}: cached={} calls={} released={}",
+            registered.shrinker.name,
+            (registered.shrinker.count)(),
+            registered.calls,
+            registered.released
+        );
+    }
+
+    results
+}
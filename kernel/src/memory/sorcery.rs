@@ -1,5 +1,6 @@
-use super::paging::PAGE_SIZE;
-use core::{arch::asm, fmt::Display};
+use super::paging::{EntryFlags, PAGE_SIZE};
+use alloc::string::String;
+use core::{arch::asm, fmt::Display, fmt::Write};
 use lazy_static::lazy_static;
 
 use crate::{
@@ -8,7 +9,10 @@ use crate::{
     memory::frame_allocator::{self, Frame},
 };
 
-use super::paging::{IterPage, MapToError, Page, PageTable};
+use super::{
+    paging::{IterPage, MapToError, Page, PageTable},
+    VirtAddr,
+};
 
 pub struct PageTableBinding {
     name: &'static str,
@@ -142,11 +146,131 @@ pub fn set_current_page_table(page_table: &'static mut PageTable) {
     }
 }
 
+extern "C" {
+    static __text_start: u8;
+    static __text_end: u8;
+    static __rodata_start: u8;
+    static __rodata_end: u8;
+    static __data_start: u8;
+    static __data_end: u8;
+}
+
+/// the sections of the kernel elf, their bounds and the flags they should be mapped with
+fn kernel_sections() -> [(&'static str, VirtAddr, VirtAddr, EntryFlags); 3] {
+    let bounds =
+        |start: &u8, end: &u8| (start as *const u8 as VirtAddr, end as *const u8 as VirtAddr);
+
+    let (text_start, text_end) = unsafe { bounds(&__text_start, &__text_end) };
+    let (rodata_start, rodata_end) = unsafe { bounds(&__rodata_start, &__rodata_end) };
+    let (data_start, data_end) = unsafe { bounds(&__data_start, &__data_end) };
+
+    [
+        (".text", text_start, text_end, EntryFlags::PRESENT),
+        (
+            ".rodata",
+            rodata_start,
+            rodata_end,
+            EntryFlags::PRESENT | EntryFlags::NO_EXECUTE,
+        ),
+        (
+            ".data",
+            data_start,
+            data_end,
+            EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+        ),
+    ]
+}
+
+/// remaps the kernel sections with the strictest flags they can have (W^X)
+/// the bootloader is supposed to do this already but we don't want to depend on it
+fn protect_kernel_sections(page_table: &mut PageTable) {
+    for (name, start, end, flags) in kernel_sections() {
+        let iter = IterPage {
+            start: Page::containing_address(start),
+            end: Page::containing_address(end + PAGE_SIZE - 1),
+        };
+
+        for page in iter {
+            if !page_table.set_flags(page, flags) {
+                debug!(
+                    PageTable,
+                    "couldn't protect {} page {:#x}", name, page.start_address
+                );
+            }
+        }
+    }
+}
+
+/// writes a range of pages mapped with `flags` (or unmapped if None) to `results` as a line of memmap
+fn write_range(results: &mut String, start: VirtAddr, end: VirtAddr, flags: Option<EntryFlags>) {
+    let perms = match flags {
+        None => "unmapped",
+        Some(flags) => match (
+            flags.contains(EntryFlags::WRITABLE),
+            flags.contains(EntryFlags::NO_EXECUTE),
+        ) {
+            (false, false) => "r-x",
+            (false, true) => "r--",
+            (true, true) => "rw-",
+            (true, false) => "rwx",
+        },
+    };
+
+    _ = writeln!(results, "  {:#x}..{:#x} {}", start, end, perms);
+}
+
+/// generates the data of proc:/memmap, the kernel sections and the permissions their pages are
+/// actually mapped with so that W^X regressions are visible
+pub fn memmap_info() -> String {
+    let page_table = unsafe { super::current_root_table() };
+    let mut results = String::new();
+
+    for (name, start, end, _) in kernel_sections() {
+        _ = writeln!(results, "{}: {:#x}..{:#x}", name, start, end);
+
+        let iter = IterPage {
+            start: Page::containing_address(start),
+            end: Page::containing_address(end + PAGE_SIZE - 1),
+        };
+
+        // pages next to each other with the same permissions are merged into a single range
+        let mut range: Option<(VirtAddr, VirtAddr, Option<EntryFlags>)> = None;
+        for page in iter {
+            let flags = page_table
+                .flags_of(page)
+                .map(|flags| flags & (EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE));
+            let page_end = page.start_address + PAGE_SIZE;
+
+            match &mut range {
+                Some((_, end, current)) if current.map(|f| f.bits()) == flags.map(|f| f.bits()) => {
+                    *end = page_end
+                }
+                _ => {
+                    if let Some((start, end, flags)) = range {
+                        write_range(&mut results, start, end, flags);
+                    }
+                    range = Some((page.start_address, page_end, flags));
+                }
+            }
+        }
+
+        if let Some((start, end, flags)) = range {
+            write_range(&mut results, start, end, flags);
+        }
+    }
+
+    if !crate::arch::nx_enabled() {
+        _ = writeln!(results, "NX is unsupported, every page is executable");
+    }
+    results
+}
+
 pub fn init_page_table() {
     debug!(PageTable, "intializing root page table ... ");
     let previous_table = unsafe { super::current_root_table() };
     let table = create_root_page_table().unwrap();
     set_current_page_table(table);
+    protect_kernel_sections(unsafe { super::current_root_table() });
     // de-allocating the previous root table
     let virt_addr = previous_table as *mut _ as usize;
     let frame = Frame::containing_address(virt_addr - limine::get_phy_offset());
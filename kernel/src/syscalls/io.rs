@@ -1,6 +1,15 @@
+use alloc::vec::Vec;
+
 use crate::{
-    drivers::vfs::{self, expose::open, FSError},
-    threading,
+    arch::usercopy::UserAccess,
+    drivers::vfs::{
+        self,
+        evq::{Event, EventQueueOp, InterestFlags},
+        expose::{open_with, PollFd, MAX_NAME_LEN, MAX_XATTR_SIZE},
+        flock::{LockOp, LockRange},
+        FSError, OpenFlags, PollEvents,
+    },
+    threading::{self, processes::Capabilities},
     utils::{
         errors::ErrorStatus,
         ffi::{Optional, RequiredMut, Slice, SliceMut},
@@ -8,13 +17,22 @@ use crate::{
 };
 
 #[no_mangle]
-extern "C" fn sysopen(path_ptr: *const u8, len: usize, dest_fd: Optional<usize>) -> ErrorStatus {
-    let path = Slice::new(path_ptr, len)?.into_str();
+extern "C" fn sysopen(
+    path_ptr: *const u8,
+    len: usize,
+    flags: u8,
+    dest_fd: Optional<usize>,
+) -> ErrorStatus {
+    let dest_fd = dest_fd.into_option()?;
+    let path = Slice::new(path_ptr, len)?.into_string()?;
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return ErrorStatus::InvaildSyscall;
+    };
 
-    match open(path) {
+    match open_with(&path, flags) {
         Ok(fd) => {
-            if let Some(dest_fd) = dest_fd.into_option() {
-                *dest_fd = fd;
+            if let Some(dest_fd) = dest_fd {
+                dest_fd.write(fd);
             }
             ErrorStatus::None
         }
@@ -24,16 +42,15 @@ extern "C" fn sysopen(path_ptr: *const u8, len: usize, dest_fd: Optional<usize>)
 
 #[no_mangle]
 extern "C" fn syswrite(fd: usize, ptr: *const u8, len: usize) -> ErrorStatus {
-    let slice = Slice::new(ptr, len)?.into_slice();
-    while let Err(err) = vfs::expose::write(fd, slice) {
-        match err {
-            FSError::ResourceBusy => {
-                threading::expose::thread_yeild();
-            }
-            _ => return err.into(),
+    let slice = Slice::new_validated(ptr, len)?;
+    loop {
+        let results = vfs::expose::write(fd, slice.into_slice(&UserAccess::open()));
+        match results {
+            Err(FSError::ResourceBusy) => threading::expose::thread_yeild(),
+            Err(err) => return err.into(),
+            Ok(_) => return ErrorStatus::None,
         }
     }
-    ErrorStatus::None
 }
 
 #[no_mangle]
@@ -43,15 +60,117 @@ extern "C" fn sysread(
     len: usize,
     dest_read: Optional<usize>,
 ) -> ErrorStatus {
-    let slice = SliceMut::new(ptr, len)?.into_slice();
+    let dest_read = dest_read.into_option()?;
+
+    let slice = SliceMut::new_validated(ptr, len)?;
+
+    loop {
+        let results = vfs::expose::read(fd, slice.into_slice(&UserAccess::open()));
+        match results {
+            Err(FSError::ResourceBusy) => threading::expose::thread_yeild(),
+            Err(err) => return err.into(),
+            Ok(bytes_read) => {
+                if let Some(dest_read) = dest_read {
+                    dest_read.write(bytes_read);
+                }
+                return ErrorStatus::None;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysreadv(
+    fd: usize,
+    iovecs_ptr: *const SliceMut<u8>,
+    iovecs_len: usize,
+    dest_read: Optional<usize>,
+) -> ErrorStatus {
+    let dest_read = dest_read.into_option()?;
+    let iovecs = Slice::new_validated(iovecs_ptr, iovecs_len)?.copy_to_vec()?;
+
+    let mut iovecs_validated = Vec::with_capacity(iovecs.len());
+    for iovec in iovecs {
+        iovecs_validated.push(SliceMut::new_validated(iovec.ptr(), iovec.len())?);
+    }
 
     loop {
-        match vfs::expose::read(fd, slice) {
+        let results = {
+            let access = UserAccess::open();
+            let mut buffers = iovecs_validated
+                .iter()
+                .map(|iovec| iovec.into_slice(&access))
+                .collect::<Vec<_>>();
+            vfs::expose::readv(fd, &mut buffers)
+        };
+
+        match results {
             Err(FSError::ResourceBusy) => threading::expose::thread_yeild(),
             Err(err) => return err.into(),
             Ok(bytes_read) => {
-                if let Some(dest_read) = dest_read.into_option() {
-                    *dest_read = bytes_read;
+                if let Some(dest_read) = dest_read {
+                    dest_read.write(bytes_read);
+                }
+                return ErrorStatus::None;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn syswritev(
+    fd: usize,
+    iovecs_ptr: *const Slice<u8>,
+    iovecs_len: usize,
+    dest_wrote: Optional<usize>,
+) -> ErrorStatus {
+    let dest_wrote = dest_wrote.into_option()?;
+    let iovecs = Slice::new_validated(iovecs_ptr, iovecs_len)?.copy_to_vec()?;
+
+    let mut iovecs_validated = Vec::with_capacity(iovecs.len());
+    for iovec in iovecs {
+        iovecs_validated.push(Slice::new_validated(iovec.ptr(), iovec.len())?);
+    }
+
+    loop {
+        let results = {
+            let access = UserAccess::open();
+            let buffers = iovecs_validated
+                .iter()
+                .map(|iovec| iovec.into_slice(&access))
+                .collect::<Vec<_>>();
+            vfs::expose::writev(fd, &buffers)
+        };
+
+        match results {
+            Err(FSError::ResourceBusy) => threading::expose::thread_yeild(),
+            Err(err) => return err.into(),
+            Ok(bytes_wrote) => {
+                if let Some(dest_wrote) = dest_wrote {
+                    dest_wrote.write(bytes_wrote);
+                }
+                return ErrorStatus::None;
+            }
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn syscopy_file_range(
+    src_fd: usize,
+    dest_fd: usize,
+    len: usize,
+    dest_copied: Optional<usize>,
+) -> ErrorStatus {
+    let dest_copied = dest_copied.into_option()?;
+
+    loop {
+        match vfs::expose::copy_file_range(src_fd, dest_fd, len) {
+            Err(FSError::ResourceBusy) => threading::expose::thread_yeild(),
+            Err(err) => return err.into(),
+            Ok(copied) => {
+                if let Some(dest_copied) = dest_copied {
+                    dest_copied.write(copied);
                 }
                 return ErrorStatus::None;
             }
@@ -59,6 +178,85 @@ extern "C" fn sysread(
     }
 }
 
+#[no_mangle]
+extern "C" fn syspoll(
+    fds_ptr: *mut PollFd,
+    fds_len: usize,
+    timeout: usize,
+    dest_ready: Optional<usize>,
+) -> ErrorStatus {
+    let dest_ready = dest_ready.into_option()?;
+    let fds = SliceMut::new_validated(fds_ptr, fds_len)?;
+
+    let results = vfs::expose::poll(fds.into_slice(&UserAccess::open()), timeout);
+    match results {
+        Ok(ready) => {
+            if let Some(dest_ready) = dest_ready {
+                dest_ready.write(ready);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err.into(),
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysevq_create(dest_ri: Optional<usize>) -> ErrorStatus {
+    let dest_ri = dest_ri.into_option()?;
+    let ri = vfs::expose::evq_create();
+    if let Some(dest_ri) = dest_ri {
+        dest_ri.write(ri);
+    }
+    ErrorStatus::None
+}
+
+#[no_mangle]
+extern "C" fn sysevq_ctl(
+    evq_ri: usize,
+    op: usize,
+    ri: usize,
+    events: u8,
+    flags: u8,
+    data: usize,
+) -> ErrorStatus {
+    let (Ok(op), Some(events), Some(flags)) = (
+        EventQueueOp::try_from(op),
+        PollEvents::from_bits(events),
+        InterestFlags::from_bits(flags),
+    ) else {
+        return ErrorStatus::InvaildSyscall;
+    };
+
+    if let Err(err) = vfs::expose::evq_ctl(evq_ri, op, ri, events, flags, data) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysevq_wait(
+    evq_ri: usize,
+    events_ptr: *mut Event,
+    events_len: usize,
+    timeout: usize,
+    dest_count: Optional<usize>,
+) -> ErrorStatus {
+    let dest_count = dest_count.into_option()?;
+    let events = SliceMut::new_validated(events_ptr, events_len)?;
+
+    let results = vfs::expose::evq_wait(evq_ri, events.into_slice(&UserAccess::open()), timeout);
+    match results {
+        Ok(count) => {
+            if let Some(dest_count) = dest_count {
+                dest_count.write(count);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err.into(),
+    }
+}
+
 #[no_mangle]
 extern "C" fn sysclose(fd: usize) -> ErrorStatus {
     if let Err(err) = vfs::expose::close(fd) {
@@ -68,11 +266,41 @@ extern "C" fn sysclose(fd: usize) -> ErrorStatus {
     }
 }
 
+#[no_mangle]
+extern "C" fn sysdup(ri: usize, dest_ri: Optional<usize>) -> ErrorStatus {
+    let dest_ri = dest_ri.into_option()?;
+
+    match vfs::expose::dup(ri) {
+        Ok(new_ri) => {
+            if let Some(dest_ri) = dest_ri {
+                dest_ri.write(new_ri);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err.into(),
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysdup2(ri: usize, dest_ri: usize, new_ri: Optional<usize>) -> ErrorStatus {
+    let new_ri = new_ri.into_option()?;
+
+    match vfs::expose::dup2(ri, dest_ri) {
+        Ok(ri) => {
+            if let Some(new_ri) = new_ri {
+                new_ri.write(ri);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err.into(),
+    }
+}
+
 #[no_mangle]
 extern "C" fn syscreate(path_ptr: *const u8, path_len: usize) -> ErrorStatus {
-    let path = Slice::new(path_ptr, path_len)?.into_str();
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
 
-    if let Err(err) = vfs::expose::create(path) {
+    if let Err(err) = vfs::expose::create(&path) {
         err.into()
     } else {
         ErrorStatus::None
@@ -81,23 +309,268 @@ extern "C" fn syscreate(path_ptr: *const u8, path_len: usize) -> ErrorStatus {
 
 #[no_mangle]
 extern "C" fn syscreatedir(path_ptr: *const u8, path_len: usize) -> ErrorStatus {
-    let path = Slice::new(path_ptr, path_len)?.into_str();
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::createdir(&path) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// removes a file or an empty directory
+#[no_mangle]
+extern "C" fn sysremove(path_ptr: *const u8, path_len: usize) -> ErrorStatus {
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::remove(&path) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// moves `old` to `new` on the same drive, `new` must not exist
+#[no_mangle]
+extern "C" fn sysrename(
+    old_ptr: *const u8,
+    old_len: usize,
+    new_ptr: *const u8,
+    new_len: usize,
+) -> ErrorStatus {
+    let old = Slice::new(old_ptr, old_len)?.into_string()?;
+    let new = Slice::new(new_ptr, new_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::rename(&old, &new) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
 
-    if let Err(err) = vfs::expose::createdir(path) {
+/// binds `drive` to the directory `target` in the caller's mount namespace, requires
+/// `Capabilities::MOUNT`, see `vfs::namespace`
+#[no_mangle]
+extern "C" fn sysbind(
+    drive_ptr: *const u8,
+    drive_len: usize,
+    target_ptr: *const u8,
+    target_len: usize,
+) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::MOUNT) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let drive = Slice::new(drive_ptr, drive_len)?.into_string()?;
+    let target = Slice::new(target_ptr, target_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::bind(&drive, &target) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// removes the bind of `drive` from the caller's mount namespace, requires `Capabilities::MOUNT`
+#[no_mangle]
+extern "C" fn sysunbind(drive_ptr: *const u8, drive_len: usize) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::MOUNT) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let drive = Slice::new(drive_ptr, drive_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::unbind(&drive) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// exposes the directory `src` at `dst` for every process, requires `Capabilities::MOUNT`, see
+/// `VFS::bind`
+#[no_mangle]
+extern "C" fn sysmount_bind(
+    src_ptr: *const u8,
+    src_len: usize,
+    dst_ptr: *const u8,
+    dst_len: usize,
+) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::MOUNT) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let src = Slice::new(src_ptr, src_len)?.into_string()?;
+    let dst = Slice::new(dst_ptr, dst_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::mount_bind(&src, &dst) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// removes the bind mount at `dst`, requires `Capabilities::MOUNT`
+#[no_mangle]
+extern "C" fn sysumount(dst_ptr: *const u8, dst_len: usize) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::MOUNT) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let dst = Slice::new(dst_ptr, dst_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::umount(&dst) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// takes or releases an advisory lock on `len` bytes of the file `ri` starting at `start`, a `len` of
+/// 0 locks up to the end of the file, waits for conflicting locks to be released unless `nonblocking`
+/// see `vfs::flock`
+#[no_mangle]
+extern "C" fn sysflock(
+    ri: usize,
+    op: usize,
+    start: usize,
+    len: usize,
+    nonblocking: bool,
+) -> ErrorStatus {
+    let Ok(op) = LockOp::try_from(op) else {
+        return ErrorStatus::InvaildSyscall;
+    };
+    let range = LockRange::new(start, len);
+
+    loop {
+        match vfs::expose::flock(ri, op, range) {
+            Err(FSError::ResourceBusy) if !nonblocking => threading::expose::thread_yeild(),
+            Err(err) => return err.into(),
+            Ok(()) => return ErrorStatus::None,
+        }
+    }
+}
+
+/// gives the unnamed file `ri` opened with `OpenFlags::TMPFILE` the name `path`, replacing the file
+/// at `path` if there is one
+#[no_mangle]
+extern "C" fn syslink(ri: usize, path_ptr: *const u8, path_len: usize) -> ErrorStatus {
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
+
+    if let Err(err) = vfs::expose::link(ri, &path) {
         err.into()
     } else {
         ErrorStatus::None
     }
 }
 
+/// copies the value of the extended attribute `name` of `ri` to `value_ptr`, `dest_len` is set to the
+/// length of the whole value which doesn't have to fit, see `vfs::FS::get_xattr`
 #[no_mangle]
-extern "C" fn sysdiriter_open(dir_ri: usize, dest_diriter: *mut usize) -> ErrorStatus {
+extern "C" fn sysget_xattr(
+    ri: usize,
+    name_ptr: *const u8,
+    name_len: usize,
+    value_ptr: *mut u8,
+    value_len: usize,
+    dest_len: Optional<usize>,
+) -> ErrorStatus {
+    let dest_len = dest_len.into_option()?;
+    let name = Slice::new(name_ptr, name_len)?.into_string_max(MAX_NAME_LEN)?;
+
+    let value = match vfs::expose::get_xattr(ri, &name) {
+        Ok(value) => value,
+        Err(err) => return err.into(),
+    };
+
+    if let Some(dest_len) = dest_len {
+        dest_len.write(value.len());
+    }
+
+    if value.len() > value_len {
+        return ErrorStatus::ResultOutOfRange;
+    }
+    SliceMut::new(value_ptr, value_len)?.copy_from(&value)?;
+
+    ErrorStatus::None
+}
+
+#[no_mangle]
+extern "C" fn sysset_xattr(
+    ri: usize,
+    name_ptr: *const u8,
+    name_len: usize,
+    value_ptr: *const u8,
+    value_len: usize,
+) -> ErrorStatus {
+    let name = Slice::new(name_ptr, name_len)?.into_string_max(MAX_NAME_LEN)?;
+    if value_len > MAX_XATTR_SIZE {
+        return ErrorStatus::StrTooLong;
+    }
+    let value = Slice::new(value_ptr, value_len)?.copy_to_vec()?;
+
+    if let Err(err) = vfs::expose::set_xattr(ri, &name, &value) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+/// copies the names of the extended attributes of `ri` each followed by a null byte to `names_ptr`,
+/// `dest_len` is set to the length of all of them which doesn't have to fit
+#[no_mangle]
+extern "C" fn syslist_xattrs(
+    ri: usize,
+    names_ptr: *mut u8,
+    names_len: usize,
+    dest_len: Optional<usize>,
+) -> ErrorStatus {
+    let dest_len = dest_len.into_option()?;
+
+    let names = match vfs::expose::list_xattrs(ri) {
+        Ok(names) => names,
+        Err(err) => return err.into(),
+    };
+
+    let mut results = Vec::new();
+    for name in names {
+        results.extend_from_slice(name.as_bytes());
+        results.push(0);
+    }
+
+    if let Some(dest_len) = dest_len {
+        dest_len.write(results.len());
+    }
+
+    if results.len() > names_len {
+        return ErrorStatus::ResultOutOfRange;
+    }
+    SliceMut::new(names_ptr, names_len)?.copy_from(&results)?;
+
+    ErrorStatus::None
+}
+
+#[no_mangle]
+extern "C" fn sysremove_xattr(ri: usize, name_ptr: *const u8, name_len: usize) -> ErrorStatus {
+    let name = Slice::new(name_ptr, name_len)?.into_string_max(MAX_NAME_LEN)?;
+
+    if let Err(err) = vfs::expose::remove_xattr(ri, &name) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysdiriter_open(dir_ri: usize, dest_diriter: RequiredMut<usize>) -> ErrorStatus {
+    let dest_diriter = dest_diriter.get()?;
+
     match vfs::expose::diriter_open(dir_ri) {
         Err(err) => err.into(),
-        Ok(ri) => unsafe {
-            *dest_diriter = ri;
+        Ok(ri) => {
+            dest_diriter.write(ri);
             ErrorStatus::None
-        },
+        }
     }
 }
 
@@ -114,15 +587,96 @@ extern "C" fn sysdiriter_next(
     diriter_ri: usize,
     direntry: RequiredMut<vfs::expose::DirEntry>,
 ) -> ErrorStatus {
-    match vfs::expose::diriter_next(diriter_ri, direntry.get()?) {
+    let direntry = direntry.get()?;
+
+    let results = vfs::expose::diriter_next(diriter_ri, direntry.get_mut(&UserAccess::open()));
+    match results {
         Err(err) => err.into(),
         Ok(()) => ErrorStatus::None,
     }
 }
 
+/// fills `entries_ptr` with as many entries of the directory `ri` as fit, `dest_count` is set to the
+/// amount filled which is 0 at the end of the directory
+#[no_mangle]
+extern "C" fn sysreaddir(
+    ri: usize,
+    entries_ptr: *mut vfs::expose::DirEntry,
+    entries_len: usize,
+    dest_count: Optional<usize>,
+) -> ErrorStatus {
+    let dest_count = dest_count.into_option()?;
+    let entries = SliceMut::new_validated(entries_ptr, entries_len)?;
+
+    let results = vfs::expose::readdir(ri, entries.into_slice(&UserAccess::open()));
+    match results {
+        Ok(count) => {
+            if let Some(dest_count) = dest_count {
+                dest_count.write(count);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err.into(),
+    }
+}
+
 #[no_mangle]
 extern "C" fn sysfstat(ri: usize, direntry: RequiredMut<vfs::expose::DirEntry>) -> ErrorStatus {
-    if let Err(err) = vfs::expose::fstat(ri, direntry.get()?) {
+    let direntry = direntry.get()?;
+
+    if let Err(err) = vfs::expose::fstat(ri, direntry.get_mut(&UserAccess::open())) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+#[no_mangle]
+/// sends a command that takes a single usize and doesn't return anything, see `sysctlbuf`
+extern "C" fn sysctl(ri: usize, cmd: usize, arg: usize) -> ErrorStatus {
+    let Ok(cmd) = u16::try_from(cmd) else {
+        return ErrorStatus::OperationNotSupported;
+    };
+
+    if let Err(err) = vfs::expose::ctl(ri, cmd, &arg.to_ne_bytes(), &mut []) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysctlbuf(
+    ri: usize,
+    cmd: usize,
+    input_ptr: *const u8,
+    input_len: usize,
+    output_ptr: *mut u8,
+    output_len: usize,
+) -> ErrorStatus {
+    let Ok(cmd) = u16::try_from(cmd) else {
+        return ErrorStatus::OperationNotSupported;
+    };
+
+    let input = Slice::new_validated(input_ptr, input_len)?;
+    let output = SliceMut::new_validated(output_ptr, output_len)?;
+
+    let access = UserAccess::open();
+    if let Err(err) = vfs::expose::ctl(
+        ri,
+        cmd,
+        input.into_slice(&access),
+        output.into_slice(&access),
+    ) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
+
+#[no_mangle]
+extern "C" fn syssync() -> ErrorStatus {
+    if let Err(err) = vfs::expose::sync() {
         err.into()
     } else {
         ErrorStatus::None
@@ -6,4 +6,5 @@
 mod io;
 mod power;
 mod processes;
+mod surfaces;
 mod utils;
@@ -1,16 +1,92 @@
 use crate::{
-    arch::power::{reboot, shutdown},
+    arch::{
+        cpu,
+        power::{reboot, shutdown},
+    },
+    devices,
+    drivers::{poll, vfs},
+    threading::{expose::has_capabilities, processes::Capabilities},
     utils::errors::ErrorStatus,
+    warn,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PowerAction {
+    Shutdown,
+    Reboot,
+}
+
+impl TryFrom<usize> for PowerAction {
+    type Error = ErrorStatus;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Shutdown),
+            1 => Ok(Self::Reboot),
+            _ => Err(ErrorStatus::NotSupported),
+        }
+    }
+}
+
+/// syncs the filesystems, quiesces the devices and then peforms `action`
+/// only returns if the action failed
+fn power(action: PowerAction) -> ErrorStatus {
+    if !has_capabilities(Capabilities::POWER) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    // nothing can be done about it at this point other then logging it
+    if let Err(err) = vfs::expose::sync() {
+        warn!(PowerAction, "failed to sync filesystems: {:?}", err);
+    }
+    poll::stop();
+    devices::quiesce();
+
+    match action {
+        PowerAction::Shutdown => shutdown(),
+        PowerAction::Reboot => reboot(),
+    }
+
+    ErrorStatus::Generic
+}
+
+#[no_mangle]
+extern "C" fn syspower(action: usize) -> ErrorStatus {
+    match PowerAction::try_from(action) {
+        Ok(action) => power(action),
+        Err(err) => err,
+    }
+}
+
 #[no_mangle]
 extern "C" fn sysshutdown() -> ErrorStatus {
-    shutdown();
-    ErrorStatus::None
+    power(PowerAction::Shutdown)
 }
 
 #[no_mangle]
 extern "C" fn sysreboot() -> ErrorStatus {
-    reboot();
-    ErrorStatus::None
+    power(PowerAction::Reboot)
+}
+
+/// takes cpu `cpu` offline if `online` is 0 or brings it online if `online` is 1, anything else
+/// returns ErrorStatus::NotSupported
+/// the kernel can neither start APs nor migrate processes yet so only requests that don't change the
+/// state of the cpu succeed, see `cpu::offline` and `cpu::online`
+#[no_mangle]
+extern "C" fn syscpuctl(cpu: usize, online: usize) -> ErrorStatus {
+    if !has_capabilities(Capabilities::POWER) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let results = match online {
+        0 => cpu::offline(cpu),
+        1 => cpu::online(cpu),
+        _ => return ErrorStatus::NotSupported,
+    };
+
+    match results {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
 }
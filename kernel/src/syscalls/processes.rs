@@ -1,8 +1,17 @@
+use alloc::{string::String, vec::Vec};
+
 use crate::{
-    threading::{self, expose::SpawnFlags, processes::ProcessInfo},
+    arch::{threading::Registers, usercopy::UserAccess},
+    debug,
+    threading::{
+        self,
+        expose::SpawnFlags,
+        processes::{ContextPriority, ExitStatus, Process, ProcessInfo},
+    },
     utils::{
-        errors::ErrorStatus,
-        ffi::{Optional, Required, Slice, SliceMut},
+        errors::{ErrorStatus, ErrorStatusResult},
+        ffi::{Optional, Required, RequiredMut, Slice, SliceMut},
+        types::MAX_PROCESS_NAME_LEN,
     },
 };
 
@@ -11,17 +20,90 @@ extern "C" fn syswait(pid: usize) -> usize {
     threading::expose::wait(pid)
 }
 
+/// waits for `pid` to exit and writes how it exited to `dest`, unlike `syswait` it tells exit codes
+/// apart from kills and fails with `ErrorStatus::InvaildPid` if `pid` doesn't exist
+#[no_mangle]
+extern "C" fn syswait_status(pid: usize, dest: RequiredMut<ExitStatus>) -> ErrorStatus {
+    let dest = dest.get()?;
+    match threading::expose::wait_status(pid) {
+        Ok(status) => {
+            dest.write(status);
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
+/// the longest message `sysreport_abort` keeps, longer ones are truncated
+const MAX_ABORT_MSG_LEN: usize = 1024;
+
+/// reports why the caller is about to exit, usually a panic message, it's logged when the caller
+/// exits and kept in proc:/<pid>/abort until it's waited on
+/// messages longer than `MAX_ABORT_MSG_LEN` are truncated instead of rejected so the report isn't lost
+#[no_mangle]
+extern "C" fn sysreport_abort(msg_ptr: *const u8, msg_len: usize) -> ErrorStatus {
+    let bytes = Slice::new(msg_ptr, msg_len.min(MAX_ABORT_MSG_LEN))?.copy_to_vec()?;
+    threading::expose::report_abort(String::from_utf8_lossy(&bytes).into_owned());
+    ErrorStatus::None
+}
+
+/// kills `pid` which has to be a descendant of the caller, it exits with 1
+#[no_mangle]
+extern "C" fn syspkill(pid: usize) -> ErrorStatus {
+    // a process can't kill itself in the middle of a syscall, it should exit instead
+    if pid == threading::expose::getpid() || !threading::expose::pexists(pid) {
+        return ErrorStatus::InvaildPid;
+    }
+
+    match threading::expose::pkill(pid) {
+        Ok(()) => ErrorStatus::None,
+        Err(()) => ErrorStatus::MissingPermissions,
+    }
+}
+
+/// waits for any child to exit, see `threading::expose::wait_any`
+#[no_mangle]
+extern "C" fn syswait_any(
+    nonblocking: bool,
+    dest_pid: Optional<usize>,
+    dest_status: Optional<usize>,
+) -> ErrorStatus {
+    let dest_pid = dest_pid.into_option()?;
+    let dest_status = dest_status.into_option()?;
+
+    match threading::expose::wait_any(nonblocking) {
+        Ok((pid, status)) => {
+            if let Some(dest_pid) = dest_pid {
+                dest_pid.write(pid);
+            }
+            if let Some(dest_status) = dest_status {
+                dest_status.write(status);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct SpawnConfig {
     pub name: Slice<u8>,
     pub argv: SliceMut<Slice<u8>>,
     pub flags: SpawnFlags,
+    /// a `ContextPriority`
+    pub priority: u8,
 }
 
 impl SpawnConfig {
-    pub fn as_rust(&self) -> (&str, &[&str], SpawnFlags) {
-        (self.name.into_str(), self.argv.into_str_slice(), self.flags)
+    /// copies the config into kernel memory
+    pub fn as_rust(&self) -> ErrorStatusResult<(String, Vec<String>, SpawnFlags, ContextPriority)> {
+        let Ok(priority) = ContextPriority::try_from(self.priority) else {
+            return ErrorStatusResult::err(ErrorStatus::NotSupported);
+        };
+        let name = self.name.into_string_max(MAX_PROCESS_NAME_LEN)?;
+        let argv = self.argv.into_strings()?;
+        ErrorStatusResult::ok((name, argv, self.flags, priority))
     }
 }
 
@@ -33,14 +115,27 @@ extern "C" fn sysspawn(
     config: Required<SpawnConfig>,
     dest_pid: Optional<usize>,
 ) -> ErrorStatus {
+    let dest_pid = dest_pid.into_option()?;
     let config = config.get()?;
-    let (name, argv, flags) = config.as_rust();
-    let elf_bytes = Slice::new(elf_ptr, elf_len)?.into_slice();
-    match threading::expose::spawn(name, elf_bytes, argv, flags) {
-        Err(err) => err.into(),
+    let (name, argv, flags, priority) = config.as_rust()?;
+    let argv = argv.iter().map(String::as_str).collect::<Vec<_>>();
+    let elf_bytes = Slice::new_validated(elf_ptr, elf_len)?;
+
+    let results = threading::expose::spawn(
+        &name,
+        elf_bytes.into_slice(&UserAccess::open()),
+        &argv,
+        flags,
+        priority,
+    );
+    match results {
+        Err(err) => {
+            debug!(Process, "spawn of {name} failed: {err}");
+            err.into()
+        }
         Ok(pid) => {
-            if let Some(dest_pid) = dest_pid.into_option() {
-                *dest_pid = pid
+            if let Some(dest_pid) = dest_pid {
+                dest_pid.write(pid);
             }
             ErrorStatus::None
         }
@@ -54,15 +149,20 @@ extern "C" fn syspspawn(
     config: Required<SpawnConfig>,
     dest_pid: Optional<usize>,
 ) -> ErrorStatus {
+    let dest_pid = dest_pid.into_option()?;
     let config = config.get()?;
-    let path = Slice::new(path_ptr, path_len)?.into_str();
-    let (name, argv, flags) = config.as_rust();
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
+    let (name, argv, flags, priority) = config.as_rust()?;
+    let argv = argv.iter().map(String::as_str).collect::<Vec<_>>();
 
-    match threading::expose::pspawn(name, path, argv, flags) {
-        Err(err) => err.into(),
+    match threading::expose::pspawn(&name, &path, &argv, flags, priority) {
+        Err(err) => {
+            debug!(Process, "spawn of {name} from {path} failed: {err}");
+            err.into()
+        }
         Ok(pid) => {
-            if let Some(dest_pid) = dest_pid.into_option() {
-                *dest_pid = pid;
+            if let Some(dest_pid) = dest_pid {
+                dest_pid.write(pid);
             }
             ErrorStatus::None
         }
@@ -71,11 +171,121 @@ extern "C" fn syspspawn(
 
 #[no_mangle]
 extern "C" fn syspcollect(ptr: *mut ProcessInfo, len: usize) -> ErrorStatus {
-    let slice = SliceMut::new(ptr, len)?.into_slice();
+    let slice = SliceMut::new_validated(ptr, len)?;
 
-    if let Err(()) = threading::expose::pcollect(slice) {
+    if let Err(()) = threading::expose::pcollect(slice.into_slice(&UserAccess::open())) {
         ErrorStatus::Generic
     } else {
         ErrorStatus::None
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PtraceOp {
+    ReadMemory,
+    WriteMemory,
+    Suspend,
+    Resume,
+    GetRegisters,
+}
+
+impl TryFrom<usize> for PtraceOp {
+    type Error = ErrorStatus;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::ReadMemory),
+            1 => Ok(Self::WriteMemory),
+            2 => Ok(Self::Suspend),
+            3 => Ok(Self::Resume),
+            4 => Ok(Self::GetRegisters),
+            _ => Err(ErrorStatus::NotSupported),
+        }
+    }
+}
+
+/// lets a process with the DEBUG capability inspect and control another userspace process
+/// `addr` is an address in `pid`'s address space, `ptr` and `len` are a buffer in the caller's
+/// for GetRegisters `ptr` points to a `Registers` and `addr` and `len` are ignored
+#[no_mangle]
+extern "C" fn sysptrace(
+    op: usize,
+    pid: usize,
+    addr: usize,
+    ptr: *mut u8,
+    len: usize,
+) -> ErrorStatus {
+    let op = match PtraceOp::try_from(op) {
+        Ok(op) => op,
+        Err(err) => return err,
+    };
+
+    let results = match op {
+        PtraceOp::ReadMemory => {
+            let buffer = SliceMut::new_validated(ptr, len)?;
+            threading::expose::debug_read(pid, addr, buffer.into_slice(&UserAccess::open()))
+        }
+        PtraceOp::WriteMemory => {
+            let buffer = Slice::new_validated(ptr, len)?;
+            threading::expose::debug_write(pid, addr, buffer.into_slice(&UserAccess::open()))
+        }
+        PtraceOp::Suspend => threading::expose::debug_suspend(pid),
+        PtraceOp::Resume => threading::expose::debug_resume(pid),
+        PtraceOp::GetRegisters => {
+            let dest = SliceMut::new_validated(ptr as *mut Registers, 1)?;
+            if dest.ptr().is_null() {
+                return ErrorStatus::InvaildPtr;
+            }
+
+            threading::expose::debug_registers(pid)
+                .map(|registers| dest.into_slice(&UserAccess::open())[0] = registers)
+        }
+    };
+
+    match results {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysbacktrace(
+    pid: usize,
+    frames_ptr: *mut usize,
+    frames_len: usize,
+    dest_count: Optional<usize>,
+) -> ErrorStatus {
+    let dest_count = dest_count.into_option()?;
+    let frames = SliceMut::new_validated(frames_ptr, frames_len)?;
+
+    let results = threading::expose::backtrace(pid, frames.into_slice(&UserAccess::open()));
+    match results {
+        Ok(count) => {
+            if let Some(dest_count) = dest_count {
+                dest_count.write(count);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysgetuid(dest: RequiredMut<u32>) -> ErrorStatus {
+    dest.get()?.write(threading::expose::getuid());
+    ErrorStatus::None
+}
+
+/// see `threading::expose::setuid`
+#[no_mangle]
+extern "C" fn syssetuid(uid: usize) -> ErrorStatus {
+    let Ok(uid) = u32::try_from(uid) else {
+        return ErrorStatus::ArgumentOutOfDomain;
+    };
+
+    match threading::expose::setuid(uid) {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    drivers::surface::{self, SurfaceInfo},
+    utils::{
+        errors::ErrorStatus,
+        ffi::{Optional, RequiredMut},
+    },
+};
+
+#[no_mangle]
+extern "C" fn syssurface_create(
+    width: usize,
+    height: usize,
+    dest_ri: Optional<usize>,
+) -> ErrorStatus {
+    let dest_ri = dest_ri.into_option()?;
+
+    match surface::create(width, height) {
+        Ok(ri) => {
+            if let Some(dest_ri) = dest_ri {
+                dest_ri.write(ri);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn syssurface_open(id: usize, dest_ri: Optional<usize>) -> ErrorStatus {
+    let dest_ri = dest_ri.into_option()?;
+
+    match surface::open(id) {
+        Ok(ri) => {
+            if let Some(dest_ri) = dest_ri {
+                dest_ri.write(ri);
+            }
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn syssurface_map(ri: usize, dest_info: RequiredMut<SurfaceInfo>) -> ErrorStatus {
+    let dest_info = dest_info.get()?;
+
+    match surface::map(ri) {
+        Ok(info) => {
+            dest_info.write(info);
+            ErrorStatus::None
+        }
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn syssurface_flush(ri: usize) -> ErrorStatus {
+    match surface::flush(ri) {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn syssurface_present(ri: usize) -> ErrorStatus {
+    match surface::present(ri) {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
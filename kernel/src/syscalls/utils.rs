@@ -1,9 +1,11 @@
 use crate::{
-    threading,
+    arch::usercopy::UserAccess,
+    threading::{self, processes::Capabilities},
     utils::{
         self,
+        env::MAX_VAR_LEN,
         errors::ErrorStatus,
-        expose::SysInfo,
+        expose::{SysInfo, Utsname},
         ffi::{Optional, RequiredMut, Slice, SliceMut},
     },
 };
@@ -19,31 +21,48 @@ extern "C" fn sysyield() {
     threading::expose::thread_yeild()
 }
 
+/// sleeps for `ticks` scheduler ticks, 0 only yields
+#[no_mangle]
+extern "C" fn syssleep(ticks: usize) {
+    threading::expose::thread_sleep(ticks)
+}
+
 #[no_mangle]
 extern "C" fn syschdir(path_ptr: *const u8, path_len: usize) -> ErrorStatus {
-    let path = Slice::new(path_ptr, path_len)?.into_str();
+    let path = Slice::new(path_ptr, path_len)?.into_string()?;
+
+    if let Err(err) = threading::expose::chdir(&path) {
+        err.into()
+    } else {
+        ErrorStatus::None
+    }
+}
 
-    if let Err(err) = threading::expose::chdir(path) {
+/// changes the cwd to the directory opened as `ri`
+#[no_mangle]
+extern "C" fn sysfchdir(ri: usize) -> ErrorStatus {
+    if let Err(err) = threading::expose::fchdir(ri) {
         err.into()
     } else {
         ErrorStatus::None
     }
 }
 
+/// `dest_len` is set to the length of the cwd even if it doesn't fit in `len`, in which case nothing
+/// is copied and `ErrorStatus::ResultOutOfRange` is returned
 #[no_mangle]
 extern "C" fn sysgetcwd(path_ptr: *mut u8, len: usize, dest_len: Optional<usize>) -> ErrorStatus {
-    let path = SliceMut::new(path_ptr, len)?.into_slice();
+    let dest_len = dest_len.into_option()?;
     let got = threading::expose::getcwd().into_bytes();
 
-    if got.len() > len {
-        return ErrorStatus::Generic;
+    if let Some(dest_len) = dest_len {
+        dest_len.write(got.len());
     }
 
-    path[..got.len()].copy_from_slice(&got);
-
-    if let Some(dest_len) = dest_len.into_option() {
-        *dest_len = got.len();
+    if got.len() > len {
+        return ErrorStatus::ResultOutOfRange;
     }
+    SliceMut::new(path_ptr, len)?.copy_from(&got)?;
 
     ErrorStatus::None
 }
@@ -56,7 +75,75 @@ extern "C" fn syssbrk(amount: isize) -> *mut u8 {
 
 #[no_mangle]
 extern "C" fn sysinfo(ptr: RequiredMut<SysInfo>) -> ErrorStatus {
-    utils::expose::info(ptr.get()?);
+    utils::expose::info(ptr.get()?.get_mut(&UserAccess::open()));
 
     ErrorStatus::None
 }
+
+/// copies the value of `name` in the default environment to `value_ptr`
+/// `dest_len` is set to the length of the whole value even if it didn't fit
+#[no_mangle]
+extern "C" fn sysenv_get(
+    name_ptr: *const u8,
+    name_len: usize,
+    value_ptr: *mut u8,
+    value_len: usize,
+    dest_len: Optional<usize>,
+) -> ErrorStatus {
+    let dest_len = dest_len.into_option()?;
+    let name = Slice::new(name_ptr, name_len)?.into_string_max(MAX_VAR_LEN)?;
+
+    let Some(value) = utils::env::get(&name) else {
+        return ErrorStatus::NoSuchAFileOrDirectory;
+    };
+
+    let copied = value.len().min(value_len);
+    SliceMut::new(value_ptr, value_len)?.copy_from(&value.as_bytes()[..copied])?;
+
+    if let Some(dest_len) = dest_len {
+        dest_len.write(value.len());
+    }
+
+    ErrorStatus::None
+}
+
+/// sets `name` to `value` in the default environment, an empty value removes `name`
+#[no_mangle]
+extern "C" fn sysenv_set(
+    name_ptr: *const u8,
+    name_len: usize,
+    value_ptr: *const u8,
+    value_len: usize,
+) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::ENVIRONMENT) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let name = Slice::new(name_ptr, name_len)?.into_string_max(MAX_VAR_LEN)?;
+    let value = Slice::new(value_ptr, value_len)?.into_string_max(MAX_VAR_LEN)?;
+
+    match utils::env::set(&name, &value) {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
+
+#[no_mangle]
+extern "C" fn sysuname(ptr: RequiredMut<Utsname>) -> ErrorStatus {
+    ptr.get()?.write(utils::expose::uname());
+    ErrorStatus::None
+}
+
+/// requires `Capabilities::HOSTNAME`, see `utils::expose::set_hostname`
+#[no_mangle]
+extern "C" fn syssethostname(name_ptr: *const u8, name_len: usize) -> ErrorStatus {
+    if !threading::expose::has_capabilities(Capabilities::HOSTNAME) {
+        return ErrorStatus::MissingPermissions;
+    }
+
+    let name = Slice::new(name_ptr, name_len)?.into_string()?;
+    match utils::expose::set_hostname(&name) {
+        Ok(()) => ErrorStatus::None,
+        Err(err) => err,
+    }
+}
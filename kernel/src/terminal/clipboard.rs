@@ -0,0 +1,50 @@
+//! the kernel clipboard, a single buffer shared by every process through `TTYCtl::SetClipboard` and
+//! `TTYCtl::GetClipboard`, the TTY pastes it with ctrl + shift + v and fills it from mark mode
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// the most bytes the clipboard can hold
+pub const MAX_CLIPBOARD: usize = 64 * 1024;
+
+static CLIPBOARD: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// replaces the contents of the clipboard, fails if `data` is bigger than `MAX_CLIPBOARD`
+pub fn set(data: &[u8]) -> Result<(), ()> {
+    if data.len() > MAX_CLIPBOARD {
+        return Err(());
+    }
+
+    let mut clipboard = CLIPBOARD.lock();
+    clipboard.clear();
+    clipboard.extend_from_slice(data);
+    Ok(())
+}
+
+/// the size of the contents of the clipboard in bytes
+pub fn len() -> usize {
+    CLIPBOARD.lock().len()
+}
+
+/// copies the contents of the clipboard into `output`, fails if `output` isn't exactly the size of it
+pub fn get(output: &mut [u8]) -> Result<(), ()> {
+    let clipboard = CLIPBOARD.lock();
+    if output.len() != clipboard.len() {
+        return Err(());
+    }
+
+    output.copy_from_slice(&clipboard);
+    Ok(())
+}
+
+/// a copy of the contents of the clipboard, None if it is locked by whatever the caller interrupted
+pub fn try_get() -> Option<Vec<u8>> {
+    CLIPBOARD.try_lock().map(|clipboard| clipboard.clone())
+}
+
+/// like `set` but gives up if the clipboard is locked and keeps only the first `MAX_CLIPBOARD` bytes
+pub fn try_set(data: &[u8]) {
+    if let Some(mut clipboard) = CLIPBOARD.try_lock() {
+        clipboard.clear();
+        clipboard.extend_from_slice(&data[..data.len().min(MAX_CLIPBOARD)]);
+    }
+}
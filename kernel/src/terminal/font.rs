@@ -0,0 +1,225 @@
+//! the fonts of the framebuffer TTY, the builtin font is compiled in and PSF2 fonts are loaded from
+//! files (see the `[tty]` section of sys:/etc/init.cfg)
+//! a char that isn't in a font is drawn as the replacement char, a `?` or as a box, in that order
+use alloc::{collections::BTreeMap, vec::Vec};
+use noto_sans_mono_bitmap::{
+    get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar,
+};
+
+const RASTER_HEIGHT: RasterHeight = RasterHeight::Size20;
+const FONT_WEIGHT: FontWeight = FontWeight::Regular;
+const RASTER_WIDTH: usize = get_raster_width(FONT_WEIGHT, RASTER_HEIGHT);
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+/// the font has a unicode table after the glyphs
+const PSF2_HAS_UNICODE_TABLE: u32 = 1;
+/// ends the chars of a glyph in the unicode table
+const PSF2_SEPARATOR: u8 = 0xFF;
+/// starts a sequence of chars drawn as a single glyph in the unicode table
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+/// glyphs bigger than this are probably a corrupt header
+const MAX_GLYPH_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    /// doesn't start with the PSF2 magic or the header is cut off
+    NotPsf2,
+    /// a glyph size of 0 or bigger than `MAX_GLYPH_SIZE`
+    InvaildSize,
+    /// the file is too small for the glyphs the header says it has
+    Truncated,
+}
+
+impl FontError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NotPsf2 => "not a PSF2 font",
+            Self::InvaildSize => "invaild glyph size",
+            Self::Truncated => "truncated font",
+        }
+    }
+}
+
+/// a PC Screen Font version 2
+pub struct Psf2 {
+    width: usize,
+    height: usize,
+    /// the bytes of each glyph, every row is padded to a whole byte
+    glyph_size: usize,
+    glyphs: Vec<u8>,
+    /// the glyph of each char, None if the font has no unicode table and chars are glyph indices
+    unicode: Option<BTreeMap<char, usize>>,
+}
+
+impl Psf2 {
+    pub fn parse(data: &[u8]) -> Result<Self, FontError> {
+        let field = |index: usize| {
+            data.get(index * 4..index * 4 + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or(FontError::NotPsf2)
+        };
+
+        if data.get(..4) != Some(&PSF2_MAGIC) {
+            return Err(FontError::NotPsf2);
+        }
+
+        let header_size = field(2)? as usize;
+        let flags = field(3)?;
+        let length = field(4)? as usize;
+        let glyph_size = field(5)? as usize;
+        let height = field(6)?;
+        let width = field(7)?;
+
+        if width == 0 || height == 0 || width > MAX_GLYPH_SIZE || height > MAX_GLYPH_SIZE {
+            return Err(FontError::InvaildSize);
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        if glyph_size < width.div_ceil(8) * height {
+            return Err(FontError::InvaildSize);
+        }
+
+        let glyphs_end = length
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(header_size))
+            .filter(|end| *end <= data.len())
+            .ok_or(FontError::Truncated)?;
+        let glyphs = data[header_size..glyphs_end].to_vec();
+
+        let unicode = (flags & PSF2_HAS_UNICODE_TABLE != 0)
+            .then(|| Self::parse_unicode_table(&data[glyphs_end..], length));
+
+        Ok(Self {
+            width,
+            height,
+            glyph_size,
+            glyphs,
+            unicode,
+        })
+    }
+
+    /// each glyph has it's chars encoded as UTF-8 followed by it's sequences and then a separator,
+    /// the sequences are skipped since a char is drawn on it's own
+    fn parse_unicode_table(mut table: &[u8], length: usize) -> BTreeMap<char, usize> {
+        let mut chars = BTreeMap::new();
+
+        for glyph in 0..length {
+            let end = table
+                .iter()
+                .position(|byte| *byte == PSF2_SEPARATOR)
+                .unwrap_or(table.len());
+            let entry = &table[..end];
+            table = table.get(end + 1..).unwrap_or_default();
+
+            let singles = entry
+                .split(|byte| *byte == PSF2_START_SEQUENCE)
+                .next()
+                .unwrap_or_default();
+            for char in core::str::from_utf8(singles).unwrap_or_default().chars() {
+                chars.entry(char).or_insert(glyph);
+            }
+        }
+
+        chars
+    }
+
+    fn glyph_index(&self, c: char) -> Option<usize> {
+        match &self.unicode {
+            Some(unicode) => unicode.get(&c).copied(),
+            None => Some(c as usize).filter(|index| *index < self.glyphs.len() / self.glyph_size),
+        }
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+}
+
+pub enum Glyph<'a> {
+    Raster(RasterizedChar),
+    /// the rows of a PSF2 glyph, the leftmost pixel is the highest bit
+    Bitmap(&'a [u8]),
+    /// the outline of a box, for chars that aren't in the font
+    Missing,
+}
+
+pub enum Font {
+    Builtin,
+    Psf2(Psf2),
+}
+
+impl Font {
+    /// the width of a cell in pixels
+    pub const fn width(&self) -> usize {
+        match self {
+            Self::Builtin => RASTER_WIDTH,
+            Self::Psf2(font) => font.width(),
+        }
+    }
+
+    /// the height of a cell in pixels
+    pub const fn height(&self) -> usize {
+        match self {
+            Self::Builtin => RASTER_HEIGHT.val(),
+            Self::Psf2(font) => font.height(),
+        }
+    }
+
+    fn find(&self, c: char) -> Option<Glyph<'_>> {
+        match self {
+            Self::Builtin => get_raster(c, FONT_WEIGHT, RASTER_HEIGHT).map(Glyph::Raster),
+            Self::Psf2(font) => {
+                let start = font.glyph_index(c)? * font.glyph_size;
+                Some(Glyph::Bitmap(&font.glyphs[start..start + font.glyph_size]))
+            }
+        }
+    }
+
+    /// the glyph of `c` or the fallback glyph if it isn't in the font
+    pub fn glyph(&self, c: char) -> Glyph<'_> {
+        self.find(c)
+            .or_else(|| self.find(char::REPLACEMENT_CHARACTER))
+            .or_else(|| self.find('?'))
+            .unwrap_or(Glyph::Missing)
+    }
+
+    /// the intensity of the pixel at `col` `row` of `glyph`, from 0 (background) to 255 (foreground)
+    pub fn intensity(&self, glyph: &Glyph, col: usize, row: usize) -> u8 {
+        match glyph {
+            Glyph::Raster(raster) => raster
+                .raster()
+                .get(row)
+                .and_then(|row| row.get(col))
+                .copied()
+                .unwrap_or_default(),
+            Glyph::Bitmap(rows) => {
+                let stride = self.width().div_ceil(8);
+                let byte = rows
+                    .get(row * stride + col / 8)
+                    .copied()
+                    .unwrap_or_default();
+                if byte & (0x80 >> (col % 8)) != 0 {
+                    u8::MAX
+                } else {
+                    0
+                }
+            }
+            Glyph::Missing => {
+                let (width, height) = (self.width(), self.height());
+                // leaves a pixel of space around the box
+                let (last_col, last_row) = (width.saturating_sub(2), height.saturating_sub(2));
+                let edge = (col == 1 || col == last_col) && (1..=last_row).contains(&row)
+                    || (row == 1 || row == last_row) && (1..=last_col).contains(&col);
+                if edge {
+                    u8::MAX
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
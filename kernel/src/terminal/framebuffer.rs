@@ -1,20 +1,16 @@
-const RASTER_HEIGHT: RasterHeight = RasterHeight::Size20;
-const FONT_WEIGHT: FontWeight = FontWeight::Regular;
-const RASTER_WIDTH: usize = get_raster_width(FONT_WEIGHT, RASTER_HEIGHT);
-
 use core::fmt::Write;
 
-use crate::utils::{
-    ansi::{self, AnsiSequence},
-    either::Either,
-};
 use lazy_static::lazy_static;
-use noto_sans_mono_bitmap::{
-    get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar,
+use safa_utils::{
+    ansi::{self, Perform},
+    utf8,
 };
 use spin::RwLock;
 
-use super::TTYInterface;
+use super::{
+    font::{Font, FontError, Psf2},
+    TTYInterface,
+};
 use crate::{
     drivers::framebuffer::{FrameBuffer, FRAMEBUFFER_DRIVER},
     utils::{display::RGB, Locked},
@@ -31,6 +27,9 @@ pub struct FrameBufferTTY<'a> {
     cursor_y: usize,
     fg_color: RGB,
     bg_color: RGB,
+    /// keeps the escape sequences that are split across writes
+    parser: ansi::Parser,
+    font: Font,
 }
 
 impl FrameBufferTTY<'_> {
@@ -46,59 +45,71 @@ impl FrameBufferTTY<'_> {
             cursor_y: 0,
             fg_color: DEFAULT_FG_COLOR,
             bg_color: DEFAULT_BG_COLOR,
+            parser: ansi::Parser::new(),
+            font: Font::Builtin,
         }
     }
+
+    /// switches to `font`, the text already drawn stays in the old font and the cursor moves to
+    /// the start of the line after it
+    pub fn set_font(&mut self, font: Font) {
+        let y = (self.cursor_y + 1) * self.font.height();
+        self.font = font;
+        self.cursor_x = 0;
+        self.cursor_y = y.div_ceil(self.font.height());
+    }
+
+    /// the amount of columns and rows of characters that fit on the screen
+    pub fn size(&self) -> (usize, usize) {
+        let framebuffer = self.framebuffer.read();
+        (
+            framebuffer.width() / self.font.width(),
+            framebuffer.height() / self.font.height(),
+        )
+    }
     #[inline(always)]
     fn get_pixel_at(&self) -> (usize, usize) {
         (self.get_x(), self.get_y())
     }
     #[inline(always)]
     fn get_x(&self) -> usize {
-        self.cursor_x * RASTER_WIDTH
+        self.cursor_x * self.font.width()
     }
     #[inline(always)]
     fn get_y(&self) -> usize {
-        self.cursor_y * RASTER_HEIGHT.val()
+        self.cursor_y * self.font.height()
     }
 
-    fn raster(&self, c: char) -> RasterizedChar {
-        get_raster(c, FONT_WEIGHT, RASTER_HEIGHT).unwrap_or(
-            get_raster('?', FONT_WEIGHT, RASTER_HEIGHT).expect("failed to get rasterized char"),
-        )
-    }
-
-    fn draw_raster(&mut self, raster: RasterizedChar, fg_color: RGB, bg_color: RGB) {
+    fn draw_char(&mut self, c: char, fg_color: RGB, bg_color: RGB) {
+        let (width, height) = (self.font.width(), self.font.height());
         let framebuffer = self.framebuffer.read();
         let stride = framebuffer.info.stride;
         let cursor = framebuffer.get_cursor();
-        let height = framebuffer.height();
+        let screen_height = framebuffer.height();
         drop(framebuffer);
 
-        if self.get_x() + raster.width() > stride {
+        if self.get_x() + width > stride {
             self.newline();
         }
 
-        if self.get_y() + raster.height() >= cursor / stride + height {
+        if self.get_y() + height >= cursor / stride + screen_height {
             self.scroll_down();
         }
 
         let (x, y) = self.get_pixel_at();
+        let glyph = self.font.glyph(c);
         let mut framebuffer = self.framebuffer.write();
 
-        for (row, rows) in raster.raster().iter().enumerate() {
-            for (col, byte) in rows.iter().enumerate() {
-                let color = fg_color.with_alpha(*byte, bg_color);
-
-                framebuffer.set_pixel(x + col, y + row, color);
-            }
-        }
+        framebuffer.blit(x, y, width, height, |col, row| {
+            fg_color.with_alpha(self.font.intensity(&glyph, col, row), bg_color)
+        });
 
         self.cursor_x += 1;
     }
 
     fn remove_char(&mut self) {
         if self.cursor_x == 0 && self.cursor_y > 0 {
-            self.cursor_x = (self.framebuffer.read().width() / RASTER_WIDTH) - 1;
+            self.cursor_x = self.size().0 - 1;
             self.cursor_y -= 1;
         } else if self.cursor_x > 0 {
             self.cursor_x -= 1;
@@ -107,11 +118,8 @@ impl FrameBufferTTY<'_> {
         let mut framebuffer = self.framebuffer.write();
         let (x, y) = self.get_pixel_at();
 
-        for row in 0..RASTER_HEIGHT.val() {
-            for col in 0..RASTER_WIDTH {
-                framebuffer.set_pixel(x + col, y + row, RGB::new(0, 0, 0));
-            }
-        }
+        let (width, height) = (self.font.width(), self.font.height());
+        framebuffer.fill_rect(x, y, width, height, RGB::new(0, 0, 0));
     }
 
     fn sync_pixels(&mut self) {
@@ -119,21 +127,32 @@ impl FrameBufferTTY<'_> {
     }
 
     fn putc_unsynced(&mut self, c: char) {
-        let raster = self.raster(c);
         match c {
             '\n' => self.newline(),
             '\r' => self.cursor_x = 0,
-            _ => self.draw_raster(raster, self.fg_color, self.bg_color),
+            // combining marks aren't in the font
+            _ => match utf8::width(c) {
+                0 => (),
+                width => {
+                    self.draw_char(c, self.fg_color, self.bg_color);
+                    // the fonts only have narrow chars, the rest of a wide char is left empty
+                    for _ in 1..width {
+                        self.draw_char(' ', self.fg_color, self.bg_color);
+                    }
+                }
+            },
         }
     }
 
-    fn handle_set_graphics_mode(&mut self, params: &[u8]) {
+    fn handle_set_graphics_mode(&mut self, params: &[u16]) {
         if params.is_empty() {
             self.fg_color = DEFAULT_FG_COLOR;
             self.bg_color = DEFAULT_BG_COLOR;
             return;
         }
-        let mut params = params.iter().copied();
+        let mut params = params
+            .iter()
+            .map(|param| (*param).min(u8::MAX as u16) as u8);
 
         while let Some(param) = params.next() {
             match param {
@@ -206,31 +225,52 @@ impl FrameBufferTTY<'_> {
         }
     }
 
-    fn handle_escape_sequence(&mut self, escape: AnsiSequence) {
-        match escape {
-            AnsiSequence::SetGraphicsMode(params) => {
-                self.handle_set_graphics_mode(&params);
-            }
+    fn write_str_unsynced(&mut self, s: &str) {
+        let mut parser = core::mem::take(&mut self.parser);
+        parser.advance_str(self, s);
+        self.parser = parser;
+    }
+}
 
-            AnsiSequence::CursorUp(count) => self.offset_cursor(0, -(count as isize)),
-            AnsiSequence::CursorDown(count) => self.offset_cursor(0, count as isize),
-            AnsiSequence::CursorForward(count) => self.offset_cursor(count as isize, 0),
-            AnsiSequence::CursorBackward(count) => self.offset_cursor(-(count as isize), 0),
-            AnsiSequence::CursorPos(x, y) => self.set_cursor(x as usize, y as usize),
+impl Perform for FrameBufferTTY<'_> {
+    fn print(&mut self, c: char) {
+        self.putc_unsynced(c);
+    }
 
-            AnsiSequence::EraseDisplay => self.clear(),
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' | b'\r' => self.putc_unsynced(byte as char),
+            _ => (),
         }
     }
 
-    fn write_str_unsynced(&mut self, s: &str) {
-        ansi::AnsiiParser::new(s).for_each(|output| match output {
-            Either::Left(escape) => self.handle_escape_sequence(escape),
-            Either::Right(text) => {
-                for c in text.chars() {
-                    self.putc_unsynced(c);
-                }
+    fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], action: char) {
+        if !intermediates.is_empty() {
+            return;
+        }
+        // a missing or 0 count is 1
+        let count = params.first().copied().unwrap_or_default().max(1) as isize;
+
+        match action {
+            'm' => self.handle_set_graphics_mode(params),
+
+            'A' => self.offset_cursor(0, -count),
+            'B' => self.offset_cursor(0, count),
+            'C' => self.offset_cursor(count, 0),
+            'D' => self.offset_cursor(-count, 0),
+            // positions in escape sequences start at 1
+            'H' | 'f' => {
+                let y = params.first().copied().unwrap_or_default();
+                let x = params.get(1).copied().unwrap_or_default();
+                self.set_cursor(
+                    (x as usize).saturating_sub(1),
+                    (y as usize).saturating_sub(1),
+                )
             }
-        });
+
+            'J' => self.clear(),
+            _ => (),
+        }
     }
 }
 
@@ -265,19 +305,19 @@ impl TTYInterface for FrameBufferTTY<'_> {
     }
 
     fn offset_cursor(&mut self, x: isize, y: isize) {
-        self.cursor_x = (self.cursor_x as isize + x) as usize;
-        self.cursor_y = (self.cursor_y as isize + y) as usize;
+        self.cursor_x = self.cursor_x.saturating_add_signed(x);
+        self.cursor_y = self.cursor_y.saturating_add_signed(y);
     }
 
     fn scroll_down(&mut self) {
         let mut framebuffer = self.framebuffer.write();
-        let stride = framebuffer.info.stride * RASTER_HEIGHT.val();
+        let stride = framebuffer.info.stride * self.font.height();
         framebuffer.shift_buffer(stride as isize);
     }
 
     fn scroll_up(&mut self) {
         let mut framebuffer = self.framebuffer.write();
-        let stride = framebuffer.info.stride * RASTER_HEIGHT.val();
+        let stride = framebuffer.info.stride * self.font.height();
         framebuffer.shift_buffer(-(stride as isize));
     }
 
@@ -288,13 +328,23 @@ impl TTYInterface for FrameBufferTTY<'_> {
         let old_cursor = self.framebuffer.read().get_cursor();
         self.framebuffer.write().set_cursor(0);
 
-        let diff = old_cursor / stride / RASTER_HEIGHT.val();
-        self.cursor_y -= diff;
+        let diff = old_cursor / stride / self.font.height();
+        self.cursor_y = self.cursor_y.saturating_sub(diff);
 
         self.sync_pixels();
     }
 }
 
+/// switches the framebuffer TTY to the PSF2 font in `data`
+pub fn load_font(data: &[u8]) -> Result<(), FontError> {
+    let font = Psf2::parse(data)?;
+    FRAMEBUFFER_TTY_INTERFACE
+        .inner
+        .lock()
+        .set_font(Font::Psf2(font));
+    Ok(())
+}
+
 lazy_static! {
     pub static ref FRAMEBUFFER_TTY_INTERFACE: Locked<FrameBufferTTY<'static>> =
         Locked::new(FrameBufferTTY::new());
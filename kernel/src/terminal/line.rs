@@ -0,0 +1,161 @@
+//! the line editing of the TTY in line mode, every program reading a line from the TTY gets it
+//! left and right (by words with ctrl), home, end, backspace and delete move around and edit the line
+//! before return submits it, up and down go through the previously submitted lines
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+use crate::drivers::keyboard::keys::{Key, KeyCode, KeyFlags};
+
+/// the amount of submitted lines kept for up and down
+pub const MAX_HISTORY: usize = 32;
+
+pub struct LineEditor {
+    line: Vec<char>,
+    /// the position in `line` characters are inserted at
+    cursor: usize,
+    /// the submitted lines, the newest last
+    history: VecDeque<String>,
+    /// the line of the history being edited counted from the newest, None if it is a new line
+    browsing: Option<usize>,
+    /// the new line that was being edited before going through the history
+    draft: Vec<char>,
+}
+
+impl LineEditor {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            history: VecDeque::new(),
+            browsing: None,
+            draft: Vec::new(),
+        }
+    }
+
+    pub fn line(&self) -> &[char] {
+        &self.line
+    }
+
+    pub const fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// drops the line being edited, keeps the history
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.browsing = None;
+    }
+
+    /// takes the line being edited adding it to the history
+    pub fn submit(&mut self) -> String {
+        let line: String = self.line.iter().collect();
+        self.clear();
+
+        if !line.is_empty() && self.history.back() != Some(&line) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(line.clone());
+        }
+        line
+    }
+
+    /// edits the line as `key` is supposed to, returns the position of the first character that
+    /// changed (the cursor is drawn as a character) or None if nothing did
+    pub fn handle_key(&mut self, key: Key) -> Option<usize> {
+        let ctrl = key.flags.contains(KeyFlags::CTRL);
+        match key.code {
+            KeyCode::Left if ctrl => self.move_to(self.word_start()),
+            KeyCode::Right if ctrl => self.move_to(self.word_end()),
+            KeyCode::Left => self.move_to(self.cursor.saturating_sub(1)),
+            KeyCode::Right => self.move_to((self.cursor + 1).min(self.line.len())),
+            KeyCode::Home => self.move_to(0),
+            KeyCode::End => self.move_to(self.line.len()),
+            KeyCode::Backspace => {
+                if self.cursor == 0 {
+                    return None;
+                }
+
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+                Some(self.cursor)
+            }
+            KeyCode::Delete => {
+                if self.cursor == self.line.len() {
+                    return None;
+                }
+
+                self.line.remove(self.cursor);
+                Some(self.cursor)
+            }
+            KeyCode::Up => self.browse(self.browsing.map_or(0, |index| index + 1)),
+            KeyCode::Down => match self.browsing? {
+                0 => {
+                    self.browsing = None;
+                    self.line = core::mem::take(&mut self.draft);
+                    self.cursor = self.line.len();
+                    Some(0)
+                }
+                index => self.browse(index - 1),
+            },
+            _ => match key.map_key() {
+                '\0' | '\n' => None,
+                char => self.insert(char),
+            },
+        }
+    }
+
+    /// inserts `char` at the cursor, returns the position of the first character that changed
+    pub fn insert(&mut self, char: char) -> Option<usize> {
+        self.line.insert(self.cursor, char);
+        self.cursor += 1;
+        Some(self.cursor - 1)
+    }
+
+    fn move_to(&mut self, cursor: usize) -> Option<usize> {
+        if cursor == self.cursor {
+            return None;
+        }
+
+        let changed = cursor.min(self.cursor);
+        self.cursor = cursor;
+        Some(changed)
+    }
+
+    /// replaces the line with the line `index` of the history counted from the newest
+    fn browse(&mut self, index: usize) -> Option<usize> {
+        let line = self.history.iter().rev().nth(index)?;
+        if self.browsing.is_none() {
+            self.draft = core::mem::take(&mut self.line);
+        }
+
+        self.browsing = Some(index);
+        self.line = line.chars().collect();
+        self.cursor = self.line.len();
+        Some(0)
+    }
+
+    /// the start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut cursor = self.cursor;
+        while cursor > 0 && self.line[cursor - 1].is_whitespace() {
+            cursor -= 1;
+        }
+        while cursor > 0 && !self.line[cursor - 1].is_whitespace() {
+            cursor -= 1;
+        }
+        cursor
+    }
+
+    /// the end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut cursor = self.cursor;
+        while cursor < self.line.len() && self.line[cursor].is_whitespace() {
+            cursor += 1;
+        }
+        while cursor < self.line.len() && !self.line[cursor].is_whitespace() {
+            cursor += 1;
+        }
+        cursor
+    }
+}
@@ -1,19 +1,25 @@
+use alloc::{string::String, vec::Vec};
 use bitflags::bitflags;
 use core::fmt::Write;
 use framebuffer::FRAMEBUFFER_TTY_INTERFACE;
 use lazy_static::lazy_static;
+use safa_utils::utf8;
 use spin::RwLock;
 
+use self::line::LineEditor;
 use crate::{
     drivers::keyboard::{
         keys::{Key, KeyCode, KeyFlags},
         HandleKey,
     },
-    threading::expose::{pspawn, SpawnFlags},
-    utils::{alloc::PageString, Locked},
+    threading::waitqueue::WaitQueue,
+    utils::{alloc::ChunkedString, Locked},
 };
 
+pub mod clipboard;
+pub mod font;
 pub mod framebuffer;
+pub mod line;
 
 /// defines the interface for a tty
 /// a tty is a user-visible device that can be written to, and that user-input can be read from
@@ -53,17 +59,62 @@ bitflags! {
     }
 }
 
+/// 256 KiB of output
+const STDOUT_HISTORY_CHUNKS: usize = 64;
+/// the most characters of the marked line shown while in mark mode
+const MARK_PREVIEW_LEN: usize = 48;
+
+/// the lines selected in mark mode, counted from the last line of the output
+#[derive(Debug, Clone, Copy)]
+pub struct Mark {
+    /// the line the selection started at, moves along with `cursor` unless shift is held
+    anchor: usize,
+    cursor: usize,
+    /// the amount of cells the preview takes on the screen
+    drawn: usize,
+}
+
+impl Mark {
+    /// the first and last line of the selection
+    pub const fn lines(&self) -> (usize, usize) {
+        if self.anchor < self.cursor {
+            (self.anchor, self.cursor)
+        } else {
+            (self.cursor, self.anchor)
+        }
+    }
+}
+
 #[allow(clippy::upper_case_acronyms)]
 pub struct TTY<'a> {
-    pub stdout_buffer: PageString,
-    pub stdin_buffer: PageString,
+    /// the last [`STDOUT_HISTORY_CHUNKS`] chunks of output
+    pub stdout_buffer: ChunkedString,
+    pub stdin_buffer: ChunkedString,
 
     pub settings: TTYSettings,
+    /// the process allowed to read input (along with it's descendants), if None or if it exited
+    /// every process that wasn't spawned in the background can, see `TTYCtl`
+    pub foreground: Option<usize>,
+    /// the process that put the TTY in raw mode, keys are passed to readers as they are pressed
+    /// without being echoed until it turns raw mode off or exits, see `TTYCtl::SetRaw`
+    pub raw: Option<usize>,
+    /// entered with ctrl + shift + m, up and down select lines of the output (extending the selection
+    /// with shift), return copies them into the clipboard and escape leaves without copying
+    pub mark: Option<Mark>,
+    /// the line being entered in line mode, it is only added to `stdin_buffer` once submitted
+    pub editor: LineEditor,
+    /// the cells taken by each character of the line being entered drawn on the screen, including
+    /// the cursor
+    drawn: Vec<usize>,
+    /// keeps the chars that are split across writes
+    decoder: utf8::Decoder,
     interface: &'a Locked<dyn TTYInterface>,
 }
 
 impl Write for TTY<'_> {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // output would be drawn after the preview of the selection
+        self.leave_mark(false);
         if self.settings.contains(TTYSettings::DRAW_GRAPHICS) {
             self.interface.inner.lock().write_str(s)?;
             self.stdout_buffer.push_str(s);
@@ -72,6 +123,7 @@ impl Write for TTY<'_> {
     }
 
     fn write_char(&mut self, c: char) -> core::fmt::Result {
+        self.leave_mark(false);
         if self.settings.contains(TTYSettings::DRAW_GRAPHICS) {
             self.interface.inner.lock().write_char(c)?;
             self.stdout_buffer.push_char(c);
@@ -83,10 +135,16 @@ impl Write for TTY<'_> {
 impl<'a> TTY<'a> {
     pub fn new(interface: &'a Locked<dyn TTYInterface>) -> Self {
         Self {
-            stdout_buffer: PageString::new(),
-            stdin_buffer: PageString::new(),
+            stdout_buffer: ChunkedString::with_max_chunks(STDOUT_HISTORY_CHUNKS),
+            stdin_buffer: ChunkedString::new(),
             interface,
             settings: TTYSettings::DRAW_GRAPHICS,
+            foreground: None,
+            raw: None,
+            mark: None,
+            editor: LineEditor::new(),
+            drawn: Vec::new(),
+            decoder: utf8::Decoder::new(),
         }
     }
 
@@ -95,41 +153,273 @@ impl<'a> TTY<'a> {
         interface.clear();
         self.stdout_buffer.clear();
         interface.set_cursor(0, 0);
+        drop(interface);
+
+        self.drawn.clear();
+        self.redraw_input(0);
     }
 
     pub fn enable_input(&mut self) {
         if !self.settings.contains(TTYSettings::RECIVE_INPUT) {
             self.settings |= TTYSettings::RECIVE_INPUT;
-            _ = self.write_char('_');
+            self.redraw_input(0);
         }
     }
 
     pub fn disable_input(&mut self) {
         if self.settings.contains(TTYSettings::RECIVE_INPUT) {
+            self.erase_input(0);
             self.settings &= !TTYSettings::RECIVE_INPUT;
-            self.interface.inner.lock().backspace();
         }
     }
 
-    pub fn peform_backspace(&mut self) {
-        if !self.stdin_buffer.is_empty() {
-            if self.settings.contains(TTYSettings::RECIVE_INPUT) {
-                // removes the cursor `_`
-                self.interface.inner.lock().backspace();
+    /// writes `bytes` decoding the chars split across writes once they are complete
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut decoder = core::mem::take(&mut self.decoder);
+        decoder.decode(bytes, |str| _ = self.write_str(str));
+        self.decoder = decoder;
+    }
+
+    /// removes the characters of the line being entered drawn from `from` onwards from the screen
+    fn erase_input(&mut self, from: usize) {
+        let mut interface = self.interface.inner.lock();
+        while self.drawn.len() > from {
+            let cells = self.drawn.pop().unwrap_or_default();
+            for _ in 0..cells {
+                interface.backspace();
+            }
+        }
+    }
+
+    /// draws the line being entered from the character `from` onwards, the character under the
+    /// cursor is drawn in reverse colors or as `_` at the end of the line
+    fn redraw_input(&mut self, from: usize) {
+        self.erase_input(from);
+        if !self
+            .settings
+            .contains(TTYSettings::RECIVE_INPUT | TTYSettings::DRAW_GRAPHICS)
+            || self.mark.is_some()
+        {
+            return;
+        }
+
+        let line = self.editor.line();
+        let cursor = self.editor.cursor();
+        let mut results = String::new();
+        for (i, char) in line.iter().enumerate().skip(self.drawn.len()) {
+            if i == cursor {
+                _ = write!(results, "\x1B[30;47m{char}\x1B[0m");
+            } else {
+                results.push(*char);
+            }
+            self.drawn.push(utf8::width(*char));
+        }
+
+        if cursor == line.len() {
+            results.push('_');
+            self.drawn.push(1);
+        }
+
+        _ = self.interface.inner.lock().write_str(&results);
+    }
+
+    /// adds the line being entered to the input and the output
+    fn submit_line(&mut self) {
+        self.erase_input(0);
+        let line = self.editor.submit();
+        _ = self.write_str(&line);
+        _ = self.write_char('\n');
+
+        self.stdin_buffer.push_str(&line);
+        self.stdin_buffer.push_char('\n');
+        self.redraw_input(0);
+    }
+
+    /// puts the TTY in raw mode for `owner` or back in line mode if None, unread input is dropped
+    pub fn set_raw(&mut self, owner: Option<usize>) {
+        self.disable_input();
+        self.stdin_buffer.clear();
+        self.editor.clear();
+        self.raw = owner;
+    }
+
+    /// pushes the bytes `key` is read as in raw mode to the input
+    fn push_raw_key(&mut self, key: Key) {
+        let sequence = match key.code {
+            KeyCode::Up => "\x1B[A",
+            KeyCode::Down => "\x1B[B",
+            KeyCode::Right => "\x1B[C",
+            KeyCode::Left => "\x1B[D",
+            KeyCode::Home => "\x1B[H",
+            KeyCode::End => "\x1B[F",
+            KeyCode::Delete => "\x1B[3~",
+            KeyCode::Backspace => "\x7F",
+            _ => {
+                let char = key.map_key();
+                if key.flags.contains(KeyFlags::CTRL) && char.is_ascii_alphabetic() {
+                    // ctrl + a..z are read as 0x1..0x1A
+                    self.stdin_buffer
+                        .push_char((char.to_ascii_lowercase() as u8 & 0x1F) as char);
+                } else if char != '\0' {
+                    self.stdin_buffer.push_char(char);
+                }
+                return;
+            }
+        };
+
+        self.stdin_buffer.push_str(sequence);
+    }
+
+    /// the lines `first..=last` of the output counted from the last one, without escape sequences
+    pub fn output_lines(&self, first: usize, last: usize) -> String {
+        let mut line = 0;
+        let mut bytes = Vec::new();
+        for byte in self.stdout_buffer.bytes().rev() {
+            if byte == b'\n' {
+                if line >= first && line < last {
+                    bytes.push(byte);
+                }
+
+                line += 1;
+                if line > last {
+                    break;
+                }
+            } else if line >= first {
+                bytes.push(byte);
             }
-            // backspace
-            self.interface.inner.lock().backspace();
-            self.stdin_buffer.pop();
-            self.stdout_buffer.pop();
-
-            if self.settings.contains(TTYSettings::RECIVE_INPUT) {
-                // puts the cursor `_`
-                _ = self.write_char('_');
+        }
+        bytes.reverse();
+
+        let mut results = Vec::with_capacity(bytes.len());
+        let mut bytes = bytes.into_iter();
+        while let Some(byte) = bytes.next() {
+            if byte == 0x1B {
+                // CSI sequences end with a byte in 0x40..=0x7E
+                _ = bytes.next();
+                _ = bytes.by_ref().find(|byte| (0x40..=0x7E).contains(byte));
+            } else {
+                results.push(byte);
             }
         }
+        String::from_utf8_lossy(&results).into_owned()
+    }
+
+    /// the amount of lines in the output
+    fn output_line_count(&self) -> usize {
+        self.stdout_buffer
+            .bytes()
+            .filter(|byte| *byte == b'\n')
+            .count()
+            + 1
+    }
+
+    /// draws the preview of the selection in mark mode without adding it to the output
+    fn draw_mark(&mut self) {
+        let Some(mark) = self.mark else {
+            return;
+        };
+
+        let mut interface = self.interface.inner.lock();
+        for _ in 0..mark.drawn {
+            interface.backspace();
+        }
+
+        let (first, last) = mark.lines();
+        let line = self.output_lines(mark.cursor, mark.cursor);
+        let mut preview = alloc::format!("[mark {} lines] ", last - first + 1);
+        preview.extend(
+            line.chars()
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .take(MARK_PREVIEW_LEN),
+        );
+
+        _ = interface.write_str(&preview);
+        drop(interface);
+        if let Some(mark) = &mut self.mark {
+            mark.drawn = utf8::str_width(&preview);
+        }
+    }
+
+    /// enters mark mode selecting the line before the current one
+    fn enter_mark(&mut self) {
+        self.erase_input(0);
+        let line = 1.min(self.output_line_count() - 1);
+        self.mark = Some(Mark {
+            anchor: line,
+            cursor: line,
+            drawn: 0,
+        });
+        self.draw_mark();
+    }
+
+    /// leaves mark mode copying the selected lines into the clipboard if `copy` is true
+    fn leave_mark(&mut self, copy: bool) {
+        let Some(mark) = self.mark.take() else {
+            return;
+        };
+
+        if copy {
+            let (first, last) = mark.lines();
+            clipboard::try_set(self.output_lines(first, last).as_bytes());
+        }
+
+        let mut interface = self.interface.inner.lock();
+        for _ in 0..mark.drawn {
+            interface.backspace();
+        }
+        drop(interface);
+
+        self.redraw_input(0);
+    }
+
+    fn handle_mark_key(&mut self, key: Key) {
+        let count = self.output_line_count();
+        let Some(mark) = &mut self.mark else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Up => mark.cursor = (mark.cursor + 1).min(count - 1),
+            KeyCode::Down => mark.cursor = mark.cursor.saturating_sub(1),
+            KeyCode::Return => return self.leave_mark(true),
+            KeyCode::Esc => return self.leave_mark(false),
+            _ => return,
+        }
+
+        if !key.flags.contains(KeyFlags::SHIFT) {
+            mark.anchor = mark.cursor;
+        }
+        self.draw_mark();
+    }
+
+    /// pastes the clipboard into the input, in line mode only the characters that can be typed are
+    pub fn paste(&mut self) {
+        let Some(data) = clipboard::try_get() else {
+            return;
+        };
+        let text = String::from_utf8_lossy(&data);
+
+        if self.raw.is_some() {
+            self.stdin_buffer.push_str(&text);
+            return;
+        }
+
+        if !self.settings.contains(TTYSettings::RECIVE_INPUT) {
+            return;
+        }
+
+        let from = self.editor.cursor();
+        for char in text.chars().filter(|c| !c.is_control()) {
+            self.editor.insert(char);
+        }
+        self.redraw_input(from);
     }
 }
 
+/// woken up whenever a key is given to the framebuffer terminal or it's foreground or mode changes,
+/// in line mode it accepts a line while there are waiters as if they were reading it
+pub static FRAMEBUFFER_TERMINAL_WAITERS: WaitQueue = WaitQueue::new();
+
 lazy_static! {
     pub static ref FRAMEBUFFER_TERMINAL: RwLock<TTY<'static>> = {
         let interface: &'static Locked<dyn TTYInterface> = &*FRAMEBUFFER_TTY_INTERFACE;
@@ -139,27 +429,31 @@ lazy_static! {
 
 impl HandleKey for TTY<'_> {
     fn handle_key(&mut self, key: Key) {
+        if self.mark.is_some() {
+            return self.handle_mark_key(key);
+        }
+
         match key.code {
             KeyCode::PageDown => self.interface.inner.lock().scroll_down(),
             KeyCode::PageUp => self.interface.inner.lock().scroll_up(),
             KeyCode::KeyC if key.flags.contains(KeyFlags::CTRL | KeyFlags::SHIFT) => {
                 self.clear();
-                pspawn("Shell", "sys:/bin/Shell", &[], SpawnFlags::CLONE_RESOURCES).unwrap();
+                self.foreground = None;
+                self.set_raw(None);
+                crate::eve::start("Shell");
             }
-            KeyCode::Backspace if self.settings.contains(TTYSettings::RECIVE_INPUT) => {
-                self.peform_backspace();
+            KeyCode::KeyV if key.flags.contains(KeyFlags::CTRL | KeyFlags::SHIFT) => self.paste(),
+            KeyCode::KeyM
+                if key.flags.contains(KeyFlags::CTRL | KeyFlags::SHIFT) && self.raw.is_none() =>
+            {
+                self.enter_mark()
             }
+            _ if self.raw.is_some() => self.push_raw_key(key),
+            _ if !self.settings.contains(TTYSettings::RECIVE_INPUT) => {}
+            KeyCode::Return => self.submit_line(),
             _ => {
-                if self.settings.contains(TTYSettings::RECIVE_INPUT) {
-                    // remove the cursor `_`
-                    self.interface.inner.lock().backspace();
-                    let char = key.map_key();
-                    if char != '\0' {
-                        let _ = self.write_char(char);
-                        self.stdin_buffer.push_char(char);
-                    }
-                    // put the cursor back
-                    _ = self.write_char('_');
+                if let Some(from) = self.editor.handle_key(key) {
+                    self.redraw_input(from);
                 }
             }
         }
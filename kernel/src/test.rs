@@ -1,15 +1,312 @@
+//! the kernel tests, ran by Eve on boot with the `test` feature
+//! each test runs in it's own kernel process so that a hung test fails instead of hanging the boot
+//! the kernel command line can have `test=<substring>` to only run the tests whose name contains it and
+//! `test_timeout=<ticks>` to change the amount of scheduler ticks a test can take
+//! besides the human readable output, every test prints a
+//! `[TEST RESULT] module=<module> name=<name> status=<ok|failed|skipped> ...` line and each module a
+//! `[TEST SUMMARY] module=<module> passed=<n> failed=<n> skipped=<n>` line over serial
+//! `snapshot_module` compares generated files against the snapshots in `sys:/snapshots` (the
+//! `ramdisk-include/snapshots` directory) with their volatile fields masked
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::{format, string::String, vec::Vec};
 use macros::test_module;
 
+use crate::{
+    cross_println,
+    drivers::vfs,
+    limine, serial,
+    threading::{
+        self,
+        expose::{getinfo, kspawn, pkill, thread_exit, thread_yeild, wait, wait_status},
+        processes::{ExitKind, ProcessStatus},
+    },
+};
+
+const DEFAULT_TIMEOUT: u64 = 1_000_000;
+
+/// the function pointer of the test `test_process` runs
+static CURRENT_TEST: AtomicUsize = AtomicUsize::new(0);
+
+enum Failure {
+    SpawnFailed,
+    TimedOut,
+    ExitCode(usize),
+    /// killed by the process with the pid
+    Killed(usize),
+}
+
+/// returns the value of `name=value` in the kernel command line
+pub fn cmdline_option(name: &str) -> Option<&'static str> {
+    limine::kernel_cmdline()
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn test_process() -> ! {
+    let test: fn() = unsafe { core::mem::transmute(CURRENT_TEST.load(Ordering::Acquire)) };
+    test();
+
+    thread_exit(0);
+    unreachable!()
+}
+
+/// runs `test` in it's own process and waits for it to exit for up to `timeout` ticks
+/// returns the amount of ticks it took
+fn run_test(name: &str, test: fn(), timeout: u64) -> Result<u64, Failure> {
+    CURRENT_TEST.store(test as usize, Ordering::Release);
+    let pid = kspawn(name, test_process).map_err(|_| Failure::SpawnFailed)?;
+    let start = threading::ticks();
+
+    loop {
+        let elapsed = threading::ticks() - start;
+        if getinfo(pid).is_some_and(|info| info.status == ProcessStatus::Zombie) {
+            let status = wait_status(pid).expect("the test process was waited on twice");
+            return match (status.kind, status.value) {
+                (ExitKind::Exited, 0) => Ok(elapsed),
+                (ExitKind::Exited, code) => Err(Failure::ExitCode(code)),
+                (ExitKind::Killed, by) => Err(Failure::Killed(by)),
+            };
+        }
+
+        // a hung test may still hold locks, the tests after it can hang too but they time out as well
+        if elapsed >= timeout {
+            _ = pkill(pid);
+            wait(pid);
+            return Err(Failure::TimedOut);
+        }
+        thread_yeild();
+    }
+}
+
+/// runs `tests` (name, function) calling `setup` before and `teardown` after each of them
+/// panics if any of them failed, called by the `test_main` generated by `#[test_module]`
+pub fn run_tests(
+    module: &str,
+    tests: &[(&str, fn())],
+    setup: Option<fn()>,
+    teardown: Option<fn()>,
+) {
+    let filter = cmdline_option("test");
+    let timeout = cmdline_option("test_timeout")
+        .and_then(|timeout| timeout.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT);
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    cross_println!("\x1B[36m[TEST]\x1B[0m: Running {} tests", tests.len());
+
+    for (name, test) in tests {
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            skipped += 1;
+            serial!("[TEST RESULT] module={module} name={name} status=skipped\n");
+            continue;
+        }
+
+        cross_println!("\x1B[36m[TEST]\x1B[0m: Running {} test", name);
+        if let Some(setup) = setup {
+            setup();
+        }
+        let results = run_test(name, *test, timeout);
+        if let Some(teardown) = teardown {
+            teardown();
+        }
+
+        match results {
+            Ok(ticks) => {
+                passed += 1;
+                cross_println!("\x1B[32m[OK]\x1B[0m");
+                serial!("[TEST RESULT] module={module} name={name} status=ok ticks={ticks}\n");
+            }
+            Err(failure) => {
+                failed += 1;
+                let reason = match failure {
+                    Failure::SpawnFailed => "spawn_failed".into(),
+                    Failure::TimedOut => "timeout".into(),
+                    Failure::ExitCode(code) => alloc::format!("exit_code:{code}"),
+                    Failure::Killed(by) => alloc::format!("killed_by:{by}"),
+                };
+                cross_println!("\x1B[31m[FAILED]\x1B[0m: {}", reason);
+                serial!(
+                    "[TEST RESULT] module={module} name={name} status=failed reason={reason}\n"
+                );
+            }
+        }
+    }
+
+    serial!("[TEST SUMMARY] module={module} passed={passed} failed={failed} skipped={skipped}\n");
+    if failed != 0 {
+        crate::arch::qemu::debug_exit(crate::arch::qemu::EXIT_TESTS_FAILED);
+    }
+    assert!(failed == 0, "{failed} tests failed");
+}
+
+fn exit_with_3() -> ! {
+    thread_exit(3);
+    unreachable!()
+}
+
+fn spin() -> ! {
+    loop {
+        thread_yeild();
+    }
+}
+
+fn sleep_forever() -> ! {
+    loop {
+        crate::threading::expose::thread_sleep(usize::MAX / 2);
+    }
+}
+
+fn busy_loop() -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+fn abort_with_report() -> ! {
+    crate::threading::expose::report_abort(String::from("kernel abort"));
+    thread_exit(1);
+    unreachable!()
+}
+
+/// woken up by `wait_queues`
+static TEST_QUEUE: crate::threading::waitqueue::WaitQueue =
+    crate::threading::waitqueue::WaitQueue::new();
+
+/// waits on `TEST_QUEUE` once then exits
+fn wait_on_test_queue() -> ! {
+    let mut waiter = crate::threading::waitqueue::Waiter::new();
+    waiter.add(&TEST_QUEUE);
+    waiter.wait();
+    drop(waiter);
+    thread_exit(0);
+    unreachable!()
+}
+
+lazy_static::lazy_static! {
+    /// updated by the writers of `rcu_overlapping_writers`
+    static ref TEST_RCU: crate::utils::locks::rcu::Rcu<usize> =
+        crate::utils::locks::rcu::Rcu::new(0);
+}
+/// the amount of writers of `rcu_overlapping_writers` allowed to start updating
+static TEST_RCU_STARTED: AtomicUsize = AtomicUsize::new(0);
+/// the amount of writers of `rcu_overlapping_writers` that are done updating
+static TEST_RCU_UPDATED: AtomicUsize = AtomicUsize::new(0);
+
+/// waits until `TEST_RCU_STARTED` lets writer `n` start, then increments `TEST_RCU` and exits
+fn update_test_rcu(n: usize) {
+    while TEST_RCU_STARTED.load(Ordering::SeqCst) <= n {
+        thread_yeild();
+    }
+
+    TEST_RCU.update(|value| *value += 1);
+    TEST_RCU_UPDATED.fetch_add(1, Ordering::SeqCst);
+    thread_exit(0);
+}
+
+fn first_test_rcu_writer() -> ! {
+    update_test_rcu(0);
+    unreachable!()
+}
+
+fn second_test_rcu_writer() -> ! {
+    update_test_rcu(1);
+    unreachable!()
+}
+
+/// exits with 0 if the drive bound by `namespace_binds` is visible
+fn open_bound() -> ! {
+    let visible = crate::drivers::vfs::expose::open("box:/a").is_ok();
+    thread_exit(if visible { 0 } else { 1 });
+    unreachable!()
+}
+
 #[test_module]
 pub mod testing_module {
-    use alloc::vec::Vec;
+    use alloc::{string::String, vec::Vec};
 
-    use crate::cross_println;
+    use super::{read_to_string, remove_all};
+    use crate::devices::{
+        self,
+        framebuffer::{FrameBufferCtl, FRAMEBUFFER_DEVICE},
+        tty::TTYCtl,
+        CharDevice,
+    };
+    use crate::drivers::framebuffer::{VideoMode, FRAMEBUFFER_DRIVER};
+    use crate::drivers::keyboard::{
+        keys::{Key, KeyCode, KeyFlags},
+        HandleKey,
+    };
+    use crate::drivers::vfs::{
+        expose::{
+            close, create, createdir, open, open_with, read, readdir, remove, rename, write,
+            DirEntry,
+        },
+        ramfs::RamFS,
+        FSError, FSResult, OpenFlags, FS, VFS_STRUCT,
+    };
+    use crate::memory::{
+        frame_allocator,
+        paging::PAGE_SIZE,
+        shrinker::{self, Shrinker},
+    };
     use crate::println;
+    use crate::terminal::{clipboard, FRAMEBUFFER_TERMINAL};
+    use crate::threading::expose::chdir;
+    use crate::threading::expose::fchdir;
+    use crate::threading::expose::getcwd;
+    use crate::threading::expose::kspawn;
     use crate::threading::expose::pspawn;
+    use crate::threading::expose::pspawn_with_stdio;
+    use crate::threading::expose::thread_yeild;
     use crate::threading::expose::wait;
+    use crate::threading::expose::wait_any;
     use crate::threading::expose::SpawnFlags;
+    use crate::threading::expose::{getinfo, getpid};
+    use crate::threading::processes::{Capabilities, ContextPriority, ProcessStatus};
+    use crate::threading::{
+        ARGV_END, ARGV_SIZE, ARGV_START, RING0_STACK_END, RING0_STACK_START, STACK_END, STACK_SIZE,
+        STACK_START,
+    };
+    use crate::utils::alloc::ChunkedString;
+    use crate::utils::env;
+    use crate::utils::errors::ErrorStatus;
+    use crate::utils::inflate::{gunzip, InflateError};
+    use crate::utils::path::normalize;
+    use crate::utils::ustar::{self, TarArchiveIter};
     use core::arch::asm;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use safa_utils::ansi::{Parser, Perform};
+
+    /// the number of the next test's directory
+    static NEXT_DIRECTORY: AtomicUsize = AtomicUsize::new(0);
+
+    /// every test runs in a fresh ramfs directory as it's cwd, the directory is removed after the test
+    /// so tests should only create files in it using relative paths
+    fn setup() {
+        let directory = alloc::format!(
+            "ram:/tests/{}",
+            NEXT_DIRECTORY.fetch_add(1, Ordering::Relaxed)
+        );
+
+        match createdir("ram:/tests") {
+            Ok(()) | Err(FSError::AlreadyExists) => {}
+            Err(err) => panic!("failed to create ram:/tests: {err:?}"),
+        }
+        createdir(&directory).unwrap();
+        chdir(&directory).unwrap();
+    }
+
+    fn teardown() {
+        chdir("ram:/").unwrap();
+        remove_all(&alloc::format!(
+            "ram:/tests/{}",
+            NEXT_DIRECTORY.load(Ordering::Relaxed) - 1
+        ));
+    }
 
     fn serial() {}
     fn print() {}
@@ -64,16 +361,2290 @@ pub mod testing_module {
     }
 
     fn spawn() {
-        let pid = pspawn("TEST_CASE", "sys:/bin/true", &[], SpawnFlags::empty()).unwrap();
+        let pid = pspawn(
+            "TEST_CASE",
+            "sys:/bin/true",
+            &[],
+            SpawnFlags::empty(),
+            ContextPriority::default(),
+        )
+        .unwrap();
         let ret = wait(pid);
 
         assert_eq!(ret, 1);
     }
 
+    fn kernel_process() {
+        let pid = kspawn("TEST_KERNEL_PROCESS", super::exit_with_3).unwrap();
+        let ret = wait(pid);
+
+        assert_eq!(ret, 3);
+    }
+
+    fn wait_any_child() {
+        assert_eq!(wait_any(true), Err(ErrorStatus::InvaildPid));
+
+        let pid = kspawn("TEST_WAIT_ANY", super::exit_with_3).unwrap();
+        assert_eq!(wait_any(false), Ok((pid, 3)));
+        assert_eq!(wait_any(true), Err(ErrorStatus::InvaildPid));
+    }
+
+    fn tty_foreground() {
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let set_foreground = |pid: usize| {
+            CharDevice::ctl(
+                tty,
+                TTYCtl::SetForeground as u16,
+                &pid.to_ne_bytes(),
+                &mut [],
+            )
+        };
+        let pid = getpid();
+        let ppid = getinfo(pid).unwrap().ppid;
+
+        set_foreground(pid).unwrap();
+        assert_eq!(tty.read().foreground, Some(pid));
+        // only the caller and it's descendants can be given the TTY
+        assert!(matches!(
+            set_foreground(ppid),
+            Err(FSError::MissingPermissions)
+        ));
+
+        set_foreground(0).unwrap();
+        assert_eq!(tty.read().foreground, None);
+    }
+
+    fn tty_raw_mode() {
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let set_raw =
+            |raw: usize| CharDevice::ctl(tty, TTYCtl::SetRaw as u16, &raw.to_ne_bytes(), &mut []);
+
+        set_raw(1).unwrap();
+        assert_eq!(tty.read().raw, Some(getpid()));
+
+        for key in [
+            Key::new(KeyCode::Up, KeyFlags::empty()),
+            Key::new(KeyCode::KeyS, KeyFlags::CTRL),
+            Key::new(KeyCode::KeyA, KeyFlags::empty()),
+            Key::new(KeyCode::Backspace, KeyFlags::empty()),
+        ] {
+            tty.write().handle_key(key);
+        }
+
+        // keys are read as soon as they are pressed
+        let mut buffer = [0u8; 16];
+        let count = CharDevice::read(tty, &mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"\x1B[A\x13a\x7F");
+        assert!(matches!(
+            CharDevice::read(tty, &mut buffer),
+            Err(FSError::ResourceBusy)
+        ));
+
+        set_raw(0).unwrap();
+        assert_eq!(tty.read().raw, None);
+    }
+
+    fn tty_clipboard() {
+        use core::fmt::Write;
+
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let get_clipboard = || {
+            let mut size = [0u8; size_of::<usize>()];
+            CharDevice::ctl(tty, TTYCtl::GetClipboardSize as u16, &[], &mut size).unwrap();
+            let mut data = alloc::vec![0u8; usize::from_ne_bytes(size)];
+            CharDevice::ctl(tty, TTYCtl::GetClipboard as u16, &[], &mut data).unwrap();
+            data
+        };
+
+        CharDevice::ctl(tty, TTYCtl::SetClipboard as u16, b"echo hi", &mut []).unwrap();
+        assert_eq!(get_clipboard(), b"echo hi");
+        assert!(matches!(
+            CharDevice::ctl(tty, TTYCtl::GetClipboard as u16, &[], &mut [0u8; 3]),
+            Err(FSError::InvaildArgument)
+        ));
+        let too_big = alloc::vec![0u8; clipboard::MAX_CLIPBOARD + 1];
+        assert!(matches!(
+            CharDevice::ctl(tty, TTYCtl::SetClipboard as u16, &too_big, &mut []),
+            Err(FSError::InvaildArgument)
+        ));
+
+        // pasted into the input line
+        tty.write().stdin_buffer.clear();
+        tty.write().enable_input();
+        for key in [
+            Key::new(KeyCode::KeyV, KeyFlags::CTRL | KeyFlags::SHIFT),
+            Key::new(KeyCode::Return, KeyFlags::empty()),
+        ] {
+            tty.write().handle_key(key);
+        }
+        assert_eq!(
+            tty.read().stdin_buffer.bytes().collect::<Vec<_>>(),
+            b"echo hi\n"
+        );
+        tty.write().disable_input();
+        tty.write().stdin_buffer.clear();
+
+        // copied from the output in mark mode
+        tty.write().write_str("\nfirst\nsecond\n").unwrap();
+        for key in [
+            Key::new(KeyCode::KeyM, KeyFlags::CTRL | KeyFlags::SHIFT),
+            Key::new(KeyCode::Up, KeyFlags::SHIFT),
+            Key::new(KeyCode::Return, KeyFlags::empty()),
+        ] {
+            tty.write().handle_key(key);
+        }
+        assert!(tty.read().mark.is_none());
+        assert_eq!(get_clipboard(), b"first\nsecond");
+    }
+
+    fn tty_line_editing() {
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let key = |code: KeyCode| Key::new(code, KeyFlags::empty());
+        let submitted = || {
+            let line = tty.read().stdin_buffer.bytes().collect::<Vec<_>>();
+            tty.write().stdin_buffer.clear();
+            line
+        };
+
+        tty.write().stdin_buffer.clear();
+        tty.write().enable_input();
+        for key in [
+            key(KeyCode::KeyA),
+            key(KeyCode::KeyB),
+            key(KeyCode::Space),
+            key(KeyCode::KeyC),
+            key(KeyCode::Left),
+            key(KeyCode::Left),
+            key(KeyCode::KeyX),
+            key(KeyCode::Home),
+            key(KeyCode::Delete),
+            key(KeyCode::End),
+            key(KeyCode::Backspace),
+            Key::new(KeyCode::Left, KeyFlags::CTRL),
+            key(KeyCode::KeyY),
+        ] {
+            tty.write().handle_key(key);
+        }
+        // nothing is read before the line is submitted
+        assert!(tty.read().stdin_buffer.is_empty());
+        tty.write().handle_key(key(KeyCode::Return));
+        assert_eq!(submitted(), b"ybx \n");
+
+        // up and down go through the submitted lines
+        for code in [KeyCode::Key1, KeyCode::Return, KeyCode::Key2] {
+            tty.write().handle_key(key(code));
+        }
+        assert_eq!(submitted(), b"1\n");
+        for code in [KeyCode::Up, KeyCode::Up, KeyCode::Down, KeyCode::Return] {
+            tty.write().handle_key(key(code));
+        }
+        assert_eq!(submitted(), b"1\n");
+        // going back down gives the line that was being entered
+        for code in [KeyCode::Key3, KeyCode::Up, KeyCode::Down, KeyCode::Return] {
+            tty.write().handle_key(key(code));
+        }
+        assert_eq!(submitted(), b"3\n");
+
+        tty.write().disable_input();
+    }
+
+    fn utf8_editing() {
+        use crate::terminal::line::LineEditor;
+        use safa_utils::utf8::{self, Decoder};
+
+        let decode = |parts: &[&[u8]]| {
+            let mut decoder = Decoder::new();
+            let mut results = String::new();
+            for part in parts {
+                decoder.decode(part, |str| results.push_str(str));
+            }
+            results
+        };
+        let text = "é😀".as_bytes();
+        // every split of the bytes gives the same chars
+        for i in 0..text.len() {
+            assert_eq!(decode(&[&text[..i], &text[i..]]), "é😀");
+        }
+        assert_eq!(decode(&[b"a\xC3", b"b\xFF"]), "a\u{FFFD}b\u{FFFD}");
+
+        assert_eq!(utf8::width('e'), 1);
+        assert_eq!(utf8::width('é'), 1);
+        assert_eq!(utf8::width('\u{301}'), 0);
+        assert_eq!(utf8::width('😀'), 2);
+        assert_eq!(utf8::width('中'), 2);
+        assert_eq!(utf8::str_width("cafe\u{301} 😀"), 7);
+
+        // backspace and the cursor move by chars not bytes
+        let mut editor = LineEditor::new();
+        for char in "café😀".chars() {
+            editor.insert(char);
+        }
+        let key = |code: KeyCode| Key::new(code, KeyFlags::empty());
+        assert_eq!(editor.handle_key(key(KeyCode::Backspace)), Some(4));
+        assert_eq!(editor.handle_key(key(KeyCode::Left)), Some(3));
+        editor.insert('ê');
+        assert_eq!(editor.handle_key(key(KeyCode::Delete)), Some(4));
+        assert_eq!(editor.submit(), "cafê");
+
+        // a char split across writes of the TTY is still drawn once
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let emoji = "😀".as_bytes();
+        CharDevice::write(tty, &emoji[..1]).unwrap();
+        CharDevice::write(tty, &emoji[1..]).unwrap();
+        assert!(tty.read().output_lines(0, 0).ends_with("😀"));
+    }
+
+    fn psf2_fonts() {
+        use crate::terminal::font::{Font, FontError, Glyph, Psf2};
+
+        // two 8x16 glyphs, `A` is filled and `é` (also written as e + U+301) has only it's top left
+        // pixel set
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0x72, 0xB5, 0x4A, 0x86]);
+        for field in [0u32, 32, 1, 2, 16, 16, 8] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&[0xFF; 16]);
+        data.push(0x80);
+        data.extend_from_slice(&[0; 15]);
+        data.extend_from_slice(b"A\xFF");
+        data.extend_from_slice("é".as_bytes());
+        data.push(0xFE);
+        data.extend_from_slice("e\u{301}".as_bytes());
+        data.push(0xFF);
+
+        assert!(matches!(
+            Psf2::parse(&data[..40]),
+            Err(FontError::Truncated)
+        ));
+        assert!(matches!(Psf2::parse(&data[1..]), Err(FontError::NotPsf2)));
+
+        let font = Font::Psf2(Psf2::parse(&data).unwrap());
+        assert_eq!((font.width(), font.height()), (8, 16));
+
+        let a = font.glyph('A');
+        assert_eq!(font.intensity(&a, 7, 15), 255);
+        let e = font.glyph('é');
+        assert_eq!(font.intensity(&e, 0, 0), 255);
+        assert_eq!(font.intensity(&e, 1, 0), 0);
+        assert_eq!(font.intensity(&e, 0, 1), 0);
+
+        // neither the char, the replacement char or `?` are in the font
+        let missing = font.glyph('Z');
+        assert!(matches!(missing, Glyph::Missing));
+        assert_eq!(font.intensity(&missing, 1, 1), 255);
+        assert_eq!(font.intensity(&missing, 0, 0), 0);
+        assert_eq!(font.intensity(&missing, 3, 3), 0);
+
+        // the builtin font falls back to `?`
+        assert!(matches!(Font::Builtin.glyph('\u{E000}'), Glyph::Raster(_)));
+    }
+
+    fn device_ctl_buffers() {
+        let tty = &*FRAMEBUFFER_TERMINAL;
+        let get_foreground = |cmd: u16| {
+            let mut output = [0u8; size_of::<usize>()];
+            CharDevice::ctl(tty, cmd, &[], &mut output).map(|()| usize::from_ne_bytes(output))
+        };
+        let pid = getpid();
+
+        CharDevice::ctl(
+            tty,
+            TTYCtl::SetForeground as u16,
+            &pid.to_ne_bytes(),
+            &mut [],
+        )
+        .unwrap();
+        assert_eq!(get_foreground(TTYCtl::GetForeground as u16).ok(), Some(pid));
+        // class 0 is accepted by every device
+        assert_eq!(
+            get_foreground(TTYCtl::GetForeground as u16 & 0xFF).ok(),
+            Some(pid)
+        );
+        // commands of other devices are rejected
+        assert!(matches!(
+            get_foreground(FrameBufferCtl::GetMode as u16),
+            Err(FSError::OperationNotSupported)
+        ));
+        // so are buffers of the wrong size
+        assert!(matches!(
+            CharDevice::ctl(tty, TTYCtl::GetForeground as u16, &[], &mut [0u8; 4]),
+            Err(FSError::InvaildArgument)
+        ));
+        assert!(matches!(
+            CharDevice::ctl(tty, TTYCtl::SetForeground as u16, &[0u8; 4], &mut []),
+            Err(FSError::InvaildArgument)
+        ));
+
+        CharDevice::ctl(
+            tty,
+            TTYCtl::SetForeground as u16,
+            &0usize.to_ne_bytes(),
+            &mut [],
+        )
+        .unwrap();
+        assert_eq!(get_foreground(TTYCtl::GetForeground as u16).ok(), Some(0));
+
+        let mut mode = [0u8; size_of::<VideoMode>()];
+        CharDevice::ctl(
+            &FRAMEBUFFER_DEVICE,
+            FrameBufferCtl::GetMode as u16,
+            &[],
+            &mut mode,
+        )
+        .unwrap();
+        let mode: VideoMode = unsafe { core::mem::transmute(mode) };
+        assert_eq!(mode, FRAMEBUFFER_DRIVER.read().mode());
+    }
+
+    fn device_hotplug() {
+        struct Hotplug;
+        impl CharDevice for Hotplug {
+            fn name(&self) -> &'static str {
+                "hotplug"
+            }
+
+            fn read(&self, buffer: &mut [u8]) -> FSResult<usize> {
+                buffer[..2].copy_from_slice(b"hi");
+                Ok(2)
+            }
+
+            fn write(&self, buffer: &[u8]) -> FSResult<usize> {
+                Ok(buffer.len())
+            }
+        }
+        static HOTPLUG: Hotplug = Hotplug;
+
+        let events = open_with("dev:/events", OpenFlags::NONBLOCKING).unwrap();
+        let mut buffer = [0u8; 64];
+        assert!(matches!(
+            read(events, &mut buffer),
+            Err(FSError::WouldBlock)
+        ));
+
+        let id = devices::register(&HOTPLUG).unwrap();
+        assert!(matches!(
+            devices::register(&HOTPLUG),
+            Err(FSError::AlreadyExists)
+        ));
+        let device = open("dev:/hotplug").unwrap();
+        let count = read(device, &mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"hi");
+
+        devices::unregister(id).unwrap();
+        assert!(matches!(
+            open("dev:/hotplug"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        // files opened before it was removed don't reach it anymore
+        assert!(matches!(
+            read(device, &mut buffer),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        close(device).unwrap();
+
+        let count = read(events, &mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"add hotplug\nremove hotplug\n");
+        close(events).unwrap();
+    }
+
+    fn pseudo_devices() {
+        let mut buffer = [0xFFu8; 32];
+
+        let null = open("dev:/null").unwrap();
+        assert_eq!(write(null, b"discarded").unwrap(), 9);
+        assert_eq!(read(null, &mut buffer).unwrap(), 0);
+        close(null).unwrap();
+
+        let zero = open("dev:/zero").unwrap();
+        assert_eq!(read(zero, &mut buffer).unwrap(), 32);
+        assert_eq!(buffer, [0; 32]);
+        assert_eq!(write(zero, b"discarded").unwrap(), 9);
+        close(zero).unwrap();
+
+        let full = open("dev:/full").unwrap();
+        buffer.fill(0xFF);
+        assert_eq!(read(full, &mut buffer).unwrap(), 32);
+        assert_eq!(buffer, [0; 32]);
+        assert!(matches!(write(full, b"x"), Err(FSError::OutOfMemory)));
+        close(full).unwrap();
+
+        let urandom = open("dev:/urandom").unwrap();
+        let mut other = [0u8; 32];
+        // odd sizes fill the whole buffer too
+        assert_eq!(read(urandom, &mut buffer[..29]).unwrap(), 29);
+        assert_eq!(write(urandom, b"some entropy").unwrap(), 12);
+        assert_eq!(read(urandom, &mut other).unwrap(), 32);
+        assert_ne!(buffer[..29], other[..29]);
+        assert_ne!(other, [0; 32]);
+        close(urandom).unwrap();
+    }
+
+    fn input_events() {
+        use crate::devices::input::InputCtl;
+        use crate::drivers::input::{self, InputEvent, InputKind};
+        use crate::drivers::vfs::expose::ctl;
+
+        let device = open_with("dev:/input/event0", OpenFlags::NONBLOCKING).unwrap();
+        let mut buffer = [0u8; size_of::<InputEvent>() * 2];
+        // drains whatever was typed before
+        while read(device, &mut buffer).is_ok() {}
+
+        let grab = |arg: usize| ctl(device, InputCtl::Grab as u16, &arg.to_ne_bytes(), &mut []);
+        // grabbed so the key doesn't reach the TTY
+        grab(1).unwrap();
+        assert_eq!(input::grabber(), Some(getpid()));
+        input::push_key(Key::new(KeyCode::KeyA, KeyFlags::SHIFT));
+        grab(0).unwrap();
+        assert_eq!(input::grabber(), None);
+        assert!(grab(0).is_err());
+
+        assert!(matches!(
+            read(device, &mut buffer[..4]),
+            Err(FSError::InvaildArgument)
+        ));
+        assert_eq!(read(device, &mut buffer).unwrap(), size_of::<InputEvent>());
+        let event: InputEvent = unsafe { core::ptr::read_unaligned(buffer.as_ptr().cast()) };
+        assert_eq!(event.kind, InputKind::Key);
+        assert_eq!(event.code, KeyCode::KeyA as u16);
+        assert_eq!(event.value, KeyFlags::SHIFT.bits() as u32);
+        assert!(matches!(
+            read(device, &mut buffer),
+            Err(FSError::WouldBlock)
+        ));
+        close(device).unwrap();
+
+        let dir = open("dev:/input").unwrap();
+        let mut entries: [DirEntry; 2] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = readdir(dir, &mut entries).unwrap();
+        close(dir).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(&entries[0].name[..entries[0].name_length], b"event0");
+    }
+
+    fn kmsg_keeps_the_log() {
+        struct KmsgTest;
+        crate::info!(KmsgTest, "logged {}", 42);
+        crate::debug!(KmsgTest, "debug builds keep debug messages");
+
+        let kmsg = read_to_string("proc:/kmsg");
+        let info = kmsg
+            .lines()
+            .rfind(|line| line.contains("KmsgTest: logged"))
+            .unwrap();
+        assert!(info.starts_with('['));
+        assert!(info.ends_with("] cpu0 INFO KmsgTest: logged 42"), "{info}");
+        assert!(kmsg.contains("DEBUG KmsgTest: debug builds keep debug messages\n"));
+    }
+
+    fn eve_supervises_services() {
+        use crate::eve::{self, Service, Status};
+
+        assert!(Service::parse("bad", "path=sys:/bin/true\nrestart=sometimes").is_err());
+        assert!(Service::parse("bad", "args=a").is_err());
+        assert!(Service::parse("bad", "path=sys:/bin/true\ncapabilities=power flying").is_err());
+
+        // `true` exits with 1 so it crashes every time
+        let services = Vec::from([
+            Service::parse("after", "path=sys:/bin/true\ndepends=crashy").unwrap(),
+            Service::parse("cycle", "path=sys:/bin/true\ndepends=cycle").unwrap(),
+            Service::parse("crashy", "# crashes\npath=sys:/bin/true\nrestart=on-crash").unwrap(),
+        ]);
+        let mut services = eve::order(services);
+        let names: Vec<&str> = services.iter().map(Service::name).collect();
+        assert_eq!(names, ["crashy", "after", "cycle"]);
+        assert_eq!(services[2].status(), Status::Failed);
+
+        // starting `after` starts `crashy` first
+        services[1].request();
+        let mut now = 0;
+        while eve::supervise(&mut services, now) {
+            now += 1;
+            assert!(now < 100_000, "services never settled");
+            thread_yeild();
+        }
+
+        assert_eq!(services[0].status(), Status::Failed);
+        assert_eq!(services[0].restarts(), 4);
+        assert_eq!(services[1].status(), Status::Exited(1));
+        assert_eq!(services[1].restarts(), 0);
+
+        let journal = eve::eve_journal();
+        assert!(journal.contains("cycle: unknown dependency or dependency cycle\n"));
+        assert!(journal.contains("crashy: crashed 5 times in a row, giving up\n"));
+    }
+
+    fn config_parse() {
+        use crate::utils::config::{self, ConfigErrorKind};
+
+        let data = "top = 1\n# comment\n[a]\nkey = value # comment\nquoted = \" spaced # kept \"\nempty =\n\n[ b ]\nkey=x\nkey=y\n";
+        let sections = config::parse(data).unwrap();
+        let names: Vec<&str> = sections.iter().map(|section| section.name).collect();
+        assert_eq!(names, ["", "a", "b"]);
+        assert_eq!(sections[0].entries, [("top", "1")]);
+        assert_eq!(
+            sections[1].entries,
+            [
+                ("key", "value"),
+                ("quoted", " spaced # kept "),
+                ("empty", "")
+            ]
+        );
+        assert_eq!(sections[2].entries, [("key", "x"), ("key", "y")]);
+
+        let cases = [
+            ("[a\n", 1, ConfigErrorKind::InvaildSection),
+            ("[]", 1, ConfigErrorKind::InvaildSection),
+            ("a = 1\nno equals", 2, ConfigErrorKind::MissingEquals),
+            (" = v", 1, ConfigErrorKind::EmptyKey),
+            ("k = \"open", 1, ConfigErrorKind::UnterminatedString),
+            ("[a] x", 1, ConfigErrorKind::TrailingCharacters),
+            ("k = \"v\" x", 1, ConfigErrorKind::TrailingCharacters),
+        ];
+        for (data, line, kind) in cases {
+            let err = config::parse(data).unwrap_err();
+            assert_eq!((err.line, err.kind), (line, kind), "{data:?}");
+        }
+    }
+
+    fn spawn_with_stdio() {
+        let path = alloc::format!("{}echo-out", getcwd());
+        create(&path).unwrap();
+
+        let pid = pspawn_with_stdio(
+            "TEST_ECHO",
+            "sys:/bin/echo",
+            &["echo", "hello"],
+            SpawnFlags::CLONE_RESOURCES,
+            ContextPriority::default(),
+            &[None, Some(&path), None],
+            Capabilities::empty(),
+        )
+        .unwrap();
+        assert_eq!(wait(pid), 0);
+        assert_eq!(read_to_string(&path), "hello\n");
+
+        assert!(matches!(
+            pspawn_with_stdio(
+                "TEST_ECHO",
+                "sys:/bin/echo",
+                &["echo", "hello"],
+                SpawnFlags::CLONE_RESOURCES,
+                ContextPriority::default(),
+                &[None, Some("ram:/no/such/file"), None],
+                Capabilities::empty(),
+            ),
+            Err(err) if matches!(err.error, FSError::NoSuchAFileOrDirectory)
+        ));
+    }
+
+    fn hostname_and_uname() {
+        use crate::utils::expose::{hostname, set_hostname, uname};
+
+        let field = |field: &[u8]| {
+            let len = field.iter().position(|b| *b == 0).unwrap();
+            String::from_utf8(field[..len].to_vec()).unwrap()
+        };
+
+        let previous = hostname();
+        for invaild in ["", "has space", "tab\t", "ünicode", &"a".repeat(65)] {
+            assert_eq!(
+                set_hostname(invaild),
+                Err(ErrorStatus::InvaildStr),
+                "{invaild:?}"
+            );
+        }
+        set_hostname("test-box.local").unwrap();
+
+        let uname = uname();
+        assert_eq!(field(&uname.sysname), "SafaOS");
+        assert_eq!(field(&uname.nodename), "test-box.local");
+        assert_eq!(field(&uname.release), env!("CARGO_PKG_VERSION"));
+        assert!(!field(&uname.version).is_empty());
+        assert_eq!(field(&uname.machine), "x86_64");
+        assert!(read_to_string("proc:/kernelinfo").contains("hostname: test-box.local\n"));
+
+        set_hostname(&previous).unwrap();
+    }
+
+    fn ansi_parser() {
+        struct Recorder(Vec<String>);
+
+        impl Recorder {
+            /// merges runs of prints and puts into one event
+            fn push_char(&mut self, kind: &str, c: char) {
+                if let Some(last) = self.0.last_mut() {
+                    if last.starts_with(kind) && last.ends_with(')') {
+                        last.pop();
+                        last.push(c);
+                        last.push(')');
+                        return;
+                    }
+                }
+                self.0.push(alloc::format!("{kind}({c})"));
+            }
+        }
+
+        impl Perform for Recorder {
+            fn print(&mut self, c: char) {
+                self.push_char("print", c);
+            }
+            fn execute(&mut self, byte: u8) {
+                self.0.push(alloc::format!("execute({byte:#x})"));
+            }
+            fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], action: char) {
+                let intermediates = core::str::from_utf8(intermediates).unwrap();
+                self.0.push(alloc::format!(
+                    "csi({params:?}, {intermediates:?}, {action})"
+                ));
+            }
+            fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) {
+                let intermediates = core::str::from_utf8(intermediates).unwrap();
+                self.0
+                    .push(alloc::format!("esc({intermediates:?}, {})", byte as char));
+            }
+            fn osc_dispatch(&mut self, data: &[u8]) {
+                self.0.push(alloc::format!(
+                    "osc({})",
+                    core::str::from_utf8(data).unwrap()
+                ));
+            }
+            fn hook(&mut self, params: &[u16], intermediates: &[u8], action: char) {
+                let intermediates = core::str::from_utf8(intermediates).unwrap();
+                self.0.push(alloc::format!(
+                    "hook({params:?}, {intermediates:?}, {action})"
+                ));
+            }
+            fn put(&mut self, c: char) {
+                self.push_char("put", c);
+            }
+            fn unhook(&mut self) {
+                self.0.push(String::from("unhook"));
+            }
+        }
+
+        let cases: &[(&str, &[&str])] = &[
+            ("hello", &["print(hello)"]),
+            (
+                "a\nb\r",
+                &["print(a)", "execute(0xa)", "print(b)", "execute(0xd)"],
+            ),
+            ("\x1b[m", &["csi([], \"\", m)"]),
+            ("\x1b[1;31mred", &["csi([1, 31], \"\", m)", "print(red)"]),
+            ("\x1b[;5H", &["csi([0, 5], \"\", H)"]),
+            ("\x1b[5;H", &["csi([5, 0], \"\", H)"]),
+            ("\x1b[;;J", &["csi([0, 0, 0], \"\", J)"]),
+            ("\x1b[?25l", &["csi([25], \"?\", l)"]),
+            ("\x1b[1 q", &["csi([1], \" \", q)"]),
+            // numbers saturate instead of overflowing
+            ("\x1b[99999999A", &["csi([65535], \"\", A)"]),
+            // only the first 16 parameters are kept
+            (
+                "\x1b[1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18m",
+                &["csi([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16], \"\", m)"],
+            ),
+            // too many intermediates drop the sequence
+            ("\x1b[1 !\"m", &[]),
+            ("\x1b !\"F", &[]),
+            // a private marker after the parameters, a parameter after an intermediate and colons
+            // are invaild, the sequence is ignored until it's final byte
+            ("\x1b[1?mok", &["print(ok)"]),
+            ("\x1b[ 1mok", &["print(ok)"]),
+            ("\x1b[1:2mok", &["print(ok)"]),
+            ("\x1b[1\u{e9}2mok", &["print(ok)"]),
+            // controls are executed in the middle of a sequence
+            ("\x1b[1\n2m", &["execute(0xa)", "csi([12], \"\", m)"]),
+            // CAN and SUB abort the sequence
+            ("\x1b[12\x18m", &["execute(0x18)", "print(m)"]),
+            ("\x1b[12\x1am", &["execute(0x1a)", "print(m)"]),
+            // ESC restarts the sequence
+            ("\x1b[12\x1b[3m", &["csi([3], \"\", m)"]),
+            ("\x1b\x1b[3m", &["csi([3], \"\", m)"]),
+            ("\x1b(B", &["esc(\"(\", B)"]),
+            ("\x1b7\x1b8", &["esc(\"\", 7)", "esc(\"\", 8)"]),
+            ("\x1b\u{e9}ok", &["print(ok)"]),
+            // a lone ST does nothing
+            ("\x1b\\ok", &["print(ok)"]),
+            ("\x1b]0;title\x07", &["osc(0;title)"]),
+            (
+                "\x1b]0;t\u{ee}tle\x1b\\ok",
+                &["osc(0;t\u{ee}tle)", "print(ok)"],
+            ),
+            ("\x1b]0;a\nb\x07", &["osc(0;ab)"]),
+            ("\x1b]0;title\x18ok", &["execute(0x18)", "print(ok)"]),
+            // an unterminated OSC string eats everything
+            ("\x1b]0;title", &[]),
+            (
+                "\x1bP1;2|data\x1b\\",
+                &["hook([1, 2], \"\", |)", "put(data)", "unhook"],
+            ),
+            (
+                "\x1bP$qm\x1b\\",
+                &["hook([], \"$\", q)", "put(m)", "unhook"],
+            ),
+            ("\x1bP1:2|data\x1b\\ok", &["print(ok)"]),
+            ("\x1bP !\"|data\x1b\\ok", &["print(ok)"]),
+            ("\x1bX sos string \x1b\\ok", &["print(ok)"]),
+            ("\x1b^pm\x07still\x1b\\ok", &["print(ok)"]),
+            ("\x1b_apc\x1b\\ok", &["print(ok)"]),
+            ("a\x7fb", &["print(ab)"]),
+            ("\u{e9}\u{1f600}", &["print(\u{e9}\u{1f600})"]),
+        ];
+
+        for (input, expected) in cases {
+            // the result doesn't depend on how the input is split
+            let mut whole = Recorder(Vec::new());
+            let mut parser = Parser::new();
+            parser.advance_str(&mut whole, input);
+
+            let mut split = Recorder(Vec::new());
+            let mut parser = Parser::new();
+            let mut buf = [0; 4];
+            for c in input.chars() {
+                parser.advance_str(&mut split, c.encode_utf8(&mut buf));
+            }
+
+            assert_eq!(whole.0, *expected, "input: {input:?}");
+            assert_eq!(split.0, *expected, "input: {input:?}");
+        }
+    }
+
+    fn chunked_string() {
+        const CHUNK: usize = ChunkedString::CHUNK_SIZE;
+        let filler = "a".repeat(CHUNK - 1);
+
+        let mut string = ChunkedString::new();
+        assert!(string.is_empty());
+        assert_eq!(string.pop(), None);
+
+        // the 2 bytes of `é` are split across 2 chunks
+        string.push_str(&filler);
+        string.push_char('é');
+        string.push_str("\n");
+        assert_eq!(string.len(), CHUNK + 2);
+        assert_eq!(string.chunks().count(), 2);
+        assert!(string.ends_with('\n'));
+
+        assert_eq!(string.pop(), Some('\n'));
+        assert!(string.ends_with('é'));
+        assert_eq!(string.pop(), Some('é'));
+        assert_eq!(string.len(), CHUNK - 1);
+        assert_eq!(string.chunks().count(), 1);
+
+        let mut buffer = [0; 16];
+        assert_eq!(string.read(&mut buffer), 16);
+        assert_eq!(&buffer, b"aaaaaaaaaaaaaaaa");
+        assert_eq!(string.len(), CHUNK - 17);
+
+        string.push_str("bc");
+        let mut buffer = alloc::vec![0; CHUNK];
+        assert_eq!(string.read(&mut buffer), CHUNK - 15);
+        assert!(buffer[..CHUNK - 15].ends_with(b"abc"));
+        assert!(string.is_empty());
+        assert_eq!(string.chunks().count(), 0);
+
+        // reading across chunks frees the ones that were fully read
+        for _ in 0..3 {
+            string.push_str(&filler);
+        }
+        let mut buffer = alloc::vec![0; CHUNK + 1];
+        assert_eq!(string.read(&mut buffer), CHUNK + 1);
+        assert_eq!(string.len(), 2 * CHUNK - 4);
+        assert_eq!(string.chunks().count(), 2);
+        assert!(string.bytes().all(|byte| byte == b'a'));
+
+        // only the newest chunks are kept, the rest of a char split by dropping a chunk is dropped
+        // too
+        let mut history = ChunkedString::with_max_chunks(2);
+        history.push_str(&filler);
+        history.push_char('é');
+        history.push_str(&"b".repeat(CHUNK));
+        history.push_char('c');
+        assert_eq!(history.chunks().count(), 2);
+        assert_eq!(history.len(), CHUNK + 1);
+        assert!(history.bytes().all(|byte| byte == b'b' || byte == b'c'));
+        assert_eq!(history.pop(), Some('c'));
+
+        history.clear();
+        assert!(history.is_empty());
+        assert_eq!(history.chunks().count(), 0);
+    }
+
+    fn name_length_limits() {
+        use crate::drivers::vfs::expose::MAX_NAME_LEN;
+        use crate::utils::types::{FileName, Name, TooLong, MAX_PROCESS_NAME_LEN};
+
+        let longest = "a".repeat(MAX_PROCESS_NAME_LEN);
+        assert_eq!(Name::try_new(&longest).unwrap().as_str(), longest);
+        assert_eq!(
+            Name::try_new(&alloc::format!("{longest}a")),
+            Err(TooLong {
+                len: MAX_PROCESS_NAME_LEN + 1,
+                max: MAX_PROCESS_NAME_LEN
+            })
+        );
+        assert!(Name::try_new("").unwrap().is_empty());
+
+        // truncating never splits a char
+        let split = alloc::format!("{}é", &longest[1..]);
+        assert_eq!(Name::new_lossy(&split).as_str(), &longest[1..]);
+        assert_eq!(Name::new_lossy(&longest).as_str(), longest);
+        let padded = Name::new_lossy("short").to_padded();
+        assert_eq!(&padded[..5], b"short");
+        assert!(padded[5..].iter().all(|byte| *byte == 0));
+
+        let longest_file = "f".repeat(MAX_NAME_LEN);
+        let too_long_file = alloc::format!("{longest_file}f");
+        assert!(FileName::try_new(&longest_file).is_ok());
+        assert!(FileName::try_new(&too_long_file).is_err());
+
+        createdir("names").unwrap();
+        let path = alloc::format!("names/{longest_file}");
+        let too_long_path = alloc::format!("names/{too_long_file}");
+        create(&path).unwrap();
+        assert!(matches!(create(&too_long_path), Err(FSError::NameTooLong)));
+        assert!(matches!(
+            createdir(&too_long_path),
+            Err(FSError::NameTooLong)
+        ));
+        assert!(matches!(
+            rename(&path, &too_long_path),
+            Err(FSError::NameTooLong)
+        ));
+        assert_eq!(
+            ErrorStatus::from(FSError::NameTooLong),
+            ErrorStatus::StrTooLong
+        );
+
+        // the whole name is listed
+        let dir = open("names").unwrap();
+        let mut entries: [DirEntry; 2] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = readdir(dir, &mut entries).unwrap();
+        close(dir).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            &entries[0].name[..entries[0].name_length],
+            longest_file.as_bytes()
+        );
+
+        let longest_var = "V".repeat(env::MAX_VAR_LEN + 1);
+        assert_eq!(
+            env::set(&longest_var, "value"),
+            Err(ErrorStatus::StrTooLong)
+        );
+        assert_eq!(
+            env::set("TOO_LONG", &longest_var),
+            Err(ErrorStatus::StrTooLong)
+        );
+        assert_eq!(env::get("TOO_LONG"), None);
+    }
+
+    fn error_context() {
+        use crate::utils::elf::ElfError;
+        use crate::utils::errors::{Context, ErrorContext, MAX_CONTEXTS};
+
+        let result: Result<(), ElfError> = Err(ElfError::MapToError);
+        let err = result
+            .context("segment map")
+            .context("elf")
+            .context("spawn")
+            .unwrap_err();
+        assert_eq!(
+            alloc::format!("{err}"),
+            "spawn: elf: segment map: MapToError"
+        );
+        assert_eq!(ErrorStatus::from(err), ErrorStatus::OutOfMemory);
+
+        // converting to another layer's error keeps the contexts
+        let err = err.map_err(|_| FSError::NotExecuteable).context("exec");
+        assert_eq!(
+            alloc::format!("{err}"),
+            "exec: spawn: elf: segment map: NotExecuteable"
+        );
+        assert_eq!(ErrorStatus::from(err), ErrorStatus::NotExecutable);
+
+        // only the innermost contexts are kept
+        let mut err = ErrorContext::new(ErrorStatus::Generic);
+        for _ in 0..MAX_CONTEXTS {
+            err = err.context("inner");
+        }
+        let err = err.context("dropped");
+        assert_eq!(err.contexts().count(), MAX_CONTEXTS);
+        assert!(err.contexts().all(|context| context == "inner"));
+
+        let spawn = |path| {
+            pspawn(
+                "TEST_CASE",
+                path,
+                &[],
+                SpawnFlags::empty(),
+                ContextPriority::default(),
+            )
+            .unwrap_err()
+        };
+        assert_eq!(
+            alloc::format!("{}", spawn("sys:/no/such/binary")),
+            "open: NoSuchAFileOrDirectory"
+        );
+
+        create("not-an-elf").unwrap();
+        let file = open("not-an-elf").unwrap();
+        write(file, b"hello").unwrap();
+        close(file).unwrap();
+        let err = spawn("not-an-elf");
+        assert_eq!(alloc::format!("{err}"), "exec: elf header: NotExecuteable");
+        assert_eq!(ErrorStatus::from(err), ErrorStatus::NotExecutable);
+    }
+
+    fn abort_report() {
+        let abort = |pid: usize| read_to_string(&alloc::format!("proc:/{pid}/abort"));
+        assert_eq!(abort(getpid()), "");
+
+        let pid = kspawn("TEST_ABORT", super::abort_with_report).unwrap();
+        while !getinfo(pid).is_some_and(|info| info.status == ProcessStatus::Zombie) {
+            thread_yeild();
+        }
+
+        // kept until the process is waited on
+        assert_eq!(abort(pid), "kernel abort\n");
+        assert_eq!(wait(pid), 1);
+        assert!(open(&alloc::format!("proc:/{pid}/abort")).is_err());
+    }
+
+    fn exit_status() {
+        use crate::threading::expose::{pkill, wait_status};
+        use crate::threading::processes::ExitStatus;
+
+        let pid = kspawn("TEST_EXITED", super::exit_with_3).unwrap();
+        assert_eq!(wait_status(pid), Ok(ExitStatus::exited(3)));
+        // it was cleaned up
+        assert_eq!(wait_status(pid), Err(ErrorStatus::InvaildPid));
+
+        let pid = kspawn("TEST_KILLED", super::spin).unwrap();
+        pkill(pid).unwrap();
+        let status = wait_status(pid).unwrap();
+        assert_eq!(status, ExitStatus::killed(getpid()));
+        // `wait` reports kills as exiting with 1
+        assert_eq!(status.code(), 1);
+    }
+
+    fn cpu_states() {
+        use crate::arch::cpu::{self, CPUState, CPUS};
+        use crate::utils::errors::ErrorStatus;
+
+        assert_eq!(cpu::offline(0), Err(ErrorStatus::Busy));
+        assert_eq!(cpu::online(0), Ok(()));
+
+        let cpus = CPUS.read().len();
+        assert_eq!(cpu::offline(cpus), Err(ErrorStatus::InvaildResource));
+        assert_eq!(cpu::online(cpus), Err(ErrorStatus::InvaildResource));
+
+        // APs are never started
+        for id in 1..cpus {
+            assert_eq!(CPUS.read()[id].state, CPUState::Offline);
+            assert_eq!(cpu::offline(id), Ok(()));
+            assert_eq!(cpu::online(id), Err(ErrorStatus::OperationNotSupported));
+        }
+    }
+
+    fn cpuinfo_counters() {
+        let context_switches = || {
+            read_to_string("proc:/cpuinfo")
+                .lines()
+                .find_map(|line| line.trim_start().strip_prefix("context switches: "))
+                .and_then(|count| count.parse::<u64>().ok())
+                .unwrap()
+        };
+
+        let before = context_switches();
+        // the test runner is waiting for this test so there is always another process to switch to
+        for _ in 0..4 {
+            thread_yeild();
+        }
+        assert!(context_switches() > before);
+
+        let cpuinfo = read_to_string("proc:/cpuinfo");
+        assert!(cpuinfo.contains("  irqs: 0x20 timer "));
+    }
+
+    fn irq_affinity() {
+        use crate::devices::irq::{IrqCtl, IRQ_DEVICE};
+
+        let interrupts = read_to_string("proc:/interrupts");
+        assert!(interrupts.starts_with("cpus: 0"));
+        assert!(interrupts.contains("\n0x20 timer: "));
+
+        let set_affinity = |irq: usize, cpu: usize| {
+            CharDevice::ctl(
+                &IRQ_DEVICE,
+                IrqCtl::SetAffinity as u16,
+                &(irq | cpu << 32).to_ne_bytes(),
+                &mut [],
+            )
+        };
+
+        let cpus = crate::arch::cpu::CPUS.read().len();
+        assert!(matches!(
+            set_affinity(u32::MAX as usize, 0),
+            Err(FSError::InvaildArgument)
+        ));
+        assert!(matches!(
+            set_affinity(0, cpus),
+            Err(FSError::InvaildArgument)
+        ));
+
+        // only present if a device uses MSI-X
+        if interrupts.contains("\nirq 0: ") {
+            set_affinity(0, 0).unwrap();
+            let irq = read_to_string("proc:/interrupts")
+                .lines()
+                .find(|line| line.starts_with("irq 0: "))
+                .map(String::from)
+                .unwrap();
+            assert!(irq.ends_with(" cpu 0"));
+        }
+    }
+
+    fn profiler() {
+        use crate::arch::profiler;
+        use crate::devices::profiler::{ProfilerCtl, PROFILER_DEVICE};
+
+        let set_running = |running: bool| {
+            CharDevice::ctl(
+                &PROFILER_DEVICE,
+                ProfilerCtl::SetRunning as u16,
+                &(running as usize).to_ne_bytes(),
+                &mut [],
+            )
+        };
+
+        // the runner may have asked for a profile of the whole boot
+        let was_running = profiler::running();
+        let before = profiler::samples();
+        set_running(true).unwrap();
+        while profiler::samples() < before + 8 {
+            core::hint::spin_loop();
+        }
+        if !was_running {
+            set_running(false).unwrap();
+        }
+
+        let profile = read_to_string("proc:/profile");
+        let (header, samples) = profile.split_once('\n').unwrap();
+        assert!(header.starts_with("# source: "));
+        // the cpu id followed by at least the interrupted rip
+        let sample = samples.lines().last().unwrap();
+        let mut fields = sample.split(' ');
+        assert!(fields.next().unwrap().parse::<u8>().is_ok());
+        assert!(fields.next().unwrap().starts_with("0x"));
+    }
+
+    fn timer_wheel() {
+        use crate::drivers::vfs::expose::{evq_create, evq_wait, poll};
+        use crate::threading::expose::{pkill, thread_sleep};
+        use crate::threading::{ticks, timer::Timeout};
+
+        let pending = || {
+            read_to_string("proc:/timers")
+                .lines()
+                .find_map(|line| line.strip_prefix("pending: "))
+                .and_then(|count| count.parse::<usize>().ok())
+                .unwrap()
+        };
+
+        // long enough to be cascaded down from the second level
+        for amount in [1, 5, 100] {
+            let start = ticks();
+            thread_sleep(amount);
+            assert!(ticks() - start >= amount as u64);
+        }
+
+        let start = ticks();
+        assert_eq!(poll(&mut [], 3).unwrap(), 0);
+        assert!(ticks() - start >= 3);
+
+        // nothing but the timer wakes up an empty event queue
+        let evq = evq_create();
+        let start = ticks();
+        assert_eq!(evq_wait(evq, &mut [], 3).unwrap(), 0);
+        assert!(ticks() - start >= 3);
+        close(evq).unwrap();
+
+        // cancelled once dropped
+        let before = pending();
+        let timeout = Timeout::new(1000);
+        assert!(!timeout.expired());
+        assert_eq!(pending(), before + 1);
+        drop(timeout);
+        assert_eq!(pending(), before);
+
+        let pid = kspawn("TEST_SLEEPER", super::sleep_forever).unwrap();
+        while !getinfo(pid).is_some_and(|info| info.status == ProcessStatus::Sleeping) {
+            thread_yeild();
+        }
+        assert_eq!(pending(), before + 1);
+        pkill(pid).unwrap();
+        wait(pid);
+        // killing it cancelled it's timer
+        assert_eq!(pending(), before);
+    }
+
+    fn wait_queues() {
+        use crate::threading::waitqueue::Waiter;
+
+        // woken up before it waits so it doesn't sleep
+        let mut waiter = Waiter::new();
+        waiter.add(&super::TEST_QUEUE);
+        waiter.add(&super::TEST_QUEUE);
+        super::TEST_QUEUE.wake_all();
+        waiter.wait();
+        drop(waiter);
+        assert!(!super::TEST_QUEUE.has_waiters());
+
+        let pid = kspawn("TEST_WAITER", super::wait_on_test_queue).unwrap();
+        while !getinfo(pid).is_some_and(|info| info.status == ProcessStatus::Sleeping) {
+            thread_yeild();
+        }
+        assert!(super::TEST_QUEUE.has_waiters());
+        super::TEST_QUEUE.wake_all();
+        assert_eq!(wait(pid), 0);
+        assert!(!super::TEST_QUEUE.has_waiters());
+    }
+
+    /// a writer that starts while another waits for a reader to leave mustn't finish before it does,
+    /// or the value the reader may have loaded in between would be freed under it
+    fn rcu_overlapping_writers() {
+        use super::{TEST_RCU, TEST_RCU_STARTED, TEST_RCU_UPDATED};
+        use crate::threading::ticks;
+
+        let first = kspawn("TEST_RCU_A", super::first_test_rcu_writer).unwrap();
+        let second = kspawn("TEST_RCU_B", super::second_test_rcu_writer).unwrap();
+
+        TEST_RCU.read(|value| {
+            TEST_RCU_STARTED.store(1, Ordering::SeqCst);
+            // the readers get preempted, the first writer publishes then waits for this one
+            while TEST_RCU.read(|value| *value) != 1 {
+                core::hint::spin_loop();
+            }
+
+            TEST_RCU_STARTED.store(2, Ordering::SeqCst);
+            let start = ticks();
+            while ticks() - start < 10 {
+                core::hint::spin_loop();
+            }
+
+            assert_eq!(*value, 0);
+            assert_eq!(TEST_RCU_UPDATED.load(Ordering::SeqCst), 0);
+        });
+
+        assert_eq!(wait(first), 0);
+        assert_eq!(wait(second), 0);
+        assert_eq!(TEST_RCU.read(|value| *value), 2);
+    }
+
+    fn watchdog_hung_thread() {
+        use crate::threading::expose::pkill;
+        use crate::threading::watchdog::{hung_thread_ms, set_hung_thread_ms};
+
+        let default = hung_thread_ms();
+        set_hung_thread_ms(100);
+
+        let pid = kspawn("TEST_HUNG", super::busy_loop).unwrap();
+        let report = alloc::format!("TEST_HUNG (pid {pid}) didn't yield for 100ms");
+        while !read_to_string("proc:/kmsg").contains(&report) {
+            thread_yeild();
+        }
+
+        set_hung_thread_ms(default);
+        pkill(pid).unwrap();
+        wait(pid);
+
+        // the backtrace starts where it was interrupted
+        let kmsg = read_to_string("proc:/kmsg");
+        let backtrace = &kmsg[kmsg.find(&report).unwrap()..];
+        assert!(backtrace.contains("busy_loop"));
+    }
+
+    fn path_normalize() {
+        let cases = [
+            ("ram:", "ram:/"),
+            ("ram:/", "ram:/"),
+            ("ram:/a/./b//../c/", "ram:/a/c"),
+            ("ram:\\a\\b", "ram:/a/b"),
+            ("ram:/../..", "ram:/"),
+            ("rod:/../ram:/x", "rod:/ram:/x"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(normalize(path).unwrap(), expected, "normalizing {path}");
+        }
+        assert!(matches!(normalize("a/b"), Err(FSError::InvaildDrive)));
+        assert!(matches!(normalize(""), Err(FSError::InvaildDrive)));
+    }
+
+    /// checks the properties of `normalize` on every path made of up to 4 of `COMPONENTS`
+    fn path_normalize_properties() {
+        const COMPONENTS: [&str; 5] = ["a", "b:", ".", "..", ""];
+        const SEPARATORS: [char; 2] = ['/', '\\'];
+        const CHOICES: usize = COMPONENTS.len() * SEPARATORS.len();
+
+        for depth in 0..=4u32 {
+            for mut choice in 0..CHOICES.pow(depth) {
+                let mut path = String::from("ram:");
+                for _ in 0..depth {
+                    path.push(SEPARATORS[choice % SEPARATORS.len()]);
+                    path.push_str(COMPONENTS[choice / SEPARATORS.len() % COMPONENTS.len()]);
+                    choice /= CHOICES;
+                }
+
+                let normalized = normalize(&path).unwrap();
+                let rest = normalized.strip_prefix("ram:/").expect("lost the drive");
+
+                if !rest.is_empty() {
+                    assert!(
+                        rest.split('/').all(|c| !matches!(c, "" | "." | "..")),
+                        "{path} normalized to {normalized}"
+                    );
+                }
+                assert_eq!(
+                    normalize(&normalized).unwrap(),
+                    normalized,
+                    "not idempotent"
+                );
+                assert_eq!(normalize(&(path.clone() + "/.")).unwrap(), normalized);
+                assert_eq!(normalize(&(path.clone() + "/x/..")).unwrap(), normalized);
+            }
+        }
+    }
+
+    fn chdir_normalizes() {
+        let cwd = getcwd();
+
+        createdir("child").unwrap();
+        chdir("./child/../child//").unwrap();
+        assert_eq!(getcwd(), cwd.clone() + "child/");
+
+        chdir("..").unwrap();
+        assert_eq!(getcwd(), cwd);
+    }
+
+    fn default_environment() {
+        assert_eq!(env::get("PATH").as_deref(), Some("sys:/bin"));
+        assert_eq!(env::get("MISSING"), None);
+
+        env::set("TEST_VAR", "value").unwrap();
+        assert_eq!(env::get("TEST_VAR").as_deref(), Some("value"));
+        assert!(read_to_string("proc:/environ").contains("TEST_VAR=value\n"));
+
+        env::set("TEST_VAR", "").unwrap();
+        assert_eq!(env::get("TEST_VAR"), None);
+
+        assert_eq!(env::set("A=B", "value"), Err(ErrorStatus::InvaildStr));
+        assert_eq!(env::set("", "value"), Err(ErrorStatus::InvaildStr));
+    }
+
+    fn proc_files_are_live() {
+        let read_all = |fd| {
+            let mut data = Vec::new();
+            let mut buffer = [0u8; 64];
+            loop {
+                let count = read(fd, &mut buffer).unwrap();
+                if count == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buffer[..count]);
+            }
+            String::from_utf8(data).unwrap()
+        };
+
+        let fd = open("proc:/environ").unwrap();
+        assert!(!read_all(fd).contains("LIVE_VAR="));
+
+        // the same file descriptor gives fresh data once it was read to the end
+        env::set("LIVE_VAR", "value").unwrap();
+        assert!(read_all(fd).contains("LIVE_VAR=value\n"));
+
+        env::set("LIVE_VAR", "").unwrap();
+        assert!(!read_all(fd).contains("LIVE_VAR="));
+        close(fd).unwrap();
+    }
+
+    fn proc_fd_lists_resources() {
+        create("listed").unwrap();
+        let file = open("listed").unwrap();
+        let fd_dir = alloc::format!("proc:/{}/fd", getpid());
+
+        let dir = open(&fd_dir).unwrap();
+        let mut entries: [DirEntry; 16] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = readdir(dir, &mut entries).unwrap();
+        close(dir).unwrap();
+
+        let name = alloc::format!("{}", file);
+        assert!(entries[..count]
+            .iter()
+            .any(|entry| &entry.name[..entry.name_length] == name.as_bytes()));
+
+        let description = read_to_string(&alloc::format!("{}/{}", fd_dir, file));
+        assert_eq!(description, alloc::format!("file {}listed\n", getcwd()));
+        close(file).unwrap();
+
+        // closed resources aren't listed anymore
+        assert!(matches!(
+            open(&alloc::format!("{}/{}", fd_dir, file)),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+    }
+
+    fn proc_maps() {
+        let maps = read_to_string(&alloc::format!("proc:/{}/maps", getpid()));
+        let has_range = |start: usize, end: usize, kind: &str| {
+            let range = alloc::format!("{:#x}-{:#x} rw", start, end);
+            // kernel processes can't be accessed by userspace
+            let kind = alloc::format!("k {}", kind);
+            maps.lines()
+                .any(|line| line.starts_with(&range) && line.ends_with(&kind))
+        };
+
+        assert!(has_range(STACK_START, STACK_END, "stack"));
+        assert!(has_range(
+            RING0_STACK_START,
+            RING0_STACK_END,
+            "kernel stack"
+        ));
+        assert!(has_range(ARGV_START, ARGV_END, "argv"));
+
+        assert!(matches!(
+            open("proc:/4294967295/maps"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+    }
+
+    fn proc_memory() {
+        let field = |data: &str, name: &str| -> usize {
+            data.lines()
+                .find_map(|line| line.strip_prefix(name))
+                .unwrap()
+                .parse()
+                .unwrap()
+        };
+
+        let memory = read_to_string(&alloc::format!("proc:/{}/memory", getpid()));
+        let resident = field(&memory, "resident: ");
+        let mapped = field(&memory, "mapped: ");
+        // the stacks and argv are always mapped
+        assert!(resident >= STACK_SIZE * 2 + ARGV_SIZE);
+        assert!(mapped >= resident);
+
+        let meminfo = read_to_string("proc:/meminfo");
+        assert!(field(&meminfo, "processes: ") >= resident);
+
+        assert!(matches!(
+            open("proc:/4294967295/memory"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+    }
+
+    fn namespace_binds() {
+        use crate::drivers::vfs::expose::{bind, unbind};
+        use crate::threading::expose::wait_status;
+        use crate::threading::processes::ExitStatus;
+
+        createdir("ns").unwrap();
+        create("ns/a").unwrap();
+
+        assert!(matches!(bind("a:b", "ns"), Err(FSError::InvaildDrive)));
+        assert!(matches!(bind("box", "ns/a"), Err(FSError::NotADirectory)));
+
+        bind("box", "ns").unwrap();
+        let file = open("box:/a").unwrap();
+        close(file).unwrap();
+        // the cwd stays in the namespace
+        let cwd = getcwd();
+        chdir("box:/").unwrap();
+        assert_eq!(getcwd(), "box:/");
+        let file = open("a").unwrap();
+        close(file).unwrap();
+        chdir(&cwd).unwrap();
+
+        // a bind hides the global drive with the same name
+        bind("sys", "box:/").unwrap();
+        let file = open("sys:/a").unwrap();
+        close(file).unwrap();
+        assert!(matches!(
+            open("sys:/bin"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        unbind("sys").unwrap();
+        let dir = open("sys:/bin").unwrap();
+        close(dir).unwrap();
+
+        // the child keeps the bind after the parent removes it
+        let pid = kspawn("TEST_NAMESPACE", super::open_bound).unwrap();
+        unbind("box").unwrap();
+        assert!(matches!(open("box:/a"), Err(FSError::InvaildDrive)));
+        assert!(matches!(unbind("box"), Err(FSError::InvaildDrive)));
+        assert_eq!(wait_status(pid), Ok(ExitStatus::exited(0)));
+    }
+
+    fn bind_mounts() {
+        use crate::drivers::vfs::expose::{mount_bind, umount};
+
+        createdir("bind").unwrap();
+        create("bind/hidden").unwrap();
+
+        // a new drive
+        mount_bind("sys:/bin", "sbin:").unwrap();
+        let file = open("sbin:/TestBot").unwrap();
+        close(file).unwrap();
+        assert!(matches!(
+            mount_bind("sys:/etc", "sbin:/"),
+            Err(FSError::AlreadyExists)
+        ));
+
+        // over a directory, the files that were in it are hidden
+        mount_bind("sys:/bin", "bind").unwrap();
+        let file = open("bind/TestBot").unwrap();
+        close(file).unwrap();
+        assert!(matches!(
+            open("bind/hidden"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        assert!(matches!(
+            mount_bind("sys:/bin", "missing"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+
+        let mounts = read_to_string("proc:/mounts");
+        assert!(mounts.contains("ram: ramfs\n"));
+        assert!(mounts.contains("sbin:/ bind sys:/bin\n"));
+        assert!(mounts.contains(&alloc::format!("{}bind bind sys:/bin\n", getcwd())));
+
+        umount("bind").unwrap();
+        umount("sbin:").unwrap();
+        assert!(matches!(
+            umount("sbin:"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        assert!(matches!(umount("ram:"), Err(FSError::ResourceBusy)));
+        assert!(matches!(open("sbin:/TestBot"), Err(FSError::InvaildDrive)));
+        let file = open("bind/hidden").unwrap();
+        close(file).unwrap();
+    }
+
+    fn file_locks() {
+        use crate::drivers::vfs::expose::{dup, flock};
+        use crate::drivers::vfs::flock::{LockOp, LockRange};
+
+        let whole = LockRange::new(0, 0);
+        create("locked").unwrap();
+        let a = open("locked").unwrap();
+        let b = open("locked").unwrap();
+
+        flock(a, LockOp::Shared, whole).unwrap();
+        flock(b, LockOp::Shared, whole).unwrap();
+        assert!(matches!(
+            flock(b, LockOp::Exclusive, whole),
+            Err(FSError::ResourceBusy)
+        ));
+
+        // a duplicate shares the locks of the file it was duplicated from
+        let a_dup = dup(a).unwrap();
+        flock(a_dup, LockOp::Unlock, whole).unwrap();
+        flock(b, LockOp::Exclusive, whole).unwrap();
+        assert!(matches!(
+            flock(a, LockOp::Shared, whole),
+            Err(FSError::ResourceBusy)
+        ));
+        flock(b, LockOp::Unlock, whole).unwrap();
+
+        // ranges only conflict if they overlap
+        flock(a, LockOp::Exclusive, LockRange::new(0, 10)).unwrap();
+        flock(b, LockOp::Exclusive, LockRange::new(10, 10)).unwrap();
+        assert!(matches!(
+            flock(b, LockOp::Shared, LockRange::new(5, 10)),
+            Err(FSError::ResourceBusy)
+        ));
+        // unlocking the middle of a lock keeps both ends of it
+        flock(a, LockOp::Unlock, LockRange::new(2, 4)).unwrap();
+        flock(b, LockOp::Exclusive, LockRange::new(3, 2)).unwrap();
+        assert!(matches!(
+            flock(b, LockOp::Exclusive, LockRange::new(1, 1)),
+            Err(FSError::ResourceBusy)
+        ));
+        assert!(matches!(
+            flock(b, LockOp::Exclusive, LockRange::new(8, 1)),
+            Err(FSError::ResourceBusy)
+        ));
+
+        // closing the last duplicate releases the locks
+        close(a).unwrap();
+        assert!(matches!(
+            flock(b, LockOp::Exclusive, whole),
+            Err(FSError::ResourceBusy)
+        ));
+        close(a_dup).unwrap();
+        flock(b, LockOp::Exclusive, whole).unwrap();
+
+        close(b).unwrap();
+    }
+
+    fn tmpfile_link() {
+        use crate::drivers::vfs::expose::link;
+
+        createdir("replace").unwrap();
+        create("replace/config").unwrap();
+        let old = open("replace/config").unwrap();
+        write(old, b"old").unwrap();
+
+        let tmp = open_with("replace", OpenFlags::TMPFILE).unwrap();
+        write(tmp, b"new").unwrap();
+        // it isn't in the directory until it is linked
+        let mut names = Vec::new();
+        let dir = open("replace").unwrap();
+        let mut entries: [DirEntry; 4] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = readdir(dir, &mut entries).unwrap();
+        for entry in &entries[..count] {
+            names.push(entry.name[..entry.name_length].to_vec());
+        }
+        close(dir).unwrap();
+        assert_eq!(names, [b"..".as_slice(), b"config"]);
+
+        link(tmp, "replace/config").unwrap();
+        assert_eq!(read_to_string("replace/config"), "new");
+        assert!(matches!(
+            link(tmp, "replace/again"),
+            Err(FSError::AlreadyExists)
+        ));
+        close(tmp).unwrap();
+
+        // files that had the old one opened keep it
+        let mut data = [0u8; 3];
+        assert_eq!(read(old, &mut data).unwrap(), 3);
+        assert_eq!(&data, b"old");
+        close(old).unwrap();
+
+        let tmp = open_with("replace", OpenFlags::TMPFILE).unwrap();
+        assert!(matches!(link(tmp, "replace"), Err(FSError::NotAFile)));
+        assert!(matches!(
+            link(tmp, "sys:/config"),
+            Err(FSError::OperationNotSupported)
+        ));
+        close(tmp).unwrap();
+        assert!(matches!(
+            open_with("replace/config", OpenFlags::TMPFILE),
+            Err(FSError::NotADirectory)
+        ));
+    }
+
+    fn xattrs() {
+        use crate::drivers::vfs::expose::{
+            get_xattr, list_xattrs, remove_xattr, set_xattr, MAX_XATTR_SIZE,
+        };
+
+        create("xattrs").unwrap();
+        let file = open("xattrs").unwrap();
+        assert!(matches!(
+            get_xattr(file, "hash"),
+            Err(FSError::NoSuchAttribute)
+        ));
+
+        set_xattr(file, "hash", b"1234").unwrap();
+        set_xattr(file, "caps", b"").unwrap();
+        set_xattr(file, "hash", b"5678").unwrap();
+        assert_eq!(get_xattr(file, "hash").unwrap(), b"5678");
+        assert_eq!(list_xattrs(file).unwrap(), ["caps", "hash"]);
+
+        // they are kept with the file not the file descriptor
+        let again = open("xattrs").unwrap();
+        assert_eq!(get_xattr(again, "caps").unwrap(), b"");
+        close(again).unwrap();
+
+        assert!(matches!(
+            set_xattr(file, "", b"value"),
+            Err(FSError::InvaildArgument)
+        ));
+        assert!(matches!(
+            set_xattr(file, "big", &[0; MAX_XATTR_SIZE + 1]),
+            Err(FSError::NameTooLong)
+        ));
+
+        remove_xattr(file, "hash").unwrap();
+        assert!(matches!(
+            remove_xattr(file, "hash"),
+            Err(FSError::NoSuchAttribute)
+        ));
+        assert_eq!(list_xattrs(file).unwrap(), ["caps"]);
+        close(file).unwrap();
+
+        let device = open("dev:/null").unwrap();
+        assert!(matches!(
+            list_xattrs(device),
+            Err(FSError::OperationNotSupported)
+        ));
+        close(device).unwrap();
+    }
+
+    fn uid_drops_capabilities() {
+        use crate::threading::expose::{getuid, has_capabilities, setuid};
+        use crate::threading::processes::{Capabilities, ROOT_UID};
+
+        // every test runs in it's own process so dropping the privileges here doesn't leak
+        assert_eq!(getuid(), ROOT_UID);
+        assert!(has_capabilities(Capabilities::RAW_DEVICES));
+        let fb = open("dev:/fb").unwrap();
+        close(fb).unwrap();
+
+        setuid(ROOT_UID).unwrap();
+        setuid(1000).unwrap();
+        assert_eq!(getuid(), 1000);
+        assert!(!has_capabilities(Capabilities::POWER));
+        assert!(!has_capabilities(Capabilities::RAW_DEVICES));
+        assert_eq!(setuid(ROOT_UID), Err(ErrorStatus::MissingPermissions));
+        assert_eq!(setuid(1001), Err(ErrorStatus::MissingPermissions));
+        setuid(1000).unwrap();
+
+        assert!(matches!(open("dev:/fb"), Err(FSError::MissingPermissions)));
+        let null = open("dev:/null").unwrap();
+        close(null).unwrap();
+
+        let pid = getpid();
+        let status = read_to_string(&alloc::format!("proc:/{}/threads/{}", pid, pid));
+        assert!(status.contains("uid: 1000\n"));
+    }
+
+    fn proc_threads() {
+        let pid = getpid();
+        let status = read_to_string(&alloc::format!("proc:/{}/threads/{}", pid, pid));
+        assert!(status.starts_with(&alloc::format!(
+            "tid: {}\nstatus: running\npriority: medium\n",
+            pid
+        )));
+        assert!(status.contains("cpu time: "));
+
+        let dir = open(&alloc::format!("proc:/{}", pid)).unwrap();
+        let mut entries: [DirEntry; 6] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = readdir(dir, &mut entries).unwrap();
+        close(dir).unwrap();
+
+        let names: Vec<&[u8]> = entries[..count]
+            .iter()
+            .map(|entry| &entry.name[..entry.name_length])
+            .collect();
+        assert_eq!(
+            names,
+            [&b"memory"[..], b"abort", b"threads", b"maps", b"fd"]
+        );
+    }
+
+    fn fchdir_to_directory() {
+        let cwd = getcwd();
+
+        createdir("child").unwrap();
+        let dir = open("./child/").unwrap();
+        fchdir(dir).unwrap();
+        assert_eq!(getcwd(), cwd.clone() + "child/");
+
+        chdir("..").unwrap();
+        close(dir).unwrap();
+
+        create("file").unwrap();
+        let file = open("file").unwrap();
+        assert!(matches!(fchdir(file), Err(FSError::NotADirectory)));
+        close(file).unwrap();
+        assert_eq!(getcwd(), cwd);
+    }
+
+    fn readdir_resumes() {
+        createdir("dir").unwrap();
+        for name in ["a", "b", "c"] {
+            create(&alloc::format!("dir/{name}")).unwrap();
+        }
+
+        let dir = open("dir").unwrap();
+        let mut entries = [unsafe { DirEntry::zeroed() }, unsafe { DirEntry::zeroed() }];
+        let mut names = Vec::new();
+
+        let count = readdir(dir, &mut entries).unwrap();
+        assert_eq!(count, 2);
+        names.extend(
+            entries
+                .iter()
+                .map(|entry| entry.name[..entry.name_length].to_vec()),
+        );
+
+        // entries created in between reads are still returned, the ones already read aren't
+        create("dir/d").unwrap();
+        loop {
+            let count = readdir(dir, &mut entries).unwrap();
+            if count == 0 {
+                break;
+            }
+            names.extend(
+                entries[..count]
+                    .iter()
+                    .map(|entry| entry.name[..entry.name_length].to_vec()),
+            );
+        }
+        close(dir).unwrap();
+
+        assert_eq!(names, [b"..".as_slice(), b"a", b"b", b"c", b"d"]);
+    }
+
+    fn remove_and_rename() {
+        createdir("moved").unwrap();
+        createdir("moved/sub").unwrap();
+        create("moved/file").unwrap();
+
+        assert!(matches!(remove("moved"), Err(FSError::DirectoryNotEmpty)));
+        assert!(matches!(
+            remove("missing"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+
+        // opened files stay usable after they are removed
+        let file = open("moved/file").unwrap();
+        write(file, b"data").unwrap();
+        remove("moved/file").unwrap();
+        assert!(matches!(
+            open("moved/file"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        let mut data = [0u8; 4];
+        assert_eq!(read(file, &mut data).unwrap(), 4);
+        assert_eq!(&data, b"data");
+        close(file).unwrap();
+
+        rename("moved/sub", "sub").unwrap();
+        assert!(matches!(
+            open("moved/sub"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        close(open("sub").unwrap()).unwrap();
+
+        assert!(matches!(
+            rename("sub", "sub/inner"),
+            Err(FSError::InvaildPath)
+        ));
+        assert!(matches!(
+            rename("sub", "moved"),
+            Err(FSError::AlreadyExists)
+        ));
+        assert!(matches!(
+            rename("ram:/sub", "sys:/sub"),
+            Err(FSError::OperationNotSupported)
+        ));
+
+        remove("sub").unwrap();
+        remove("moved").unwrap();
+        assert!(matches!(
+            open("moved"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+    }
+
+    fn tar_round_trip() {
+        let vfs = VFS_STRUCT.read();
+        let archive = vfs.pack_tar("sys:/").unwrap();
+
+        let mut tar = unsafe { TarArchiveIter::new(archive.as_ptr()) };
+        let mut files = 0;
+        while let Some(inode) = tar.next() {
+            if inode.kind != ustar::Type::NORMAL {
+                continue;
+            }
+
+            let mut file = vfs.open(&alloc::format!("sys:/{}", inode.name())).unwrap();
+            let mut data = alloc::vec![0u8; inode.data().len()];
+            assert_eq!(vfs.read(&mut file, &mut data).unwrap(), data.len());
+            vfs.close(&mut file).unwrap();
+
+            assert_eq!(inode.data(), data.as_slice());
+            files += 1;
+        }
+
+        assert!(files > 0);
+    }
+
+    fn ramfs_copy_on_write() {
+        static DATA: &[u8] = b"hello world";
+        let mut fs = RamFS::new();
+        fs.create_static("written", DATA).unwrap();
+        fs.create_static("truncated", DATA).unwrap();
+
+        let read_all = |path: &str| {
+            let inode = fs.reslove_path(path).unwrap();
+            let size = inode.size().unwrap();
+            let mut buffer = alloc::vec![0u8; size];
+            inode.read(&mut buffer, 0, size).unwrap();
+            buffer
+        };
+        assert_eq!(read_all("written"), DATA);
+
+        let written = fs.reslove_path("written").unwrap();
+        assert_eq!(written.write(b"HELLO", 0).unwrap(), 5);
+        written.write(b"!", DATA.len()).unwrap();
+        assert_eq!(read_all("written"), b"HELLO world!");
+
+        fs.reslove_path("truncated").unwrap().truncate(5).unwrap();
+        assert_eq!(read_all("truncated"), b"hello");
+        // the original data is never written to
+        assert_eq!(DATA, b"hello world");
+    }
+
+    fn gunzip_blocks() {
+        // `gzip -0` only makes stored blocks, small inputs get fixed huffman codes and bigger ones
+        // dynamic codes
+        let stored = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x18, 0x00, 0xe7,
+            0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x68,
+            0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0a, 0x00, 0x88, 0x59,
+            0x0b, 0x18, 0x00, 0x00, 0x00,
+        ];
+        let fixed = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00, 0x00, 0x88, 0x59, 0x0b, 0x18, 0x00, 0x00,
+            0x00,
+        ];
+        let dynamic = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x05, 0xc1, 0x01, 0x01,
+            0x00, 0x00, 0x08, 0x83, 0xb0, 0xac, 0xc0, 0xed, 0x5f, 0xc1, 0x4d, 0xc2, 0x20, 0x9d,
+            0x48, 0x87, 0x0f, 0x96, 0xea, 0x37, 0xe5, 0x14, 0x00, 0x00, 0x00,
+        ];
+
+        assert_eq!(gunzip(&stored).unwrap(), b"hello hello hello hello\n");
+        assert_eq!(gunzip(&fixed).unwrap(), b"hello hello hello hello\n");
+        assert_eq!(gunzip(&dynamic).unwrap(), b"bacabcaacbbdbabaceab");
+
+        let mut corrupted = fixed;
+        corrupted[fixed.len() - 8] ^= 1;
+        assert_eq!(gunzip(&corrupted), Err(InflateError::ChecksumMismatch));
+        assert_eq!(gunzip(&fixed[..20]), Err(InflateError::UnexpectedEnd));
+        assert_eq!(gunzip(b"not a gzip file"), Err(InflateError::InvaildHeader));
+    }
+
+    fn rofs_images() {
+        use crate::drivers::vfs::rofs::{RoFS, RoFSError};
+
+        // made by build/rofs.rs, dir/a and dir/copy share 4096 bytes of "abc" that are compressed
+        // followed by "0123456789" which is stored as is, and empty is an empty directory
+        static IMAGE: [u8; 303] = [
+            0x53, 0x41, 0x46, 0x41, 0x52, 0x4f, 0x46, 0x53, 0x01, 0x00, 0x00, 0x00, 0x00, 0x10,
+            0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xd8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x04, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x0a, 0x10,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+            0x0a, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x0a, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x06, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00,
+            0x00, 0x10, 0x00, 0x00, 0x25, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+            0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x2f, 0x64, 0x69, 0x72,
+            0x65, 0x6d, 0x70, 0x74, 0x79, 0x61, 0x63, 0x6f, 0x70, 0x79, 0x4b, 0x4c, 0x4a, 0x1e,
+            0x45, 0xa3, 0x68, 0x14, 0x8d, 0xa2, 0x51, 0x34, 0x8a, 0x46, 0xd1, 0x28, 0x1a, 0x45,
+            0xa3, 0x68, 0x14, 0x8d, 0xa2, 0x51, 0x34, 0x8a, 0x46, 0xd1, 0x30, 0x47, 0x00, 0x30,
+            0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+        ];
+
+        let mut expected: Vec<u8> = b"abc".iter().cycle().take(4096).copied().collect();
+        expected.extend(b"0123456789");
+
+        let mut fs = RoFS::new(&IMAGE).unwrap();
+        for path in ["/dir/a", "/dir/copy"] {
+            let mut file = fs.open(path).unwrap();
+            // in chunks that cross the end of the first block
+            let mut chunk = [0u8; 1000];
+            let mut data: Vec<u8> = Vec::new();
+            loop {
+                let read = fs.read(&mut file, &mut chunk).unwrap();
+                if read == 0 {
+                    break;
+                }
+                data.extend(&chunk[..read]);
+            }
+            assert_eq!(data, expected);
+            fs.close(&mut file).unwrap();
+        }
+
+        let mut dir = fs.open("/dir").unwrap();
+        let mut entries: [DirEntry; 4] = core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+        let count = fs.readdir(&mut dir, &mut entries).unwrap();
+        let names: Vec<&[u8]> = entries[..count]
+            .iter()
+            .map(|entry| &entry.name[..entry.name_length])
+            .collect();
+        assert_eq!(names, [b"a".as_slice(), b"copy"]);
+
+        let mut empty = fs.open("/empty").unwrap();
+        assert_eq!(fs.readdir(&mut empty, &mut entries).unwrap(), 0);
+        assert!(matches!(
+            fs.open("/dir/a/b"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        assert!(matches!(
+            fs.open("/missing"),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        assert!(matches!(
+            fs.create("/dir/new"),
+            Err(FSError::OperationNotSupported)
+        ));
+
+        assert_eq!(
+            RoFS::new(b"not an image").err(),
+            Some(RoFSError::NotAnImage)
+        );
+        assert_eq!(RoFS::new(&IMAGE[..100]).err(), Some(RoFSError::Corrupted));
+
+        // the compressed block ends early, which is only noticed once it is read
+        let mut image = IMAGE.to_vec();
+        let blocks_offset = u64::from_le_bytes(image[40..48].try_into().unwrap()) as usize;
+        image[blocks_offset + 8..blocks_offset + 12].copy_from_slice(&2u32.to_le_bytes());
+        let fs = RoFS::new(image.leak()).unwrap();
+        let mut file = fs.open("/dir/a").unwrap();
+        assert!(matches!(
+            fs.read(&mut file, &mut [0u8; 16]),
+            Err(FSError::Corrupted)
+        ));
+
+        // unless the kernel was built with a tar ramdisk sys: is an image and sys:/pkgs is bound
+        // from ram: to stay writable
+        if read_to_string("proc:/mounts").contains("sys: rofs\n") {
+            create("sys:/pkgs/rofs").unwrap();
+            remove("sys:/pkgs/rofs").unwrap();
+            assert!(matches!(
+                create("sys:/rofs"),
+                Err(FSError::OperationNotSupported)
+            ));
+        }
+    }
+
+    fn ninep_messages() {
+        use crate::drivers::virtio::ninep::{check_response, parse_dirents, Message};
+
+        // a Twalk (110) of fid 0 to fid 1 through "ab"
+        let walk = Message::new(110, 1).u32(0).u32(1).u16(1).str("ab").finish();
+        assert_eq!(
+            walk,
+            [21, 0, 0, 0, 110, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 2, 0, b'a', b'b']
+        );
+
+        // a Rlerror (7) with ENOENT
+        let error = Message::new(7, 1).u32(2).finish();
+        assert!(matches!(
+            check_response(&error, 110),
+            Err(FSError::NoSuchAFileOrDirectory)
+        ));
+        // a Rclunk (121) isn't the response to a Twalk
+        let clunk = Message::new(121, 1).finish();
+        assert!(check_response(&clunk, 120).is_ok());
+        assert!(matches!(
+            check_response(&clunk, 110),
+            Err(FSError::Corrupted)
+        ));
+        assert!(matches!(
+            check_response(&error[..9], 110),
+            Err(FSError::Corrupted)
+        ));
+
+        // qid[13] offset[8] type[1] name[s]
+        let entry = |name: &str, offset: u64| {
+            let mut entry = alloc::vec![0x80];
+            entry.extend([0; 12]);
+            entry.extend(offset.to_le_bytes());
+            entry.push(0x80);
+            entry.extend((name.len() as u16).to_le_bytes());
+            entry.extend(name.as_bytes());
+            entry
+        };
+        let mut data = entry(".", 1);
+        data.extend(entry("file", 2));
+        let dirents = parse_dirents(&data).unwrap();
+        let names: Vec<(&str, u64)> = dirents
+            .iter()
+            .map(|dirent| (dirent.name.as_str(), dirent.offset))
+            .collect();
+        assert_eq!(names, [(".", 1), ("file", 2)]);
+        assert!(matches!(
+            parse_dirents(&data[..data.len() - 1]),
+            Err(FSError::Corrupted)
+        ));
+
+        // only when the runner shares a directory with `share=<dir>`
+        if read_to_string("proc:/mounts").contains("host: hostfs\n") {
+            createdir("host:/ninep-test").unwrap();
+            create("host:/ninep-test/file").unwrap();
+
+            let fd = open("host:/ninep-test/file").unwrap();
+            let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+            assert_eq!(write(fd, &data).unwrap(), data.len());
+            close(fd).unwrap();
+
+            // more than fits in a single message
+            let fd = open("host:/ninep-test/file").unwrap();
+            let mut read_back = alloc::vec![0u8; data.len() + 1];
+            let mut total = 0;
+            loop {
+                let amount = read(fd, &mut read_back[total..]).unwrap();
+                if amount == 0 {
+                    break;
+                }
+                total += amount;
+            }
+            close(fd).unwrap();
+            assert_eq!(&read_back[..total], data);
+
+            rename("host:/ninep-test/file", "host:/ninep-test/moved").unwrap();
+            let fd = open("host:/ninep-test").unwrap();
+            let mut entries: [DirEntry; 4] =
+                core::array::from_fn(|_| unsafe { DirEntry::zeroed() });
+            let count = readdir(fd, &mut entries).unwrap();
+            close(fd).unwrap();
+            let names: Vec<&[u8]> = entries[..count]
+                .iter()
+                .map(|entry| &entry.name[..entry.name_length])
+                .collect();
+            assert_eq!(names, [b"moved".as_slice()]);
+
+            assert!(matches!(
+                remove("host:/ninep-test"),
+                Err(FSError::DirectoryNotEmpty)
+            ));
+            remove("host:/ninep-test/moved").unwrap();
+            remove("host:/ninep-test").unwrap();
+        }
+    }
+
+    fn out_of_memory() {
+        let too_much = (frame_allocator::free_frames() + 1) * PAGE_SIZE;
+
+        let mut data: Vec<u8> = Vec::new();
+        assert!(data.try_reserve(too_much).is_err());
+
+        create("big").unwrap();
+        let vfs = VFS_STRUCT.read();
+        let mut file = vfs.open(&alloc::format!("{}/big", getcwd())).unwrap();
+        file.write_pos = too_much;
+        assert!(matches!(
+            vfs.write(&mut file, b"data"),
+            Err(FSError::OutOfMemory)
+        ));
+        file.write_pos = usize::MAX;
+        assert!(matches!(
+            vfs.write(&mut file, b"data"),
+            Err(FSError::OutOfMemory)
+        ));
+
+        // the file is still usable afterwards
+        file.write_pos = 0;
+        assert_eq!(vfs.write(&mut file, b"data").unwrap(), 4);
+        vfs.close(&mut file).unwrap();
+    }
+
+    fn shrinkers() {
+        static CACHED: AtomicUsize = AtomicUsize::new(0);
+        static SHRINKER: Shrinker = Shrinker {
+            name: "test",
+            count: || CACHED.load(Ordering::Relaxed),
+            shrink: |target| {
+                let released = target.min(CACHED.load(Ordering::Relaxed));
+                CACHED.fetch_sub(released, Ordering::Relaxed);
+                released
+            },
+        };
+
+        shrinker::register(&SHRINKER);
+        CACHED.store(100, Ordering::Relaxed);
+        assert!(shrinker::meminfo().contains("shrinker test: cached=100 calls=0 released=0"));
+
+        assert!(shrinker::shrink(30) >= 30);
+        assert_eq!(CACHED.load(Ordering::Relaxed), 70);
+
+        // a failed allocation is retried once memory was released
+        let mut tries = 0;
+        let results: Result<(), ()> = shrinker::retry(10, || {
+            tries += 1;
+            if tries == 1 {
+                Err(())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(results, Ok(()));
+        assert!(shrinker::meminfo().contains("shrinker test: cached=60 calls=2 released=40"));
+
+        shrinker::unregister(&SHRINKER);
+        assert!(!shrinker::meminfo().contains("shrinker test"));
+    }
+
     fn userspace() {
-        let pid = pspawn("TEST_BOT", "sys:/bin/TestBot", &[], SpawnFlags::empty()).unwrap();
+        // `stress=1` in the command line runs TestBot's stress tests after the others
+        let argv: &[&str] = if super::cmdline_option("stress").is_some() {
+            &["TestBot", "stress"]
+        } else {
+            &[]
+        };
+
+        let pid = pspawn(
+            "TEST_BOT",
+            "sys:/bin/TestBot",
+            argv,
+            SpawnFlags::empty(),
+            ContextPriority::default(),
+        )
+        .unwrap();
         let ret = wait(pid);
 
         assert_eq!(ret, 0);
     }
 }
+
+/// masks the volatile parts of `data`, the value of every `field: value` line whose field is in
+/// `fields` becomes `*` and every hex number becomes `0x*`
+fn mask(data: &str, fields: &[&str]) -> String {
+    let mut results = String::new();
+
+    for line in data.lines() {
+        let trimmed = line.trim_start();
+        let field = fields.iter().find(|field| {
+            trimmed
+                .strip_prefix(**field)
+                .is_some_and(|rest| rest.starts_with(':'))
+        });
+
+        match field {
+            Some(field) => {
+                let indent = &line[..line.len() - trimmed.len()];
+                _ = writeln!(results, "{indent}{field}: *");
+            }
+            None => {
+                let mut rest = line;
+                while let Some(index) = rest.find("0x") {
+                    results.push_str(&rest[..index + 2]);
+                    results.push('*');
+                    rest = rest[index + 2..].trim_start_matches(|c: char| c.is_ascii_hexdigit());
+                }
+                _ = writeln!(results, "{rest}");
+            }
+        }
+    }
+
+    results
+}
+
+fn read_to_string(path: &str) -> String {
+    let fd = vfs::expose::open(path).unwrap_or_else(|err| panic!("failed to open {path}: {err:?}"));
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 512];
+
+    loop {
+        let count = vfs::expose::read(fd, &mut buffer).unwrap();
+        if count == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..count]);
+    }
+
+    vfs::expose::close(fd).unwrap();
+    String::from_utf8(data).expect("file isn't utf8")
+}
+
+/// removes `path` and everything in it
+fn remove_all(path: &str) {
+    let dir =
+        vfs::expose::open(path).unwrap_or_else(|err| panic!("failed to open {path}: {err:?}"));
+    let mut entries: [vfs::expose::DirEntry; 8] =
+        core::array::from_fn(|_| unsafe { vfs::expose::DirEntry::zeroed() });
+    let mut children = Vec::new();
+
+    loop {
+        let count = vfs::expose::readdir(dir, &mut entries).unwrap();
+        if count == 0 {
+            break;
+        }
+
+        for entry in &entries[..count] {
+            let name = String::from_utf8_lossy(&entry.name[..entry.name_length]).into_owned();
+            if name != ".." {
+                children.push((name, entry.kind));
+            }
+        }
+    }
+    vfs::expose::close(dir).unwrap();
+
+    for (name, kind) in children {
+        let child = format!("{path}/{name}");
+        if kind == vfs::InodeType::Directory {
+            remove_all(&child);
+        } else {
+            vfs::expose::remove(&child)
+                .unwrap_or_else(|err| panic!("failed to remove {child}: {err:?}"));
+        }
+    }
+    vfs::expose::remove(path).unwrap_or_else(|err| panic!("failed to remove {path}: {err:?}"));
+}
+
+/// compares `path` with `fields` masked against `sys:/snapshots/<name>`
+/// on a mismatch the masked output is printed over serial between `[SNAPSHOT] name=<name>` and
+/// `[SNAPSHOT END]` so that the snapshot can be updated if the change was on purpose
+fn assert_snapshot(name: &str, path: &str, fields: &[&str]) {
+    let actual = mask(&read_to_string(path), fields);
+    let expected = read_to_string(&format!("sys:/snapshots/{name}"));
+
+    if actual != expected {
+        serial!("[SNAPSHOT] name={name}\n{actual}[SNAPSHOT END]\n");
+        panic!("{path} doesn't match the snapshot {name}");
+    }
+}
+
+#[test_module]
+pub mod snapshot_module {
+    use super::assert_snapshot;
+
+    fn cpuinfo() {
+        assert_snapshot(
+            "cpuinfo",
+            "proc:/cpuinfo",
+            &["frequency", "idle", "context switches", "irqs"],
+        );
+    }
+
+    fn drivers() {
+        assert_snapshot(
+            "drivers",
+            "proc:/drivers",
+            &["polls", "poll time", "max wake latency"],
+        );
+    }
+
+    fn kernelinfo() {
+        assert_snapshot(
+            "kernelinfo",
+            "proc:/kernelinfo",
+            &[
+                "commit",
+                "built",
+                "rustc",
+                "features",
+                "profile",
+                "boot memory",
+                "boot arch",
+                "boot devices",
+                "boot vfs",
+                "boot ramdisk",
+                "boot drivers",
+                "boot total",
+            ],
+        );
+    }
+
+    fn memmap() {
+        assert_snapshot("memmap", "proc:/memmap", &[]);
+    }
+}
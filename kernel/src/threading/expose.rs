@@ -2,26 +2,41 @@ use core::arch::asm;
 
 use alloc::{
     string::{String, ToString},
+    sync::Arc,
     vec,
     vec::Vec,
 };
 use bitflags::bitflags;
 
 use crate::{
-    drivers::vfs::{
-        expose::{fstat, open, read, DirEntry},
-        FSError, FSResult, InodeType, VFS_STRUCT,
+    arch::threading::Registers,
+    drivers::{
+        surface::Surface,
+        vfs::{
+            self,
+            expose::{fstat, open, read, DirEntry},
+            namespace::Namespace,
+            FSError, FSResult, FileDescriptor, InodeType, Path, FS, VFS_STRUCT,
+        },
     },
     khalt,
+    memory::{paging::MapToError, shrinker, VirtAddr},
     threading::processes::Process,
-    utils::elf::{Elf, ElfError},
+    utils::{
+        elf::{Elf, ElfError},
+        errors::{Context, ErrorContext, ErrorStatus},
+    },
 };
 
-use super::processes::{ProcessInfo, ProcessState};
+use super::processes::{
+    Capabilities, ContextPriority, ExitStatus, ProcessFlags, ProcessInfo, ProcessState,
+    ProcessStatus, ROOT_UID,
+};
+use super::resources::Resource;
 
 #[no_mangle]
 pub fn thread_exit(code: usize) {
-    super::with_current(|process| process.terminate(code, 0));
+    super::with_current(|process| process.terminate(ExitStatus::exited(code)));
     // enables interrupts if they were disabled to give control back to the scheduler
     #[cfg(target_arch = "x86_64")]
     unsafe {
@@ -32,88 +47,293 @@ pub fn thread_exit(code: usize) {
 
 #[no_mangle]
 pub fn thread_yeild() {
+    #[cfg(feature = "lockdep")]
+    crate::utils::locks::lockdep::assert_none_held("yielding");
+    super::YIELDED.store(true, core::sync::atomic::Ordering::Relaxed);
     #[cfg(target_arch = "x86_64")]
     unsafe {
         asm!("int 0x20")
     }
 }
 
+/// puts the current process to sleep until `ticks` scheduler ticks pass, 0 only yields
+pub fn thread_sleep(ticks: usize) {
+    if ticks != 0 {
+        // registered while the scheduler is locked so the timer can't fire before the process is asleep
+        super::with_current(|process| {
+            process.status = ProcessStatus::Sleeping;
+            process.sleep_timer = Some(super::timer::register(
+                super::ticks() + ticks as u64,
+                process.pid,
+            ));
+        });
+    }
+
+    thread_yeild();
+}
+
+/// forgets any wake up of the current process so far, called before the process starts checking what it
+/// waits for, returns the pid of the current process
+pub fn prepare_wait() -> usize {
+    super::with_current(|process| {
+        process.woken = false;
+        process.pid
+    })
+}
+
+/// puts the current process to sleep until it's woken up by a timer or a wait queue (see `waitqueue`),
+/// doesn't sleep if that already happened since `prepare_wait` or the last `thread_wait`
+pub fn thread_wait() {
+    let woken = super::with_current(|process| {
+        let woken = core::mem::take(&mut process.woken);
+        if !woken {
+            process.status = ProcessStatus::Sleeping;
+        }
+        woken
+    });
+
+    if !woken {
+        thread_yeild();
+    }
+}
+
 #[no_mangle]
 /// waits for `pid` to exit
-/// returns it's exit code after cleaning it up
+/// returns it's exit code after cleaning it up, 0 if it doesn't exist, see `wait_status`
 pub fn wait(pid: usize) -> usize {
+    wait_status(pid).map_or(0, ExitStatus::code)
+}
+
+/// waits for `pid` to exit
+/// returns how it exited after cleaning it up, or `ErrorStatus::InvaildPid` if it doesn't exist
+pub fn wait_status(pid: usize) -> Result<ExitStatus, ErrorStatus> {
     // loops through the processes until it finds the process with `pid` as a zombie
     loop {
         // cycles through the processes one by one untils it finds the process with `pid`
-        // returns the exit code of the process if it's a zombie and cleans it up
+        // returns the exit status of the process if it's a zombie and cleans it up
         // if it's not a zombie it will be caught by the next above loop
         let found = super::find(
             |process| process.pid == pid,
-            |process| {
-                if let ProcessState::Zombie(ref state) = process.state {
-                    let exit_code = state.exit_code;
-                    Some(exit_code)
-                } else {
-                    None
-                }
-            },
+            |process| process.exit_status(),
         );
 
         return match found {
-            Some(Some(exit_code)) => {
+            Some(Some(exit_status)) => {
                 // cleans up the process
                 super::remove(|p| p.pid == pid);
-                exit_code
+                Ok(exit_status)
             }
             Some(None) => {
                 thread_yeild();
                 continue;
             }
-            None => 0,
+            None => Err(ErrorStatus::InvaildPid),
         };
     }
 }
 
+/// like `wait` but returns None instead of waiting if `pid` didn't exit yet
+pub fn try_wait(pid: usize) -> Option<usize> {
+    let found = super::find(
+        |process| process.pid == pid,
+        |process| process.exit_status().map(ExitStatus::code),
+    );
+
+    match found {
+        Some(Some(exit_code)) => {
+            super::remove(|p| p.pid == pid);
+            Some(exit_code)
+        }
+        Some(None) => None,
+        None => Some(0),
+    }
+}
+
+/// waits for any child of the current process to exit, returns it's pid and exit code after cleaning it up
+/// returns `ErrorStatus::InvaildPid` if the current process has no children and `ErrorStatus::Busy` if
+/// `nonblocking` and none of them exited yet
+pub fn wait_any(nonblocking: bool) -> Result<(usize, usize), ErrorStatus> {
+    let current_pid = super::with_current(|process| process.pid);
+
+    loop {
+        let zombie = super::find(
+            |process| process.ppid() == current_pid && process.exit_status().is_some(),
+            |process| {
+                let exit_status = process.exit_status().unwrap();
+                (process.pid, exit_status.code())
+            },
+        );
+
+        if let Some((pid, exit_code)) = zombie {
+            super::remove(|p| p.pid == pid);
+            return Ok((pid, exit_code));
+        }
+
+        if super::find(|process| process.ppid() == current_pid, |_| ()).is_none() {
+            return Err(ErrorStatus::InvaildPid);
+        }
+
+        if nonblocking {
+            return Err(ErrorStatus::Busy);
+        }
+        thread_yeild();
+    }
+}
+
 #[no_mangle]
 pub fn getinfo(pid: usize) -> Option<ProcessInfo> {
-    super::find(|p| p.pid == pid, |p| p.info())
+    super::with_process(pid, |p| p.info())
+}
+
+/// returns the ri and description of every resource `pid` has open, None if it doesn't exist or
+/// is a zombie
+/// locks the scheduler so it can't be called while reading a file
+pub fn presources(pid: usize) -> Option<Vec<(usize, String)>> {
+    super::with_process(pid, |p| match &p.state {
+        ProcessState::Alive(state) => Some(state.resource_manager.lock().describe()),
+        _ => None,
+    })
+    .flatten()
+}
+
+/// generates the data of proc:/<pid>/maps, None if `pid` doesn't exist or is a zombie
+/// locks the scheduler so it can't be called while reading a file
+pub fn pmaps(pid: usize) -> Option<String> {
+    super::with_process(pid, |p| match &p.state {
+        ProcessState::Alive(state) => Some(state.maps()),
+        _ => None,
+    })
+    .flatten()
+}
+
+/// generates the data of proc:/<pid>/memory, None if `pid` doesn't exist or is a zombie
+/// locks the scheduler so it can't be called while reading a file
+pub fn pmemory(pid: usize) -> Option<String> {
+    super::with_process(pid, |p| match &p.state {
+        ProcessState::Alive(state) => Some(state.memory_info()),
+        _ => None,
+    })
+    .flatten()
+}
+
+/// generates the data of proc:/<pid>/threads/<tid>, processes only have a single thread so `tid`
+/// is a pid, None if it doesn't exist
+/// locks the scheduler so it can't be called while reading a file
+pub fn pthread_status(tid: usize) -> Option<String> {
+    super::with_process(tid, |p| p.thread_status())
+}
+
+/// stores `message` as the reason the current process is about to exit, replacing the previous one
+pub fn report_abort(message: String) {
+    super::with_current_state(|state| state.abort_message = Some(message));
+}
+
+/// generates the data of proc:/<pid>/abort, the message `pid` reported with `report_abort` or
+/// nothing, None if it doesn't exist
+/// locks the scheduler so it can't be called while reading a file
+pub fn pabort(pid: usize) -> Option<String> {
+    super::with_process(pid, |p| match p.abort_message() {
+        Some(message) => alloc::format!("{message}\n"),
+        None => String::new(),
+    })
 }
 
 pub fn getpids() -> Vec<usize> {
     let mut pids = Vec::with_capacity(super::pcount());
     super::for_each(|process| pids.push(process.pid));
-
     pids
 }
+
+/// returns the pid and name of the current process, or None if the scheduler is locked
+#[cfg(feature = "debug-poison")]
+pub fn try_current_ident() -> Option<(usize, crate::utils::types::Name)> {
+    super::try_with_current(|current| (current.pid, current.name))
+}
+
+pub fn getpid() -> usize {
+    super::with_current(|process| process.pid)
+}
+
+/// returns wether or not `pid` is `ancestor` or one of it's descendants
+pub fn is_descendant(pid: usize, ancestor: usize) -> bool {
+    let Some(ppid) = super::find(|p| p.pid == pid, |process| process.ppid()) else {
+        return false;
+    };
+    can_terminate(ppid, pid, ancestor)
+}
+
+/// returns wether or not a process with `pid` exists without locking the scheduler
+pub fn pexists(pid: usize) -> bool {
+    super::find(|p| p.pid == pid, |_| ()).is_some()
+}
 bitflags! {
     #[derive(Debug, Clone, Copy)]
     #[repr(C)]
     pub struct SpawnFlags: u8 {
         const CLONE_RESOURCES = 1 << 0;
         const CLONE_CWD = 1 << 1;
+        /// the child (and it's children) can only read from the TTY while it's the TTY's
+        /// foreground process, see `TTYCtl`
+        const BACKGROUND = 1 << 2;
     }
 }
 
+/// the files the stdin, stdout and stderr of a spawned process are replaced with, None keeps what
+/// it would have gotten from the flags
+pub type Stdio = [Option<FileDescriptor>; 3];
+
 pub fn spawn(
     name: &str,
     elf_bytes: &[u8],
     argv: &[&str],
     flags: SpawnFlags,
-) -> Result<usize, ElfError> {
+    priority: ContextPriority,
+) -> Result<usize, ErrorContext<ElfError>> {
+    spawn_with_stdio(
+        name,
+        elf_bytes,
+        argv,
+        flags,
+        priority,
+        Stdio::default(),
+        Capabilities::empty(),
+    )
+}
+
+/// like `spawn` but replaces the child's stdin, stdout and stderr with `stdio`
+/// the child gets the `Capabilities::INHERITED` of the caller's capabilities and those of `grant`
+/// the caller has, a child can never have capabilities the caller doesn't
+pub fn spawn_with_stdio(
+    name: &str,
+    elf_bytes: &[u8],
+    argv: &[&str],
+    flags: SpawnFlags,
+    priority: ContextPriority,
+    stdio: Stdio,
+    grant: Capabilities,
+) -> Result<usize, ErrorContext<ElfError>> {
     let cwd = if flags.contains(SpawnFlags::CLONE_CWD) {
         getcwd().to_string()
     } else {
         String::from("ram:/")
     };
 
-    let elf = Elf::new(elf_bytes)?;
+    let elf = Elf::new(elf_bytes).context("elf header")?;
 
-    let current_pid = super::with_current(|p| p.pid);
+    let (current_pid, capabilities, uid, current_flags) =
+        super::with_current(|p| (p.pid, p.capabilities, p.uid, p.flags));
     let mut process = Process::from_elf(current_pid, elf, name, cwd, argv)?;
+    process.capabilities = capabilities & (Capabilities::INHERITED | grant);
+    process.uid = uid;
+    process.priority = priority;
+    if flags.contains(SpawnFlags::BACKGROUND) || current_flags.contains(ProcessFlags::BACKGROUND) {
+        process.flags |= ProcessFlags::BACKGROUND;
+    }
 
     let ProcessState::Alive(ref mut state) = process.state else {
         unreachable!()
     };
+    state.namespace = namespace();
     // handles the flags
     if flags.contains(SpawnFlags::CLONE_RESOURCES) {
         let clone =
@@ -121,25 +341,122 @@ pub fn spawn(
         state.resource_manager.lock().overwrite_resources(clone);
     }
 
+    let mut resources = state.resource_manager.lock();
+    for (ri, fd) in stdio.into_iter().enumerate() {
+        let Some(fd) = fd else {
+            continue;
+        };
+
+        let added = resources.add_resource(Resource::File(fd));
+        if added != ri {
+            let (_, removed) = resources.dup2(added, ri).unwrap();
+            if let Some(Resource::File(mut fd)) = removed {
+                _ = VFS_STRUCT.read().close(&mut fd);
+            }
+            // the copy in `ri` is now the only one that gets closed
+            _ = resources.remove_resource(added);
+        }
+    }
+    drop(resources);
+
     let pid = super::add_process(process);
     Ok(pid)
 }
 
+/// spawns a kernel process running `function` in it's own address space, `function` has to exit with
+/// `thread_exit`
+/// the process starts with a clone of the current process's resources and cwd
+pub fn kspawn(name: &str, function: fn() -> !) -> Result<usize, MapToError> {
+    kspawn_with(name, function, ProcessFlags::empty())
+}
+
+/// like `kspawn` but with `flags`, `ProcessFlags::UNTRUSTED` processes start with no resources and
+/// see only the global drives
+pub fn kspawn_with(
+    name: &str,
+    function: fn() -> !,
+    flags: ProcessFlags,
+) -> Result<usize, MapToError> {
+    let page_table_addr = crate::memory::paging::allocate_pml4()?;
+    let current_pid = super::with_current(|process| process.pid);
+
+    let mut process = Process::new(
+        function as usize,
+        current_pid,
+        0,
+        name,
+        &[],
+        0,
+        page_table_addr,
+        getcwd(),
+        flags,
+    )?;
+
+    if flags.contains(ProcessFlags::UNTRUSTED) {
+        return Ok(super::add_process(process));
+    }
+
+    let ProcessState::Alive(ref mut state) = process.state else {
+        unreachable!()
+    };
+    state.namespace = namespace();
+    let clone = super::with_current_state(|state| state.resource_manager.lock().clone_resources());
+    state.resource_manager.lock().overwrite_resources(clone);
+
+    Ok(super::add_process(process))
+}
+
 /// spawns an elf process from a path
-pub fn pspawn(name: &str, path: &str, argv: &[&str], flags: SpawnFlags) -> Result<usize, FSError> {
-    let file = open(path)?;
+pub fn pspawn(
+    name: &str,
+    path: &str,
+    argv: &[&str],
+    flags: SpawnFlags,
+    priority: ContextPriority,
+) -> Result<usize, ErrorContext<FSError>> {
+    pspawn_with_stdio(
+        name,
+        path,
+        argv,
+        flags,
+        priority,
+        &[None; 3],
+        Capabilities::empty(),
+    )
+}
+
+/// like `pspawn` but the child's stdin, stdout and stderr are opened from the paths in `stdio`, None
+/// keeps what it would have gotten from the flags, the child is granted `grant` see `spawn_with_stdio`
+pub fn pspawn_with_stdio(
+    name: &str,
+    path: &str,
+    argv: &[&str],
+    flags: SpawnFlags,
+    priority: ContextPriority,
+    stdio: &[Option<Path>; 3],
+    grant: Capabilities,
+) -> Result<usize, ErrorContext<FSError>> {
+    let file = open(path).context("open")?;
 
     let mut stat = unsafe { DirEntry::zeroed() };
-    fstat(file, &mut stat)?;
+    fstat(file, &mut stat).context("stat")?;
 
     if stat.kind != InodeType::File {
-        return Err(FSError::NotAFile);
+        return Err(FSError::NotAFile.into());
     }
 
     let mut buffer = vec![0; stat.size];
 
-    read(file, &mut buffer)?;
-    spawn(name, &buffer, argv, flags).map_err(|_| FSError::NotExecuteable)
+    read(file, &mut buffer).context("read")?;
+
+    let mut fds = Stdio::default();
+    for (fd, path) in fds.iter_mut().zip(stdio) {
+        if let Some(path) = path {
+            *fd = Some(VFS_STRUCT.read().open(path).context("stdio")?);
+        }
+    }
+    spawn_with_stdio(name, &buffer, argv, flags, priority, fds, grant)
+        .map_err(|err| err.map_err(|_| FSError::NotExecuteable).context("exec"))
 }
 
 /// also ensures the cwd ends with /
@@ -147,15 +464,25 @@ pub fn pspawn(name: &str, path: &str, argv: &[&str], flags: SpawnFlags) -> Resul
 #[no_mangle]
 pub fn chdir(new_dir: &str) -> FSResult<()> {
     let new_dir = VFS_STRUCT.read().verify_path_dir(new_dir)?;
+    set_cwd(new_dir);
+    Ok(())
+}
+
+/// changes the cwd to the directory `ri`, unlike `chdir` the directory can't be replaced between
+/// checking it and changing to it
+pub fn fchdir(ri: usize) -> FSResult<()> {
+    let new_dir = vfs::expose::dir_path(ri)?;
+    set_cwd(new_dir);
+    Ok(())
+}
 
+fn set_cwd(new_dir: String) {
     super::with_current_state(move |state| {
         state.current_dir = new_dir;
-        // TODO: implement a Path type with abillity to append paths to prevent this, and also to
-        // prevent path's like ram:/dir/../dir/ from existing idiots
+        // `new_dir` is normalized, relative paths are appended to the cwd so it has to end with a `/`
         if !state.current_dir.ends_with('/') {
             state.current_dir.push('/');
         }
-        Ok(())
     })
 }
 
@@ -164,6 +491,205 @@ pub fn getcwd() -> String {
     super::with_current_state(|state| state.current_dir.clone())
 }
 
+/// the mount namespace of the current process, see `vfs::namespace`
+pub fn namespace() -> Arc<Namespace> {
+    super::with_current_state(|state| state.namespace.clone())
+}
+
+/// changes the mount namespace of the current process, it is copied first if it's shared with
+/// another process
+pub fn with_namespace_mut<R>(then: impl FnOnce(&mut Namespace) -> R) -> R {
+    super::with_current_state(|state| then(Arc::make_mut(&mut state.namespace)))
+}
+
+/// replaces the mount namespace of the current process with `namespace`
+pub fn set_namespace(namespace: Arc<Namespace>) {
+    super::with_current_state(|state| state.namespace = namespace)
+}
+
+/// returns wether or not the current process has all of `capabilities`
+pub fn has_capabilities(capabilities: Capabilities) -> bool {
+    super::with_current(|process| process.capabilities.contains(capabilities))
+}
+
+/// the uid of the current process
+pub fn getuid() -> u32 {
+    super::with_current(|process| process.uid)
+}
+
+/// changes the uid of the current process, only `ROOT_UID` can change it's uid and a process that
+/// leaves `ROOT_UID` loses all of it's capabilities, there is no way back since there is no setuid bit
+/// so a service drops it's privileges once it's done with them
+pub fn setuid(uid: u32) -> Result<(), ErrorStatus> {
+    super::with_current(|process| {
+        if process.uid == uid {
+            return Ok(());
+        }
+
+        if process.uid != ROOT_UID {
+            return Err(ErrorStatus::MissingPermissions);
+        }
+
+        process.uid = uid;
+        process.capabilities = Capabilities::empty();
+        Ok(())
+    })
+}
+
+/// returns wether or not the current process is a userspace process
+pub fn is_userspace() -> bool {
+    super::with_current(|process| process.flags.contains(ProcessFlags::USERSPACE))
+}
+
+/// returns wether or not `pid` was spawned in the background, see `SpawnFlags::BACKGROUND`
+pub fn is_background(pid: usize) -> bool {
+    super::find(
+        |process| process.pid == pid,
+        |process| process.flags.contains(ProcessFlags::BACKGROUND),
+    )
+    .unwrap_or(false)
+}
+
+/// returns wether or not the pointers the current process passes to syscalls have to be validated
+pub fn is_untrusted() -> bool {
+    super::with_current(|process| {
+        process
+            .flags
+            .intersects(ProcessFlags::USERSPACE | ProcessFlags::UNTRUSTED)
+    })
+}
+
+/// executes `then` on the alive userspace process `pid` if the current process is allowed to debug it
+/// a process cannot debug itself
+fn with_debuggee<T, R>(pid: usize, then: T) -> Result<R, ErrorStatus>
+where
+    T: FnOnce(&mut Process) -> Result<R, ErrorStatus>,
+{
+    if !has_capabilities(Capabilities::DEBUG) {
+        return Err(ErrorStatus::MissingPermissions);
+    }
+
+    if super::with_current(|current| current.pid) == pid {
+        return Err(ErrorStatus::InvaildPid);
+    }
+
+    super::with_process(pid, |process| {
+        if process.flags.contains(ProcessFlags::USERSPACE)
+            && matches!(process.state, ProcessState::Alive(_))
+        {
+            then(process)
+        } else {
+            Err(ErrorStatus::InvaildPid)
+        }
+    })
+    .unwrap_or(Err(ErrorStatus::InvaildPid))
+}
+
+/// reads `buffer.len()` bytes at `addr` in the address space of `pid`
+pub fn debug_read(pid: usize, addr: usize, buffer: &mut [u8]) -> Result<(), ErrorStatus> {
+    with_debuggee(pid, |process| {
+        let ProcessState::Alive(ref state) = process.state else {
+            unreachable!()
+        };
+
+        state
+            .read_memory(addr, buffer)
+            .then_some(())
+            .ok_or(ErrorStatus::InvaildPtr)
+    })
+}
+
+/// writes `buffer` at `addr` in the address space of `pid`
+pub fn debug_write(pid: usize, addr: usize, buffer: &[u8]) -> Result<(), ErrorStatus> {
+    with_debuggee(pid, |process| {
+        let ProcessState::Alive(ref state) = process.state else {
+            unreachable!()
+        };
+
+        state
+            .write_memory(addr, buffer)
+            .then_some(())
+            .ok_or(ErrorStatus::InvaildPtr)
+    })
+}
+
+/// stops `pid` from being scheduled until `debug_resume` is called on it
+/// if `pid` was switched out in the kernel it may be holding kernel locks, so it is only stopped the next
+/// time it is switched out in userspace, until then it's status isn't `ProcessStatus::Suspended`
+pub fn debug_suspend(pid: usize) -> Result<(), ErrorStatus> {
+    with_debuggee(pid, |process| {
+        if process.status == ProcessStatus::Waiting && process.context.cs & 3 == 3 {
+            process.status = ProcessStatus::Suspended;
+        } else if process.status != ProcessStatus::Suspended {
+            process.stop_pending = true;
+        }
+        Ok(())
+    })
+}
+
+/// lets `pid` be scheduled again after `debug_suspend`, cancels the suspension if it wasn't stopped yet
+pub fn debug_resume(pid: usize) -> Result<(), ErrorStatus> {
+    with_debuggee(pid, |process| {
+        if process.stop_pending {
+            process.stop_pending = false;
+            return Ok(());
+        }
+
+        if process.status != ProcessStatus::Suspended {
+            return Err(ErrorStatus::Generic);
+        }
+
+        process.status = ProcessStatus::Waiting;
+        Ok(())
+    })?;
+
+    super::wake(pid);
+    Ok(())
+}
+
+/// returns the registers of `pid` as they were the last time it was switched out
+pub fn debug_registers(pid: usize) -> Result<Registers, ErrorStatus> {
+    with_debuggee(pid, |process| Ok(process.context.registers()))
+}
+
+/// captures the return addresses of `pid` into `frames` by walking it's frame pointers, returns the
+/// amount captured
+/// the current process is captured where it invoked this syscall, other processes where they were last
+/// switched out, only the current process, it's descendants or processes that can be debugged can be captured
+pub fn backtrace(pid: usize, frames: &mut [usize]) -> Result<usize, ErrorStatus> {
+    let current_pid = super::with_current(|current| current.pid);
+
+    if pid == current_pid {
+        if !is_userspace() {
+            return Err(ErrorStatus::NotSupported);
+        }
+
+        let context = unsafe { crate::arch::syscalls::caller_context() };
+        return Ok(super::with_current_state(|state| {
+            state.backtrace(
+                context.frame.insturaction as usize,
+                context.rbp as usize,
+                frames,
+            )
+        }));
+    }
+
+    let ppid =
+        super::find(|p| p.pid == pid, |process| process.ppid()).ok_or(ErrorStatus::InvaildPid)?;
+    if !can_terminate(ppid, pid, current_pid) && !has_capabilities(Capabilities::DEBUG) {
+        return Err(ErrorStatus::MissingPermissions);
+    }
+
+    super::with_process(pid, |process| {
+        let ProcessState::Alive(ref state) = process.state else {
+            return Err(ErrorStatus::InvaildPid);
+        };
+        let registers = process.context.registers();
+        Ok(state.backtrace(registers.rip as usize, registers.rbp as usize, frames))
+    })
+    .unwrap_or(Err(ErrorStatus::InvaildPid))
+}
+
 fn can_terminate(mut process_ppid: usize, process_pid: usize, terminator_pid: usize) -> bool {
     if process_ppid == terminator_pid || process_pid == terminator_pid {
         return true;
@@ -173,25 +699,24 @@ fn can_terminate(mut process_ppid: usize, process_pid: usize, terminator_pid: us
         if process_ppid == terminator_pid {
             return true;
         }
-        process_ppid = super::find(|p| p.pid == process_ppid, |process| process.ppid).unwrap_or(0);
+        process_ppid =
+            super::find(|p| p.pid == process_ppid, |process| process.ppid()).unwrap_or(0);
     }
 
     false
 }
 
 fn terminate(process_pid: usize, terminator_pid: usize) {
-    super::for_each(|process| {
-        if process.pid == process_pid {
-            process.terminate(1, terminator_pid);
-        }
+    super::with_process(process_pid, |process| {
+        process.terminate(ExitStatus::killed(terminator_pid))
     });
 
     // moves the parentership of all processes with `ppid` as `process_pid` to `terminator_pid`
     // prevents orphan processes from being left behind
     // TODO: figure out if orphan processes should be killed
     super::for_each(|p| {
-        if p.ppid == process_pid {
-            p.ppid = terminator_pid;
+        if p.ppid() == process_pid {
+            p.set_ppid(terminator_pid);
         }
     });
 }
@@ -205,7 +730,7 @@ pub fn pkill(pid: usize) -> Result<(), ()> {
     }
 
     let (process_ppid, process_pid) =
-        super::find(|p| p.pid == pid, |process| (process.ppid, process.pid)).ok_or(())?;
+        super::find(|p| p.pid == pid, |process| (process.ppid(), process.pid)).ok_or(())?;
     if can_terminate(process_ppid, process_pid, current_pid) {
         terminate(process_pid, current_pid);
         return Ok(());
@@ -238,5 +763,61 @@ pub fn pcollect(info: &mut [ProcessInfo]) -> Result<(), ()> {
 /// returns the new program break ptr
 /// on fail returns null
 pub fn sbrk(amount: isize) -> *mut u8 {
-    super::with_current_state(|state| state.extend_data_by(amount)).unwrap_or(core::ptr::null_mut())
+    let extend = || super::with_current_state(|state| state.extend_data_by(amount));
+    let extend = || shrinker::retry(amount.unsigned_abs(), extend);
+    #[cfg(feature = "oom-killer")]
+    let extend = || oom_retry(extend);
+    extend().unwrap_or(core::ptr::null_mut())
+}
+
+/// calls `then` until it succeeds, killing a process with `oom_kill` after each failure
+#[cfg(feature = "oom-killer")]
+fn oom_retry<T, E>(then: impl Fn() -> Result<T, E>) -> Result<T, E> {
+    loop {
+        match then() {
+            Err(_) if oom_kill() => continue,
+            results => return results,
+        }
+    }
+}
+
+/// kills the userspace process with the biggest heap to free memory, the current process and the
+/// processes spawned by the kernel (like the shell) are never picked
+/// returns false if there was nothing to kill
+#[cfg(feature = "oom-killer")]
+fn oom_kill() -> bool {
+    let current_pid = getpid();
+    let mut victim: Option<(usize, usize)> = None;
+
+    super::while_each(|process| {
+        let ProcessState::Alive(ref state) = process.state else {
+            return true;
+        };
+
+        if process.pid == current_pid
+            || process.entry.ppid() == 0
+            || !process.flags.contains(ProcessFlags::USERSPACE)
+        {
+            return true;
+        }
+
+        let pages = state.data_pages();
+        if victim.is_none_or(|(_, most)| pages > most) {
+            victim = Some((process.pid, pages));
+        }
+        true
+    });
+
+    let Some((pid, pages)) = victim else {
+        return false;
+    };
+
+    crate::cross_println!("out of memory, killed process {} ({} pages)", pid, pages);
+    terminate(pid, 0);
+    true
+}
+
+/// maps `surface` in the current process, returns where it is mapped
+pub fn map_surface(surface: Arc<Surface>) -> Result<VirtAddr, MapToError> {
+    super::with_current_state(|state| state.map_surface(surface))
 }
@@ -1,6 +1,9 @@
 pub mod expose;
 pub mod processes;
 pub mod resources;
+pub mod timer;
+pub mod waitqueue;
+pub mod watchdog;
 
 pub const STACK_SIZE: usize = PAGE_SIZE * 6;
 pub const STACK_START: usize = 0x00007A3000000000;
@@ -9,17 +12,30 @@ pub const STACK_END: usize = STACK_START + STACK_SIZE;
 pub const RING0_STACK_START: usize = 0x00007A0000000000;
 pub const RING0_STACK_END: usize = RING0_STACK_START + STACK_SIZE;
 
+/// where surfaces are mapped in userspace processes
+pub const SURFACES_START: usize = 0x00007C0000000000;
+
 pub const ENVIROMENT_START: usize = 0x00007E0000000000;
 pub const ARGV_START: usize = ENVIROMENT_START + 0xA000000000;
 pub const ARGV_SIZE: usize = PAGE_SIZE * 4;
+pub const ARGV_END: usize = ARGV_START + ARGV_SIZE;
 
-use core::arch::asm;
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
 use lazy_static::lazy_static;
 use processes::{
-    AliveProcessState, Process, ProcessFlags, ProcessInfo, ProcessState, ProcessStatus,
+    AliveProcessState, ContextPriority, Process, ProcessEntry, ProcessFlags, ProcessInfo,
+    ProcessState, ProcessStatus,
 };
 
-use alloc::string::String;
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet, vec_deque::VecDeque},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
 use spin::Mutex;
 
 use crate::{
@@ -29,12 +45,13 @@ use crate::{
         frame_allocator::Frame,
         paging::{current_root_table, EntryFlags, MapToError, Page, PageTable, PAGE_SIZE},
     },
-    utils::alloc::LinkedList,
+    utils::locks::rcu::Rcu,
 };
 
 /// allocates and maps an area starting from `$start` with size `$size` and returns `Result<(), MapToError>` in `$page_table`
+/// the area is writable and not executable, `$access` is added to it's flags
 macro_rules! alloc_map {
-    ($page_table: expr, $start: ident, $size: ident) => {
+    ($page_table: expr, $start: ident, $size: ident, $access: expr) => {
         let page_table = $page_table;
 
         const PAGES: usize = $size / PAGE_SIZE;
@@ -64,7 +81,7 @@ macro_rules! alloc_map {
             page_table.map_to(
                 page,
                 frames[i],
-                EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE | EntryFlags::PRESENT,
+                EntryFlags::WRITABLE | EntryFlags::PRESENT | EntryFlags::NO_EXECUTE | $access,
             )?;
         }
 
@@ -72,23 +89,62 @@ macro_rules! alloc_map {
     };
 }
 
-/// allocates and maps a stack to page_table
-pub fn alloc_stack(page_table: &mut PageTable) -> Result<(), MapToError> {
-    alloc_map!(page_table, STACK_START, STACK_SIZE);
+/// allocates and maps a stack to page_table, `access` is `EntryFlags::USER_ACCESSIBLE` for userspace processes
+pub fn alloc_stack(page_table: &mut PageTable, access: EntryFlags) -> Result<(), MapToError> {
+    alloc_map!(page_table, STACK_START, STACK_SIZE, access);
 }
 
-/// allocates and maps the argv area to `page_table`
-pub fn alloc_argv(page_table: &mut PageTable) -> Result<(), MapToError> {
-    alloc_map!(page_table, ARGV_START, ARGV_SIZE);
+/// allocates and maps the argv area to `page_table`, see `alloc_stack`
+pub fn alloc_argv(page_table: &mut PageTable, access: EntryFlags) -> Result<(), MapToError> {
+    alloc_map!(page_table, ARGV_START, ARGV_SIZE, access);
 }
 
 /// allocates and maps a ring0 stack to page_table
+/// never accessible from userspace, with SMAP the kernel can't even use it if it was
 pub fn alloc_ring0_stack(page_table: &mut PageTable) -> Result<(), MapToError> {
-    alloc_map!(page_table, RING0_STACK_START, STACK_SIZE);
+    alloc_map!(
+        page_table,
+        RING0_STACK_START,
+        STACK_SIZE,
+        EntryFlags::empty()
+    );
+}
+
+/// vruntime is accounted in ticks * `VRUNTIME_SCALE` / weight, every priority weight divides it
+pub const VRUNTIME_SCALE: u64 = 16;
+
+/// the processes of a single priority waiting to run
+struct RunQueue {
+    pids: VecDeque<usize>,
+    /// the ticks processes of this priority ran for scaled down by the priority's weight, the queue
+    /// with the lowest vruntime runs next so each priority gets a share of the CPU proportional to it's
+    /// weight
+    vruntime: u64,
+}
+
+impl RunQueue {
+    const fn new() -> Self {
+        Self {
+            pids: VecDeque::new(),
+            vruntime: 0,
+        }
+    }
 }
 
 pub struct Scheduler {
-    processes: LinkedList<Process>,
+    processes: BTreeMap<usize, Process>,
+    /// indexed by `ContextPriority`
+    run_queues: [RunQueue; ContextPriority::COUNT],
+    /// the vruntime of the last picked run queue, a queue that had nothing to run starts from here
+    /// instead of catching up on the time it didn't use
+    min_vruntime: u64,
+    /// processes that can't run (zombies, sleeping and suspended processes) are moved here instead of
+    /// being checked on every switch, see `wake`
+    blocked: BTreeSet<usize>,
+    /// the pid of the running process
+    current: usize,
+    /// the amount of ticks the current process can run for before it is preempted
+    slice_left: u64,
     next_pid: usize,
 }
 
@@ -98,7 +154,12 @@ unsafe impl Sync for Scheduler {}
 impl Scheduler {
     pub fn new() -> Self {
         Self {
-            processes: LinkedList::new(),
+            processes: BTreeMap::new(),
+            run_queues: [const { RunQueue::new() }; ContextPriority::COUNT],
+            min_vruntime: 0,
+            blocked: BTreeSet::new(),
+            current: 0,
+            slice_left: 0,
             next_pid: 0,
         }
     }
@@ -122,6 +183,7 @@ impl Scheduler {
         )
         .unwrap();
         add_process(process);
+        SCHEDULER.lock().run_next();
 
         // getting the context of the first process
         // like this so the scheduler read lock is released
@@ -133,64 +195,156 @@ impl Scheduler {
 
     /// gets a mutable reference to the current process
     fn current(&mut self) -> &mut Process {
-        unsafe { self.processes.current_mut().unwrap_unchecked() }
+        unsafe { self.processes.get_mut(&self.current).unwrap_unchecked() }
+    }
+
+    /// puts `pid` at the end of the run queue of `priority`
+    fn enqueue(&mut self, pid: usize, priority: ContextPriority) {
+        let queue = &mut self.run_queues[priority as usize];
+        if queue.pids.is_empty() {
+            queue.vruntime = queue.vruntime.max(self.min_vruntime);
+        }
+        queue.pids.push_back(pid);
+    }
+
+    /// pops the next process that can run from the run queue with the lowest vruntime and makes it
+    /// the current process, processes that can't run are moved to the blocked set on the way
+    /// returns false if there is nothing to run
+    fn run_next(&mut self) -> bool {
+        loop {
+            let Some(queue) = self
+                .run_queues
+                .iter_mut()
+                .filter(|queue| !queue.pids.is_empty())
+                .min_by_key(|queue| queue.vruntime)
+            else {
+                return false;
+            };
+
+            self.min_vruntime = self.min_vruntime.max(queue.vruntime);
+            let pid = unsafe { queue.pids.pop_front().unwrap_unchecked() };
+
+            match self.processes.get_mut(&pid) {
+                Some(process) if process.status == ProcessStatus::Waiting => {
+                    process.status = ProcessStatus::Running;
+                    self.slice_left = process.priority.timeslice();
+                    self.current = pid;
+                    return true;
+                }
+                Some(_) => _ = self.blocked.insert(pid),
+                // removed while waiting
+                None => {}
+            }
+        }
     }
 
     /// context switches into next process, takes current context outputs new context
-    pub unsafe fn switch(&mut self, context: CPUStatus) -> CPUStatus {
+    /// the current process keeps running until it's time slice is over unless it `yielded`
+    pub unsafe fn switch(&mut self, context: CPUStatus, yielded: bool) -> CPUStatus {
         unsafe { asm!("cli") }
 
-        self.current().context = context;
-        self.current().status = ProcessStatus::Waiting;
+        let current = self.current();
+        current.context = context;
 
-        for process in self.processes.continue_iter() {
-            if process.status == ProcessStatus::Waiting {
-                process.status = ProcessStatus::Running;
-                break;
-            }
+        // only stopped in userspace, in the kernel it may be holding locks other processes need
+        if current.stop_pending && current.status == ProcessStatus::Running && context.cs & 3 == 3 {
+            current.stop_pending = false;
+            current.status = ProcessStatus::Suspended;
+        }
+
+        let (pid, status, priority) = (current.pid, current.status, current.priority);
+        let vruntime = VRUNTIME_SCALE / priority.weight();
+        current.vruntime += vruntime;
+        self.run_queues[priority as usize].vruntime += vruntime;
+
+        if yielded {
+            watchdog::yielded(self.current());
+        } else if status == ProcessStatus::Running {
+            watchdog::check_thread(self.current());
+        }
+
+        self.slice_left = self.slice_left.saturating_sub(1);
+        if status == ProcessStatus::Running && self.slice_left > 0 && !yielded {
+            return context;
+        }
+
+        #[cfg(feature = "lockdep")]
+        {
+            self.current().held_locks = crate::utils::locks::lockdep::save();
+        }
+
+        // zombies, sleeping and suspended processes stay out of the queues
+        if status == ProcessStatus::Running {
+            self.current().status = ProcessStatus::Waiting;
+            self.enqueue(pid, priority);
+        } else {
+            self.blocked.insert(pid);
+        }
+
+        timer::advance(ticks(), |pid| _ = self.wake_sleeping(pid));
+
+        // there is always something to run as long as the first process doesn't exit
+        if !self.run_next() {
+            self.current = pid;
+        }
+        if self.current != pid {
+            crate::arch::cpu::count_context_switch();
         }
 
+        #[cfg(feature = "lockdep")]
+        crate::utils::locks::lockdep::restore(self.current().held_locks);
         self.current().context
     }
 
-    /// appends a process to the end of the scheduler Processes list
-    /// returns the pid of the added process
-    pub fn add_process(&mut self, mut process: Process) -> usize {
+    /// adds a process to the scheduler and queues it to run
+    /// returns the entry of the added process, see `PROCESS_LIST`
+    pub fn add_process(&mut self, mut process: Process) -> Arc<ProcessEntry> {
         let pid = self.next_pid;
+        let priority = process.priority;
         process.pid = pid;
         process.status = ProcessStatus::Waiting;
+        // the pid and flags are final now
+        process.entry = Arc::new(ProcessEntry::new(pid, process.entry.ppid(), process.flags));
+        let entry = process.entry.clone();
+
         self.next_pid += 1;
-        self.processes.push(process);
+        self.processes.insert(pid, process);
+        self.enqueue(pid, priority);
 
         debug!(Scheduler, "process with pid {} CREATED ...", pid);
-        pid
+        entry
     }
 
-    /// finds a process where executing `condition` on returns true, then executes `then` on it
-    /// returns the result of `then` if a process was found
-    fn find<C, T, R>(&self, condition: C, mut then: T) -> Option<R>
-    where
-        C: Fn(&Process) -> bool,
-        T: FnMut(&Process) -> R,
-    {
-        for process in self.processes.clone_iter() {
-            if condition(process) {
-                return Some(then(process));
-            }
+    /// puts `pid` back in the run queues if it was blocked and can run again
+    pub fn wake(&mut self, pid: usize) {
+        let Some(process) = self.processes.get_mut(&pid) else {
+            return;
+        };
+
+        let priority = process.priority;
+        if process.status == ProcessStatus::Waiting && self.blocked.remove(&pid) {
+            // the time spent blocked doesn't count as not yielding
+            watchdog::yielded(process);
+            self.enqueue(pid, priority);
         }
-
-        None
     }
 
-    /// iterates through all processes and executes `then` on each of them
-    /// executed on all processes
-    pub fn for_each<T>(&mut self, mut then: T)
-    where
-        T: FnMut(&mut Process),
-    {
-        for process in self.processes.clone_iter_mut() {
-            then(process);
+    /// wakes up `pid` if it is sleeping, called once it's timer fires or a wait queue it waits on is woken up
+    /// if it isn't asleep yet it won't go to sleep on it's next `expose::thread_wait`
+    /// returns false if `pid` doesn't exist
+    fn wake_sleeping(&mut self, pid: usize) -> bool {
+        let Some(process) = self.processes.get_mut(&pid) else {
+            return false;
+        };
+
+        if process.status == ProcessStatus::Sleeping {
+            process.sleep_timer = None;
+            process.status = ProcessStatus::Waiting;
+            self.wake(pid);
+        } else {
+            process.woken = true;
         }
+        true
     }
 
     /// iterates through all processes and executes `then` on each of them
@@ -200,7 +354,7 @@ impl Scheduler {
     where
         T: FnMut(&mut Process) -> bool,
     {
-        for process in self.processes.clone_iter_mut() {
+        for process in self.processes.values_mut() {
             if !then(process) {
                 break;
             }
@@ -208,37 +362,59 @@ impl Scheduler {
     }
 
     /// attempt to remove a process where executing `condition` on returns true, returns the removed process info
+    /// the process is dropped from the run queues lazily
     pub fn remove(&mut self, condition: impl Fn(&Process) -> bool) -> Option<ProcessInfo> {
-        self.processes
-            .remove_where(|process| condition(process))
-            .map(|process| process.info())
-    }
-
-    #[inline(always)]
-    pub fn processes_count(&self) -> usize {
-        self.processes.len()
+        let pid = self
+            .processes
+            .values()
+            .find(|process| condition(process))?
+            .pid;
+
+        self.blocked.remove(&pid);
+        self.processes.remove(&pid).map(|process| process.info())
     }
 
     #[inline(always)]
     /// wether or not has been properly initialized using `init`
     pub fn inited(&self) -> bool {
-        self.processes.len() > 0
+        !self.processes.is_empty()
     }
 }
 
+/// how many times the scheduler was invoked, both by the timer and by yielding
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// set by `expose::thread_yeild` so the next switch gives up the rest of the time slice
+static YIELDED: AtomicBool = AtomicBool::new(false);
+
+/// returns the amount of scheduler ticks since boot, yielding counts as a tick
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
 #[inline(always)]
 /// peforms a context switch using the scheduler, switching to the next process context
 /// to be used
 pub fn swtch(context: CPUStatus) -> CPUStatus {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    let yielded = YIELDED.swap(false, Ordering::Relaxed);
+    if !yielded {
+        crate::arch::cpu::count_timer_irq();
+        crate::arch::profiler::tick(&context);
+    }
     if let Some(mut scheduler) = SCHEDULER.try_lock().filter(|s| s.inited()) {
-        unsafe { scheduler.switch(context) }
+        watchdog::touch();
+        unsafe { scheduler.switch(context, yielded) }
     } else {
+        watchdog::check_cpu(&context);
         context
     }
 }
 
 lazy_static! {
     static ref SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::new());
+    /// the entries of all processes in the scheduler, read mostly so readers don't have to lock the scheduler
+    /// only updated when a process is added or removed
+    static ref PROCESS_LIST: Rcu<Vec<Arc<ProcessEntry>>> = Rcu::new(Vec::new());
 }
 
 /// acquires lock on scheduler and executes `then` on the current process
@@ -249,6 +425,18 @@ where
     then(SCHEDULER.lock().current())
 }
 
+/// like `with_current` but returns None instead of spinning if the scheduler is already locked
+/// for use in fault handlers which may have interrupted the lock holder
+#[cfg(feature = "debug-poison")]
+fn try_with_current<T, R>(then: T) -> Option<R>
+where
+    T: FnOnce(&mut Process) -> R,
+{
+    SCHEDULER
+        .try_lock()
+        .map(|mut scheduler| then(scheduler.current()))
+}
+
 /// acquires lock on scheduler and executes `then` on the current process state
 fn with_current_state<T, R>(then: T) -> R
 where
@@ -260,27 +448,42 @@ where
     })
 }
 
-/// acquires lock on scheduler and finds a process where executing `condition` on returns true, then executes `then` on it
+/// finds a process where executing `condition` on it's entry returns true, then executes `then` on it
 /// returns the result of `then` if a process was found
-fn find<C, T, R>(condition: C, then: T) -> Option<R>
+/// doesn't lock the scheduler, `condition` and `then` must not yield
+fn find<C, T, R>(condition: C, mut then: T) -> Option<R>
 where
-    C: Fn(&Process) -> bool,
-    T: FnMut(&Process) -> R,
+    C: Fn(&ProcessEntry) -> bool,
+    T: FnMut(&ProcessEntry) -> R,
 {
-    SCHEDULER.lock().find(condition, then)
+    PROCESS_LIST.read(|entries| {
+        entries
+            .iter()
+            .find(|entry| condition(entry))
+            .map(|entry| then(entry))
+    })
 }
 
-/// acquires lock on scheduler
-/// executes `then` on each process
+/// executes `then` on the entry of each process
+/// doesn't lock the scheduler, `then` must not yield
 fn for_each<T>(then: T)
 where
-    T: FnMut(&mut Process),
+    T: FnMut(&ProcessEntry),
+{
+    PROCESS_LIST.read(|entries| entries.iter().map(|entry| &**entry).for_each(then))
+}
+
+/// acquires lock on scheduler and executes `then` on the process `pid`
+/// returns the result of `then` if `pid` exists
+fn with_process<T, R>(pid: usize, then: T) -> Option<R>
+where
+    T: FnOnce(&mut Process) -> R,
 {
-    SCHEDULER.lock().for_each(then)
+    SCHEDULER.lock().processes.get_mut(&pid).map(then)
 }
 
 /// acquires lock on scheduler
-/// executes `then` on each process until it returns false
+/// executes `then` on each process until it returns false, for walks that need more than the entries
 fn while_each<T>(then: T)
 where
     T: FnMut(&mut Process) -> bool,
@@ -288,17 +491,32 @@ where
     SCHEDULER.lock().while_each(then)
 }
 
-/// acquires lock on scheduler and returns the number of processes
+/// returns the number of processes without locking the scheduler
 pub fn pcount() -> usize {
-    SCHEDULER.lock().processes_count()
+    PROCESS_LIST.read(|entries| entries.len())
 }
 
 /// acquires lock on scheduler and adds a process to it
 fn add_process(process: Process) -> usize {
-    SCHEDULER.lock().add_process(process)
+    let entry = SCHEDULER.lock().add_process(process);
+    let pid = entry.pid;
+    PROCESS_LIST.update(|entries| entries.push(entry));
+    pid
+}
+
+/// acquires lock on scheduler and puts `pid` back in the run queues if it can run again
+fn wake(pid: usize) {
+    SCHEDULER.lock().wake(pid)
+}
+
+/// acquires lock on scheduler and wakes up `pid` if it is sleeping, see `Scheduler::wake_sleeping`
+fn wake_sleeping(pid: usize) -> bool {
+    SCHEDULER.lock().wake_sleeping(pid)
 }
 
 /// acquires lock on scheduler and removes a process from it where `condition` on the process returns true
 fn remove(condition: impl Fn(&Process) -> bool) -> Option<ProcessInfo> {
-    SCHEDULER.lock().remove(condition)
+    let info = SCHEDULER.lock().remove(condition)?;
+    PROCESS_LIST.update(|entries| entries.retain(|entry| entry.pid != info.pid));
+    Some(info)
 }
@@ -1,24 +1,130 @@
-use core::slice;
+use core::{
+    fmt::Write,
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use super::resources::ResourceManager;
-use super::{ARGV_START, STACK_END};
-
-use crate::memory::{align_up, copy_to_userspace, frame_allocator};
+use super::timer::{self, TimerId};
+use super::{
+    ARGV_END, ARGV_START, RING0_STACK_END, RING0_STACK_START, STACK_END, STACK_START,
+    SURFACES_START,
+};
+
+use crate::drivers::surface::Surface;
+use crate::drivers::vfs::namespace::Namespace;
+use crate::memory::{align_up, copy_to_userspace, frame_allocator, VirtAddr};
 use crate::utils::elf::{Elf, ElfError};
-use crate::{arch, debug, hddm, PhysAddr};
+use crate::utils::errors::{Context, ErrorContext};
+use crate::utils::expose::ABI_VERSION;
+use crate::utils::types::{Name, MAX_PROCESS_NAME_LEN};
+use crate::{arch, debug, hddm, warn, PhysAddr};
 
 use crate::memory::paging::{self, EntryFlags, MapToError, Page, PAGE_SIZE};
+use crate::utils::locks::Mutex;
 use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use bitflags::bitflags;
-use spin::Mutex;
 
 use crate::{arch::threading::CPUStatus, memory::paging::PageTable};
 
+/// the sum of the resident pages of every alive process, kept separately so proc:/meminfo can read it
+/// without locking the scheduler
+static RESIDENT_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// the amount of pages resident in all alive processes, see `AliveProcessState::memory_info`
+pub fn resident_pages() -> usize {
+    RESIDENT_PAGES.load(Ordering::Relaxed)
+}
+
 bitflags! {
     #[repr(C)]
     #[derive(Debug, Clone, Copy)]
     pub struct ProcessFlags: u8 {
         const USERSPACE = 1 << 0;
+        /// a kernel process that syscalls treat like a userspace process, it's pointers are
+        /// validated and it has no capabilities, used by the syscall fuzzer
+        const UNTRUSTED = 1 << 1;
+        /// see `SpawnFlags::BACKGROUND`
+        const BACKGROUND = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// what a process is allowed to do, the processes it spawns inherit `Capabilities::INHERITED` of them
+    #[derive(Debug, Clone, Copy)]
+    pub struct Capabilities: u32 {
+        /// can shutdown and reboot the system, control the cpus and which cpu irqs interrupt
+        const POWER = 1 << 0;
+        /// can read and write the memory of other userspace processes, suspend and resume them
+        const DEBUG = 1 << 1;
+        /// can open and map the surfaces of other processes and present them to the display
+        const COMPOSITOR = 1 << 2;
+        /// can change the default environment
+        const ENVIRONMENT = 1 << 3;
+        /// can change the hostname
+        const HOSTNAME = 1 << 4;
+        /// can open devices that give direct access to the hardware, see `Device::raw`
+        const RAW_DEVICES = 1 << 5;
+        /// can bind and unbind drives in it's mount namespace, see `vfs::namespace`
+        const MOUNT = 1 << 6;
+    }
+}
+
+impl Capabilities {
+    /// the capabilities a process passes on to the processes it spawns, the rest are only held by
+    /// kernel processes and the services Eve grants them to, see `expose::spawn_with_stdio`
+    pub const INHERITED: Self = Self::all().difference(Self::POWER.union(Self::DEBUG));
+}
+
+/// the uid of Eve, the kernel processes and everything they spawn, the only uid that can change
+/// it's uid
+pub const ROOT_UID: u32 = 0;
+/// the uid of `ProcessFlags::UNTRUSTED` processes
+pub const NOBODY_UID: u32 = u32::MAX;
+
+/// the priority of a process, higher priorities get a bigger share of the CPU and longer time slices
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContextPriority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ContextPriority {
+    pub const COUNT: usize = 3;
+
+    /// the share of the CPU the priority gets relative to the others
+    pub const fn weight(self) -> u64 {
+        match self {
+            Self::Low => 1,
+            Self::Medium => 4,
+            Self::High => 16,
+        }
+    }
+
+    /// the amount of ticks a process runs before being preempted
+    pub const fn timeslice(self) -> u64 {
+        match self {
+            Self::Low => 1,
+            Self::Medium => 2,
+            Self::High => 4,
+        }
+    }
+}
+
+impl TryFrom<u8> for ContextPriority {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Low),
+            1 => Ok(Self::Medium),
+            2 => Ok(Self::High),
+            _ => Err(()),
+        }
     }
 }
 
@@ -28,6 +134,61 @@ pub enum ProcessStatus {
     Waiting,
     Running,
     Zombie,
+    /// stopped by a debugger, isn't scheduled until it is resumed
+    Suspended,
+    /// waiting for a timer, see `expose::thread_sleep`
+    Sleeping,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitKind {
+    /// the process called exit
+    Exited,
+    /// the process was killed by another process or by the kernel
+    Killed,
+}
+
+/// how a process exited, returned by `syswait_status`
+/// exit codes are whatever the program exited with and aren't `ErrorStatus`es, a process that was
+/// killed has no exit code, `value` is the pid of the process that killed it instead (0 for the kernel)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus {
+    pub kind: ExitKind,
+    pub value: usize,
+}
+
+impl ExitStatus {
+    pub const fn exited(code: usize) -> Self {
+        Self {
+            kind: ExitKind::Exited,
+            value: code,
+        }
+    }
+
+    pub const fn killed(by: usize) -> Self {
+        Self {
+            kind: ExitKind::Killed,
+            value: by,
+        }
+    }
+
+    /// the exit code `wait` returns, killed processes are reported as exiting with 1
+    pub const fn code(self) -> usize {
+        match self.kind {
+            ExitKind::Exited => self.value,
+            ExitKind::Killed => 1,
+        }
+    }
+
+    /// the pid of the process that killed the process, 0 if it exited or was killed by the kernel
+    pub const fn killed_by(self) -> usize {
+        match self.kind {
+            ExitKind::Exited => 0,
+            ExitKind::Killed => self.value,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -36,25 +197,75 @@ pub struct AliveProcessState {
     pub(super) resource_manager: Mutex<ResourceManager>,
     data_pages: usize,
     pub(super) current_dir: String,
+    /// the drives bound for this process, shared with the processes it spawned until one of them
+    /// changes it, see `vfs::namespace`
+    pub(super) namespace: Arc<Namespace>,
 
     data_start: usize,
     data_break: usize,
+
+    /// the surfaces mapped in this process and where, the frames are shared so they are unmapped
+    /// before the page table is freed
+    surfaces: Vec<(VirtAddr, Arc<Surface>)>,
+    surfaces_break: VirtAddr,
+    /// the pages mapped in this process that are backed by frames it owns
+    resident_pages: usize,
+    /// all the pages mapped in the lower half, including the frames of surfaces shared with other processes
+    mapped_pages: usize,
+    /// set by `sysreport_abort` before the process exits, see `Process::abort_message`
+    pub(super) abort_message: Option<String>,
 }
 
 impl AliveProcessState {
     pub fn new(current_dir: String, root_page_table_addr: PhysAddr, data_break: usize) -> Self {
         let data_break = align_up(data_break, PAGE_SIZE);
+        let root_page_table = (root_page_table_addr | hddm()) as *mut PageTable;
+        // everything mapped so far (the elf, stacks and argv) was allocated for this process
+        let pages = unsafe { (*root_page_table).lower_half_ranges() }
+            .into_iter()
+            .map(|(start, end, _)| (end - start) / PAGE_SIZE)
+            .sum();
+        RESIDENT_PAGES.fetch_add(pages, Ordering::Relaxed);
+
         AliveProcessState {
-            root_page_table: (root_page_table_addr | hddm()) as *mut PageTable,
-            resource_manager: Mutex::new(ResourceManager::new()),
+            root_page_table,
+            resource_manager: Mutex::new("ResourceManager", ResourceManager::new()),
             current_dir,
+            namespace: Arc::new(Namespace::new()),
 
             data_pages: 0,
             data_break,
             data_start: data_break,
+
+            surfaces: Vec::new(),
+            surfaces_break: SURFACES_START,
+            resident_pages: pages,
+            mapped_pages: pages,
+            abort_message: None,
         }
     }
 
+    /// adds `resident` and `mapped` to the page counters, negative amounts are unmapped pages
+    fn account(&mut self, resident: isize, mapped: isize) {
+        self.resident_pages = self.resident_pages.wrapping_add_signed(resident);
+        self.mapped_pages = self.mapped_pages.wrapping_add_signed(mapped);
+
+        if resident >= 0 {
+            RESIDENT_PAGES.fetch_add(resident as usize, Ordering::Relaxed);
+        } else {
+            RESIDENT_PAGES.fetch_sub(resident.unsigned_abs(), Ordering::Relaxed);
+        }
+    }
+
+    /// generates the data of proc:/<pid>/memory, the bytes resident in the process and the bytes mapped
+    /// in it (including shared surfaces)
+    pub fn memory_info(&self) -> String {
+        let mut results = String::new();
+        _ = writeln!(results, "resident: {}", self.resident_pages * PAGE_SIZE);
+        _ = writeln!(results, "mapped: {}", self.mapped_pages * PAGE_SIZE);
+        results
+    }
+
     #[inline(always)]
     fn data_break_actual(&self) -> usize {
         self.data_start + PAGE_SIZE * self.data_pages
@@ -70,7 +281,10 @@ impl AliveProcessState {
             (*self.root_page_table).map_to(
                 new_page,
                 frame,
-                EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE | EntryFlags::PRESENT,
+                EntryFlags::WRITABLE
+                    | EntryFlags::USER_ACCESSIBLE
+                    | EntryFlags::PRESENT
+                    | EntryFlags::NO_EXECUTE,
             )?
         };
 
@@ -80,22 +294,230 @@ impl AliveProcessState {
 
         slice.fill(0);
         self.data_pages += 1;
+        self.account(1, 1);
         Ok(())
     }
 
     fn page_unextend_data(&mut self) {
-        let page_end = self.data_break_actual();
-        let new_page = Page::containing_address(page_end);
+        self.data_pages -= 1;
+        // the last page of the data is the one before the break
+        let last_page = Page::containing_address(self.data_break_actual());
+
+        unsafe { (*self.root_page_table).unmap(last_page) };
+        self.account(-1, -1);
+    }
 
-        let frame = unsafe { (*self.root_page_table).get_frame(new_page).unwrap() };
-        frame_allocator::deallocate_frame(frame);
+    /// executes `then` on each part of `addr..addr + len` in this process's address space that is in
+    /// a single page, with a pointer to that part in the higher half and it's offset from `addr`
+    /// returns false if any of the pages isn't mapped or is not accessible from userspace
+    fn with_user_memory(
+        &self,
+        addr: usize,
+        len: usize,
+        mut then: impl FnMut(*mut u8, usize, usize),
+    ) -> bool {
+        let page_table = unsafe { &mut *self.root_page_table };
+        if !page_table.is_mapped_with(addr, len, EntryFlags::USER_ACCESSIBLE) {
+            return false;
+        }
 
-        self.data_pages -= 1;
+        let mut offset = 0;
+        while offset < len {
+            let current = addr + offset;
+            let page = Page::containing_address(current);
+            let page_offset = current - page.start_address;
+            let amount = (PAGE_SIZE - page_offset).min(len - offset);
+
+            let frame = page_table.get_frame(page).unwrap();
+            let ptr = (frame.start_address + page_offset) | hddm();
+            then(ptr as *mut u8, offset, amount);
+            offset += amount;
+        }
+        true
+    }
+
+    /// reads `buffer.len()` bytes at `addr` in this process's address space into `buffer`
+    pub fn read_memory(&self, addr: usize, buffer: &mut [u8]) -> bool {
+        self.with_user_memory(addr, buffer.len(), |ptr, offset, amount| unsafe {
+            core::ptr::copy_nonoverlapping(ptr, buffer[offset..].as_mut_ptr(), amount)
+        })
+    }
+
+    /// writes `buffer` at `addr` in this process's address space, read-only pages are written too
+    pub fn write_memory(&self, addr: usize, buffer: &[u8]) -> bool {
+        self.with_user_memory(addr, buffer.len(), |ptr, offset, amount| unsafe {
+            core::ptr::copy_nonoverlapping(buffer[offset..].as_ptr(), ptr, amount)
+        })
+    }
+
+    /// walks the frame pointers starting at `rbp` and writes `rip` followed by the return address of each
+    /// frame to `frames`, returns the amount of frames written
+    pub fn backtrace(&self, rip: usize, mut rbp: usize, frames: &mut [usize]) -> usize {
+        if frames.is_empty() {
+            return 0;
+        }
+
+        frames[0] = rip;
+        let mut count = 1;
+
+        while count < frames.len() && rbp != 0 && rbp % 8 == 0 {
+            let mut frame = [0u8; 16];
+            if !self.read_memory(rbp, &mut frame) {
+                break;
+            }
+
+            let next_rbp = usize::from_ne_bytes(frame[..8].try_into().unwrap());
+            let return_addr = usize::from_ne_bytes(frame[8..].try_into().unwrap());
+            if return_addr == 0 {
+                break;
+            }
+
+            frames[count] = return_addr;
+            count += 1;
+
+            // the stack grows down so the caller's frame is always above, stops loops in corrupted stacks
+            if next_rbp <= rbp {
+                break;
+            }
+            rbp = next_rbp;
+        }
+
+        count
+    }
+
+    /// maps `surface` in this process, returns where it is mapped
+    /// a surface is only mapped once per process, mapping it again returns the same address
+    pub fn map_surface(&mut self, surface: Arc<Surface>) -> Result<VirtAddr, MapToError> {
+        if let Some((addr, _)) = self.surfaces.iter().find(|(_, s)| Arc::ptr_eq(s, &surface)) {
+            return Ok(*addr);
+        }
+
+        let page_table = unsafe { &mut *self.root_page_table };
+        let addr = self.surfaces_break;
+
+        for (i, frame) in surface.frames().iter().enumerate() {
+            let page = Page::containing_address(addr + i * PAGE_SIZE);
+            let results = page_table.map_to(
+                page,
+                *frame,
+                EntryFlags::PRESENT
+                    | EntryFlags::WRITABLE
+                    | EntryFlags::USER_ACCESSIBLE
+                    | EntryFlags::NO_EXECUTE,
+            );
+
+            if let Err(err) = results {
+                for i in 0..i {
+                    page_table.unmap_shared(Page::containing_address(addr + i * PAGE_SIZE));
+                }
+                return Err(err);
+            }
+        }
+
+        // leaves a guard page between surfaces
+        self.surfaces_break += (surface.frames().len() + 1) * PAGE_SIZE;
+        self.account(0, surface.frames().len() as isize);
+        self.surfaces.push((addr, surface));
+        Ok(addr)
+    }
+
+    /// returns what the page at `addr` is used for
+    fn mapping_kind(&self, addr: VirtAddr) -> &'static str {
+        match addr {
+            STACK_START..STACK_END => "stack",
+            RING0_STACK_START..RING0_STACK_END => "kernel stack",
+            ARGV_START..ARGV_END => "argv",
+            _ if (self.data_start..self.data_break_actual()).contains(&addr) => "heap",
+            _ if addr >= SURFACES_START => "surface",
+            _ => "elf",
+        }
+    }
+
+    /// generates the data of proc:/<pid>/maps, a `start-end permissions kind` line for each mapped
+    /// range, the permissions are `r`, `w` if writable, `x` if executable and `u` if userspace can
+    /// access it otherwise `k`
+    pub fn maps(&self) -> String {
+        let ranges = unsafe { (*self.root_page_table).lower_half_ranges() };
+        let mut results = String::new();
+
+        for (start, end, flags) in ranges {
+            let permissions = [
+                'r',
+                if flags.contains(EntryFlags::WRITABLE) {
+                    'w'
+                } else {
+                    '-'
+                },
+                if flags.contains(EntryFlags::NO_EXECUTE) {
+                    '-'
+                } else {
+                    'x'
+                },
+                if flags.contains(EntryFlags::USER_ACCESSIBLE) {
+                    'u'
+                } else {
+                    'k'
+                },
+            ];
+
+            // a range with the same flags can still be used for different things
+            let mut range_start = start;
+            while range_start < end {
+                let kind = self.mapping_kind(range_start);
+                let mut range_end = range_start + PAGE_SIZE;
+                while range_end < end && self.mapping_kind(range_end) == kind {
+                    range_end += PAGE_SIZE;
+                }
+
+                _ = writeln!(
+                    results,
+                    "{:#x}-{:#x} {} {}",
+                    range_start,
+                    range_end,
+                    permissions.iter().collect::<String>(),
+                    kind
+                );
+                range_start = range_end;
+            }
+        }
+
+        results
+    }
+
+    /// unmaps all the surfaces mapped in this process
+    fn unmap_surfaces(&mut self) {
+        let page_table = unsafe { &mut *self.root_page_table };
+        let mut unmapped = 0;
+
+        for (addr, surface) in self.surfaces.drain(..) {
+            for i in 0..surface.frames().len() {
+                page_table.unmap_shared(Page::containing_address(addr + i * PAGE_SIZE));
+            }
+            unmapped += surface.frames().len();
+        }
+
+        self.account(0, -(unmapped as isize));
+    }
+
+    /// the number of pages the data (the heap) of the process takes
+    #[cfg(feature = "oom-killer")]
+    pub fn data_pages(&self) -> usize {
+        self.data_pages
     }
 
     pub fn extend_data_by(&mut self, amount: isize) -> Result<*mut u8, MapToError> {
         if amount >= 0 {
             let amount = amount as usize;
+            let needed = self
+                .data_break
+                .saturating_add(amount)
+                .saturating_sub(self.data_break_actual())
+                .div_ceil(PAGE_SIZE);
+            // fails before mapping anything so a failed extend doesn't leave the process half extended
+            if needed > frame_allocator::free_frames() {
+                return Err(MapToError::FrameAllocationFailed);
+            }
+
             while self.data_break_actual() < self.data_break + amount {
                 self.page_extend_data()?;
             }
@@ -116,15 +538,15 @@ impl AliveProcessState {
 
 #[derive(Debug)]
 pub struct ZombieProcessState {
-    pub exit_code: usize,
+    pub exit_status: ExitStatus,
     pub exit_addr: usize,
     pub exit_stack_addr: usize,
-    /// the pid of the process that killed this process
-    pub killed_by: usize,
     pub last_resource_id: usize,
 
     pub data_start: usize,
     pub data_break: usize,
+    /// the message the process reported before exiting, if any
+    pub abort_message: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -132,7 +554,7 @@ pub struct ZombieProcessState {
 pub struct ProcessInfo {
     pub ppid: usize,
     pub pid: usize,
-    pub name: [u8; 64],
+    pub name: [u8; MAX_PROCESS_NAME_LEN],
     pub status: ProcessStatus,
 
     pub resource_count: usize,
@@ -145,6 +567,44 @@ pub struct ProcessInfo {
     pub data_break: usize,
 }
 
+/// the parts of a process that can be read without locking the scheduler, published in
+/// `super::PROCESS_LIST`
+/// the pid and flags are copied from the process when it's added to the scheduler and never change
+/// after that
+#[derive(Debug)]
+pub struct ProcessEntry {
+    pub pid: usize,
+    pub flags: ProcessFlags,
+    ppid: AtomicUsize,
+    /// set once the process is a zombie
+    exit_status: spin::Once<ExitStatus>,
+}
+
+impl ProcessEntry {
+    pub fn new(pid: usize, ppid: usize, flags: ProcessFlags) -> Self {
+        Self {
+            pid,
+            flags,
+            ppid: AtomicUsize::new(ppid),
+            exit_status: spin::Once::new(),
+        }
+    }
+
+    pub fn ppid(&self) -> usize {
+        self.ppid.load(Ordering::Relaxed)
+    }
+
+    /// moves the process to another parent
+    pub fn set_ppid(&self, ppid: usize) {
+        self.ppid.store(ppid, Ordering::Relaxed)
+    }
+
+    /// how the process exited, None if it is still alive
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status.get().copied()
+    }
+}
+
 #[derive(Debug)]
 pub enum ProcessState {
     Zombie(ZombieProcessState),
@@ -153,11 +613,33 @@ pub enum ProcessState {
 
 #[derive(Debug)]
 pub struct Process {
-    pub ppid: usize,
+    /// shared with `super::PROCESS_LIST`, replaced when the process is added to the scheduler
+    pub entry: Arc<ProcessEntry>,
     pub pid: usize,
-    pub name: [u8; 64],
+    pub name: Name,
     pub status: ProcessStatus,
     pub context: CPUStatus,
+    pub capabilities: Capabilities,
+    /// who the process runs as, inherited by the processes it spawns, see `expose::setuid`
+    pub uid: u32,
+    pub flags: ProcessFlags,
+    pub priority: ContextPriority,
+    /// the time the process ran for scaled down by it's priority's weight, in ticks * `VRUNTIME_SCALE`
+    pub vruntime: u64,
+    /// the timer that wakes the process up while it is sleeping, see `expose::thread_sleep`
+    pub sleep_timer: Option<TimerId>,
+    /// set if the process was woken up while it wasn't sleeping so that it doesn't sleep through the
+    /// wake up, see `expose::thread_wait`
+    pub woken: bool,
+    /// set if a debugger suspended the process while it was in the kernel, it is suspended the next time
+    /// it is switched out in userspace instead, see `expose::debug_suspend`
+    pub stop_pending: bool,
+    /// the tsc value the last time the process yielded, see `watchdog::check_thread`
+    pub last_yield: u64,
+    /// wether or not the process was reported as hung since it last yielded
+    pub hung_reported: bool,
+    #[cfg(feature = "lockdep")]
+    pub held_locks: crate::utils::locks::lockdep::HeldLocks,
 
     pub state: ProcessState,
 }
@@ -178,23 +660,38 @@ impl Process {
         current_work_dir: String,
         flags: ProcessFlags,
     ) -> Result<Self, MapToError> {
-        let name_bytes = name.as_bytes();
-
-        let mut name = [0u8; 64];
-
-        let len = name_bytes.len().min(64);
-        name[..len].copy_from_slice(&name_bytes[..len]);
+        // the syscalls reject names that are too long, the kernel's own are truncated
+        let name = Name::new_lossy(name);
 
         let status = ProcessStatus::Waiting;
         let mut context = CPUStatus::default();
+        // kernel processes can do anything, userspace processes gets their capabilities from
+        // their parent
+        let capabilities = if flags.intersects(ProcessFlags::USERSPACE | ProcessFlags::UNTRUSTED) {
+            Capabilities::empty()
+        } else {
+            Capabilities::all()
+        };
+        let uid = if flags.contains(ProcessFlags::UNTRUSTED) {
+            NOBODY_UID
+        } else {
+            ROOT_UID
+        };
 
         let root_page_table = (root_page_table_addr | hddm()) as *mut PageTable;
 
         unsafe {
             let page_table = &mut *root_page_table;
-            super::alloc_stack(page_table)?;
+            // kernel processes run in ring0 so with SMAP they can't touch user pages
+            let access = if flags.contains(ProcessFlags::USERSPACE) {
+                EntryFlags::USER_ACCESSIBLE
+            } else {
+                EntryFlags::empty()
+            };
+
+            super::alloc_stack(page_table, access)?;
             super::alloc_ring0_stack(page_table)?;
-            super::alloc_argv(page_table)?;
+            super::alloc_argv(page_table, access)?;
 
             if !argv.is_empty() {
                 let mut start_addr = ARGV_START;
@@ -269,11 +766,23 @@ impl Process {
         }
 
         Ok(Process {
-            ppid,
+            entry: Arc::new(ProcessEntry::new(pid, ppid, flags)),
             pid,
             name,
             status,
             context,
+            capabilities,
+            uid,
+            flags,
+            priority: ContextPriority::default(),
+            vruntime: 0,
+            sleep_timer: None,
+            woken: false,
+            stop_pending: false,
+            last_yield: arch::x86_64::cycles(),
+            hung_reported: false,
+            #[cfg(feature = "lockdep")]
+            held_locks: crate::utils::locks::lockdep::HeldLocks::new(),
 
             state: ProcessState::Alive(AliveProcessState::new(
                 current_work_dir,
@@ -291,11 +800,35 @@ impl Process {
         name: &str,
         current_work_dir: String,
         argv: &[&str],
-    ) -> Result<Self, ElfError> {
-        let page_table_addr = paging::allocate_pml4().map_err(|_| ElfError::MapToError)?;
+    ) -> Result<Self, ErrorContext<ElfError>> {
+        match elf.abi_version() {
+            Some(version) if !ABI_VERSION.supports(version) => {
+                warn!(
+                    Process,
+                    "{} was built against ABI {:?} which isn't supported by {:?}",
+                    name,
+                    version,
+                    ABI_VERSION
+                );
+                return Err(ElfError::IncompatibleAbi).context("abi");
+            }
+            Some(_) => {}
+            None => {
+                debug!(
+                    Process,
+                    "{} has no ABI version note, assuming it's compatible", name
+                );
+            }
+        }
+
+        let page_table_addr = paging::allocate_pml4()
+            .map_err(|_| ElfError::MapToError)
+            .context("page table")?;
 
-        let data_break =
-            unsafe { elf.load_exec(&mut *((page_table_addr | hddm()) as *mut PageTable))? };
+        let data_break = unsafe {
+            elf.load_exec(&mut *((page_table_addr | hddm()) as *mut PageTable))
+                .context("elf")?
+        };
 
         let process = Self::new(
             elf.header.entry_point,
@@ -308,8 +841,8 @@ impl Process {
             current_work_dir,
             ProcessFlags::USERSPACE,
         )
-        .ok()
-        .ok_or(ElfError::MapToError)?;
+        .map_err(|_| ElfError::MapToError)
+        .context("stacks")?;
 
         Ok(process)
     }
@@ -317,29 +850,84 @@ impl Process {
     /// makes a process a zombie
     /// does nothing if the process is already a zombie
     /// also moves the parentership of the process (it's children) to it's parent
-    pub fn terminate(&mut self, exit_code: usize, terminator: usize) {
+    pub fn terminate(&mut self, exit_status: ExitStatus) {
+        if let Some(id) = self.sleep_timer.take() {
+            timer::cancel(id);
+        }
+
         if let ProcessState::Alive(ref mut state) = &mut self.state {
+            state.unmap_surfaces();
             let root_page_table = unsafe { &mut (*state.root_page_table) };
             unsafe { root_page_table.free(4) };
+            state.account(
+                -(state.resident_pages as isize),
+                -(state.mapped_pages as isize),
+            );
 
             let last_resource_id = state.resource_manager.lock().clean();
+            let abort_message = state.abort_message.take();
+            if let Some(ref message) = abort_message {
+                warn!(
+                    Process,
+                    "{} (pid {}) aborted with {}: {message}",
+                    self.name,
+                    self.pid,
+                    exit_status.code()
+                );
+            }
+
             let zombified = ProcessState::Zombie(ZombieProcessState {
-                exit_code,
+                exit_status,
                 exit_addr: self.context.at(),
                 exit_stack_addr: self.context.stack_at(),
-                killed_by: terminator,
                 last_resource_id,
                 data_start: state.data_start,
                 data_break: state.data_break,
+                abort_message,
             });
 
             self.state = zombified;
             self.status = ProcessStatus::Zombie;
+            self.entry.exit_status.call_once(|| exit_status);
 
             debug!(Process, "process with pid {} TERMINATED ...", self.pid);
         }
     }
 
+    /// the status, priority and cpu time of the process's only thread, see proc:/<pid>/threads
+    pub fn thread_status(&self) -> String {
+        let status = match self.status {
+            ProcessStatus::Waiting => "runnable",
+            ProcessStatus::Running => "running",
+            ProcessStatus::Zombie => "zombie",
+            ProcessStatus::Suspended => "suspended",
+            ProcessStatus::Sleeping => "sleeping",
+        };
+        let priority = match self.priority {
+            ContextPriority::Low => "low",
+            ContextPriority::Medium => "medium",
+            ContextPriority::High => "high",
+        };
+        // vruntime is scaled down by the priority's weight
+        let cpu_time = self.vruntime * self.priority.weight() / super::VRUNTIME_SCALE;
+
+        let mut results = String::new();
+        _ = writeln!(results, "tid: {}", self.pid);
+        _ = writeln!(results, "status: {}", status);
+        _ = writeln!(results, "priority: {}", priority);
+        _ = writeln!(results, "cpu time: {} ticks", cpu_time);
+        _ = writeln!(results, "uid: {}", self.uid);
+        results
+    }
+
+    /// the message the process reported with `sysreport_abort`, kept until the process is waited on
+    pub fn abort_message(&self) -> Option<&str> {
+        match &self.state {
+            ProcessState::Alive(state) => state.abort_message.as_deref(),
+            ProcessState::Zombie(state) => state.abort_message.as_deref(),
+        }
+    }
+
     pub fn info(&self) -> ProcessInfo {
         let (
             exit_code,
@@ -351,10 +939,10 @@ impl Process {
             data_break,
         ) = match &self.state {
             ProcessState::Zombie(state) => (
-                state.exit_code,
+                state.exit_status.code(),
                 state.exit_addr,
                 state.exit_stack_addr,
-                state.killed_by,
+                state.exit_status.killed_by(),
                 state.last_resource_id,
                 state.data_start,
                 state.data_break,
@@ -364,16 +952,16 @@ impl Process {
                 0,
                 0,
                 0,
-                state.resource_manager.lock().next_ri(),
+                state.resource_manager.lock().count(),
                 state.data_start,
                 state.data_break,
             ),
         };
 
         ProcessInfo {
-            ppid: self.ppid,
+            ppid: self.entry.ppid(),
             pid: self.pid,
-            name: self.name,
+            name: self.name.to_padded(),
             status: self.status,
 
             exit_code,
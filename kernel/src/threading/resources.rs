@@ -1,30 +1,81 @@
 use core::fmt::Debug;
 
-use alloc::vec::Vec;
+use alloc::{format, string::String, vec::Vec};
 
-use crate::drivers::vfs::{DirIter, FileDescriptor, FS, VFS_STRUCT};
+use crate::drivers::{
+    surface::SurfaceHandle,
+    vfs::{evq::EventQueue, DirIter, FileDescriptor, InodeType, FS, VFS_STRUCT},
+};
 
 #[derive(Clone)]
 pub enum Resource {
-    Null,
     File(FileDescriptor),
     /// TODO: better diriter implementation
     DirIter(DirIter),
+    EventQueue(EventQueue),
+    Surface(SurfaceHandle),
 }
 
 impl Resource {
-    pub const fn variant(&self) -> u8 {
+    /// the type of the resource followed by the path it was opened with if it has one, listed in
+    /// proc:/<pid>/fd
+    pub fn describe(&self) -> String {
         match self {
-            Resource::Null => 0,
-            Resource::File(_) => 1,
-            Resource::DirIter(_) => 2,
+            Self::File(fd) => {
+                let kind = match fd.node.kind() {
+                    InodeType::File => "file",
+                    InodeType::Directory => "dir",
+                    InodeType::Device => "device",
+                };
+
+                if fd.path.is_empty() {
+                    String::from(kind)
+                } else {
+                    format!("{} {}", kind, fd.path)
+                }
+            }
+            Self::DirIter(_) => String::from("diriter"),
+            Self::EventQueue(_) => String::from("evq"),
+            Self::Surface(_) => String::from("surface"),
         }
     }
 }
 
+/// the first `UNTAGGED_RESOURCES` resources (stdin, stdout and stderr) are refered to by their index
+/// from userspace, so they are never tagged with a generation
+const UNTAGGED_RESOURCES: usize = 3;
+const GENERATION_SHIFT: usize = 32;
+const INDEX_MASK: usize = (1 << GENERATION_SHIFT) - 1;
+/// `dup2` can't grow the table past this many slots
+const MAX_DUP2_INDEX: usize = 1 << 16;
+
+#[derive(Clone)]
+enum Slot {
+    Free { next: Option<usize> },
+    Used(Resource),
+}
+
+#[derive(Clone)]
+struct Entry {
+    /// incremented each time the slot is freed, so that ris of closed resources don't point to
+    /// the next resource using this slot
+    generation: u32,
+    slot: Slot,
+}
+
+/// a per-process table of resources
+/// resources are refered to by a ri (resource index), the low 32 bits of a ri are the index of the
+/// resource in the table, the high 32 bits are the generation of the slot it was allocated in
+/// allocating and removing resources is O(1), free slots are kept in a free-list
+///
+/// when a process is spawned with `SpawnFlags::CLONE_RESOURCES` the child gets a copy of the parent's
+/// table, all the ris stay the same, file positions are copied not shared and closing a resource in one
+/// process doesn't close it in the other, otherwise the child starts with an empty table
+#[derive(Clone)]
 pub struct ResourceManager {
-    resources: Vec<Resource>,
-    next_ri: usize,
+    entries: Vec<Entry>,
+    free_head: Option<usize>,
+    count: usize,
 }
 
 impl Debug for ResourceManager {
@@ -33,14 +84,14 @@ impl Debug for ResourceManager {
             .field(
                 "resources",
                 &self
-                    .resources
+                    .entries
                     .iter()
                     .enumerate()
-                    .filter(|(_, r)| !matches!(r, Resource::Null))
-                    .map(|(i, _)| i)
+                    .filter(|(_, entry)| matches!(entry.slot, Slot::Used(_)))
+                    .map(|(i, entry)| Self::make_ri(i, entry.generation))
                     .collect::<Vec<usize>>(),
             )
-            .field("next_ri", &self.next_ri)
+            .field("count", &self.count)
             .finish()
     }
 }
@@ -48,83 +99,200 @@ impl Debug for ResourceManager {
 impl ResourceManager {
     pub fn new() -> Self {
         ResourceManager {
-            resources: Vec::with_capacity(2),
-            next_ri: 0,
+            entries: Vec::with_capacity(2),
+            free_head: None,
+            count: 0,
         }
     }
 
-    pub fn add_resource(&mut self, resource: Resource) -> usize {
-        let resources = &mut self.resources[self.next_ri..];
+    #[inline(always)]
+    fn make_ri(index: usize, generation: u32) -> usize {
+        if index < UNTAGGED_RESOURCES {
+            index
+        } else {
+            index | ((generation as usize) << GENERATION_SHIFT)
+        }
+    }
+
+    /// returns the index of the entry `ri` points to if `ri` is vaild
+    fn index_of(&self, ri: usize) -> Option<usize> {
+        let index = ri & INDEX_MASK;
+        let entry = self.entries.get(index)?;
 
-        for (mut ri, res) in resources.iter_mut().enumerate() {
-            if res.variant() == Resource::Null.variant() {
-                ri += self.next_ri;
+        (ri == Self::make_ri(index, entry.generation) && matches!(entry.slot, Slot::Used(_)))
+            .then_some(index)
+    }
 
-                self.next_ri = ri;
-                *res = resource;
+    /// puts `resource` in the free slot `index` which must already be unlinked from the free-list
+    fn occupy(&mut self, index: usize, resource: Resource) -> usize {
+        let entry = &mut self.entries[index];
+        entry.slot = Slot::Used(resource);
+        self.count += 1;
+        Self::make_ri(index, entry.generation)
+    }
 
-                return ri;
-            }
+    /// frees the slot at `index` bumping it's generation and returns the resource that was in it
+    fn vacate(&mut self, index: usize) -> Resource {
+        let entry = &mut self.entries[index];
+        entry.generation = entry.generation.wrapping_add(1);
+        let slot = core::mem::replace(
+            &mut entry.slot,
+            Slot::Free {
+                next: self.free_head,
+            },
+        );
+
+        self.free_head = Some(index);
+        self.count -= 1;
+
+        let Slot::Used(resource) = slot else {
+            unreachable!()
+        };
+        resource
+    }
+
+    /// unlinks the free slot at `index` from the free-list
+    /// this is O(n) in the length of the free-list, only used by `dup2`
+    fn unlink_free(&mut self, index: usize) {
+        let Slot::Free { next } = self.entries[index].slot else {
+            unreachable!()
+        };
+
+        if self.free_head == Some(index) {
+            self.free_head = next;
+            return;
         }
 
-        self.resources.push(resource);
+        let mut current = self.free_head;
+        while let Some(i) = current {
+            let Slot::Free { next: ref mut link } = self.entries[i].slot else {
+                unreachable!()
+            };
 
-        let ri = self.resources.len() - 1;
-        self.next_ri = ri;
+            if *link == Some(index) {
+                *link = next;
+                return;
+            }
+            current = *link;
+        }
+    }
 
-        ri
+    pub fn add_resource(&mut self, resource: Resource) -> usize {
+        let index = match self.free_head {
+            Some(index) => {
+                let Slot::Free { next } = self.entries[index].slot else {
+                    unreachable!()
+                };
+                self.free_head = next;
+                index
+            }
+            None => {
+                self.entries.push(Entry {
+                    generation: 0,
+                    slot: Slot::Free { next: None },
+                });
+                self.entries.len() - 1
+            }
+        };
+
+        self.occupy(index, resource)
     }
 
     #[inline]
-    pub fn remove_resource(&mut self, ri: usize) -> Result<(), ()> {
-        if ri >= self.resources.len() {
+    pub fn remove_resource(&mut self, ri: usize) -> Result<Resource, ()> {
+        let index = self.index_of(ri).ok_or(())?;
+        Ok(self.vacate(index))
+    }
+
+    /// adds a copy of the resource `ri` to the table, returns the ri of the copy
+    pub fn dup(&mut self, ri: usize) -> Result<usize, ()> {
+        let resource = self.get(ri).ok_or(())?.clone();
+        Ok(self.add_resource(resource))
+    }
+
+    /// makes the slot `dest_ri` points to a copy of the resource `ri`, removing whatever `dest_ri` pointed to before,
+    /// only the index part of `dest_ri` is used, returns the new ri of the copy and the resource that was removed if any
+    pub fn dup2(&mut self, ri: usize, dest_ri: usize) -> Result<(usize, Option<Resource>), ()> {
+        let resource = self.get(ri).ok_or(())?.clone();
+        let index = dest_ri & INDEX_MASK;
+        if index >= MAX_DUP2_INDEX {
             return Err(());
         }
 
-        self.resources[ri] = Resource::Null;
-        if ri < self.next_ri {
-            self.next_ri = ri;
+        if self.index_of(ri) == Some(index) {
+            return Ok((ri, None));
         }
-        Ok(())
+
+        while self.entries.len() <= index {
+            let new = self.entries.len();
+            self.entries.push(Entry {
+                generation: 0,
+                slot: Slot::Free {
+                    next: self.free_head,
+                },
+            });
+            self.free_head = Some(new);
+        }
+
+        let removed = match self.entries[index].slot {
+            Slot::Used(_) => Some(self.vacate(index)),
+            Slot::Free { .. } => None,
+        };
+
+        self.unlink_free(index);
+        Ok((self.occupy(index, resource), removed))
     }
 
     /// cleans up all resources
-    /// returns the **previous** next resource index
+    /// returns the amount of resources that were open
     pub fn clean(&mut self) -> usize {
-        for resource in &mut self.resources {
-            match resource {
-                Resource::File(fd) => VFS_STRUCT.read().close(fd).unwrap(),
-                _ => *resource = Resource::Null,
+        for entry in &mut self.entries {
+            if let Slot::Used(Resource::File(ref mut fd)) = entry.slot {
+                VFS_STRUCT.read().close(fd).unwrap()
             }
         }
 
-        let prev = self.next_ri;
-        self.next_ri = 0;
+        let prev = self.count;
+        *self = Self::new();
         prev
     }
 
-    pub fn next_ri(&self) -> usize {
-        self.next_ri
+    /// returns the ri and description of every open resource
+    pub fn describe(&self) -> Vec<(usize, String)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| match entry.slot {
+                Slot::Used(ref resource) => {
+                    Some((Self::make_ri(i, entry.generation), resource.describe()))
+                }
+                Slot::Free { .. } => None,
+            })
+            .collect()
+    }
+
+    /// the amount of open resources
+    pub fn count(&self) -> usize {
+        self.count
     }
 
-    pub fn overwrite_resources(&mut self, resources: Vec<Resource>) {
-        self.resources = resources;
+    pub fn overwrite_resources(&mut self, resources: ResourceManager) {
+        *self = resources;
     }
 
-    pub fn clone_resources(&self) -> Vec<Resource> {
-        self.resources.clone()
+    pub fn clone_resources(&self) -> ResourceManager {
+        self.clone()
     }
 
     /// gets a mutable reference to the resource with index `ri`
-    /// returns `None` if `ri` is invaild
+    /// returns `None` if `ri` is invaild or was closed
     pub fn get(&mut self, ri: usize) -> Option<&mut Resource> {
-        let resources = &mut self.resources;
+        let index = self.index_of(ri)?;
 
-        if ri >= resources.len() {
-            return None;
+        match self.entries[index].slot {
+            Slot::Used(ref mut resource) => Some(resource),
+            Slot::Free { .. } => unreachable!(),
         }
-
-        Some(&mut resources[ri])
     }
 }
 
@@ -147,4 +315,16 @@ pub fn add_resource(resource: Resource) -> usize {
 /// removes a resource from the current process with `ri`
 pub fn remove_resource(ri: usize) -> Result<(), ()> {
     super::with_current_state(move |state| state.resource_manager.lock().remove_resource(ri))
+        .map(|_| ())
+}
+
+/// duplicates the resource `ri` of the current process, returns the ri of the duplicate
+pub fn dup(ri: usize) -> Result<usize, ()> {
+    super::with_current_state(move |state| state.resource_manager.lock().dup(ri))
+}
+
+/// makes `dest_ri` a duplicate of the resource `ri` in the current process, returns the new ri of `dest_ri`
+/// and the resource that `dest_ri` used to point to if any so that the caller can close it
+pub fn dup2(ri: usize, dest_ri: usize) -> Result<(usize, Option<Resource>), ()> {
+    super::with_current_state(move |state| state.resource_manager.lock().dup2(ri, dest_ri))
 }
@@ -0,0 +1,191 @@
+//! a hierarchical timing wheel counting scheduler ticks, shared by everything that waits with a timeout
+//! (`expose::thread_sleep`, `poll` and `evq_wait`)
+//! level `n` has `SLOTS` slots each covering `SLOTS^n` ticks, timers are put in the lowest level
+//! their deadline fits in and cascaded down into the lower levels as the wheel turns
+//! so registering, cancelling and firing a timer doesn't scan every waiter
+use core::fmt::Write;
+
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+use spin::Mutex;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 4;
+/// the furthest a timer can be put in the wheel, timers further than that are put in the last slot of the
+/// last level and cascaded back up until their deadline fits
+const MAX_DELTA: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerId(u64);
+
+struct Slot {
+    timers: Vec<(TimerId, u64)>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self { timers: Vec::new() }
+    }
+}
+
+struct TimerWheel {
+    levels: [[Slot; SLOTS]; LEVELS],
+    /// the tick the wheel has advanced to, every timer with a deadline before it has fired
+    now: u64,
+    /// the pid to wake up for each timer that hasn't fired or been cancelled yet,
+    /// cancelled timers are only removed from their slot once the wheel reaches it
+    pending: BTreeMap<TimerId, usize>,
+    next_id: u64,
+}
+
+impl TimerWheel {
+    const fn new() -> Self {
+        Self {
+            levels: [const { [const { Slot::new() }; SLOTS] }; LEVELS],
+            now: 0,
+            pending: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// puts the timer in the lowest level it's deadline fits in
+    fn insert(&mut self, id: TimerId, deadline: u64) {
+        let delta = deadline.saturating_sub(self.now).min(MAX_DELTA);
+
+        let mut level = 0;
+        while level < LEVELS - 1 && delta >> (SLOT_BITS * (level as u32 + 1)) != 0 {
+            level += 1;
+        }
+
+        let slot = ((self.now + delta) >> (SLOT_BITS * level as u32)) as usize % SLOTS;
+        self.levels[level][slot].timers.push((id, deadline));
+    }
+
+    fn register(&mut self, deadline: u64, pid: usize) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+
+        self.pending.insert(id, pid);
+        self.insert(id, deadline.max(self.now + 1));
+        id
+    }
+
+    /// turns the wheel until `now` executing `fire` on the pid of each expired timer
+    fn advance(&mut self, now: u64, mut fire: impl FnMut(usize)) {
+        while self.now < now {
+            self.now += 1;
+            let tick = self.now;
+
+            // cascades the higher levels whenever the lower one wraps around
+            for level in 1..LEVELS {
+                let shift = SLOT_BITS * level as u32;
+                if tick & ((1 << shift) - 1) != 0 {
+                    break;
+                }
+
+                let slot = (tick >> shift) as usize % SLOTS;
+                let timers = core::mem::take(&mut self.levels[level][slot].timers);
+                for (id, deadline) in timers {
+                    if self.pending.contains_key(&id) {
+                        self.insert(id, deadline);
+                    }
+                }
+            }
+
+            let slot = tick as usize % SLOTS;
+            let timers = core::mem::take(&mut self.levels[0][slot].timers);
+            for (id, deadline) in timers {
+                if deadline > tick {
+                    self.insert(id, deadline);
+                } else if let Some(pid) = self.pending.remove(&id) {
+                    fire(pid);
+                }
+            }
+        }
+    }
+}
+
+static WHEEL: Mutex<TimerWheel> = Mutex::new(TimerWheel::new());
+
+/// registers a timer that wakes up `pid` once `threading::ticks()` reaches `deadline`
+pub fn register(deadline: u64, pid: usize) -> TimerId {
+    WHEEL.lock().register(deadline, pid)
+}
+
+/// cancels `id`, returns false if it has already fired or was already cancelled
+pub fn cancel(id: TimerId) -> bool {
+    WHEEL.lock().pending.remove(&id).is_some()
+}
+
+/// wether or not `id` has fired or was cancelled
+pub fn expired(id: TimerId) -> bool {
+    !WHEEL.lock().pending.contains_key(&id)
+}
+
+/// the tick the wheel is at, the amount of pending timers and the amount of timers in each level
+/// (including cancelled ones that weren't reached yet), see proc:/timers
+pub fn timers_info() -> String {
+    let wheel = WHEEL.lock();
+    let mut results = String::new();
+
+    _ = writeln!(results, "now: {}", wheel.now);
+    _ = writeln!(results, "pending: {}", wheel.pending.len());
+    for (i, level) in wheel.levels.iter().enumerate() {
+        let timers: usize = level.iter().map(|slot| slot.timers.len()).sum();
+        _ = writeln!(results, "level {i}: {timers}");
+    }
+
+    results
+}
+
+/// turns the wheel until `now` executing `fire` on the pid of each expired timer,
+/// called by the scheduler on every tick, does nothing if the wheel is already locked
+/// the skipped ticks are caught up on the next call
+pub(super) fn advance(now: u64, fire: impl FnMut(usize)) {
+    if let Some(mut wheel) = WHEEL.try_lock() {
+        wheel.advance(now, fire);
+    }
+}
+
+enum TimeoutKind {
+    Never,
+    Now,
+    Timer(TimerId),
+}
+
+/// a timer that is cancelled once dropped, used for timeouts that may end early
+/// once it expires it wakes up the process waiting with it, see `waitqueue::Waiter::wait`
+pub struct Timeout(TimeoutKind);
+
+impl Timeout {
+    /// a timeout for the current process that expires after `ticks`,
+    /// 0 is already expired and usize::MAX never expires
+    pub fn new(ticks: usize) -> Self {
+        let kind = match ticks {
+            0 => TimeoutKind::Now,
+            usize::MAX => TimeoutKind::Never,
+            ticks => {
+                let pid = super::expose::getpid();
+                TimeoutKind::Timer(register(super::ticks() + ticks as u64, pid))
+            }
+        };
+
+        Self(kind)
+    }
+
+    pub fn expired(&self) -> bool {
+        match self.0 {
+            TimeoutKind::Never => false,
+            TimeoutKind::Now => true,
+            TimeoutKind::Timer(id) => expired(id),
+        }
+    }
+}
+
+impl Drop for Timeout {
+    fn drop(&mut self) {
+        if let TimeoutKind::Timer(id) = self.0 {
+            cancel(id);
+        }
+    }
+}
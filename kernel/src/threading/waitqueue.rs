@@ -0,0 +1,87 @@
+//! wait queues, a resource that can be waited on with `poll` or an event queue keeps a `WaitQueue`
+//! and wakes it up whenever the result of polling it may have changed, the woken up processes then poll
+//! it again
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// the processes waiting for a resource to become ready
+pub struct WaitQueue {
+    waiters: Mutex<Vec<usize>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn add(&self, pid: usize) {
+        let mut waiters = self.waiters.lock();
+        if !waiters.contains(&pid) {
+            waiters.push(pid);
+        }
+    }
+
+    fn remove(&self, pid: usize) {
+        self.waiters.lock().retain(|waiter| *waiter != pid);
+    }
+
+    /// wether or not any process is waiting on the queue
+    pub fn has_waiters(&self) -> bool {
+        !self.waiters.lock().is_empty()
+    }
+
+    /// wakes up every process waiting on the queue, they stay in the queue until they stop waiting
+    /// processes that were killed while waiting are dropped from the queue
+    pub fn wake_all(&self) {
+        // waiters are added while the scheduler is locked, so the queue can't be locked while waking them
+        let waiters = self.waiters.lock().clone();
+        let killed = waiters
+            .into_iter()
+            .filter(|pid| !super::wake_sleeping(*pid))
+            .collect::<Vec<_>>();
+
+        if !killed.is_empty() {
+            self.waiters.lock().retain(|pid| !killed.contains(pid));
+        }
+    }
+}
+
+/// the wait queues the current process waits on, the process is removed from all of them once dropped
+/// a resource's queue has to be added before it is polled so that it becoming ready in between isn't missed
+pub struct Waiter {
+    pid: usize,
+    queues: Vec<&'static WaitQueue>,
+}
+
+impl Waiter {
+    pub fn new() -> Self {
+        Self {
+            pid: super::expose::prepare_wait(),
+            queues: Vec::new(),
+        }
+    }
+
+    /// adds the current process to `queue`
+    pub fn add(&mut self, queue: &'static WaitQueue) {
+        if !self.queues.iter().any(|added| core::ptr::eq(*added, queue)) {
+            queue.add(self.pid);
+            self.queues.push(queue);
+        }
+    }
+
+    /// sleeps until any of the queues or a timer of the current process wakes it up,
+    /// returns immediately if that happened since the last `wait`
+    pub fn wait(&self) {
+        super::expose::thread_wait();
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        for queue in &self.queues {
+            queue.remove(self.pid);
+        }
+    }
+}
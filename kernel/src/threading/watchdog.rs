@@ -0,0 +1,131 @@
+//! the soft-lockup watchdog
+//! every timer interrupt the scheduler manages to run on touches the heartbeat of the current cpu, if it
+//! didn't manage to for `SOFT_LOCKUP_SECS` the cpu is stuck (usually spinning on the scheduler lock or
+//! on a lock held by the code it interrupted) and the kernel panics with where it is stuck
+//! kernel threads that didn't yield for `hung_thread_ms` are reported once with their backtrace
+//! only the BSP is started so it is the only cpu checked, checking the others would need an IPI to
+//! capture where they are stuck
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use alloc::string::String;
+
+use crate::{
+    arch::{
+        cpu,
+        threading::CPUStatus,
+        x86_64::{cycles, cycles_frequency},
+    },
+    warn, KERNEL_ELF,
+};
+
+use super::{
+    processes::{Process, ProcessFlags, ProcessState},
+    Scheduler,
+};
+
+const SOFT_LOCKUP_SECS: u64 = 20;
+const DEFAULT_HUNG_THREAD_MS: u64 = 20_000;
+/// assumed when the cpu doesn't report the tsc frequency, on slower cpus the thresholds are longer
+const FALLBACK_FREQUENCY: u64 = 3_000_000_000;
+const MAX_FRAMES: usize = 16;
+
+static HUNG_THREAD_MS: AtomicU64 = AtomicU64::new(DEFAULT_HUNG_THREAD_MS);
+
+fn ms_to_cycles(ms: u64) -> u64 {
+    ms * (cycles_frequency().unwrap_or(FALLBACK_FREQUENCY) / 1000)
+}
+
+fn symbol(addr: usize) -> &'static str {
+    KERNEL_ELF
+        .sym_from_value_range(addr)
+        .map_or("??", |sym| KERNEL_ELF.string_table_index(sym.name_index))
+}
+
+/// how long a kernel thread can go without yielding before it is reported
+pub fn hung_thread_ms() -> u64 {
+    HUNG_THREAD_MS.load(Ordering::Relaxed)
+}
+
+/// lowered by the tests so they don't have to wait for the default
+#[cfg(feature = "test")]
+pub fn set_hung_thread_ms(ms: u64) {
+    HUNG_THREAD_MS.store(ms, Ordering::Relaxed);
+}
+
+/// called each time the scheduler runs, the current cpu isn't stuck
+pub fn touch() {
+    cpu::touch_heartbeat();
+}
+
+/// called each time the scheduler couldn't run on the current cpu, `context` is what the timer interrupted
+/// panics if the scheduler didn't run for `SOFT_LOCKUP_SECS`
+pub fn check_cpu(context: &CPUStatus) {
+    let Some((id, heartbeat)) = cpu::heartbeat() else {
+        return;
+    };
+
+    // the scheduler never ran on this cpu yet
+    if heartbeat == 0 {
+        return;
+    }
+
+    let stalled = cycles().saturating_sub(heartbeat);
+    if stalled < ms_to_cycles(SOFT_LOCKUP_SECS * 1000) {
+        return;
+    }
+
+    let registers = context.registers();
+    panic!(
+        "watchdog: cpu {id} stuck for {}s at {:#x} <{}>, rbp: {:#x}",
+        stalled / ms_to_cycles(1000),
+        registers.rip,
+        symbol(registers.rip as usize),
+        registers.rbp,
+    );
+}
+
+/// called on each tick the current process keeps running without yielding, reports `process` once if it
+/// is a kernel thread that didn't yield for `hung_thread_ms`
+/// Eve is never reported, it's idle loop waits for interrupts instead of yielding
+pub fn check_thread(process: &mut Process) {
+    if process.pid == 0
+        || process.flags.contains(ProcessFlags::USERSPACE)
+        || process.hung_reported
+        || cycles().saturating_sub(process.last_yield) < ms_to_cycles(hung_thread_ms())
+    {
+        return;
+    }
+
+    process.hung_reported = true;
+
+    let registers = process.context.registers();
+    let mut frames = [0usize; MAX_FRAMES];
+    let count = match &process.state {
+        ProcessState::Alive(state) => {
+            state.backtrace(registers.rip as usize, registers.rbp as usize, &mut frames)
+        }
+        _ => 0,
+    };
+
+    let mut backtrace = String::new();
+    for frame in &frames[..count] {
+        _ = write!(backtrace, "\n  {frame:#x} <{}>", symbol(*frame));
+    }
+
+    warn!(
+        Scheduler,
+        "{} (pid {}) didn't yield for {}ms{backtrace}",
+        process.name,
+        process.pid,
+        hung_thread_ms()
+    );
+}
+
+/// called each time `process` yields
+pub fn yielded(process: &mut Process) {
+    process.last_yield = cycles();
+    process.hung_reported = false;
+}
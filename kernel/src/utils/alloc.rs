@@ -1,29 +1,21 @@
-use core::marker::PhantomData;
-use core::ops::RangeBounds;
-use core::ptr::NonNull;
 use core::str;
 
 use crate::memory::page_allocator::{PageAlloc, GLOBAL_PAGE_ALLOCATOR};
 use crate::memory::{align_up, paging::PAGE_SIZE};
-use alloc::boxed::Box;
-use alloc::str::pattern::{Pattern, ReverseSearcher};
-use alloc::vec::{Drain, Vec};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 
 pub struct PageVec<T> {
     inner: Vec<T, PageAlloc>,
 }
 
 impl<T> PageVec<T> {
-    pub fn new() -> Self {
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            inner: Vec::new_in(&*GLOBAL_PAGE_ALLOCATOR),
+            inner: Vec::with_capacity_in(capacity, &*GLOBAL_PAGE_ALLOCATOR),
         }
     }
 
-    pub fn clear(&mut self) {
-        self.inner.clear();
-    }
-
     pub fn len(&self) -> usize {
         self.inner.len()
     }
@@ -50,10 +42,6 @@ impl<T> PageVec<T> {
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
-
-    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, PageAlloc> {
-        self.inner.drain(range)
-    }
 }
 
 impl<T> core::ops::Deref for PageVec<T> {
@@ -64,300 +52,215 @@ impl<T> core::ops::Deref for PageVec<T> {
     }
 }
 
-pub struct PageString {
-    pub inner: PageVec<u8>,
+/// a string stored in page sized chunks, appending never moves the bytes already in it and
+/// consuming from the front frees the chunks that were fully consumed, made for the TTY buffers
+/// which would otherwise reallocate and copy everything on large bursts of output
+pub struct ChunkedString {
+    chunks: VecDeque<PageVec<u8>>,
+    /// the amount of bytes already consumed from the first chunk
+    head: usize,
+    len: usize,
+    /// the oldest chunk is dropped when pushing would go over this amount of chunks
+    max_chunks: Option<usize>,
 }
 
-impl PageString {
-    pub fn new() -> Self {
+impl ChunkedString {
+    pub const CHUNK_SIZE: usize = PAGE_SIZE;
+
+    pub const fn new() -> Self {
         Self {
-            inner: PageVec::new(),
+            chunks: VecDeque::new(),
+            head: 0,
+            len: 0,
+            max_chunks: None,
         }
     }
 
-    #[inline]
-    pub fn push_str(&mut self, s: &str) {
-        self.inner.extend_from_slice(s.as_bytes());
-    }
-
-    pub fn push_char(&mut self, c: char) {
-        let mut dst = [0; 4];
-        let fake_str = c.encode_utf8(&mut dst);
-        self.push_str(fake_str);
-    }
-
-    pub fn pop(&mut self) -> Option<char> {
-        let char = self.as_str().chars().next_back()?;
-        self.inner.truncate(self.len() - char.len_utf8());
-        Some(char)
-    }
-
-    pub fn clear(&mut self) {
-        self.inner.clear();
+    /// a [`ChunkedString`] that only keeps the last `max_chunks` chunks it's pushed
+    pub const fn with_max_chunks(max_chunks: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            head: 0,
+            len: 0,
+            max_chunks: Some(max_chunks),
+        }
     }
 
+    #[cfg(feature = "test")]
     pub fn len(&self) -> usize {
-        self.inner.len()
-    }
-
-    pub fn as_str(&self) -> &str {
-        unsafe { core::str::from_utf8_unchecked(&self.inner) }
-    }
-
-    pub fn ends_with<P>(&self, other: P) -> bool
-    where
-        P: Pattern,
-        for<'a> P::Searcher<'a>: ReverseSearcher<'a>,
-    {
-        self.as_str().ends_with(other)
+        self.len
     }
 
     pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+        self.len == 0
     }
-}
 
-struct LinkedListNode<T> {
-    inner: T,
-    next: Option<NonNull<Self>>,
-    prev: Option<NonNull<Self>>,
-    marker: PhantomData<Box<Self>>,
-}
-
-/// An Iterator like LinkedList
-pub struct LinkedList<T> {
-    head: Option<NonNull<LinkedListNode<T>>>,
-    tail: Option<NonNull<LinkedListNode<T>>>,
-    current: Option<NonNull<LinkedListNode<T>>>,
-    prev: Option<NonNull<LinkedListNode<T>>>,
-
-    len: usize,
-}
-
-impl<T> LinkedList<T> {
-    pub fn new() -> Self {
-        Self {
-            head: None,
-            current: None,
-            prev: None,
-            tail: None,
-            len: 0,
-        }
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.head = 0;
+        self.len = 0;
     }
 
-    pub fn len(&self) -> usize {
-        self.len
+    /// the chunks of the string from the front, a char may be split across 2 chunks
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.iter().enumerate().map(|(i, chunk)| {
+            if i == 0 {
+                &chunk[self.head..]
+            } else {
+                &chunk[..]
+            }
+        })
     }
 
-    /// Pushes a value to the end of the list.
-    pub fn push(&mut self, value: T) {
-        let node = Box::new(LinkedListNode {
-            inner: value,
-            next: None,
-            prev: None,
-            marker: PhantomData,
-        });
-        let node_ptr = NonNull::from(Box::leak(node));
-        unsafe {
-            self.push_node(node_ptr);
-        }
+    /// the bytes of the string from the front
+    pub fn bytes(&self) -> impl DoubleEndedIterator<Item = u8> + '_ {
+        self.chunks.iter().enumerate().flat_map(|(i, chunk)| {
+            let chunk = if i == 0 {
+                &chunk[self.head..]
+            } else {
+                &chunk[..]
+            };
+            chunk.iter().copied()
+        })
     }
 
-    unsafe fn push_node(&mut self, node: NonNull<LinkedListNode<T>>) {
-        if let Some(tail) = self.tail {
-            (*tail.as_ptr()).next = Some(node);
-            (*node.as_ptr()).prev = Some(tail);
-
-            self.tail = Some(node);
-        } else {
-            // initializes the list if it is empty
-            self.head = Some(node);
-            self.tail = Some(node);
-            self.current = Some(node);
+    fn drop_front_chunk(&mut self) {
+        if let Some(chunk) = self.chunks.pop_front() {
+            self.len -= chunk.len() - self.head;
+            self.head = 0;
         }
-        self.len += 1;
     }
 
-    unsafe fn remove_node(&mut self, node: NonNull<LinkedListNode<T>>) -> T {
-        let next = (*node.as_ptr()).next;
-        let prev = (*node.as_ptr()).prev;
-
-        if let Some(next) = next {
-            (*next.as_ptr()).prev = prev;
-        }
-
-        if let Some(prev) = prev {
-            (*prev.as_ptr()).next = next;
-        }
+    fn push_bytes(&mut self, mut bytes: &[u8]) {
+        let mut dropped = false;
+        while !bytes.is_empty() {
+            let chunk = match self.chunks.back_mut() {
+                Some(chunk) if chunk.len() < Self::CHUNK_SIZE => chunk,
+                _ => {
+                    if self.max_chunks.is_some_and(|max| self.chunks.len() >= max) {
+                        self.drop_front_chunk();
+                        dropped = true;
+                    }
+                    self.chunks
+                        .push_back(PageVec::with_capacity(Self::CHUNK_SIZE));
+                    self.chunks.back_mut().unwrap()
+                }
+            };
 
-        if self.head.is_some_and(|head| head == node) {
-            self.head = next;
+            let count = (Self::CHUNK_SIZE - chunk.len()).min(bytes.len());
+            chunk.extend_from_slice(&bytes[..count]);
+            self.len += count;
+            bytes = &bytes[count..];
         }
 
-        if self.tail.is_some_and(|tail| tail == node) {
-            self.tail = prev;
+        // dropping a chunk might have split the char at it's start, the remaining continuation
+        // bytes are dropped so that the string stays valid utf8
+        while dropped && self.bytes().next().is_some_and(|byte| byte & 0xC0 == 0x80) {
+            self.consume(1);
         }
+    }
 
-        if self.current.is_some_and(|c| c == node) {
-            self.current = prev;
-        }
+    #[inline]
+    pub fn push_str(&mut self, s: &str) {
+        self.push_bytes(s.as_bytes());
+    }
 
-        self.len -= 1;
-        let results = Box::from_non_null(node);
-        results.inner
+    pub fn push_char(&mut self, c: char) {
+        let mut dst = [0; 4];
+        self.push_str(c.encode_utf8(&mut dst));
     }
 
-    /// removes the first element where `condition` on returns true
-    /// returns the removed element
-    pub fn remove_where<C>(&mut self, condition: C) -> Option<T>
-    where
-        C: Fn(&mut T) -> bool,
-    {
-        let mut current_node = self.head;
+    /// returns the last char of the string without removing it
+    fn last_char(&self) -> Option<char> {
+        let mut bytes = [0; 4];
+        let mut count = 0;
 
-        while let Some(node) = current_node {
-            unsafe {
-                if condition(&mut (*node.as_ptr()).inner) {
-                    return Some(self.remove_node(node));
-                }
-                current_node = (*node.as_ptr()).next;
+        for byte in self.bytes().rev().take(4) {
+            count += 1;
+            bytes[4 - count] = byte;
+            // not a continuation byte
+            if byte & 0xC0 != 0x80 {
+                break;
             }
         }
-        None
-    }
 
-    pub fn next(&mut self) -> Option<&mut T> {
-        let current = self.current?;
-        unsafe {
-            if let Some(node) = (*current.as_ptr()).next {
-                self.current = Some(node);
-                Some(&mut (*node.as_ptr()).inner)
-            } else {
-                None
-            }
-        }
+        str::from_utf8(&bytes[4 - count..]).ok()?.chars().next()
     }
-    /// same as `Self::next` but wraps around back to the start if it reaches the end
-    /// returns `None` if the list is empty
-    pub fn next_wrap(&mut self) -> Option<&mut T> {
-        let current = self.current?;
-        unsafe {
-            if let Some(node) = (*current.as_ptr()).next {
-                self.current = Some(node);
-                Some(&mut (*current.as_ptr()).inner)
+
+    // only used by the tests since the TTY edits it's input with `LineEditor`
+    #[allow(dead_code)]
+    pub fn pop(&mut self) -> Option<char> {
+        let char = self.last_char()?;
+        let mut count = char.len_utf8();
+
+        while count > 0 {
+            let only_chunk = self.chunks.len() == 1;
+            let chunk = self.chunks.back_mut().unwrap();
+            let available = if only_chunk {
+                chunk.len() - self.head
             } else {
-                self.current = self.head;
-                Some(&mut (*current.as_ptr()).inner)
+                chunk.len()
+            };
+
+            let removed = available.min(count);
+            chunk.truncate(chunk.len() - removed);
+            self.len -= removed;
+            count -= removed;
+
+            if chunk.is_empty() || (only_chunk && chunk.len() == self.head) {
+                self.chunks.pop_back();
+                if self.chunks.is_empty() {
+                    self.head = 0;
+                }
             }
         }
-    }
 
-    #[allow(dead_code)]
-    pub fn current(&self) -> Option<&T> {
-        let current = self.current?;
-        unsafe { Some(&(*current.as_ptr()).inner) }
+        Some(char)
     }
 
-    pub fn current_mut(&mut self) -> Option<&mut T> {
-        let current = self.current?;
-        unsafe { Some(&mut (*current.as_ptr()).inner) }
+    pub fn ends_with(&self, c: char) -> bool {
+        self.last_char() == Some(c)
     }
 
-    /// returns an iterator that 'continues' the list which means calling `next` on the iterator
-    /// would be the same as calling `next_wrap` on the list, this iterator muttates the list...
-    pub fn continue_iter(&mut self) -> LinkedListContinue<T> {
-        LinkedListContinue { list: self }
-    }
+    /// removes the first `count` bytes of the string, freeing the chunks that were fully consumed
+    pub fn consume(&mut self, count: usize) {
+        let mut count = count.min(self.len);
 
-    /// returns an iterator that acts like a clone of the list
-    /// iterating over the list will yield the same values as iterating over the original list
-    pub fn clone_iter(&self) -> LinkedListCloneIter<T> {
-        let list = Self {
-            head: self.head,
-            tail: self.tail,
-            current: self.head,
-            prev: self.prev,
-            len: self.len,
-        };
-
-        LinkedListCloneIter {
-            list,
-            marker: PhantomData,
-        }
-    }
+        while count > 0 {
+            let available = self.chunks[0].len() - self.head;
+            if count < available {
+                self.head += count;
+                self.len -= count;
+                return;
+            }
 
-    /// returns an iterator that acts like a clone of the list
-    /// iterating over the list will yield the same values as iterating over the original list
-    pub fn clone_iter_mut(&mut self) -> LinkedListCloneIterMut<T> {
-        let list = Self {
-            head: self.head,
-            tail: self.tail,
-            current: self.head,
-            prev: self.prev,
-            len: self.len,
-        };
-
-        LinkedListCloneIterMut {
-            list,
-            marker: PhantomData,
+            count -= available;
+            self.drop_front_chunk();
         }
     }
-}
-
-unsafe impl<T: Send> Send for LinkedList<T> {}
-unsafe impl<T: Sync> Sync for LinkedList<T> {}
 
-/// This `struct` is created by the [`LinkedList::clone_iter`] method
-/// this does not muttate the original list it is a clone of the original list
-pub struct LinkedListCloneIter<'a, T: 'a> {
-    list: LinkedList<T>,
-    marker: PhantomData<&'a LinkedList<T>>,
-}
+    /// moves as many bytes as possible from the front of the string to `buffer`, returns the
+    /// amount of bytes moved
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        let mut count = 0;
 
-impl<'a, T> Iterator for LinkedListCloneIter<'a, T> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let it = self.list.current?;
-        // TODO: this is a hack to prevent the iterator from being used after it has been finished
-        if self.list.next().is_none() {
-            self.list.current = None;
-        }
+        for chunk in self.chunks() {
+            let amount = chunk.len().min(buffer.len() - count);
+            buffer[count..count + amount].copy_from_slice(&chunk[..amount]);
+            count += amount;
 
-        unsafe { Some(&(*it.as_ptr()).inner) }
-    }
-}
-
-/// This `struct` is created by the [`LinkedList::clone_iter_mut`] method
-/// this does not muttate the original list it is a clone of the original list
-pub struct LinkedListCloneIterMut<'a, T: 'a> {
-    list: LinkedList<T>,
-    marker: PhantomData<&'a mut LinkedList<T>>,
-}
-
-impl<'a, T> Iterator for LinkedListCloneIterMut<'a, T> {
-    type Item = &'a mut T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let it = self.list.current?;
-        // TODO: this is a hack to prevent the iterator from being used after it has been finished
-        if self.list.next().is_none() {
-            self.list.current = None;
+            if count == buffer.len() {
+                break;
+            }
         }
 
-        unsafe { Some(&mut (*it.as_ptr()).inner) }
+        self.consume(count);
+        count
     }
 }
-/// This `struct` is created by the [`LinkedList::iter_mut`] method
-/// it provides a wrap_around iterator over the elements of a `LinkedList`. which means it warps around
-/// when it reaches the end of the list.to the head of the list for now
-/// this muttates the original list
-pub struct LinkedListContinue<'a, T: 'a> {
-    list: &'a mut LinkedList<T>,
-}
 
-impl<'a, T> Iterator for LinkedListContinue<'a, T> {
-    type Item = &'a mut T;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.list.next_wrap();
-        unsafe { Some(&mut (*self.list.current?.as_ptr()).inner) }
+impl Default for ChunkedString {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,107 @@
+//! how long each stage of the boot took, shown in proc:/kernelinfo
+//! the stages before the scheduler runs one after the other in `kinit`, once the scheduler is up the
+//! ramdisk is unpacked and the slow drivers are initialized on their own threads which `kmain` joins
+//! before running anything that needs them
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    arch::x86_64::{cycles, cycles_frequency},
+    warn,
+};
+
+/// booting for longer than this is warned about
+const BUDGET_MS: u64 = 2000;
+
+/// what the boot messages are logged as
+struct Boot;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum Stage {
+    Memory,
+    Arch,
+    Devices,
+    Vfs,
+    Ramdisk,
+    Drivers,
+}
+
+impl Stage {
+    const ALL: [Self; 6] = [
+        Self::Memory,
+        Self::Arch,
+        Self::Devices,
+        Self::Vfs,
+        Self::Ramdisk,
+        Self::Drivers,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Arch => "arch",
+            Self::Devices => "devices",
+            Self::Vfs => "vfs",
+            Self::Ramdisk => "ramdisk",
+            Self::Drivers => "drivers",
+        }
+    }
+}
+
+/// the cycles each stage took, indexed by `Stage`
+static DURATIONS: [AtomicU64; Stage::ALL.len()] = [const { AtomicU64::new(0) }; Stage::ALL.len()];
+static START: AtomicU64 = AtomicU64::new(0);
+/// the cycles from `start` to `done`, 0 while booting
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// called as early as possible in `kinit`
+pub fn start() {
+    START.store(cycles(), Ordering::Relaxed);
+}
+
+/// executes `then` timing it as `stage`
+pub fn time<R>(stage: Stage, then: impl FnOnce() -> R) -> R {
+    let start = cycles();
+    let results = then();
+    DURATIONS[stage as usize].store(cycles() - start, Ordering::Relaxed);
+    results
+}
+
+/// called once every stage is done, warns if the boot went over `BUDGET_MS`
+pub fn done() {
+    let total = cycles() - START.load(Ordering::Relaxed);
+    TOTAL.store(total, Ordering::Relaxed);
+
+    if let Some(frequency) = cycles_frequency() {
+        let ms = total / (frequency / 1000);
+        if ms > BUDGET_MS {
+            warn!(Boot, "booting took {ms}ms, over the {BUDGET_MS}ms budget");
+        }
+    }
+}
+
+/// `cycles` in microseconds, or in cycles if the cpu doesn't report it's TSC frequency
+struct Duration(u64);
+
+impl core::fmt::Display for Duration {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match cycles_frequency() {
+            Some(frequency) => write!(f, "{}us", self.0 / (frequency / 1_000_000).max(1)),
+            None => write!(f, "{} cycles", self.0),
+        }
+    }
+}
+
+/// the name and duration of each stage followed by the total, as `boot <stage>: <duration>` lines
+pub fn timings_info(results: &mut impl core::fmt::Write) {
+    for stage in Stage::ALL {
+        let cycles = DURATIONS[stage as usize].load(Ordering::Relaxed);
+        _ = writeln!(results, "boot {}: {}", stage.name(), Duration(cycles));
+    }
+
+    _ = writeln!(
+        results,
+        "boot total: {}",
+        Duration(TOTAL.load(Ordering::Relaxed))
+    );
+}
@@ -0,0 +1,129 @@
+//! a parser for TOML-lite config files like sys:/etc/init.cfg
+//! a file is `[section]` headers each followed by `key = value` lines, lines before the first header
+//! are in the section named "", `#` starts a comment
+//! a value is the rest of it's line trimmed, it can be quoted with `"` to keep it's spaces and `#`s,
+//! there are no escapes, arrays or nested tables, the users split their values themselves
+use core::fmt::{self, Display};
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    /// a `[` without a `]` or an empty section name
+    InvaildSection,
+    /// a line that is neither a section header nor a `key = value`
+    MissingEquals,
+    EmptyKey,
+    UnterminatedString,
+    /// something other than a comment after a section header or a quoted value
+    TrailingCharacters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigError {
+    /// starts at 1
+    pub line: usize,
+    pub kind: ConfigErrorKind,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            ConfigErrorKind::InvaildSection => "invaild section header",
+            ConfigErrorKind::MissingEquals => "expected `key = value`",
+            ConfigErrorKind::EmptyKey => "empty key",
+            ConfigErrorKind::UnterminatedString => "unterminated string",
+            ConfigErrorKind::TrailingCharacters => "trailing characters",
+        };
+        write!(f, "line {}: {reason}", self.line)
+    }
+}
+
+#[derive(Debug)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub entries: Vec<(&'a str, &'a str)>,
+}
+
+/// fails if anything but a comment is left in `rest`
+fn only_comment(rest: &str) -> Result<(), ConfigErrorKind> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(ConfigErrorKind::TrailingCharacters)
+    }
+}
+
+fn parse_value(raw: &str) -> Result<&str, ConfigErrorKind> {
+    let raw = raw.trim_start();
+
+    match raw.strip_prefix('"') {
+        Some(quoted) => {
+            let (value, rest) = quoted
+                .split_once('"')
+                .ok_or(ConfigErrorKind::UnterminatedString)?;
+            only_comment(rest)?;
+            Ok(value)
+        }
+        None => Ok(raw.split('#').next().unwrap_or_default().trim_end()),
+    }
+}
+
+fn parse_header(line: &str) -> Result<&str, ConfigErrorKind> {
+    let (name, rest) = line[1..]
+        .split_once(']')
+        .ok_or(ConfigErrorKind::InvaildSection)?;
+    only_comment(rest)?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ConfigErrorKind::InvaildSection);
+    }
+    Ok(name)
+}
+
+/// parses `data` into it's sections in the order they appear, a section that appears twice is
+/// returned twice
+pub fn parse(data: &str) -> Result<Vec<Section<'_>>, ConfigError> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (index, line) in data.lines().enumerate() {
+        let error = |kind| ConfigError {
+            line: index + 1,
+            kind,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            let name = parse_header(line).map_err(error)?;
+            sections.push(Section {
+                name,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(error(ConfigErrorKind::MissingEquals))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(error(ConfigErrorKind::EmptyKey));
+        }
+        let value = parse_value(value).map_err(error)?;
+
+        if sections.is_empty() {
+            sections.push(Section {
+                name: "",
+                entries: Vec::new(),
+            });
+        }
+        sections.last_mut().unwrap().entries.push((key, value));
+    }
+
+    Ok(sections)
+}
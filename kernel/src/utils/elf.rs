@@ -10,10 +10,18 @@ use crate::{
         copy_to_userspace, frame_allocator,
         paging::{EntryFlags, IterPage, Page, PageTable, PAGE_SIZE},
     },
-    utils::errors::{ErrorStatus, IntoErr},
+    utils::{
+        errors::{Context, ErrorContext, ErrorStatus, IntoErr},
+        expose::AbiVersion,
+    },
     VirtAddr,
 };
 
+const SECTION_NOTE: u32 = 7;
+/// the owner of the note holding the `AbiVersion` a binary was built against
+const ABI_NOTE_OWNER: &[u8] = b"SafaOS\0";
+const ABI_NOTE_TYPE: u32 = 1;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ElfType(u16);
 #[display_consts]
@@ -91,13 +99,17 @@ pub enum ElfError {
     NotAnExecutable,
     MapToError,
     SupportedElfCorrupted,
+    /// a segment is both writable and executable
+    WriteExecSegment,
+    /// the binary was built against an `AbiVersion` the kernel doesn't support
+    IncompatibleAbi,
 }
 
 impl IntoErr for ElfError {
     fn into_err(self) -> ErrorStatus {
         match self {
             Self::NotAnExecutable | Self::NotAnElf => ErrorStatus::NotExecutable,
-            Self::MapToError => ErrorStatus::MMapError,
+            Self::MapToError => ErrorStatus::OutOfMemory,
             Self::SupportedElfCorrupted => ErrorStatus::Corrupted,
 
             _ => ErrorStatus::NotSupported,
@@ -192,6 +204,7 @@ pub struct ProgramHeader {
 
 #[derive(Debug)]
 pub struct Elf<'a> {
+    bytes: &'a [u8],
     pub header: &'a ElfHeader,
     pub sections: &'a [SectionHeader],
     pub program_headers: &'a [ProgramHeader],
@@ -269,8 +282,57 @@ impl<'a> Elf<'a> {
         None
     }
 
+    /// returns the `AbiVersion` in the binary's `.note.safaos` note, None if it has none
+    pub fn abi_version(&self) -> Option<AbiVersion> {
+        let read_u32 = |bytes: &[u8], offset: usize| -> Option<u32> {
+            Some(u32::from_ne_bytes(
+                bytes.get(offset..offset + 4)?.try_into().unwrap(),
+            ))
+        };
+
+        for section in self.sections {
+            if section.section_type != SECTION_NOTE {
+                continue;
+            }
+
+            let Some(notes) = self
+                .bytes
+                .get(section.offset..section.offset.saturating_add(section.size))
+            else {
+                continue;
+            };
+
+            // a note is (name size, desc size, type, name, desc) where the name and desc are
+            // padded to 4 bytes
+            let mut offset = 0;
+            while let (Some(name_size), Some(desc_size), Some(kind)) = (
+                read_u32(notes, offset),
+                read_u32(notes, offset + 4),
+                read_u32(notes, offset + 8),
+            ) {
+                let name_start = offset + 12;
+                let desc_start = name_start + (name_size as usize).next_multiple_of(4);
+                let name = notes.get(name_start..name_start + name_size as usize)?;
+
+                if name == ABI_NOTE_OWNER
+                    && kind == ABI_NOTE_TYPE
+                    && desc_size as usize == size_of::<AbiVersion>()
+                {
+                    return Some(AbiVersion {
+                        major: read_u32(notes, desc_start)?,
+                        minor: read_u32(notes, desc_start + 4)?,
+                    });
+                }
+
+                offset = desc_start + (desc_size as usize).next_multiple_of(4);
+            }
+        }
+
+        None
+    }
+
     /// creates an elf from a u8 ptr that lives as long as `bytes`
-    pub fn new(bytes: &[u8]) -> Result<Self, ElfError> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ElfError> {
         if bytes.len() < size_of::<ElfHeader>() {
             return Err(ElfError::NotAnElf);
         }
@@ -334,6 +396,7 @@ impl<'a> Elf<'a> {
         };
 
         Ok(Self {
+            bytes,
             header,
             sections: section_header_table,
             program_headers: program_headers_table,
@@ -342,9 +405,12 @@ impl<'a> Elf<'a> {
 
     /// loads an executable ELF, maps, and copies it to `page_table`.
     /// returns the program break on success.
-    pub fn load_exec(&self, page_table: &mut PageTable) -> Result<VirtAddr, ElfError> {
+    pub fn load_exec(
+        &self,
+        page_table: &mut PageTable,
+    ) -> Result<VirtAddr, ErrorContext<ElfError>> {
         if self.header.kind != ElfType::EXE {
-            return Err(ElfError::NotAnExecutable);
+            return Err(ElfError::NotAnExecutable.into());
         }
 
         let mut program_break = 0;
@@ -353,18 +419,22 @@ impl<'a> Elf<'a> {
                 continue;
             }
 
-            let mut entry_flags = EntryFlags::PRESENT | EntryFlags::USER_ACCESSIBLE;
-
-            if header.flags.contains(ProgramFlags::READ) {
-                entry_flags |= EntryFlags::empty();
+            // W^X, a segment can either be written or executed never both
+            if header
+                .flags
+                .contains(ProgramFlags::WRITE | ProgramFlags::EXEC)
+            {
+                return Err(ElfError::WriteExecSegment.into());
             }
 
+            let mut entry_flags = EntryFlags::PRESENT | EntryFlags::USER_ACCESSIBLE;
+
             if header.flags.contains(ProgramFlags::WRITE) {
                 entry_flags |= EntryFlags::WRITABLE;
             }
 
-            if header.flags.contains(ProgramFlags::EXEC) {
-                entry_flags |= EntryFlags::WRITABLE;
+            if !header.flags.contains(ProgramFlags::EXEC) {
+                entry_flags |= EntryFlags::NO_EXECUTE;
             }
 
             let start_page = Page::containing_address(header.vaddr);
@@ -376,12 +446,14 @@ impl<'a> Elf<'a> {
 
             unsafe {
                 for page in iter {
-                    let frame = frame_allocator::allocate_frame().ok_or(ElfError::MapToError)?;
+                    let frame = frame_allocator::allocate_frame()
+                        .ok_or(ElfError::MapToError)
+                        .context("segment frame")?;
 
                     page_table
                         .map_to(page, frame, entry_flags)
-                        .ok()
-                        .ok_or(ElfError::MapToError)?;
+                        .map_err(|_| ElfError::MapToError)
+                        .context("segment map")?;
 
                     let slice = slice::from_raw_parts_mut(
                         (frame.start_address | hddm()) as *mut u8,
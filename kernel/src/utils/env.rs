@@ -0,0 +1,63 @@
+//! the default environment, shells spawned by the kernel (the TTY) read it from proc:/environ instead
+//! of hardcoding their environment, it can be changed with `sysenv_set`
+use core::fmt::Write;
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+use super::errors::ErrorStatus;
+
+/// the longest name or value a variable can have
+pub const MAX_VAR_LEN: usize = 1024;
+
+lazy_static! {
+    static ref DEFAULT_ENV: RwLock<Vec<(String, String)>> = RwLock::new(Vec::from([
+        ("PATH".to_owned(), "sys:/bin".to_owned()),
+        ("SHELL".to_owned(), "sys:/bin/Shell".to_owned()),
+    ]));
+}
+
+/// returns the value of `name` in the default environment
+pub fn get(name: &str) -> Option<String> {
+    DEFAULT_ENV
+        .read()
+        .iter()
+        .find(|(var, _)| var == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// sets `name` to `value` in the default environment, an empty `value` removes `name`
+/// fails with `ErrorStatus::StrTooLong` if `name` or `value` are longer than `MAX_VAR_LEN`
+pub fn set(name: &str, value: &str) -> Result<(), ErrorStatus> {
+    if name.len() > MAX_VAR_LEN || value.len() > MAX_VAR_LEN {
+        return Err(ErrorStatus::StrTooLong);
+    }
+
+    if name.is_empty() || name.contains(['=', '\n']) || value.contains('\n') {
+        return Err(ErrorStatus::InvaildStr);
+    }
+
+    let mut env = DEFAULT_ENV.write();
+    let index = env.iter().position(|(var, _)| var == name);
+
+    match index {
+        Some(index) if value.is_empty() => _ = env.remove(index),
+        Some(index) => env[index].1 = value.to_owned(),
+        None if value.is_empty() => {}
+        None => env.push((name.to_owned(), value.to_owned())),
+    }
+
+    Ok(())
+}
+
+/// generates the data of proc:/environ, a `NAME=VALUE` line for each variable
+pub fn environ() -> String {
+    let mut results = String::new();
+
+    for (name, value) in DEFAULT_ENV.read().iter() {
+        _ = writeln!(results, "{}={}", name, value);
+    }
+
+    results
+}
@@ -1,5 +1,11 @@
+use core::fmt::{self, Debug, Display};
 use core::ops::{FromResidual, Try};
 
+use crate::drivers::vfs::FSError;
+use crate::memory::paging::MapToError;
+
+use super::elf::ElfError;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
@@ -30,11 +36,20 @@ pub enum ErrorStatus {
     DirectoryNotEmpty,
     // Generic premissions(protection) related error
     MissingPermissions,
-    // memory allocations and mapping error, most likely that memory is full
-    MMapError,
+    // memory allocations and mapping error, the memory is full
+    OutOfMemory,
     Busy,
     // errors sent by processes
     NotEnoughArguments,
+    // the iso errors libc defines after `NotEnoughArguments`, kept in the same order so the numbers line up
+    ArgumentOutOfDomain,
+    IllegalByteSequence,
+    // for example a buffer too small for the result
+    ResultOutOfRange,
+    // a name or a value is longer than the kernel can store, see `utils::types`
+    StrTooLong,
+    // a non-blocking read or write on a resource that isn't ready, the operation would've waited
+    WouldBlock,
 }
 
 impl FromResidual for ErrorStatus {
@@ -105,3 +120,116 @@ impl<T> Try for ErrorStatusResult<T> {
         }
     }
 }
+
+/// the most contexts an `ErrorContext` keeps, the innermost ones are kept
+pub const MAX_CONTEXTS: usize = 6;
+
+/// an error and the static contexts it passed through, the innermost first, so logs can show
+/// chains like `spawn: elf: segment map: MapToError`, it doesn't allocate
+/// converts into the `ErrorStatus` of the error it wraps at the ABI boundary
+#[derive(Clone, Copy)]
+pub struct ErrorContext<E> {
+    pub error: E,
+    contexts: [&'static str; MAX_CONTEXTS],
+    len: usize,
+}
+
+impl<E> ErrorContext<E> {
+    pub const fn new(error: E) -> Self {
+        Self {
+            error,
+            contexts: [""; MAX_CONTEXTS],
+            len: 0,
+        }
+    }
+
+    /// adds `context` as the outermost context
+    pub fn context(mut self, context: &'static str) -> Self {
+        if self.len < MAX_CONTEXTS {
+            self.contexts[self.len] = context;
+            self.len += 1;
+        }
+        self
+    }
+
+    /// the contexts from the outermost to the innermost
+    pub fn contexts(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.contexts[..self.len].iter().rev().copied()
+    }
+
+    /// converts the error to another layer's error keeping the contexts, for example an `ElfError`
+    /// into the `FSError` a path based spawn returns
+    pub fn map_err<F>(self, map: impl FnOnce(E) -> F) -> ErrorContext<F> {
+        ErrorContext {
+            error: map(self.error),
+            contexts: self.contexts,
+            len: self.len,
+        }
+    }
+}
+
+impl<E> From<E> for ErrorContext<E> {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl<E: Debug> Display for ErrorContext<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for context in self.contexts() {
+            write!(f, "{context}: ")?;
+        }
+        write!(f, "{:?}", self.error)
+    }
+}
+
+impl<E: Debug> Debug for ErrorContext<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<E: IntoErr> IntoErr for ErrorContext<E> {
+    fn into_err(self) -> ErrorStatus {
+        self.error.into_err()
+    }
+}
+
+/// an error that can be wrapped in an `ErrorContext`, or an `ErrorContext` itself
+pub trait IntoContext {
+    type Error;
+    fn into_context(self) -> ErrorContext<Self::Error>;
+}
+
+impl<E> IntoContext for ErrorContext<E> {
+    type Error = E;
+    fn into_context(self) -> ErrorContext<E> {
+        self
+    }
+}
+
+macro_rules! into_context {
+    ($($error: ty),*) => {
+        $(
+            impl IntoContext for $error {
+                type Error = Self;
+                fn into_context(self) -> ErrorContext<Self> {
+                    ErrorContext::new(self)
+                }
+            }
+        )*
+    };
+}
+
+into_context!(ErrorStatus, FSError, ElfError, MapToError);
+
+pub trait Context<T, E> {
+    /// adds `context` to the error as it's outermost context
+    fn context(self, context: &'static str) -> Result<T, ErrorContext<E>>;
+}
+
+impl<T, C: IntoContext> Context<T, C::Error> for Result<T, C> {
+    fn context(self, context: &'static str) -> Result<T, ErrorContext<C::Error>> {
+        self.map_err(|err| err.into_context().context(context))
+    }
+}
@@ -1,8 +1,38 @@
+use core::fmt::Write;
+
+use alloc::string::String;
+use lazy_static::lazy_static;
+use spin::RwLock;
+
 use crate::{
     memory::{frame_allocator, paging::PAGE_SIZE},
     threading::{self},
 };
 
+use super::errors::ErrorStatus;
+
+/// the version of the syscall ABI, binaries embed the version they were built against in a
+/// `.note.safaos` ELF note
+/// `major` is bumped on breaking changes and `minor` on additions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct AbiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl AbiVersion {
+    /// returns wether or not a binary built against `binary` can run on this ABI version
+    pub fn supports(&self, binary: AbiVersion) -> bool {
+        self.major == binary.major && binary.minor <= self.minor
+    }
+}
+
+pub const ABI_VERSION: AbiVersion = AbiVersion {
+    major: 1,
+    minor: 17,
+};
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct SysInfo {
@@ -21,3 +51,97 @@ pub fn info(sysinfo: &mut SysInfo) {
         processes_count: threading::pcount(),
     }
 }
+
+pub const SYSNAME: &str = "SafaOS";
+// the metadata of the build, injected by kernel/build.rs
+/// the git commit the kernel was built from, ends with `-dirty` if the tree had uncommitted changes
+pub const COMMIT: &str = env!("SAFAOS_COMMIT");
+/// in UTC
+pub const BUILD_TIME: &str = env!("SAFAOS_BUILD_TIME");
+pub const RUSTC_VERSION: &str = env!("SAFAOS_RUSTC_VERSION");
+/// the cargo features separated by spaces
+pub const FEATURES: &str = env!("SAFAOS_FEATURES");
+pub const PROFILE: &str = env!("SAFAOS_PROFILE");
+#[cfg(target_arch = "x86_64")]
+pub const MACHINE: &str = "x86_64";
+
+/// the longest hostname
+pub const HOSTNAME_MAX: usize = 64;
+const DEFAULT_HOSTNAME: &str = "safaos";
+
+lazy_static! {
+    static ref HOSTNAME: RwLock<String> = RwLock::new(String::from(DEFAULT_HOSTNAME));
+}
+
+pub fn hostname() -> String {
+    HOSTNAME.read().clone()
+}
+
+/// fails with `ErrorStatus::InvaildStr` unless `name` is 1 to `HOSTNAME_MAX` ascii letters, digits,
+/// `-`s and `.`s
+pub fn set_hostname(name: &str) -> Result<(), ErrorStatus> {
+    let vaild = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+    if name.is_empty() || name.len() > HOSTNAME_MAX || !name.chars().all(vaild) {
+        return Err(ErrorStatus::InvaildStr);
+    }
+
+    *HOSTNAME.write() = String::from(name);
+    Ok(())
+}
+
+/// the length of each field of `Utsname`, the fields are nul terminated
+pub const UTSNAME_LEN: usize = HOSTNAME_MAX + 1;
+
+/// identifies the system, returned by `sysuname`
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Utsname {
+    pub sysname: [u8; UTSNAME_LEN],
+    pub nodename: [u8; UTSNAME_LEN],
+    /// the version of the kernel
+    pub release: [u8; UTSNAME_LEN],
+    /// the git commit the kernel was built from
+    pub version: [u8; UTSNAME_LEN],
+    pub machine: [u8; UTSNAME_LEN],
+}
+
+/// copies `value` into a nul terminated field, cutting it if it doesn't fit
+fn utsname_field(value: &str) -> [u8; UTSNAME_LEN] {
+    let mut field = [0; UTSNAME_LEN];
+    let len = value.len().min(UTSNAME_LEN - 1);
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+    field
+}
+
+pub fn uname() -> Utsname {
+    Utsname {
+        sysname: utsname_field(SYSNAME),
+        nodename: utsname_field(&hostname()),
+        release: utsname_field(env!("CARGO_PKG_VERSION")),
+        version: utsname_field(COMMIT),
+        machine: utsname_field(MACHINE),
+    }
+}
+
+/// generates the data of proc:/kernelinfo
+pub fn kernelinfo() -> String {
+    let mut results = String::new();
+
+    _ = writeln!(results, "name: {SYSNAME}");
+    _ = writeln!(results, "version: {}", env!("CARGO_PKG_VERSION"));
+    _ = writeln!(results, "commit: {COMMIT}");
+    _ = writeln!(results, "built: {BUILD_TIME}");
+    _ = writeln!(results, "rustc: {RUSTC_VERSION}");
+    _ = writeln!(results, "features: {FEATURES}");
+    _ = writeln!(results, "profile: {PROFILE}");
+    _ = writeln!(results, "machine: {MACHINE}");
+    _ = writeln!(results, "hostname: {}", hostname());
+    _ = writeln!(
+        results,
+        "abi version: {}.{}",
+        ABI_VERSION.major, ABI_VERSION.minor
+    );
+    super::boot::timings_info(&mut results);
+
+    results
+}
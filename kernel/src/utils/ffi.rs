@@ -1,5 +1,68 @@
 //! safe FFI types to make it easier to interact with userspace
+use core::{marker::PhantomData, mem::MaybeUninit};
+
+use alloc::{string::String, vec::Vec};
+
 use super::errors::{ErrorStatus, ErrorStatusResult};
+use crate::{
+    arch::usercopy::{self, UserAccess},
+    memory::paging::{current_root_table, EntryFlags},
+    threading,
+};
+
+/// makes sure the pages `ptr..ptr + len` are mapped and accessible from userspace (and writable if
+/// `writable`) in the current address space, nothing is pinned or copied
+/// processes are single threaded and only a process can unmap it's own pages so the pages stay
+/// mapped until the syscall returns, the memory can still only be accessed inside a `UserAccess`
+/// window so the refrences to it are bound to one
+/// `len` is the amount of `T`s, does nothing for trusted kernel processes
+fn validate_user_range<T>(ptr: *const T, len: usize, writable: bool) -> ErrorStatusResult<()> {
+    if len == 0 || !threading::expose::is_untrusted() {
+        return ErrorStatusResult::ok(());
+    }
+
+    let Some(len) = len.checked_mul(size_of::<T>()) else {
+        return ErrorStatusResult::err(ErrorStatus::InvaildPtr);
+    };
+
+    let flags = if writable {
+        EntryFlags::USER_ACCESSIBLE | EntryFlags::WRITABLE
+    } else {
+        EntryFlags::USER_ACCESSIBLE
+    };
+
+    if unsafe { current_root_table() }.is_mapped_with(ptr as usize, len, flags) {
+        ErrorStatusResult::ok(())
+    } else {
+        ErrorStatusResult::err(ErrorStatus::InvaildPtr)
+    }
+}
+
+/// a validated pointer to a value in user memory, see `validate_user_range`
+/// the value can only be accessed inside a `UserAccess` window
+#[derive(Debug)]
+pub struct UserMut<'a, T> {
+    value: *mut T,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> UserMut<'a, T> {
+    /// writes `value` inside it's own user access window
+    pub fn write(self, value: T) {
+        let _access = UserAccess::open();
+        unsafe { self.value.write(value) }
+    }
+
+    /// returns a refrence to the value that can only be used while `access` is open
+    // `access` is only a proof that the window is open, it isn't what is borrowed
+    #[allow(clippy::mut_from_ref)]
+    pub fn get_mut<'b>(self, _access: &'b UserAccess) -> &'b mut T
+    where
+        'a: 'b,
+    {
+        unsafe { &mut *self.value }
+    }
+}
 
 /// a Nullable refrence to a value
 /// if null it is a None if Some it is a valid reference
@@ -24,16 +87,21 @@ impl<'a, T> Optional<T> {
         self.value.is_null()
     }
 
-    pub unsafe fn unwrap_unchecked(self) -> &'a mut T {
-        &mut *self.value
-    }
-
-    pub fn into_option(self) -> Option<&'a mut T> {
+    /// validates the value, see `validate_user_range`
+    /// returns Err(ErrorStatus::InvaildPtr) if it isn't null and isn't aligned or writable by the caller
+    pub fn into_option(self) -> ErrorStatusResult<Option<UserMut<'a, T>>> {
         if self.is_none() {
-            None
-        } else {
-            unsafe { Some(self.unwrap_unchecked()) }
+            return ErrorStatusResult::ok(None);
         }
+
+        if !self.value.is_aligned() {
+            return ErrorStatusResult::err(ErrorStatus::InvaildPtr);
+        }
+        validate_user_range(self.value, 1, true)?;
+        ErrorStatusResult::ok(Some(UserMut {
+            value: self.value,
+            _marker: PhantomData,
+        }))
     }
 
     pub fn from_option(value: Option<&mut T>) -> Self {
@@ -78,14 +146,36 @@ impl<'a, T> Slice<T> {
         if !(ptr.is_aligned() || ptr.is_null()) {
             ErrorStatusResult::err(ErrorStatus::InvaildPtr)
         } else {
+            #[cfg(feature = "debug-poison")]
+            if !ptr.is_null() {
+                validate_user_range(ptr, len, false)?;
+            }
             ErrorStatusResult::ok(Self { ptr, len })
         }
     }
 
-    /// converts Slice to a slice
+    /// like `new` but also makes sure the pages the slice refers to are accessible by the caller, see `validate_user_range`
+    /// returns Err(ErrorStatus::InvaildPtr) if any of the pages isn't accessible by the caller
+    pub fn new_validated(ptr: *const T, len: usize) -> ErrorStatusResult<Self> {
+        let slice = Self::new(ptr, len)?;
+        if !ptr.is_null() {
+            validate_user_range(ptr, len, false)?;
+        }
+        ErrorStatusResult::ok(slice)
+    }
+
+    pub const fn ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// converts Slice to a slice that can only be used while `access` is open
     /// returns an empty slice if the pointer is null
     #[inline(always)]
-    pub fn into_slice(self) -> &'a [T] {
+    pub fn into_slice(self, _access: &'a UserAccess) -> &'a [T] {
         if self.ptr.is_null() {
             return &[];
         }
@@ -93,12 +183,6 @@ impl<'a, T> Slice<T> {
     }
 }
 
-impl<'a, T> From<Slice<T>> for &'a [T] {
-    fn from(slice: Slice<T>) -> &'a [T] {
-        slice.into_slice()
-    }
-}
-
 /// a mutable slice of values
 /// if into_slice is called on a null pointer it will return an empty slice
 #[derive(Debug, Clone, Copy)]
@@ -115,14 +199,37 @@ impl<'a, T> SliceMut<T> {
         if !(ptr.is_aligned() || ptr.is_null()) {
             ErrorStatusResult::err(ErrorStatus::InvaildPtr)
         } else {
+            #[cfg(feature = "debug-poison")]
+            if !ptr.is_null() {
+                validate_user_range(ptr, len, true)?;
+            }
             ErrorStatusResult::ok(Self { ptr, len })
         }
     }
 
-    /// converts Slice to a slice
+    /// like `new` but also makes sure the pages the slice refers to are writable by the caller, see `validate_user_range`
+    /// returns Err(ErrorStatus::InvaildPtr) if any of the pages isn't writable by the caller
+    pub fn new_validated(ptr: *mut T, len: usize) -> ErrorStatusResult<Self> {
+        let slice = Self::new(ptr, len)?;
+        if !ptr.is_null() {
+            validate_user_range(ptr, len, true)?;
+        }
+        ErrorStatusResult::ok(slice)
+    }
+
+    pub const fn ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// converts Slice to a slice that can only be used while `access` is open
     /// returns an empty slice if the pointer is null
     #[inline(always)]
-    pub fn into_slice(self) -> &'a mut [T] {
+    #[allow(clippy::mut_from_ref)]
+    pub fn into_slice(self, _access: &'a UserAccess) -> &'a mut [T] {
         if self.ptr.is_null() {
             return &mut [];
         }
@@ -131,52 +238,78 @@ impl<'a, T> SliceMut<T> {
     }
 }
 
-impl<'a, T> From<SliceMut<T>> for &'a mut [T] {
-    fn from(slice: SliceMut<T>) -> &'a mut [T] {
-        slice.into_slice()
+impl Slice<u8> {
+    /// copies the slice into a kernel string
+    /// returns Err(ErrorStatus::InvaildPtr) if the slice isn't mapped and Err(ErrorStatus::InvaildStr) if
+    /// it isn't vaild utf8
+    pub fn into_string(self) -> ErrorStatusResult<String> {
+        let bytes = self.copy_to_vec()?;
+        match String::from_utf8(bytes) {
+            Ok(string) => ErrorStatusResult::ok(string),
+            Err(_) => ErrorStatusResult::err(ErrorStatus::InvaildStr),
+        }
     }
-}
 
-impl SliceMut<u8> {
-    /// converts the slice to a str which is accepted by the kernel
-    /// may panic if the slice is not valid utf8 in the future
-    pub fn into_str<'a>(self) -> &'a str {
-        unsafe { core::str::from_utf8_unchecked(self.into_slice()) }
+    /// like `into_string` but fails with Err(ErrorStatus::StrTooLong) without copying anything if
+    /// the slice is longer than `max` bytes
+    pub fn into_string_max(self, max: usize) -> ErrorStatusResult<String> {
+        if self.len > max {
+            return ErrorStatusResult::err(ErrorStatus::StrTooLong);
+        }
+        self.into_string()
     }
 }
 
-impl Slice<u8> {
-    /// converts the slice to a str which is accepted by the kernel
-    /// may panic if the slice is not valid utf8 in the future
-    pub fn into_str<'a>(self) -> &'a str {
-        unsafe { core::str::from_utf8_unchecked(self.into_slice()) }
-    }
-}
+impl<T: Copy> Slice<T> {
+    /// copies the slice into kernel memory
+    /// returns Err(ErrorStatus::InvaildPtr) instead of faulting if the slice isn't mapped
+    pub fn copy_to_vec(self) -> ErrorStatusResult<Vec<T>> {
+        if self.ptr.is_null() {
+            return ErrorStatusResult::ok(Vec::new());
+        }
+        // validated before allocating so that a bogus length fails instead of exhausting the heap
+        validate_user_range(self.ptr, self.len, false)?;
+
+        let mut vec = Vec::with_capacity(self.len);
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(vec.as_mut_ptr() as *mut u8, self.len * size_of::<T>())
+        };
+
+        if let Err(err) = usercopy::copy_from_user(bytes, self.ptr as *const u8) {
+            return ErrorStatusResult::err(err);
+        }
 
-impl<'a> From<Slice<u8>> for &'a str {
-    fn from(slice: Slice<u8>) -> &'a str {
-        slice.into_str()
+        unsafe { vec.set_len(self.len) };
+        ErrorStatusResult::ok(vec)
     }
 }
 
-impl<'a> From<SliceMut<u8>> for &'a str {
-    fn from(slice: SliceMut<u8>) -> &'a str {
-        slice.into_str()
+impl SliceMut<u8> {
+    /// copies `src` to the start of the slice
+    /// returns Err(ErrorStatus::InvaildPtr) instead of faulting if the slice isn't mapped
+    pub fn copy_from(self, src: &[u8]) -> ErrorStatusResult<()> {
+        if src.len() > self.len {
+            return ErrorStatusResult::err(ErrorStatus::Generic);
+        }
+        validate_user_range(self.ptr, src.len(), true)?;
+
+        match usercopy::copy_to_user(self.ptr, src) {
+            Ok(()) => ErrorStatusResult::ok(()),
+            Err(err) => ErrorStatusResult::err(err),
+        }
     }
 }
 
 impl SliceMut<Slice<u8>> {
-    /// converts the slice to a slice of strs which is accepted by the kernel
-    /// may panic if the slice is not valid utf8 in the future
-    pub fn into_str_slice<'a>(self) -> &'a [&'a str] {
-        let slice = self.into_slice();
-        let double_slice = unsafe { &mut *(self.into_slice() as *const _ as *mut [&str]) };
+    /// copies the slice and the strings it points to into kernel memory, see `Slice::into_string`
+    pub fn into_strings(self) -> ErrorStatusResult<Vec<String>> {
+        let slices = Slice::new(self.ptr, self.len)?.copy_to_vec()?;
 
-        for (i, item) in slice.iter().enumerate() {
-            double_slice[i] = item.into_str();
+        let mut strings = Vec::with_capacity(slices.len());
+        for slice in slices {
+            strings.push(slice.into_string()?);
         }
-
-        double_slice
+        ErrorStatusResult::ok(strings)
     }
 }
 /// a non-Nullable muttable refrence to a value
@@ -188,11 +321,16 @@ pub struct RequiredMut<T> {
 }
 
 impl<'a, T> RequiredMut<T> {
-    pub fn get(self) -> ErrorStatusResult<&'a mut T> {
+    /// validates the value, see `validate_user_range`
+    pub fn get(self) -> ErrorStatusResult<UserMut<'a, T>> {
         if self.value.is_null() || !self.value.is_aligned() {
             ErrorStatusResult::err(ErrorStatus::InvaildPtr)
         } else {
-            ErrorStatusResult::ok(unsafe { &mut *self.value })
+            validate_user_range(self.value, 1, true)?;
+            ErrorStatusResult::ok(UserMut {
+                value: self.value,
+                _marker: PhantomData,
+            })
         }
     }
 }
@@ -205,12 +343,22 @@ pub struct Required<T> {
     value: *const T,
 }
 
-impl<'a, T> Required<T> {
-    pub fn get(self) -> ErrorStatusResult<&'a T> {
+impl<T: Copy> Required<T> {
+    /// copies the value into kernel memory
+    /// returns Err(ErrorStatus::InvaildPtr) instead of faulting if the value isn't mapped
+    pub fn get(self) -> ErrorStatusResult<T> {
         if self.value.is_null() || !self.value.is_aligned() {
-            ErrorStatusResult::err(ErrorStatus::InvaildPtr)
-        } else {
-            ErrorStatusResult::ok(unsafe { &*self.value })
+            return ErrorStatusResult::err(ErrorStatus::InvaildPtr);
+        }
+
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+
+        match usercopy::copy_from_user(bytes, self.value as *const u8) {
+            Ok(()) => ErrorStatusResult::ok(unsafe { value.assume_init() }),
+            Err(err) => ErrorStatusResult::err(err),
         }
     }
 }
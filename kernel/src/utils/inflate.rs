@@ -0,0 +1,386 @@
+//! a deflate (RFC 1951) decoder and the gzip (RFC 1952) container around it, used to
+//! decompress the ramdisk when the builder compresses it
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    /// the input ended in the middle of the stream
+    UnexpectedEnd,
+    /// invaild block type, huffman code or distance
+    Corrupted,
+    /// not a gzip member or uses an unsupported compression method
+    InvaildHeader,
+    /// the crc32 or the size in the gzip trailer doesn't match the output
+    ChecksumMismatch,
+}
+
+type Result<T> = core::result::Result<T, InflateError>;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// the order the code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const MAX_BITS: usize = 15;
+const MAX_LIT_CODES: usize = 288;
+const MAX_DIST_CODES: usize = 30;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// reads `count` bits (at most 16) least significant bit first
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.bit_count < count {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::UnexpectedEnd)?;
+            self.pos += 1;
+
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bit_buf & ((1 << count) - 1);
+        self.bit_buf >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// skips the bits left in the current byte
+    fn align(&mut self) {
+        let extra = self.bit_count % 8;
+        self.bit_buf >>= extra;
+        self.bit_count -= extra;
+    }
+
+    /// the amount of input bytes used so far
+    fn consumed(&self) -> usize {
+        self.pos - (self.bit_count / 8) as usize
+    }
+}
+
+/// a canonical huffman code, decoded one bit at a time
+struct Huffman {
+    /// the amount of codes of each length
+    counts: [u16; MAX_BITS + 1],
+    /// the symbols sorted by their code
+    symbols: [u16; MAX_LIT_CODES],
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // a code can't have more codes of a length then there is space left for
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(InflateError::Corrupted);
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = [0u16; MAX_LIT_CODES];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                let offset = &mut offsets[*length as usize];
+                symbols[*offset as usize] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = *count as i32;
+
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(InflateError::Corrupted)
+    }
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<()> {
+    reader.align();
+    let len = reader.bits(16)?;
+    let nlen = reader.bits(16)?;
+    if len != !nlen & 0xFFFF {
+        return Err(InflateError::Corrupted);
+    }
+
+    output.reserve(len as usize);
+    for _ in 0..len {
+        output.push(reader.bits(8)? as u8);
+    }
+    Ok(())
+}
+
+fn inflate_codes(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..256 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= LENGTH_BASE.len() {
+                    return Err(InflateError::Corrupted);
+                }
+                let len = LENGTH_BASE[symbol] as usize
+                    + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+                let symbol = distances.decode(reader)? as usize;
+                if symbol >= DIST_BASE.len() {
+                    return Err(InflateError::Corrupted);
+                }
+                let distance =
+                    DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err(InflateError::Corrupted);
+                }
+
+                // the copy can overlap with the bytes it produces
+                let start = output.len() - distance;
+                output.reserve(len);
+                for i in 0..len {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; MAX_LIT_CODES];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    let literals = Huffman::new(&lengths).unwrap();
+    let distances = Huffman::new(&[5; MAX_DIST_CODES]).unwrap();
+    (literals, distances)
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literals_count = reader.bits(5)? as usize + 257;
+    let distances_count = reader.bits(5)? as usize + 1;
+    let code_lengths_count = reader.bits(4)? as usize + 4;
+
+    if literals_count > 286 || distances_count > MAX_DIST_CODES {
+        return Err(InflateError::Corrupted);
+    }
+
+    let mut lengths = [0u8; MAX_LIT_CODES + MAX_DIST_CODES];
+    for index in &CODE_LENGTH_ORDER[..code_lengths_count] {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&lengths[..CODE_LENGTH_ORDER.len()])?;
+
+    let total = literals_count + distances_count;
+    let mut index = 0;
+    while index < total {
+        let symbol = code_lengths.decode(reader)?;
+
+        let (length, repeat) = match symbol {
+            0..16 => (symbol as u8, 1),
+            16 => {
+                let previous = *index
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or(InflateError::Corrupted)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+
+        if index + repeat > total {
+            return Err(InflateError::Corrupted);
+        }
+        lengths[index..index + repeat].fill(length);
+        index += repeat;
+    }
+
+    // the end of block code has to exist
+    if lengths[256] == 0 {
+        return Err(InflateError::Corrupted);
+    }
+
+    let literals = Huffman::new(&lengths[..literals_count])?;
+    let distances = Huffman::new(&lengths[literals_count..total])?;
+    Ok((literals, distances))
+}
+
+/// decompresses the raw deflate stream at the start of `data` appending it to `output`
+/// returns the amount of bytes of `data` the stream took
+pub fn inflate(data: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+    let mut reader = BitReader::new(data);
+
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, output)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_codes(&mut reader, output, &literals, &distances)?
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_codes(&mut reader, output, &literals, &distances)?
+            }
+            _ => return Err(InflateError::Corrupted),
+        }
+
+        if last {
+            return Ok(reader.consumed());
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const GZIP_DEFLATE: u8 = 8;
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+
+#[inline]
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&GZIP_MAGIC)
+}
+
+/// decompresses the first gzip member in `data`
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    if !is_gzip(data) || data.len() < 18 || data[2] != GZIP_DEFLATE {
+        return Err(InflateError::InvaildHeader);
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        let extra = data.get(pos..pos + 2).ok_or(InflateError::UnexpectedEnd)?;
+        pos += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+
+    // the name and the comment are NUL terminated
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let rest = data.get(pos..).ok_or(InflateError::UnexpectedEnd)?;
+            let len = rest
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or(InflateError::UnexpectedEnd)?;
+            pos += len + 1;
+        }
+    }
+
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    let stream = data.get(pos..).ok_or(InflateError::UnexpectedEnd)?;
+    let mut output = Vec::new();
+
+    let used = inflate(stream, &mut output)?;
+    let trailer = stream
+        .get(used..used + 8)
+        .ok_or(InflateError::UnexpectedEnd)?;
+
+    let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+    let size = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+
+    if size != output.len() as u32 || crc != crc32(&output) {
+        return Err(InflateError::ChecksumMismatch);
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,193 @@
+//! lockdep-lite, tracks the locks held by the current context and the order lock classes are
+//! acquired in, reports acquiring a lock class that could complete a cycle (a possible deadlock)
+//! the held locks belong to the process not the cpu, they are saved and restored on context switch
+use core::ptr::addr_of_mut;
+
+use crate::{arch::without_interrupts, serial};
+
+const MAX_CLASSES: usize = 64;
+const MAX_HELD: usize = 32;
+
+/// the lock classes held by a context in order of acquiring
+#[derive(Debug, Clone, Copy)]
+pub struct HeldLocks {
+    classes: [usize; MAX_HELD],
+    len: usize,
+}
+
+impl HeldLocks {
+    pub const fn new() -> Self {
+        Self {
+            classes: [0; MAX_HELD],
+            len: 0,
+        }
+    }
+
+    fn as_slice(&self) -> &[usize] {
+        &self.classes[..self.len]
+    }
+}
+
+struct LockDep {
+    classes: [&'static str; MAX_CLASSES],
+    class_count: usize,
+    /// bit b of `order[a]` is set if class b was acquired while class a was held
+    order: [u64; MAX_CLASSES],
+    /// bit b of `reported[a]` is set if acquiring a while holding b was already reported
+    reported: [u64; MAX_CLASSES],
+}
+
+impl LockDep {
+    const fn new() -> Self {
+        Self {
+            classes: [""; MAX_CLASSES],
+            class_count: 0,
+            order: [0; MAX_CLASSES],
+            reported: [0; MAX_CLASSES],
+        }
+    }
+
+    /// returns the class id of `name`, registers it if it doesn't exist
+    /// returns None if there is no more space for classes
+    fn class_id(&mut self, name: &'static str) -> Option<usize> {
+        if let Some(id) = self.classes[..self.class_count]
+            .iter()
+            .position(|class| *class == name)
+        {
+            return Some(id);
+        }
+
+        if self.class_count >= MAX_CLASSES {
+            return None;
+        }
+
+        self.classes[self.class_count] = name;
+        self.class_count += 1;
+        Some(self.class_count - 1)
+    }
+
+    /// wether or not `to` was ever acquired after `from` directly or indirectly
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut visited: u64 = 0;
+        let mut stack = [0usize; MAX_CLASSES];
+        let mut len = 1;
+        stack[0] = from;
+
+        while len > 0 {
+            len -= 1;
+            let class = stack[len];
+
+            if class == to {
+                return true;
+            }
+
+            if visited & (1 << class) != 0 {
+                continue;
+            }
+            visited |= 1 << class;
+
+            for next in 0..self.class_count {
+                if self.order[class] & (1 << next) != 0 && visited & (1 << next) == 0 {
+                    stack[len] = next;
+                    len += 1;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn print_held(&self, held: &HeldLocks) {
+        serial!("held locks:\n");
+        for class in held.as_slice() {
+            serial!("  {}\n", self.classes[*class]);
+        }
+    }
+}
+
+static STATE: spin::Mutex<LockDep> = spin::Mutex::new(LockDep::new());
+/// the held locks of the current context
+static mut HELD: HeldLocks = HeldLocks::new();
+
+/// tracks acquiring lock class `name`, a `try_acquire` cannot deadlock so it isn't checked
+pub fn acquire(name: &'static str, try_acquire: bool) {
+    without_interrupts(|| {
+        let mut state = STATE.lock();
+        let Some(id) = state.class_id(name) else {
+            return;
+        };
+
+        let held = unsafe { &mut *addr_of_mut!(HELD) };
+
+        if !try_acquire {
+            for index in 0..held.len {
+                let holding = held.classes[index];
+                if holding == id {
+                    continue;
+                }
+
+                if state.reaches(id, holding) {
+                    if state.reported[id] & (1 << holding) == 0 {
+                        state.reported[id] |= 1 << holding;
+                        serial!(
+                            "\x1B[38;2;255;0;0m[LOCKDEP]\x1B[0m possible deadlock: acquiring {} while holding {}, but {} was acquired before {} before\n",
+                            state.classes[id],
+                            state.classes[holding],
+                            state.classes[id],
+                            state.classes[holding]
+                        );
+                        state.print_held(held);
+                    }
+                } else {
+                    state.order[holding] |= 1 << id;
+                }
+            }
+        }
+
+        if held.len < MAX_HELD {
+            held.classes[held.len] = id;
+            held.len += 1;
+        }
+    })
+}
+
+/// tracks releasing lock class `name`, locks may be released in any order
+pub fn release(name: &'static str) {
+    without_interrupts(|| {
+        let mut state = STATE.lock();
+        let Some(id) = state.class_id(name) else {
+            return;
+        };
+
+        let held = unsafe { &mut *addr_of_mut!(HELD) };
+        if let Some(index) = held.as_slice().iter().rposition(|class| *class == id) {
+            held.classes.copy_within(index + 1..held.len, index);
+            held.len -= 1;
+        }
+    })
+}
+
+/// panics if the current context is holding any lock, `at` describes what is about to happen
+pub fn assert_none_held(at: &str) {
+    without_interrupts(|| {
+        let held = unsafe { &*addr_of_mut!(HELD) };
+        if held.len != 0 {
+            let state = STATE.lock();
+            state.print_held(held);
+            drop(state);
+            panic!("lockdep: {} lock(s) held while {}", held.len, at);
+        }
+    })
+}
+
+/// saves the held locks of the context being switched from
+/// must be called with interrupts disabled
+pub fn save() -> HeldLocks {
+    unsafe { *addr_of_mut!(HELD) }
+}
+
+/// restores the held locks of the context being switched to
+/// must be called with interrupts disabled
+pub fn restore(held: HeldLocks) {
+    unsafe { *addr_of_mut!(HELD) = held }
+}
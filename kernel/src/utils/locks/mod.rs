@@ -0,0 +1,197 @@
+//! named locks, with the `lockdep` feature enabled acquiring and releasing them is tracked to detect
+//! lock order inversions (possible deadlocks) and locks held while yielding
+//! locks with the same name are treated as the same lock class by lockdep
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+pub mod rcu;
+
+use core::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+};
+
+pub struct Mutex<T: ?Sized> {
+    name: &'static str,
+    inner: spin::Mutex<T>,
+}
+
+pub struct MutexGuard<'a, T: ?Sized + 'a> {
+    #[cfg(feature = "lockdep")]
+    name: &'static str,
+    guard: spin::MutexGuard<'a, T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            inner: spin::Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    #[inline]
+    fn guard<'a>(&self, guard: spin::MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        MutexGuard {
+            #[cfg(feature = "lockdep")]
+            name: self.name,
+            guard,
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, false);
+        self.guard(self.inner.lock())
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Debug> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Mutex")
+            .field("name", &self.name)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.name);
+    }
+}
+
+pub struct RwLock<T: ?Sized> {
+    name: &'static str,
+    inner: spin::RwLock<T>,
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    #[cfg(feature = "lockdep")]
+    name: &'static str,
+    guard: spin::RwLockReadGuard<'a, T>,
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    #[cfg(feature = "lockdep")]
+    name: &'static str,
+    guard: spin::RwLockWriteGuard<'a, T>,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            inner: spin::RwLock::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, false);
+        RwLockReadGuard {
+            #[cfg(feature = "lockdep")]
+            name: self.name,
+            guard: self.inner.read(),
+        }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        let guard = self.inner.try_read()?;
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, true);
+        Some(RwLockReadGuard {
+            #[cfg(feature = "lockdep")]
+            name: self.name,
+            guard,
+        })
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, false);
+        RwLockWriteGuard {
+            #[cfg(feature = "lockdep")]
+            name: self.name,
+            guard: self.inner.write(),
+        }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        let guard = self.inner.try_write()?;
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, true);
+        Some(RwLockWriteGuard {
+            #[cfg(feature = "lockdep")]
+            name: self.name,
+            guard,
+        })
+    }
+}
+
+impl<T: Debug> Debug for RwLock<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RwLock")
+            .field("name", &self.name)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.name);
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.name);
+    }
+}
@@ -0,0 +1,108 @@
+//! a read-copy-update cell for read mostly data
+//! readers never take a lock, they just register themselves in the current epoch and read the
+//! published pointer, writers publish a new copy then flip the epoch and wait for all readers of the old epoch
+//! to leave before freeing the old copy (epoch based reclamation)
+//! readers must not yield or sleep while reading
+use core::{
+    hint::spin_loop,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+use alloc::boxed::Box;
+
+use crate::threading::expose::thread_yeild;
+
+pub struct Rcu<T> {
+    data: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    /// readers in each epoch parity
+    readers: [AtomicUsize; 2],
+    /// serializes writers including their grace period, a grace period only waits for the parity
+    /// before it's flip so it mustn't overlap with another writer's
+    writer: spin::Mutex<()>,
+}
+
+unsafe impl<T: Send + Sync> Send for Rcu<T> {}
+unsafe impl<T: Send + Sync> Sync for Rcu<T> {}
+
+impl<T> Rcu<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            data: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: spin::Mutex::new(()),
+        }
+    }
+
+    /// enters a read side critical section, returns the epoch parity the reader registered in
+    fn read_lock(&self) -> usize {
+        loop {
+            let epoch = self.epoch.load(Ordering::Acquire);
+            let parity = epoch & 1;
+            self.readers[parity].fetch_add(1, Ordering::SeqCst);
+            // the epoch may have been flipped between loading it and registering, in which case a
+            // writer may already be done waiting for our parity
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                return parity;
+            }
+            self.readers[parity].fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    /// executes `then` on the currently published value without taking any lock
+    /// `then` must not yield
+    pub fn read<R>(&self, then: impl FnOnce(&T) -> R) -> R {
+        let parity = self.read_lock();
+        let data = unsafe { &*self.data.load(Ordering::Acquire) };
+        let results = then(data);
+        self.readers[parity].fetch_sub(1, Ordering::Release);
+        results
+    }
+
+    /// waits until all readers that could have seen the previously published value are gone
+    /// readers that register after the flip load the pointer after it was swapped, so only the
+    /// parity before the flip is waited for, the writer lock has to be held so that no other writer
+    /// flips the epoch back to that parity meanwhile
+    fn synchronize(&self) {
+        let parity = self.epoch.fetch_add(1, Ordering::SeqCst) & 1;
+        while self.readers[parity].load(Ordering::Acquire) != 0 {
+            // the readers may have been preempted
+            spin_loop();
+            thread_yeild();
+        }
+    }
+
+    /// publishes a new value made by executing `then` on a copy of the current one
+    /// then frees the old value once there are no readers left
+    pub fn update(&self, then: impl FnOnce(&mut T))
+    where
+        T: Clone,
+    {
+        let writer = loop {
+            if let Some(writer) = self.writer.try_lock() {
+                break writer;
+            }
+            // the writer holding it may be waiting for preempted readers
+            thread_yeild();
+        };
+        // only the writer that swaps a value out frees it, so the current value cannot be freed
+        // while we hold the lock and clone it
+        let mut new = unsafe { (*self.data.load(Ordering::Acquire)).clone() };
+        then(&mut new);
+
+        let old = self
+            .data
+            .swap(Box::into_raw(Box::new(new)), Ordering::AcqRel);
+
+        self.synchronize();
+        drop(writer);
+        drop(unsafe { Box::from_raw(old) });
+    }
+}
+
+impl<T> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(*self.data.get_mut()) });
+    }
+}
@@ -1,11 +1,16 @@
 pub mod alloc;
-pub mod ansi;
+pub mod boot;
+pub mod config;
 pub mod display;
-pub mod either;
 pub mod elf;
+pub mod env;
 pub mod errors;
 pub mod expose;
 pub mod ffi;
+pub mod inflate;
+pub mod locks;
+pub mod path;
+pub mod types;
 pub mod ustar;
 
 use core::ops::Deref;
@@ -0,0 +1,33 @@
+//! string level path manipulation
+use alloc::{string::String, vec::Vec};
+
+use crate::drivers::vfs::{FSError, FSResult, Path};
+
+/// normalizes the absolute path `path` collapsing `.`, `..` and empty components, so that
+/// `ram:/a/./b//../c/` becomes `ram:/a/c`
+/// `..` at the root of a drive stays at the root, so a path can never leave it's drive and
+/// `rod:/../ram:/x` is `rod:/ram:/x`
+/// both `/` and `\` are separators, the results only uses `/`
+/// returns Err(FSError::InvaildDrive) if `path` doesn't start with a drive
+pub fn normalize(path: Path) -> FSResult<String> {
+    let mut components = path.split(['/', '\\']);
+
+    let drive = components
+        .next()
+        .filter(|drive| drive.ends_with(':'))
+        .ok_or(FSError::InvaildDrive)?;
+
+    let mut normalized: Vec<&str> = Vec::new();
+    for component in components {
+        match component {
+            "" | "." => {}
+            ".." => _ = normalized.pop(),
+            component => normalized.push(component),
+        }
+    }
+
+    let mut results = String::from(drive);
+    results.push('/');
+    results.push_str(&normalized.join("/"));
+    Ok(results)
+}
@@ -0,0 +1,100 @@
+//! strings stored inline in fixed size buffers, for names that end up in the fixed size fields of
+//! the ABI such as `ProcessInfo::name` and `DirEntry::name`
+//! `try_new` fails with [`TooLong`] (`ErrorStatus::StrTooLong`) for strings that don't fit, the
+//! `*_lossy` constructors truncate them at a char boundary instead, use them only for names that
+//! don't come from userspace
+use core::fmt::{self, Debug, Display};
+use core::ops::Deref;
+
+use super::errors::{ErrorStatus, IntoErr};
+use crate::drivers::vfs::expose::MAX_NAME_LEN;
+
+/// the longest name a process can have
+pub const MAX_PROCESS_NAME_LEN: usize = 64;
+
+/// the name of a process
+pub type Name = InlineStr<MAX_PROCESS_NAME_LEN>;
+/// the name of a file or a directory, not a whole path
+pub type FileName = InlineStr<MAX_NAME_LEN>;
+
+/// a string was longer than `max` bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLong {
+    pub len: usize,
+    pub max: usize,
+}
+
+impl IntoErr for TooLong {
+    fn into_err(self) -> ErrorStatus {
+        ErrorStatus::StrTooLong
+    }
+}
+
+/// a str of at most `N` bytes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct InlineStr<const N: usize> {
+    len: usize,
+    bytes: [u8; N],
+}
+
+impl<const N: usize> InlineStr<N> {
+    fn from_fitting(s: &str) -> Self {
+        let mut bytes = [0; N];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Self {
+            len: s.len(),
+            bytes,
+        }
+    }
+
+    pub fn try_new(s: &str) -> Result<Self, TooLong> {
+        if s.len() > N {
+            return Err(TooLong {
+                len: s.len(),
+                max: N,
+            });
+        }
+
+        Ok(Self::from_fitting(s))
+    }
+
+    /// truncates `s` to the longest prefix that fits without splitting a char
+    pub fn new_lossy(s: &str) -> Self {
+        let mut len = s.len().min(N);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        Self::from_fitting(&s[..len])
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever made from a str truncated at a char boundary
+        unsafe { core::str::from_utf8_unchecked(&self.bytes[..self.len]) }
+    }
+
+    /// the bytes of the str followed by zeros, as it's stored in the ABI structures
+    pub const fn to_padded(self) -> [u8; N] {
+        self.bytes
+    }
+}
+
+impl<const N: usize> Deref for InlineStr<N> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> Debug for InlineStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> Display for InlineStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
@@ -1,7 +1,12 @@
 use core::{fmt::Debug, str};
 
+#[cfg(feature = "test")]
+use alloc::vec::Vec;
 use macros::display_consts;
 
+#[cfg(feature = "test")]
+const BLOCK_SIZE: usize = 512;
+
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Type(u8);
@@ -93,10 +98,10 @@ pub struct TarArchiveIter<'a> {
     at: Option<&'a Inode>,
 }
 
-impl TarArchiveIter<'_> {
+impl<'a> TarArchiveIter<'a> {
     /// safe warpper around Inode::next that verifies the inode before returning it
     /// also returns a refrence instead of a pointer
-    pub fn next(&mut self) -> Option<&Inode> {
+    pub fn next(&mut self) -> Option<&'a Inode> {
         let ret = self.at?;
         let next_ptr = unsafe { Inode::next(ret) };
         let next = unsafe { &*next_ptr };
@@ -106,7 +111,7 @@ impl TarArchiveIter<'_> {
     }
 
     /// makes a new tar archive from ptr
-    /// unsafe because ptr has to be mapped and non-null
+    /// unsafe because ptr has to be mapped and non-null for `'a`
     pub unsafe fn new(ptr: *const u8) -> Self {
         let at = &*(ptr as *const Inode);
         assert!(at.verify());
@@ -114,3 +119,88 @@ impl TarArchiveIter<'_> {
         Self { at: Some(at) }
     }
 }
+
+/// writes `value` as zero padded octal digits followed by a NUL filling `field`
+#[cfg(feature = "test")]
+fn write_octal(field: &mut [u8], mut value: usize) {
+    let digits = field.len() - 1;
+    for digit in field[..digits].iter_mut().rev() {
+        *digit = b'0' + (value & 7) as u8;
+        value >>= 3;
+    }
+    field[digits] = 0;
+}
+
+/// builds a tar archive in memory, can be read back with `TarArchiveIter`
+/// only the tests pack archives in the kernel for now, userspace has it's own tar
+#[cfg(feature = "test")]
+#[derive(Debug, Default)]
+pub struct TarArchiveBuilder {
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "test")]
+impl TarArchiveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns Err(()) if `name` is too long to fit in the header
+    fn append_header(
+        &mut self,
+        name: &str,
+        kind: Type,
+        mode: usize,
+        size: usize,
+    ) -> Result<(), ()> {
+        if name.len() > 100 {
+            return Err(());
+        }
+
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], mode);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], size);
+        write_octal(&mut header[136..148], 0);
+        header[156] = kind.0;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // the checksum is calculated with it's own field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|byte| *byte as usize).sum();
+        write_octal(&mut header[148..155], checksum);
+
+        self.data.extend_from_slice(&header);
+        Ok(())
+    }
+
+    /// appends a file named `name` containing `data`
+    /// returns Err(()) if `name` is longer then 100 bytes
+    pub fn append_file(&mut self, name: &str, data: &[u8]) -> Result<(), ()> {
+        self.append_header(name, Type::NORMAL, 0o644, data.len())?;
+        self.data.extend_from_slice(data);
+
+        let padding = data.len().next_multiple_of(BLOCK_SIZE) - data.len();
+        self.data.resize(self.data.len() + padding, 0);
+        Ok(())
+    }
+
+    /// appends a directory named `name`, a `/` is appended to `name` if it doesn't end with one
+    /// returns Err(()) if `name` is longer then 100 bytes
+    pub fn append_dir(&mut self, name: &str) -> Result<(), ()> {
+        if name.ends_with('/') {
+            self.append_header(name, Type::DIR, 0o755, 0)
+        } else {
+            self.append_header(&alloc::format!("{name}/"), Type::DIR, 0o755, 0)
+        }
+    }
+
+    /// ends the archive with 2 zeroed blocks and returns it
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data.resize(self.data.len() + BLOCK_SIZE * 2, 0);
+        self.data
+    }
+}
@@ -11,10 +11,37 @@ ssize_t close(ssize_t arg0);
 ssize_t diriter_open(ssize_t arg0);
 ssize_t diriter_close(ssize_t arg0);
 DirEntry *diriter_next(ssize_t arg0);
+ssize_t readdir(ssize_t arg0, DirEntry *arg1, size_t arg2);
 DirEntry *fstat(ssize_t arg0);
 ssize_t read(ssize_t arg0, uint8_t *arg1, size_t arg2);
 ssize_t write(ssize_t arg0, const uint8_t *arg1, size_t arg2);
 ssize_t create(const uint8_t *arg0, size_t arg1);
 ssize_t createdir(const uint8_t *arg0, size_t arg1);
+ssize_t remove(const uint8_t *arg0, size_t arg1);
+ssize_t rename(const uint8_t *arg0, size_t arg1, const uint8_t *arg2, size_t arg3);
+ssize_t link(ssize_t arg0, const uint8_t *arg1, size_t arg2);
+ssize_t bind(const uint8_t *arg0, size_t arg1, const uint8_t *arg2, size_t arg3);
+ssize_t unbind(const uint8_t *arg0, size_t arg1);
+ssize_t mount_bind(const uint8_t *arg0, size_t arg1, const uint8_t *arg2, size_t arg3);
+ssize_t umount(const uint8_t *arg0, size_t arg1);
+ssize_t flock_range(ssize_t arg0, LockOp arg1, size_t arg2, size_t arg3, bool arg4);
+ssize_t flock(ssize_t arg0, LockOp arg1, bool arg2);
+ssize_t get_xattr(ssize_t arg0, const uint8_t *arg1, size_t arg2, uint8_t *arg3, size_t arg4);
+ssize_t set_xattr(ssize_t arg0, const uint8_t *arg1, size_t arg2, const uint8_t *arg3, size_t arg4);
+ssize_t list_xattrs(ssize_t arg0, uint8_t *arg1, size_t arg2);
+ssize_t remove_xattr(ssize_t arg0, const uint8_t *arg1, size_t arg2);
+ssize_t sync();
+ssize_t fchdir(ssize_t arg0);
+ssize_t ctl(ssize_t arg0, size_t arg1, size_t arg2);
+ssize_t ctlbuf(ssize_t arg0, size_t arg1, const uint8_t *arg2, size_t arg3, uint8_t *arg4, size_t arg5);
+ssize_t dup(ssize_t arg0);
+ssize_t dup2(ssize_t arg0, ssize_t arg1);
+ssize_t readv(ssize_t arg0, const IoVec *arg1, size_t arg2);
+ssize_t writev(ssize_t arg0, const IoVec *arg1, size_t arg2);
+ssize_t copy_file_range(ssize_t arg0, ssize_t arg1, size_t arg2);
+ssize_t poll(PollFd *arg0, size_t arg1, size_t arg2);
+ssize_t evq_create();
+ssize_t evq_ctl(ssize_t arg0, EventQueueOp arg1, ssize_t arg2, uint8_t arg3, uint8_t arg4, size_t arg5);
+ssize_t evq_wait(ssize_t arg0, Event *arg1, size_t arg2, size_t arg3);
 
 #endif
\ No newline at end of file
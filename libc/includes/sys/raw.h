@@ -13,6 +13,39 @@ typedef struct DirEntry {
   uint8_t name[128];
 } DirEntry;
 
+#define POLL_READABLE (1 << 0)
+#define POLL_WRITABLE (1 << 1)
+
+typedef struct PollFd {
+  size_t fd;
+  uint8_t events;
+  uint8_t revents;
+} PollFd;
+
+typedef enum EventQueueOp: size_t {
+  EventQueueAdd,
+  EventQueueModify,
+  EventQueueRemove,
+} EventQueueOp;
+
+#define INTEREST_EDGE_TRIGGERED (1 << 0)
+
+typedef struct Event {
+  size_t data;
+  uint8_t events;
+} Event;
+
+typedef struct IoVec {
+  uint8_t *ptr;
+  size_t len;
+} IoVec;
+
+typedef enum ContextPriority: uint8_t {
+  PriorityLow,
+  PriorityMedium,
+  PriorityHigh,
+} ContextPriority;
+
 typedef struct SpawnConfig {
   struct {
     const uint8_t *ptr;
@@ -24,6 +57,7 @@ typedef struct SpawnConfig {
   } *argv;
   size_t argc;
   uint8_t flags;
+  ContextPriority priority;
 } SpawnConfig;
 
 typedef struct SysInfo {
@@ -32,10 +66,32 @@ typedef struct SysInfo {
   size_t processes_count;
 } SysInfo;
 
+// the length of each field of Utsname, the fields are nul terminated
+#define UTSNAME_LEN 65
+// identifies the system, release is the version of the kernel and version the git commit it was built from
+typedef struct Utsname {
+  char sysname[UTSNAME_LEN];
+  char nodename[UTSNAME_LEN];
+  char release[UTSNAME_LEN];
+  char version[UTSNAME_LEN];
+  char machine[UTSNAME_LEN];
+} Utsname;
+
+// the version of the syscall ABI, major is bumped on breaking changes and minor on additions
+typedef struct AbiVersion {
+  uint32_t major;
+  uint32_t minor;
+} AbiVersion;
+
+#define ABI_VERSION_MAJOR 1
+#define ABI_VERSION_MINOR 17
+
 typedef enum ProcessStatus: uint8_t {
   Waiting, 
   Running, 
   WaitingForBurying, 
+  Suspended, 
+  Sleeping, 
 } ProcessStatus;
 
 typedef struct ProcessInfo {
@@ -45,6 +101,118 @@ typedef struct ProcessInfo {
   ProcessStatus status;
 } ProcessInfo;
 
+typedef enum ExitKind: uint8_t {
+  Exited,
+  Killed,
+} ExitKind;
+
+// how a process exited, value is the exit code if it exited or the pid of the process that killed it (0 for the kernel)
+typedef struct ExitStatus {
+  ExitKind kind;
+  size_t value;
+} ExitStatus;
+
+typedef enum PtraceOp: size_t {
+  PtraceReadMemory,
+  PtraceWriteMemory,
+  PtraceSuspend,
+  PtraceResume,
+  PtraceGetRegisters,
+} PtraceOp;
+
+typedef struct Registers {
+  uint64_t rip, rsp, rflags;
+  uint64_t rax, rbx, rcx, rdx, rsi, rdi, rbp;
+  uint64_t r8, r9, r10, r11, r12, r13, r14, r15;
+} Registers;
+
+typedef struct VideoMode {
+  uint64_t width;
+  uint64_t height;
+  uint16_t bpp;
+} VideoMode;
+
+// the high byte of a ctl command is the class of the device it's for, see `CtlClass` in raw.zig
+typedef enum FrameBufferCtl: size_t {
+  FrameBufferSetMode = 0x200,
+  // ctlbuf only, writes the current VideoMode into the output
+  FrameBufferGetMode = 0x201,
+} FrameBufferCtl;
+
+/// ctl commands for dev:/tty, see `TTYCtl` in raw.zig for job control
+typedef enum TTYCtl: size_t {
+  TTYSetForeground = 0x100,
+  TTYSetRaw = 0x101,
+  // ctlbuf only, writes the foreground pid as a size_t into the output
+  TTYGetForeground = 0x102,
+  // ctlbuf only, the input is the new contents of the clipboard (up to 64 KiB)
+  TTYSetClipboard = 0x103,
+  // ctlbuf only, writes the size of the clipboard as a size_t into the output
+  TTYGetClipboardSize = 0x104,
+  // ctlbuf only, writes the clipboard into an output of exactly TTYGetClipboardSize bytes
+  TTYGetClipboard = 0x105,
+} TTYCtl;
+
+typedef enum AudioCtl: size_t {
+  AudioSetFormat = 0x300,
+} AudioCtl;
+
+// commands for ctl on dev:/irq, the arg of IrqSetAffinity is the irq in bits 0..32 and the cpu in bits 32..64
+typedef enum IrqCtl: size_t {
+  IrqSetAffinity = 0x400,
+} IrqCtl;
+
+// commands for ctl on dev:/profiler, ProfilerSetRunning starts profiling if arg isn't 0 and stops otherwise
+typedef enum ProfilerCtl: size_t {
+  ProfilerSetRunning = 0x500,
+} ProfilerCtl;
+
+// commands for ctl on dev:/input/event0, InputGrab grabs the input if arg isn't 0 and releases it otherwise
+typedef enum InputCtl: size_t {
+  InputGrab = 0x600,
+} InputCtl;
+
+typedef enum InputKind: uint16_t {
+  InputKey = 1,
+} InputKind;
+
+// an event read from dev:/input/event0, for keys code is the key code and value the key flags
+typedef struct InputEvent {
+  uint64_t time;
+  InputKind kind;
+  uint16_t code;
+  uint32_t value;
+} InputEvent;
+
+typedef enum AudioFormat: uint8_t {
+  AudioFormatU8,
+  AudioFormatS16,
+  AudioFormatS32,
+} AudioFormat;
+
+/// packs the rate in Hz, channel count and format into the arg of AudioSetFormat
+#define AUDIO_FORMAT(rate, channels, format)                                   \
+  ((size_t)(uint32_t)(rate) | ((size_t)(uint8_t)(channels) << 32) |            \
+   ((size_t)(uint8_t)(format) << 40))
+
+typedef struct SurfaceInfo {
+  size_t id;
+  size_t width;
+  size_t height;
+  size_t addr;
+} SurfaceInfo;
+
+typedef enum PowerAction: size_t {
+  Shutdown,
+  Reboot,
+} PowerAction;
+
+typedef enum LockOp: size_t {
+  Shared,
+  Exclusive,
+  Unlock,
+} LockOp;
+
 typedef struct OsStr {
   size_t len;
   uint8_t data_off[1];
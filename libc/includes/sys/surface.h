@@ -0,0 +1,16 @@
+#ifndef __nlibc__SRC_SYS_SURFACE_
+#define __nlibc__SRC_SYS_SURFACE_
+
+#include <stddef.h>
+#include <stdint.h>
+#include <stdbool.h>
+#include <sys/types.h>
+
+#include "raw.h"
+ssize_t surface_create(size_t arg0, size_t arg1);
+ssize_t surface_open(size_t arg0);
+ssize_t surface_map(ssize_t arg0, SurfaceInfo *arg1);
+ssize_t surface_flush(ssize_t arg0);
+ssize_t surface_present(ssize_t arg0);
+
+#endif
\ No newline at end of file
@@ -8,5 +8,20 @@
 
 #include "raw.h"
 SysInfo *sysinfo();
+int32_t cpuctl(size_t cpu, bool online);
+int32_t power(PowerAction action);
+int32_t ptrace(PtraceOp op, size_t pid, size_t addr, void *ptr, size_t len);
+ssize_t backtrace(size_t pid, size_t *frames, size_t len);
+ssize_t env_get(const char *name, size_t name_len, char *value, size_t len);
+ssize_t wait_any(bool nonblocking, size_t *status);
+int32_t pkill(size_t pid);
+int32_t env_set(const char *name, size_t name_len, const char *value, size_t value_len);
+int32_t uname(Utsname *utsname);
+int32_t sethostname(const char *name, size_t name_len);
+int32_t report_abort(const char *msg, size_t msg_len);
+int32_t wait_status(size_t pid, ExitStatus *status);
+void sleep_ticks(size_t ticks);
+uint32_t getuid();
+int32_t setuid(uint32_t uid);
 
 #endif
\ No newline at end of file
@@ -34,6 +34,8 @@ pub export fn exit(code: usize) noreturn {
 pub fn panic(msg: []const u8, error_return_trace: ?*builtin.StackTrace, return_addr: ?usize) noreturn {
     @setCold(true);
     const at = return_addr orelse @returnAddress();
+    // stdout may be redirected or broken, the kernel logs the message when the process exits
+    _ = syscalls.report_abort(msg.ptr, msg.len);
     stdio.zprintf("\x1B[38;2;200;0;0mlibc panic: %.*s at %p <??>\n", .{ msg.len, msg.ptr, at }) catch {};
 
     stdio.zprintf("trace:\n", .{}) catch {};
@@ -139,10 +141,13 @@ fn _start() callconv(.Naked) noreturn {
     );
 }
 
+const abi_note = sys.raw.AbiNote{};
+
 // we cannot export start directly to avoid problems with headergen
 comptime {
     if (builtin_info.os.tag == .freestanding) {
         @export(_start, .{ .name = "_start" });
+        @export(abi_note, .{ .name = "__safaos_abi_note", .section = ".note.safaos" });
 
         @export(__libc_c_start, .{ .name = "__libc_c_start" });
         @export(_redirect_start, .{ .name = "_redirect_start" });
@@ -32,6 +32,8 @@ pub const Error = error{
     ArgumentOutOfDomain,
     IllegalByteSequence,
     ResultOutOfRange,
+    StrTooLong,
+    WouldBlock,
     // method to identify the enum max
     Last,
 };
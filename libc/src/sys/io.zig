@@ -4,9 +4,13 @@ const stdio = @import("../stdio.zig");
 pub const raw = @import("raw.zig");
 
 pub fn open(path: *const u8, len: usize) isize {
+    return open_with(path, len, .{});
+}
+
+pub fn open_with(path: *const u8, len: usize, flags: raw.OpenFlags) isize {
     var fd: usize = undefined;
 
-    const err = syscalls.open(path, len, &fd);
+    const err = syscalls.open(path, len, flags, &fd);
     if (err != 0) {
         errors.errno = @truncate(err);
         return -1;
@@ -23,6 +27,25 @@ pub export fn close(fd: isize) isize {
     return 0;
 }
 
+/// fills `entries` with as many entries of the opened directory `ri` as fit, the next call continues where
+/// this one stopped, returns the amount filled (0 at the end of the directory) or -1 on failure
+pub export fn readdir(ri: isize, entries: [*]raw.DirEntry, len: usize) isize {
+    var count: usize = undefined;
+    const err = syscalls.readdir(@bitCast(ri), entries, len, &count);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @intCast(count);
+}
+
+/// returns the part of `entries` that was filled, empty at the end of the directory
+pub fn zreaddir(ri: isize, entries: []raw.DirEntry) errors.Error![]raw.DirEntry {
+    const count = readdir(ri, entries.ptr, entries.len);
+    if (count == -1) return errors.geterr();
+    return entries[0..@intCast(count)];
+}
+
 pub export fn diriter_open(dir: isize) isize {
     var diriter: usize = undefined;
     const err = syscalls.diriter_open(@bitCast(dir), &diriter);
@@ -116,6 +139,13 @@ pub fn zopen(path: []const u8) errors.Error!isize {
     return fd;
 }
 
+/// with `.nonblocking` reads and writes on the returned fd return error.WouldBlock instead of waiting
+pub fn zopen_with(path: []const u8, flags: raw.OpenFlags) errors.Error!isize {
+    const fd = open_with(@ptrCast(path.ptr), path.len, flags);
+    if (fd == -1) return errors.geterr();
+    return fd;
+}
+
 pub fn zclose(fd: isize) errors.Error!void {
     const err = close(fd);
     if (err == -1) return errors.geterr();
@@ -164,6 +194,200 @@ pub fn zcreatedir(path: []const u8) errors.Error!void {
     if (err == -1) return errors.geterr();
 }
 
+/// removes a file or an empty directory, opened files stay usable
+pub export fn remove(path: [*]const u8, len: usize) isize {
+    const err = syscalls.remove(path, len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+
+    return 0;
+}
+
+/// moves `old` to `new` which must not exist, both have to be on the same drive
+pub export fn rename(old: [*]const u8, old_len: usize, new: [*]const u8, new_len: usize) isize {
+    const err = syscalls.rename(old, old_len, new, new_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+
+    return 0;
+}
+
+/// gives the unnamed file `ri` opened with the `tmpfile` flag the name `path`, replacing the file at `path` if there is
+/// one, other processes either see the old file or the new one
+pub export fn link(ri: isize, path: [*]const u8, path_len: usize) isize {
+    const err = syscalls.link(@bitCast(ri), path, path_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+
+    return 0;
+}
+
+pub fn zremove(path: []const u8) errors.Error!void {
+    if (remove(path.ptr, path.len) == -1) return errors.geterr();
+}
+
+pub fn zrename(old: []const u8, new: []const u8) errors.Error!void {
+    if (rename(old.ptr, old.len, new.ptr, new.len) == -1) return errors.geterr();
+}
+
+pub fn zlink(ri: isize, path: []const u8) errors.Error!void {
+    if (link(ri, path.ptr, path.len) == -1) return errors.geterr();
+}
+
+/// binds `drive` to the directory `target` for the caller and the processes it spawns afterwards, a bind can hide an
+/// existing drive, requires the mount capability
+pub export fn bind(drive: [*]const u8, drive_len: usize, target: [*]const u8, target_len: usize) isize {
+    const err = syscalls.bind(drive, drive_len, target, target_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// the processes spawned before the unbind keep seeing the drive
+pub export fn unbind(drive: [*]const u8, drive_len: usize) isize {
+    const err = syscalls.unbind(drive, drive_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// takes or releases an advisory lock on `len` bytes of `ri` starting at `start`, a `len` of 0 locks up to the end of
+/// the file, waits for conflicting locks to be released unless `nonblocking` in which case it fails with `Busy`
+/// locks are released when the last duplicate of `ri` is closed
+pub export fn flock_range(ri: isize, op: raw.LockOp, start: usize, len: usize, nonblocking: bool) isize {
+    const err = syscalls.flock(@bitCast(ri), op, start, len, nonblocking);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// locks the whole file `ri`, see `flock_range`
+pub export fn flock(ri: isize, op: raw.LockOp, nonblocking: bool) isize {
+    return flock_range(ri, op, 0, 0, nonblocking);
+}
+
+pub fn zflock(ri: isize, op: raw.LockOp, nonblocking: bool) errors.Error!void {
+    if (flock(ri, op, nonblocking) == -1) return errors.geterr();
+}
+
+pub fn zflock_range(ri: isize, op: raw.LockOp, start: usize, len: usize, nonblocking: bool) errors.Error!void {
+    if (flock_range(ri, op, start, len, nonblocking) == -1) return errors.geterr();
+}
+
+/// copies the value of the extended attribute `name` of `ri` to `value`, returns the length of the whole value, fails
+/// with `ResultOutOfRange` if it doesn't fit in `len` bytes
+pub export fn get_xattr(ri: isize, name: [*]const u8, name_len: usize, value: [*]u8, len: usize) isize {
+    var value_len: usize = undefined;
+    const err = syscalls.get_xattr(@bitCast(ri), name, name_len, value, len, &value_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(value_len);
+}
+
+/// sets the extended attribute `name` of `ri` to `value`, values are limited to 4096 bytes
+pub export fn set_xattr(ri: isize, name: [*]const u8, name_len: usize, value: [*]const u8, value_len: usize) isize {
+    const err = syscalls.set_xattr(@bitCast(ri), name, name_len, value, value_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// copies the names of the extended attributes of `ri` each followed by a null byte to `names`, returns the length of
+/// all of them, fails with `ResultOutOfRange` if they don't fit in `len` bytes
+pub export fn list_xattrs(ri: isize, names: [*]u8, len: usize) isize {
+    var names_len: usize = undefined;
+    const err = syscalls.list_xattrs(@bitCast(ri), names, len, &names_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(names_len);
+}
+
+pub export fn remove_xattr(ri: isize, name: [*]const u8, name_len: usize) isize {
+    const err = syscalls.remove_xattr(@bitCast(ri), name, name_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// returns the part of `buffer` the value of `name` was copied to
+pub fn zget_xattr(ri: isize, name: []const u8, buffer: []u8) errors.Error![]u8 {
+    const len = get_xattr(ri, name.ptr, name.len, buffer.ptr, buffer.len);
+    if (len == -1) return errors.geterr();
+    return buffer[0..@intCast(len)];
+}
+
+pub fn zset_xattr(ri: isize, name: []const u8, value: []const u8) errors.Error!void {
+    if (set_xattr(ri, name.ptr, name.len, value.ptr, value.len) == -1) return errors.geterr();
+}
+
+/// returns the part of `buffer` the names were copied to, each name is followed by a null byte
+pub fn zlist_xattrs(ri: isize, buffer: []u8) errors.Error![]u8 {
+    const len = list_xattrs(ri, buffer.ptr, buffer.len);
+    if (len == -1) return errors.geterr();
+    return buffer[0..@intCast(len)];
+}
+
+pub fn zremove_xattr(ri: isize, name: []const u8) errors.Error!void {
+    if (remove_xattr(ri, name.ptr, name.len) == -1) return errors.geterr();
+}
+
+/// exposes the directory `src` at `dst` for every process, `dst` is either an existing directory or the root of a drive
+/// that doesn't exist such as `bin:/`, requires the mount capability, see proc:/mounts
+pub export fn mount_bind(src: [*]const u8, src_len: usize, dst: [*]const u8, dst_len: usize) isize {
+    const err = syscalls.mount_bind(src, src_len, dst, dst_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// removes the bind mount at `dst`, drives can't be unmounted
+pub export fn umount(dst: [*]const u8, dst_len: usize) isize {
+    const err = syscalls.umount(dst, dst_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zmount_bind(src: []const u8, dst: []const u8) errors.Error!void {
+    if (mount_bind(src.ptr, src.len, dst.ptr, dst.len) == -1) return errors.geterr();
+}
+
+pub fn zumount(dst: []const u8) errors.Error!void {
+    if (umount(dst.ptr, dst.len) == -1) return errors.geterr();
+}
+
+pub fn zbind(drive: []const u8, target: []const u8) errors.Error!void {
+    if (bind(drive.ptr, drive.len, target.ptr, target.len) == -1) return errors.geterr();
+}
+
+pub fn zunbind(drive: []const u8) errors.Error!void {
+    if (unbind(drive.ptr, drive.len) == -1) return errors.geterr();
+}
+
 pub export fn chdir(path: [*]const u8, path_len: usize) isize {
     const err = syscalls.chdir(path, path_len);
     if (err != 0) {
@@ -173,6 +397,17 @@ pub export fn chdir(path: [*]const u8, path_len: usize) isize {
     return 0;
 }
 
+/// changes the cwd to the directory opened as `ri`
+pub export fn fchdir(ri: isize) isize {
+    const err = syscalls.fchdir(@bitCast(ri));
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// fails with `ResultOutOfRange` if the cwd doesn't fit in `len`
 pub export fn getcwd(ptr: [*]const u8, len: usize) isize {
     var dest_len: usize = undefined;
     const err = syscalls.getcwd(ptr, len, &dest_len);
@@ -193,3 +428,232 @@ pub fn zchdir(path: []const u8) errors.Error!void {
     const err = chdir(@ptrCast(path.ptr), path.len);
     if (err == -1) return errors.geterr();
 }
+
+pub fn zfchdir(ri: isize) errors.Error!void {
+    if (fchdir(ri) == -1) return errors.geterr();
+}
+
+pub export fn sync() isize {
+    const err = syscalls.sync();
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zsync() errors.Error!void {
+    const err = sync();
+    if (err == -1) return errors.geterr();
+}
+
+/// sends the device specific command `cmd` with the argument `arg` to `fd`, for example `raw.FrameBufferCtl`
+pub export fn ctl(fd: isize, cmd: usize, arg: usize) isize {
+    const err = syscalls.ctl(@bitCast(fd), cmd, arg);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zctl(fd: isize, cmd: usize, arg: usize) errors.Error!void {
+    if (ctl(fd, cmd, arg) == -1) return errors.geterr();
+}
+
+/// like `ctl` but the command takes it's argument from `input` and writes it's result into `output`, both
+/// have to be the exact size the command expects
+pub export fn ctlbuf(fd: isize, cmd: usize, input_ptr: [*]const u8, input_len: usize, output_ptr: [*]u8, output_len: usize) isize {
+    const err = syscalls.ctlbuf(@bitCast(fd), cmd, input_ptr, input_len, output_ptr, output_len);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zctlbuf(fd: isize, cmd: usize, input: []const u8, output: []u8) errors.Error!void {
+    if (ctlbuf(fd, cmd, input.ptr, input.len, output.ptr, output.len) == -1) return errors.geterr();
+}
+
+/// sends the command `cmd` that returns a `T` to `fd`, for example `raw.FrameBufferCtl.GetMode`
+pub fn zctl_get(comptime T: type, fd: isize, cmd: usize) errors.Error!T {
+    var value: T = undefined;
+    try zctlbuf(fd, cmd, &.{}, @as([*]u8, @ptrCast(&value))[0..@sizeOf(T)]);
+    return value;
+}
+
+/// duplicates `fd`, the duplicate has it's own file position
+pub export fn dup(fd: isize) isize {
+    var new_fd: usize = undefined;
+    const err = syscalls.dup(@bitCast(fd), &new_fd);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(new_fd);
+}
+
+/// makes `dest_fd` a duplicate of `fd` closing `dest_fd` first if it was open
+/// returns the new value of `dest_fd`
+pub export fn dup2(fd: isize, dest_fd: isize) isize {
+    var new_fd: usize = undefined;
+    const err = syscalls.dup2(@bitCast(fd), @bitCast(dest_fd), &new_fd);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(new_fd);
+}
+
+pub fn zdup(fd: isize) errors.Error!isize {
+    const new_fd = dup(fd);
+    if (new_fd == -1) return errors.geterr();
+    return new_fd;
+}
+
+pub fn zdup2(fd: isize, dest_fd: isize) errors.Error!isize {
+    const new_fd = dup2(fd, dest_fd);
+    if (new_fd == -1) return errors.geterr();
+    return new_fd;
+}
+
+/// reads into each buffer of `iovecs` in order, returns the total amount of bytes read
+pub export fn readv(fd: isize, iovecs: [*]const raw.IoVec, len: usize) isize {
+    var bytes_read: usize = undefined;
+    const err = syscalls.readv(@bitCast(fd), iovecs, len, &bytes_read);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(bytes_read);
+}
+
+/// writes each buffer of `iovecs` in order in a single syscall, returns the total amount of bytes written
+pub export fn writev(fd: isize, iovecs: [*]const raw.IoVec, len: usize) isize {
+    var bytes_wrote: usize = undefined;
+    const err = syscalls.writev(@bitCast(fd), iovecs, len, &bytes_wrote);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(bytes_wrote);
+}
+
+pub fn zreadv(fd: isize, iovecs: []const raw.IoVec) errors.Error!usize {
+    const bytes_read = readv(fd, iovecs.ptr, iovecs.len);
+    if (bytes_read == -1) return errors.geterr();
+    return @bitCast(bytes_read);
+}
+
+pub fn zwritev(fd: isize, iovecs: []const raw.IoVec) errors.Error!usize {
+    const bytes_wrote = writev(fd, iovecs.ptr, iovecs.len);
+    if (bytes_wrote == -1) return errors.geterr();
+    return @bitCast(bytes_wrote);
+}
+
+/// copies up to `len` bytes from `src_fd` to `dest_fd` inside the kernel, returns the amount of bytes copied
+pub export fn copy_file_range(src_fd: isize, dest_fd: isize, len: usize) isize {
+    var copied: usize = undefined;
+    const err = syscalls.copy_file_range(@bitCast(src_fd), @bitCast(dest_fd), len, &copied);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(copied);
+}
+
+pub fn zcopy_file_range(src_fd: isize, dest_fd: isize, len: usize) errors.Error!usize {
+    const copied = copy_file_range(src_fd, dest_fd, len);
+    if (copied == -1) return errors.geterr();
+    return @bitCast(copied);
+}
+
+/// waits until one of `fds` is ready or `timeout` scheduler ticks pass, a `timeout` of 0 only checks once
+/// and a `timeout` of maxInt(usize) waits forever, returns the amount of ready fds
+pub export fn poll(fds: [*]raw.PollFd, len: usize, timeout: usize) isize {
+    var ready: usize = undefined;
+    const err = syscalls.poll(fds, len, timeout, &ready);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(ready);
+}
+
+pub fn zpoll(fds: []raw.PollFd, timeout: usize) errors.Error!usize {
+    const ready = poll(fds.ptr, fds.len, timeout);
+    if (ready == -1) return errors.geterr();
+    return @bitCast(ready);
+}
+
+pub export fn evq_create() isize {
+    var evq: usize = undefined;
+    const err = syscalls.evq_create(&evq);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(evq);
+}
+
+pub export fn evq_ctl(evq: isize, op: raw.EventQueueOp, fd: isize, events: raw.PollEvents, flags: raw.InterestFlags, data: usize) isize {
+    const err = syscalls.evq_ctl(@bitCast(evq), op, @bitCast(fd), events, flags, data);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// waits until any of the fds in `evq` is ready or `timeout` scheduler ticks pass (see `poll`)
+/// returns the amount of events written to `events`
+pub export fn evq_wait(evq: isize, events: [*]raw.Event, len: usize, timeout: usize) isize {
+    var count: usize = undefined;
+    const err = syscalls.evq_wait(@bitCast(evq), events, len, timeout, &count);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(count);
+}
+
+/// a wrapper around an event queue resource
+/// register interest in fds once then `wait` on all of them
+pub const EventQueue = struct {
+    fd: isize,
+
+    pub fn init() errors.Error!EventQueue {
+        const fd = evq_create();
+        if (fd == -1) return errors.geterr();
+        return .{ .fd = fd };
+    }
+
+    fn ctl(self: EventQueue, op: raw.EventQueueOp, fd: isize, events: raw.PollEvents, flags: raw.InterestFlags, data: usize) errors.Error!void {
+        if (evq_ctl(self.fd, op, fd, events, flags, data) == -1) return errors.geterr();
+    }
+
+    /// `data` is returned with every event of `fd`
+    pub fn add(self: EventQueue, fd: isize, events: raw.PollEvents, flags: raw.InterestFlags, data: usize) errors.Error!void {
+        return self.ctl(.Add, fd, events, flags, data);
+    }
+
+    pub fn modify(self: EventQueue, fd: isize, events: raw.PollEvents, flags: raw.InterestFlags, data: usize) errors.Error!void {
+        return self.ctl(.Modify, fd, events, flags, data);
+    }
+
+    pub fn remove(self: EventQueue, fd: isize) errors.Error!void {
+        return self.ctl(.Remove, fd, .{}, .{}, 0);
+    }
+
+    /// returns the events that were written to `events`
+    pub fn wait(self: EventQueue, events: []raw.Event, timeout: usize) errors.Error![]raw.Event {
+        const count = evq_wait(self.fd, events.ptr, events.len, timeout);
+        if (count == -1) return errors.geterr();
+        return events[0..@bitCast(count)];
+    }
+
+    pub fn deinit(self: EventQueue) void {
+        zclose(self.fd) catch {};
+    }
+};
@@ -17,28 +17,269 @@ pub fn Slice(comptime T: type) type {
 pub const SpawnFlags = packed struct {
     clone_resources: bool = false,
     clone_cwd: bool = false,
+    /// the child (and it's children) can only read from the TTY while it's the foreground process, see `TTYCtl`
+    background: bool = false,
+    _padding: u5 = 0,
+};
+
+pub const OpenFlags = packed struct {
+    nonblocking: bool = false,
+    /// creates an unnamed file in the directory instead of opening it, see `link`
+    tmpfile: bool = false,
+    _padding: u6 = 0,
+};
+
+pub const PollEvents = packed struct {
+    readable: bool = false,
+    writable: bool = false,
     _padding: u6 = 0,
 };
 
+pub const PollFd = extern struct {
+    fd: usize,
+    events: PollEvents,
+    revents: PollEvents = .{},
+};
+
+pub const EventQueueOp = enum(usize) {
+    Add,
+    Modify,
+    Remove,
+};
+
+pub const InterestFlags = packed struct {
+    /// only report events that became ready since the last wait
+    edge_triggered: bool = false,
+    _padding: u7 = 0,
+};
+
+pub const Event = extern struct {
+    data: usize,
+    events: PollEvents,
+};
+
+/// a buffer for vectored io (readv and writev)
+pub const IoVec = extern struct {
+    ptr: [*]u8,
+    len: usize,
+
+    pub fn from(slice: []u8) IoVec {
+        return .{ .ptr = slice.ptr, .len = slice.len };
+    }
+};
+
 pub const DirEntry = extern struct { kind: u8, size: usize, name_length: usize, name: [128]u8 };
 
+/// higher priorities get a bigger share of the CPU
+pub const ContextPriority = enum(u8) {
+    Low,
+    Medium,
+    High,
+};
+
 pub const SpawnConfig = extern struct {
     name: Slice(u8),
     argv: [*]const Slice(u8),
     argc: usize,
     flags: SpawnFlags,
+    priority: ContextPriority = .Medium,
 };
 
 pub const SysInfo = extern struct { total_mem: usize, used_mem: usize, processes_count: usize };
 
+/// the length of each field of `Utsname`, the fields are nul terminated
+pub const UTSNAME_LEN = 65;
+/// identifies the system, `release` is the version of the kernel and `version` the git commit it was built from
+pub const Utsname = extern struct {
+    sysname: [UTSNAME_LEN]u8,
+    nodename: [UTSNAME_LEN]u8,
+    release: [UTSNAME_LEN]u8,
+    version: [UTSNAME_LEN]u8,
+    machine: [UTSNAME_LEN]u8,
+};
+
+/// the version of the syscall ABI this libc was written against, see `AbiNote`
+/// `major` is bumped on breaking changes and `minor` on additions
+pub const AbiVersion = extern struct { major: u32, minor: u32 };
+pub const ABI_VERSION = AbiVersion{ .major = 1, .minor = 17 };
+
+/// the `.note.safaos` ELF note every binary linked against libc carries, the kernel refuses to
+/// spawn binaries built against an `AbiVersion` it doesn't support
+pub const AbiNote = extern struct {
+    name_size: u32 = 7,
+    desc_size: u32 = @sizeOf(AbiVersion),
+    kind: u32 = 1,
+    /// "SafaOS" padded to 4 bytes
+    name: [8]u8 = "SafaOS\x00\x00".*,
+    desc: AbiVersion = ABI_VERSION,
+};
+
 pub const ProcessStatus = enum(u8) {
     Waiting,
     Running,
     Zombie,
+    Suspended,
+    Sleeping,
 };
 
 pub const ProcessInfo = extern struct { ppid: u64, pid: u64, name: [64]u8, status: ProcessStatus, resource_count: usize, exit_code: usize, exit_addr: usize, exit_stack_addr: usize, killed_by: u64, data_start: usize, data_break: usize };
 
+pub const ExitKind = enum(u8) {
+    /// the process called exit
+    Exited,
+    /// the process was killed by another process or by the kernel
+    Killed,
+};
+
+/// how a process exited, see `zwait_status`
+/// exit codes are whatever the program exited with and aren't errors, by convention 0 is a success and
+/// programs that fail with an `errno.Error` exit with it's number, a process that was killed has no exit
+/// code, `value` is the pid of the process that killed it instead (0 for the kernel)
+pub const ExitStatus = extern struct { kind: ExitKind, value: usize };
+
+pub const PtraceOp = enum(usize) {
+    ReadMemory,
+    WriteMemory,
+    Suspend,
+    Resume,
+    GetRegisters,
+};
+
+pub const Registers = extern struct { rip: u64, rsp: u64, rflags: u64, rax: u64, rbx: u64, rcx: u64, rdx: u64, rsi: u64, rdi: u64, rbp: u64, r8: u64, r9: u64, r10: u64, r11: u64, r12: u64, r13: u64, r14: u64, r15: u64 };
+
+/// the modes read from dev:/fb
+pub const VideoMode = extern struct {
+    width: u64,
+    height: u64,
+    /// bits per pixel
+    bpp: u16,
+};
+
+/// every device has it's own class of `ctl` commands, the class is the high byte of the command so a command
+/// sent to the wrong device fails with `OperationNotSupported`
+pub const CtlClass = enum(u8) {
+    TTY = 1,
+    FrameBuffer = 2,
+    Audio = 3,
+
+    pub fn cmd(self: CtlClass, nr: u8) usize {
+        return (@as(usize, @intFromEnum(self)) << 8) | nr;
+    }
+};
+
+/// commands for `ctl` on dev:/fb
+pub const FrameBufferCtl = enum(usize) {
+    /// switches to the mode at index `arg` in the mode list
+    SetMode = 0x200,
+    /// `ctlbuf` only, writes the current `VideoMode` into the output
+    GetMode = 0x201,
+};
+
+/// commands for `ctl` on dev:/tty
+/// job control: a shell spawns `&` jobs with `SpawnFlags.background` so they can't read input, for `fg` it
+/// gives the job the TTY with `SetForeground`, waits for it and then takes the TTY back with it's own pid,
+/// `zwait_any` collects the jobs that exited
+pub const TTYCtl = enum(usize) {
+    /// makes `arg` the foreground process, only it and it's descendants can read input, 0 gives the input back
+    /// to every process that isn't in the background
+    SetForeground = 0x100,
+    /// raw mode if `arg` isn't 0, every key is read as soon as it's pressed without being echoed, arrows, home,
+    /// end and delete are read as their escape sequences, backspace as 0x7F and ctrl + a..z as 0x1..0x1A,
+    /// raw mode ends when the process exits
+    SetRaw = 0x101,
+    /// `ctlbuf` only, writes the pid of the foreground process as a usize into the output, 0 if there is none
+    GetForeground = 0x102,
+    /// `ctlbuf` only, replaces the contents of the clipboard with the input (up to 64 KiB), the TTY pastes it
+    /// with ctrl + shift + v and ctrl + shift + m selects lines of the output to copy into it
+    SetClipboard = 0x103,
+    /// `ctlbuf` only, writes the size of the clipboard as a usize into the output
+    GetClipboardSize = 0x104,
+    /// `ctlbuf` only, writes the clipboard into the output which has to be exactly `GetClipboardSize` bytes
+    GetClipboard = 0x105,
+};
+
+/// commands for `ctl` on dev:/audio
+pub const AudioCtl = enum(usize) {
+    /// sets the format of the written frames, `arg` is made with `AudioFormat.pack`
+    SetFormat = 0x300,
+};
+
+/// commands for `ctl` on dev:/irq, the irqs are listed in proc:/interrupts
+pub const IrqCtl = enum(usize) {
+    /// requires the power capability, makes an irq interrupt another cpu, `arg` is made with `affinity`
+    SetAffinity = 0x400,
+
+    /// packs an irq and a cpu id into the `arg` of `IrqCtl.SetAffinity`
+    pub fn affinity(irq: u32, cpu: u32) usize {
+        return @as(usize, irq) | (@as(usize, cpu) << 32);
+    }
+};
+
+/// commands for `ctl` on dev:/profiler, the samples are in proc:/profile
+pub const ProfilerCtl = enum(usize) {
+    /// requires the debug capability, starts profiling if `arg` isn't 0 and stops otherwise
+    SetRunning = 0x500,
+};
+
+/// commands for `ctl` on dev:/input/event0
+pub const InputCtl = enum(usize) {
+    /// requires the compositor capability, if `arg` isn't 0 the caller grabs the input so keys are no longer
+    /// given to the TTY, otherwise it releases it's grab, fails with `Busy` if another process grabbed it
+    Grab = 0x600,
+};
+
+pub const InputKind = enum(u16) {
+    /// a key was pressed, `code` is the key code and `value` the key flags
+    Key = 1,
+    _,
+};
+
+/// an event read from dev:/input/event0
+pub const InputEvent = extern struct {
+    /// the scheduler tick the event happened at
+    time: u64,
+    kind: InputKind,
+    code: u16,
+    value: u32,
+};
+
+pub const AudioFormat = enum(u8) {
+    U8,
+    S16,
+    S32,
+
+    /// packs a format into the `arg` of `AudioCtl.SetFormat`
+    pub fn pack(self: AudioFormat, rate: u32, channels: u8) usize {
+        return @as(usize, rate) | (@as(usize, channels) << 32) | (@as(usize, @intFromEnum(self)) << 40);
+    }
+};
+
+/// a surface returned by `surface_map`, pixels are 0x00RRGGBB
+pub const SurfaceInfo = extern struct {
+    id: usize,
+    width: usize,
+    height: usize,
+    /// where the surface is mapped
+    addr: usize,
+
+    pub fn pixels(self: SurfaceInfo) []u32 {
+        const ptr: [*]u32 = @ptrFromInt(self.addr);
+        return ptr[0 .. self.width * self.height];
+    }
+};
+
+pub const PowerAction = enum(usize) {
+    Shutdown,
+    Reboot,
+};
+
+/// the operations of `flock`
+pub const LockOp = enum(usize) {
+    Shared,
+    Exclusive,
+    Unlock,
+};
+
 pub const OsStr = extern struct {
     len: usize,
     data_off: [1]u8,
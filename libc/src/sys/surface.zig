@@ -0,0 +1,89 @@
+//! surfaces, pixel buffers shared with a compositor
+//! the owner draws to it's mapped surface then commits the frame with `surface_flush`, the compositor
+//! (which needs the compositor capability) opens the surface by it's id, maps it and composes it,
+//! a surface fd is readable when the other end did something that wasn't acknowledged with `surface_flush`
+//! yet (a commit for the compositor and a present for the owner) so it can be waited on with poll and event queues
+const syscalls = @import("syscalls.zig");
+const errors = @import("errno.zig");
+const raw = @import("raw.zig");
+
+/// creates a `width`x`height` surface, returns it's fd
+/// fails with error.OutOfMemory if the surfaces created by the caller that weren't freed yet would take more than 64 MiB
+pub export fn surface_create(width: usize, height: usize) isize {
+    var fd: usize = undefined;
+    const err = syscalls.surface_create(width, height, &fd);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(fd);
+}
+
+pub fn zsurface_create(width: usize, height: usize) errors.Error!isize {
+    const fd = surface_create(width, height);
+    if (fd == -1) return errors.geterr();
+    return fd;
+}
+
+/// opens the surface `id` of another process, requires the compositor capability
+pub export fn surface_open(id: usize) isize {
+    var fd: usize = undefined;
+    const err = syscalls.surface_open(id, &fd);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return @bitCast(fd);
+}
+
+pub fn zsurface_open(id: usize) errors.Error!isize {
+    const fd = surface_open(id);
+    if (fd == -1) return errors.geterr();
+    return fd;
+}
+
+/// maps the surface `fd` and writes where to `info`, a surface is mapped only once per process
+pub export fn surface_map(fd: isize, info: *raw.SurfaceInfo) isize {
+    const err = syscalls.surface_map(@bitCast(fd), info);
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zsurface_map(fd: isize) errors.Error!raw.SurfaceInfo {
+    var info: raw.SurfaceInfo = undefined;
+    if (surface_map(fd, &info) == -1) return errors.geterr();
+    return info;
+}
+
+/// for the owner commits a frame and acknowledges the last present,
+/// for the compositor acknowledges the last commit
+pub export fn surface_flush(fd: isize) isize {
+    const err = syscalls.surface_flush(@bitCast(fd));
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zsurface_flush(fd: isize) errors.Error!void {
+    if (surface_flush(fd) == -1) return errors.geterr();
+}
+
+/// draws the surface `fd` to the display and wakes up the owners waiting for a present,
+/// requires the compositor capability
+pub export fn surface_present(fd: isize) isize {
+    const err = syscalls.surface_present(@bitCast(fd));
+    if (err != 0) {
+        errors.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zsurface_present(fd: isize) errors.Error!void {
+    if (surface_present(fd) == -1) return errors.geterr();
+}
@@ -61,8 +61,8 @@ pub inline fn yield() void {
     _ = syscall1(1);
 }
 
-pub inline fn open(path: *const u8, len: usize, fd: *usize) usize {
-    return syscall3(2, @intFromPtr(path), len, @intFromPtr(fd));
+pub inline fn open(path: *const u8, len: usize, flags: raw.OpenFlags, fd: *usize) usize {
+    return syscall4(2, @intFromPtr(path), len, @as(u8, @bitCast(flags)), @intFromPtr(fd));
 }
 
 pub inline fn write(fd: usize, ptr: *const u8, len: usize) usize {
@@ -133,12 +133,196 @@ pub inline fn pspawn(path_ptr: *const u8, path_len: usize, config: *const raw.Sp
     return syscall4(19, @intFromPtr(path_ptr), path_len, @intFromPtr(config), @intFromPtr(dest_pid));
 }
 
-pub inline fn shutdown() noreturn {
-    _ = syscall0(20);
-    unreachable;
+/// only returns if the process is missing the power capability or the action failed
+pub inline fn shutdown() usize {
+    return syscall0(20);
 }
 
-pub inline fn reboot() noreturn {
-    _ = syscall0(21);
-    unreachable;
+/// only returns if the process is missing the power capability or the action failed
+pub inline fn reboot() usize {
+    return syscall0(21);
+}
+
+pub inline fn cpuctl(cpu: usize, online: bool) usize {
+    return syscall3(22, cpu, @intFromBool(online), 0);
+}
+
+pub inline fn power(action: raw.PowerAction) usize {
+    return syscall1(23, @intFromEnum(action));
+}
+
+pub inline fn sync() usize {
+    return syscall0(24);
+}
+
+pub inline fn dup(ri: usize, dest_ri: *usize) usize {
+    return syscall3(25, ri, @intFromPtr(dest_ri), 0);
+}
+
+pub inline fn dup2(ri: usize, dest_ri: usize, new_ri: *usize) usize {
+    return syscall3(26, ri, dest_ri, @intFromPtr(new_ri));
+}
+
+pub inline fn readv(fd: usize, iovecs: [*]const raw.IoVec, len: usize, num_read: *usize) usize {
+    return syscall4(27, fd, @intFromPtr(iovecs), len, @intFromPtr(num_read));
+}
+
+pub inline fn writev(fd: usize, iovecs: [*]const raw.IoVec, len: usize, num_wrote: *usize) usize {
+    return syscall4(28, fd, @intFromPtr(iovecs), len, @intFromPtr(num_wrote));
+}
+
+pub inline fn copy_file_range(src_fd: usize, dest_fd: usize, len: usize, dest_copied: *usize) usize {
+    return syscall4(29, src_fd, dest_fd, len, @intFromPtr(dest_copied));
+}
+
+pub inline fn poll(fds: [*]raw.PollFd, len: usize, timeout: usize, dest_ready: *usize) usize {
+    return syscall4(30, @intFromPtr(fds), len, timeout, @intFromPtr(dest_ready));
+}
+
+pub inline fn evq_create(dest_evq: *usize) usize {
+    return syscall1(31, @intFromPtr(dest_evq));
+}
+
+pub inline fn evq_ctl(evq: usize, op: raw.EventQueueOp, fd: usize, events: raw.PollEvents, flags: raw.InterestFlags, data: usize) usize {
+    return syscall6(32, evq, @intFromEnum(op), fd, @as(u8, @bitCast(events)), @as(u8, @bitCast(flags)), data);
+}
+
+pub inline fn evq_wait(evq: usize, events: [*]raw.Event, len: usize, timeout: usize, dest_count: *usize) usize {
+    return syscall6(33, evq, @intFromPtr(events), len, timeout, @intFromPtr(dest_count), 0);
+}
+
+pub inline fn ptrace(op: raw.PtraceOp, pid: usize, addr: usize, ptr: ?*anyopaque, len: usize) usize {
+    return syscall6(34, @intFromEnum(op), pid, addr, @intFromPtr(ptr), len, 0);
+}
+
+pub inline fn backtrace(pid: usize, frames_ptr: [*]usize, frames_len: usize, dest_count: ?*usize) usize {
+    return syscall4(35, pid, @intFromPtr(frames_ptr), frames_len, @intFromPtr(dest_count));
+}
+
+pub inline fn ctl(ri: usize, cmd: usize, arg: usize) usize {
+    return syscall3(36, ri, cmd, arg);
+}
+
+pub inline fn surface_create(width: usize, height: usize, dest_surface: *usize) usize {
+    return syscall3(37, width, height, @intFromPtr(dest_surface));
+}
+
+pub inline fn surface_open(id: usize, dest_surface: *usize) usize {
+    return syscall3(38, id, @intFromPtr(dest_surface), 0);
+}
+
+pub inline fn surface_map(surface: usize, dest_info: *raw.SurfaceInfo) usize {
+    return syscall3(39, surface, @intFromPtr(dest_info), 0);
+}
+
+pub inline fn surface_flush(surface: usize) usize {
+    return syscall1(40, surface);
+}
+
+pub inline fn surface_present(surface: usize) usize {
+    return syscall1(41, surface);
+}
+
+pub inline fn env_get(name_ptr: [*]const u8, name_len: usize, value_ptr: [*]u8, value_len: usize, dest_len: ?*usize) usize {
+    return syscall6(42, @intFromPtr(name_ptr), name_len, @intFromPtr(value_ptr), value_len, @intFromPtr(dest_len), 0);
+}
+
+pub inline fn env_set(name_ptr: [*]const u8, name_len: usize, value_ptr: [*]const u8, value_len: usize) usize {
+    return syscall4(43, @intFromPtr(name_ptr), name_len, @intFromPtr(value_ptr), value_len);
+}
+
+pub inline fn wait_any(nonblocking: bool, dest_pid: ?*usize, dest_status: ?*usize) usize {
+    return syscall3(44, @intFromBool(nonblocking), @intFromPtr(dest_pid), @intFromPtr(dest_status));
+}
+
+pub inline fn fchdir(ri: usize) usize {
+    return syscall1(45, ri);
+}
+
+pub inline fn readdir(ri: usize, entries_ptr: [*]raw.DirEntry, entries_len: usize, dest_count: ?*usize) usize {
+    return syscall4(46, ri, @intFromPtr(entries_ptr), entries_len, @intFromPtr(dest_count));
+}
+
+pub inline fn remove(path_ptr: [*]const u8, path_len: usize) usize {
+    return syscall3(47, @intFromPtr(path_ptr), path_len, 0);
+}
+
+pub inline fn rename(old_ptr: [*]const u8, old_len: usize, new_ptr: [*]const u8, new_len: usize) usize {
+    return syscall4(48, @intFromPtr(old_ptr), old_len, @intFromPtr(new_ptr), new_len);
+}
+
+pub inline fn pkill(pid: usize) usize {
+    return syscall1(49, pid);
+}
+
+pub inline fn ctlbuf(ri: usize, cmd: usize, input_ptr: [*]const u8, input_len: usize, output_ptr: [*]u8, output_len: usize) usize {
+    return syscall6(50, ri, cmd, @intFromPtr(input_ptr), input_len, @intFromPtr(output_ptr), output_len);
+}
+
+pub inline fn uname(ptr: *raw.Utsname) usize {
+    return syscall1(51, @intFromPtr(ptr));
+}
+
+pub inline fn sethostname(name_ptr: [*]const u8, name_len: usize) usize {
+    return syscall3(52, @intFromPtr(name_ptr), name_len, 0);
+}
+
+pub inline fn report_abort(msg_ptr: [*]const u8, msg_len: usize) usize {
+    return syscall3(53, @intFromPtr(msg_ptr), msg_len, 0);
+}
+
+pub inline fn wait_status(pid: usize, dest: *raw.ExitStatus) usize {
+    return syscall3(54, pid, @intFromPtr(dest), 0);
+}
+
+pub inline fn sleep(ticks: usize) void {
+    _ = syscall1(55, ticks);
+}
+
+pub inline fn getuid(dest: *u32) usize {
+    return syscall1(56, @intFromPtr(dest));
+}
+
+pub inline fn setuid(uid: u32) usize {
+    return syscall1(57, uid);
+}
+
+pub inline fn bind(drive_ptr: [*]const u8, drive_len: usize, target_ptr: [*]const u8, target_len: usize) usize {
+    return syscall4(58, @intFromPtr(drive_ptr), drive_len, @intFromPtr(target_ptr), target_len);
+}
+
+pub inline fn unbind(drive_ptr: [*]const u8, drive_len: usize) usize {
+    return syscall3(59, @intFromPtr(drive_ptr), drive_len, 0);
+}
+
+pub inline fn mount_bind(src_ptr: [*]const u8, src_len: usize, dst_ptr: [*]const u8, dst_len: usize) usize {
+    return syscall4(60, @intFromPtr(src_ptr), src_len, @intFromPtr(dst_ptr), dst_len);
+}
+
+pub inline fn umount(dst_ptr: [*]const u8, dst_len: usize) usize {
+    return syscall3(61, @intFromPtr(dst_ptr), dst_len, 0);
+}
+
+pub inline fn flock(ri: usize, op: raw.LockOp, start: usize, len: usize, nonblocking: bool) usize {
+    return syscall6(62, ri, @intFromEnum(op), start, len, @intFromBool(nonblocking), 0);
+}
+
+pub inline fn link(ri: usize, path_ptr: [*]const u8, path_len: usize) usize {
+    return syscall3(63, ri, @intFromPtr(path_ptr), path_len);
+}
+
+pub inline fn get_xattr(ri: usize, name_ptr: [*]const u8, name_len: usize, value_ptr: [*]u8, value_len: usize, dest_len: ?*usize) usize {
+    return syscall6(64, ri, @intFromPtr(name_ptr), name_len, @intFromPtr(value_ptr), value_len, @intFromPtr(dest_len));
+}
+
+pub inline fn set_xattr(ri: usize, name_ptr: [*]const u8, name_len: usize, value_ptr: [*]const u8, value_len: usize) usize {
+    return syscall6(65, ri, @intFromPtr(name_ptr), name_len, @intFromPtr(value_ptr), value_len, 0);
+}
+
+pub inline fn list_xattrs(ri: usize, names_ptr: [*]u8, names_len: usize, dest_len: ?*usize) usize {
+    return syscall4(66, ri, @intFromPtr(names_ptr), names_len, @intFromPtr(dest_len));
+}
+
+pub inline fn remove_xattr(ri: usize, name_ptr: [*]const u8, name_len: usize) usize {
+    return syscall3(67, ri, @intFromPtr(name_ptr), name_len);
 }
@@ -36,30 +36,340 @@ pub fn zpcollect(processes: []raw.ProcessInfo) !bool {
     return true;
 }
 
+/// builds the `raw.SpawnConfig` for spawning a process, for example
+/// `try Spawn.init("name").args(argv).with_priority(.High).path("sys:/bin/ls")`
+pub const Spawn = struct {
+    name: []const u8,
+    argv: []const raw.Slice(u8) = &.{},
+    flags: raw.SpawnFlags = .{ .clone_cwd = true, .clone_resources = true },
+    priority: raw.ContextPriority = .Medium,
+
+    pub fn init(name: []const u8) Spawn {
+        return .{ .name = name };
+    }
+
+    pub fn args(self: Spawn, argv: []const raw.Slice(u8)) Spawn {
+        var spawn = self;
+        spawn.argv = argv;
+        return spawn;
+    }
+
+    /// by default the child gets a copy of the cwd and the resources
+    pub fn with_flags(self: Spawn, flags: raw.SpawnFlags) Spawn {
+        var spawn = self;
+        spawn.flags = flags;
+        return spawn;
+    }
+
+    pub fn with_priority(self: Spawn, priority: raw.ContextPriority) Spawn {
+        var spawn = self;
+        spawn.priority = priority;
+        return spawn;
+    }
+
+    fn config(self: *const Spawn) raw.SpawnConfig {
+        return .{ .argv = self.argv.ptr, .argc = self.argv.len, .name = raw.Slice(u8).from(self.name), .flags = self.flags, .priority = self.priority };
+    }
+
+    /// spawns the elf at `path_name`, returns the pid
+    pub fn path(self: Spawn, path_name: []const u8) errno.Error!u64 {
+        const spawn_config = self.config();
+
+        var pid: u64 = undefined;
+        const err = syscalls.pspawn(@ptrCast(path_name.ptr), path_name.len, &spawn_config, &pid);
+        if (err != 0) {
+            errno.errno = @truncate(err);
+            return errno.geterr();
+        }
+
+        return pid;
+    }
+
+    /// spawns the elf in `bytes`, returns the pid
+    pub fn elf(self: Spawn, bytes: []const u8) errno.Error!u64 {
+        const spawn_config = self.config();
+
+        var pid: u64 = undefined;
+        const err = syscalls.spawn(@ptrCast(bytes.ptr), bytes.len, &spawn_config, &pid);
+        if (err != 0) {
+            errno.errno = @truncate(err);
+            return errno.geterr();
+        }
+
+        return pid;
+    }
+};
+
 pub fn zspwan(bytes: []const u8, argv: []const raw.Slice(u8), name: []const u8) errno.Error!u64 {
-    const config: raw.SpawnConfig = .{ .argv = argv.ptr, .argc = argv.len, .name = .{ .ptr = name.ptr, .len = name.len }, .flags = .{ .clone_cwd = true, .clone_resources = true } };
+    return Spawn.init(name).args(argv).elf(bytes);
+}
+
+pub fn zpspwan(path: []const u8, argv: []const raw.Slice(u8), name: []const u8) errno.Error!u64 {
+    return Spawn.init(name).args(argv).path(path);
+}
 
-    var pid: u64 = undefined;
-    const err = syscalls.spawn(@ptrCast(bytes.ptr), bytes.len, &config, &pid);
+/// waits for `pid` to exit and returns it's exit code
+pub fn zwait(pid: u64) usize {
+    return syscalls.wait(pid);
+}
+
+/// waits for `pid` to exit and writes how it exited to `status`, unlike `wait` it tells exit codes apart
+/// from kills and fails with `InvaildPid` if `pid` doesn't exist
+pub export fn wait_status(pid: usize, status: *raw.ExitStatus) i32 {
+    const err = syscalls.wait_status(pid, status);
     if (err != 0) {
-        const res: u32 = @truncate(err);
-        errno.errno = res;
-        return errno.geterr();
+        errno.errno = @truncate(err);
+        return -1;
     }
+    return 0;
+}
 
-    return pid;
+pub fn zwait_status(pid: u64) errno.Error!raw.ExitStatus {
+    var status: raw.ExitStatus = undefined;
+    if (wait_status(pid, &status) == -1) return errno.geterr();
+    return status;
 }
 
-pub fn zpspwan(path: []const u8, argv: []const raw.Slice(u8), name: []const u8) errno.Error!u64 {
-    const config: raw.SpawnConfig = .{ .argv = argv.ptr, .argc = argv.len, .name = .{ .ptr = name.ptr, .len = name.len }, .flags = .{ .clone_cwd = true, .clone_resources = true } };
+/// waits for any child to exit, returns the pid or -1 on failure, `Busy` if `nonblocking` and no child exited yet
+/// and `InvaildPid` if there are no children
+pub export fn wait_any(nonblocking: bool, status: ?*usize) isize {
+    var pid: usize = undefined;
+    const err = syscalls.wait_any(nonblocking, &pid, status);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return @intCast(pid);
+}
+
+pub const Exited = struct { pid: u64, status: usize };
+
+/// waits for any child to exit and returns it's pid and exit code, with `nonblocking` returns null if
+/// none of the children exited yet
+pub fn zwait_any(nonblocking: bool) errno.Error!?Exited {
+    var status: usize = undefined;
+    const pid = wait_any(nonblocking, &status);
+    if (pid == -1) {
+        const err = errno.geterr();
+        if (err == error.Busy) return null;
+        return err;
+    }
+    return .{ .pid = @intCast(pid), .status = status };
+}
+
+/// kills `pid` which has to be a descendant of the caller, it's exit code becomes 1
+pub export fn pkill(pid: usize) i32 {
+    const err = syscalls.pkill(pid);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zpkill(pid: u64) errno.Error!void {
+    if (pkill(pid) == -1) return errno.geterr();
+}
+
+/// gives up the rest of the current time slice
+pub fn zyield() void {
+    syscalls.yield();
+}
+
+/// sleeps for `ticks` scheduler ticks, 0 only yields
+pub export fn sleep_ticks(ticks: usize) void {
+    syscalls.sleep(ticks);
+}
+
+pub export fn cpuctl(cpu: usize, online: bool) i32 {
+    const err = syscalls.cpuctl(cpu, online);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// takes `cpu` offline if `online` is false or brings it online otherwise
+/// cpus can't change state yet, only requesting the state a cpu is already in succeeds, anything
+/// else fails with error.OperationNotSupported (or error.Busy for offlining the bootstrap cpu)
+pub fn zcpuctl(cpu: usize, online: bool) errno.Error!void {
+    if (cpuctl(cpu, online) == -1) return errno.geterr();
+}
+
+pub export fn power(action: raw.PowerAction) i32 {
+    const err = syscalls.power(action);
+    errno.errno = @truncate(err);
+    return -1;
+}
+
+/// shuts down or reboots the system, only returns on failure
+pub fn zpower(action: raw.PowerAction) errno.Error {
+    _ = power(action);
+    return errno.geterr();
+}
+
+/// copies the value of `name` in the kernel's default environment to `value`
+/// returns the length of the whole value (which may be bigger then `len`) or -1 on failure
+pub export fn env_get(name: [*]const u8, name_len: usize, value: [*]u8, len: usize) isize {
+    var value_len: usize = undefined;
+    const err = syscalls.env_get(name, name_len, value, len, &value_len);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return @intCast(value_len);
+}
 
-    var pid: u64 = undefined;
-    const err = syscalls.pspawn(@ptrCast(path.ptr), path.len, &config, &pid);
+/// the default environment is what shells spawned by the kernel start with, also readable from proc:/environ
+/// returns the part of `buffer` the value of `name` was copied to, the value is cut if `buffer` is too small
+pub fn zenv_get(name: []const u8, buffer: []u8) errno.Error![]u8 {
+    const len = env_get(name.ptr, name.len, buffer.ptr, buffer.len);
+    if (len == -1) return errno.geterr();
+    return buffer[0..@min(@as(usize, @intCast(len)), buffer.len)];
+}
+
+/// requires the environment capability
+pub export fn env_set(name: [*]const u8, name_len: usize, value: [*]const u8, value_len: usize) i32 {
+    const err = syscalls.env_set(name, name_len, value, value_len);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// sets `name` to `value` in the default environment, an empty `value` removes `name`
+pub fn zenv_set(name: []const u8, value: []const u8) errno.Error!void {
+    if (env_set(name.ptr, name.len, value.ptr, value.len) == -1) return errno.geterr();
+}
+
+pub export fn uname(utsname: *raw.Utsname) i32 {
+    const err = syscalls.uname(utsname);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// identifies the system, the fields are nul terminated, see `field`
+pub fn zuname() errno.Error!raw.Utsname {
+    var utsname: raw.Utsname = undefined;
+    if (uname(&utsname) == -1) return errno.geterr();
+    return utsname;
+}
+
+/// returns `field` of a `raw.Utsname` without it's nul terminator
+pub fn field(value: *const [raw.UTSNAME_LEN]u8) []const u8 {
+    var len: usize = 0;
+    while (len < value.len and value[len] != 0) : (len += 1) {}
+    return value[0..len];
+}
+
+/// requires the hostname capability
+pub export fn sethostname(name: [*]const u8, name_len: usize) i32 {
+    const err = syscalls.sethostname(name, name_len);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// `name` has to be 1 to 64 ascii letters, digits, `-`s and `.`s
+pub fn zsethostname(name: []const u8) errno.Error!void {
+    if (sethostname(name.ptr, name.len) == -1) return errno.geterr();
+}
+
+/// reports why the caller is about to exit, it's shown in the kernel log and in proc:/<pid>/abort
+/// messages longer than 1024 bytes are truncated
+pub export fn report_abort(msg: [*]const u8, msg_len: usize) i32 {
+    const err = syscalls.report_abort(msg, msg_len);
     if (err != 0) {
-        const res: u32 = @truncate(err);
-        errno.errno = res;
-        return errno.geterr();
+        errno.errno = @truncate(err);
+        return -1;
     }
+    return 0;
+}
+
+pub fn zreport_abort(msg: []const u8) errno.Error!void {
+    if (report_abort(msg.ptr, msg.len) == -1) return errno.geterr();
+}
+
+/// the uid of the current process, 0 is root
+pub export fn getuid() u32 {
+    var uid: u32 = undefined;
+    _ = syscalls.getuid(&uid);
+    return uid;
+}
+
+/// only root can change it's uid and a process that stops being root loses all of it's capabilities for good
+pub export fn setuid(uid: u32) i32 {
+    const err = syscalls.setuid(uid);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+pub fn zsetuid(uid: u32) errno.Error!void {
+    if (setuid(uid) == -1) return errno.geterr();
+}
+
+/// requires the debug capability, see `zptrace_read` and friends
+pub export fn ptrace(op: raw.PtraceOp, pid: usize, addr: usize, ptr: ?*anyopaque, len: usize) i32 {
+    const err = syscalls.ptrace(op, pid, addr, ptr, len);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return 0;
+}
+
+/// reads `buffer.len` bytes at `addr` in the memory of `pid`
+pub fn zptrace_read(pid: usize, addr: usize, buffer: []u8) errno.Error!void {
+    if (ptrace(.ReadMemory, pid, addr, buffer.ptr, buffer.len) == -1) return errno.geterr();
+}
+
+/// writes `buffer` at `addr` in the memory of `pid`, read-only memory is written too
+pub fn zptrace_write(pid: usize, addr: usize, buffer: []const u8) errno.Error!void {
+    if (ptrace(.WriteMemory, pid, addr, @constCast(buffer.ptr), buffer.len) == -1) return errno.geterr();
+}
+
+/// stops `pid` from running until `zptrace_resume` is called, if `pid` is in a syscall it stops once it is back in
+/// userspace
+pub fn zptrace_suspend(pid: usize) errno.Error!void {
+    if (ptrace(.Suspend, pid, 0, null, 0) == -1) return errno.geterr();
+}
+
+pub fn zptrace_resume(pid: usize) errno.Error!void {
+    if (ptrace(.Resume, pid, 0, null, 0) == -1) return errno.geterr();
+}
+
+/// the registers of `pid` as they were when it was last switched out
+pub fn zptrace_registers(pid: usize) errno.Error!raw.Registers {
+    var registers: raw.Registers = undefined;
+    if (ptrace(.GetRegisters, pid, 0, &registers, 0) == -1) return errno.geterr();
+    return registers;
+}
+
+/// captures the return addresses of `pid` (the current process, a descendant or any process with the debug capability)
+/// by walking it's frame pointers, returns the amount captured or -1 on failure
+pub export fn backtrace(pid: usize, frames: [*]usize, len: usize) isize {
+    var count: usize = undefined;
+    const err = syscalls.backtrace(pid, frames, len, &count);
+    if (err != 0) {
+        errno.errno = @truncate(err);
+        return -1;
+    }
+    return @intCast(count);
+}
 
-    return pid;
+/// the first frame is where `pid` currently is, the rest are return addresses
+pub fn zbacktrace(pid: usize, frames: []usize) errno.Error![]usize {
+    const count = backtrace(pid, frames.ptr, frames.len);
+    if (count == -1) return errno.geterr();
+    return frames[0..@intCast(count)];
 }
@@ -6,32 +6,55 @@ use syn::{parse_macro_input, parse_quote, DeriveInput, ImplItem, Item, ItemImpl,
 
 /// takes a mod and puts a function called `test_main` in it which executes all of it is functions
 /// used by kernel for tests (test feature)
+///
+/// the tests are ran with `crate::test::run_tests`, the functions named `setup` and `teardown` aren't
+/// tests, they are ran before and after each test
 #[proc_macro_attribute]
 pub fn test_module(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut module = parse_macro_input!(item as ItemMod);
+    let module_name = module.ident.clone();
 
     let mut content = module.content.take().unwrap();
 
-    let func_names: Vec<_> = content
+    let funcs: Vec<_> = content
         .1
         .iter()
         .filter_map(|x| {
             if let Item::Fn(func) = x {
-                Some(func.sig.ident.clone())
+                Some(func)
             } else {
                 None
             }
         })
         .collect();
-    let len = func_names.len();
+
+    let has_fixture = |name: &str| funcs.iter().any(|func| func.sig.ident == name);
+    let setup = if has_fixture("setup") {
+        quote! { Some(setup as fn()) }
+    } else {
+        quote! { None }
+    };
+    let teardown = if has_fixture("teardown") {
+        quote! { Some(teardown as fn()) }
+    } else {
+        quote! { None }
+    };
+
+    let tests: Vec<_> = funcs
+        .iter()
+        .filter(|func| func.sig.ident != "setup" && func.sig.ident != "teardown")
+        .map(|func| {
+            let name = &func.sig.ident;
+            // tests that are compiled out are left out of the list too
+            let cfgs = func.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            quote! { #(#cfgs)* (stringify!(#name), #name as fn()) }
+        })
+        .collect();
+
     let test_main: Item = parse_quote! {
         pub fn test_main() {
-            cross_println!("\x1B[36m[TEST]\x1B[0m: Running {} tests", #len);
-            #(
-                cross_println!("\x1B[36m[TEST]\x1B[0m: Running {} test", stringify!(#func_names));
-                #func_names();
-                cross_println!("\x1B[32m[OK]\x1B[0m");
-            )*
+            let tests: &[(&str, fn())] = &[#(#tests),*];
+            crate::test::run_tests(stringify!(#module_name), tests, #setup, #teardown);
         }
     };
 
@@ -0,0 +1,53 @@
+// what qemu boots, shared by `cargo run` and `cargo run -- test`
+// the iso unless `kernel=<path>` or `ramdisk=<path>` is given or the builder only built a part (see
+// `cargo safa build --only`), then a usb image like `cargo safa image` makes is put together out of
+// `iso_root` with the given kernel and ramdisk, limine loads the ramdisk as a module from it's
+// second partition
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::image;
+
+/// recreated on every run that doesn't boot the iso
+const BOOT_IMAGE: &str = "target/boot.img";
+
+#[derive(Debug, Default)]
+pub struct BootFiles {
+    kernel: Option<PathBuf>,
+    ramdisk: Option<PathBuf>,
+}
+
+impl BootFiles {
+    /// returns true if `arg` was a boot file option
+    pub fn parse(&mut self, arg: &str) -> bool {
+        match arg.split_once('=') {
+            Some(("kernel", path)) => self.kernel = Some(PathBuf::from(path)),
+            Some(("ramdisk", path)) => self.ramdisk = Some(PathBuf::from(path)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// the path of the image to boot
+    pub fn image(&self, iso_path: &str, iso_root: &str) -> io::Result<String> {
+        let partial_build = env!("BUILD_ONLY") != "iso";
+        if self.kernel.is_none() && self.ramdisk.is_none() && !partial_build {
+            return Ok(iso_path.to_string());
+        }
+
+        let iso_root = Path::new(iso_root);
+        let kernel = match &self.kernel {
+            Some(kernel) => kernel.clone(),
+            None => iso_root.join("boot/kernel"),
+        };
+        let ramdisk = match &self.ramdisk {
+            Some(ramdisk) => ramdisk.clone(),
+            None => image::find_ramdisk(iso_root)?,
+        };
+
+        image::make_boot_image(iso_root, &kernel, &ramdisk, Path::new(BOOT_IMAGE))?;
+        Ok(BOOT_IMAGE.to_string())
+    }
+}
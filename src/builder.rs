@@ -0,0 +1,75 @@
+// `cargo safa build --only <kernel|ramdisk|iso>`, runs the builder again for only a part of
+// `iso_root` so kernel changes don't rebuild the zig projects and userspace changes don't relink the
+// kernel or remake the iso, the runner then boots the new part with the rest of the last build
+// cargo already ran the whole builder before starting the runner unless `SAFA_BUILD_ONLY` is set,
+// `SAFA_BUILD_ONLY=kernel cargo safa` builds only the kernel and boots it right away
+use std::process::Command;
+
+const BUILD_ONLY_ENV: &str = "SAFA_BUILD_ONLY";
+const TARGETS: [&str; 3] = ["kernel", "ramdisk", "iso"];
+
+/// the features the runner was built with, the builder is ran with the same ones
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "bench") {
+        features.push("bench");
+    }
+    if cfg!(feature = "fuzz") {
+        features.push("fuzz");
+    }
+    if cfg!(feature = "tar-ramdisk") {
+        features.push("tar-ramdisk");
+    }
+    if cfg!(feature = "compressed-ramdisk") {
+        features.push("compressed-ramdisk");
+    }
+    features
+}
+
+/// runs `cargo safa build`, returns the exit code
+pub fn run(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut only = String::from("iso");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--only" => {
+                only = args
+                    .next()
+                    .unwrap_or_else(|| panic!("{} expects a value", arg))
+            }
+            arg => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    if !TARGETS.contains(&only.as_str()) {
+        panic!(
+            "Unknown build target {}, expected one of {:?}",
+            only, TARGETS
+        );
+    }
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+    let mut cmd = Command::new(cargo);
+    cmd.arg("build")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env(BUILD_ONLY_ENV, &only);
+    if !cfg!(debug_assertions) {
+        cmd.arg("--release");
+    }
+    let features = features();
+    if !features.is_empty() {
+        cmd.arg("--features").arg(features.join(","));
+    }
+
+    match cmd.status() {
+        Ok(status) if status.success() => {
+            println!("built {} into {}", only, env!("ISO_ROOT"));
+            0
+        }
+        Ok(status) => status.code().unwrap_or(1),
+        Err(err) => {
+            eprintln!("failed running cargo: {}", err);
+            1
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// the disks attached to qemu, shared by `cargo run` and `cargo run -- test`
+// `disk=<path>[,<raw|qcow2>]` attaches an image (created if it doesn't exist), `blank-disk=<size>`
+// attaches a new blank raw image and `disk-controller=<virtio|nvme|ahci>` chooses the controller
+// every disk is attached to
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// blank disks are recreated here on every run
+const BLANK_DISKS_DIR: &str = "target/blank-disks";
+/// the size of the images `disk=` creates
+const DEFAULT_DISK_SIZE: u64 = 64 * 1024 * 1024;
+/// the amount of ports qemu's ahci controller has
+const AHCI_PORTS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Raw,
+    Qcow2,
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "raw" => Some(Self::Raw),
+            "qcow2" => Some(Self::Qcow2),
+            _ => None,
+        }
+    }
+
+    /// guesses the format from the extension of `path`, images are raw unless they end with `.qcow2`
+    fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext == "qcow2") {
+            Self::Qcow2
+        } else {
+            Self::Raw
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Qcow2 => "qcow2",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Controller {
+    #[default]
+    Virtio,
+    Nvme,
+    Ahci,
+}
+
+impl Controller {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "virtio" => Some(Self::Virtio),
+            "nvme" => Some(Self::Nvme),
+            "ahci" => Some(Self::Ahci),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Disk {
+    path: PathBuf,
+    format: Format,
+    /// if set the image is recreated with this size on every run
+    blank_size: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Disks {
+    disks: Vec<Disk>,
+    controller: Controller,
+}
+
+/// parses sizes like `512K`, `64M`, `1G` or a plain amount of bytes
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, unit) = match size.char_indices().last()? {
+        (at, 'K' | 'k') => (&size[..at], 1024),
+        (at, 'M' | 'm') => (&size[..at], 1024 * 1024),
+        (at, 'G' | 'g') => (&size[..at], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+fn create_image(path: &Path, format: Format, size: u64) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match format {
+        Format::Raw => File::create(path)?.set_len(size),
+        Format::Qcow2 => {
+            let status = Command::new("qemu-img")
+                .arg("create")
+                .arg("-q")
+                .arg("-f")
+                .arg("qcow2")
+                .arg(path)
+                .arg(size.to_string())
+                .status()?;
+
+            if status.success() {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("qemu-img failed with {status}")))
+            }
+        }
+    }
+}
+
+impl Disks {
+    /// parses `arg` if it is a disk option, returns false if it isn't one
+    pub fn parse(&mut self, arg: &str) -> bool {
+        match arg.split_once('=') {
+            Some(("disk", disk)) => {
+                let (path, format) = match disk.rsplit_once(',') {
+                    Some((path, format)) => (
+                        PathBuf::from(path),
+                        Format::parse(format)
+                            .unwrap_or_else(|| panic!("Unknown disk format {}", format)),
+                    ),
+                    None => (PathBuf::from(disk), Format::from_path(Path::new(disk))),
+                };
+
+                self.disks.push(Disk {
+                    path,
+                    format,
+                    blank_size: None,
+                });
+            }
+            Some(("blank-disk", size)) => {
+                let size = parse_size(size).unwrap_or_else(|| panic!("Invalid disk size {}", size));
+                let path =
+                    Path::new(BLANK_DISKS_DIR).join(format!("blank-{}.img", self.disks.len()));
+
+                self.disks.push(Disk {
+                    path,
+                    format: Format::Raw,
+                    blank_size: Some(size),
+                });
+            }
+            Some(("disk-controller", name)) => {
+                self.controller = Controller::parse(name)
+                    .unwrap_or_else(|| panic!("Unknown disk controller {}", name))
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// creates the images that don't exist yet (and the blank ones) then attaches every disk to `cmd`
+    pub fn attach(&self, cmd: &mut Command) -> io::Result<()> {
+        if self.controller == Controller::Ahci {
+            if self.disks.len() > AHCI_PORTS {
+                return Err(io::Error::other(format!(
+                    "the ahci controller only has {AHCI_PORTS} ports"
+                )));
+            }
+
+            if !self.disks.is_empty() {
+                cmd.arg("-device").arg("ahci,id=ahci");
+            }
+        }
+
+        for (index, disk) in self.disks.iter().enumerate() {
+            match disk.blank_size {
+                Some(size) => create_image(&disk.path, disk.format, size)?,
+                None if !disk.path.exists() => {
+                    println!("creating disk image {}", disk.path.display());
+                    create_image(&disk.path, disk.format, DEFAULT_DISK_SIZE)?
+                }
+                None => {}
+            }
+
+            let id = format!("disk{index}");
+            // commas in qemu options are escaped by doubling them
+            let path = disk.path.display().to_string().replace(',', ",,");
+            cmd.arg("-drive").arg(format!(
+                "file={path},format={},if=none,id={id}",
+                disk.format.name()
+            ));
+
+            let device = match self.controller {
+                Controller::Virtio => format!("virtio-blk-pci,drive={id}"),
+                Controller::Nvme => format!("nvme,drive={id},serial=safaos{index}"),
+                Controller::Ahci => format!("ide-hd,drive={id},bus=ahci.{index}"),
+            };
+            cmd.arg("-device").arg(device);
+        }
+
+        Ok(())
+    }
+}
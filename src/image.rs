@@ -0,0 +1,629 @@
+// `cargo safa image --target usb`, makes a raw GPT disk image for real hardware out of the files
+// the builder put in `iso_root`, the first partition is a FAT32 EFI system partition with limine and
+// the kernel and the second is a FAT32 data partition with the ramdisk which limine loads as a module
+// the image can also be written to a block device with `--device <path>`
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fs::{self, File},
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const SECTOR_SIZE: usize = 512;
+/// partitions are aligned to 1MiB
+const ALIGNMENT: usize = 2048;
+/// the smallest partition size, FAT32 needs at least 65525 clusters
+const MIN_PARTITION_SECTORS: usize = 64 * 1024 * 1024 / SECTOR_SIZE;
+const GPT_ENTRIES: usize = 128;
+const GPT_ENTRY_SIZE: usize = 128;
+/// the sectors the partition entries take
+const GPT_ENTRIES_SECTORS: usize = GPT_ENTRIES * GPT_ENTRY_SIZE / SECTOR_SIZE;
+
+const ESP_TYPE: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+const BASIC_DATA_TYPE: &str = "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7";
+
+/// the recording date of every FAT entry, 2024-01-01
+const FAT_DATE: u16 = (44 << 9) | (1 << 5) | 1;
+const FAT_END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+const DEFAULT_OUTPUT: &str = "safaos-x86_64-usb.img";
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// turns a GUID string into it's on disk form, the first 3 fields are little endian
+fn guid(s: &str) -> [u8; 16] {
+    let hex: Vec<u8> = s
+        .split('-')
+        .flat_map(|part| {
+            (0..part.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&part[i..i + 2], 16).unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let mut guid = [0u8; 16];
+    guid.copy_from_slice(&hex);
+    guid[0..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+    guid
+}
+
+/// a random version 4 GUID
+fn random_guid() -> [u8; 16] {
+    let mut guid = [0u8; 16];
+    for half in guid.chunks_mut(8) {
+        let random = RandomState::new().build_hasher().finish();
+        half.copy_from_slice(&random.to_le_bytes());
+    }
+
+    guid[7] = (guid[7] & 0x0F) | 0x40;
+    guid[8] = (guid[8] & 0x3F) | 0x80;
+    guid
+}
+
+fn sectors(size: usize) -> usize {
+    size.div_ceil(SECTOR_SIZE)
+}
+
+enum Entry {
+    Dir(BTreeMap<String, Entry>),
+    File(Vec<u8>),
+}
+
+impl Entry {
+    /// puts `data` at `path` creating the parent directories, `self` has to be a directory
+    fn insert(&mut self, path: &str, data: Vec<u8>) {
+        let Self::Dir(entries) = self else {
+            unreachable!()
+        };
+
+        match path.split_once('/') {
+            Some((dir, rest)) => entries
+                .entry(dir.to_string())
+                .or_insert_with(|| Self::Dir(BTreeMap::new()))
+                .insert(rest, data),
+            None => {
+                entries.insert(path.to_string(), Self::File(data));
+            }
+        }
+    }
+
+    /// the size of the entry's data on disk, directories include the data of their children
+    fn size(&self) -> usize {
+        match self {
+            Self::File(data) => data.len().next_multiple_of(SECTOR_SIZE),
+            Self::Dir(entries) => {
+                dir_records_size(entries).next_multiple_of(SECTOR_SIZE)
+                    + entries.values().map(Self::size).sum::<usize>()
+            }
+        }
+    }
+}
+
+/// the short name is used as is if `name` is an uppercase 8.3 name, otherwise a long name is needed
+fn is_short_name(name: &str) -> bool {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let valid = |part: &str| {
+        part.bytes()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || b"_-~".contains(&c))
+    };
+
+    !base.is_empty() && base.len() <= 8 && ext.len() <= 3 && valid(base) && valid(ext)
+}
+
+/// the amount of 32 bytes records `name` takes
+fn name_records(name: &str) -> usize {
+    if is_short_name(name) {
+        1
+    } else {
+        name.encode_utf16().count().div_ceil(13) + 1
+    }
+}
+
+/// the size of the records of a directory with `entries` including `.` and `..`
+fn dir_records_size(entries: &BTreeMap<String, Entry>) -> usize {
+    (2 + entries.keys().map(|name| name_records(name)).sum::<usize>()) * 32
+}
+
+/// the 8.3 name of the `index`th entry in a directory, `NAME~N.EXT` if a long name is needed
+fn short_name(name: &str, index: usize) -> [u8; 11] {
+    let mut short = [b' '; 11];
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) if !base.is_empty() => (base, ext),
+        _ => (name, ""),
+    };
+
+    let filter = |part: &str| -> Vec<u8> {
+        part.bytes()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    };
+
+    let ext = filter(ext);
+    let ext = &ext[..ext.len().min(3)];
+    short[8..8 + ext.len()].copy_from_slice(ext);
+
+    if is_short_name(name) {
+        short[..base.len()].copy_from_slice(base.as_bytes());
+        return short;
+    }
+
+    let tail = format!("~{}", index + 1);
+    let base = filter(base);
+    let base = &base[..base.len().min(8 - tail.len())];
+    short[..base.len()].copy_from_slice(base);
+    short[base.len()..base.len() + tail.len()].copy_from_slice(tail.as_bytes());
+    short
+}
+
+fn short_name_checksum(short: &[u8; 11]) -> u8 {
+    short
+        .iter()
+        .fold(0u8, |sum, c| sum.rotate_right(1).wrapping_add(*c))
+}
+
+fn dir_record(short: &[u8; 11], attr: u8, cluster: u32, size: u32) -> [u8; 32] {
+    let mut record = [0u8; 32];
+    record[..11].copy_from_slice(short);
+    record[11] = attr;
+    record[16..18].copy_from_slice(&FAT_DATE.to_le_bytes());
+    record[18..20].copy_from_slice(&FAT_DATE.to_le_bytes());
+    record[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    record[24..26].copy_from_slice(&FAT_DATE.to_le_bytes());
+    record[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    record[28..32].copy_from_slice(&size.to_le_bytes());
+    record
+}
+
+/// the long name records of `name` in the order they are stored in, the last part comes first
+fn long_name_records(name: &str, checksum: u8) -> Vec<[u8; 32]> {
+    let mut chars: Vec<u16> = name.encode_utf16().collect();
+    let count = chars.len().div_ceil(13);
+    // terminated with a NUL unless it fills the last record and padded with 0xFFFF
+    if chars.len() % 13 != 0 {
+        chars.push(0);
+    }
+    chars.resize(count * 13, 0xFFFF);
+
+    (0..count)
+        .rev()
+        .map(|index| {
+            let mut record = [0u8; 32];
+            record[0] = index as u8 + 1;
+            if index == count - 1 {
+                record[0] |= 0x40;
+            }
+            record[11] = ATTR_LONG_NAME;
+            record[13] = checksum;
+
+            let part = &chars[index * 13..index * 13 + 13];
+            let offsets = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2));
+            for (offset, c) in offsets.zip(part) {
+                record[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            record
+        })
+        .collect()
+}
+
+/// a FAT32 file system with a cluster for each sector
+struct Fat32 {
+    image: Vec<u8>,
+    fat: Vec<u32>,
+    reserved: usize,
+    fat_sectors: usize,
+    next_cluster: u32,
+}
+
+impl Fat32 {
+    const RESERVED_SECTORS: usize = 32;
+    const ROOT_CLUSTER: u32 = 2;
+
+    /// makes a FAT32 file system with the contents of `root` that starts at `start` on the disk
+    fn make(root: &Entry, start: usize, label: &str) -> io::Result<Vec<u8>> {
+        let total = sectors(root.size() * 11 / 10 + 4 * 1024 * 1024)
+            .next_multiple_of(ALIGNMENT)
+            .max(MIN_PARTITION_SECTORS);
+        // (clusters + 2) * 4 bytes have to fit in the FAT and there is 2 of them
+        let fat_sectors = ((total - Self::RESERVED_SECTORS + 2) * 4).div_ceil(SECTOR_SIZE + 8);
+        let clusters = total - Self::RESERVED_SECTORS - fat_sectors * 2;
+
+        let mut fat = Self {
+            image: vec![0u8; total * SECTOR_SIZE],
+            fat: vec![0; clusters + 2],
+            reserved: Self::RESERVED_SECTORS,
+            fat_sectors,
+            next_cluster: Self::ROOT_CLUSTER,
+        };
+        fat.fat[0] = 0x0FFF_FFF8;
+        fat.fat[1] = FAT_END_OF_CHAIN;
+
+        let Entry::Dir(entries) = root else {
+            unreachable!()
+        };
+        let root_cluster = fat.alloc(dir_records_size(entries))?;
+        fat.write_dir(entries, root_cluster, 0)?;
+
+        fat.write_boot_sectors(total, start, label);
+        fat.write_fats();
+        Ok(fat.image)
+    }
+
+    /// allocates a contiguous chain big enough for `size` bytes, returns 0 for empty files
+    fn alloc(&mut self, size: usize) -> io::Result<u32> {
+        let count = sectors(size) as u32;
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let first = self.next_cluster;
+        if (first + count) as usize > self.fat.len() {
+            return Err(invalid("the partition is too small".to_string()));
+        }
+
+        for cluster in first..first + count - 1 {
+            self.fat[cluster as usize] = cluster + 1;
+        }
+        self.fat[(first + count - 1) as usize] = FAT_END_OF_CHAIN;
+        self.next_cluster += count;
+        Ok(first)
+    }
+
+    /// writes `data` to the chain starting at `cluster`, chains are always contiguous
+    fn write(&mut self, cluster: u32, data: &[u8]) {
+        let sector = self.reserved + self.fat_sectors * 2 + (cluster - Self::ROOT_CLUSTER) as usize;
+        let at = sector * SECTOR_SIZE;
+        self.image[at..at + data.len()].copy_from_slice(data);
+    }
+
+    /// writes the records of a directory at `cluster` and it's children, the root doesn't get
+    /// `.` and `..` and has a `parent` of 0
+    fn write_dir(
+        &mut self,
+        entries: &BTreeMap<String, Entry>,
+        cluster: u32,
+        parent: u32,
+    ) -> io::Result<()> {
+        let mut records = Vec::new();
+        if cluster != Self::ROOT_CLUSTER {
+            records.extend(dir_record(b".          ", ATTR_DIRECTORY, cluster, 0));
+            records.extend(dir_record(b"..         ", ATTR_DIRECTORY, parent, 0));
+        }
+
+        for (index, (name, entry)) in entries.iter().enumerate() {
+            let short = short_name(name, index);
+            if !is_short_name(name) {
+                for record in long_name_records(name, short_name_checksum(&short)) {
+                    records.extend(record);
+                }
+            }
+
+            match entry {
+                Entry::File(data) => {
+                    let first = self.alloc(data.len())?;
+                    if first != 0 {
+                        self.write(first, data);
+                    }
+                    records.extend(dir_record(&short, ATTR_ARCHIVE, first, data.len() as u32));
+                }
+                Entry::Dir(children) => {
+                    let first = self.alloc(dir_records_size(children))?;
+                    // `..` pointing to the root is 0
+                    let this = if cluster == Self::ROOT_CLUSTER {
+                        0
+                    } else {
+                        cluster
+                    };
+                    self.write_dir(children, first, this)?;
+                    records.extend(dir_record(&short, ATTR_DIRECTORY, first, 0));
+                }
+            }
+        }
+
+        self.write(cluster, &records);
+        Ok(())
+    }
+
+    fn write_boot_sectors(&mut self, total: usize, start: usize, label: &str) {
+        let mut boot = [0u8; SECTOR_SIZE];
+        boot[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"SAFAOS  ");
+        boot[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+        boot[13] = 1;
+        boot[14..16].copy_from_slice(&(self.reserved as u16).to_le_bytes());
+        boot[16] = 2;
+        boot[21] = 0xF8;
+        boot[24..26].copy_from_slice(&32u16.to_le_bytes());
+        boot[26..28].copy_from_slice(&64u16.to_le_bytes());
+        boot[28..32].copy_from_slice(&(start as u32).to_le_bytes());
+        boot[32..36].copy_from_slice(&(total as u32).to_le_bytes());
+        boot[36..40].copy_from_slice(&(self.fat_sectors as u32).to_le_bytes());
+        boot[44..48].copy_from_slice(&Self::ROOT_CLUSTER.to_le_bytes());
+        // the FSInfo sector and the backup boot sector
+        boot[48..50].copy_from_slice(&1u16.to_le_bytes());
+        boot[50..52].copy_from_slice(&6u16.to_le_bytes());
+        boot[64] = 0x80;
+        boot[66] = 0x29;
+        boot[67..71].copy_from_slice(&random_guid()[..4]);
+        boot[71..82].fill(b' ');
+        boot[71..71 + label.len()].copy_from_slice(label.as_bytes());
+        boot[82..90].copy_from_slice(b"FAT32   ");
+        boot[510] = 0x55;
+        boot[511] = 0xAA;
+
+        let free = self.fat.len() as u32 - self.next_cluster;
+        let mut fs_info = [0u8; SECTOR_SIZE];
+        fs_info[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        fs_info[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        fs_info[488..492].copy_from_slice(&free.to_le_bytes());
+        fs_info[492..496].copy_from_slice(&self.next_cluster.to_le_bytes());
+        fs_info[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+
+        for sector in [0, 6] {
+            let at = sector * SECTOR_SIZE;
+            self.image[at..at + SECTOR_SIZE].copy_from_slice(&boot);
+            self.image[at + SECTOR_SIZE..at + SECTOR_SIZE * 2].copy_from_slice(&fs_info);
+        }
+    }
+
+    fn write_fats(&mut self) {
+        let fat: Vec<u8> = self
+            .fat
+            .iter()
+            .flat_map(|entry| entry.to_le_bytes())
+            .collect();
+        for copy in 0..2 {
+            let at = (self.reserved + self.fat_sectors * copy) * SECTOR_SIZE;
+            self.image[at..at + fat.len()].copy_from_slice(&fat);
+        }
+    }
+}
+
+struct Partition {
+    type_guid: [u8; 16],
+    name: &'static str,
+    data: Vec<u8>,
+}
+
+fn gpt_header(
+    total: usize,
+    this: usize,
+    alternate: usize,
+    entries_lba: usize,
+    disk: &[u8; 16],
+    entries_crc: u32,
+) -> [u8; SECTOR_SIZE] {
+    let mut header = [0u8; SECTOR_SIZE];
+    header[0..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92u32.to_le_bytes());
+    header[24..32].copy_from_slice(&(this as u64).to_le_bytes());
+    header[32..40].copy_from_slice(&(alternate as u64).to_le_bytes());
+    header[40..48].copy_from_slice(&((2 + GPT_ENTRIES_SECTORS) as u64).to_le_bytes());
+    header[48..56].copy_from_slice(&((total - 2 - GPT_ENTRIES_SECTORS) as u64).to_le_bytes());
+    header[56..72].copy_from_slice(disk);
+    header[72..80].copy_from_slice(&(entries_lba as u64).to_le_bytes());
+    header[80..84].copy_from_slice(&(GPT_ENTRIES as u32).to_le_bytes());
+    header[84..88].copy_from_slice(&(GPT_ENTRY_SIZE as u32).to_le_bytes());
+    header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+
+    let crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+    header
+}
+
+/// writes a GPT disk with `partitions` in order to `output`
+fn write_gpt(output: &Path, partitions: &[Partition]) -> io::Result<()> {
+    let mut entries = vec![0u8; GPT_ENTRIES * GPT_ENTRY_SIZE];
+    let mut lba = ALIGNMENT;
+    let mut starts = Vec::new();
+
+    for (index, partition) in partitions.iter().enumerate() {
+        let count = sectors(partition.data.len());
+        let entry = &mut entries[index * GPT_ENTRY_SIZE..(index + 1) * GPT_ENTRY_SIZE];
+        entry[0..16].copy_from_slice(&partition.type_guid);
+        entry[16..32].copy_from_slice(&random_guid());
+        entry[32..40].copy_from_slice(&(lba as u64).to_le_bytes());
+        entry[40..48].copy_from_slice(&((lba + count - 1) as u64).to_le_bytes());
+        for (at, c) in (56..128).step_by(2).zip(partition.name.encode_utf16()) {
+            entry[at..at + 2].copy_from_slice(&c.to_le_bytes());
+        }
+
+        starts.push(lba);
+        lba = (lba + count).next_multiple_of(ALIGNMENT);
+    }
+
+    // the backup entries and header are at the end of the disk
+    let total = lba + ALIGNMENT;
+    let backup_entries = total - 1 - GPT_ENTRIES_SECTORS;
+    let disk = random_guid();
+    let entries_crc = crc32(&entries);
+
+    // the protective MBR covers the whole disk
+    let mut mbr = [0u8; SECTOR_SIZE];
+    mbr[446 + 1..446 + 4].copy_from_slice(&[0x00, 0x02, 0x00]);
+    mbr[446 + 4] = 0xEE;
+    mbr[446 + 5..446 + 8].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+    mbr[446 + 8..446 + 12].copy_from_slice(&1u32.to_le_bytes());
+    mbr[446 + 12..446 + 16]
+        .copy_from_slice(&((total - 1).min(u32::MAX as usize) as u32).to_le_bytes());
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+
+    let mut file = File::create(output)?;
+    file.set_len((total * SECTOR_SIZE) as u64)?;
+
+    let mut write_at = |lba: usize, data: &[u8]| -> io::Result<()> {
+        file.seek(SeekFrom::Start((lba * SECTOR_SIZE) as u64))?;
+        file.write_all(data)
+    };
+
+    write_at(0, &mbr)?;
+    write_at(1, &gpt_header(total, 1, total - 1, 2, &disk, entries_crc))?;
+    write_at(2, &entries)?;
+    for (partition, start) in partitions.iter().zip(starts) {
+        write_at(start, &partition.data)?;
+    }
+    write_at(backup_entries, &entries)?;
+    write_at(
+        total - 1,
+        &gpt_header(total, total - 1, 1, backup_entries, &disk, entries_crc),
+    )?;
+    Ok(())
+}
+
+/// the ramdisk the builder put in `iso_root`
+pub fn find_ramdisk(iso_root: &Path) -> io::Result<PathBuf> {
+    let boot = iso_root.join("boot");
+    // only one of them exists depending on the `tar-ramdisk` and `compressed-ramdisk` features
+    ["ramdisk.rofs", "ramdisk.tar", "ramdisk.tar.gz"]
+        .into_iter()
+        .map(|name| boot.join(name))
+        .find(|path| path.exists())
+        .ok_or_else(|| invalid(format!("no ramdisk in {}", boot.display())))
+}
+
+/// makes the usb image from the files in `iso_root`
+fn make_usb_image(iso_root: &Path, output: &Path) -> io::Result<()> {
+    let ramdisk = find_ramdisk(iso_root)?;
+    make_boot_image(iso_root, &iso_root.join("boot/kernel"), &ramdisk, output)
+}
+
+/// makes a usb image that boots `kernel` and `ramdisk` with limine and it's config from `iso_root`
+pub fn make_boot_image(
+    iso_root: &Path,
+    kernel: &Path,
+    ramdisk: &Path,
+    output: &Path,
+) -> io::Result<()> {
+    let boot = iso_root.join("boot");
+    let ramdisk_name = ramdisk
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| invalid(format!("invaild ramdisk path {}", ramdisk.display())))?;
+
+    // the kernel's internal modules are looked up next to the kernel, the ramdisk is on the
+    // second partition instead so it is loaded as a normal module
+    let mut config = fs::read_to_string(boot.join("limine/limine.conf"))?;
+    if !config.ends_with('\n') {
+        config.push('\n');
+    }
+    config.push_str(&format!("    module_path: boot(2):/{ramdisk_name}\n"));
+
+    let mut esp = Entry::Dir(BTreeMap::new());
+    for path in ["EFI/BOOT/BOOTX64.EFI", "EFI/BOOT/BOOTIA32.EFI"] {
+        esp.insert(path, fs::read(iso_root.join(path))?);
+    }
+    esp.insert("boot/kernel", fs::read(kernel)?);
+    esp.insert("boot/limine/limine.conf", config.into_bytes());
+
+    let mut data = Entry::Dir(BTreeMap::new());
+    data.insert(ramdisk_name, fs::read(ramdisk)?);
+
+    let esp = Fat32::make(&esp, ALIGNMENT, "SAFAOS ESP")?;
+    let data_start = (ALIGNMENT + sectors(esp.len())).next_multiple_of(ALIGNMENT);
+    let data = Fat32::make(&data, data_start, "SAFAOS")?;
+
+    write_gpt(
+        output,
+        &[
+            Partition {
+                type_guid: guid(ESP_TYPE),
+                name: "EFI system partition",
+                data: esp,
+            },
+            Partition {
+                type_guid: guid(BASIC_DATA_TYPE),
+                name: "SafaOS data",
+                data,
+            },
+        ],
+    )
+}
+
+/// writes `image` to `device` after the user confirms it
+fn flash(image: &Path, device: &Path) -> io::Result<()> {
+    print!(
+        "everything on {} will be overwritten, type 'yes' to continue: ",
+        device.display()
+    );
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if answer.trim() != "yes" {
+        println!("aborted");
+        return Ok(());
+    }
+
+    let mut device = File::options().write(true).open(device)?;
+    io::copy(&mut File::open(image)?, &mut device)?;
+    device.sync_all()?;
+    println!("done");
+    Ok(())
+}
+
+/// runs `cargo safa image`, returns the exit code
+pub fn run(iso_root: &str, mut args: impl Iterator<Item = String>) -> i32 {
+    let mut target = None;
+    let mut output = PathBuf::from(DEFAULT_OUTPUT);
+    let mut device = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} expects a value", arg))
+        };
+
+        match arg.as_str() {
+            "--target" => target = Some(value()),
+            "--output" => output = PathBuf::from(value()),
+            "--device" => device = Some(PathBuf::from(value())),
+            arg => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    match target.as_deref() {
+        Some("usb") => {}
+        Some(target) => panic!("Unknown image target {}", target),
+        None => panic!("expected --target usb"),
+    }
+
+    if let Err(err) = make_usb_image(Path::new(iso_root), &output) {
+        eprintln!("failed making {}: {}", output.display(), err);
+        return 1;
+    }
+    println!("made {}", output.display());
+
+    if let Some(device) = device {
+        if let Err(err) = flash(&output, &device) {
+            eprintln!("failed writing to {}: {}", device.display(), err);
+            return 1;
+        }
+    }
+
+    0
+}
@@ -1,4 +1,20 @@
-use std::env::args;
+use std::{
+    env::args,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+mod boot;
+mod builder;
+mod disks;
+mod image;
+mod network;
+mod profile;
+mod share;
+mod test_runner;
 
 // code for running qemu and testing, kernel src avalible at kernel
 
@@ -8,6 +24,23 @@ fn main() {
 
     let iso_path = env!("ISO_PATH");
 
+    let mut args = args.peekable();
+    if args.peek().is_some_and(|arg| arg == "test") {
+        args.next();
+        let options = test_runner::TestOptions::parse(args);
+        std::process::exit(test_runner::run(iso_path, env!("ISO_ROOT"), &options));
+    }
+
+    if args.peek().is_some_and(|arg| arg == "build") {
+        args.next();
+        std::process::exit(builder::run(args));
+    }
+
+    if args.peek().is_some_and(|arg| arg == "image") {
+        args.next();
+        std::process::exit(image::run(env!("ISO_ROOT"), args));
+    }
+
     let uefi = true;
 
     let mut cmd = std::process::Command::new("qemu-system-x86_64");
@@ -16,29 +49,75 @@ fn main() {
             .arg("sdl")
             .arg("-bios")
             .arg(ovmf_prebuilt::ovmf_pure_efi());
-        cmd.arg("-drive")
-            .arg(format!("format=raw,file={iso_path}"))
-            .arg("-serial")
-            .arg("stdio")
-            .arg("-m")
-            .arg("512M");
+        cmd.arg("-serial").arg("stdio").arg("-m").arg("512M");
     }
 
     let mut kvm = true;
     let mut gui = true;
+    let mut boot_files = boot::BootFiles::default();
+    let mut disks = disks::Disks::default();
+    let mut network = network::Network::default();
+    let mut share = share::Share::default();
+    let mut serial_log = None;
+    let mut profile_path = None;
+    let mut timeout = None;
 
     for arg in args {
         match arg.as_str() {
             "no-kvm" => kvm = false,
             "no-gui" => gui = false,
             "debugger" => {
-                cmd.arg("-s").arg("-S");
+                // keeps qemu around after a triple fault or a shutdown so the state can be inspected
+                cmd.arg("-s")
+                    .arg("-S")
+                    .arg("-no-reboot")
+                    .arg("-no-shutdown");
                 println!("listening on port 1234 for debugger...");
             }
-            arg => panic!("Unknown argument {}", arg),
+            // for CI, qemu exits with the kernel instead of rebooting and the exit code is the
+            // kernel's (see `test_runner::exit_codes`)
+            "headless" => {
+                gui = false;
+                cmd.arg("-no-reboot");
+                test_runner::add_debug_exit(&mut cmd);
+            }
+            arg if boot_files.parse(arg) => {}
+            arg if disks.parse(arg) => {}
+            arg if network.parse(arg) => {}
+            arg if share.parse(arg) => {}
+            arg => match arg.split_once('=') {
+                Some(("serial-log", path)) => {
+                    serial_log = Some(
+                        File::create(path)
+                            .unwrap_or_else(|err| panic!("failed creating {}: {}", path, err)),
+                    )
+                }
+                Some(("profile", path)) => {
+                    profile::add_profile_request(&mut cmd);
+                    profile_path = Some(path.to_string());
+                }
+                Some(("timeout", secs)) => {
+                    let secs = secs.parse().expect("timeout must be a number of seconds");
+                    timeout = Some(Duration::from_secs(secs));
+                }
+                _ => panic!("Unknown argument {}", arg),
+            },
         }
     }
 
+    let image = boot_files
+        .image(iso_path, env!("ISO_ROOT"))
+        .unwrap_or_else(|err| panic!("failed making the boot image: {}", err));
+    cmd.arg("-drive").arg(format!("format=raw,file={image}"));
+
+    disks
+        .attach(&mut cmd)
+        .unwrap_or_else(|err| panic!("failed attaching the disks: {}", err));
+    network.attach(&mut cmd);
+    share
+        .attach(&mut cmd)
+        .unwrap_or_else(|err| panic!("failed sharing the directory: {}", err));
+
     if kvm {
         cmd.arg("-enable-kvm");
     }
@@ -46,6 +125,69 @@ fn main() {
         cmd.arg("-display").arg("none");
     }
 
+    let profiling = profile_path.is_some();
+    if serial_log.is_some() || profiling {
+        cmd.stdout(Stdio::piped());
+    }
+
     let mut child = cmd.spawn().unwrap();
-    child.wait().unwrap();
+
+    // the serial output is copied to the log and searched for the profile on another thread
+    let serial_thread = child.stdout.take().map(|mut serial| {
+        std::thread::spawn(move || {
+            let mut collector = profile::Collector::default();
+            let mut line = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while let Ok(read @ 1..) = serial.read(&mut buffer) {
+                _ = io::stdout().write_all(&buffer[..read]);
+                if let Some(log) = serial_log.as_mut() {
+                    _ = log.write_all(&buffer[..read]);
+                }
+
+                if profiling {
+                    for byte in &buffer[..read] {
+                        if *byte == b'\n' {
+                            collector.feed(&String::from_utf8_lossy(&line));
+                            line.clear();
+                        } else {
+                            line.push(*byte);
+                        }
+                    }
+                }
+            }
+            collector
+        })
+    });
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            println!("timed out, killing qemu");
+            _ = child.kill();
+            _ = child.wait();
+            std::process::exit(test_runner::exit_codes::TIMED_OUT);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+
+    if let (Some(path), Some(thread)) = (profile_path, serial_thread) {
+        let collector = thread.join().unwrap();
+        let kernel = Path::new(env!("ISO_ROOT")).join("boot/kernel");
+        match profile::write_folded(&collector.samples, &kernel, Path::new(&path)) {
+            Ok(0) => println!("the kernel didn't dump a profile, it has to shut down for it to"),
+            Ok(samples) => println!("wrote {samples} samples to {path}"),
+            Err(err) => println!("failed writing the profile to {path}: {err}"),
+        }
+    }
+
+    let code = match test_runner::guest_exit_code(status) {
+        Some(code) => code,
+        None if status.success() => test_runner::exit_codes::PASSED,
+        None => test_runner::exit_codes::QEMU_FAILED,
+    };
+    std::process::exit(code);
 }
@@ -0,0 +1,93 @@
+// qemu user networking, shared by `cargo run` and `cargo run -- test`
+// `forward=[udp:][<host addr>:]<host port>:<guest port>` forwards a host port to the guest (tcp
+// unless prefixed with `udp:`, the host address defaults to 127.0.0.1), a virtio-net card is only
+// attached once a port is forwarded
+use std::{net::Ipv4Addr, process::Command};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug)]
+struct Forward {
+    protocol: Protocol,
+    host_addr: Ipv4Addr,
+    host_port: u16,
+    guest_port: u16,
+}
+
+impl Forward {
+    fn parse(rule: &str) -> Option<Self> {
+        let (protocol, rule) = match rule.split_once(':') {
+            Some(("tcp", rule)) => (Protocol::Tcp, rule),
+            Some(("udp", rule)) => (Protocol::Udp, rule),
+            _ => (Protocol::Tcp, rule),
+        };
+
+        let (host, guest_port) = rule.rsplit_once(':')?;
+        let (host_addr, host_port) = match host.rsplit_once(':') {
+            Some((addr, port)) => (addr.parse().ok()?, port),
+            None => (Ipv4Addr::LOCALHOST, host),
+        };
+
+        Some(Self {
+            protocol,
+            host_addr,
+            host_port: host_port.parse().ok()?,
+            guest_port: guest_port.parse().ok()?,
+        })
+    }
+
+    /// the rule in qemu's `hostfwd` syntax
+    fn hostfwd(&self) -> String {
+        let protocol = match self.protocol {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        };
+        format!(
+            "hostfwd={protocol}:{}:{}-:{}",
+            self.host_addr, self.host_port, self.guest_port
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Network {
+    forwards: Vec<Forward>,
+}
+
+impl Network {
+    /// parses `arg` if it is a network option, returns false if it isn't one
+    pub fn parse(&mut self, arg: &str) -> bool {
+        match arg.split_once('=') {
+            Some(("forward", rule)) => {
+                let forward =
+                    Forward::parse(rule).unwrap_or_else(|| panic!("Invalid forward rule {}", rule));
+                self.forwards.push(forward);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// attaches a virtio-net card on qemu's user network with the forwarded ports to `cmd`
+    /// does nothing if no port is forwarded
+    pub fn attach(&self, cmd: &mut Command) {
+        if self.forwards.is_empty() {
+            return;
+        }
+
+        let mut netdev = String::from("user,id=net0");
+        for forward in &self.forwards {
+            netdev.push(',');
+            netdev.push_str(&forward.hostfwd());
+        }
+
+        cmd.arg("-netdev")
+            .arg(netdev)
+            .arg("-device")
+            .arg("virtio-net-pci,netdev=net0");
+    }
+}
@@ -0,0 +1,212 @@
+// `cargo run -- profile=<path>`, asks the kernel to profile from boot (see the kernel's
+// `arch::x86_64::profiler`), collects the samples it dumps to the serial once it shuts down and writes
+// them to `path` as folded stacks, one `frame;frame;frame count` line per unique stack, the input
+// flamegraph.pl and inferno expect
+// kernel addresses are named using the symbol table of the kernel the iso was built with, userspace
+// ones are all folded into `[userspace]`
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::Command,
+};
+
+/// the fw_cfg file that tells the kernel to profile from boot
+const PROFILE_FW_CFG: &str = "opt/safaos/profile";
+const DUMP_BEGIN: &str = "[PROFILE BEGIN]";
+const DUMP_END: &str = "[PROFILE END]";
+/// kernel addresses are in the higher half
+const KERNEL_START: u64 = 0xFFFF_8000_0000_0000;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+pub fn add_profile_request(cmd: &mut Command) {
+    cmd.arg("-fw_cfg")
+        .arg(format!("name={PROFILE_FW_CFG},string=1"));
+}
+
+/// picks the sample lines out of the serial output, fed a line at a time
+#[derive(Debug, Default)]
+pub struct Collector {
+    collecting: bool,
+    pub samples: Vec<String>,
+}
+
+impl Collector {
+    pub fn feed(&mut self, line: &str) {
+        let line = line.trim_end();
+        match line {
+            DUMP_BEGIN => {
+                self.collecting = true;
+                self.samples.clear();
+            }
+            DUMP_END => self.collecting = false,
+            // the header starts with #
+            line if self.collecting && !line.starts_with('#') => {
+                self.samples.push(line.to_string())
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Symbols {
+    /// (start, end, name) sorted by start
+    functions: Vec<(u64, u64, String)>,
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+}
+
+impl Symbols {
+    /// reads the function symbols of an ELF64 little endian file
+    fn read(path: &Path) -> io::Result<Self> {
+        let elf = fs::read(path)?;
+        if elf.len() < 64 || &elf[..4] != b"\x7FELF" || elf[4] != 2 || elf[5] != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} isn't a 64-bit little endian ELF", path.display()),
+            ));
+        }
+
+        let sections = u64_at(&elf, 0x28) as usize;
+        let section_size = u16_at(&elf, 0x3A) as usize;
+        let section_count = u16_at(&elf, 0x3C) as usize;
+        let section = |index: usize| sections + index * section_size;
+
+        let mut functions = Vec::new();
+        for index in 0..section_count {
+            let header = section(index);
+            if u32_at(&elf, header + 4) != SHT_SYMTAB {
+                continue;
+            }
+
+            let (offset, size) = (
+                u64_at(&elf, header + 0x18) as usize,
+                u64_at(&elf, header + 0x20) as usize,
+            );
+            let entry_size = u64_at(&elf, header + 0x38) as usize;
+            let strings = u64_at(&elf, section(u32_at(&elf, header + 0x28) as usize) + 0x18) as usize;
+
+            for sym in (offset..offset + size).step_by(entry_size) {
+                let (name, info) = (u32_at(&elf, sym) as usize, elf[sym + 4]);
+                let (value, size) = (u64_at(&elf, sym + 8), u64_at(&elf, sym + 16));
+                if info & 0xF != STT_FUNC || value == 0 {
+                    continue;
+                }
+
+                let name = &elf[strings + name..];
+                let len = name.iter().position(|byte| *byte == 0).unwrap_or(name.len());
+                let name = demangle(&String::from_utf8_lossy(&name[..len]));
+                functions.push((value, value + size.max(1), name));
+            }
+        }
+
+        functions.sort_by_key(|(start, ..)| *start);
+        Ok(Self { functions })
+    }
+
+    fn name(&self, addr: u64) -> String {
+        if addr < KERNEL_START {
+            return String::from("[userspace]");
+        }
+
+        let index = self.functions.partition_point(|(start, ..)| *start <= addr);
+        match index.checked_sub(1).map(|index| &self.functions[index]) {
+            Some((_, end, name)) if addr < *end => name.clone(),
+            _ => format!("{addr:#x}"),
+        }
+    }
+}
+
+/// demangles rust's legacy mangling (`_ZN` followed by length prefixed path segments and a hash),
+/// anything else is returned as is
+fn demangle(name: &str) -> String {
+    let Some(mut rest) = name.strip_prefix("_ZN") else {
+        return name.to_string();
+    };
+
+    let mut segments = Vec::new();
+    while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|at| *at > 0) {
+        let Ok(len) = rest[..digits].parse::<usize>() else {
+            break;
+        };
+        let Some(segment) = rest.get(digits..digits + len) else {
+            break;
+        };
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+
+    // the last segment is the hash
+    if segments
+        .last()
+        .is_some_and(|last| last.len() == 17 && last.starts_with('h'))
+    {
+        segments.pop();
+    }
+
+    if segments.is_empty() {
+        return name.to_string();
+    }
+
+    segments
+        .join("::")
+        .replace("$LT$", "<")
+        .replace("$GT$", ">")
+        .replace("$RF$", "&")
+        .replace("$BP$", "*")
+        .replace("$C$", ",")
+        .replace("$u20$", " ")
+        .replace("$u7b$", "{")
+        .replace("$u7d$", "}")
+        .replace("..", "::")
+}
+
+/// writes the samples as folded stacks to `output`, returns the amount of samples written
+pub fn write_folded(samples: &[String], kernel: &Path, output: &Path) -> io::Result<usize> {
+    let symbols = Symbols::read(kernel)?;
+    let mut stacks: BTreeMap<String, usize> = BTreeMap::new();
+
+    for sample in samples {
+        // the cpu id followed by the frames from the innermost
+        // return addresses are looked up 1 byte back so a call at the very end of a function isn't
+        // taken for the next one
+        let frames = sample
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|frame| u64::from_str_radix(frame.trim_start_matches("0x"), 16).ok())
+            .enumerate()
+            .map(|(i, addr)| symbols.name(if i == 0 { addr } else { addr - 1 }))
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            continue;
+        }
+
+        let stack = frames
+            .into_iter()
+            .rev()
+            .map(|frame| frame.replace(';', ":"))
+            .collect::<Vec<_>>()
+            .join(";");
+        *stacks.entry(stack).or_default() += 1;
+    }
+
+    let mut file = fs::File::create(output)?;
+    for (stack, count) in &stacks {
+        writeln!(file, "{stack} {count}")?;
+    }
+
+    Ok(stacks.values().sum())
+}
@@ -0,0 +1,50 @@
+// a host directory shared over virtio-9p, shared by `cargo run` and `cargo run -- test`
+// `share=<dir>` makes `<dir>` appear as host: in SafaOS, files can be moved in and out of the guest
+// without rebuilding the iso, the guest sees changes made on the host right away
+use std::{io, path::PathBuf, process::Command};
+
+/// the mount tag of the share, the kernel mounts any share as host: whatever it's tag is
+const MOUNT_TAG: &str = "host";
+
+#[derive(Debug, Default)]
+pub struct Share {
+    dir: Option<PathBuf>,
+}
+
+impl Share {
+    /// returns true if `arg` was a share option
+    pub fn parse(&mut self, arg: &str) -> bool {
+        match arg.split_once('=') {
+            Some(("share", dir)) => {
+                self.dir = Some(PathBuf::from(dir));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// attaches a virtio-9p device sharing the directory to `cmd`, does nothing if no directory is
+    /// shared
+    pub fn attach(&self, cmd: &mut Command) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let dir = dir.canonicalize()?;
+        if !dir.is_dir() {
+            return Err(io::Error::other(format!(
+                "{} isn't a directory",
+                dir.display()
+            )));
+        }
+
+        // commas in qemu options are escaped by doubling them
+        let path = dir.display().to_string().replace(',', ",,");
+        // files are created with the permissions of the user running qemu
+        cmd.arg("-fsdev")
+            .arg(format!("local,id=share,path={path},security_model=none"))
+            .arg("-device")
+            .arg(format!("virtio-9p-pci,fsdev=share,mount_tag={MOUNT_TAG}"));
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+// `cargo run -- test`, boots the kernel (which runs it's tests on boot) without a display and watches
+// the serial output for the results the kernel test harness prints, the whole serial log and the
+// results are written to the artifacts directory
+use crate::{boot::BootFiles, disks::Disks, network::Network, share::Share};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// printed by the kernel once the tests are done and it went idle
+const SUCCESS_MARKER: &str = "finished initing";
+const PANIC_MARKER: &str = "kernel panic";
+const RESULT_PREFIX: &str = "[TEST RESULT]";
+const SUMMARY_PREFIX: &str = "[TEST SUMMARY]";
+const BENCH_PREFIX: &str = "[BENCH]";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_ARTIFACTS: &str = "target/test-artifacts";
+
+/// the exit codes of `cargo run -- test`
+pub mod exit_codes {
+    pub const PASSED: i32 = 0;
+    /// at least one test failed
+    pub const TESTS_FAILED: i32 = 1;
+    /// the kernel panicked outside of a test
+    pub const KERNEL_PANIC: i32 = 2;
+    /// the kernel didn't finish before the global timeout
+    pub const TIMED_OUT: i32 = 3;
+    /// qemu couldn't be started or exited before the kernel finished
+    pub const QEMU_FAILED: i32 = 4;
+    pub const UNSUPPORTED_ARCH: i32 = 5;
+}
+
+/// the fw_cfg file that tells the kernel to exit qemu through isa-debug-exit once it is done
+const DEBUG_EXIT_FW_CFG: &str = "opt/safaos/debug-exit";
+
+/// attaches isa-debug-exit and asks the kernel to use it, the kernel exits qemu after the tests
+/// (or a panic) and `guest_exit_code` turns qemu's exit status back into one of `exit_codes`
+pub fn add_debug_exit(cmd: &mut Command) {
+    cmd.arg("-device")
+        .arg("isa-debug-exit,iobase=0xf4,iosize=0x04")
+        .arg("-fw_cfg")
+        .arg(format!("name={DEBUG_EXIT_FW_CFG},string=1"));
+}
+
+/// isa-debug-exit makes qemu exit with `(code << 1) | 1`, the kernel's codes match `exit_codes`
+pub fn guest_exit_code(status: ExitStatus) -> Option<i32> {
+    status
+        .code()
+        .filter(|code| code & 1 == 1)
+        .map(|code| code >> 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "x86_64" => Some(Self::X86_64),
+            "aarch64" => Some(Self::Aarch64),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64",
+            Self::Aarch64 => "aarch64",
+        }
+    }
+}
+
+pub struct TestOptions {
+    pub kvm: bool,
+    pub timeout: Duration,
+    pub archs: Vec<Arch>,
+    pub artifacts: PathBuf,
+    pub boot_files: BootFiles,
+    pub disks: Disks,
+    pub network: Network,
+    pub share: Share,
+}
+
+impl TestOptions {
+    /// parses the arguments after `test`
+    /// `no-kvm`, `timeout=<secs>`, `arch=<x86_64|aarch64|all>`, `artifacts=<dir>` and the boot
+    /// file, disk, network and share options in `boot`, `disks`, `network` and `share`
+    pub fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut options = Self {
+            kvm: true,
+            timeout: DEFAULT_TIMEOUT,
+            archs: vec![Arch::X86_64],
+            artifacts: PathBuf::from(DEFAULT_ARTIFACTS),
+            boot_files: BootFiles::default(),
+            disks: Disks::default(),
+            network: Network::default(),
+            share: Share::default(),
+        };
+
+        for arg in args {
+            match arg.split_once('=') {
+                None if arg == "no-kvm" => options.kvm = false,
+                Some(("timeout", secs)) => {
+                    let secs = secs.parse().expect("timeout must be a number of seconds");
+                    options.timeout = Duration::from_secs(secs);
+                }
+                Some(("arch", "all")) => options.archs = vec![Arch::X86_64, Arch::Aarch64],
+                Some(("arch", name)) => {
+                    options.archs =
+                        vec![Arch::parse(name).unwrap_or_else(|| panic!("Unknown arch {}", name))]
+                }
+                Some(("artifacts", dir)) => options.artifacts = PathBuf::from(dir),
+                _ if options.boot_files.parse(&arg) => {}
+                _ if options.disks.parse(&arg) => {}
+                _ if options.network.parse(&arg) => {}
+                _ if options.share.parse(&arg) => {}
+                _ => panic!("Unknown test argument {}", arg),
+            }
+        }
+
+        options
+    }
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    /// the names of the failed tests
+    failures: Vec<String>,
+    finished: bool,
+    panicked: bool,
+}
+
+impl Report {
+    /// updates the report with a line of the serial output
+    fn feed(&mut self, line: &str) {
+        if let Some(result) = line.strip_prefix(RESULT_PREFIX) {
+            let field = |name: &str| {
+                result
+                    .split_whitespace()
+                    .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+                    .unwrap_or("?")
+            };
+
+            match field("status") {
+                "ok" => self.passed += 1,
+                "skipped" => self.skipped += 1,
+                _ => {
+                    self.failed += 1;
+                    self.failures.push(format!(
+                        "{}::{} ({})",
+                        field("module"),
+                        field("name"),
+                        field("reason")
+                    ));
+                }
+            }
+        } else if line.to_lowercase().contains(PANIC_MARKER) {
+            self.panicked = true;
+        } else if line.contains(SUCCESS_MARKER) {
+            self.finished = true;
+        }
+    }
+}
+
+/// runs the tests on every arch in `options`, returns the exit code of the worst run
+pub fn run(iso_path: &str, iso_root: &str, options: &TestOptions) -> i32 {
+    let image = match options.boot_files.image(iso_path, iso_root) {
+        Ok(image) => image,
+        Err(err) => {
+            println!("failed making the boot image: {}", err);
+            return exit_codes::QEMU_FAILED;
+        }
+    };
+    let mut exit_code = exit_codes::PASSED;
+
+    for arch in &options.archs {
+        let code = run_arch(&image, *arch, options);
+        println!("[{}] exit code {}", arch.name(), code);
+        exit_code = exit_code.max(code);
+    }
+
+    exit_code
+}
+
+fn run_arch(image: &str, arch: Arch, options: &TestOptions) -> i32 {
+    if arch != Arch::X86_64 {
+        println!(
+            "[{}] the kernel isn't ported to {} yet",
+            arch.name(),
+            arch.name()
+        );
+        return exit_codes::UNSUPPORTED_ARCH;
+    }
+
+    let artifacts = options.artifacts.join(arch.name());
+    fs::create_dir_all(&artifacts).expect("failed to create the artifacts directory");
+    let mut serial_log = File::create(artifacts.join("serial.log")).unwrap();
+    let mut results = File::create(artifacts.join("results.txt")).unwrap();
+    let mut bench = File::create(artifacts.join("bench.txt")).unwrap();
+
+    let mut cmd = Command::new("qemu-system-x86_64");
+    cmd.arg("-bios")
+        .arg(ovmf_prebuilt::ovmf_pure_efi())
+        .arg("-drive")
+        .arg(format!("format=raw,file={image}"))
+        .arg("-serial")
+        .arg("stdio")
+        .arg("-m")
+        .arg("512M")
+        .arg("-display")
+        .arg("none")
+        .arg("-no-reboot")
+        .stdout(Stdio::piped());
+    add_debug_exit(&mut cmd);
+    if options.kvm {
+        cmd.arg("-enable-kvm");
+    }
+    if let Err(err) = options.disks.attach(&mut cmd) {
+        println!("[{}] failed attaching the disks: {}", arch.name(), err);
+        return exit_codes::QEMU_FAILED;
+    }
+    options.network.attach(&mut cmd);
+    if let Err(err) = options.share.attach(&mut cmd) {
+        println!("[{}] failed sharing the directory: {}", arch.name(), err);
+        return exit_codes::QEMU_FAILED;
+    }
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            println!("[{}] failed to start qemu: {}", arch.name(), err);
+            return exit_codes::QEMU_FAILED;
+        }
+    };
+
+    // the serial output is read on another thread so the timeout can be enforced
+    let stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+        while reader
+            .read_until(b'\n', &mut line)
+            .is_ok_and(|read| read > 0)
+        {
+            if sender
+                .send(String::from_utf8_lossy(&line).into_owned())
+                .is_err()
+            {
+                break;
+            }
+            line.clear();
+        }
+    });
+
+    let deadline = Instant::now() + options.timeout;
+    let mut report = Report::default();
+    let mut timed_out = false;
+
+    while !report.finished && !report.panicked {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(line) => {
+                serial_log.write_all(line.as_bytes()).unwrap();
+
+                let trimmed = line.trim_end();
+                if trimmed.starts_with(RESULT_PREFIX) || trimmed.starts_with(SUMMARY_PREFIX) {
+                    writeln!(results, "{}", trimmed).unwrap();
+                    println!("[{}] {}", arch.name(), trimmed);
+                } else if trimmed.starts_with(BENCH_PREFIX) {
+                    writeln!(bench, "{}", trimmed).unwrap();
+                    println!("[{}] {}", arch.name(), trimmed);
+                }
+                report.feed(trimmed);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                timed_out = true;
+                break;
+            }
+            // qemu exited
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    _ = child.kill();
+    // only set if the kernel exited qemu itself
+    let guest_code = child.wait().ok().and_then(guest_exit_code);
+
+    println!(
+        "[{}] passed: {}, failed: {}, skipped: {}",
+        arch.name(),
+        report.passed,
+        report.failed,
+        report.skipped
+    );
+    for failure in &report.failures {
+        println!("[{}] FAILED {}", arch.name(), failure);
+    }
+    println!("[{}] logs at {}", arch.name(), artifacts.display());
+
+    // a failed test panics the kernel after the summary, so failures are checked first
+    if report.failed > 0 {
+        exit_codes::TESTS_FAILED
+    } else if report.panicked {
+        exit_codes::KERNEL_PANIC
+    } else if timed_out {
+        exit_codes::TIMED_OUT
+    } else if !report.finished {
+        guest_code.unwrap_or(exit_codes::QEMU_FAILED)
+    } else {
+        exit_codes::PASSED
+    }
+}